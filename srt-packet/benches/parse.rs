@@ -0,0 +1,57 @@
+//! How long parsing a data packet takes for growing payloads, from `Bytes` and from a byte slice
+//!
+//! From `Bytes` the payload isn't copied, so the time stays flat as the payload grows, while from a slice it grows
+//! with the copy. Run with `cargo bench -p srt-packet`.
+
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use srt_packet::packet::{DataEncryption, PacketLocation};
+use srt_packet::{DataPacket, MsgNumber, Packet, SeqNumber, SocketID, TimeStamp};
+
+const ITERATIONS: u32 = 100_000;
+
+fn datagram(payload_len: usize) -> Bytes {
+    let packet = DataPacket {
+        seq_number: SeqNumber::new_truncate(1),
+        message_loc: PacketLocation::ONLY,
+        in_order_delivery: false,
+        encryption: DataEncryption::None,
+        retransmitted: false,
+        message_number: MsgNumber::new_truncate(1),
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: SocketID(1),
+        payload: vec![0xAB; payload_len].into(),
+    };
+    let mut buf = Vec::new();
+    Packet::Data(packet).serialize(&mut buf);
+    buf.into()
+}
+
+fn time(mut parse: impl FnMut() -> Packet) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(parse());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    for &payload_len in &[16, 1_316, 16_384, 65_000] {
+        let datagram = datagram(payload_len);
+
+        // the payload must be the datagram's own memory
+        match Packet::parse(&mut datagram.clone()).unwrap() {
+            Packet::Data(data) => assert_eq!(data.payload.as_ptr(), datagram[16..].as_ptr()),
+            Packet::Control(_) => unreachable!(),
+        }
+
+        let from_bytes = time(|| Packet::parse(&mut datagram.clone()).unwrap());
+        let from_slice = time(|| Packet::parse(&mut Cursor::new(&datagram[..])).unwrap());
+        println!(
+            "payload {:>6} bytes: from Bytes {:>8?}, from a slice {:>8?}",
+            payload_len, from_bytes, from_slice
+        );
+    }
+}
@@ -0,0 +1,27 @@
+//! The wire format of SRT: packets, and the modular numbers and timestamps in them, and the buffer messages are
+//! reassembled in on receipt
+//!
+//! This is the part of `srt-protocol` that doesn't need an operating system. Without the default `std` feature it's
+//! `no_std` and only needs `alloc`, so encoders on embedded targets can use the exact parsing, serialization and
+//! buffering of the full stack. Whatever keeps time is generic over the clock, see [`TimePoint`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod modular_num;
+mod msg_number;
+pub mod packet;
+mod recv_buffer;
+mod seq_number;
+mod socket_id;
+mod srt_version;
+mod time;
+
+pub use msg_number::MsgNumber;
+pub use packet::{ControlPacket, DataPacket, HandshakeExtension, Packet, PacketParseError};
+pub use recv_buffer::RecvBuffer;
+pub use seq_number::{seq_num_range, SeqNumber, SeqNumberRange};
+pub use socket_id::SocketID;
+pub use srt_version::SrtVersion;
+pub use time::{TimeBase, TimePoint, TimeSpan, TimeStamp};
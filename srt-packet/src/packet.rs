@@ -1,7 +1,7 @@
 // Packet structures
 // see https://tools.ietf.org/html/draft-gg-udt-03#page-5
 
-use std::fmt::{self, Debug, Formatter};
+use core::fmt::{self, Debug, Formatter};
 
 use bytes::{Buf, BufMut};
 
@@ -13,8 +13,7 @@ pub use self::control::*;
 pub use self::data::*;
 pub use error::PacketParseError;
 
-use crate::protocol::TimeStamp;
-use crate::SocketID;
+use crate::{SocketID, TimeStamp};
 
 /// Represents A UDT/SRT packet
 #[allow(clippy::large_enum_variant)]
@@ -1,15 +1,20 @@
-use std::fmt::{self, Debug, Formatter};
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::{self, Debug, Formatter};
+#[cfg(not(feature = "std"))]
+use core::net::{IpAddr, Ipv4Addr};
+#[cfg(feature = "std")]
 use std::net::{IpAddr, Ipv4Addr};
 
 use bitflags::bitflags;
-use bytes::{Buf, BufMut};
+use bytes::{buf::BufExt, Buf, BufMut, Bytes};
 use log::warn;
 
-use crate::protocol::{TimeSpan, TimeStamp};
-use crate::{MsgNumber, SeqNumber, SocketID};
+use crate::{MsgNumber, SeqNumber, SocketID, TimeSpan, TimeStamp};
 
 mod srt;
+mod template;
 pub use self::srt::*;
+pub use self::template::ControlTemplate;
 
 use super::PacketParseError;
 
@@ -88,6 +93,22 @@ pub enum ControlTypes {
     /// Srt control packets
     /// These use the UDT extension type 0xFF
     Srt(SrtControlPacket),
+
+    /// Any other control packet, either a reserved type or a user defined (0x7FFF) type that isn't an SRT packet.
+    /// This allows experimenting with new control packets without changing this module
+    Custom {
+        /// The type, the 15 bits after the control bit
+        custom_type: u16,
+
+        /// The reserved field, the subtype for user defined packets
+        reserved: u16,
+
+        /// The additional info field
+        additional_info: i32,
+
+        /// The control information field
+        payload: Bytes,
+    },
 }
 
 bitflags! {
@@ -120,9 +141,138 @@ pub enum HandshakeVSInfo {
 
         /// The extension config (SID, smoother)
         ext_config: Option<SrtControlPacket>,
+
+        /// The extension blocks this library doesn't parse, like the stream id, sent after the others
+        ext_other: Vec<HandshakeExtension>,
     },
 }
 
+/// A raw handshake extension block, for extensions this library doesn't parse
+///
+/// Sent with the config extension flag, zero padded to a whole number of 32-bit words.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeExtension {
+    /// The extension type, 5 is the stream id and 6 the congestion controller
+    pub type_id: u16,
+
+    /// The contents of the block, without the type and length
+    pub data: Bytes,
+}
+
+impl HandshakeExtension {
+    /// The type of the stream id extension, `SRT_CMD_SID`
+    pub const STREAM_ID: u16 = 5;
+
+    /// A stream id extension, holding `id` in 32-bit words of reversed bytes like the reference implementation does
+    pub fn stream_id(id: &str) -> Self {
+        let mut data = id.as_bytes().to_vec();
+        data.resize((data.len() + 3) / 4 * 4, 0);
+        for word in data.chunks_mut(4) {
+            word.reverse();
+        }
+        HandshakeExtension {
+            type_id: Self::STREAM_ID,
+            data: data.into(),
+        }
+    }
+
+    /// The stream id this holds, if it's a stream id extension with valid UTF-8, see [`stream_id`](Self::stream_id)
+    pub fn as_stream_id(&self) -> Option<String> {
+        if self.type_id != Self::STREAM_ID {
+            return None;
+        }
+        let mut data: Vec<u8> = self
+            .data
+            .chunks(4)
+            .flat_map(|word| word.iter().rev().copied())
+            .collect();
+        while data.last() == Some(&0) {
+            data.pop();
+        }
+        String::from_utf8(data).ok()
+    }
+
+    /// The type of the application metadata extension, from the top of the range so it won't clash with the reference
+    /// implementation's, which ignores it
+    pub const METADATA: u16 = 0x7ff0;
+
+    /// The most application metadata that can be sent, so the conclusion handshake still fits in one packet
+    pub const MAX_METADATA: usize = 512;
+
+    /// An application metadata extension, holding `data` after its length as a big endian 32-bit word, so the padding
+    /// can be told apart from it
+    pub fn metadata(data: &[u8]) -> Self {
+        let mut block = (data.len() as u32).to_be_bytes().to_vec();
+        block.extend_from_slice(data);
+        block.resize((block.len() + 3) / 4 * 4, 0);
+        HandshakeExtension {
+            type_id: Self::METADATA,
+            data: block.into(),
+        }
+    }
+
+    /// The application metadata this holds, if it's a metadata extension, see [`metadata`](Self::metadata)
+    pub fn as_metadata(&self) -> Option<Bytes> {
+        if self.type_id != Self::METADATA || self.data.len() < 4 {
+            return None;
+        }
+        let len =
+            u32::from_be_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]) as usize;
+        if len > self.data.len() - 4 {
+            return None;
+        }
+        Some(self.data.slice(4..4 + len))
+    }
+
+    /// The type of the application protocol extension, next to [`METADATA`](Self::METADATA)
+    pub const APP_PROTOCOL: u16 = 0x7ff1;
+
+    /// An application protocol extension, holding `protocols` in order of preference, each after its length as a
+    /// byte like in TLS ALPN, all after their total length as a big endian 32-bit word
+    ///
+    /// # Panics:
+    /// * a protocol is empty or longer than 255 bytes
+    pub fn app_protocols(protocols: &[&str]) -> Self {
+        let mut list = Vec::new();
+        for protocol in protocols {
+            assert!(
+                !protocol.is_empty() && protocol.len() <= 255,
+                "Application protocol {:?} must be 1 to 255 bytes",
+                protocol
+            );
+            list.push(protocol.len() as u8);
+            list.extend_from_slice(protocol.as_bytes());
+        }
+        let mut block = (list.len() as u32).to_be_bytes().to_vec();
+        block.extend_from_slice(&list);
+        block.resize((block.len() + 3) / 4 * 4, 0);
+        HandshakeExtension {
+            type_id: Self::APP_PROTOCOL,
+            data: block.into(),
+        }
+    }
+
+    /// The application protocols this holds, if it's a well formed application protocol extension, see
+    /// [`app_protocols`](Self::app_protocols)
+    pub fn as_app_protocols(&self) -> Option<Vec<String>> {
+        if self.type_id != Self::APP_PROTOCOL || self.data.len() < 4 {
+            return None;
+        }
+        let len =
+            u32::from_be_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]) as usize;
+        let mut list = self.data.get(4..4 + len)?;
+        let mut protocols = Vec::new();
+        while let Some((&protocol_len, rest)) = list.split_first() {
+            let protocol = rest
+                .get(..usize::from(protocol_len))
+                .filter(|p| !p.is_empty())?;
+            protocols.push(String::from_utf8(protocol.to_vec()).ok()?);
+            list = &rest[protocol.len()..];
+        }
+        Some(protocols)
+    }
+}
+
 /// The control info for handshake packets
 #[derive(Clone, PartialEq, Eq)]
 pub struct HandshakeControlInfo {
@@ -178,6 +328,24 @@ pub struct AckControlInfo {
 
     /// Estimated Link capacity
     pub est_link_cap: Option<i32>,
+
+    /// receive rate, in bytes/sec, which older versions of the reference implementation don't send
+    pub byte_recv_rate: Option<u32>,
+}
+
+impl AckControlInfo {
+    // the control information field, as it's serialized
+    fn words(&self) -> [u32; 7] {
+        [
+            self.ack_number.as_raw(),
+            self.rtt.map(|t| t.as_micros()).unwrap_or(10_000) as u32,
+            self.rtt_variance.map(|t| t.as_micros()).unwrap_or(50_000) as u32,
+            self.buffer_available.unwrap_or(8175) as u32, // TODO: better defaults
+            self.packet_recv_rate.unwrap_or(10_000),
+            self.est_link_cap.unwrap_or(1_000) as u32,
+            self.byte_recv_rate.unwrap_or(0),
+        ]
+    }
 }
 
 /// The socket type for a handshake.
@@ -214,17 +382,63 @@ pub enum SocketType {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShakeType {
     /// First handshake exchange in client-server connection
-    Induction = 1,
+    Induction,
 
     /// A rendezvous connection, initial connect request, 0
-    Waveahand = 0,
+    Waveahand,
 
     /// A rendezvous connection, response to initial connect request, -1
     /// Also a regular connection client response to the second handshake
-    Conclusion = -1,
+    Conclusion,
 
     /// Final rendezvous check, -2
-    Agreement = -2,
+    Agreement,
+
+    /// The connection was rejected, 1000 + the reason
+    Rejection(RejectReason),
+}
+
+/// Why a connection was rejected, the `SRT_REJECT_REASON` of the reference implementation
+///
+/// Sent in a handshake with the type 1000 + the reason.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// Unknown, or a reason this library doesn't know about
+    Unknown = 0,
+    /// System function error
+    System = 1,
+    /// Rejected by the peer
+    Peer = 2,
+    /// Resource allocation problem
+    Resource = 3,
+    /// Incorrect data in the handshake
+    Rogue = 4,
+    /// The listener's backlog is exceeded
+    Backlog = 5,
+    /// Internal program error
+    Ipe = 6,
+    /// The socket is closing
+    Close = 7,
+    /// The peer is older than the configured minimum version
+    Version = 8,
+    /// Rendezvous cookie collision
+    RdvCookie = 9,
+    /// Wrong password
+    BadSecret = 10,
+    /// The connection is encrypted on one side only
+    Unsecure = 11,
+    /// The message API settings don't match
+    MessageApi = 12,
+    /// The congestion controllers don't match
+    Congestion = 13,
+    /// The packet filters don't match
+    Filter = 14,
+    /// The groups don't match
+    Group = 15,
+    /// The connection timed out
+    Timeout = 16,
+    /// The encryption settings conflict, such as the key lengths
+    Crypto = 17,
 }
 
 impl HandshakeVSInfo {
@@ -238,9 +452,13 @@ impl HandshakeVSInfo {
                 ext_hs,
                 ext_km,
                 ext_config,
+                ext_other,
             } => {
                 if shake_type == ShakeType::Induction
-                    && (ext_hs.is_some() || ext_km.is_some() || ext_config.is_some())
+                    && (ext_hs.is_some()
+                        || ext_km.is_some()
+                        || ext_config.is_some()
+                        || !ext_other.is_empty())
                 {
                     // induction does not include any extensions, and instead has the
                     // magic code. this is an incompatialbe place to be.
@@ -255,7 +473,7 @@ impl HandshakeVSInfo {
                 if ext_km.is_some() {
                     flags |= ExtFlags::KM;
                 }
-                if ext_config.is_some() {
+                if ext_config.is_some() || !ext_other.is_empty() {
                     flags |= ExtFlags::CONFIG;
                 }
                 // take the crypto size, get rid of the frist three (garunteed zero) bits, then shift it into the
@@ -350,6 +568,27 @@ impl Debug for ControlPacket {
     }
 }
 
+/// The body of a handshake extension, which starts with its length in 32-bit words, so parsing it can't run over
+/// into the next one
+fn extension<T: Buf>(buf: &mut T) -> Result<Bytes, PacketParseError> {
+    let len = usize::from(buf.get_u16()) * 4;
+    if buf.remaining() < len {
+        return Err(PacketParseError::NotEnoughData);
+    }
+    Ok(buf.take(len).to_bytes())
+}
+
+/// Swap the bytes of each 32-bit word of a handshake's peer address, between network order and how it's sent
+///
+/// The reference implementation keeps the address as four 32-bit words in network order, then sends them like every
+/// other word, converting from host order. So on the little-endian hosts it runs on, the bytes of each word end up
+/// reversed, 127.0.0.1 being sent as `01 00 00 7F`. Doing it on the bytes here sends the same whatever this host is.
+fn peer_addr_words(addr: &mut [u8; 16]) {
+    for word in addr.chunks_mut(4) {
+        word.reverse();
+    }
+}
+
 // I definitely don't totally understand this yet.
 // Points of interest: handshake.h:wrapFlags
 // core.cpp:8176 (processConnectionRequest -> if INDUCTION)
@@ -400,13 +639,13 @@ impl ControlTypes {
                 let socket_id = SocketID(buf.get_u32());
                 let syn_cookie = buf.get_i32();
 
-                // get the IP
+                // get the IP, see peer_addr_words
                 let mut ip_buf: [u8; 16] = [0; 16];
                 buf.copy_to_slice(&mut ip_buf);
+                peer_addr_words(&mut ip_buf);
 
-                // TODO: this is probably really wrong, so fix it
                 let peer_addr = if ip_buf[4..] == [0; 12][..] {
-                    IpAddr::from(Ipv4Addr::new(ip_buf[3], ip_buf[2], ip_buf[1], ip_buf[0]))
+                    IpAddr::from(Ipv4Addr::new(ip_buf[0], ip_buf[1], ip_buf[2], ip_buf[3]))
                 } else {
                     IpAddr::from(ip_buf)
                 };
@@ -440,6 +679,7 @@ impl ControlTypes {
                                 ext_hs: None,
                                 ext_km: None,
                                 ext_config: None,
+                                ext_other: vec![],
                             }
                         } else {
                             // if this is not induction, this is the extension flags
@@ -461,10 +701,10 @@ impl ControlTypes {
                                     return Err(PacketParseError::NotEnoughData);
                                 }
                                 let pack_type = buf.get_u16();
-                                let _pack_size = buf.get_u16(); // TODO: why exactly is this needed?
+                                let mut ext = extension(&mut buf)?;
                                 match pack_type {
                                     // 1 and 2 are handshake response and requests
-                                    1 | 2 => Some(SrtControlPacket::parse(pack_type, &mut buf)?),
+                                    1 | 2 => Some(SrtControlPacket::parse(pack_type, &mut ext)?),
                                     e => return Err(PacketParseError::BadSRTHsExtensionType(e)),
                                 }
                             } else {
@@ -475,36 +715,33 @@ impl ControlTypes {
                                     return Err(PacketParseError::NotEnoughData);
                                 }
                                 let pack_type = buf.get_u16();
-                                let _pack_size = buf.get_u16(); // TODO: why exactly is this needed?
+                                let mut ext = extension(&mut buf)?;
                                 match pack_type {
                                     // 3 and 4 are km packets
-                                    3 | 4 => Some(SrtControlPacket::parse(pack_type, &mut buf)?),
+                                    3 | 4 => Some(SrtControlPacket::parse(pack_type, &mut ext)?),
                                     e => return Err(PacketParseError::BadSRTKmExtensionType(e)),
                                 }
                             } else {
                                 None
                             };
-                            let ext_config = if extensions.contains(ExtFlags::CONFIG) {
+                            // none of the config extensions are parsed yet, so they are all kept raw
+                            let mut ext_other = vec![];
+                            if extensions.contains(ExtFlags::CONFIG) {
                                 if buf.remaining() < 4 {
                                     return Err(PacketParseError::NotEnoughData);
                                 }
-                                let pack_type = buf.get_u16();
-                                let _pack_size = buf.get_u16(); // TODO: why exactly is this needed?
-                                match pack_type {
-                                    // 5 is sid 6 is smoother
-                                    5 | 6 => Some(SrtControlPacket::parse(pack_type, &mut buf)?),
-                                    e => {
-                                        return Err(PacketParseError::BadSRTConfigExtensionType(e))
-                                    }
+                                while buf.remaining() >= 4 {
+                                    let type_id = buf.get_u16();
+                                    let data = extension(&mut buf)?;
+                                    ext_other.push(HandshakeExtension { type_id, data });
                                 }
-                            } else {
-                                None
-                            };
+                            }
                             HandshakeVSInfo::V5 {
                                 crypto_size,
                                 ext_hs,
                                 ext_km,
-                                ext_config,
+                                ext_config: None,
+                                ext_other,
                             }
                         }
                     }
@@ -530,6 +767,13 @@ impl ControlTypes {
                 if buf.remaining() < 4 {
                     return Err(PacketParseError::NotEnoughData);
                 }
+                // the fields are all 32-bit words
+                if buf.remaining() & 0b11 != 0 {
+                    return Err(PacketParseError::BadControlLength(
+                        packet_type,
+                        buf.remaining(),
+                    ));
+                }
 
                 // read control info
                 let ack_number = SeqNumber::new_truncate(buf.get_u32());
@@ -554,6 +798,7 @@ impl ControlTypes {
                 let buffer_available = opt_read_next_i32(&mut buf);
                 let packet_recv_rate = opt_read_next_u32(&mut buf);
                 let est_link_cap = opt_read_next_i32(&mut buf);
+                let byte_recv_rate = opt_read_next_u32(&mut buf);
 
                 Ok(ControlTypes::Ack(AckControlInfo {
                     ack_seq_num: extra_info,
@@ -563,11 +808,19 @@ impl ControlTypes {
                     buffer_available,
                     packet_recv_rate,
                     est_link_cap,
+                    byte_recv_rate,
                 }))
             }
             0x3 => {
                 // NAK
 
+                // so is every loss list entry
+                if buf.remaining() & 0b11 != 0 {
+                    return Err(PacketParseError::BadControlLength(
+                        packet_type,
+                        buf.remaining(),
+                    ));
+                }
                 let mut loss_info = Vec::new();
                 while buf.remaining() >= 4 {
                     loss_info.push(buf.get_u32());
@@ -594,11 +847,26 @@ impl ControlTypes {
             }
             0x7FFF => {
                 // Srt
-                Ok(ControlTypes::Srt(SrtControlPacket::parse(
-                    reserved, &mut buf,
-                )?))
+                match SrtControlPacket::parse(reserved, &mut buf) {
+                    Ok(srt) => Ok(ControlTypes::Srt(srt)),
+                    // not an SRT packet, but still a valid user defined one
+                    Err(PacketParseError::BadSRTConfigExtensionType(_)) => {
+                        Ok(ControlTypes::Custom {
+                            custom_type: packet_type,
+                            reserved,
+                            additional_info: extra_info,
+                            payload: buf.to_bytes(),
+                        })
+                    }
+                    Err(e) => Err(e),
+                }
             }
-            x => Err(PacketParseError::BadControlType(x)),
+            x => Ok(ControlTypes::Custom {
+                custom_type: x,
+                reserved,
+                additional_info: extra_info,
+                payload: buf.to_bytes(),
+            }),
         }
     }
 
@@ -612,6 +880,7 @@ impl ControlTypes {
             ControlTypes::Ack2(_) => 0x6,
             ControlTypes::DropRequest { .. } => 0x7,
             ControlTypes::Srt(_) => 0x7FFF,
+            ControlTypes::Custom { custom_type, .. } => custom_type,
         }
     }
 
@@ -620,6 +889,9 @@ impl ControlTypes {
             // These types have additional info
             ControlTypes::DropRequest { msg_to_drop: a, .. } => a.as_raw() as i32,
             ControlTypes::Ack2(a) | ControlTypes::Ack(AckControlInfo { ack_seq_num: a, .. }) => *a,
+            ControlTypes::Custom {
+                additional_info, ..
+            } => *additional_info,
             // These do not, just use zero
             _ => 0,
         }
@@ -628,6 +900,7 @@ impl ControlTypes {
     fn reserved(&self) -> u16 {
         match self {
             ControlTypes::Srt(srt) => srt.type_id(),
+            ControlTypes::Custom { reserved, .. } => *reserved,
             _ => 0,
         }
     }
@@ -640,32 +913,25 @@ impl ControlTypes {
                 into.put_u32(c.init_seq_num.as_raw());
                 into.put_u32(c.max_packet_size);
                 into.put_u32(c.max_flow_size);
-                into.put_i32(c.shake_type as i32);
+                into.put_i32(c.shake_type.as_i32());
                 into.put_u32(c.socket_id.0);
                 into.put_i32(c.syn_cookie);
 
+                // the data structure requires enough space for an ipv6, so an ipv4 is padded with 12 zero bytes
+                let mut ip_buf = [0; 16];
                 match c.peer_addr {
-                    IpAddr::V4(four) => {
-                        let mut v = Vec::from(&four.octets()[..]);
-                        v.reverse(); // reverse bytes
-                        into.put(&v[..]);
-
-                        // the data structure reuiqres enough space for an ipv6, so pad the end with 16 - 4 = 12 bytes
-                        into.put(&[0; 12][..]);
-                    }
-                    IpAddr::V6(six) => {
-                        let mut v = Vec::from(&six.octets()[..]);
-                        v.reverse();
-
-                        into.put(&v[..]);
-                    }
+                    IpAddr::V4(four) => ip_buf[..4].copy_from_slice(&four.octets()),
+                    IpAddr::V6(six) => ip_buf = six.octets(),
                 }
+                peer_addr_words(&mut ip_buf);
+                into.put(&ip_buf[..]);
 
                 // serialzie extensions
                 if let HandshakeVSInfo::V5 {
                     ref ext_hs,
                     ref ext_km,
                     ref ext_config,
+                    ref ext_other,
                     ..
                 } = c.info
                 {
@@ -678,30 +944,29 @@ impl ControlTypes {
                         into.put_u16(ext.size_words());
                         ext.serialize(into);
                     }
+                    for ext in ext_other {
+                        let padding = (4 - ext.data.len() % 4) % 4;
+                        into.put_u16(ext.type_id);
+                        into.put_u16(((ext.data.len() + padding) / 4) as u16);
+                        into.put(&ext.data[..]);
+                        into.put(&[0; 3][..padding]);
+                    }
                 }
             }
-            ControlTypes::Ack(AckControlInfo {
-                ack_number,
-                rtt,
-                rtt_variance,
-                buffer_available,
-                packet_recv_rate,
-                est_link_cap,
-                ..
-            }) => {
-                into.put_u32(ack_number.as_raw());
-                into.put_i32(rtt.map(|t| t.as_micros()).unwrap_or(10_000));
-                into.put_i32(rtt_variance.map(|t| t.as_micros()).unwrap_or(50_000));
-                into.put_i32(buffer_available.unwrap_or(8175)); // TODO: better defaults
-                into.put_u32(packet_recv_rate.unwrap_or(10_000));
-                into.put_i32(est_link_cap.unwrap_or(1_000));
+            ControlTypes::Ack(ack) => {
+                for word in &ack.words() {
+                    into.put_u32(*word);
+                }
             }
             ControlTypes::Nak(ref n) => {
                 for &loss in n {
                     into.put_u32(loss);
                 }
             }
-            ControlTypes::DropRequest { .. } => unimplemented!(),
+            ControlTypes::DropRequest { first, last, .. } => {
+                into.put_u32(first.as_raw());
+                into.put_u32(last.as_raw());
+            }
             ControlTypes::Ack2(_) | ControlTypes::Shutdown | ControlTypes::KeepAlive => {
                 // The reference implementation appends one (4 byte) word at the end of these packets, which wireshark labels as 'Unused'
                 // I have no idea why, but wireshark reports it as a "malformed packet" without it. For the record,
@@ -712,6 +977,9 @@ impl ControlTypes {
             ControlTypes::Srt(srt) => {
                 srt.serialize(into);
             }
+            ControlTypes::Custom { payload, .. } => {
+                into.put(&payload[..]);
+            }
         };
     }
 }
@@ -729,6 +997,7 @@ impl Debug for ControlTypes {
                 buffer_available,
                 packet_recv_rate,
                 est_link_cap,
+                byte_recv_rate,
             }) => {
                 write!(f, "Ack(asn={} an={}", ack_seq_num, ack_number,)?;
                 if let Some(rtt) = rtt {
@@ -746,6 +1015,9 @@ impl Debug for ControlTypes {
                 if let Some(link_cap) = est_link_cap {
                     write!(f, " link_cap={}", link_cap)?;
                 }
+                if let Some(brr) = byte_recv_rate {
+                    write!(f, " byte_rr={}", brr)?;
+                }
                 write!(f, ")")?;
                 Ok(())
             }
@@ -760,6 +1032,19 @@ impl Debug for ControlTypes {
                 last,
             } => write!(f, "DropReq(msg={} {}-{})", msg_to_drop, first, last),
             ControlTypes::Srt(srt) => write!(f, "{:?}", srt),
+            ControlTypes::Custom {
+                custom_type,
+                reserved,
+                additional_info,
+                payload,
+            } => write!(
+                f,
+                "Custom(type={:#x} reserved={:#x} info={} len={})",
+                custom_type,
+                reserved,
+                additional_info,
+                payload.len()
+            ),
         }
     }
 }
@@ -809,6 +1094,7 @@ impl Debug for HandshakeVSInfo {
                 ext_hs,
                 ext_km,
                 ext_config,
+                ext_other,
             } => {
                 write!(f, "SRT: crypto={:?}", crypto_size)?;
                 if let Some(pack) = ext_hs {
@@ -820,6 +1106,9 @@ impl Debug for HandshakeVSInfo {
                 if let Some(pack) = ext_config {
                     write!(f, " config={:?}", pack)?;
                 }
+                for ext in ext_other {
+                    write!(f, " ext{}={:?}", ext.type_id, ext.data)?;
+                }
                 Ok(())
             }
         }
@@ -834,9 +1123,52 @@ impl ShakeType {
             0 => Ok(ShakeType::Waveahand),
             -1 => Ok(ShakeType::Conclusion),
             -2 => Ok(ShakeType::Agreement),
+            i if i >= Self::REJECTION_BASE => Ok(ShakeType::Rejection(RejectReason::from_i32(
+                i - Self::REJECTION_BASE,
+            ))),
             i => Err(i),
         }
     }
+
+    /// The value of this type on the wire
+    pub fn as_i32(self) -> i32 {
+        match self {
+            ShakeType::Induction => 1,
+            ShakeType::Waveahand => 0,
+            ShakeType::Conclusion => -1,
+            ShakeType::Agreement => -2,
+            ShakeType::Rejection(reason) => Self::REJECTION_BASE + reason as i32,
+        }
+    }
+
+    const REJECTION_BASE: i32 = 1000;
+}
+
+impl RejectReason {
+    /// Turns the reason part of a rejection handshake into a `RejectReason`, unknown reasons are `Unknown`
+    pub fn from_i32(num: i32) -> RejectReason {
+        use RejectReason::*;
+        match num {
+            1 => System,
+            2 => Peer,
+            3 => Resource,
+            4 => Rogue,
+            5 => Backlog,
+            6 => Ipe,
+            7 => Close,
+            8 => Version,
+            9 => RdvCookie,
+            10 => BadSecret,
+            11 => Unsecure,
+            12 => MessageApi,
+            13 => Congestion,
+            14 => Filter,
+            15 => Group,
+            16 => Timeout,
+            17 => Crypto,
+            _ => Unknown,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -870,6 +1202,7 @@ mod test {
                     })),
                     ext_km: None,
                     ext_config: None,
+                    ext_other: vec![],
                 },
             }),
         };
@@ -895,6 +1228,7 @@ mod test {
                 buffer_available: Some(1314),
                 packet_recv_rate: Some(0),
                 est_link_cap: Some(0),
+                byte_recv_rate: Some(0),
             }),
         };
 
@@ -906,6 +1240,171 @@ mod test {
         assert_eq!(pack, des);
     }
 
+    // the conclusion handshake of `handshake_ser_des_test`, serialized, its HS extension's length is at 66
+    fn conclusion() -> Vec<u8> {
+        let pack = ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(0),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber::new_truncate(1_827_131),
+                max_packet_size: 1500,
+                max_flow_size: 25600,
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketID(1231),
+                syn_cookie: 0,
+                peer_addr: "127.0.0.1".parse().unwrap(),
+                info: HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
+                        version: SrtVersion::CURRENT,
+                        flags: SrtShakeFlags::NAKREPORT | SrtShakeFlags::TSBPDSND,
+                        send_latency: Duration::from_millis(3000),
+                        recv_latency: Duration::from_millis(12345),
+                    })),
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            }),
+        };
+        let mut buf = vec![];
+        pack.serialize(&mut buf);
+        assert_eq!(buf[66..68], [0, 3]);
+        buf
+    }
+
+    #[test]
+    fn extension_bounds() {
+        let parse = |buf: Vec<u8>| ControlPacket::parse(&mut Cursor::new(buf));
+
+        // shorter than what's in it
+        let mut buf = conclusion();
+        buf[67] = 2;
+        assert!(matches!(parse(buf), Err(PacketParseError::NotEnoughData)));
+
+        // longer than the packet
+        let mut buf = conclusion();
+        buf[67] = 4;
+        assert!(matches!(parse(buf), Err(PacketParseError::NotEnoughData)));
+
+        // longer than what's in it, the rest is skipped
+        let mut buf = conclusion();
+        buf[67] = 4;
+        buf.extend_from_slice(&[0xff; 4]);
+        assert!(parse(buf).is_ok());
+    }
+
+    #[test]
+    fn unknown_extensions() {
+        let info = |ext_other| HandshakeVSInfo::V5 {
+            crypto_size: 0,
+            ext_hs: None,
+            ext_km: None,
+            ext_config: None,
+            ext_other,
+        };
+        let pack = |info| ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(0),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber::new_truncate(0),
+                max_packet_size: 1500,
+                max_flow_size: 25600,
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketID(1231),
+                syn_cookie: 0,
+                peer_addr: "127.0.0.1".parse().unwrap(),
+                info,
+            }),
+        };
+        let ext = |type_id, data: &'static [u8]| HandshakeExtension {
+            type_id,
+            data: Bytes::from_static(data),
+        };
+
+        let mut buf = vec![];
+        pack(info(vec![ext(5, b"#!::r=live"), ext(0x7f00, b"1234")])).serialize(&mut buf);
+        // the config flag, and the blocks padded to whole words
+        assert_eq!(buf[22..24], [0, 0b100]);
+        assert_eq!(buf.len(), 64 + 4 + 12 + 4 + 4);
+
+        assert_eq!(
+            ControlPacket::parse(&mut Cursor::new(buf)).unwrap(),
+            pack(info(vec![ext(5, b"#!::r=live\0\0"), ext(0x7f00, b"1234")]))
+        );
+    }
+
+    #[test]
+    fn stream_id_extension() {
+        let ext = HandshakeExtension::stream_id("#!::r=live");
+        assert_eq!(ext.type_id, 5);
+        assert_eq!(&ext.data[..], b"::!#il=r\0\0ev");
+        assert_eq!(ext.as_stream_id().as_deref(), Some("#!::r=live"));
+
+        let other = HandshakeExtension {
+            type_id: 6,
+            data: ext.data.clone(),
+        };
+        assert_eq!(other.as_stream_id(), None);
+    }
+
+    #[test]
+    fn metadata_extension() {
+        let ext = HandshakeExtension::metadata(b"codec=h264");
+        assert_eq!(ext.type_id, HandshakeExtension::METADATA);
+        assert_eq!(&ext.data[..], b"\0\0\0\x0acodec=h264\0\0");
+        assert_eq!(ext.as_metadata().as_deref(), Some(&b"codec=h264"[..]));
+
+        // trailing zeros are the application's
+        let zeros = HandshakeExtension::metadata(&[1, 0, 0, 0]);
+        assert_eq!(zeros.as_metadata().as_deref(), Some(&[1, 0, 0, 0][..]));
+
+        let empty = HandshakeExtension::metadata(b"");
+        assert_eq!(&empty.data[..], b"\0\0\0\0");
+        assert_eq!(empty.as_metadata().as_deref(), Some(&b""[..]));
+
+        // a length past the block
+        let bad = HandshakeExtension {
+            type_id: HandshakeExtension::METADATA,
+            data: Bytes::from_static(b"\0\0\0\x09abcd"),
+        };
+        assert_eq!(bad.as_metadata(), None);
+    }
+
+    #[test]
+    fn app_protocol_extension() {
+        let ext = HandshakeExtension::app_protocols(&["h3", "srt-live/1"]);
+        assert_eq!(ext.type_id, HandshakeExtension::APP_PROTOCOL);
+        assert_eq!(&ext.data[..], b"\0\0\0\x0e\x02h3\x0asrt-live/1\0\0");
+        assert_eq!(
+            ext.as_app_protocols(),
+            Some(vec!["h3".to_string(), "srt-live/1".to_string()])
+        );
+
+        // a protocol running past the list
+        let bad = HandshakeExtension {
+            type_id: HandshakeExtension::APP_PROTOCOL,
+            data: Bytes::from_static(b"\0\0\0\x03\x05abcd"),
+        };
+        assert_eq!(bad.as_app_protocols(), None);
+    }
+
+    #[test]
+    fn misaligned_control_length() {
+        let pack = ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(0),
+            control_type: ControlTypes::Nak(vec![1, 2]),
+        };
+        let mut buf = vec![];
+        pack.serialize(&mut buf);
+        buf.push(0);
+        assert!(matches!(
+            ControlPacket::parse(&mut Cursor::new(buf)),
+            Err(PacketParseError::BadControlLength(3, 9))
+        ));
+    }
+
     #[test]
     fn ack2_ser_des_test() {
         let pack = ControlPacket {
@@ -926,6 +1425,68 @@ mod test {
         assert_eq!(pack, des);
     }
 
+    #[test]
+    fn rejection_ser_des_test() {
+        let pack = ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(1234),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber(5),
+                max_packet_size: 1500,
+                max_flow_size: 8192,
+                shake_type: ShakeType::Rejection(RejectReason::Version),
+                socket_id: SocketID(5678),
+                syn_cookie: 42,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: None,
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            }),
+        };
+
+        let mut buf = vec![];
+        pack.serialize(&mut buf);
+        // the handshake type is the 6th word of the control information, after the 16 byte header
+        assert_eq!(&buf[36..40], &1008_i32.to_be_bytes()[..]);
+
+        let des = ControlPacket::parse(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(pack, des);
+
+        assert_eq!(
+            ShakeType::from_i32(1100),
+            Ok(ShakeType::Rejection(RejectReason::Unknown))
+        );
+    }
+
+    #[test]
+    fn custom_ser_des_test() {
+        // a reserved type, and a user defined type that isn't an SRT packet
+        for &(custom_type, reserved) in &[(0x9, 0), (0x7FFF, 0x123)] {
+            let pack = ControlPacket {
+                timestamp: TimeStamp::from_micros(1234),
+                dest_sockid: SocketID(42),
+                control_type: ControlTypes::Custom {
+                    custom_type,
+                    reserved,
+                    additional_info: -5,
+                    payload: Bytes::from_static(b"\x01\x02\x03\x04"),
+                },
+            };
+
+            let mut buf = vec![];
+            pack.serialize(&mut buf);
+            assert_eq!(&buf[..2], &(0x8000 | custom_type).to_be_bytes()[..]);
+            assert_eq!(&buf[2..4], &reserved.to_be_bytes()[..]);
+
+            let des = ControlPacket::parse(&mut Cursor::new(buf)).unwrap();
+            assert_eq!(pack, des);
+        }
+    }
+
     #[test]
     fn raw_srt_packet_test() {
         // this was taken from wireshark on a packet from stransmit that crashed
@@ -976,7 +1537,8 @@ mod test {
                             recv_latency: Duration::new(0, 0)
                         })),
                         ext_km: None,
-                        ext_config: None
+                        ext_config: None,
+                        ext_other: vec![]
                     }
                 })
             }
@@ -1032,7 +1594,8 @@ mod test {
                             )
                             .unwrap()
                         })),
-                        ext_config: None
+                        ext_config: None,
+                        ext_other: vec![]
                     }
                 })
             }
@@ -1077,6 +1640,7 @@ mod test {
                 info: HandshakeVSInfo::V5 {
                     crypto_size: 16,
                     ext_config: None,
+                    ext_other: vec![],
                     ext_hs: None,
                     ext_km: None,
                 },
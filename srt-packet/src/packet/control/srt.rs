@@ -1,4 +1,5 @@
-use std::{convert::TryFrom, time::Duration};
+use alloc::{vec, vec::Vec};
+use core::{convert::TryFrom, time::Duration};
 
 use bitflags::bitflags;
 use bytes::{Buf, BufMut};
@@ -134,6 +135,7 @@ impl TryFrom<u8> for PacketType {
 
 /// from https://github.com/Haivision/srt/blob/2ef4ef003c2006df1458de6d47fbe3d2338edf69/haicrypt/hcrypt_msg.h#L121-L124
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CipherType {
     None = 0,
     ECB = 1,
@@ -298,6 +300,11 @@ impl SrtKeyMessage {
     const SIGN: u16 =
         ((b'H' - b'@') as u16) << 10 | ((b'A' - b'@') as u16) << 5 | (b'I' - b'@') as u16;
 
+    /// The length of each key in bytes, 16, 24 or 32
+    pub fn key_length(&self) -> usize {
+        (self.wrapped_keys.len() - 8) / self.key_flags.bits.count_ones() as usize
+    }
+
     pub fn parse(buf: &mut impl Buf) -> Result<SrtKeyMessage, PacketParseError> {
         // first 32-bit word:
         //
@@ -337,6 +344,10 @@ impl SrtKeyMessage {
 
         // next 6 bits is reserved, then two bits of KF
         let key_flags = KeyFlags::from_bits_truncate(buf.get_u8() & 0b0000_0011);
+        // at least one of the keys has to be there
+        if key_flags.is_empty() {
+            return Err(PacketParseError::BadSRTExtensionMessage);
+        }
 
         // second 32-bit word: keki
         let keki = buf.get_u32();
@@ -445,9 +456,7 @@ impl SrtKeyMessage {
         into.put_u16(0); // resv2
         into.put_u8((self.salt.len() / 4) as u8);
 
-        // this unwrap is okay because we already panic above if both are None
-        let key_len = (self.wrapped_keys.len() - 8) / self.key_flags.bits.count_ones() as usize;
-        into.put_u8((key_len / 4) as u8);
+        into.put_u8((self.key_length() / 4) as u8);
 
         // put the salt then key[s]
         into.put(&self.salt[..]);
@@ -491,9 +500,12 @@ impl TryFrom<u8> for CipherType {
 
 #[cfg(test)]
 mod tests {
-    use super::{SrtControlPacket, SrtHandshake, SrtShakeFlags};
+    use super::{
+        Auth, CipherType, KeyFlags, PacketType, SrtControlPacket, SrtHandshake, SrtKeyMessage,
+        SrtShakeFlags,
+    };
     use crate::packet::ControlTypes;
-    use crate::{protocol::TimeStamp, ControlPacket, Packet, SocketID, SrtVersion};
+    use crate::{ControlPacket, Packet, PacketParseError, SocketID, SrtVersion, TimeStamp};
 
     use std::io::Cursor;
     use std::time::Duration;
@@ -518,4 +530,46 @@ mod tests {
 
         assert_eq!(handshake, deserialized);
     }
+
+    #[test]
+    fn key_length() {
+        let km = SrtKeyMessage {
+            pt: PacketType::KeyingMaterial,
+            key_flags: KeyFlags::EVEN | KeyFlags::ODD,
+            keki: 0,
+            cipher: CipherType::CTR,
+            auth: Auth::None,
+            salt: vec![1; 16],
+            wrapped_keys: vec![2; 2 * 24 + 8],
+        };
+        assert_eq!(km.key_length(), 24);
+
+        let mut buf = Vec::new();
+        km.serialize(&mut buf);
+        let parsed = SrtKeyMessage::parse(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(parsed.key_length(), 24);
+        assert_eq!(parsed, km);
+    }
+
+    // with neither key there's no key length, a peer can't make that divide by zero
+    #[test]
+    fn no_keys() {
+        let km = SrtKeyMessage {
+            pt: PacketType::KeyingMaterial,
+            key_flags: KeyFlags::EVEN,
+            keki: 0,
+            cipher: CipherType::CTR,
+            auth: Auth::None,
+            salt: vec![1; 16],
+            wrapped_keys: vec![2; 16 + 8],
+        };
+        let mut buf = Vec::new();
+        km.serialize(&mut buf);
+        buf[3] &= !0b11;
+
+        assert!(matches!(
+            SrtKeyMessage::parse(&mut Cursor::new(buf)),
+            Err(PacketParseError::BadSRTExtensionMessage)
+        ));
+    }
 }
@@ -0,0 +1,142 @@
+use crate::{SeqNumber, SocketID, TimeStamp};
+
+use super::{AckControlInfo, ControlPacket, ControlTypes};
+
+// the header, then the seven words of a full ACK, or the one unused word of a keepalive
+const ACK_LEN: usize = 16 + 7 * 4;
+const KEEPALIVE_LEN: usize = 16 + 4;
+
+/// The ACKs and keepalives a connection sends its peer every few milliseconds, encoded once, so encoding another only
+/// patches the fields that change into the same buffer, rather than serializing it field by field
+///
+/// The buffers are on the stack, so nothing is allocated either, which adds up for servers with many connections.
+/// Other packets, and those to another socket, aren't encoded from it.
+#[derive(Debug, Clone)]
+pub struct ControlTemplate {
+    dest_sockid: SocketID,
+    ack: [u8; ACK_LEN],
+    keepalive: [u8; KEEPALIVE_LEN],
+}
+
+impl ControlTemplate {
+    pub fn new(dest_sockid: SocketID) -> Self {
+        let prototype = |control_type| ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid,
+            control_type,
+        };
+        let mut template = ControlTemplate {
+            dest_sockid,
+            ack: [0; ACK_LEN],
+            keepalive: [0; KEEPALIVE_LEN],
+        };
+        prototype(ControlTypes::Ack(AckControlInfo {
+            ack_seq_num: 0,
+            ack_number: SeqNumber::new_truncate(0),
+            rtt: None,
+            rtt_variance: None,
+            buffer_available: None,
+            packet_recv_rate: None,
+            est_link_cap: None,
+            byte_recv_rate: None,
+        }))
+        .serialize(&mut &mut template.ack[..]);
+        prototype(ControlTypes::KeepAlive).serialize(&mut &mut template.keepalive[..]);
+        template
+    }
+
+    /// The socket the packets are sent to
+    pub fn dest_sockid(&self) -> SocketID {
+        self.dest_sockid
+    }
+
+    /// `packet` as it's serialized, if it's an ACK or keepalive to [`dest_sockid`](ControlTemplate::dest_sockid)
+    pub fn encode(&mut self, packet: &ControlPacket) -> Option<&[u8]> {
+        if packet.dest_sockid != self.dest_sockid {
+            return None;
+        }
+        let timestamp = packet.timestamp.as_micros();
+        match &packet.control_type {
+            ControlTypes::Ack(ack) => {
+                patch(&mut self.ack, 4, ack.ack_seq_num as u32);
+                patch(&mut self.ack, 8, timestamp);
+                for (i, word) in ack.words().iter().enumerate() {
+                    patch(&mut self.ack, 16 + i * 4, *word);
+                }
+                Some(&self.ack[..])
+            }
+            ControlTypes::KeepAlive => {
+                patch(&mut self.keepalive, 8, timestamp);
+                Some(&self.keepalive[..])
+            }
+            _ => None,
+        }
+    }
+}
+
+fn patch(buf: &mut [u8], at: usize, word: u32) {
+    buf[at..at + 4].copy_from_slice(&word.to_be_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::TimeSpan;
+
+    fn serialized(packet: &ControlPacket) -> Vec<u8> {
+        let mut buf = Vec::new();
+        packet.serialize(&mut buf);
+        buf
+    }
+
+    fn control(dest_sockid: u32, micros: u32, control_type: ControlTypes) -> ControlPacket {
+        ControlPacket {
+            timestamp: TimeStamp::from_micros(micros),
+            dest_sockid: SocketID(dest_sockid),
+            control_type,
+        }
+    }
+
+    fn ack(ack_seq_num: i32, ack_number: u32, rtt: Option<i32>) -> ControlTypes {
+        ControlTypes::Ack(AckControlInfo {
+            ack_seq_num,
+            ack_number: SeqNumber::new_truncate(ack_number),
+            rtt: rtt.map(TimeSpan::from_micros),
+            rtt_variance: Some(TimeSpan::from_micros(2_000)),
+            buffer_available: Some(8000),
+            packet_recv_rate: Some(5_000),
+            est_link_cap: None,
+            byte_recv_rate: Some(6_580_000),
+        })
+    }
+
+    #[test]
+    fn same_as_serialized() {
+        let mut template = ControlTemplate::new(SocketID(1234));
+        for packet in &[
+            control(1234, 10_000, ack(1, 100, Some(20_000))),
+            control(1234, 20_000, ControlTypes::KeepAlive),
+            // every field changed since the last one
+            control(1234, 30_000, ack(2, 150, None)),
+            control(1234, 1_030_000, ControlTypes::KeepAlive),
+        ] {
+            assert_eq!(template.encode(packet), Some(&serialized(packet)[..]));
+        }
+    }
+
+    #[test]
+    fn not_templated() {
+        let mut template = ControlTemplate::new(SocketID(1234));
+        assert_eq!(
+            template.encode(&control(4321, 0, ControlTypes::KeepAlive)),
+            None
+        );
+        assert_eq!(
+            template.encode(&control(1234, 0, ControlTypes::Shutdown)),
+            None
+        );
+    }
+}
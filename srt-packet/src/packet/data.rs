@@ -1,12 +1,11 @@
 use bitflags::bitflags;
 use bytes::{Buf, BufMut, Bytes};
 
-use std::cmp::min;
-use std::{convert::TryFrom, fmt};
+use core::cmp::min;
+use core::{convert::TryFrom, fmt};
 
 use super::PacketParseError;
-use crate::protocol::TimeStamp;
-use crate::{MsgNumber, SeqNumber, SocketID};
+use crate::{MsgNumber, SeqNumber, SocketID, TimeStamp};
 
 /// A UDT packet carrying data
 ///
@@ -83,7 +82,13 @@ pub enum DataEncryption {
 }
 
 impl DataPacket {
+    /// Parse the packet in `buf`. Parsed from [`Bytes`], the payload is a slice of the same memory rather than a copy.
     pub fn parse(buf: &mut impl Buf) -> Result<DataPacket, PacketParseError> {
+        // the header is 4 32-bit words
+        if buf.remaining() < 16 {
+            return Err(PacketParseError::NotEnoughData);
+        }
+
         // get the sequence number, which is the last 31 bits of the header
         let seq_number = SeqNumber::new_truncate(buf.get_u32());
 
@@ -210,5 +215,177 @@ mod tests {
             dp2.serialize(&mut v2);
             assert_eq!(v, v2);
         }
+
+        #[test]
+        fn datapacket_roundtrip(
+            seq_number in 0u32..(1 << 31),
+            message_loc in 0u8..4,
+            in_order_delivery: bool,
+            enc in 0u8..3,
+            retransmitted: bool,
+            message_number in 0u32..(1 << 26),
+            timestamp: u32,
+            dest_sockid: u32,
+            payload in proptest::collection::vec(any::<u8>(), 0..1500),
+        ) {
+            let dp = DataPacket {
+                seq_number: SeqNumber::new_truncate(seq_number),
+                message_loc: PacketLocation::from_bits_truncate(message_loc << 6),
+                in_order_delivery,
+                encryption: DataEncryption::try_from(enc << 3).unwrap(),
+                retransmitted,
+                message_number: MsgNumber::new_truncate(message_number),
+                timestamp: TimeStamp::from_micros(timestamp),
+                dest_sockid: SocketID(dest_sockid),
+                payload: Bytes::from(payload),
+            };
+            let mut v = vec![];
+            dp.serialize(&mut v);
+            assert_eq!(v.len(), 16 + dp.payload.len());
+
+            // the top bit is what makes this a data packet
+            assert_eq!(v[0] & 0x80, 0);
+
+            let dp2 = DataPacket::parse(&mut Cursor::new(&v)).unwrap();
+            assert_eq!(dp, dp2);
+        }
+    }
+
+    fn second_word(dp: &DataPacket) -> u32 {
+        let mut v = vec![];
+        dp.serialize(&mut v);
+        u32::from_be_bytes([v[4], v[5], v[6], v[7]])
+    }
+
+    fn basic_packet() -> DataPacket {
+        DataPacket {
+            seq_number: SeqNumber::new_truncate(0),
+            message_loc: PacketLocation::MIDDLE,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber::new_truncate(0),
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(0),
+            payload: Bytes::new(),
+        }
+    }
+
+    // FF|O|KK|R|MSGNO, from the SRT RFC draft
+    #[test]
+    fn header_bits() {
+        let cases = [
+            (
+                DataPacket {
+                    message_loc: PacketLocation::FIRST,
+                    ..basic_packet()
+                },
+                0x8000_0000,
+            ),
+            (
+                DataPacket {
+                    message_loc: PacketLocation::LAST,
+                    ..basic_packet()
+                },
+                0x4000_0000,
+            ),
+            (
+                DataPacket {
+                    in_order_delivery: true,
+                    ..basic_packet()
+                },
+                0x2000_0000,
+            ),
+            (
+                DataPacket {
+                    encryption: DataEncryption::Odd,
+                    ..basic_packet()
+                },
+                0x1000_0000,
+            ),
+            (
+                DataPacket {
+                    encryption: DataEncryption::Even,
+                    ..basic_packet()
+                },
+                0x0800_0000,
+            ),
+            (
+                DataPacket {
+                    retransmitted: true,
+                    ..basic_packet()
+                },
+                0x0400_0000,
+            ),
+            (
+                DataPacket {
+                    message_number: MsgNumber::new_truncate((1 << 26) - 1),
+                    ..basic_packet()
+                },
+                0x03FF_FFFF,
+            ),
+            (
+                DataPacket {
+                    message_loc: PacketLocation::ONLY,
+                    in_order_delivery: true,
+                    encryption: DataEncryption::Odd,
+                    retransmitted: true,
+                    message_number: MsgNumber::new_truncate((1 << 26) - 1),
+                    ..basic_packet()
+                },
+                0xF7FF_FFFF,
+            ),
+        ];
+
+        for (dp, expected) in &cases {
+            assert_eq!(
+                second_word(dp),
+                *expected,
+                "{:?} serialized to {:#010x}",
+                dp,
+                second_word(dp)
+            );
+            let mut v = vec![];
+            dp.serialize(&mut v);
+            assert_eq!(&DataPacket::parse(&mut Cursor::new(&v)).unwrap(), dp);
+        }
+    }
+
+    #[test]
+    fn bad_key_bits() {
+        // KK = 0b11 is not valid for a data packet
+        let mut v = vec![];
+        basic_packet().serialize(&mut v);
+        v[4] |= 0b0001_1000;
+        assert!(matches!(
+            DataPacket::parse(&mut Cursor::new(&v)),
+            Err(PacketParseError::BadDataEncryption(0b0001_1000))
+        ));
+    }
+
+    #[test]
+    fn not_enough_data() {
+        let mut v = vec![];
+        basic_packet().serialize(&mut v);
+        v.truncate(15);
+        assert!(matches!(
+            DataPacket::parse(&mut Cursor::new(&v)),
+            Err(PacketParseError::NotEnoughData)
+        ));
+    }
+
+    #[test]
+    fn payload_not_copied() {
+        let mut v = vec![];
+        DataPacket {
+            payload: Bytes::from_static(b"hello"),
+            ..basic_packet()
+        }
+        .serialize(&mut v);
+        let datagram = Bytes::from(v);
+
+        let dp = DataPacket::parse(&mut datagram.clone()).unwrap();
+        assert_eq!(dp.payload, "hello");
+        assert_eq!(dp.payload.as_ptr(), datagram[16..].as_ptr());
     }
 }
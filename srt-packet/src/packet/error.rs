@@ -1,4 +1,6 @@
-use std::{error::Error, fmt, io};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{error::Error, io};
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -20,6 +22,8 @@ pub enum PacketParseError {
     BadStreamEncapsulation(u8),
     StreamEncapsulationNotSrt,
     BadDataEncryption(u8),
+    BadControlLength(u16, usize), // control type, body length
+    #[cfg(feature = "std")]
     Io(io::Error),
 }
 
@@ -28,6 +32,7 @@ impl fmt::Display for PacketParseError {
         <Self as fmt::Debug>::fmt(self, f)
     }
 }
+#[cfg(feature = "std")]
 impl Error for PacketParseError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         if let PacketParseError::Io(e) = self {
@@ -38,12 +43,14 @@ impl Error for PacketParseError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<PacketParseError> for io::Error {
     fn from(s: PacketParseError) -> Self {
         io::Error::new(io::ErrorKind::InvalidData, s)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for PacketParseError {
     fn from(s: io::Error) -> PacketParseError {
         PacketParseError::Io(s)
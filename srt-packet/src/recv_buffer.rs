@@ -0,0 +1,871 @@
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::cmp::min;
+use core::fmt;
+use core::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use log::{debug, info, warn};
+
+use crate::packet::PacketLocation;
+use crate::time::{SynchronizedRemoteClock, TimeBase, TimePoint};
+use crate::{DataPacket, SeqNumber, TimeStamp};
+
+/// How much slower than real time messages are released while the latency grows, 1/10th
+const LATENCY_RAMP: u32 = 10;
+
+/// Fixed storage for the packets from the next to be released on, indexed by their offset from it, so it's never
+/// reallocated while packets come in
+struct PacketRing {
+    slots: Box<[Option<DataPacket>]>,
+
+    /// The slot at offset 0
+    start: usize,
+
+    /// The slots in use, up to and including the last packet stored
+    len: usize,
+}
+
+impl PacketRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| None).collect(),
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, offset: usize) -> usize {
+        (self.start + offset) % self.slots.len()
+    }
+
+    fn get(&self, offset: usize) -> Option<&DataPacket> {
+        if offset >= self.len {
+            return None;
+        }
+        self.slots[self.slot(offset)].as_ref()
+    }
+
+    fn front(&self) -> Option<&DataPacket> {
+        self.get(0)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Option<&DataPacket>> + '_ {
+        self.iter_from(0).map(|(_, packet)| packet)
+    }
+
+    /// The slots from `offset` on, with their offsets
+    fn iter_from(&self, offset: usize) -> impl Iterator<Item = (usize, Option<&DataPacket>)> + '_ {
+        (offset..self.len).map(move |offset| (offset, self.slots[self.slot(offset)].as_ref()))
+    }
+
+    /// Store `packet` at `offset`, giving it back if that's past the capacity
+    fn insert(&mut self, offset: usize, packet: DataPacket) -> Result<(), DataPacket> {
+        if offset >= self.capacity() {
+            return Err(packet);
+        }
+        let slot = self.slot(offset);
+        self.slots[slot] = Some(packet);
+        self.len = self.len.max(offset + 1);
+        Ok(())
+    }
+
+    /// Take what's at offset 0, moving everything else one closer
+    fn pop_front(&mut self) -> Option<DataPacket> {
+        if self.len == 0 {
+            return None;
+        }
+        let packet = self.slots[self.start].take();
+        self.start = self.slot(1);
+        self.len -= 1;
+        packet
+    }
+
+    /// Move everything `count` closer, emptying the slots in between
+    fn skip(&mut self, count: usize) {
+        let used = count.min(self.len);
+        for _ in 0..used {
+            self.pop_front();
+        }
+        // the slots past `len` are empty already
+        self.start = self.slot(count - used);
+    }
+}
+
+/// The packets received and not released yet, that are reassembled into messages and released on time
+///
+/// `T` is the clock it's run on, see [`TimePoint`].
+/// How much of the message at the front [`RecvBuffer::drop_invalid_messages`] checked, so it carries on from there the
+/// next time instead of from the start of the message
+#[derive(Default, Clone, Copy)]
+struct FrontScan {
+    /// The packets checked, from the head on
+    packets: usize,
+
+    /// Their payloads, in bytes
+    size: usize,
+
+    /// The last packet of the message was among them
+    complete: bool,
+}
+
+pub struct RecvBuffer<T> {
+    // stores the incoming packets as they arrive
+    // `buffer.get(0)` will hold sequence number `head`
+    buffer: PacketRing,
+
+    // The next to be released sequence number
+    head: SeqNumber,
+
+    remote_clock: SynchronizedRemoteClock<T>,
+    time_base: TimeBase<T>,
+
+    /// The TSBPD latency configured by the user.
+    /// Not necessarily the actual decided on latency, which
+    /// is the max of both side's respective latencies.
+    tsbpd_latency: Duration,
+
+    /// The latency being grown to, and when it started growing from `tsbpd_latency`, see [`RecvBuffer::set_latency`]
+    latency_ramp: Option<(Duration, T)>,
+
+    /// The largest message that will be reassembled, in bytes
+    max_message_size: usize,
+
+    /// Ranges of packets the sender dropped, inclusive, that are skipped once they reach the head
+    dropped: VecDeque<(SeqNumber, SeqNumber)>,
+
+    /// Reset whenever the head moves
+    front_scan: FrontScan,
+}
+
+impl<T: TimePoint> RecvBuffer<T> {
+    /// Creates a `RecvBuffer`
+    ///
+    /// * `head` - The sequence number of the next packet
+    /// * `max_message_size` - Messages larger than this, in bytes, are dropped instead of reassembled
+    /// * `capacity` - How many packets past `head` can be stored, the flow window
+    pub fn new(
+        head: SeqNumber,
+        start: T,
+        tsbpd_latency: Duration,
+        max_message_size: usize,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            buffer: PacketRing::new(capacity),
+            head,
+            time_base: TimeBase::new(start),
+            remote_clock: SynchronizedRemoteClock::new(start),
+            tsbpd_latency,
+            latency_ramp: None,
+            max_message_size,
+            dropped: VecDeque::new(),
+            front_scan: FrontScan::default(),
+        }
+    }
+
+    /// The latency messages are released with at `now`
+    pub fn latency(&self, now: T) -> Duration {
+        match self.latency_ramp {
+            Some((target, start)) => min(
+                target,
+                self.tsbpd_latency + (now.max(start) - start) / LATENCY_RAMP,
+            ),
+            None => self.tsbpd_latency,
+        }
+    }
+
+    /// Grow the latency to `latency`, slowing releases down a little until it's reached instead of pausing them
+    ///
+    /// The latency can't be lowered, that would drop the messages that became too late all at once.
+    pub fn set_latency(&mut self, latency: Duration, now: T) {
+        let current = self.latency(now);
+        if latency < current {
+            warn!(
+                "Not lowering the latency from {:?} to {:?}",
+                current, latency
+            );
+            return;
+        }
+        self.tsbpd_latency = current;
+        self.latency_ramp = Some((latency, now));
+    }
+
+    /// The next to be released sequence number
+    pub fn next_release(&self) -> SeqNumber {
+        self.head
+    }
+
+    /// The slots from `next_release` to the last packet received, including the ones still missing
+    pub fn slots(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The slots that have a packet
+    pub fn buffered_packets(&self) -> usize {
+        self.buffer.iter().filter(|p| p.is_some()).count()
+    }
+
+    /// If the packet `seq_number` can be added now, it's either already released or within the capacity
+    pub fn has_room(&self, seq_number: SeqNumber) -> bool {
+        seq_number < self.head || ((seq_number - self.head) as usize) < self.buffer.capacity()
+    }
+
+    /// Adds a packet to the buffer
+    /// If `pack.seq_number < self.head`, this is nop (ie it appears before an already released packet), as it is if
+    /// the packet is past the capacity, which a peer respecting the flow window doesn't send
+    pub fn add(&mut self, pack: DataPacket) {
+        if pack.seq_number < self.head {
+            return; // packet is too late
+        }
+
+        let idx = (pack.seq_number - self.head) as usize;
+        if let Err(pack) = self.buffer.insert(idx, pack) {
+            warn!(
+                "No room for packet {}, {} past the next to be released",
+                pack.seq_number, idx
+            );
+        }
+    }
+
+    /// Skips the packets `first..=last`, which the sender dropped and won't send
+    pub fn drop_range(&mut self, first: SeqNumber, last: SeqNumber) {
+        if last < self.head {
+            return;
+        }
+        // drop requests can arrive out of order
+        let idx = self
+            .dropped
+            .iter()
+            .position(|&(f, _)| f > first)
+            .unwrap_or(self.dropped.len());
+        self.dropped.insert(idx, (first, last));
+        self.skip_dropped();
+    }
+
+    // release the head past the dropped ranges it reached
+    fn skip_dropped(&mut self) {
+        while let Some(&(first, last)) = self.dropped.front() {
+            if last < self.head {
+                self.dropped.pop_front();
+            } else if first <= self.head {
+                debug!(
+                    "Skipping packets [{},{}], dropped by the sender",
+                    self.head, last
+                );
+                let count = (last + 1 - self.head) as usize;
+                self.head = last + 1;
+                self.buffer.skip(count);
+                self.front_scan = FrontScan::default();
+                self.dropped.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn synchronize_clock(&mut self, now: T, ts: TimeStamp) {
+        self.remote_clock.synchronize(now, ts);
+    }
+
+    /// Drops the packets that are deemed to be too late
+    /// IE: there is a packet after it that is ready to be released
+    ///
+    /// Returns the number of packets dropped
+    pub fn drop_too_late_packets(&mut self, now: T) -> usize {
+        // Not only does it have to be non-none, it also has to be a First (don't drop half messages)
+        let first_non_none_idx = self.buffer.iter().position(
+            |a| matches!(a, Some(pack) if pack.message_loc.contains(PacketLocation::FIRST)),
+        );
+
+        let first_non_none_idx = match first_non_none_idx {
+            None | Some(0) => return 0, // even though some of these may be too late, there are none that can be released so they can't them back.
+            Some(i) => i,
+        };
+
+        let first_pack_ts_us = self.buffer.get(first_non_none_idx).unwrap().timestamp;
+        // we are too late if that packet is ready
+        // give a 2 ms buffer range, be ok with releasing them 2ms late
+        let too_late =
+            self.tsbpd_instant_from(now, first_pack_ts_us) + Duration::from_millis(2) <= now;
+
+        if too_late {
+            info!(
+                "Dropping packets [{},{}), {} ms too late",
+                self.head,
+                self.head + first_non_none_idx as u32,
+                (now - self.tsbpd_instant_from(now, first_pack_ts_us)).as_millis()
+            );
+            // start dropping packets
+            self.pop_packets(first_non_none_idx);
+            first_non_none_idx
+        } else {
+            0 // the next available packet isn't ready to be sent yet
+        }
+    }
+
+    /// Drops messages at the front of the buffer that can never be released, so the buffer doesn't grow without bound:
+    /// * packets that are not part of a message because its first packet is missing or was dropped
+    /// * messages whose packets add up to more than `max_message_size`
+    /// * messages that are cut off by the first packet of another message
+    ///
+    /// The message at the front is checked on from where the last call left off, so calling this after every packet
+    /// added doesn't go over its packets again.
+    ///
+    /// Returns the number of messages that were too large, and the number of other packets dropped
+    pub fn drop_invalid_messages(&mut self) -> (usize, usize) {
+        let mut oversized = 0;
+        let mut orphaned = 0;
+
+        while let Some(first) = self.buffer.front() {
+            if !first.message_loc.contains(PacketLocation::FIRST) {
+                debug!("Dropping packet {} with no message start", first.seq_number);
+                self.pop_packets(1);
+                orphaned += 1;
+                continue;
+            }
+            if self.front_scan.complete {
+                break;
+            }
+
+            let mut scan = self.front_scan;
+            let mut drop = None;
+            for (i, pack) in self.buffer.iter_from(scan.packets) {
+                let pack = match pack {
+                    Some(pack) => pack,
+                    None => break,
+                };
+                if i != 0 && pack.message_loc.contains(PacketLocation::FIRST) {
+                    // a new message started before this one ended
+                    drop = Some(i);
+                    break;
+                }
+
+                scan.packets = i + 1;
+                scan.size += pack.payload.len();
+                if scan.size > self.max_message_size {
+                    oversized += 1;
+                    drop = Some(i + 1);
+                    break;
+                }
+                if pack.message_loc.contains(PacketLocation::LAST) {
+                    scan.complete = true;
+                    break;
+                }
+            }
+            self.front_scan = scan;
+
+            match drop {
+                Some(count) => {
+                    info!(
+                        "Dropping invalid message [{},{}), {} bytes so far",
+                        self.head,
+                        self.head + count as u32,
+                        scan.size
+                    );
+                    if scan.size <= self.max_message_size {
+                        orphaned += count;
+                    }
+                    self.pop_packets(count);
+                }
+                None => break,
+            }
+        }
+
+        (oversized, orphaned)
+    }
+
+    fn pop_packets(&mut self, count: usize) {
+        self.head += count as u32;
+        self.buffer.skip(count);
+        self.front_scan = FrontScan::default();
+        self.skip_dropped();
+    }
+
+    /// Check if there is an available message to release with TSBPD
+    /// ie - `start_time + timestamp + tsbpd <= now`
+    ///
+    /// * `latency` - The latency to release with
+    /// * `start_time` - The start time of the socket to add to timestamps
+    /// TODO: this does not account for timestamp wrapping
+    ///
+    /// Returns `None` if there is no message available, or `Some(i)` if there is a packet available, `i` being the number of packets it spans.
+    pub fn next_msg_ready_tsbpd(&self, now: T) -> Option<usize> {
+        let msg_size = self.next_msg_ready()?;
+
+        let pack = self.buffer.front().unwrap();
+
+        if self.tsbpd_instant_from(now, pack.timestamp) <= now {
+            debug!(
+                "Message was deemed ready for release, Now={:?}, Ts={:?}, dT={:?}, Latency={:?}, buf.len={}, sn={}, npackets={}",
+                now - self.remote_clock.origin_time(),
+                pack.timestamp.as_duration(),
+                now - self.remote_clock.instant_from(now, pack.timestamp),
+                self.latency(now),
+                self.buffer.len(),
+                pack.seq_number,
+                msg_size,
+            );
+            Some(msg_size)
+        } else {
+            None
+        }
+    }
+
+    /// Check if the next message is available. Returns `None` if there is no message,
+    /// and `Some(i)` if there is a message available, where `i` is the number of packets this message spans
+    pub fn next_msg_ready(&self) -> Option<usize> {
+        if let Some(first) = self.buffer.front() {
+            // we have a first packet, make sure it has the start flag set
+            assert!(
+                first.message_loc.contains(PacketLocation::FIRST),
+                "Packet seq={} was not marked as the first in it's message",
+                first.seq_number
+            );
+
+            let mut count = 1;
+
+            for i in self.buffer.iter() {
+                match i {
+                    Some(pack) if pack.message_loc.contains(PacketLocation::LAST) => {
+                        return Some(count)
+                    }
+                    None => return None,
+                    _ => count += 1,
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn next_message_release_time(&self, now: T) -> Option<T> {
+        let _msg_size = self.next_msg_ready()?;
+        let timestamp = self.buffer.front()?.timestamp;
+        Some(self.tsbpd_instant_from(now, timestamp))
+    }
+
+    /// A convenience function for
+    /// `self.next_msg_ready_tsbpd(...).map(|_| self.next_msg().unwrap()`
+    pub fn next_msg_tsbpd(&mut self, now: T) -> Option<(T, Bytes)> {
+        self.next_msg_ready_tsbpd(now)
+            .map(|_| self.next_msg(now).unwrap())
+    }
+
+    /// Check if there is an available message, returning, and its origin timestamp it if found
+    pub fn next_msg(&mut self, now: T) -> Option<(T, Bytes)> {
+        let count = self.next_msg_ready()?;
+
+        self.head += count as u32;
+        self.front_scan = FrontScan::default();
+
+        let origin_time = self
+            .remote_clock
+            .instant_from(now, self.buffer.front().unwrap().timestamp);
+
+        // optimize for single packet messages
+        let payload = if count == 1 {
+            self.buffer.pop_front().unwrap().payload
+        } else {
+            // accumulate the rest
+            let buffer = &mut self.buffer;
+            (0..count)
+                .fold(BytesMut::new(), |mut bytes, _| {
+                    bytes.extend(buffer.pop_front().unwrap().payload);
+                    bytes
+                })
+                .freeze()
+        };
+        self.skip_dropped();
+
+        Some((origin_time, payload))
+    }
+
+    fn tsbpd_instant_from(&self, now: T, timestamp: TimeStamp) -> T {
+        self.remote_clock.instant_from(now, timestamp) + self.latency(now)
+    }
+
+    pub fn timestamp_from(&self, at: T) -> TimeStamp {
+        self.time_base.timestamp_from(at)
+    }
+}
+
+impl<T> fmt::Debug for RecvBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{:?}",
+            self.buffer
+                .iter()
+                .map(|o| o.map(|pack| (pack.seq_number.as_raw(), pack.message_loc)))
+                .collect::<Vec<_>>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::RecvBuffer;
+    use crate::{
+        packet::{DataEncryption, PacketLocation},
+        DataPacket, MsgNumber, SeqNumber, SocketID, TimeStamp,
+    };
+    use bytes::Bytes;
+    use std::time::{Duration, Instant};
+
+    fn basic_pack() -> DataPacket {
+        DataPacket {
+            seq_number: SeqNumber::new_truncate(5),
+            message_loc: PacketLocation::FIRST,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber(0),
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(4),
+            payload: Bytes::new(),
+        }
+    }
+
+    fn new_buffer(head: SeqNumber) -> RecvBuffer<Instant> {
+        RecvBuffer::new(head, Instant::now(), Duration::from_millis(100), 10, 8192)
+    }
+
+    #[test]
+    fn not_ready_empty() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(3));
+
+        assert_eq!(buf.next_msg_ready(), None);
+        assert_eq!(buf.next_msg(Instant::now()), None);
+        assert_eq!(buf.next_release(), SeqNumber(3));
+    }
+
+    #[test]
+    fn not_ready_no_more() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.next_msg_ready(), None);
+        assert_eq!(buf.next_msg(Instant::now()), None);
+        assert_eq!(buf.next_release(), SeqNumber(5));
+    }
+
+    #[test]
+    fn not_ready_none() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(7),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.next_msg_ready(), None);
+        assert_eq!(buf.next_msg(Instant::now()), None);
+        assert_eq!(buf.next_release(), SeqNumber(5));
+    }
+
+    #[test]
+    fn not_ready_middle() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::empty(),
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.next_msg_ready(), None);
+        assert_eq!(buf.next_msg(Instant::now()), None);
+        assert_eq!(buf.next_release(), SeqNumber(5));
+    }
+
+    #[test]
+    fn ready_single() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            payload: From::from(&b"hello"[..]),
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::empty(),
+            payload: From::from(&b"no"[..]),
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.next_msg_ready(), Some(1));
+        assert_eq!(
+            buf.next_msg(Instant::now()),
+            Some((buf.remote_clock.origin_time(), From::from(&b"hello"[..])))
+        );
+        assert_eq!(buf.next_release(), SeqNumber(6));
+        assert_eq!(buf.buffer.len(), 1);
+    }
+
+    #[test]
+    fn ready_multi() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            payload: From::from(&b"hello"[..]),
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::empty(),
+            payload: From::from(&b"yas"[..]),
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(7),
+            message_loc: PacketLocation::LAST,
+            payload: From::from(&b"nas"[..]),
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.next_msg_ready(), Some(3));
+        assert_eq!(
+            buf.next_msg(Instant::now()),
+            Some((
+                buf.remote_clock.origin_time(),
+                From::from(&b"helloyasnas"[..])
+            ))
+        );
+        assert_eq!(buf.next_release(), SeqNumber(8));
+        assert_eq!(buf.buffer.len(), 0);
+    }
+
+    #[test]
+    fn drop_orphaned() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::LAST,
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(7),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            ..basic_pack()
+        });
+
+        assert_eq!(buf.drop_invalid_messages(), (0, 2));
+        assert_eq!(buf.next_release(), SeqNumber(7));
+        assert_eq!(buf.next_msg_ready(), Some(1));
+    }
+
+    #[test]
+    fn drop_oversized() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            payload: From::from(&b"hello"[..]),
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::empty(),
+            payload: From::from(&b"hello"[..]),
+            ..basic_pack()
+        });
+
+        // exactly the max is ok
+        assert_eq!(buf.drop_invalid_messages(), (0, 0));
+        assert_eq!(buf.next_release(), SeqNumber(5));
+
+        buf.add(DataPacket {
+            seq_number: SeqNumber(7),
+            message_loc: PacketLocation::empty(),
+            payload: From::from(&b"!"[..]),
+            ..basic_pack()
+        });
+        buf.add(DataPacket {
+            seq_number: SeqNumber(8),
+            message_loc: PacketLocation::LAST,
+            payload: From::from(&b"!"[..]),
+            ..basic_pack()
+        });
+
+        // the rest of the message is dropped as it has no start
+        assert_eq!(buf.drop_invalid_messages(), (1, 1));
+        assert_eq!(buf.next_release(), SeqNumber(9));
+        assert_eq!(buf.buffer.len(), 0);
+    }
+
+    // checked a packet at a time, like the receiver does, until the next message cuts it off
+    #[test]
+    fn drop_cut_off() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST,
+            ..basic_pack()
+        });
+        assert_eq!(buf.drop_invalid_messages(), (0, 0));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(6),
+            message_loc: PacketLocation::empty(),
+            ..basic_pack()
+        });
+        assert_eq!(buf.drop_invalid_messages(), (0, 0));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(7),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            ..basic_pack()
+        });
+        assert_eq!(buf.drop_invalid_messages(), (0, 2));
+        assert_eq!(buf.next_release(), SeqNumber(7));
+
+        // the complete message is kept
+        assert_eq!(buf.drop_invalid_messages(), (0, 0));
+        assert_eq!(buf.next_msg_ready(), Some(1));
+    }
+
+    #[test]
+    fn drop_range() {
+        let mut buf = new_buffer(SeqNumber::new_truncate(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(8),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            payload: From::from(&b"hello"[..]),
+            ..basic_pack()
+        });
+
+        // skipped once the packets before it are released
+        buf.drop_range(SeqNumber(6), SeqNumber(7));
+        assert_eq!(buf.next_release(), SeqNumber(5));
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            ..basic_pack()
+        });
+        assert!(buf.next_msg(Instant::now()).is_some());
+        assert_eq!(buf.next_release(), SeqNumber(8));
+        assert_eq!(buf.next_msg_ready(), Some(1));
+
+        // or right away at the head, even if some were already released
+        buf.drop_range(SeqNumber(7), SeqNumber(10));
+        assert_eq!(buf.next_release(), SeqNumber(11));
+        assert_eq!(buf.buffer.len(), 0);
+    }
+
+    #[test]
+    fn grow_latency() {
+        let start = Instant::now();
+        let mut buf = RecvBuffer::new(SeqNumber(5), start, Duration::from_millis(100), 10, 8192);
+        let at = |ms| start + Duration::from_millis(ms);
+
+        buf.set_latency(Duration::from_millis(200), at(1_000));
+        assert_eq!(buf.latency(at(1_000)), Duration::from_millis(100));
+        assert_eq!(buf.latency(at(1_500)), Duration::from_millis(150));
+        assert_eq!(buf.latency(at(2_000)), Duration::from_millis(200));
+        assert_eq!(buf.latency(at(5_000)), Duration::from_millis(200));
+
+        // from where it got to, not where it was going
+        buf.set_latency(Duration::from_millis(300), at(500));
+        assert_eq!(buf.latency(at(500)), Duration::from_millis(100));
+        buf.set_latency(Duration::from_millis(50), at(2_000));
+        assert_eq!(buf.latency(at(2_000)), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn grow_latency_releases_later() {
+        let start = Instant::now();
+        let mut buf = RecvBuffer::new(SeqNumber(5), start, Duration::from_millis(100), 10, 8192);
+        buf.add(DataPacket {
+            seq_number: SeqNumber(5),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            timestamp: TimeStamp::from_micros(1_000_000),
+            ..basic_pack()
+        });
+        let sent = buf
+            .remote_clock
+            .instant_from(start, TimeStamp::from_micros(1_000_000));
+
+        buf.set_latency(Duration::from_millis(200), sent);
+        // 100ms later it's only been grown by 10ms
+        assert_eq!(
+            buf.next_msg_ready_tsbpd(sent + Duration::from_millis(100)),
+            None
+        );
+        assert_eq!(
+            buf.next_msg_ready_tsbpd(sent + Duration::from_millis(112)),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn full() {
+        let mut buf = RecvBuffer::new(
+            SeqNumber(5),
+            Instant::now(),
+            Duration::from_millis(100),
+            10,
+            4,
+        );
+        let single = |seq| DataPacket {
+            seq_number: SeqNumber(seq),
+            message_loc: PacketLocation::FIRST | PacketLocation::LAST,
+            payload: Bytes::from(seq.to_string()),
+            ..basic_pack()
+        };
+
+        // no room past the capacity
+        assert!(!buf.has_room(SeqNumber(9)));
+        buf.add(single(9));
+        assert_eq!(buf.slots(), 0);
+        assert!(buf.has_room(SeqNumber(8)));
+        assert!(buf.has_room(SeqNumber(4)));
+        buf.add(single(8));
+        buf.add(single(5));
+        assert_eq!(buf.slots(), 4);
+        assert_eq!(buf.buffered_packets(), 2);
+
+        // releasing makes room, around the end of the storage
+        assert_eq!(buf.next_msg(Instant::now()).unwrap().1, "5");
+        buf.add(single(9));
+        buf.add(single(6));
+        buf.add(single(7));
+        for seq in 6..10 {
+            assert_eq!(buf.next_msg(Instant::now()).unwrap().1, seq.to_string());
+        }
+        assert_eq!(buf.slots(), 0);
+
+        // skipping past the packets stored
+        buf.add(single(11));
+        buf.drop_range(SeqNumber(10), SeqNumber(15));
+        assert_eq!(buf.slots(), 0);
+        buf.add(single(16));
+        assert_eq!(buf.next_msg(Instant::now()).unwrap().1, "16");
+    }
+}
@@ -1,7 +1,7 @@
 use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 
-use std::fmt;
+use core::fmt;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct SocketID(pub u32);
@@ -1,8 +1,9 @@
-use std::{cmp::Ordering, fmt};
+use core::{cmp::Ordering, fmt};
 
 /// Serialied, it looks like:
 /// major * 0x10000 + minor * 0x100 + patch
 #[derive(PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SrtVersion {
     pub major: u8,
     pub minor: u8,
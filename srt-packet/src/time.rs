@@ -0,0 +1,396 @@
+use core::cmp::Ordering;
+use core::num::Wrapping;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+use core::time::Duration;
+
+const TIMESTAMP_MASK: u128 = u32::MAX as u128;
+
+/// A point in time on the local clock, that timestamps are taken against
+///
+/// `std::time::Instant` is one, and without `std` so is any monotonic clock that can be moved by a `Duration`, the
+/// `Duration` since boot will do.
+pub trait TimePoint:
+    Copy + Ord + Add<Duration, Output = Self> + Sub<Duration, Output = Self> + Sub<Output = Duration>
+{
+}
+
+impl<T> TimePoint for T where
+    T: Copy + Ord + Add<Duration, Output = T> + Sub<Duration, Output = T> + Sub<Output = Duration>
+{
+}
+
+/// Timestamp in us after creation
+/// These wrap every 2^32 microseconds
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord)]
+pub struct TimeStamp(Wrapping<u32>);
+
+/// Signed duration in us, e.g. RTT
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeSpan(i32);
+
+impl TimeSpan {
+    pub fn from_micros(us: i32) -> Self {
+        Self(us)
+    }
+
+    pub fn as_micros(self) -> i32 {
+        self.0
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 / 1e6
+    }
+}
+
+impl TimeStamp {
+    pub fn from_micros(us: u32) -> Self {
+        Self(Wrapping(us))
+    }
+
+    pub fn as_micros(self) -> u32 {
+        (self.0).0
+    }
+
+    pub fn as_secs_f64(self) -> f64 {
+        (self.0).0 as f64 / 1e6
+    }
+
+    pub fn as_duration(self) -> Duration {
+        Duration::from_micros(u64::from(self.as_micros()))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TimeBase<T>(T);
+
+impl<T: TimePoint> TimeBase<T> {
+    pub fn new(start_time: T) -> Self {
+        Self(start_time)
+    }
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn timestamp_from(&self, instant: T) -> TimeStamp {
+        assert!(
+            self.0 <= instant,
+            "Timestamps are only valid after the timebase start time"
+        );
+        TimeStamp::from_micros(((instant - self.0).as_micros() & TIMESTAMP_MASK) as u32)
+    }
+
+    // Get Instant closest to `now` that is consistent with `timestamp`
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn instant_from(&self, now: T, timestamp: TimeStamp) -> T {
+        let wraps = ((now - self.0).as_micros() >> 32) as u64;
+        self.0 + Duration::from_micros(wraps * u64::from(u32::MAX) + timestamp.as_micros() as u64)
+    }
+
+    pub fn adjust(&mut self, delta: TimeSpan) {
+        let delta = delta.as_micros();
+        if delta > 0 {
+            self.0 = self.0 + Duration::from_micros(delta as u64);
+        } else {
+            self.0 = self.0 - Duration::from_micros(delta.abs() as u64);
+        }
+    }
+
+    pub fn origin_time(&self) -> T {
+        self.0
+    }
+}
+
+/// The mean and variance of the drift samples, in us
+#[derive(Default)]
+struct DriftStats {
+    len: usize,
+    mean: f64,
+    sum_squares: f64,
+}
+
+impl DriftStats {
+    fn add(&mut self, sample: i32) {
+        let sample = f64::from(sample);
+        self.len += 1;
+        let delta = sample - self.mean;
+        self.mean += delta / self.len as f64;
+        self.sum_squares += delta * (sample - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        self.sum_squares / self.len as f64
+    }
+}
+
+pub(crate) struct SynchronizedRemoteClock<T> {
+    tolerance: Duration,
+    time_base: TimeBase<T>,
+    stats: Option<DriftStats>,
+}
+
+impl<T: TimePoint> SynchronizedRemoteClock<T> {
+    const MAX_SAMPLES: usize = 1_000;
+    const DRIFT_TOLERANCE: Duration = Duration::from_millis(5);
+
+    pub fn new(now: T) -> Self {
+        Self {
+            // TODO: Drift deviation tolerance should be parameterized.
+            //       It wasn't in the reference implementation, but I added it because the reference
+            //       implementation is susceptible to invalid clock adjustments during periods of
+            //       acute network latency
+            tolerance: Self::DRIFT_TOLERANCE,
+            time_base: TimeBase::new(now),
+            stats: None,
+        }
+    }
+
+    pub fn synchronize(&mut self, now: T, ts: TimeStamp) {
+        let drift = self.time_base.timestamp_from(now) - ts;
+        match &mut self.stats {
+            None => {
+                self.time_base.adjust(drift);
+            }
+            Some(stats) => {
+                stats.add(drift.as_micros());
+
+                if stats.len < Self::MAX_SAMPLES {
+                    return;
+                }
+
+                // the standard deviation is within the tolerance, squared as there's no sqrt without std
+                let tolerance = self.tolerance.as_micros() as f64;
+                if stats.variance() < tolerance * tolerance {
+                    self.time_base
+                        .adjust(TimeSpan::from_micros(stats.mean as i32));
+                }
+            }
+        }
+        self.stats = Some(DriftStats::default());
+    }
+
+    pub fn instant_from(&self, now: T, ts: TimeStamp) -> T {
+        self.time_base.instant_from(now, ts)
+    }
+
+    pub fn origin_time(&self) -> T {
+        self.time_base.origin_time()
+    }
+}
+
+impl PartialOrd<TimeStamp> for TimeStamp {
+    fn partial_cmp(&self, other: &TimeStamp) -> Option<Ordering> {
+        // this is a "best effort" implementation, and goes for close
+        // if timestamps are very far apart, this will not work (and cannot)
+        Some((*self - *other).as_micros().cmp(&0))
+    }
+}
+
+impl Add<TimeSpan> for TimeStamp {
+    type Output = TimeStamp;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn add(self, rhs: TimeSpan) -> Self::Output {
+        TimeStamp(if rhs.0 > 0 {
+            self.0 + Wrapping(rhs.0 as u32)
+        } else {
+            self.0 - Wrapping(rhs.0.abs() as u32)
+        })
+    }
+}
+
+impl Sub<TimeSpan> for TimeStamp {
+    type Output = TimeStamp;
+
+    fn sub(self, rhs: TimeSpan) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Sub<TimeStamp> for TimeStamp {
+    type Output = TimeSpan;
+
+    fn sub(self, rhs: TimeStamp) -> TimeSpan {
+        // This is also a "best effort" implementation, and cannot be precise
+        let pos_sub = self.0 - rhs.0;
+        let neg_sub = rhs.0 - self.0;
+        if pos_sub < neg_sub {
+            TimeSpan(pos_sub.0 as i32)
+        } else {
+            -TimeSpan(neg_sub.0 as i32)
+        }
+    }
+}
+
+impl Neg for TimeSpan {
+    type Output = TimeSpan;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl Mul<i32> for TimeSpan {
+    type Output = TimeSpan;
+
+    fn mul(self, rhs: i32) -> Self::Output {
+        Self(self.0 * rhs)
+    }
+}
+
+impl Add<TimeSpan> for TimeSpan {
+    type Output = TimeSpan;
+
+    fn add(self, rhs: TimeSpan) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Div<i32> for TimeSpan {
+    type Output = TimeSpan;
+
+    fn div(self, rhs: i32) -> Self::Output {
+        Self(self.0 / rhs)
+    }
+}
+
+impl Sub<TimeSpan> for TimeSpan {
+    type Output = TimeSpan;
+
+    fn sub(self, rhs: TimeSpan) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod timestamp {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::eq_op)]
+    fn subtract_timestamp() {
+        let a = TimeStamp::from_micros(10);
+        let max = a - TimeSpan(11);
+        let b = TimeStamp::from_micros(11);
+
+        assert_eq!(a - a, TimeSpan::from_micros(0));
+        assert_eq!(b - a, TimeSpan::from_micros(1));
+        assert_eq!(a - b, TimeSpan::from_micros(-1));
+        assert!(max < a);
+        assert!(b > a);
+        assert!(b > max);
+        assert_eq!(max.as_micros(), u32::MAX);
+    }
+}
+
+#[cfg(test)]
+mod timebase {
+    use super::*;
+    use proptest::prelude::*;
+    use std::time::Instant;
+
+    proptest! {
+        #[test]
+        fn timestamp_roundtrip(expected_ts: u32) {
+            let timebase = TimeBase::new(Instant::now());
+            let expected_ts = TimeStamp::from_micros(expected_ts);
+
+            let ts = timebase.timestamp_from(timebase.instant_from(Instant::now(), expected_ts));
+            assert_eq!(ts, expected_ts);
+        }
+
+        #[test]
+        fn timestamp_from(expected_ts: u32, n in 0u64..10) {
+            let now = Instant::now();
+            let timebase = TimeBase::new(now);
+            let delta = ((std::u32::MAX as u64 + 1)* n) + expected_ts as u64;
+            let instant =  now + Duration::from_micros(delta as u64);
+            let ts = timebase.timestamp_from(instant);
+            assert_eq!(ts, TimeStamp::from_micros(expected_ts));
+        }
+
+        #[test]
+        fn adjust(drift: i16) {
+            let now = Instant::now();
+            let mut timebase = TimeBase::new(now);
+            let drift = TimeSpan::from_micros(i32::from(drift));
+
+            let original_ts = timebase.timestamp_from(now);
+            timebase.adjust(drift);
+            let ts = timebase.timestamp_from(now + Duration::from_micros(1_000_000));
+
+            assert_eq!(ts, original_ts - drift + TimeSpan::from_micros(1_000_000));
+        }
+
+    }
+
+    // a clock without std
+    #[test]
+    fn since_boot() {
+        let timebase = TimeBase::new(Duration::from_secs(10));
+        let ts = timebase.timestamp_from(Duration::from_secs(12));
+        assert_eq!(ts, TimeStamp::from_micros(2_000_000));
+        assert_eq!(
+            timebase.instant_from(Duration::from_secs(12), ts),
+            Duration::from_secs(12)
+        );
+    }
+}
+
+#[cfg(test)]
+mod synchronized_remote_clock {
+    use std::{
+        cmp::Ordering,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn synchronize(drift_ts in 1u64..5_000_000) {
+            const MAX_SAMPLES: i32 = 1000;
+
+            let drift = Duration::from_micros(drift_ts);
+            let start = Instant::now();
+            let start_ts = TimeStamp::from_micros(100_000_000);
+
+            let mut clock = SynchronizedRemoteClock::new(start);
+
+            clock.synchronize(start, start_ts);
+            let instant = clock.instant_from(start, start_ts);
+
+            prop_assert_eq!(instant, start, "the clock should be adjusted on the first sample");
+
+            for tick_ts in 1..1002 {
+                let tick = Duration::from_micros(tick_ts as u64);
+                let now = start + tick + drift;
+                let now_ts = start_ts + TimeSpan::from_micros(tick_ts);
+
+                clock.synchronize(now, now_ts);
+                let instant = clock.instant_from(start, now_ts);
+
+                match tick_ts.cmp(&MAX_SAMPLES) {
+                    Ordering::Less => assert_eq!(instant, start + tick, "the clock should not be adjusted until {} samples: tick_ts = {}", MAX_SAMPLES, tick_ts),
+                    Ordering::Equal => assert_eq!(instant, now, "the clock should be adjusted after {} samples", MAX_SAMPLES),
+                    Ordering::Greater => assert_eq!(instant, now, "the clock should not be adjusted until the next {} samples: tick_ts = {}", MAX_SAMPLES, tick_ts),
+                }
+            }
+
+            // simulate drift variance outside tolerance (+/- 5ms)
+            for tick_ts in 1002..2002 {
+                let tick = Duration::from_micros(tick_ts as u64);
+                let now = start + tick + drift;
+                let now_ts = start_ts + TimeSpan::from_micros(tick_ts);
+
+                clock.synchronize(now, now_ts - TimeSpan::from_micros((tick_ts % 2) * 11000)); // constant 5ms drift variance
+                let instant = clock.instant_from(start, now_ts);
+
+                prop_assert_eq!(instant, now, "the clock should not be adjusted: tick_ts = {}", tick_ts);
+            }
+        }
+    }
+}
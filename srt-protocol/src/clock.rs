@@ -0,0 +1,77 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where a connection gets the current time from, for its timestamps, TSBPD and timers
+///
+/// The state machines of the protocol are given the time by their callers, this is what the callers use.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock, [`Instant::now`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for driving the protocol by hand in tests and simulations
+///
+/// Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<Instant>>);
+
+impl MockClock {
+    pub fn new(start: Instant) -> Self {
+        MockClock(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+
+    /// # Panics
+    /// If `to` is before the current time, the clock is monotonic
+    pub fn set(&self, to: Instant) {
+        let mut now = self.0.lock().unwrap();
+        assert!(to >= *now, "MockClock can't go back in time");
+        *now = to;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mock_clock() {
+        let start = Instant::now();
+        let clock = MockClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(shared.now(), start + Duration::from_millis(10));
+
+        shared.set(start + Duration::from_secs(1));
+        assert_eq!(clock.now(), start + Duration::from_secs(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn mock_clock_monotonic() {
+        let start = Instant::now();
+        let clock = MockClock::new(start + Duration::from_secs(1));
+        clock.set(start);
+    }
+}
@@ -1,10 +1,24 @@
+//! The settings of a connection, and the callbacks an application hooks into it
+//!
+//! The callbacks are called from the task of the socket, unless their docs say otherwise, so they should not block.
+
 use std::{
+    fmt,
+    future::Future,
     net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
+use crate::packet::{
+    CipherType, ControlPacket, HandshakeExtension, Packet, RejectReason, SrtShakeFlags,
+};
 use crate::protocol::handshake::Handshake;
-use crate::{crypto::CryptoManager, SeqNumber, SocketID};
+use crate::{
+    crypto::CryptoManager, Clock, KmState, ProtocolVersion, SeqNumber, SocketID, SocketStatistics,
+    SrtVersion,
+};
 
 #[derive(Clone, Debug)]
 pub struct Connection {
@@ -26,6 +40,9 @@ pub struct ConnectionSettings {
     /// The time that this socket started at, used to develop timestamps
     pub socket_start_time: Instant,
 
+    /// Where the socket gets the current time from
+    pub clock: Arc<dyn Clock>,
+
     /// The first sequence number that will be sent/received
     pub init_send_seq_num: SeqNumber,
     pub init_recv_seq_num: SeqNumber,
@@ -42,9 +59,829 @@ pub struct ConnectionSettings {
 
     // if this stream is encrypted, it needs a crypto manager
     pub crypto_manager: Option<CryptoManager>,
+
+    /// The key material state of the data this side sends, and of the data it receives
+    pub send_km_state: KmState,
+    pub recv_km_state: KmState,
+
+    /// The transmission type of this side of the connection, selects TSBPD, packet drop, etc
+    pub transmission_type: TransmissionType,
+
+    /// How the sender answers loss reports
+    pub retransmit_algorithm: RetransmitAlgorithm,
+
+    /// The number of packets to handle in a row before yielding to other tasks
+    pub packet_budget: usize,
+
+    /// The largest message that will be reassembled by the receiver, in bytes
+    pub max_message_size: usize,
+
+    /// The SRT version of the peer, from its handshake
+    pub peer_version: SrtVersion,
+
+    /// The flags the peer advertised in its handshake
+    pub peer_flags: SrtShakeFlags,
+
+    /// Called with every control packet of an unknown or user defined type, see [`ControlTypes::Custom`](crate::packet::ControlTypes::Custom)
+    pub control_packet_handler: Option<ControlPacketHandler>,
+
+    /// Called with every packet sent or received, see [`PacketTap`]
+    pub packet_tap: Option<PacketTap>,
+
+    /// Called when the data buffered by the sender crosses a threshold, see [`SendBufferMonitor`]
+    pub send_buffer_monitor: Option<SendBufferMonitor>,
+
+    /// Called when data stops arriving and when it comes back, see [`DataIdleMonitor`]
+    pub data_idle_monitor: Option<DataIdleMonitor>,
+
+    /// Called when the connection's task stops making progress, see [`StallMonitor`]
+    pub stall_monitor: Option<StallMonitor>,
+
+    /// Called when the sender notices an MTU blackhole and clamps the payload size, see [`MtuBlackholeMonitor`]
+    pub mtu_blackhole_monitor: Option<MtuBlackholeMonitor>,
+
+    /// When the connection is declared broken, see [`BreakCriteria`]
+    pub break_criteria: BreakCriteria,
+
+    /// A cap on the rate data is sent at, see [`RateLimit`]
+    pub rate_limit: Option<RateLimit>,
+
+    /// Changes the rate limit while the connection runs, see [`RateLimitControl`]
+    pub rate_limit_control: RateLimitControl,
+
+    /// A cap on the share of what's sent that can be retransmissions, see [`RetransmitBudget`]
+    pub retransmit_budget: Option<RetransmitBudget>,
+
+    /// How often the receiver acknowledges, or `None` for 4 * RTT + RTTVar + SYN, following the RTT
+    pub ack_interval: Option<Duration>,
+
+    /// How often the receiver reports its losses again, or `None` for twice the ACK period following the RTT
+    pub nak_interval: Option<Duration>,
+
+    /// What the sender drops when it falls behind, see [`SendDropPolicy`]
+    pub send_drop_policy: SendDropPolicy,
+
+    /// What's done with packets for this connection from another address than the peer's, see [`SourceValidation`]
+    pub source_validation: SourceValidation,
+
+    /// How much the connection can transfer before it's closed, see [`TransferQuota`]
+    pub transfer_quota: TransferQuota,
+
+    /// How long closing waits for the peer to acknowledge what was sent, `None` to wait until it has. What's
+    /// still unacknowledged then is abandoned, and counted in the sender's metrics.
+    pub linger: Option<Duration>,
+
+    /// How long receiving a message waits before failing with [`TimedOut`](std::io::ErrorKind::TimedOut), `None` to
+    /// wait forever, like `SRTO_RCVTIMEO`
+    pub recv_timeout: Option<Duration>,
+
+    /// How long sending a message waits to be queued and acknowledged before failing with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut), `None` to wait forever, like `SRTO_SNDTIMEO`
+    pub send_timeout: Option<Duration>,
+
+    /// The handshake extensions the peer sent that this library doesn't parse, see [`HandshakeExtension`]
+    pub peer_extensions: Vec<HandshakeExtension>,
+
+    /// The application protocol agreed on with the peer, see
+    /// [`HandshakeExtension::app_protocols`](crate::packet::HandshakeExtension::app_protocols)
+    pub app_protocol: Option<String>,
+
+    /// How the handshake went, see [`ConnectStats`]
+    pub connect_stats: ConnectStats,
+}
+
+/// What was negotiated with the peer during the handshake, see [`ConnectionSettings::info`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionInfo {
+    /// The SRT version of the peer
+    pub peer_version: SrtVersion,
+
+    /// If packets sent are delivered based on their timestamp, this needs both sides to agree
+    pub send_tsbpd: bool,
+
+    /// If packets received are released based on their timestamp, this needs both sides to agree
+    pub recv_tsbpd: bool,
+
+    /// If this side drops packets that are too late to be delivered
+    pub too_late_packet_drop: bool,
+
+    /// If this side periodically re-reports packets that are still missing
+    pub periodic_nak: bool,
+
+    /// The cipher used to encrypt the data, `CipherType::None` if the connection isn't encrypted
+    pub cipher: CipherType,
+
+    /// The length of the encryption key in bytes, 0 if the connection isn't encrypted
+    pub key_length: u8,
+
+    /// If `key_length` is the peer's rather than the configured one, see [`KeyLengthPolicy`](crate::KeyLengthPolicy)
+    pub key_length_adopted: bool,
+
+    /// If the peer can decrypt the data sent, see [`KmState`]
+    pub send_km_state: KmState,
+
+    /// If the data received can be decrypted, see [`KmState`]
+    pub recv_km_state: KmState,
+
+    /// The stream id the peer sent, usually the caller, `None` if it didn't, see
+    /// [`HandshakeExtension::stream_id`]
+    pub stream_id: Option<String>,
+
+    /// The application metadata the peer sent, `None` if it didn't, see [`HandshakeExtension::metadata`]
+    pub peer_metadata: Option<Vec<u8>>,
+
+    /// The application protocol agreed on, `None` if either side didn't have any, see
+    /// [`HandshakeExtension::app_protocols`]
+    pub app_protocol: Option<String>,
+
+    /// The latency packets are sent with
+    pub send_latency: Duration,
+
+    /// The latency packets are received with
+    pub recv_latency: Duration,
+}
+
+/// How the connection was established, see [`ConnectionSettings::connect_stats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectStats {
+    /// How many times the induction handshake, or the wave-a-hand in rendezvous, was sent again as it was, because
+    /// the peer didn't answer or sent its own again
+    pub induction_retries: u32,
+
+    /// How many times the conclusion or agreement handshake was sent again as it was
+    pub conclusion_retries: u32,
+
+    /// How long it took to connect, from the first handshake sent or received
+    pub connect_time: Duration,
+
+    /// In rendezvous, if this side's cookie won the contest, making it the initiator of the handshake extensions
+    pub won_cookie_contest: Option<bool>,
+
+    /// The types of the extension blocks in the peer's conclusion handshake, in order, such as 1 for the handshake
+    /// request and 3 for the key material, see [`SrtControlPacket::type_id`](crate::packet::SrtControlPacket::type_id)
+    pub peer_extension_types: Vec<u16>,
+
+    /// If the keys are of the peer's length rather than the configured one, see
+    /// [`KeyLengthPolicy`](crate::KeyLengthPolicy)
+    pub key_length_adopted: bool,
+}
+
+/// Where a connection is in establishing itself, see [`ConnectProgress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectPhase {
+    /// Looking up the address of the peer
+    Resolving,
+
+    /// The induction handshake, or the wave-a-hand in rendezvous, was sent, waiting for the peer to answer it
+    InductionSent,
+
+    /// The conclusion or agreement handshake was sent, waiting for the peer to accept it
+    ConclusionSent,
+
+    /// The listener answered the conclusion of an encrypted connection, checking its key material against the
+    /// passphrase, for callers
+    KeyExchange,
+
+    /// The handshake is over, and the connection is ready
+    Connected,
+}
+
+/// A callback an application hooks into a connection, `F` being its signature, such as [`PacketTap`]
+///
+/// Clones share the callback, so settings holding one can be cloned for every connection.
+pub struct Callback<F: ?Sized>(Arc<F>);
+
+impl<F: ?Sized> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        Callback(self.0.clone())
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Callback")
+    }
+}
+
+/// A callback for when a connection moves on to another [`ConnectPhase`], to show how connecting is going, or which
+/// phase it got stuck in
+///
+/// Each phase is reported once, when entered. Callers and rendezvous report them all as they go, listeners only
+/// report being connected, as their handshakes are answers to the caller's.
+pub type ConnectProgress = Callback<dyn Fn(ConnectPhase) + Send + Sync>;
+
+impl ConnectProgress {
+    pub fn new(f: impl Fn(ConnectPhase) + Send + Sync + 'static) -> Self {
+        Callback(Arc::new(f))
+    }
+
+    pub fn call(&self, phase: ConnectPhase) {
+        (self.0)(phase)
+    }
+}
+
+/// A callback for control packets this library doesn't know how to handle
+pub type ControlPacketHandler = Callback<dyn Fn(&ControlPacket) + Send + Sync>;
+
+impl ControlPacketHandler {
+    pub fn new(f: impl Fn(&ControlPacket) + Send + Sync + 'static) -> Self {
+        Callback(Arc::new(f))
+    }
+
+    pub fn call(&self, packet: &ControlPacket) {
+        (self.0)(packet)
+    }
+}
+
+/// Decides if a listener accepts a connection, once the handshake is otherwise complete, see
+/// [`ConnInitSettings::authenticator`](crate::pending_connection::ConnInitSettings::authenticator)
+///
+/// It's given the settings of the connection, with the peer's address, stream id and extensions, and may call out to
+/// another service, such as to validate a token in the stream id. The connection is rejected with the returned reason
+/// on failure, and with [`RejectReason::Timeout`] if it doesn't decide in time.
+pub type Authenticator = Callback<dyn Fn(&ConnectionSettings) -> AuthFuture + Send + Sync>;
+
+type AuthFuture = Pin<Box<dyn Future<Output = Result<(), RejectReason>> + Send>>;
+
+impl Authenticator {
+    pub fn new<F>(f: impl Fn(&ConnectionSettings) -> F + Send + Sync + 'static) -> Self
+    where
+        F: Future<Output = Result<(), RejectReason>> + Send + 'static,
+    {
+        Callback(Arc::new(move |settings| Box::pin(f(settings))))
+    }
+
+    pub fn call(&self, settings: &ConnectionSettings) -> AuthFuture {
+        (self.0)(settings)
+    }
+}
+
+/// If a tapped packet was received or sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    Ingress,
+    Egress,
+}
+
+/// A callback seeing every packet of a connection, with when it was received or sent, and the address of the peer
+pub type PacketTap = Callback<dyn Fn(Instant, PacketDirection, &Packet, SocketAddr) + Send + Sync>;
+
+impl PacketTap {
+    pub fn new(
+        f: impl Fn(Instant, PacketDirection, &Packet, SocketAddr) + Send + Sync + 'static,
+    ) -> Self {
+        Callback(Arc::new(f))
+    }
+
+    pub fn call(
+        &self,
+        time: Instant,
+        direction: PacketDirection,
+        packet: &Packet,
+        peer: SocketAddr,
+    ) {
+        (self.0)(time, direction, packet, peer)
+    }
+}
+
+/// How much data the sender has buffered, passed to a [`SendBufferMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendBufferLevel {
+    /// How many of the monitor's thresholds `timespan` has reached, 0 if none
+    pub level: usize,
+
+    /// The time between the oldest and the newest packet buffered, see
+    /// [`SenderMetrics::buffered_timespan`](crate::protocol::sender::SenderMetrics::buffered_timespan)
+    pub timespan: Duration,
+
+    /// The packets buffered, those waiting to be sent and those waiting to be acknowledged
+    pub packets: u32,
+}
+
+/// A callback for when the time span of the data buffered by the sender crosses one of a set of thresholds, in either
+/// direction
+///
+/// A sender fed from a real-time source can use this to notice that the network isn't keeping up, before packets get
+/// too late to be delivered: once the time span reaches the latency, they are.
+#[derive(Debug, Clone)]
+pub struct SendBufferMonitor {
+    thresholds: Vec<Duration>,
+    callback: Callback<dyn Fn(SendBufferLevel) + Send + Sync>,
+}
+
+impl SendBufferMonitor {
+    pub fn new(
+        thresholds: impl IntoIterator<Item = Duration>,
+        f: impl Fn(SendBufferLevel) + Send + Sync + 'static,
+    ) -> Self {
+        let mut thresholds: Vec<_> = thresholds.into_iter().collect();
+        thresholds.sort();
+        SendBufferMonitor {
+            thresholds,
+            callback: Callback(Arc::new(f)),
+        }
+    }
+
+    /// How many thresholds `timespan` has reached
+    pub fn level(&self, timespan: Duration) -> usize {
+        self.thresholds
+            .iter()
+            .take_while(|t| **t <= timespan)
+            .count()
+    }
+
+    pub fn call(&self, level: SendBufferLevel) {
+        (self.callback.0)(level)
+    }
+}
+
+/// A change in whether data is arriving, passed to a [`DataIdleMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataIdleEvent {
+    /// No data arrived for `idle_for`, the monitor's timeout
+    Idle { idle_for: Duration },
+
+    /// Data arrived again, after `gap` without any
+    Resumed { gap: Duration },
+}
+
+/// A callback for when no data arrives for a while on a connection that is still up, and for when it comes back
+///
+/// A player can use this to show that the signal is lost as soon as the source stops, instead of waiting for the
+/// connection to be declared broken, which takes seconds and doesn't happen at all while the peer still sends
+/// keepalives.
+#[derive(Debug, Clone)]
+pub struct DataIdleMonitor {
+    timeout: Duration,
+    callback: Callback<dyn Fn(DataIdleEvent) + Send + Sync>,
+}
+
+impl DataIdleMonitor {
+    pub fn new(timeout: Duration, f: impl Fn(DataIdleEvent) + Send + Sync + 'static) -> Self {
+        DataIdleMonitor {
+            timeout,
+            callback: Callback(Arc::new(f)),
+        }
+    }
+
+    /// How long no data has to arrive for the stream to be idle
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn call(&self, event: DataIdleEvent) {
+        (self.callback.0)(event)
+    }
+}
+
+/// An MTU blackhole noticed by the sender, passed to an [`MtuBlackholeMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtuBlackholeEvent {
+    /// The payload size packets are clamped to from now on, the largest that got through
+    pub payload_size: usize,
+
+    /// The payload size packets were sent with before
+    pub previous_payload_size: usize,
+}
+
+/// A callback for when the sender notices an MTU blackhole: packets over some size being lost every time, while
+/// smaller ones get through, as happens on GRE and VPN paths that drop what doesn't fit instead of fragmenting it or
+/// reporting it
+///
+/// The sender then clamps the payload of new packets to the largest that got through, which is also in
+/// [`SenderMetrics::max_payload_size`](crate::protocol::sender::SenderMetrics::max_payload_size). Packets already
+/// split at the larger size are still lost.
+pub type MtuBlackholeMonitor = Callback<dyn Fn(MtuBlackholeEvent) + Send + Sync>;
+
+impl MtuBlackholeMonitor {
+    pub fn new(f: impl Fn(MtuBlackholeEvent) + Send + Sync + 'static) -> Self {
+        Callback(Arc::new(f))
+    }
+
+    pub fn call(&self, event: MtuBlackholeEvent) {
+        (self.0)(event)
+    }
+}
+
+/// What the task of a connection was doing last, see [`StallEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStage {
+    /// Waiting for a packet, data to send or a timer. Timers fire at least every few milliseconds, so a task stalled
+    /// here isn't being polled, such as when the runtime is starved by blocking code.
+    Waiting,
+    /// Handling what woke it up
+    Handling,
+    /// Sending packets on the socket, which isn't taking them
+    Sending,
+    /// Handing received data to the application, which isn't reading it
+    Releasing,
+}
+
+/// A connection's task not making progress, passed to a [`StallMonitor`]
+#[derive(Debug, Clone, Copy)]
+pub struct StallEvent {
+    /// How long since the task last made progress, at least the monitor's timeout
+    pub stalled_for: Duration,
+
+    /// What it was doing then
+    pub stage: TaskStage,
+
+    /// The statistics of the connection as of then
+    pub statistics: SocketStatistics,
+}
+
+/// A watchdog for a connection's task not making progress, to triage connections that freeze without an error
+///
+/// The callback is called once per stall, from a thread of its own, so it's called even when the runtime the task
+/// runs on is starved.
+#[derive(Debug, Clone)]
+pub struct StallMonitor {
+    timeout: Duration,
+    callback: Callback<dyn Fn(StallEvent) + Send + Sync>,
+}
+
+impl StallMonitor {
+    pub fn new(timeout: Duration, f: impl Fn(StallEvent) + Send + Sync + 'static) -> Self {
+        StallMonitor {
+            timeout,
+            callback: Callback(Arc::new(f)),
+        }
+    }
+
+    /// How long the task has to make no progress to be stalled
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn call(&self, event: StallEvent) {
+        (self.callback.0)(event)
+    }
+}
+
+/// How the sender answers loss reports, the equivalent of `SRTO_RETRANSMITALGO` in the reference implementation
+///
+/// * `Aggressive` - every packet in every loss report is retransmitted, including the periodic re-reports of the
+///   receiver, for the lowest latency
+/// * `Reduced` - a packet is only retransmitted again once the last retransmission should have arrived, an RTT later,
+///   so only packets that were really lost again are sent again. This saves a lot of bandwidth on high RTT links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RetransmitAlgorithm {
+    Aggressive,
+    Reduced,
+}
+
+/// When a connection is declared broken and closed
+///
+/// The defaults are those of the reference implementation, which suit most links. Links with long outages, such as
+/// satellite links, may need more patience.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BreakCriteria {
+    /// How many times in a row the expiration timer can fire without hearing from the peer, twice a second
+    pub max_exp_count: u32,
+
+    /// How long the peer can stay silent, the equivalent of `SRTO_PEERIDLETIMEO`. The connection is only broken once
+    /// both this and `max_exp_count` are reached
+    pub peer_idle_timeout: Duration,
+
+    /// How many loss reports in a row the peer can send without the acknowledged sequence number moving forward,
+    /// `None` to never break because of loss reports
+    pub max_nak_storm: Option<u32>,
+}
+
+impl Default for BreakCriteria {
+    fn default() -> Self {
+        BreakCriteria {
+            max_exp_count: 16,
+            peer_idle_timeout: Duration::from_secs(5),
+            max_nak_storm: None,
+        }
+    }
+}
+
+impl BreakCriteria {
+    pub fn with_max_exp_count(mut self, max_exp_count: u32) -> Self {
+        self.max_exp_count = max_exp_count;
+
+        self
+    }
+
+    pub fn with_peer_idle_timeout(mut self, peer_idle_timeout: Duration) -> Self {
+        self.peer_idle_timeout = peer_idle_timeout;
+
+        self
+    }
+
+    pub fn with_max_nak_storm(mut self, max_nak_storm: Option<u32>) -> Self {
+        self.max_nak_storm = max_nak_storm;
+
+        self
+    }
+}
+
+/// A hard cap on the rate data is sent at, whatever congestion control would allow
+///
+/// It is enforced with a token bucket, so up to `burst` bytes can be sent at once after being idle. Retransmissions
+/// count, as do the packet headers, control packets don't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RateLimit {
+    pub bytes_per_second: u64,
+    pub burst: u64,
+}
+
+impl RateLimit {
+    /// A limit with a burst of 10ms worth of data, but at least two full packets
+    pub fn new(bytes_per_second: u64) -> Self {
+        // a packet every 16 is sent right after the previous one, see the sender algorithm
+        const TWO_PACKETS: u64 = 2 * 1500;
+        RateLimit {
+            bytes_per_second,
+            burst: (bytes_per_second / 100).max(TWO_PACKETS),
+        }
+    }
+}
+
+/// Changes the [`RateLimit`] of a running connection, see [`ConnectionSettings::rate_limit_control`]
+///
+/// Clones change the same connection. The sender picks the new limit up the next time it wakes up to send, with a
+/// full burst.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitControl(Arc<Mutex<Option<Option<RateLimit>>>>);
+
+impl RateLimitControl {
+    /// Cap the rate data is sent at to `limit`, or lift the cap with `None`
+    pub fn set(&self, limit: Option<RateLimit>) {
+        *self.0.lock().unwrap() = Some(limit);
+    }
+
+    /// The limit set since the last call, if any
+    pub fn take(&self) -> Option<Option<RateLimit>> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// A cap on the share of what the sender sends that can be retransmissions, so a link that can't carry the rate asked
+/// of it isn't swamped by retransmissions of packets that will be too late anyway
+///
+/// The budget of an interval is `max_share` of the payload bytes sent in it, or in the interval before if that's more,
+/// so retransmissions can go out ahead of the new data. While there is new data waiting, retransmissions over the
+/// budget wait, in live mode until the receiver drops the packets as too late and moves on. The intervals they were
+/// held back in are counted in the sender's metrics, as `retrans_budget_hits`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetransmitBudget {
+    /// The most of the bytes sent in an interval that can be retransmissions, above 0 and at most 1
+    pub max_share: f64,
+
+    /// How long the intervals are
+    pub interval: Duration,
+}
+
+impl RetransmitBudget {
+    /// A budget of `max_share` of every second
+    pub fn new(max_share: f64) -> Self {
+        RetransmitBudget {
+            max_share,
+            interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// How important a message is, for which to drop first when the sender falls behind in live mode
+///
+/// A message still waiting to be sent is dropped once it has waited longer than its priority allows, so the messages
+/// behind it can make it in time: half the send latency for `Low`, the whole latency for `Normal`, after which it would
+/// be too late anyway. `High` messages are never dropped by the sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+}
+
+impl Priority {
+    /// How long a message can wait to be sent, `None` if it can wait forever
+    pub fn max_wait(self, latency: Duration) -> Option<Duration> {
+        match self {
+            Priority::Low => Some(latency / 2),
+            Priority::Normal => Some(latency),
+            Priority::High => None,
+        }
+    }
+}
+
+/// What the sender does when the application gives it messages faster than it can send them in live mode
+///
+/// The sender is behind once the oldest message waiting to be sent has waited longer than the send latency.
+///
+/// * `Block` - nothing is dropped, new messages aren't taken until the sender catches up, so the application waits
+/// * `DropNewest` - new messages are dropped while the sender is behind, what was already queued is sent
+/// * `DropOldest` - any message that waited longer than the send latency is dropped, whatever its priority
+/// * `DropByPriority` - messages are dropped once they waited longer than their [`Priority`] allows
+///
+/// What was dropped is counted in the sender's metrics. In file mode nothing is ever dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SendDropPolicy {
+    Block,
+    DropNewest,
+    DropOldest,
+    DropByPriority,
+}
+
+impl Default for SendDropPolicy {
+    fn default() -> Self {
+        SendDropPolicy::DropByPriority
+    }
+}
+
+/// Limits on what a connection transfers, after which it's closed, such as for trial tiers of an ingest service
+///
+/// The bytes are those of the payloads of the data packets sent and received, retransmissions included, so they're
+/// what went over the network rather than what the application saw. `None` is no limit, which is the default for
+/// all of them.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransferQuota {
+    pub send_bytes: Option<u64>,
+    pub send_packets: Option<u64>,
+    pub recv_bytes: Option<u64>,
+    pub recv_packets: Option<u64>,
+
+    /// How long the connection can last, from when it was established
+    pub duration: Option<Duration>,
+}
+
+impl TransferQuota {
+    pub fn with_send_bytes(mut self, send_bytes: u64) -> Self {
+        self.send_bytes = Some(send_bytes);
+
+        self
+    }
+
+    pub fn with_send_packets(mut self, send_packets: u64) -> Self {
+        self.send_packets = Some(send_packets);
+
+        self
+    }
+
+    pub fn with_recv_bytes(mut self, recv_bytes: u64) -> Self {
+        self.recv_bytes = Some(recv_bytes);
+
+        self
+    }
+
+    pub fn with_recv_packets(mut self, recv_packets: u64) -> Self {
+        self.recv_packets = Some(recv_packets);
+
+        self
+    }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+
+        self
+    }
+}
+
+/// What a connection does with packets that carry its socket ID but come from another address than the peer's
+///
+/// * `Lenient` - the sender and receiver ignore them, but they still count as hearing from the peer, and custom
+///   control packets among them still reach the [`ControlPacketHandler`]
+/// * `Strict` - they are dropped before anything sees them, and counted in the receiver's metrics, so a spoofed packet
+///   can't keep the connection alive or shut it down. For listeners facing the internet.
+/// * `Migrate` - the connection moves to the new address, for peers whose address changes, such as mobile ones whose
+///   NAT mapping is rebound. Anyone who can guess the socket ID can take the connection over, so this is only for
+///   trusted networks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SourceValidation {
+    Lenient,
+    Strict,
+    Migrate,
+}
+
+impl Default for SourceValidation {
+    fn default() -> Self {
+        SourceValidation::Lenient
+    }
+}
+
+/// Selects a bundle of options at once, the equivalent of `SRTO_TRANSTYPE` in the reference implementation
+///
+/// * `Live` - timestamp based delivery, too late packets are dropped, and lost packets are periodically re-reported
+/// * `File` - every packet is delivered in order as soon as possible, nothing is dropped, and the flow window limits the sender
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TransmissionType {
+    Live,
+    File,
+}
+
+impl TransmissionType {
+    /// If packets should be released based on their timestamp + latency (TSBPD)
+    pub fn tsbpd(self) -> bool {
+        self == TransmissionType::Live
+    }
+
+    /// If packets that are too late to be delivered should be dropped (TLPKTDROP)
+    pub fn too_late_packet_drop(self) -> bool {
+        self == TransmissionType::Live
+    }
+
+    /// If the receiver should periodically resend NAKs for packets still missing (NAKREPORT)
+    pub fn nak_report(self) -> bool {
+        self == TransmissionType::Live
+    }
+
+    /// The default latency, used for both sending and receiving
+    pub fn default_latency(self) -> Duration {
+        match self {
+            TransmissionType::Live => Duration::from_millis(50),
+            TransmissionType::File => Duration::from_millis(0),
+        }
+    }
+
+    /// The default flow window, in packets
+    pub fn default_flow_size(self) -> u32 {
+        match self {
+            TransmissionType::Live => 8192,
+            TransmissionType::File => 25600,
+        }
+    }
+
+    /// The window the sender's congestion control uses, `None` means the congestion control default
+    pub fn congestion_window(self, max_flow_size: u32) -> Option<usize> {
+        match self {
+            TransmissionType::Live => None,
+            TransmissionType::File => Some(max_flow_size as usize),
+        }
+    }
+
+    /// The flags to advertise in the SRT handshake
+    pub fn shake_flags(self) -> SrtShakeFlags {
+        match self {
+            TransmissionType::Live => SrtShakeFlags::SUPPORTED,
+            TransmissionType::File => {
+                SrtShakeFlags::SUPPORTED - (SrtShakeFlags::TSBPDSND | SrtShakeFlags::TSBPDRCV)
+            }
+        }
+    }
 }
 
 impl ConnectionSettings {
+    /// The version of the protocol spoken with the peer, see [`ProtocolVersion`]
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        ProtocolVersion::with_peer(self.peer_version)
+    }
+
+    /// What was negotiated with the peer, see [`ConnectionInfo`]
+    pub fn info(&self) -> ConnectionInfo {
+        let flags = self.transmission_type.shake_flags();
+        ConnectionInfo {
+            peer_version: self.peer_version,
+            send_tsbpd: flags.contains(SrtShakeFlags::TSBPDSND)
+                && self.peer_flags.contains(SrtShakeFlags::TSBPDRCV),
+            recv_tsbpd: flags.contains(SrtShakeFlags::TSBPDRCV)
+                && self.peer_flags.contains(SrtShakeFlags::TSBPDSND),
+            too_late_packet_drop: self.transmission_type.too_late_packet_drop(),
+            periodic_nak: self.transmission_type.nak_report(),
+            // HaiCrypt is always AES-CTR
+            cipher: if self.crypto_manager.is_some() {
+                CipherType::CTR
+            } else {
+                CipherType::None
+            },
+            key_length: self
+                .crypto_manager
+                .as_ref()
+                .map(|cm| cm.key_length())
+                .unwrap_or(0),
+            key_length_adopted: self.connect_stats.key_length_adopted,
+            send_km_state: self.send_km_state,
+            recv_km_state: self.recv_km_state,
+            stream_id: self
+                .peer_extensions
+                .iter()
+                .find_map(HandshakeExtension::as_stream_id),
+            peer_metadata: self
+                .peer_extensions
+                .iter()
+                .find_map(HandshakeExtension::as_metadata)
+                .map(|data| data.to_vec()),
+            app_protocol: self.app_protocol.clone(),
+            send_latency: self.send_tsbpd_latency,
+            recv_latency: self.recv_tsbpd_latency,
+        }
+    }
+
     /// Timestamp in us
     pub fn get_timestamp(&self, at: Instant) -> i32 {
         let elapsed = at - self.socket_start_time;
@@ -54,6 +891,6 @@ impl ConnectionSettings {
 
     /// Timestamp in us
     pub fn get_timestamp_now(&self) -> i32 {
-        self.get_timestamp(Instant::now())
+        self.get_timestamp(self.clock.now())
     }
 }
@@ -19,10 +19,95 @@ use std::{convert::TryInto, fmt};
 
 mod wrap;
 
+/// The state of the key material exchange in one direction, the `SRT_KM_STATE` of the reference implementation
+///
+/// The exchange is part of the handshake, so `Securing` is never seen on a connected socket.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KmState {
+    /// Not encrypted
+    Unsecured,
+    /// The exchange is in progress
+    Securing,
+    /// Encrypted, and both sides have the keys
+    Secured,
+    /// Encrypted, but one side has no password, so it can't decrypt the data
+    NoSecret,
+    /// Encrypted, but the passwords don't match, so the data can't be decrypted
+    BadSecret,
+    /// Encrypted, but the sides don't agree on how, such as on the key length, so the data can't be decrypted
+    BadCryptoMode,
+}
+
+/// What a responder does when the initiator's keys are of another length than the one it was configured with
+///
+/// The initiator, the caller or the side that won the rendezvous cookie contest, makes the keys, so its length is the
+/// one used if the connection is made. The outcome is in [`ConnectionInfo`](crate::ConnectionInfo).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyLengthPolicy {
+    /// Use the initiator's length, as the reference implementation does
+    Adopt,
+    /// Use the initiator's length if it's at least the configured one, refusing shorter keys
+    NoDowngrade,
+    /// Refuse any other length
+    Exact,
+}
+
+impl KeyLengthPolicy {
+    /// If keys of `offered` bytes can be used when configured for `configured`
+    pub fn accepts(self, configured: u8, offered: u8) -> bool {
+        match self {
+            KeyLengthPolicy::Adopt => true,
+            KeyLengthPolicy::NoDowngrade => offered >= configured,
+            KeyLengthPolicy::Exact => offered == configured,
+        }
+    }
+}
+
+impl Default for KeyLengthPolicy {
+    fn default() -> Self {
+        KeyLengthPolicy::Adopt
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CryptoOptions {
+    /// The length of the stream encrypting keys, 16, 24 or 32 bytes
     pub size: u8,
-    pub passphrase: String,
+
+    /// What the keys are wrapped with when exchanged, see [`Secret`]
+    pub secret: Secret,
+
+    /// The stream encrypting key, of `size` bytes, to encrypt with when this side makes the keys, as the initiator,
+    /// instead of a random one. The responder always uses the initiator's.
+    pub sek: Option<Vec<u8>>,
+}
+
+impl CryptoOptions {
+    /// Keys of `size` bytes, wrapped with a key derived from `passphrase`
+    pub fn passphrase(size: u8, passphrase: impl Into<String>) -> Self {
+        CryptoOptions {
+            size,
+            secret: Secret::Passphrase(passphrase.into()),
+            sek: None,
+        }
+    }
+}
+
+/// The secret the key encrypting key (KEK), which wraps the stream encrypting keys (SEK) in the key material
+/// exchange, comes from. Either way, what's exchanged is the same, so both sides need the same KEK.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Secret {
+    /// A passphrase the KEK is derived from with PBKDF2 and the salt of the connection, as the reference
+    /// implementation does
+    Passphrase(String),
+
+    /// The KEK itself, 16, 24 or 32 bytes, for keys managed elsewhere such as in a KMS or HSM. A peer with a
+    /// passphrase can't derive it.
+    Kek(Vec<u8>),
 }
 
 // i would love for this to be not clone, maybe someday
@@ -42,8 +127,11 @@ impl CryptoManager {
         let mut salt = [0; 16];
         OsRng.fill_bytes(&mut salt[..]);
 
-        let mut even_key = vec![0; usize::from(options.size)];
-        OsRng.fill_bytes(&mut even_key[..]);
+        let even_key = options.sek.clone().unwrap_or_else(|| {
+            let mut key = vec![0; usize::from(options.size)];
+            OsRng.fill_bytes(&mut key[..]);
+            key
+        });
 
         // let mut odd_key = vec![0; usize::from(options.size)];
         // rand_bytes(&mut odd_key[..]).unwrap();
@@ -55,14 +143,16 @@ impl CryptoManager {
         options: CryptoOptions,
         kmreq: &SrtKeyMessage,
     ) -> Result<Self, ConnectError> {
+        if kmreq.key_length() != usize::from(options.size) {
+            return Err(ConnectError::KeyLengthMismatch(
+                options.size,
+                kmreq.key_length() as u8,
+            ));
+        }
+
         let salt = kmreq.salt[..].try_into().unwrap();
         let kek = CryptoManager::gen_kek(&options, &salt);
 
-        assert_eq!(
-            kmreq.wrapped_keys.len(),
-            kmreq.key_flags.bits().count_ones() as usize * usize::from(options.size) + 8
-        );
-
         let mut keys = vec![0; kmreq.wrapped_keys.len() - 8];
 
         let mut iv = [0; 8];
@@ -124,6 +214,11 @@ impl CryptoManager {
     }
 
     fn gen_kek(options: &CryptoOptions, salt: &[u8; 16]) -> Vec<u8> {
+        let passphrase = match &options.secret {
+            Secret::Passphrase(passphrase) => passphrase,
+            Secret::Kek(kek) => return kek.clone(),
+        };
+
         // Generate the key encrypting key from the passphrase, caching it in the struct
         // https://github.com/Haivision/srt/blob/2ef4ef003c2006df1458de6d47fbe3d2338edf69/haicrypt/hcrypt_sa.c#L69-L103
 
@@ -135,7 +230,7 @@ impl CryptoManager {
         let mut kek = vec![0; usize::from(options.size)];
 
         pbkdf2::<Hmac<Sha1>>(
-            options.passphrase.as_bytes(),
+            passphrase.as_bytes(),
             &salt[salt.len() - salt_len..], // last salt_len bytes
             2048, // is what the reference implementation uses.https://github.com/Haivision/srt/blob/2ef4ef003c2006df1458de6d47fbe3d2338edf69/haicrypt/haicrypt.h#L73
             &mut kek[..],
@@ -290,15 +385,8 @@ mod test {
         let mut salt = [0; 16];
         salt.copy_from_slice(&hex::decode("7D59759C2B1A3F0B06C7028790C81C7D").unwrap());
 
-        let manager = CryptoManager::new(
-            CryptoOptions {
-                size: 16,
-                passphrase: password.into(),
-            },
-            &salt,
-            None,
-            None,
-        );
+        let manager =
+            CryptoManager::new(CryptoOptions::passphrase(16, password), &salt, None, None);
 
         assert_eq!(manager.kek, &kek[..]);
     }
@@ -306,10 +394,7 @@ mod test {
     #[test]
     fn wrap_key() {
         let manager = CryptoManager::new(
-            CryptoOptions {
-                size: 16,
-                passphrase: "password123".into(),
-            },
+            CryptoOptions::passphrase(16, "password123"),
             &b"\x00\x00\x00\x00\x00\x00\x00\x00\x85\x2c\x3c\xcd\x02\x65\x1a\x22",
             None,
             Some(b"\r\xab\xc8n/2\xb4\xa7\xb9\xbb\xa2\xf31*\xe4\"".to_vec()),
@@ -348,10 +433,7 @@ mod test {
         let wrapped =
             b"31ea\x11\xe8\xb0P\xfe\x99\x9f\xd5h\xc2b\xfb\x1a3\xcc\xc8\x9cNw\xca"[..].into();
         let res = CryptoManager::new_from_kmreq(
-            CryptoOptions {
-                size: 16,
-                passphrase: "badpassword".into(),
-            },
+            CryptoOptions::passphrase(16, "badpassword"),
             &SrtKeyMessage {
                 pt: PacketType::KeyingMaterial,
                 key_flags: KeyFlags::ODD,
@@ -368,12 +450,66 @@ mod test {
     }
 
     #[test]
-    fn wrap_key2() {
-        let manager = CryptoManager::new(
+    fn key_length_mismatch() {
+        let manager = CryptoManager::new_random(CryptoOptions::passphrase(32, "password123"));
+        let res = CryptoManager::new_from_kmreq(
+            CryptoOptions::passphrase(16, "password123"),
+            &manager.generate_km(),
+        );
+        assert!(matches!(res, Err(ConnectError::KeyLengthMismatch(16, 32))));
+    }
+
+    #[test]
+    fn pre_shared_key() {
+        let kek = vec![7; 24];
+        let sek = vec![9; 24];
+        let initiator = CryptoManager::new_random(CryptoOptions {
+            size: 24,
+            secret: Secret::Kek(kek.clone()),
+            sek: Some(sek.clone()),
+        });
+        assert_eq!(initiator.kek, kek);
+        let km = initiator.generate_km();
+        assert_eq!(km.key_length(), 24);
+
+        let responder = CryptoManager::new_from_kmreq(
+            CryptoOptions {
+                size: 24,
+                secret: Secret::Kek(kek),
+                sek: None,
+            },
+            &km,
+        )
+        .unwrap();
+        assert_eq!(responder.even_sek, Some(sek));
+
+        let res = CryptoManager::new_from_kmreq(
             CryptoOptions {
-                size: 16,
-                passphrase: "password123".into(),
+                size: 24,
+                secret: Secret::Kek(vec![8; 24]),
+                sek: None,
             },
+            &km,
+        );
+        assert!(matches!(res, Err(ConnectError::BadSecret)));
+        let res = CryptoManager::new_from_kmreq(CryptoOptions::passphrase(24, "password123"), &km);
+        assert!(matches!(res, Err(ConnectError::BadSecret)));
+    }
+
+    #[test]
+    fn key_length_policy() {
+        use KeyLengthPolicy::*;
+        assert!(Adopt.accepts(32, 16));
+        assert!(NoDowngrade.accepts(16, 32));
+        assert!(!NoDowngrade.accepts(32, 24));
+        assert!(Exact.accepts(24, 24));
+        assert!(!Exact.accepts(16, 32));
+    }
+
+    #[test]
+    fn wrap_key2() {
+        let manager = CryptoManager::new(
+            CryptoOptions::passphrase(16, "password123"),
             &b"\x00\x00\x00\x00\x00\x00\x00\x00n\xd5+\x196\nq8",
             None,
             None,
@@ -404,10 +540,7 @@ mod test {
     fn gen_iv() {
         // example from the reference implementation
         let manager = CryptoManager::new(
-            CryptoOptions {
-                size: 16,
-                passphrase: "password123".into(),
-            },
+            CryptoOptions::passphrase(16, "password123"),
             &hex::decode("87647f8a2361fb1a9e692de576985949").unwrap()[..]
                 .try_into()
                 .unwrap(),
@@ -0,0 +1,422 @@
+//! Snapshots of the internal state of a live connection, for debugging
+//!
+//! A [`DebugDump`] prints as a plain text report, meant to be attached to bug reports about stalled or broken
+//! connections.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::protocol::TimeSpan;
+use crate::{ConnectionInfo, ControlPacket, PacketDirection, SeqNumber, SocketID};
+
+/// How many control packets a [`ControlHistory`] keeps by default
+pub const CONTROL_HISTORY_LEN: usize = 32;
+
+/// How many events an [`EventLog`] keeps by default
+pub const EVENT_LOG_LEN: usize = 64;
+
+/// The state of the sending side of a connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct SenderDump {
+    /// Packets waiting to be sent for the first time
+    pub transmit_buffer: usize,
+
+    /// Packets sent and waiting to be acknowledged
+    pub send_buffer: usize,
+
+    /// Packets the peer reported lost, waiting to be retransmitted
+    pub loss_list: Vec<SeqNumber>,
+
+    /// The sequence number of the first packet the peer hasn't acknowledged
+    pub lr_acked_packet: SeqNumber,
+
+    /// The round trip time reported by the peer
+    pub rtt: TimeSpan,
+
+    /// When the sender can send its next packet
+    pub next_send: Instant,
+
+    /// If the sender was asked to close
+    pub close_requested: bool,
+}
+
+/// The state of the receiving side of a connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverDump {
+    /// The sequence number of the next packet to be released
+    pub next_release: SeqNumber,
+
+    /// The slots of the receive buffer, from `next_release` to the last packet received
+    pub buffer_slots: usize,
+
+    /// The slots of the receive buffer that have a packet
+    pub buffered_packets: usize,
+
+    /// The packets detected as lost, that haven't arrived yet
+    pub loss_list: Vec<SeqNumber>,
+
+    /// The sequence number after the largest one received
+    pub lrsn: SeqNumber,
+
+    /// The largest ACK number the peer confirmed with an ACK2
+    pub lr_ack_acked: SeqNumber,
+
+    /// The round trip time, measured from ACK/ACK2 pairs
+    pub rtt: TimeSpan,
+
+    /// The round trip time variance
+    pub rtt_variance: TimeSpan,
+
+    /// When the next ACK is due
+    pub next_ack: Instant,
+
+    /// When the next periodic NAK is due
+    pub next_nak: Instant,
+
+    /// If the peer asked to shut down
+    pub shutdown: bool,
+}
+
+/// The state of the timers that keep a connection alive or break it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionDump {
+    /// The expiration timer events since the peer was last heard from
+    pub exp_count: u32,
+
+    /// When the expiration timer fires next
+    pub next_exp: Instant,
+
+    /// When the next keepalive is due, if nothing is sent before
+    pub next_keepalive: Instant,
+
+    /// When the peer was last heard from
+    pub last_heard: Instant,
+
+    /// The NAKs received since the acknowledged sequence number last moved forward
+    pub nak_storm: u32,
+}
+
+/// A control packet sent or received
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlRecord {
+    pub at: Instant,
+    pub direction: PacketDirection,
+    pub packet: ControlPacket,
+}
+
+/// The last control packets of a connection
+#[derive(Debug, Clone)]
+pub struct ControlHistory {
+    capacity: usize,
+    records: VecDeque<ControlRecord>,
+}
+
+impl ControlHistory {
+    pub fn new(capacity: usize) -> Self {
+        ControlHistory {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, at: Instant, direction: PacketDirection, packet: &ControlPacket) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(ControlRecord {
+            at,
+            direction,
+            packet: packet.clone(),
+        });
+    }
+
+    /// The packets recorded, oldest first
+    pub fn records(&self) -> Vec<ControlRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+impl Default for ControlHistory {
+    fn default() -> Self {
+        ControlHistory::new(CONTROL_HISTORY_LEN)
+    }
+}
+
+/// A significant event in the life of a connection, see [`EventLog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The connection's task started, after the handshake, see [`ConnectStats`](crate::ConnectStats)
+    Connected {
+        connect_time: Duration,
+        induction_retries: u32,
+        conclusion_retries: u32,
+    },
+    /// Key material was sent or received after the handshake, such as to refresh the stream key
+    KeyMaterial(PacketDirection),
+    /// The receiver dropped packets that arrived too late to be released
+    TooLateDrop { packets: u32 },
+    /// The peer moved to another address, see [`SourceValidation::Migrate`](crate::SourceValidation::Migrate)
+    Migrated { from: SocketAddr, to: SocketAddr },
+    /// The payload size was clamped, see [`MtuBlackholeEvent`](crate::MtuBlackholeEvent)
+    MtuClamped {
+        payload_size: usize,
+        previous_payload_size: usize,
+    },
+    /// The receive latency was raised
+    LatencyRaised(Duration),
+    /// The connection broke, nothing having been heard from the peer for too long, or too many NAKs without progress,
+    /// see [`BreakCriteria`](crate::BreakCriteria)
+    Broken,
+    /// The connection closed, for the reason given
+    Closed(String),
+}
+
+/// An event of a connection, and when it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    pub at: Instant,
+    pub event: ConnectionEvent,
+}
+
+/// The last significant events of a connection, so what led to it breaking can be told without having had logging
+/// configured
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    capacity: usize,
+    records: VecDeque<EventRecord>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        EventLog {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn record(&mut self, at: Instant, event: ConnectionEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(EventRecord { at, event });
+    }
+
+    /// The events recorded, oldest first
+    pub fn records(&self) -> Vec<EventRecord> {
+        self.records.iter().cloned().collect()
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog::new(EVENT_LOG_LEN)
+    }
+}
+
+/// A snapshot of a connection, taken at `at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugDump {
+    pub at: Instant,
+    pub local_sockid: SocketID,
+    pub remote_sockid: SocketID,
+    pub remote: SocketAddr,
+
+    /// What was negotiated in the handshake
+    pub info: ConnectionInfo,
+
+    pub sender: SenderDump,
+    pub receiver: ReceiverDump,
+    pub connection: ConnectionDump,
+
+    /// The last control packets sent and received, oldest first
+    pub recent_control: Vec<ControlRecord>,
+
+    /// The last significant events, oldest first
+    pub events: Vec<EventRecord>,
+}
+
+/// `instant` as an offset from `at`, like `+10ms` or `-1.5s`
+struct Offset(Instant, Instant);
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Offset(instant, at) = *self;
+        if instant >= at {
+            write!(f, "+{:?}", instant - at)
+        } else {
+            write!(f, "-{:?}", at - instant)
+        }
+    }
+}
+
+struct SeqList<'a>(&'a [SeqNumber]);
+
+impl fmt::Display for SeqList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // long loss lists are cut short, the count is what matters
+        const SHOWN: usize = 16;
+        write!(f, "{} [", self.0.len())?;
+        for (i, seq) in self.0.iter().take(SHOWN).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", seq)?;
+        }
+        if self.0.len() > SHOWN {
+            write!(f, " ...")?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl fmt::Display for DebugDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let at = self.at;
+        let (info, snd, rcv, conn) = (&self.info, &self.sender, &self.receiver, &self.connection);
+        writeln!(
+            f,
+            "connection {:?} -> {:?} at {}",
+            self.local_sockid, self.remote_sockid, self.remote
+        )?;
+        writeln!(
+            f,
+            "  negotiated: peer={} latency={:?}/{:?} tsbpd={}/{} tlpktdrop={} nakreport={} cipher={:?}/{} km={:?}/{:?}",
+            info.peer_version,
+            info.send_latency,
+            info.recv_latency,
+            info.send_tsbpd,
+            info.recv_tsbpd,
+            info.too_late_packet_drop,
+            info.periodic_nak,
+            info.cipher,
+            info.key_length,
+            info.send_km_state,
+            info.recv_km_state,
+        )?;
+        writeln!(
+            f,
+            "  sender: to_send={} unacked={} acked_until={} lost={} rtt={}us next_send={} closing={}",
+            snd.transmit_buffer,
+            snd.send_buffer,
+            snd.lr_acked_packet,
+            SeqList(&snd.loss_list),
+            snd.rtt.as_micros(),
+            Offset(snd.next_send, at),
+            snd.close_requested,
+        )?;
+        writeln!(
+            f,
+            "  receiver: next_release={} buffered={}/{} lrsn={} ack2={} lost={} rtt={}us rttvar={}us next_ack={} next_nak={} shutdown={}",
+            rcv.next_release,
+            rcv.buffered_packets,
+            rcv.buffer_slots,
+            rcv.lrsn,
+            rcv.lr_ack_acked,
+            SeqList(&rcv.loss_list),
+            rcv.rtt.as_micros(),
+            rcv.rtt_variance.as_micros(),
+            Offset(rcv.next_ack, at),
+            Offset(rcv.next_nak, at),
+            rcv.shutdown,
+        )?;
+        writeln!(
+            f,
+            "  timers: exp_count={} next_exp={} next_keepalive={} last_heard={} nak_storm={}",
+            conn.exp_count,
+            Offset(conn.next_exp, at),
+            Offset(conn.next_keepalive, at),
+            Offset(conn.last_heard, at),
+            conn.nak_storm,
+        )?;
+        writeln!(f, "  recent control packets:")?;
+        for record in &self.recent_control {
+            let arrow = match record.direction {
+                PacketDirection::Ingress => "<-",
+                PacketDirection::Egress => "->",
+            };
+            writeln!(
+                f,
+                "    {} {} {:?}",
+                Offset(record.at, at),
+                arrow,
+                record.packet
+            )?;
+        }
+        writeln!(f, "  events:")?;
+        for record in &self.events {
+            writeln!(f, "    {} {:?}", Offset(record.at, at), record.event)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::packet::ControlTypes;
+
+    fn keepalive() -> ControlPacket {
+        ControlPacket {
+            timestamp: crate::protocol::TimeStamp::from_micros(0),
+            dest_sockid: SocketID(1),
+            control_type: ControlTypes::KeepAlive,
+        }
+    }
+
+    #[test]
+    fn history_keeps_last() {
+        let start = Instant::now();
+        let mut history = ControlHistory::new(3);
+        for i in 0..5 {
+            history.record(
+                start + Duration::from_millis(i),
+                PacketDirection::Egress,
+                &keepalive(),
+            );
+        }
+        let ats: Vec<_> = history.records().iter().map(|r| r.at - start).collect();
+        assert_eq!(
+            ats,
+            [2, 3, 4]
+                .iter()
+                .map(|&i| Duration::from_millis(i))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn event_log_keeps_last() {
+        let start = Instant::now();
+        let mut log = EventLog::new(2);
+        log.record(start, ConnectionEvent::Broken);
+        log.record(start, ConnectionEvent::TooLateDrop { packets: 3 });
+        log.record(start, ConnectionEvent::Closed("Timed out".into()));
+        let events: Vec<_> = log.records().into_iter().map(|r| r.event).collect();
+        assert_eq!(
+            events,
+            vec![
+                ConnectionEvent::TooLateDrop { packets: 3 },
+                ConnectionEvent::Closed("Timed out".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn seq_list_cut_short() {
+        let seqs: Vec<_> = (0..20).map(SeqNumber::new_truncate).collect();
+        assert_eq!(SeqList(&seqs[..2]).to_string(), "2 [0 1]".to_string());
+        assert!(SeqList(&seqs).to_string().starts_with("20 [0 1 2"));
+        assert!(SeqList(&seqs).to_string().ends_with(" 15 ...]"));
+    }
+}
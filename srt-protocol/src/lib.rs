@@ -1,18 +1,40 @@
+mod clock;
 pub mod connection;
 pub mod crypto;
+mod dump;
 mod loss_compression;
-mod modular_num;
-mod msg_number;
-pub mod packet;
+mod options;
 pub mod pending_connection;
 pub mod protocol;
-mod seq_number;
-mod socket_id;
-mod srt_version;
+pub mod replay;
+mod snapshot;
+mod statistics;
+mod stream_id;
+pub mod transport;
+mod version;
 
-pub use connection::{Connection, ConnectionSettings};
-pub use msg_number::MsgNumber;
-pub use packet::{ControlPacket, DataPacket, Packet, PacketParseError};
-pub use seq_number::SeqNumber;
-pub use socket_id::SocketID;
-pub use srt_version::SrtVersion;
+pub use clock::{Clock, MockClock, SystemClock};
+pub use connection::{
+    Authenticator, BreakCriteria, Callback, ConnectPhase, ConnectProgress, ConnectStats,
+    Connection, ConnectionInfo, ConnectionSettings, ControlPacketHandler, DataIdleEvent,
+    DataIdleMonitor, MtuBlackholeEvent, MtuBlackholeMonitor, PacketDirection, PacketTap, Priority,
+    RateLimit, RateLimitControl, RetransmitAlgorithm, RetransmitBudget, SendBufferLevel,
+    SendBufferMonitor, SendDropPolicy, SourceValidation, StallEvent, StallMonitor, TaskStage,
+    TransferQuota, TransmissionType,
+};
+pub use crypto::{KeyLengthPolicy, KmState};
+pub use dump::{
+    ConnectionDump, ConnectionEvent, ControlHistory, ControlRecord, DebugDump, EventLog,
+    EventRecord, ReceiverDump, SenderDump, CONTROL_HISTORY_LEN, EVENT_LOG_LEN,
+};
+pub use options::LibsrtOptionError;
+pub use snapshot::{ConnectionSnapshot, WrappedKeys};
+pub use srt_packet::packet;
+pub use srt_packet::{
+    ControlPacket, DataPacket, HandshakeExtension, MsgNumber, Packet, PacketParseError, SeqNumber,
+    SocketID, SrtVersion,
+};
+pub use statistics::SocketStatistics;
+pub use stream_id::{StreamId, StreamIdParseError, StreamMode};
+pub use transport::DatagramTransport;
+pub use version::{Feature, ProtocolVersion};
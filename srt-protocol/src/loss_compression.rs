@@ -1,4 +1,4 @@
-use std::iter::Iterator;
+use std::iter::{self, Iterator};
 
 use crate::SeqNumber;
 
@@ -96,12 +96,15 @@ where
     }
 }
 
+// NAKs are read as ranges, with decompress_loss_ranges, this one is for checking compression round trips
+#[cfg(test)]
 pub struct DecompressLossList<I> {
     iterator: I,
 
     loop_next_end: Option<(u32, u32)>,
 }
 
+#[cfg(test)]
 impl<I: Iterator<Item = u32>> Iterator for DecompressLossList<I> {
     type Item = SeqNumber;
 
@@ -145,6 +148,7 @@ impl<I: Iterator<Item = u32>> Iterator for DecompressLossList<I> {
     }
 }
 
+#[cfg(test)]
 pub fn decompress_loss_list<I: Iterator<Item = u32>>(loss_list: I) -> DecompressLossList<I> {
     DecompressLossList {
         iterator: loss_list,
@@ -152,10 +156,27 @@ pub fn decompress_loss_list<I: Iterator<Item = u32>>(loss_list: I) -> Decompress
     }
 }
 
+/// The inclusive ranges of a compressed loss list, without going through the sequence numbers in them
+///
+/// A range start without an end is taken as a single sequence number.
+pub fn decompress_loss_ranges<I: Iterator<Item = u32>>(
+    mut loss_list: I,
+) -> impl Iterator<Item = (SeqNumber, SeqNumber)> {
+    iter::from_fn(move || {
+        let next = loss_list.next()?;
+        let first = SeqNumber::new_truncate(next & !(1 << 31));
+        if next & (1 << 31) == 0 {
+            return Some((first, first));
+        }
+        let last = loss_list.next().map_or(first, SeqNumber::new_truncate);
+        Some((first, last))
+    })
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::{compress_loss_list, decompress_loss_list};
+    use super::{compress_loss_list, decompress_loss_list, decompress_loss_ranges};
     use crate::SeqNumber;
 
     const ONE: u32 = 1 << 31;
@@ -208,4 +229,18 @@ mod test {
     fn unterminated_loop() {
         let _ = decompress_loss_list([10 | ONE].iter().copied()).collect::<Vec<_>>();
     }
+
+    #[test]
+    fn loss_ranges() {
+        let seq = SeqNumber::new_truncate;
+        assert_eq!(
+            decompress_loss_ranges([1 | ONE, 5, 9, 11 | ONE, 13].iter().cloned())
+                .collect::<Vec<_>>(),
+            [(seq(1), seq(5)), (seq(9), seq(9)), (seq(11), seq(13))]
+        );
+        assert_eq!(
+            decompress_loss_ranges([7, 20 | ONE].iter().cloned()).collect::<Vec<_>>(),
+            [(seq(7), seq(7)), (seq(20), seq(20))]
+        );
+    }
 }
@@ -0,0 +1,388 @@
+use std::{error::Error, fmt, time::Duration};
+
+use crate::{
+    crypto::CryptoOptions, packet::HandshakeExtension, pending_connection::ConnInitSettings,
+    RateLimit, RetransmitAlgorithm, SrtVersion, TransmissionType,
+};
+
+/// Why an option of the reference implementation couldn't be applied, see
+/// [`ConnInitSettings::from_libsrt_options`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LibsrtOptionError {
+    /// The option isn't one this library supports
+    Unknown(String),
+    /// The value can't be parsed for the option, the option then the value
+    Invalid(String, String),
+}
+
+impl fmt::Display for LibsrtOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LibsrtOptionError::*;
+        match self {
+            Unknown(name) => write!(f, "Unknown SRT option {:?}", name),
+            Invalid(name, value) => {
+                write!(f, "Invalid value {:?} for SRT option {:?}", value, name)
+            }
+        }
+    }
+}
+
+impl Error for LibsrtOptionError {}
+
+impl ConnInitSettings {
+    /// The default settings with `options` applied, see [`apply_libsrt_options`](Self::apply_libsrt_options)
+    ///
+    /// ```
+    /// # use srt_protocol::pending_connection::ConnInitSettings;
+    /// let settings =
+    ///     ConnInitSettings::from_libsrt_options(&[("latency", "200"), ("passphrase", "password123")])
+    ///         .unwrap();
+    /// ```
+    pub fn from_libsrt_options(options: &[(&str, &str)]) -> Result<Self, LibsrtOptionError> {
+        let mut settings = ConnInitSettings::default();
+        settings.apply_libsrt_options(options)?;
+        Ok(settings)
+    }
+
+    /// Apply options named as in the reference implementation, without the `SRTO_` prefix and in lower case, such
+    /// as those of an `srt://` URL, in the same units:
+    ///
+    /// * `transtype` - `live` or `file`, applied before the others as it resets the latency
+    /// * `latency`, `rcvlatency`, `peerlatency` - milliseconds
+    /// * `passphrase` - empty for no encryption, and `pbkeylen` - 16, 24 or 32, 0 for the default of 16
+    /// * `enforcedencryption` - `1`, `0`, `true`, `false`, `yes`, `no`, `on` or `off`
+    /// * `maxbw` - bytes per second, `-1` or `0` for no limit
+    /// * `peeridletimeo` - milliseconds
+    /// * `minversion` - as `0x010300`, or `1.3.0`
+    /// * `linger` - seconds
+    /// * `rcvtimeo`, `sndtimeo` - milliseconds, `-1` to wait forever
+    /// * `retransmitalgo` - `0` for aggressive, `1` for reduced
+    /// * `payloadsize` - bytes
+    /// * `streamid`
+    ///
+    /// Others are refused, rather than silently ignored. On error, the options before the one that failed are applied.
+    pub fn apply_libsrt_options(
+        &mut self,
+        options: &[(&str, &str)],
+    ) -> Result<(), LibsrtOptionError> {
+        if let Some((name, value)) = options.iter().find(|(name, _)| *name == "transtype") {
+            let transmission_type = match *value {
+                "live" => TransmissionType::Live,
+                "file" => TransmissionType::File,
+                _ => return Err(invalid(name, value)),
+            };
+            self.transmission_type = transmission_type;
+            self.send_latency = transmission_type.default_latency();
+            self.recv_latency = transmission_type.default_latency();
+        }
+
+        let mut key_length = None;
+        for &(name, value) in options {
+            match name {
+                "transtype" => {}
+                "latency" => {
+                    let latency = millis(name, value)?;
+                    self.send_latency = latency;
+                    self.recv_latency = latency;
+                }
+                "rcvlatency" => self.recv_latency = millis(name, value)?,
+                "peerlatency" => self.send_latency = millis(name, value)?,
+                "passphrase" if value.is_empty() => self.crypto = None,
+                "passphrase" => self.crypto = Some(CryptoOptions::passphrase(16, value)),
+                "pbkeylen" => {
+                    key_length = match value.parse() {
+                        Ok(0) => Some(16),
+                        Ok(size @ 16) | Ok(size @ 24) | Ok(size @ 32) => Some(size),
+                        _ => return Err(invalid(name, value)),
+                    }
+                }
+                "enforcedencryption" => self.enforced_encryption = boolean(name, value)?,
+                "maxbw" => {
+                    self.rate_limit = match value.parse::<i64>() {
+                        Ok(-1) | Ok(0) => None,
+                        Ok(bytes_per_second) if bytes_per_second > 0 => {
+                            Some(RateLimit::new(bytes_per_second as u64))
+                        }
+                        _ => return Err(invalid(name, value)),
+                    }
+                }
+                "peeridletimeo" => self.break_criteria.peer_idle_timeout = millis(name, value)?,
+                "minversion" => self.min_peer_version = version(name, value)?,
+                "linger" => {
+                    self.linger = Some(Duration::from_secs(
+                        value.parse().map_err(|_| invalid(name, value))?,
+                    ))
+                }
+                "rcvtimeo" => self.recv_timeout = timeout(name, value)?,
+                "sndtimeo" => self.send_timeout = timeout(name, value)?,
+                "retransmitalgo" => {
+                    self.retransmit_algorithm = match value {
+                        "0" => RetransmitAlgorithm::Aggressive,
+                        "1" => RetransmitAlgorithm::Reduced,
+                        _ => return Err(invalid(name, value)),
+                    }
+                }
+                "payloadsize" => {
+                    self.max_packet_size = match value.parse() {
+                        Ok(size) if size > 0 => size,
+                        _ => return Err(invalid(name, value)),
+                    }
+                }
+                "streamid" => self.extensions.push(HandshakeExtension::stream_id(value)),
+                _ => return Err(LibsrtOptionError::Unknown(name.into())),
+            }
+        }
+
+        // like the reference implementation, the key length alone doesn't turn encryption on
+        if let (Some(crypto), Some(size)) = (&mut self.crypto, key_length) {
+            crypto.size = size;
+        }
+        Ok(())
+    }
+
+    /// The default settings with `options` applied, see [`apply_ffmpeg_options`](Self::apply_ffmpeg_options)
+    pub fn from_ffmpeg_options(options: &[(&str, &str)]) -> Result<Self, LibsrtOptionError> {
+        let mut settings = ConnInitSettings::default();
+        settings.apply_ffmpeg_options(options)?;
+        Ok(settings)
+    }
+
+    /// Apply options as FFmpeg's `srt` protocol names them, such as in `srt://host:port?latency=200000`, for command
+    /// lines to carry over unchanged. Where FFmpeg differs from the reference implementation:
+    ///
+    /// * `latency`, `tsbpddelay`, `rcvlatency`, `peerlatency` - microseconds, rather than milliseconds. Like FFmpeg,
+    ///   `rcvlatency` and `peerlatency` take precedence over `latency` whatever the order
+    /// * `timeout` - microseconds, for both receiving and sending, `-1` to wait forever
+    /// * `enforced_encryption`, `srt_streamid` - for `enforcedencryption` and `streamid`
+    /// * `minversion` - a plain number as well, such as `66304` for 1.3.0
+    /// * `payload_size`, `pkt_size` - for `payloadsize`, `-1` for the default
+    /// * `mode` - `caller`, `listener` or `rendezvous`, which is how the socket connects rather than a setting of the
+    ///   connection, so it's only checked to be one of them here
+    ///
+    /// `passphrase`, `pbkeylen`, `maxbw`, `transtype`, `linger` and `streamid` are as in
+    /// [`apply_libsrt_options`](Self::apply_libsrt_options). Others are refused.
+    pub fn apply_ffmpeg_options(
+        &mut self,
+        options: &[(&str, &str)],
+    ) -> Result<(), LibsrtOptionError> {
+        let (mut latency, mut recv_latency, mut send_latency) = (None, None, None);
+        let mut timeouts = None;
+        let mut libsrt = Vec::new();
+        for &(name, value) in options {
+            match name {
+                "latency" | "tsbpddelay" => latency = Some(micros(name, value)?),
+                "rcvlatency" => recv_latency = Some(micros(name, value)?),
+                "peerlatency" => send_latency = Some(micros(name, value)?),
+                "timeout" if value == "-1" => timeouts = Some(None),
+                "timeout" => timeouts = Some(Some(micros(name, value)?)),
+                "minversion" => {
+                    self.min_peer_version = match value.parse() {
+                        Ok(version) => SrtVersion::parse(version),
+                        Err(_) => version(name, value)?,
+                    }
+                }
+                "enforced_encryption" => libsrt.push(("enforcedencryption", value)),
+                "srt_streamid" => libsrt.push(("streamid", value)),
+                "payload_size" | "pkt_size" if value == "-1" => {}
+                "payload_size" | "pkt_size" => libsrt.push(("payloadsize", value)),
+                "mode" => match value {
+                    "caller" | "listener" | "rendezvous" => {}
+                    _ => return Err(invalid(name, value)),
+                },
+                "passphrase" | "pbkeylen" | "maxbw" | "transtype" | "linger" | "streamid" => {
+                    libsrt.push((name, value))
+                }
+                _ => return Err(LibsrtOptionError::Unknown(name.into())),
+            }
+        }
+        // transtype resets the latency, so this goes first
+        self.apply_libsrt_options(&libsrt)?;
+
+        if let Some(latency) = latency {
+            self.send_latency = latency;
+            self.recv_latency = latency;
+        }
+        if let Some(latency) = recv_latency {
+            self.recv_latency = latency;
+        }
+        if let Some(latency) = send_latency {
+            self.send_latency = latency;
+        }
+        if let Some(timeout) = timeouts {
+            self.recv_timeout = timeout;
+            self.send_timeout = timeout;
+        }
+        Ok(())
+    }
+}
+
+fn invalid(name: &str, value: &str) -> LibsrtOptionError {
+    LibsrtOptionError::Invalid(name.into(), value.into())
+}
+
+fn millis(name: &str, value: &str) -> Result<Duration, LibsrtOptionError> {
+    value
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| invalid(name, value))
+}
+
+fn micros(name: &str, value: &str) -> Result<Duration, LibsrtOptionError> {
+    value
+        .parse()
+        .map(Duration::from_micros)
+        .map_err(|_| invalid(name, value))
+}
+
+fn timeout(name: &str, value: &str) -> Result<Option<Duration>, LibsrtOptionError> {
+    match value {
+        "-1" => Ok(None),
+        _ => millis(name, value).map(Some),
+    }
+}
+
+fn boolean(name: &str, value: &str) -> Result<bool, LibsrtOptionError> {
+    match value {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(invalid(name, value)),
+    }
+}
+
+fn version(name: &str, value: &str) -> Result<SrtVersion, LibsrtOptionError> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16)
+            .map(SrtVersion::parse)
+            .map_err(|_| invalid(name, value));
+    }
+    let parts = value
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid(name, value))?;
+    match parts[..] {
+        [major, minor, patch] => Ok(SrtVersion::new(major, minor, patch)),
+        _ => Err(invalid(name, value)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::crypto::Secret;
+
+    #[test]
+    fn options() {
+        let settings = ConnInitSettings::from_libsrt_options(&[
+            ("latency", "120"),
+            ("peerlatency", "200"),
+            ("pbkeylen", "32"),
+            ("passphrase", "password123"),
+            ("maxbw", "1000000"),
+            ("peeridletimeo", "10000"),
+            ("minversion", "0x010300"),
+            ("rcvtimeo", "-1"),
+            ("sndtimeo", "500"),
+            ("enforcedencryption", "false"),
+        ])
+        .unwrap();
+
+        assert_eq!(settings.recv_latency, Duration::from_millis(120));
+        assert_eq!(settings.send_latency, Duration::from_millis(200));
+        let crypto = settings.crypto.unwrap();
+        assert_eq!(crypto.size, 32);
+        assert_eq!(crypto.secret, Secret::Passphrase("password123".into()));
+        assert_eq!(settings.rate_limit, Some(RateLimit::new(1_000_000)));
+        assert_eq!(
+            settings.break_criteria.peer_idle_timeout,
+            Duration::from_secs(10)
+        );
+        assert_eq!(settings.min_peer_version, SrtVersion::new(1, 3, 0));
+        assert_eq!(settings.recv_timeout, None);
+        assert_eq!(settings.send_timeout, Some(Duration::from_millis(500)));
+        assert!(!settings.enforced_encryption);
+    }
+
+    #[test]
+    fn transtype_first() {
+        let settings =
+            ConnInitSettings::from_libsrt_options(&[("latency", "300"), ("transtype", "file")])
+                .unwrap();
+        assert_eq!(settings.transmission_type, TransmissionType::File);
+        assert_eq!(settings.recv_latency, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn key_length_alone() {
+        let settings = ConnInitSettings::from_libsrt_options(&[("pbkeylen", "24")]).unwrap();
+        assert!(settings.crypto.is_none());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            ConnInitSettings::from_libsrt_options(&[("latency", "soon")]).unwrap_err(),
+            LibsrtOptionError::Invalid("latency".into(), "soon".into())
+        );
+        assert_eq!(
+            ConnInitSettings::from_libsrt_options(&[("pbkeylen", "20")]).unwrap_err(),
+            LibsrtOptionError::Invalid("pbkeylen".into(), "20".into())
+        );
+        assert_eq!(
+            ConnInitSettings::from_libsrt_options(&[("minversion", "1.3")]).unwrap_err(),
+            LibsrtOptionError::Invalid("minversion".into(), "1.3".into())
+        );
+        assert_eq!(
+            ConnInitSettings::from_libsrt_options(&[("payloadsize", "0")]).unwrap_err(),
+            LibsrtOptionError::Invalid("payloadsize".into(), "0".into())
+        );
+        assert_eq!(
+            ConnInitSettings::from_libsrt_options(&[("tlpktdrop", "1")]).unwrap_err(),
+            LibsrtOptionError::Unknown("tlpktdrop".into())
+        );
+    }
+
+    #[test]
+    fn ffmpeg_options() {
+        let settings = ConnInitSettings::from_ffmpeg_options(&[
+            ("rcvlatency", "250000"),
+            ("latency", "120500"),
+            ("transtype", "live"),
+            ("passphrase", "password123"),
+            ("pbkeylen", "24"),
+            ("enforced_encryption", "1"),
+            ("minversion", "66304"),
+            ("timeout", "2000000"),
+            ("srt_streamid", "#!::r=live/cam1"),
+            ("pkt_size", "1316"),
+            ("mode", "caller"),
+        ])
+        .unwrap();
+
+        assert_eq!(settings.send_latency, Duration::from_micros(120_500));
+        assert_eq!(settings.recv_latency, Duration::from_millis(250));
+        assert_eq!(settings.crypto.unwrap().size, 24);
+        assert!(settings.enforced_encryption);
+        assert_eq!(settings.min_peer_version, SrtVersion::new(1, 3, 0));
+        assert_eq!(settings.recv_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(settings.send_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(
+            settings.extensions,
+            vec![HandshakeExtension::stream_id("#!::r=live/cam1")]
+        );
+        assert_eq!(settings.max_packet_size, 1316);
+
+        let settings = ConnInitSettings::from_ffmpeg_options(&[("payload_size", "-1")]).unwrap();
+        assert_eq!(settings.max_packet_size, 1500);
+        assert_eq!(
+            ConnInitSettings::from_ffmpeg_options(&[("mode", "server")]).unwrap_err(),
+            LibsrtOptionError::Invalid("mode".into(), "server".into())
+        );
+        // the libsrt name isn't FFmpeg's
+        assert_eq!(
+            ConnInitSettings::from_ffmpeg_options(&[("enforcedencryption", "1")]).unwrap_err(),
+            LibsrtOptionError::Unknown("enforcedencryption".into())
+        );
+    }
+}
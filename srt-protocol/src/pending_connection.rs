@@ -5,12 +5,21 @@ pub mod listen;
 pub mod rendezvous;
 
 use crate::{
-    crypto::CryptoOptions,
-    packet::{ControlTypes, HandshakeControlInfo},
-    DataPacket, SeqNumber, SocketID,
+    crypto::{CryptoOptions, KeyLengthPolicy},
+    packet::{ControlTypes, HandshakeControlInfo, HandshakeExtension, RejectReason, ShakeType},
+    Authenticator, BreakCriteria, Clock, ConnectPhase, ConnectProgress, ConnectStats,
+    ControlPacketHandler, DataIdleMonitor, DataPacket, MtuBlackholeMonitor, PacketTap, RateLimit,
+    RetransmitAlgorithm, RetransmitBudget, SendBufferMonitor, SendDropPolicy, SeqNumber, SocketID,
+    SourceValidation, SrtVersion, StallMonitor, SystemClock, TransferQuota, TransmissionType,
 };
 use rand::random;
-use std::{error::Error, fmt, net::SocketAddr, time::Duration};
+use std::{
+    error::Error,
+    fmt,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -29,15 +38,102 @@ pub enum ConnectError {
     ExpectedExtFlags,
     ExpectedNoExtFlags,
     BadSecret,
+    /// Only one side has a password
+    Unsecure,
+    /// The key length of the peer (second) doesn't go with the configured one (first), see
+    /// [`KeyLengthPolicy`]
+    KeyLengthMismatch(u8, u8),
+    /// None of the application protocols the peer offered are supported, see
+    /// [`HandshakeExtension::app_protocols`]
+    NoAppProtocol(Vec<String>),
+    /// The peer's version (first) is older than the minimum version (second)
+    PeerTooOld(SrtVersion, SrtVersion),
+    /// The peer rejected the connection
+    Rejected(RejectReason),
+}
+
+impl ConnectError {
+    /// The reason to send the peer in a rejection handshake, if this error should reject the connection
+    pub fn reject_reason(&self) -> Option<RejectReason> {
+        match self {
+            ConnectError::PeerTooOld(_, _) => Some(RejectReason::Version),
+            ConnectError::BadSecret => Some(RejectReason::BadSecret),
+            ConnectError::Unsecure => Some(RejectReason::Unsecure),
+            ConnectError::KeyLengthMismatch(_, _) => Some(RejectReason::Crypto),
+            ConnectError::NoAppProtocol(_) => Some(RejectReason::Peer),
+            _ => None,
+        }
+    }
 }
 
+/// The settings a connection starts with, before the handshake
+///
+/// With the `serde` feature, missing fields take their default. The clock, callbacks and extensions aren't serialized,
+/// nor are the socket id and sequence number, which are random for every connection.
+///
+/// Start from [`Default`], or from the option names of the reference implementation with
+/// [`from_libsrt_options`](ConnInitSettings::from_libsrt_options), and set the fields that differ, so new options
+/// don't break existing code.
+#[non_exhaustive]
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct ConnInitSettings {
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub starting_send_seqnum: SeqNumber,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub local_sockid: SocketID,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub clock: Arc<dyn Clock>,
     pub crypto: Option<CryptoOptions>,
     pub send_latency: Duration,
     pub recv_latency: Duration,
+    pub transmission_type: TransmissionType,
+    pub retransmit_algorithm: RetransmitAlgorithm,
+    pub packet_budget: usize,
+    pub max_message_size: usize,
+    /// The largest payload of a data packet, the equivalent of `SRTO_PAYLOADSIZE`
+    pub max_packet_size: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub control_packet_handler: Option<ControlPacketHandler>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub packet_tap: Option<PacketTap>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub send_buffer_monitor: Option<SendBufferMonitor>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub data_idle_monitor: Option<DataIdleMonitor>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub stall_monitor: Option<StallMonitor>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub mtu_blackhole_monitor: Option<MtuBlackholeMonitor>,
+    pub break_criteria: BreakCriteria,
+    pub rate_limit: Option<RateLimit>,
+    pub retransmit_budget: Option<RetransmitBudget>,
+    pub ack_interval: Option<Duration>,
+    pub nak_interval: Option<Duration>,
+    pub send_drop_policy: SendDropPolicy,
+    pub source_validation: SourceValidation,
+    pub transfer_quota: TransferQuota,
+    pub linger: Option<Duration>,
+    pub recv_timeout: Option<Duration>,
+    pub send_timeout: Option<Duration>,
+    /// Sent with the conclusion handshake, for the peer to find in its [`ConnectionSettings::peer_extensions`](crate::ConnectionSettings::peer_extensions)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub extensions: Vec<HandshakeExtension>,
+    pub min_peer_version: SrtVersion,
+    pub enforced_encryption: bool,
+    pub key_length_policy: KeyLengthPolicy,
+    /// Decides if listeners accept a connection, see [`Authenticator`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub authenticator: Option<Authenticator>,
+    /// How long the authenticator has to decide
+    pub auth_timeout: Duration,
+    /// Told where the handshake is at, see [`ConnectProgress`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub connect_progress: Option<ConnectProgress>,
 }
 
 impl fmt::Display for ConnectError {
@@ -84,6 +180,23 @@ impl fmt::Display for ConnectError {
                 write!(f, "Initiator did not expect handshake flags, but got some")
             }
             BadSecret => write!(f, "Wrong password"),
+            Unsecure => write!(f, "Only one side of the connection has a password"),
+            KeyLengthMismatch(configured, peer) => write!(
+                f,
+                "Peer key length {} doesn't go with the configured key length {}",
+                peer, configured
+            ),
+            NoAppProtocol(offered) => write!(
+                f,
+                "None of the application protocols offered are supported: {:?}",
+                offered
+            ),
+            PeerTooOld(peer, min) => write!(
+                f,
+                "Peer version {} is older than the minimum version {}",
+                peer, min
+            ),
+            Rejected(reason) => write!(f, "Connection rejected by peer: {:?}", reason),
         }
     }
 }
@@ -97,6 +210,36 @@ impl Default for ConnInitSettings {
             recv_latency: Duration::from_micros(50),
             starting_send_seqnum: random(),
             local_sockid: random(),
+            clock: Arc::new(SystemClock),
+            transmission_type: TransmissionType::Live,
+            retransmit_algorithm: RetransmitAlgorithm::Aggressive,
+            packet_budget: 64,
+            max_message_size: 8 * 1024 * 1024,
+            max_packet_size: 1500,
+            control_packet_handler: None,
+            packet_tap: None,
+            send_buffer_monitor: None,
+            data_idle_monitor: None,
+            stall_monitor: None,
+            mtu_blackhole_monitor: None,
+            break_criteria: BreakCriteria::default(),
+            rate_limit: None,
+            retransmit_budget: None,
+            ack_interval: None,
+            nak_interval: None,
+            send_drop_policy: SendDropPolicy::default(),
+            source_validation: SourceValidation::default(),
+            transfer_quota: TransferQuota::default(),
+            linger: None,
+            recv_timeout: None,
+            send_timeout: None,
+            extensions: vec![],
+            min_peer_version: SrtVersion::new(0, 0, 0),
+            enforced_encryption: true,
+            key_length_policy: KeyLengthPolicy::default(),
+            authenticator: None,
+            auth_timeout: Duration::from_secs(1),
+            connect_progress: None,
         }
     }
 }
@@ -108,6 +251,142 @@ impl ConnInitSettings {
             recv_latency: self.recv_latency,
             starting_send_seqnum: random(),
             local_sockid: random(),
+            clock: self.clock.clone(),
+            transmission_type: self.transmission_type,
+            retransmit_algorithm: self.retransmit_algorithm,
+            packet_budget: self.packet_budget,
+            max_message_size: self.max_message_size,
+            max_packet_size: self.max_packet_size,
+            control_packet_handler: self.control_packet_handler.clone(),
+            packet_tap: self.packet_tap.clone(),
+            send_buffer_monitor: self.send_buffer_monitor.clone(),
+            data_idle_monitor: self.data_idle_monitor.clone(),
+            stall_monitor: self.stall_monitor.clone(),
+            mtu_blackhole_monitor: self.mtu_blackhole_monitor.clone(),
+            break_criteria: self.break_criteria,
+            rate_limit: self.rate_limit,
+            retransmit_budget: self.retransmit_budget,
+            ack_interval: self.ack_interval,
+            nak_interval: self.nak_interval,
+            send_drop_policy: self.send_drop_policy,
+            source_validation: self.source_validation,
+            transfer_quota: self.transfer_quota,
+            linger: self.linger,
+            recv_timeout: self.recv_timeout,
+            send_timeout: self.send_timeout,
+            extensions: self.extensions.clone(),
+            min_peer_version: self.min_peer_version,
+            enforced_encryption: self.enforced_encryption,
+            key_length_policy: self.key_length_policy,
+            authenticator: self.authenticator.clone(),
+            auth_timeout: self.auth_timeout,
+            connect_progress: self.connect_progress.clone(),
+        }
+    }
+}
+
+/// How the handshake is going, for the [`ConnectStats`] of the connection
+#[derive(Clone, Default)]
+struct HandshakeProgress {
+    started: Option<Instant>,
+    last_sent: Option<HandshakeControlInfo>,
+    induction_retries: u32,
+    conclusion_retries: u32,
+    phase: Option<ConnectPhase>,
+    on_phase: Option<ConnectProgress>,
+}
+
+impl HandshakeProgress {
+    fn new(on_phase: Option<ConnectProgress>) -> Self {
+        HandshakeProgress {
+            on_phase,
+            ..HandshakeProgress::default()
         }
     }
+
+    // reports `phase` if the handshake just moved on to it
+    fn enter(&mut self, phase: ConnectPhase) {
+        if self.phase != Some(phase) {
+            self.phase = Some(phase);
+            if let Some(on_phase) = &self.on_phase {
+                on_phase.call(phase);
+            }
+        }
+    }
+
+    fn received(&mut self, now: Instant) {
+        self.started.get_or_insert(now);
+    }
+
+    // a handshake sent again as it was is a retransmission, on a timer or to answer the peer's own
+    fn sent(&mut self, now: Instant, control_type: &ControlTypes) {
+        self.started.get_or_insert(now);
+        let shake = match control_type {
+            ControlTypes::Handshake(shake) => shake,
+            _ => return,
+        };
+        if self.last_sent.as_ref() == Some(shake) {
+            match shake.shake_type {
+                ShakeType::Induction | ShakeType::Waveahand => self.induction_retries += 1,
+                ShakeType::Conclusion | ShakeType::Agreement => self.conclusion_retries += 1,
+                ShakeType::Rejection(_) => {}
+            }
+        }
+        match shake.shake_type {
+            ShakeType::Induction | ShakeType::Waveahand => self.enter(ConnectPhase::InductionSent),
+            ShakeType::Conclusion | ShakeType::Agreement => {
+                self.enter(ConnectPhase::ConclusionSent)
+            }
+            ShakeType::Rejection(_) => {}
+        }
+        self.last_sent = Some(shake.clone());
+    }
+
+    fn finish(&self, now: Instant, stats: &mut ConnectStats) {
+        stats.induction_retries = self.induction_retries;
+        stats.conclusion_retries = self.conclusion_retries;
+        stats.connect_time = self
+            .started
+            .map(|started| now - started)
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_test {
+    use super::*;
+
+    use serde::de::value::{Error, MapDeserializer, StrDeserializer};
+    use serde::de::IntoDeserializer;
+    use serde::Deserialize;
+
+    #[test]
+    fn missing_fields_default() {
+        let fields = vec![("packet_budget", 10u32), ("max_message_size", 1500)];
+        let settings =
+            ConnInitSettings::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()))
+                .unwrap();
+
+        let default = ConnInitSettings::default();
+        assert_eq!(settings.packet_budget, 10);
+        assert_eq!(settings.max_message_size, 1500);
+        assert_eq!(settings.send_latency, default.send_latency);
+        assert_eq!(settings.break_criteria, default.break_criteria);
+        assert!(settings.crypto.is_none());
+        assert!(settings.packet_tap.is_none());
+    }
+
+    #[test]
+    fn unit_enums_by_name() {
+        let de: StrDeserializer<Error> = "File".into_deserializer();
+        assert_eq!(
+            TransmissionType::deserialize(de).unwrap(),
+            TransmissionType::File
+        );
+        let de: StrDeserializer<Error> = "Reduced".into_deserializer();
+        assert_eq!(
+            RetransmitAlgorithm::deserialize(de).unwrap(),
+            RetransmitAlgorithm::Reduced
+        );
+    }
 }
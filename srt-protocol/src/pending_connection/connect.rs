@@ -3,11 +3,11 @@ use std::time::Instant;
 
 use crate::packet::*;
 use crate::protocol::TimeStamp;
-use crate::{ConnectionSettings, SocketID};
+use crate::{ConnectPhase, ConnectionSettings, SocketID};
 
 use super::{
     hsv5::{start_hsv5_initiation, StartedInitiator},
-    ConnInitSettings, ConnectError,
+    ConnInitSettings, ConnectError, HandshakeProgress,
 };
 use ConnectError::*;
 use ConnectState::*;
@@ -40,6 +40,7 @@ pub struct Connect {
     local_addr: IpAddr,
     init_settings: ConnInitSettings,
     state: ConnectState,
+    progress: HandshakeProgress,
 }
 
 pub type ConnectResult = Result<Option<(Packet, SocketAddr)>, ConnectError>;
@@ -49,6 +50,7 @@ impl Connect {
         Connect {
             remote,
             local_addr,
+            progress: HandshakeProgress::new(init_settings.connect_progress.clone()),
             init_settings,
             state: ConnectState::new(),
         }
@@ -59,8 +61,8 @@ impl Connect {
             timestamp: TimeStamp::from_micros(0), // TODO: this is not zero in the reference implementation
             control_type: ControlTypes::Handshake(HandshakeControlInfo {
                 init_seq_num: self.init_settings.starting_send_seqnum,
-                max_packet_size: 1500, // TODO: take as a parameter
-                max_flow_size: 8192,   // TODO: take as a parameter
+                max_packet_size: self.init_settings.max_packet_size,
+                max_flow_size: 8192, // TODO: take as a parameter
                 socket_id: self.init_settings.local_sockid,
                 shake_type: ShakeType::Induction,
                 peer_addr: self.local_addr,
@@ -115,7 +117,12 @@ impl Connect {
     ) -> ConnectResult {
         match (info.shake_type, info.info.version(), from) {
             (ShakeType::Conclusion, 5, from) if from == self.remote => {
-                let settings = initiator.finish_hsv5_initiation(&info, from)?;
+                if self.init_settings.crypto.is_some() {
+                    self.progress.enter(ConnectPhase::KeyExchange);
+                }
+                let mut settings = initiator.finish_hsv5_initiation(&info, from)?;
+                let now = self.init_settings.clock.now();
+                self.progress.finish(now, &mut settings.connect_stats);
 
                 self.state = Connected(settings);
 
@@ -126,11 +133,19 @@ impl Connect {
             (ShakeType::Conclusion, 5, from) => Err(UnexpectedHost(self.remote, from)),
             (ShakeType::Conclusion, version, _) => Err(UnsupportedProtocolVersion(version)),
             (ShakeType::Induction, _, _) => Ok(None),
+            (ShakeType::Rejection(reason), _, from) if from == self.remote => Err(Rejected(reason)),
             (_, _, _) => Err(ConclusionExpected(info)),
         }
     }
 
     pub fn handle_packet(&mut self, next: (Packet, SocketAddr)) -> ConnectResult {
+        let now = self.init_settings.clock.now();
+        self.progress.received(now);
+        let result = self.dispatch_packet(next);
+        self.track(now, result)
+    }
+
+    fn dispatch_packet(&mut self, next: (Packet, SocketAddr)) -> ConnectResult {
         let (packet, from) = next;
         match (self.state.clone(), packet) {
             (InductionResponseWait(_), Packet::Control(control)) => match control.control_type {
@@ -150,6 +165,12 @@ impl Connect {
     }
 
     pub fn handle_tick(&mut self, _now: Instant) -> ConnectResult {
+        let now = self.init_settings.clock.now();
+        let result = self.on_tick();
+        self.track(now, result)
+    }
+
+    fn on_tick(&mut self) -> ConnectResult {
         match &self.state {
             Configured => self.on_start(),
             InductionResponseWait(request_packet) => {
@@ -162,6 +183,13 @@ impl Connect {
         }
     }
 
+    fn track(&mut self, now: Instant, result: ConnectResult) -> ConnectResult {
+        if let Ok(Some((Packet::Control(control), _))) = &result {
+            self.progress.sent(now, &control.control_type);
+        }
+        result
+    }
+
     pub fn state(&self) -> &ConnectState {
         &self.state
     }
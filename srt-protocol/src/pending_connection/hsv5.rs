@@ -2,32 +2,64 @@
 
 use super::{ConnInitSettings, ConnectError};
 use crate::{
-    crypto::CryptoManager,
-    packet::{
-        HandshakeControlInfo, HandshakeVSInfo, SrtControlPacket, SrtHandshake, SrtShakeFlags,
-    },
-    ConnectionSettings, SrtVersion,
-};
-use std::{
-    net::SocketAddr,
-    time::{Duration, Instant},
+    crypto::{CryptoManager, CryptoOptions},
+    packet::{HandshakeControlInfo, HandshakeVSInfo, SrtControlPacket, SrtHandshake},
+    ConnectStats, ConnectionSettings, Feature, HandshakeExtension, KmState, ProtocolVersion,
+    SrtVersion,
 };
+use log::info;
+use std::{net::SocketAddr, time::Duration};
+
+// the type of every extension block in `info`, in the order they're sent
+fn extension_types(info: &HandshakeVSInfo) -> Vec<u16> {
+    match info {
+        HandshakeVSInfo::V5 {
+            ext_hs,
+            ext_km,
+            ext_config,
+            ext_other,
+            ..
+        } => ext_hs
+            .iter()
+            .chain(ext_km)
+            .chain(ext_config)
+            .map(SrtControlPacket::type_id)
+            .chain(ext_other.iter().map(|ext| ext.type_id))
+            .collect(),
+        HandshakeVSInfo::V4(_) => vec![],
+    }
+}
+
+// the extensions that can be exchanged with a peer of version `peer`
+fn gate_extensions(peer: SrtVersion, extensions: &[HandshakeExtension]) -> Vec<HandshakeExtension> {
+    if ProtocolVersion::with_peer(peer).supports(Feature::Hsv5Extensions) {
+        extensions.to_vec()
+    } else {
+        vec![]
+    }
+}
+
+// the application protocols in `extensions`, if there are any
+fn app_protocols(extensions: &[HandshakeExtension]) -> Option<Vec<String>> {
+    extensions
+        .iter()
+        .find_map(HandshakeExtension::as_app_protocols)
+}
 
 pub fn gen_hsv5_response(
     settings: ConnInitSettings,
     with_hsv5: &HandshakeControlInfo,
     from: SocketAddr,
 ) -> Result<(HandshakeVSInfo, ConnectionSettings), ConnectError> {
-    let (crypto_size, incoming_ext_hs, incoming_ext_km, _incoming_ext_config) =
-        match &with_hsv5.info {
-            HandshakeVSInfo::V5 {
-                crypto_size,
-                ext_hs,
-                ext_km,
-                ext_config,
-            } => (crypto_size, ext_hs, ext_km, ext_config),
-            i => return Err(ConnectError::UnsupportedProtocolVersion(i.version())),
-        };
+    let (incoming_ext_hs, incoming_ext_km, incoming_ext_other) = match &with_hsv5.info {
+        HandshakeVSInfo::V5 {
+            ext_hs,
+            ext_km,
+            ext_other,
+            ..
+        } => (ext_hs, ext_km, ext_other),
+        i => return Err(ConnectError::UnsupportedProtocolVersion(i.version())),
+    };
 
     let hs = match incoming_ext_hs {
         Some(SrtControlPacket::HandshakeRequest(hs)) => hs,
@@ -35,26 +67,94 @@ pub fn gen_hsv5_response(
         None => return Err(ConnectError::ExpectedExtFlags),
     };
 
+    if hs.version < settings.min_peer_version {
+        return Err(ConnectError::PeerTooOld(
+            hs.version,
+            settings.min_peer_version,
+        ));
+    }
+
+    // the first of our application protocols the peer offered, sent back alone
+    let app_protocol = match (
+        app_protocols(&settings.extensions),
+        app_protocols(incoming_ext_other),
+    ) {
+        (Some(ours), Some(offered)) => match ours.into_iter().find(|p| offered.contains(p)) {
+            Some(protocol) => Some(protocol),
+            None => return Err(ConnectError::NoAppProtocol(offered)),
+        },
+        _ => None,
+    };
+    let mut extensions: Vec<_> = settings
+        .extensions
+        .iter()
+        .filter(|ext| ext.type_id != HandshakeExtension::APP_PROTOCOL)
+        .cloned()
+        .collect();
+    extensions.extend(
+        app_protocol
+            .as_deref()
+            .map(|protocol| HandshakeExtension::app_protocols(&[protocol])),
+    );
+
     // crypto
-    let cm = match (&settings.crypto, incoming_ext_km) {
+    let enforced = settings.enforced_encryption;
+    let mut key_length_adopted = false;
+    let (cm, send_km_state, recv_km_state) = match (&settings.crypto, incoming_ext_km) {
         // ok, both sizes have crypto
         (Some(co), Some(SrtControlPacket::KeyManagerRequest(km))) => {
-            if co.size != *crypto_size {
-                unimplemented!("Key size mismatch");
-            }
+            let offered = km.key_length() as u8;
+            if !settings.key_length_policy.accepts(co.size, offered) {
+                if enforced {
+                    return Err(ConnectError::KeyLengthMismatch(co.size, offered));
+                }
+                // still encrypt what's sent, with keys of a length the peer didn't want
+                (
+                    Some(CryptoManager::new_random(co.clone())),
+                    KmState::BadCryptoMode,
+                    KmState::BadCryptoMode,
+                )
+            } else {
+                if offered != co.size {
+                    info!(
+                        "Using the peer's key length of {} instead of {}",
+                        offered, co.size
+                    );
+                    key_length_adopted = true;
+                }
+                let co = CryptoOptions {
+                    size: offered,
+                    ..co.clone()
+                };
 
-            Some(CryptoManager::new_from_kmreq(co.clone(), km)?)
+                match CryptoManager::new_from_kmreq(co.clone(), km) {
+                    Ok(cm) => (Some(cm), KmState::Secured, KmState::Secured),
+                    // still encrypt what's sent, with a key the peer doesn't have
+                    Err(ConnectError::BadSecret) if !enforced => (
+                        Some(CryptoManager::new_random(co)),
+                        KmState::BadSecret,
+                        KmState::BadSecret,
+                    ),
+                    Err(e) => return Err(e),
+                }
+            }
         }
         // ok, neither have crypto
-        (None, None) => None,
+        (None, None) => (None, KmState::Unsecured, KmState::Unsecured),
         // bad cases
         (Some(_), Some(_)) => unimplemented!("Expected kmreq"),
-        (Some(_), None) | (None, Some(_)) => unimplemented!("Crypto mismatch"),
+        (Some(co), None) if !enforced => (
+            Some(CryptoManager::new_random(co.clone())),
+            KmState::NoSecret,
+            KmState::Unsecured,
+        ),
+        (None, Some(_)) if !enforced => (None, KmState::Unsecured, KmState::NoSecret),
+        (Some(_), None) | (None, Some(_)) => return Err(ConnectError::Unsecure),
     };
-    let outgoing_ext_km = if let Some(cm) = &cm {
-        Some(cm.generate_km())
-    } else {
-        None
+    // only answer with keys if the peer's could be used
+    let outgoing_ext_km = match (&cm, send_km_state) {
+        (Some(cm), KmState::Secured) => Some(cm.generate_km()),
+        _ => None,
     };
 
     Ok((
@@ -62,25 +162,60 @@ pub fn gen_hsv5_response(
             crypto_size: cm.as_ref().map(|c| c.key_length()).unwrap_or(0),
             ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: settings.transmission_type.shake_flags(),
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km: outgoing_ext_km.map(SrtControlPacket::KeyManagerResponse),
             ext_config: None,
+            ext_other: gate_extensions(hs.version, &extensions),
         },
         ConnectionSettings {
             remote: from,
             remote_sockid: with_hsv5.socket_id,
             local_sockid: settings.local_sockid,
-            socket_start_time: Instant::now(), // xxx?
+            socket_start_time: settings.clock.now(), // xxx?
+            clock: settings.clock.clone(),
             init_send_seq_num: settings.starting_send_seqnum,
             init_recv_seq_num: with_hsv5.init_seq_num,
-            max_packet_size: 1500, // todo: parameters!
-            max_flow_size: 8192,
+            max_packet_size: settings.max_packet_size,
+            max_flow_size: settings.transmission_type.default_flow_size(),
             send_tsbpd_latency: Duration::max(settings.send_latency, hs.recv_latency),
             recv_tsbpd_latency: Duration::max(settings.recv_latency, hs.send_latency),
             crypto_manager: cm,
+            send_km_state,
+            recv_km_state,
+            transmission_type: settings.transmission_type,
+            retransmit_algorithm: settings.retransmit_algorithm,
+            packet_budget: settings.packet_budget,
+            max_message_size: settings.max_message_size,
+            peer_version: hs.version,
+            peer_flags: hs.flags,
+            control_packet_handler: settings.control_packet_handler.clone(),
+            packet_tap: settings.packet_tap.clone(),
+            send_buffer_monitor: settings.send_buffer_monitor.clone(),
+            data_idle_monitor: settings.data_idle_monitor.clone(),
+            stall_monitor: settings.stall_monitor.clone(),
+            mtu_blackhole_monitor: settings.mtu_blackhole_monitor.clone(),
+            break_criteria: settings.break_criteria,
+            rate_limit: settings.rate_limit,
+            rate_limit_control: Default::default(),
+            retransmit_budget: settings.retransmit_budget,
+            ack_interval: settings.ack_interval,
+            nak_interval: settings.nak_interval,
+            send_drop_policy: settings.send_drop_policy,
+            source_validation: settings.source_validation,
+            transfer_quota: settings.transfer_quota,
+            linger: settings.linger,
+            recv_timeout: settings.recv_timeout,
+            send_timeout: settings.send_timeout,
+            app_protocol,
+            peer_extensions: gate_extensions(hs.version, incoming_ext_other),
+            connect_stats: ConnectStats {
+                peer_extension_types: extension_types(&with_hsv5.info),
+                key_length_adopted,
+                ..ConnectStats::default()
+            },
         },
     ))
 }
@@ -96,10 +231,6 @@ pub fn start_hsv5_initiation(
 ) -> Result<(HandshakeVSInfo, StartedInitiator), ConnectError> {
     let self_crypto_size = settings.crypto.as_ref().map(|co| co.size).unwrap_or(0);
 
-    // if peer_crypto_size != self_crypto_size {
-    //     unimplemented!("Unimplemted crypto mismatch!");
-    // }
-
     let (cm, ext_km) = if let Some(co) = &settings.crypto {
         let cm = CryptoManager::new_random(co.clone());
         let kmreq = SrtControlPacket::KeyManagerRequest(cm.generate_km());
@@ -113,12 +244,13 @@ pub fn start_hsv5_initiation(
             crypto_size: self_crypto_size,
             ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: settings.transmission_type.shake_flags(),
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km,
             ext_config: None,
+            ext_other: settings.extensions.clone(),
         },
         StartedInitiator { cm, settings },
     ))
@@ -131,14 +263,15 @@ impl StartedInitiator {
         from: SocketAddr,
     ) -> Result<ConnectionSettings, ConnectError> {
         // TODO: factor this out with above...
-        let (_crypto_size, incoming_ext_hs, _incoming_ext_km, _incoming_ext_config) =
+        let (crypto_size, incoming_ext_hs, incoming_ext_km, incoming_ext_other) =
             match &response.info {
                 HandshakeVSInfo::V5 {
                     crypto_size,
                     ext_hs,
                     ext_km,
-                    ext_config,
-                } => (crypto_size, ext_hs, ext_km, ext_config),
+                    ext_other,
+                    ..
+                } => (crypto_size, ext_hs, ext_km, ext_other),
                 i => return Err(ConnectError::UnsupportedProtocolVersion(i.version())),
             };
 
@@ -148,21 +281,100 @@ impl StartedInitiator {
             None => return Err(ConnectError::ExpectedExtFlags),
         };
 
+        if hs.version < self.settings.min_peer_version {
+            return Err(ConnectError::PeerTooOld(
+                hs.version,
+                self.settings.min_peer_version,
+            ));
+        }
+
+        // the application protocol the responder picked, from those offered
+        let offered = app_protocols(&self.settings.extensions).unwrap_or_default();
+        let app_protocol = app_protocols(incoming_ext_other)
+            .and_then(|picked| picked.into_iter().next())
+            .filter(|protocol| offered.contains(protocol));
+
         // todo: validate km!
+        // the responder only answers with keys if it could use ours, and only has a key size if it has a password,
+        // its own if it didn't take the length of ours
+        let (send_km_state, recv_km_state) = match (&self.cm, incoming_ext_km, *crypto_size) {
+            (None, _, 0) => (KmState::Unsecured, KmState::Unsecured),
+            (None, _, _) => (KmState::Unsecured, KmState::NoSecret),
+            (Some(cm), Some(SrtControlPacket::KeyManagerResponse(km)), _)
+                if km.key_length() == usize::from(cm.key_length()) =>
+            {
+                (KmState::Secured, KmState::Secured)
+            }
+            (Some(_), Some(SrtControlPacket::KeyManagerResponse(_)), _) => {
+                (KmState::BadCryptoMode, KmState::BadCryptoMode)
+            }
+            (Some(_), _, 0) => (KmState::NoSecret, KmState::Unsecured),
+            (Some(cm), _, size) if size != cm.key_length() => {
+                (KmState::BadCryptoMode, KmState::BadCryptoMode)
+            }
+            (Some(_), _, _) => (KmState::BadSecret, KmState::BadSecret),
+        };
+        if self.settings.enforced_encryption {
+            match (send_km_state, recv_km_state) {
+                (KmState::Unsecured, KmState::Unsecured) | (KmState::Secured, KmState::Secured) => {
+                }
+                (KmState::BadSecret, _) => return Err(ConnectError::BadSecret),
+                (KmState::BadCryptoMode, _) => {
+                    return Err(ConnectError::KeyLengthMismatch(
+                        self.settings.crypto.as_ref().map_or(0, |co| co.size),
+                        *crypto_size,
+                    ))
+                }
+                _ => return Err(ConnectError::Unsecure),
+            }
+        }
 
         // validate response
         Ok(ConnectionSettings {
             remote: from,
             remote_sockid: response.socket_id,
             local_sockid: self.settings.local_sockid,
-            socket_start_time: Instant::now(), // xxx?
+            socket_start_time: self.settings.clock.now(), // xxx?
+            clock: self.settings.clock.clone(),
             init_send_seq_num: self.settings.starting_send_seqnum,
             init_recv_seq_num: response.init_seq_num,
-            max_packet_size: 1500, // todo: parameters!
-            max_flow_size: 8192,
+            max_packet_size: self.settings.max_packet_size,
+            max_flow_size: self.settings.transmission_type.default_flow_size(),
             send_tsbpd_latency: Duration::max(self.settings.send_latency, hs.recv_latency),
             recv_tsbpd_latency: Duration::max(self.settings.recv_latency, hs.send_latency),
             crypto_manager: self.cm,
+            send_km_state,
+            recv_km_state,
+            transmission_type: self.settings.transmission_type,
+            retransmit_algorithm: self.settings.retransmit_algorithm,
+            packet_budget: self.settings.packet_budget,
+            max_message_size: self.settings.max_message_size,
+            peer_version: hs.version,
+            peer_flags: hs.flags,
+            control_packet_handler: self.settings.control_packet_handler.clone(),
+            packet_tap: self.settings.packet_tap.clone(),
+            send_buffer_monitor: self.settings.send_buffer_monitor.clone(),
+            data_idle_monitor: self.settings.data_idle_monitor.clone(),
+            stall_monitor: self.settings.stall_monitor.clone(),
+            mtu_blackhole_monitor: self.settings.mtu_blackhole_monitor.clone(),
+            break_criteria: self.settings.break_criteria,
+            rate_limit: self.settings.rate_limit,
+            rate_limit_control: Default::default(),
+            retransmit_budget: self.settings.retransmit_budget,
+            ack_interval: self.settings.ack_interval,
+            nak_interval: self.settings.nak_interval,
+            send_drop_policy: self.settings.send_drop_policy,
+            source_validation: self.settings.source_validation,
+            transfer_quota: self.settings.transfer_quota,
+            linger: self.settings.linger,
+            recv_timeout: self.settings.recv_timeout,
+            send_timeout: self.settings.send_timeout,
+            app_protocol,
+            peer_extensions: gate_extensions(hs.version, incoming_ext_other),
+            connect_stats: ConnectStats {
+                peer_extension_types: extension_types(&response.info),
+                ..ConnectStats::default()
+            },
         })
     }
 }
@@ -1,16 +1,21 @@
 use std::net::SocketAddr;
 
+use log::warn;
+
 use crate::packet::*;
 use crate::protocol::TimeStamp;
 use crate::{ConnectionSettings, SocketID};
 
-use super::{cookie::gen_cookie, hsv5::gen_hsv5_response, ConnInitSettings, ConnectError};
+use super::{
+    cookie::gen_cookie, hsv5::gen_hsv5_response, ConnInitSettings, ConnectError, HandshakeProgress,
+};
 use ConnectError::*;
 use ListenState::*;
 
 pub struct Listen {
     init_settings: ConnInitSettings,
     state: ListenState,
+    progress: HandshakeProgress,
 }
 
 #[derive(Clone)]
@@ -31,11 +36,55 @@ pub enum ListenState {
 
 type ListenResult = Result<Option<(Packet, SocketAddr)>, ConnectError>;
 
+// a rejection of the conclusion `shake`, or of the response to it, its parameters are passed back as is
+fn reject(
+    timestamp: TimeStamp,
+    dest_sockid: SocketID,
+    shake: &HandshakeControlInfo,
+    reason: RejectReason,
+) -> Packet {
+    Packet::Control(ControlPacket {
+        timestamp,
+        dest_sockid,
+        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+            shake_type: ShakeType::Rejection(reason),
+            info: HandshakeVSInfo::V5 {
+                crypto_size: 0,
+                ext_hs: None,
+                ext_km: None,
+                ext_config: None,
+                ext_other: vec![],
+            },
+            ..shake.clone()
+        }),
+    })
+}
+
+// if `shake` is the conclusion the listener connected with, sent again, told apart by the caller's address, initial
+// sequence number and cookie
+fn is_same_conclusion(
+    resp_handshake: &ControlPacket,
+    settings: &ConnectionSettings,
+    shake: &HandshakeControlInfo,
+    from: SocketAddr,
+) -> bool {
+    match &resp_handshake.control_type {
+        ControlTypes::Handshake(resp) => {
+            shake.shake_type == ShakeType::Conclusion
+                && from == settings.remote
+                && shake.init_seq_num == settings.init_recv_seq_num
+                && shake.syn_cookie == resp.syn_cookie
+        }
+        _ => false,
+    }
+}
+
 impl Listen {
     pub fn new(init_settings: ConnInitSettings) -> Listen {
         Listen {
             state: InductionWait,
             init_settings,
+            progress: HandshakeProgress::default(),
         }
     }
     fn wait_for_induction(
@@ -68,6 +117,7 @@ impl Listen {
                             ext_hs: None,
                             ext_km: None,
                             ext_config: None,
+                            ext_other: vec![],
                         },
                         init_seq_num: self.init_settings.starting_send_seqnum,
                         ..shake
@@ -112,8 +162,20 @@ impl Listen {
             // first induction received, wait for response (with cookie)
             (ShakeType::Conclusion, VERSION_5, syn_cookie) if syn_cookie == state.cookie => {
                 // construct a packet to send back
-                let (hsv5, connection) =
-                    gen_hsv5_response(self.init_settings.clone(), &shake, from)?;
+                let (hsv5, mut connection) =
+                    match gen_hsv5_response(self.init_settings.clone(), &shake, from) {
+                        Ok(r) => r,
+                        Err(e) => match e.reject_reason() {
+                            Some(reason) => {
+                                warn!("Rejecting connection from {}: {}", from, e);
+                                return Ok(Some((
+                                    reject(timestamp, shake.socket_id, &shake, reason),
+                                    from,
+                                )));
+                            }
+                            None => return Err(e),
+                        },
+                    };
 
                 let resp_handshake = ControlPacket {
                     timestamp,
@@ -133,6 +195,8 @@ impl Listen {
                 // use the remote ones
 
                 // finish the connection
+                let now = self.init_settings.clock.now();
+                self.progress.finish(now, &mut connection.connect_stats);
                 self.state = Connected(resp_handshake.clone(), connection);
 
                 Ok(Some((Packet::Control(resp_handshake), from)))
@@ -156,20 +220,51 @@ impl Listen {
             (InductionWait, control_type) | (ConclusionWait(_), control_type) => {
                 Err(HandshakeExpected(control_type))
             }
+            // the caller didn't get the response, it gets the same one again
+            (Connected(resp_handshake, settings), ControlTypes::Handshake(shake))
+                if is_same_conclusion(&resp_handshake, &settings, &shake, from) =>
+            {
+                Ok(Some((Packet::Control(resp_handshake), from)))
+            }
             (Connected(_, _), _) => Ok(None),
         }
     }
 
     pub fn handle_packet(&mut self, (packet, from): (Packet, SocketAddr)) -> ListenResult {
-        match packet {
+        let now = self.init_settings.clock.now();
+        self.progress.received(now);
+        let result = match packet {
             Packet::Control(control) => self.handle_control_packets(control, from),
             Packet::Data(data) => Err(ControlExpected(data)),
+        };
+        if let Ok(Some((Packet::Control(control), _))) = &result {
+            self.progress.sent(now, &control.control_type);
         }
+        result
     }
 
     pub fn state(&self) -> &ListenState {
         &self.state
     }
+
+    /// The rejection to send instead of the response once connected, for when the connection is turned down after
+    /// the handshake, such as by an [`Authenticator`](crate::Authenticator). `None` if not connected yet.
+    pub fn reject(&self, reason: RejectReason) -> Option<(Packet, SocketAddr)> {
+        match &self.state {
+            Connected(
+                ControlPacket {
+                    timestamp,
+                    dest_sockid,
+                    control_type: ControlTypes::Handshake(shake),
+                },
+                settings,
+            ) => Some((
+                reject(*timestamp, *dest_sockid, shake, reason),
+                settings.remote,
+            )),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -182,8 +277,9 @@ mod test {
     use rand::random;
 
     use crate::{
+        crypto::CryptoOptions,
         packet::{ControlPacket, DataPacket, HandshakeControlInfo, Packet, ShakeType},
-        SrtVersion,
+        KmState, SrtVersion,
     };
 
     fn test_listen() -> Listen {
@@ -204,6 +300,7 @@ mod test {
                 ext_hs: None,
                 ext_km: None,
                 ext_config: None,
+                ext_other: vec![],
             },
         }
     }
@@ -227,6 +324,7 @@ mod test {
                 })),
                 ext_km: None,
                 ext_config: None,
+                ext_other: vec![],
             },
         }
     }
@@ -259,6 +357,151 @@ mod test {
         );
     }
 
+    #[test]
+    fn connect_stats() {
+        let mut l = test_listen();
+
+        let from = "127.0.0.1:8765".parse().unwrap();
+        let induction = test_induction();
+        l.handle_packet((build_hs_pack(induction.clone()), from))
+            .unwrap();
+        // the caller didn't get the response, so it asks again
+        l.handle_packet((build_hs_pack(induction), from)).unwrap();
+        l.handle_packet((build_hs_pack(test_conclusion()), from))
+            .unwrap();
+        match l.state() {
+            Connected(_, settings) => {
+                assert_eq!(settings.connect_stats.induction_retries, 1);
+                assert_eq!(settings.connect_stats.conclusion_retries, 0);
+                assert_eq!(settings.connect_stats.won_cookie_contest, None);
+                // only the handshake request
+                assert_eq!(settings.connect_stats.peer_extension_types, vec![1]);
+            }
+            _ => panic!("Expected to be connected"),
+        }
+    }
+
+    #[test]
+    fn reject_connected() {
+        let mut l = test_listen();
+
+        let from = "127.0.0.1:8765".parse().unwrap();
+        assert!(l.reject(RejectReason::Peer).is_none());
+        l.handle_packet((build_hs_pack(test_induction()), from))
+            .unwrap();
+        let conclusion = test_conclusion();
+        l.handle_packet((build_hs_pack(conclusion.clone()), from))
+            .unwrap();
+
+        match l.reject(RejectReason::Peer) {
+            Some((Packet::Control(control), to)) => {
+                assert_eq!(to, from);
+                assert_eq!(control.dest_sockid, conclusion.socket_id);
+                match control.control_type {
+                    ControlTypes::Handshake(shake) => {
+                        assert_eq!(shake.shake_type, ShakeType::Rejection(RejectReason::Peer))
+                    }
+                    other => panic!("Expected a handshake, got {:?}", other),
+                }
+            }
+            other => panic!("Expected a rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_conclusion() {
+        let mut l = test_listen();
+
+        let from = "127.0.0.1:8765".parse().unwrap();
+        l.handle_packet((build_hs_pack(test_induction()), from))
+            .unwrap();
+        let conclusion = test_conclusion();
+        let resp = l
+            .handle_packet((build_hs_pack(conclusion.clone()), from))
+            .unwrap();
+        assert!(resp.is_some());
+
+        // the response was lost, the caller sends its conclusion again
+        let again = l
+            .handle_packet((build_hs_pack(conclusion.clone()), from))
+            .unwrap();
+        assert_eq!(again, resp);
+
+        // another caller, or another connection of the same one, isn't answered
+        let other = "127.0.0.1:8766".parse().unwrap();
+        let resp = l.handle_packet((build_hs_pack(conclusion.clone()), other));
+        assert!(matches!(resp, Ok(None)), "{:?}", resp);
+        let resp = l.handle_packet((
+            build_hs_pack(HandshakeControlInfo {
+                init_seq_num: conclusion.init_seq_num + 1,
+                ..conclusion
+            }),
+            from,
+        ));
+        assert!(matches!(resp, Ok(None)), "{:?}", resp);
+    }
+
+    #[test]
+    fn reject_old_version() {
+        let mut l = Listen::new(ConnInitSettings {
+            min_peer_version: SrtVersion::new(99, 0, 0),
+            ..ConnInitSettings::default()
+        });
+
+        let resp = l.handle_packet((
+            build_hs_pack(test_induction()),
+            "127.0.0.1:8765".parse().unwrap(),
+        ));
+        assert!(matches!(resp, Ok(Some(_))));
+
+        let resp = l.handle_packet((
+            build_hs_pack(test_conclusion()),
+            "127.0.0.1:8765".parse().unwrap(),
+        ));
+        assert!(
+            matches!(resp,
+                Ok(Some((Packet::Control(ControlPacket{control_type: ControlTypes::Handshake(HandshakeControlInfo{shake_type: ShakeType::Rejection(RejectReason::Version), ..}), ..}), _)))),
+            "{:?}", resp
+        );
+        assert!(matches!(l.state(), ConclusionWait(_)));
+    }
+
+    #[test]
+    fn unsecure_caller() {
+        let crypto = Some(CryptoOptions::passphrase(16, "password123"));
+        let mut l = Listen::new(ConnInitSettings {
+            crypto: crypto.clone(),
+            ..ConnInitSettings::default()
+        });
+
+        let from = "127.0.0.1:8765".parse().unwrap();
+        l.handle_packet((build_hs_pack(test_induction()), from))
+            .unwrap();
+        let resp = l.handle_packet((build_hs_pack(test_conclusion()), from));
+        assert!(
+            matches!(resp,
+                Ok(Some((Packet::Control(ControlPacket{control_type: ControlTypes::Handshake(HandshakeControlInfo{shake_type: ShakeType::Rejection(RejectReason::Unsecure), ..}), ..}), _)))),
+            "{:?}", resp
+        );
+
+        let mut l = Listen::new(ConnInitSettings {
+            crypto,
+            enforced_encryption: false,
+            ..ConnInitSettings::default()
+        });
+        l.handle_packet((build_hs_pack(test_induction()), from))
+            .unwrap();
+        l.handle_packet((build_hs_pack(test_conclusion()), from))
+            .unwrap();
+        match l.state() {
+            Connected(_, settings) => {
+                assert_eq!(settings.send_km_state, KmState::NoSecret);
+                assert_eq!(settings.recv_km_state, KmState::Unsecured);
+            }
+            _ => panic!("Expected to be connected"),
+        }
+    }
+
     #[test]
     fn send_data_packet() {
         let mut l = test_listen();
@@ -377,6 +620,7 @@ mod test {
             ext_hs: None,
             ext_km: None,
             ext_config: None,
+            ext_other: vec![],
         };
 
         let resp = l.handle_packet((build_hs_pack(c), "127.0.0.1:8765".parse().unwrap()));
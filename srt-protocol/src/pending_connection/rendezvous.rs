@@ -3,7 +3,7 @@ use std::{cmp::Ordering, net::SocketAddr, time::Instant};
 use super::{
     cookie::gen_cookie,
     hsv5::{gen_hsv5_response, start_hsv5_initiation, StartedInitiator},
-    ConnInitSettings, ConnectError,
+    ConnInitSettings, ConnectError, HandshakeProgress,
 };
 
 use log::debug;
@@ -25,6 +25,9 @@ pub struct Rendezvous {
     cookie: i32,
     last_packet: (ControlPacket, SocketAddr),
     connection: Option<Connection>,
+    progress: HandshakeProgress,
+    // once the cookies were compared
+    won_cookie_contest: Option<bool>,
 }
 
 // see haivision/srt/docs/handshake.md for documentation
@@ -54,8 +57,8 @@ impl Rendezvous {
                 timestamp: TimeStamp::from_micros(0),
                 control_type: ControlTypes::Handshake(HandshakeControlInfo {
                     init_seq_num: init_settings.starting_send_seqnum,
-                    max_packet_size: 1500, // TODO: take as a parameter
-                    max_flow_size: 8192,   // TODO: take as a parameter
+                    max_packet_size: init_settings.max_packet_size,
+                    max_flow_size: 8192, // TODO: take as a parameter
                     socket_id: init_settings.local_sockid,
                     shake_type: ShakeType::Waveahand,
                     peer_addr: local_addr.ip(),
@@ -71,6 +74,8 @@ impl Rendezvous {
             cookie,
             last_packet,
             connection: None,
+            progress: HandshakeProgress::new(init_settings.connect_progress.clone()),
+            won_cookie_contest: None,
             init_settings,
             local_addr,
             remote_public,
@@ -118,6 +123,7 @@ impl Rendezvous {
         HandshakeVSInfo::V5 {
             crypto_size: 0,
             ext_config: None,
+            ext_other: vec![],
             ext_hs: None,
             ext_km: None,
         }
@@ -134,8 +140,8 @@ impl Rendezvous {
     fn gen_packet(&self, shake_type: ShakeType, info: HandshakeVSInfo) -> HandshakeControlInfo {
         HandshakeControlInfo {
             init_seq_num: self.init_settings.starting_send_seqnum,
-            max_packet_size: 1500, // TODO: take as a parameter
-            max_flow_size: 8192,   // TODO: take as a parameter
+            max_packet_size: self.init_settings.max_packet_size,
+            max_flow_size: 8192, // TODO: take as a parameter
             socket_id: self.init_settings.local_sockid,
             shake_type,
             peer_addr: self.local_addr.ip(),
@@ -169,7 +175,10 @@ impl Rendezvous {
         self.send(dest_sockid, self.gen_packet(ShakeType::Agreement, info))
     }
 
-    fn set_connected(&mut self, settings: ConnectionSettings, resp: Option<ControlTypes>) {
+    fn set_connected(&mut self, mut settings: ConnectionSettings, resp: Option<ControlTypes>) {
+        let now = self.init_settings.clock.now();
+        self.progress.finish(now, &mut settings.connect_stats);
+        settings.connect_stats.won_cookie_contest = self.won_cookie_contest;
         self.connection = Some(Connection {
             settings,
             handshake: Handshake::Rendezvous(resp),
@@ -185,6 +194,7 @@ impl Rendezvous {
             Ordering::Less => Responder,
             Ordering::Equal => return Err(CookiesMatched(self.cookie)),
         };
+        self.won_cookie_contest = Some(matches!(role, Initiator));
 
         match (info.shake_type, role) {
             (ShakeType::Waveahand, Initiator) => {
@@ -225,6 +235,7 @@ impl Rendezvous {
             }
             (ShakeType::Agreement, _) => Ok(None),
             (ShakeType::Induction, _) => Err(RendezvousExpected(info.clone())),
+            (ShakeType::Rejection(reason), _) => Err(Rejected(reason)),
         }
     }
 
@@ -361,7 +372,14 @@ impl Rendezvous {
         self.send_agreement(remote_sockid, Rendezvous::empty_flags())
     }
 
-    pub fn handle_packet(&mut self, (packet, from): (Packet, SocketAddr)) -> RendezvousResult {
+    pub fn handle_packet(&mut self, next: (Packet, SocketAddr)) -> RendezvousResult {
+        let now = self.init_settings.clock.now();
+        self.progress.received(now);
+        let result = self.dispatch_packet(next);
+        self.track(now, result)
+    }
+
+    fn dispatch_packet(&mut self, (packet, from): (Packet, SocketAddr)) -> RendezvousResult {
         if from != self.remote_public {
             return Err(UnexpectedHost(self.remote_public, from));
         }
@@ -381,6 +399,14 @@ impl Rendezvous {
     }
 
     pub fn handle_tick(&mut self, _now: Instant) -> RendezvousResult {
-        Ok(Some(self.last_packet.clone()))
+        let now = self.init_settings.clock.now();
+        self.track(now, Ok(Some(self.last_packet.clone())))
+    }
+
+    fn track(&mut self, now: Instant, result: RendezvousResult) -> RendezvousResult {
+        if let Ok(Some((control, _))) = &result {
+            self.progress.sent(now, &control.control_type);
+        }
+        result
     }
 }
@@ -1,5 +1,8 @@
-use crate::connection::ConnectionSettings;
+use crate::connection::{BreakCriteria, ConnectionSettings, TransferQuota};
+use crate::dump::ConnectionDump;
+use crate::packet::{ControlTypes, Packet};
 use crate::protocol::Timer;
+use crate::SeqNumber;
 
 use std::cmp::min;
 use std::time::{Duration, Instant};
@@ -16,12 +19,29 @@ pub struct Connection {
     // this isn't in the spec, but it's in the reference implementation
     // https://github.com/Haivision/srt/blob/1d7b391905d7e344d80b86b39ac5c90fda8764a9/srtcore/core.cpp#L10610-L10614
     keepalive_timer: Timer,
+
+    /// When the last packet was received from the peer
+    last_heard: Instant,
+
+    /// NAKs received since the acknowledged sequence number last moved forward
+    nak_storm: u32,
+    last_ack_number: Option<SeqNumber>,
+
+    break_criteria: BreakCriteria,
+
+    /// What the connection can transfer, and what it did so far, as bytes and packets
+    quota: TransferQuota,
+    quota_deadline: Option<Instant>,
+    sent: (u64, u64),
+    received: (u64, u64),
 }
 
 pub enum ConnectionAction {
     ContinueUntil(Instant),
     SendKeepAlive,
     Close, // due to timeout
+    /// The connection transferred all its [`TransferQuota`] allows
+    QuotaExceeded,
 }
 
 impl Connection {
@@ -31,21 +51,65 @@ impl Connection {
             exp_timer: Timer::new(Duration::from_millis(500), conn.socket_start_time),
             // 1s period https://github.com/Haivision/srt/blob/1d7b391905d7e344d80b86b39ac5c90fda8764a9/srtcore/core.h#L647
             keepalive_timer: Timer::new(Duration::from_secs(1), conn.socket_start_time),
+            last_heard: conn.socket_start_time,
+            nak_storm: 0,
+            last_ack_number: None,
+            break_criteria: conn.break_criteria,
+            quota: conn.transfer_quota,
+            quota_deadline: conn
+                .transfer_quota
+                .duration
+                .map(|duration| conn.socket_start_time + duration),
+            sent: (0, 0),
+            received: (0, 0),
         }
     }
-    pub fn on_packet(&mut self, now: Instant) {
+    pub fn on_packet(&mut self, now: Instant, packet: &Packet) {
         self.exp_count = 1;
         self.exp_timer.reset(now);
+        self.last_heard = now;
+
+        if let Packet::Data(data) = packet {
+            self.received.0 += data.payload.len() as u64;
+            self.received.1 += 1;
+        }
+        if let Packet::Control(ctrl) = packet {
+            match &ctrl.control_type {
+                ControlTypes::Nak(_) => self.nak_storm += 1,
+                ControlTypes::Ack(ack) if self.last_ack_number < Some(ack.ack_number) => {
+                    self.last_ack_number = Some(ack.ack_number);
+                    self.nak_storm = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+    /// A snapshot of the timers, see [`crate::DebugDump`]
+    pub fn dump(&self) -> ConnectionDump {
+        ConnectionDump {
+            exp_count: self.exp_count,
+            next_exp: self.exp_timer.next_instant(),
+            next_keepalive: self.keepalive_timer.next_instant(),
+            last_heard: self.last_heard,
+            nak_storm: self.nak_storm,
+        }
     }
     pub fn on_send(&mut self, now: Instant) {
         self.keepalive_timer.reset(now);
     }
+    /// Count a packet sent against the [`TransferQuota`]
+    pub fn on_packet_sent(&mut self, packet: &Packet) {
+        if let Packet::Data(data) = packet {
+            self.sent.0 += data.payload.len() as u64;
+            self.sent.1 += 1;
+        }
+    }
     pub fn next_action(&mut self, now: Instant) -> ConnectionAction {
         if let Some(exp) = self.exp_timer.check_expired(now) {
             self.exp_count += 1;
             info!("Exp event hit, exp count={}", self.exp_count);
-            if self.exp_count == 16 {
-                info!("16 exps, timeout!");
+            if self.exp_count == self.break_criteria.max_exp_count {
+                info!("{} exps, timeout!", self.exp_count);
             }
             self.exp_timer.reset(exp)
         }
@@ -53,13 +117,49 @@ impl Connection {
             self.keepalive_timer.reset(exp);
             return ConnectionAction::SendKeepAlive;
         }
-        if self.exp_count >= 16 {
+        if self.is_broken(now) {
             ConnectionAction::Close
+        } else if self.is_over_quota(now) {
+            ConnectionAction::QuotaExceeded
         } else {
-            ConnectionAction::ContinueUntil(min(
+            let next = min(
                 self.exp_timer.next_instant(),
                 self.keepalive_timer.next_instant(),
-            ))
+            );
+            ConnectionAction::ContinueUntil(self.quota_deadline.map_or(next, |d| min(next, d)))
+        }
+    }
+
+    fn is_over_quota(&self, now: Instant) -> bool {
+        let quota = &self.quota;
+        let over = |used: u64, limit: Option<u64>| matches!(limit, Some(limit) if used >= limit);
+        let over = over(self.sent.0, quota.send_bytes)
+            || over(self.sent.1, quota.send_packets)
+            || over(self.received.0, quota.recv_bytes)
+            || over(self.received.1, quota.recv_packets)
+            || matches!(self.quota_deadline, Some(deadline) if now >= deadline);
+        if over {
+            info!(
+                "Transfer quota {:?} exceeded, sent {:?} and received {:?} bytes and packets",
+                quota, self.sent, self.received
+            );
+        }
+        over
+    }
+
+    fn is_broken(&self, now: Instant) -> bool {
+        let criteria = &self.break_criteria;
+        if self.exp_count >= criteria.max_exp_count
+            && now - self.last_heard >= criteria.peer_idle_timeout
+        {
+            return true;
+        }
+        match criteria.max_nak_storm {
+            Some(max) if self.nak_storm >= max => {
+                info!("{} NAKs without progress, breaking", self.nak_storm);
+                true
+            }
+            _ => false,
         }
     }
 }
@@ -69,3 +169,246 @@ impl Connection {
 //     4 * rtt + rtt_var + self.syn.period(),
 //     Duration::from_millis(500),
 // ));
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use crate::packet::{
+        AckControlInfo, ControlPacket, DataEncryption, DataPacket, PacketLocation, SrtShakeFlags,
+    };
+    use crate::protocol::TimeStamp;
+    use crate::{
+        KmState, MsgNumber, RetransmitAlgorithm, SendDropPolicy, SocketID, SrtVersion, SystemClock,
+        TransmissionType,
+    };
+
+    fn test_connection(break_criteria: BreakCriteria, start: Instant) -> Connection {
+        Connection::new(ConnectionSettings {
+            remote: ([127, 0, 0, 1], 2223).into(),
+            remote_sockid: SocketID(1),
+            local_sockid: SocketID(2),
+            socket_start_time: start,
+            clock: Arc::new(SystemClock),
+            init_send_seq_num: SeqNumber::new_truncate(0),
+            init_recv_seq_num: SeqNumber::new_truncate(0),
+            max_packet_size: 1316,
+            max_flow_size: 8192,
+            send_tsbpd_latency: Duration::from_millis(120),
+            recv_tsbpd_latency: Duration::from_millis(120),
+            crypto_manager: None,
+            send_km_state: KmState::Unsecured,
+            recv_km_state: KmState::Unsecured,
+            transmission_type: TransmissionType::Live,
+            retransmit_algorithm: RetransmitAlgorithm::Aggressive,
+            packet_budget: 64,
+            max_message_size: 8 * 1024 * 1024,
+            peer_version: SrtVersion::CURRENT,
+            peer_flags: SrtShakeFlags::SUPPORTED,
+            control_packet_handler: None,
+            packet_tap: None,
+            send_buffer_monitor: None,
+            data_idle_monitor: None,
+            stall_monitor: None,
+            mtu_blackhole_monitor: None,
+            break_criteria,
+            rate_limit: None,
+            rate_limit_control: Default::default(),
+            retransmit_budget: None,
+            ack_interval: None,
+            nak_interval: None,
+            send_drop_policy: SendDropPolicy::default(),
+            source_validation: Default::default(),
+            transfer_quota: Default::default(),
+            linger: None,
+            recv_timeout: None,
+            send_timeout: None,
+            app_protocol: None,
+            peer_extensions: vec![],
+            connect_stats: Default::default(),
+        })
+    }
+
+    fn control(control_type: ControlTypes) -> Packet {
+        Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(2),
+            control_type,
+        })
+    }
+
+    fn ack(ack_number: u32) -> Packet {
+        control(ControlTypes::Ack(AckControlInfo {
+            ack_seq_num: 1,
+            ack_number: SeqNumber::new_truncate(ack_number),
+            rtt: None,
+            rtt_variance: None,
+            buffer_available: None,
+            packet_recv_rate: None,
+            est_link_cap: None,
+            byte_recv_rate: None,
+        }))
+    }
+
+    fn data(payload: &'static [u8]) -> Packet {
+        Packet::Data(DataPacket {
+            seq_number: SeqNumber::new_truncate(0),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber::new_truncate(0),
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(2),
+            payload: Bytes::from_static(payload),
+        })
+    }
+
+    fn over_quota(connection: &mut Connection, now: Instant) -> bool {
+        matches!(connection.next_action(now), ConnectionAction::QuotaExceeded)
+    }
+
+    // when the connection breaks, if the peer is silent from the start
+    fn broken_after(connection: &mut Connection, start: Instant) -> Option<Duration> {
+        let mut now = start;
+        while now - start < Duration::from_secs(60) {
+            match connection.next_action(now) {
+                ConnectionAction::Close => return Some(now - start),
+                ConnectionAction::ContinueUntil(t) => now = t,
+                ConnectionAction::SendKeepAlive | ConnectionAction::QuotaExceeded => {}
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn default_timeout() {
+        let start = Instant::now();
+        let mut connection = test_connection(BreakCriteria::default(), start);
+        assert_eq!(
+            broken_after(&mut connection, start),
+            Some(Duration::from_millis(7_500))
+        );
+    }
+
+    #[test]
+    fn peer_idle_timeout() {
+        let start = Instant::now();
+        let mut connection = test_connection(
+            BreakCriteria {
+                peer_idle_timeout: Duration::from_secs(30),
+                ..BreakCriteria::default()
+            },
+            start,
+        );
+        assert_eq!(
+            broken_after(&mut connection, start),
+            Some(Duration::from_secs(30))
+        );
+
+        // the silence only counts from the last packet
+        let mut connection = test_connection(
+            BreakCriteria {
+                peer_idle_timeout: Duration::from_secs(30),
+                ..BreakCriteria::default()
+            },
+            start,
+        );
+        let heard = start + Duration::from_secs(10);
+        connection.on_packet(heard, &control(ControlTypes::KeepAlive));
+        assert_eq!(
+            broken_after(&mut connection, heard),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn nak_storm() {
+        let start = Instant::now();
+        let mut connection = test_connection(
+            BreakCriteria {
+                max_nak_storm: Some(3),
+                ..BreakCriteria::default()
+            },
+            start,
+        );
+        let nak = control(ControlTypes::Nak(vec![5]));
+
+        connection.on_packet(start, &ack(5));
+        connection.on_packet(start, &nak);
+        connection.on_packet(start, &nak);
+        // an ACK that doesn't move forward doesn't end the storm
+        connection.on_packet(start, &ack(5));
+        assert!(matches!(
+            connection.next_action(start),
+            ConnectionAction::ContinueUntil(_)
+        ));
+
+        // one that does
+        connection.on_packet(start, &ack(6));
+        connection.on_packet(start, &nak);
+        connection.on_packet(start, &nak);
+        assert!(matches!(
+            connection.next_action(start),
+            ConnectionAction::ContinueUntil(_)
+        ));
+
+        connection.on_packet(start, &nak);
+        assert!(matches!(
+            connection.next_action(start),
+            ConnectionAction::Close
+        ));
+    }
+
+    #[test]
+    fn byte_quota() {
+        let start = Instant::now();
+        let mut connection = test_connection(BreakCriteria::default(), start);
+        connection.quota = TransferQuota {
+            send_bytes: Some(8),
+            recv_packets: Some(3),
+            ..TransferQuota::default()
+        };
+
+        // control packets don't count
+        connection.on_packet_sent(&control(ControlTypes::KeepAlive));
+        connection.on_packet_sent(&data(b"asdf"));
+        connection.on_packet(start, &data(b"asdf"));
+        connection.on_packet(start, &data(b"asdf"));
+        assert!(!over_quota(&mut connection, start));
+
+        connection.on_packet_sent(&data(b"asdf"));
+        assert!(over_quota(&mut connection, start));
+
+        let mut connection = test_connection(BreakCriteria::default(), start);
+        connection.quota.recv_packets = Some(3);
+        for _ in 0..3 {
+            assert!(!over_quota(&mut connection, start));
+            connection.on_packet(start, &data(b"asdf"));
+        }
+        assert!(over_quota(&mut connection, start));
+    }
+
+    #[test]
+    fn duration_quota() {
+        let start = Instant::now();
+        let mut connection = test_connection(BreakCriteria::default(), start);
+        connection.quota_deadline = Some(start + Duration::from_millis(1_200));
+
+        // the deadline wakes the connection up, between keepalives
+        let mut now = start;
+        loop {
+            match connection.next_action(now) {
+                ConnectionAction::ContinueUntil(t) => now = t,
+                ConnectionAction::SendKeepAlive => {}
+                ConnectionAction::QuotaExceeded => break,
+                ConnectionAction::Close => panic!("timed out instead"),
+            }
+        }
+        assert_eq!(now - start, Duration::from_millis(1_200));
+    }
+}
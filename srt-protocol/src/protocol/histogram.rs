@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+
+/// Percentiles of a latency over the whole connection, which unlike its mean show the tail spikes that make packets
+/// arrive too late
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatencyPercentiles {
+    /// How many latencies were recorded
+    pub samples: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+/// A histogram of latencies, to within 1% from a microsecond to a minute, longer ones counted as a minute
+#[derive(Clone)]
+pub struct LatencyHistogram(Histogram<u64>);
+
+impl LatencyHistogram {
+    const MAX_MICROS: u64 = 60_000_000;
+
+    pub fn new() -> Self {
+        LatencyHistogram(Histogram::new_with_bounds(1, Self::MAX_MICROS, 2).unwrap())
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u128::from(Self::MAX_MICROS)) as u64;
+        self.0.saturating_record(micros.max(1));
+    }
+
+    /// How many latencies were recorded
+    pub fn samples(&self) -> u64 {
+        self.0.len()
+    }
+
+    /// The latency `quantile` of those recorded are at or below, zero if there are none
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        if self.0.is_empty() {
+            return Duration::from_secs(0);
+        }
+        Duration::from_micros(self.0.value_at_quantile(quantile))
+    }
+
+    pub fn percentiles(&self) -> LatencyPercentiles {
+        LatencyPercentiles {
+            samples: self.samples(),
+            p50: self.quantile(0.5),
+            p90: self.quantile(0.9),
+            p99: self.quantile(0.99),
+            p999: self.quantile(0.999),
+            max: self.quantile(1.0),
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    // within the 1% the histogram keeps to
+    fn assert_near(actual: Duration, expected: Duration) {
+        let error = (actual.as_secs_f64() - expected.as_secs_f64()).abs();
+        assert!(
+            error <= expected.as_secs_f64() / 100.,
+            "{:?} isn't near {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentiles(), LatencyPercentiles::default());
+    }
+
+    #[test]
+    fn tail() {
+        let mut histogram = LatencyHistogram::new();
+        // mostly 20ms, with a spike to 300ms in one of every hundred
+        for i in 0..10_000 {
+            histogram.record(if i % 100 == 0 { ms(300) } else { ms(20) });
+        }
+
+        let percentiles = histogram.percentiles();
+        assert_eq!(percentiles.samples, 10_000);
+        assert_near(percentiles.p50, ms(20));
+        assert_near(percentiles.p90, ms(20));
+        assert_near(percentiles.p99, ms(20));
+        assert_near(percentiles.p999, ms(300));
+        assert_near(percentiles.max, ms(300));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_secs(0));
+        histogram.record(Duration::from_secs(3600));
+
+        assert_eq!(histogram.samples(), 2);
+        assert_eq!(histogram.quantile(0.0), Duration::from_micros(1));
+        assert_near(histogram.quantile(1.0), Duration::from_secs(60));
+    }
+}
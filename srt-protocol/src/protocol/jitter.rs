@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use super::TimeStamp;
+
+/// The inter-arrival jitter of RFC 3550, section 6.4.1: how much the time packets take to arrive varies, smoothed over
+/// the last 16 or so
+///
+/// The time a packet took, its transit, is when it arrived on the receiver's clock less its timestamp on the sender's.
+/// That's off by the difference between the clocks, which cancels out between packets, so only the variation counts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterarrivalJitter {
+    last_transit: Option<u32>,
+    // in microseconds, as a float so the small steps of the smoothing aren't rounded away
+    jitter: f64,
+}
+
+impl InterarrivalJitter {
+    /// Account for a packet sent at `timestamp` that arrived at `arrived`. Retransmitted packets shouldn't be counted,
+    /// as they were held up on purpose.
+    pub fn on_packet(&mut self, arrived: TimeStamp, timestamp: TimeStamp) {
+        let transit = arrived.as_micros().wrapping_sub(timestamp.as_micros());
+        if let Some(last) = self.last_transit.replace(transit) {
+            let diff = f64::from((transit.wrapping_sub(last) as i32).unsigned_abs());
+            self.jitter += (diff - self.jitter) / 16.0;
+        }
+    }
+
+    pub fn jitter(&self) -> Duration {
+        Duration::from_micros(self.jitter as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ts(us: u32) -> TimeStamp {
+        TimeStamp::from_micros(us)
+    }
+
+    #[test]
+    fn steady() {
+        let mut jitter = InterarrivalJitter::default();
+        // a constant delay, on a clock far ahead
+        for i in 0..100 {
+            jitter.on_packet(ts(5_000_000 + i * 1000), ts(i * 1000));
+        }
+        assert_eq!(jitter.jitter(), Duration::from_micros(0));
+    }
+
+    #[test]
+    fn varying() {
+        let mut jitter = InterarrivalJitter::default();
+        // alternately 10ms and 30ms late, across the timestamps wrapping
+        for i in 0..1000u32 {
+            let sent = (u32::MAX - 500_000).wrapping_add(i * 1000);
+            let delay = if i % 2 == 0 { 10_000 } else { 30_000 };
+            jitter.on_packet(ts(sent.wrapping_add(delay)), ts(sent));
+        }
+        let jitter = jitter.jitter().as_micros();
+        assert!(jitter > 19_000 && jitter <= 20_000, "{}", jitter);
+    }
+}
@@ -3,25 +3,28 @@ use std::cmp::{min, Ordering};
 use std::collections::VecDeque;
 use std::iter::Iterator;
 use std::net::SocketAddr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use bytes::{Bytes, BytesMut};
 use log::{debug, error, info, trace, warn};
 
 use super::TimeSpan;
+use crate::dump::ReceiverDump;
 use crate::loss_compression::compress_loss_list;
 use crate::packet::{
     AckControlInfo, ControlPacket, ControlTypes, DataEncryption, DataPacket, HandshakeControlInfo,
     Packet, SrtControlPacket,
 };
 use crate::protocol::handshake::Handshake;
+#[cfg(feature = "histograms")]
+use crate::protocol::histogram::{LatencyHistogram, LatencyPercentiles};
+use crate::protocol::jitter::InterarrivalJitter;
 use crate::protocol::TimeStamp;
-use crate::{seq_number::seq_num_range, ConnectionSettings, SeqNumber};
+use crate::{ConnectionSettings, DataIdleEvent, KmState, SeqNumber};
+use srt_packet::{seq_num_range, RecvBuffer};
 
-mod buffer;
 mod time;
 
-use buffer::RecvBuffer;
 use time::{ReceiveTimers, RTT};
 
 #[derive(Debug, Clone)]
@@ -33,6 +36,53 @@ pub enum ReceiverAlgorithmAction {
     Close,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceiverMetrics {
+    /// Messages dropped because they were larger than the max message size
+    pub oversized_messages: u32,
+
+    /// Packets dropped because they weren't part of a valid message
+    pub invalid_message_packets: u32,
+
+    /// Packets that were still missing when the messages after them were due, and were given up on
+    pub too_late_packets: u32,
+
+    /// Packets dropped because they were garbage, either failing to parse or with an impossible payload size
+    pub malformed_packets: u32,
+
+    /// Packets dropped because they came from another address than the peer's, see
+    /// [`SourceValidation`](crate::SourceValidation)
+    pub spoofed_packets: u32,
+
+    /// How many times no data arrived for the timeout of the [`DataIdleMonitor`](crate::DataIdleMonitor)
+    pub data_idle_periods: u32,
+
+    /// The latency messages are released with, which can grow during the connection
+    pub latency: Duration,
+
+    /// The end-to-end delay messages are delivered with, averaged over the last few: from when the sender was given
+    /// them, going by their timestamps on the drift corrected peer clock, to when they were released, plus half the
+    /// round trip time for the way there. Above `latency` when messages are released late.
+    pub delivery_delay: Duration,
+
+    /// The largest end-to-end delay a message was delivered with, see `delivery_delay`
+    pub max_delivery_delay: Duration,
+
+    /// How much the time data packets take to arrive varies, the inter-arrival jitter of RFC 3550, see
+    /// [`InterarrivalJitter`]. The latency needs to cover it, with some margin.
+    pub jitter: Duration,
+
+    /// Percentiles of the round trip times measured, refreshed with each ACK
+    #[cfg(feature = "histograms")]
+    pub rtt_percentiles: LatencyPercentiles,
+
+    /// Percentiles of the end-to-end delays messages were delivered with, see `delivery_delay`, refreshed with each
+    /// ACK
+    #[cfg(feature = "histograms")]
+    pub delivery_delay_percentiles: LatencyPercentiles,
+}
+
 struct LossListEntry {
     seq_num: SeqNumber,
 
@@ -87,8 +137,8 @@ pub struct Receiver {
     /// PKT History Window: A circular array that records the arrival time
     /// of each data packet.
     ///
-    /// First is sequence number, second is timestamp
-    packet_history_window: Vec<(SeqNumber, TimeStamp)>,
+    /// First is sequence number, second is timestamp, third is the payload size
+    packet_history_window: Vec<(SeqNumber, TimeStamp, usize)>,
 
     /// https://tools.ietf.org/html/draft-gg-udt-03#page-12
     /// Packet Pair Window: A circular array that records the time
@@ -111,10 +161,26 @@ pub struct Receiver {
     lr_ack_acked: (i32, SeqNumber),
 
     /// The buffer
-    receive_buffer: RecvBuffer,
+    receive_buffer: RecvBuffer<Instant>,
 
     /// Shutdown flag. This is set so when the buffer is flushed, it returns Async::Ready(None)
     shutdown_flag: bool,
+
+    /// When the last data packet arrived, and if the stream was reported idle since
+    last_data: Instant,
+    data_idle: bool,
+
+    /// The changes in whether data is arriving, waiting to be taken by the host
+    data_idle_events: VecDeque<DataIdleEvent>,
+
+    jitter: InterarrivalJitter,
+
+    #[cfg(feature = "histograms")]
+    rtt_histogram: LatencyHistogram,
+    #[cfg(feature = "histograms")]
+    delivery_delay_histogram: LatencyHistogram,
+
+    metrics: ReceiverMetrics,
 }
 
 impl Receiver {
@@ -128,7 +194,11 @@ impl Receiver {
 
         Receiver {
             settings: settings.clone(),
-            timers: ReceiveTimers::new(settings.socket_start_time),
+            timers: ReceiveTimers::new(
+                settings.socket_start_time,
+                settings.ack_interval,
+                settings.nak_interval,
+            ),
             control_packets: VecDeque::new(),
             data_release: VecDeque::new(),
             handshake,
@@ -141,17 +211,101 @@ impl Receiver {
             next_ack: 1,
             probe_time: None,
             lr_ack_acked: (0, init_seq_num),
-            receive_buffer: RecvBuffer::with(&settings),
+            receive_buffer: RecvBuffer::new(
+                settings.init_recv_seq_num,
+                settings.socket_start_time,
+                settings.recv_tsbpd_latency,
+                settings.max_message_size,
+                settings.max_flow_size as usize,
+            ),
             shutdown_flag: false,
+            last_data: settings.socket_start_time,
+            data_idle: false,
+            data_idle_events: VecDeque::new(),
+            jitter: InterarrivalJitter::default(),
+            #[cfg(feature = "histograms")]
+            rtt_histogram: LatencyHistogram::new(),
+            #[cfg(feature = "histograms")]
+            delivery_delay_histogram: LatencyHistogram::new(),
+            metrics: ReceiverMetrics {
+                latency: settings.recv_tsbpd_latency,
+                ..ReceiverMetrics::default()
+            },
         }
     }
 
+    pub fn metrics(&self) -> ReceiverMetrics {
+        self.metrics
+    }
+
+    /// The sequence number after the largest one received
+    pub fn lrsn(&self) -> SeqNumber {
+        self.lrsn
+    }
+
+    /// A snapshot of the buffer, loss list and timers, see [`crate::DebugDump`]
+    pub fn dump(&self) -> ReceiverDump {
+        ReceiverDump {
+            next_release: self.receive_buffer.next_release(),
+            buffer_slots: self.receive_buffer.slots(),
+            buffered_packets: self.receive_buffer.buffered_packets(),
+            loss_list: self.loss_list.iter().map(|e| e.seq_num).collect(),
+            lrsn: self.lrsn,
+            lr_ack_acked: self.lr_ack_acked.1,
+            rtt: self.rtt.mean(),
+            rtt_variance: self.rtt.variance(),
+            next_ack: self.timers.ack.next_instant(),
+            next_nak: self.timers.nak.next_instant(),
+            shutdown: self.shutdown_flag,
+        }
+    }
+
+    /// Grow the latency messages are released with, see [`RecvBuffer::set_latency`]
+    pub fn set_latency(&mut self, latency: Duration, now: Instant) {
+        info!(
+            "{:?}: growing the latency to {:?}",
+            self.settings.local_sockid, latency
+        );
+        self.receive_buffer.set_latency(latency, now);
+    }
+
     pub fn handle_shutdown(&mut self) {
         self.shutdown_flag = true;
     }
 
+    /// Count a packet that couldn't be parsed
+    pub fn handle_malformed_packet(&mut self) {
+        self.metrics.malformed_packets += 1;
+    }
+
+    /// Count a packet for this connection from `from`, which isn't the peer's address
+    pub fn handle_spoofed_packet(&mut self, from: SocketAddr) {
+        debug!(
+            "{:?}: dropping packet from {}, the peer is at {}",
+            self.settings.local_sockid, from, self.settings.remote
+        );
+        self.metrics.spoofed_packets += 1;
+    }
+
+    /// Take packets from and send them to `remote` from now on, see [`SourceValidation`](crate::SourceValidation)
+    pub fn set_remote(&mut self, remote: SocketAddr) {
+        self.settings.remote = remote;
+    }
+
     // handles an incoming a packet
-    pub fn handle_packet(&mut self, now: Instant, (packet, from): (Packet, SocketAddr)) {
+    pub fn handle_packet(&mut self, now: Instant, packet: (Packet, SocketAddr)) {
+        self.handle_timestamped_packet(now, now, packet)
+    }
+
+    /// Handle a packet that arrived at `arrived`, before `now`, like when the OS timestamped it on reception. The
+    /// arrival time is used for the arrival speed, link capacity and clock drift estimates, so they don't depend on
+    /// how long the packet waited to be handled.
+    pub fn handle_timestamped_packet(
+        &mut self,
+        now: Instant,
+        arrived: Instant,
+        (packet, from): (Packet, SocketAddr),
+    ) {
         // We don't care about packets from elsewhere
         if from != self.settings.remote {
             info!("Packet received from unknown address: {:?}", from);
@@ -172,13 +326,16 @@ impl Receiver {
 
         match packet {
             Packet::Control(ctrl) => {
-                self.receive_buffer.synchronize_clock(now, ctrl.timestamp);
+                self.receive_buffer
+                    .synchronize_clock(arrived, ctrl.timestamp);
 
                 // handle the control packet
                 match ctrl.control_type {
                     ControlTypes::Ack { .. } => warn!("Receiver received ACK packet, unusual"),
                     ControlTypes::Ack2(seq_num) => self.handle_ack2(seq_num, now),
-                    ControlTypes::DropRequest { .. } => unimplemented!(),
+                    ControlTypes::DropRequest { first, last, .. } => {
+                        self.handle_drop_request(now, first, last)
+                    }
                     ControlTypes::Handshake(shake) => self.handle_handshake_packet(now, shake),
                     ControlTypes::KeepAlive => {} // TODO: actually reset EXP etc
                     ControlTypes::Nak { .. } => warn!("Receiver received NAK packet, unusual"),
@@ -192,9 +349,20 @@ impl Receiver {
                     ControlTypes::Srt(srt_packet) => {
                         self.handle_srt_control_packet(srt_packet);
                     }
+                    ControlTypes::Custom { custom_type, .. } => {
+                        debug!("Receiver ignoring custom control packet {:#x}", custom_type)
+                    }
                 }
             }
-            Packet::Data(data) => self.handle_data_packet(data, now),
+            Packet::Data(data) if data.payload.len() > self.settings.max_packet_size as usize => {
+                warn!(
+                    "Dropping data packet {:?} with a {} byte payload, larger than the max packet size",
+                    data.seq_number,
+                    data.payload.len()
+                );
+                self.handle_malformed_packet();
+            }
+            Packet::Data(data) => self.handle_data_packet(data, now, arrived),
         };
     }
 
@@ -209,11 +377,17 @@ impl Receiver {
         //      ACK, also check the ACK packet interval.
         if self.timers.ack.check_expired(now).is_some() {
             self.on_ack_event(now);
+            #[cfg(feature = "histograms")]
+            self.refresh_percentiles();
         }
-        if self.timers.nak.check_expired(now).is_some() {
+        if self.timers.nak.check_expired(now).is_some()
+            && self.settings.transmission_type.nak_report()
+        {
             self.on_nak_event(now);
         }
 
+        self.check_data_idle(now);
+
         if let Some(data) = self.pop_data(now) {
             OutputData(data)
         } else if let Some(Packet::Control(packet)) = self.pop_conotrol_packet() {
@@ -226,6 +400,11 @@ impl Receiver {
         }
     }
 
+    /// The next change in whether data is arriving, for the [`DataIdleMonitor`](crate::DataIdleMonitor)
+    pub fn pop_data_idle_event(&mut self) -> Option<DataIdleEvent> {
+        self.data_idle_events.pop_front()
+    }
+
     pub fn is_flushed(&self) -> bool {
         self.receive_buffer.next_msg_ready().is_none()
             && self.lr_ack_acked.1 == self.receive_buffer.next_release() // packets have been acked and all acks have been acked (ack2)
@@ -321,6 +500,17 @@ impl Receiver {
             }
         } as u32;
 
+        // the same, in bytes, going by the average payload of these packets
+        let byte_recv_rate = if self.packet_history_window.len() < 16 {
+            0
+        } else {
+            let bytes: usize = self.packet_history_window[self.packet_history_window.len() - 16..]
+                .iter()
+                .map(|&(_, _, len)| len)
+                .sum();
+            (u64::from(packet_recv_rate) * bytes as u64 / 16) as u32
+        };
+
         // 5) Calculate the estimated link capacity according to the following algorithm:
         let est_link_cap = {
             if self.packet_pair_window.len() < 16 {
@@ -346,6 +536,8 @@ impl Receiver {
 
         // Pack the ACK packet with RTT, RTT Variance, and flow window size (available
         // receiver buffer size).
+        let buffer_available =
+            (self.settings.max_flow_size as usize).saturating_sub(self.receive_buffer.slots());
 
         self.send_control(
             now,
@@ -354,9 +546,10 @@ impl Receiver {
                 ack_number,
                 rtt: Some(self.rtt.mean()),
                 rtt_variance: Some(self.rtt.variance()),
-                buffer_available: None, // TODO: add this
+                buffer_available: Some(buffer_available as i32),
                 packet_recv_rate: Some(packet_recv_rate),
                 est_link_cap: Some(est_link_cap),
+                byte_recv_rate: Some(byte_recv_rate),
             }),
         );
 
@@ -455,8 +648,11 @@ impl Receiver {
             //    departure time, and update the RTT value as: RTT = (RTT * 7 +
             //    rtt) / 8
             // 4) Update RTTVar by: RTTVar = (RTTVar * 3 + abs(RTT - rtt)) / 4.
-            self.rtt
-                .update(self.receive_buffer.timestamp_from(now) - send_timestamp);
+            let rtt = self.receive_buffer.timestamp_from(now) - send_timestamp;
+            self.rtt.update(rtt);
+            #[cfg(feature = "histograms")]
+            self.rtt_histogram
+                .record(Duration::from_micros(rtt.as_micros().max(0) as u64));
 
             // 5) Update both ACK and NAK period to 4 * RTT + RTTVar + SYN.
             self.timers.update_rtt(&self.rtt);
@@ -468,27 +664,64 @@ impl Receiver {
         }
     }
 
-    fn handle_data_packet(&mut self, mut data: DataPacket, now: Instant) {
+    fn handle_data_packet(&mut self, mut data: DataPacket, now: Instant, arrived: Instant) {
         let ts_now = self.receive_buffer.timestamp_from(now);
+        let ts_arrived = self.receive_buffer.timestamp_from(arrived);
+
+        if self.data_idle {
+            self.data_idle = false;
+            self.data_idle_events.push_back(DataIdleEvent::Resumed {
+                gap: arrived.saturating_duration_since(self.last_data),
+            });
+        }
+        self.last_data = max(self.last_data, arrived);
 
         // 2&3 don't apply
 
         // 4) If the sequence number of the current data packet is 16n + 1,
         //     where n is an integer, record the time interval between this
         if data.seq_number % 16 == 0 {
-            self.probe_time = Some(ts_now)
+            self.probe_time = Some(ts_arrived)
         } else if data.seq_number % 16 == 1 {
             // if there is an entry
             if let Some(pt) = self.probe_time {
                 // calculate and insert
-                self.packet_pair_window.push((data.seq_number, ts_now - pt));
+                self.packet_pair_window
+                    .push((data.seq_number, ts_arrived - pt));
 
                 // reset
                 self.probe_time = None
             }
         }
         // 5) Record the packet arrival time in PKT History Window.
-        self.packet_history_window.push((data.seq_number, ts_now));
+        self.packet_history_window
+            .push((data.seq_number, ts_arrived, data.payload.len()));
+
+        if !data.retransmitted {
+            self.jitter.on_packet(ts_arrived, data.timestamp);
+            self.metrics.jitter = self.jitter.jitter();
+        }
+
+        // no room for it until the ones before it are released, so it's lost: asked for again instead of acknowledged
+        if !self.receive_buffer.has_room(data.seq_number) {
+            debug!(
+                "No room for packet {:?}, treating it as lost",
+                data.seq_number
+            );
+            if data.seq_number >= self.lrsn {
+                for seq_num in seq_num_range(self.lrsn, data.seq_number + 1) {
+                    self.loss_list.push(LossListEntry {
+                        seq_num,
+                        feedback_time: ts_now,
+                        k: 2,
+                    })
+                }
+                self.send_nak(now, seq_num_range(self.lrsn, data.seq_number + 1));
+                self.lrsn = data.seq_number + 1;
+            }
+            // a retransmission stays in the loss list, to be asked for again later
+            return;
+        }
 
         // 6)
         // a. If the sequence number of the current data packet is greater
@@ -546,6 +779,53 @@ impl Receiver {
         }
 
         self.receive_buffer.add(data);
+        self.drop_invalid_messages();
+    }
+
+    // the sender won't send these, so stop waiting and asking for them
+    fn handle_drop_request(&mut self, now: Instant, first: SeqNumber, last: SeqNumber) {
+        debug!(
+            "{:?}: sender dropped packets [{},{}]",
+            self.settings.local_sockid, first, last
+        );
+        self.loss_list
+            .retain(|ll| ll.seq_num < first || ll.seq_num > last);
+        if last >= self.lrsn {
+            // the packets before the dropped ones are still coming, and treated as lost if they don't
+            let ts_now = self.receive_buffer.timestamp_from(now);
+            for seq_num in seq_num_range(self.lrsn, first) {
+                self.loss_list.push(LossListEntry {
+                    seq_num,
+                    feedback_time: ts_now,
+                    k: 2,
+                })
+            }
+            self.lrsn = last + 1;
+        }
+        self.receive_buffer.drop_range(first, last);
+        self.forget_dropped_losses();
+    }
+
+    // keep the buffer from growing without bound with corrupt or malicious messages
+    fn drop_invalid_messages(&mut self) {
+        let (oversized, invalid) = self.receive_buffer.drop_invalid_messages();
+        self.metrics.oversized_messages += oversized as u32;
+        self.metrics.invalid_message_packets += invalid as u32;
+        if oversized > 0 || invalid > 0 {
+            self.forget_dropped_losses();
+        }
+    }
+
+    // packets before the head of the buffer can't be released anymore, so stop asking for them, which also lets the
+    // ACK number move past them for the sender to free its buffer
+    fn forget_dropped_losses(&mut self) {
+        let head = self.receive_buffer.next_release();
+        let dropped = self
+            .loss_list
+            .iter()
+            .take_while(|ll| ll.seq_num < head)
+            .count();
+        self.loss_list.drain(..dropped);
     }
 
     fn decrypt_packet(&self, data: &mut DataPacket) {
@@ -556,6 +836,14 @@ impl Receiver {
             }
             Some(cm) => cm,
         };
+        // without the peer's key, decrypting would only make things worse
+        if self.settings.recv_km_state != KmState::Secured {
+            error!(
+                "Can't decrypt packet {:?}, {:?}",
+                data.seq_number, self.settings.recv_km_state
+            );
+            return;
+        }
 
         // this requies an extra copy here...maybe DataPacket should have a BytesMut in it instead...
         let mut bm = BytesMut::with_capacity(data.payload.len());
@@ -577,26 +865,105 @@ impl Receiver {
     }
 
     fn pop_data(&mut self, now: Instant) -> Option<(Instant, Bytes)> {
-        // try to release packets
-        while let Some(d) = self.receive_buffer.next_msg_tsbpd(now) {
-            self.data_release.push_back(d);
+        let transmission_type = self.settings.transmission_type;
+
+        // try to release packets, only valid messages are kept at the front of the buffer
+        loop {
+            self.drop_invalid_messages();
+
+            let released = if transmission_type.tsbpd() {
+                self.receive_buffer.next_msg_tsbpd(now)
+            } else {
+                self.receive_buffer.next_msg(now)
+            };
+            match released {
+                Some(d) => {
+                    self.record_delivery_delay(now, d.0);
+                    self.data_release.push_back(d)
+                }
+                None => break,
+            }
         }
+        self.metrics.latency = self.receive_buffer.latency(now);
 
         // drop packets
-        // TODO: do something with this
-        let _dropped = self.receive_buffer.drop_too_late_packets(now);
+        if transmission_type.too_late_packet_drop() {
+            let dropped = self.receive_buffer.drop_too_late_packets(now);
+            if dropped > 0 {
+                self.metrics.too_late_packets += dropped as u32;
+                self.forget_dropped_losses();
+            }
+        }
 
         self.data_release.pop_front()
     }
 
+    // the time since the sender stamped the message, plus the one way delay the peer clock's origin doesn't include
+    fn record_delivery_delay(&mut self, now: Instant, origin_time: Instant) {
+        let delay = now.saturating_duration_since(origin_time) + self.rtt.mean_as_duration() / 2;
+        let metrics = &mut self.metrics;
+        metrics.delivery_delay = if metrics.delivery_delay == Duration::from_secs(0) {
+            delay
+        } else {
+            (metrics.delivery_delay * 7 + delay) / 8
+        };
+        metrics.max_delivery_delay = max(metrics.max_delivery_delay, delay);
+        #[cfg(feature = "histograms")]
+        self.delivery_delay_histogram.record(delay);
+    }
+
+    // going through the histograms only takes a moment, but more than it's worth for every packet
+    #[cfg(feature = "histograms")]
+    fn refresh_percentiles(&mut self) {
+        if self.rtt_histogram.samples() != self.metrics.rtt_percentiles.samples {
+            self.metrics.rtt_percentiles = self.rtt_histogram.percentiles();
+        }
+        if self.delivery_delay_histogram.samples()
+            != self.metrics.delivery_delay_percentiles.samples
+        {
+            self.metrics.delivery_delay_percentiles = self.delivery_delay_histogram.percentiles();
+        }
+    }
+
     fn pop_conotrol_packet(&mut self) -> Option<Packet> {
         self.control_packets.pop_front()
     }
 
+    // report the stream idle once no data arrived for the monitor's timeout
+    fn check_data_idle(&mut self, now: Instant) {
+        if !matches!(self.data_idle_deadline(), Some(deadline) if now >= deadline) {
+            return;
+        }
+        let idle_for = now - self.last_data;
+        info!(
+            "{:?}: no data received for {:?}",
+            self.settings.local_sockid, idle_for
+        );
+        self.data_idle = true;
+        self.metrics.data_idle_periods += 1;
+        self.data_idle_events
+            .push_back(DataIdleEvent::Idle { idle_for });
+    }
+
+    fn data_idle_deadline(&self) -> Option<Instant> {
+        if self.data_idle || self.shutdown_flag {
+            return None;
+        }
+        let monitor = self.settings.data_idle_monitor.as_ref()?;
+        Some(self.last_data + monitor.timeout())
+    }
+
     fn next_timer(&self, now: Instant) -> Instant {
-        match self.receive_buffer.next_message_release_time(now) {
-            Some(next_rel_time) => min(self.timers.next_timer(now), next_rel_time),
+        let timer = match self.data_idle_deadline() {
+            Some(deadline) => min(self.timers.next_timer(now), deadline),
             None => self.timers.next_timer(now),
+        };
+        if !self.settings.transmission_type.tsbpd() {
+            return timer;
+        }
+        match self.receive_buffer.next_message_release_time(now) {
+            Some(next_rel_time) => min(timer, next_rel_time),
+            None => timer,
         }
     }
 
@@ -609,3 +976,270 @@ impl Receiver {
             }));
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::packet::{PacketLocation, SrtShakeFlags};
+    use crate::{
+        MsgNumber, RetransmitAlgorithm, SendDropPolicy, SocketID, SrtVersion, SystemClock,
+        TransmissionType,
+    };
+
+    fn test_receiver(start: Instant) -> Receiver {
+        Receiver::new(
+            ConnectionSettings {
+                remote: ([127, 0, 0, 1], 2223).into(),
+                remote_sockid: SocketID(1),
+                local_sockid: SocketID(2),
+                socket_start_time: start,
+                clock: Arc::new(SystemClock),
+                init_send_seq_num: SeqNumber::new_truncate(0),
+                init_recv_seq_num: SeqNumber::new_truncate(0),
+                max_packet_size: 1316,
+                max_flow_size: 8192,
+                send_tsbpd_latency: Duration::from_millis(120),
+                recv_tsbpd_latency: Duration::from_millis(120),
+                crypto_manager: None,
+                send_km_state: KmState::Unsecured,
+                recv_km_state: KmState::Unsecured,
+                transmission_type: TransmissionType::Live,
+                retransmit_algorithm: RetransmitAlgorithm::Aggressive,
+                packet_budget: 64,
+                max_message_size: 8 * 1024 * 1024,
+                peer_version: SrtVersion::CURRENT,
+                peer_flags: SrtShakeFlags::SUPPORTED,
+                control_packet_handler: None,
+                packet_tap: None,
+                send_buffer_monitor: None,
+                data_idle_monitor: None,
+                stall_monitor: None,
+                mtu_blackhole_monitor: None,
+                break_criteria: Default::default(),
+                rate_limit: None,
+                rate_limit_control: Default::default(),
+                retransmit_budget: None,
+                ack_interval: None,
+                nak_interval: None,
+                send_drop_policy: SendDropPolicy::default(),
+                source_validation: Default::default(),
+                transfer_quota: Default::default(),
+                linger: None,
+                recv_timeout: None,
+                send_timeout: None,
+                app_protocol: None,
+                peer_extensions: vec![],
+                connect_stats: Default::default(),
+            },
+            Handshake::Connector,
+        )
+    }
+
+    fn data(seq: u32, receiver: &Receiver) -> (Packet, SocketAddr) {
+        let packet = Packet::Data(DataPacket {
+            seq_number: SeqNumber::new_truncate(seq),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber::new_truncate(seq),
+            timestamp: TimeStamp::from_micros(seq * 1_000),
+            dest_sockid: SocketID(2),
+            payload: Bytes::from_static(b"asdf"),
+        });
+        (packet, receiver.settings.remote)
+    }
+
+    // the first full ACK after `end`
+    fn reported_ack(receiver: &mut Receiver, end: Instant) -> Option<AckControlInfo> {
+        let mut now = end;
+        for _ in 0..100 {
+            match receiver.next_algorithm_action(now) {
+                ReceiverAlgorithmAction::SendControl(
+                    ControlPacket {
+                        control_type: ControlTypes::Ack(info),
+                        ..
+                    },
+                    _,
+                ) if info.packet_recv_rate.is_some() => return Some(info),
+                ReceiverAlgorithmAction::TimeBoundedReceive(t) => now = t,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn oversized_payload() {
+        let start = Instant::now();
+        let mut receiver = test_receiver(start);
+        let (mut packet, from) = data(0, &receiver);
+        if let Packet::Data(data) = &mut packet {
+            data.payload = Bytes::from(vec![0; 1317]);
+        }
+        receiver.handle_packet(start, (packet, from));
+        assert_eq!(receiver.metrics().malformed_packets, 1);
+
+        // it's as if it never arrived
+        let packet = data(1, &receiver);
+        receiver.handle_packet(start, packet);
+        assert_eq!(receiver.metrics().malformed_packets, 1);
+        assert_eq!(receiver.dump().loss_list, [SeqNumber::new_truncate(0)]);
+    }
+
+    #[test]
+    fn no_room() {
+        let start = Instant::now();
+        let receiver = test_receiver(start);
+        let mut receiver = Receiver::new(
+            ConnectionSettings {
+                max_flow_size: 4,
+                ..receiver.settings
+            },
+            Handshake::Connector,
+        );
+        for seq in 0..6 {
+            let packet = data(seq, &receiver);
+            receiver.handle_packet(start, packet);
+        }
+
+        // the ones past the flow window are lost, not acknowledged
+        let lost = [SeqNumber::new_truncate(4), SeqNumber::new_truncate(5)];
+        assert_eq!(receiver.dump().loss_list, lost);
+        assert_eq!(receiver.lrsn(), SeqNumber::new_truncate(6));
+        let ack = reported_ack(&mut receiver, start).unwrap();
+        assert_eq!(ack.ack_number, SeqNumber::new_truncate(4));
+
+        // and taken once releasing the first makes room again
+        released_at(&mut receiver, start);
+        let (mut packet, from) = data(4, &receiver);
+        if let Packet::Data(data) = &mut packet {
+            data.retransmitted = true;
+        }
+        receiver.handle_packet(start + Duration::from_millis(200), (packet, from));
+        assert_eq!(receiver.dump().loss_list, [SeqNumber::new_truncate(5)]);
+    }
+
+    #[test]
+    fn arrival_speed_from_timestamps() {
+        let start = Instant::now();
+        // a packet every ms, handled in pairs
+        let handled = |i: u32| start + Duration::from_millis(u64::from(i / 2 * 2 + 1));
+
+        let mut receiver = test_receiver(start);
+        for i in 0..32 {
+            let arrived = start + Duration::from_millis(u64::from(i));
+            let packet = data(i, &receiver);
+            receiver.handle_timestamped_packet(handled(i), arrived, packet);
+        }
+        let ack = reported_ack(&mut receiver, handled(31)).unwrap();
+        assert_eq!(ack.packet_recv_rate, Some(1_000));
+        // 4 byte payloads
+        assert_eq!(ack.byte_recv_rate, Some(4_000));
+        assert!(matches!(ack.buffer_available, Some(room) if room <= 8192 && room >= 8192 - 32));
+
+        // without the timestamps it looks like bursts
+        let mut receiver = test_receiver(start);
+        for i in 0..32 {
+            let packet = data(i, &receiver);
+            receiver.handle_packet(handled(i), packet);
+        }
+        assert_ne!(
+            reported_ack(&mut receiver, handled(31))
+                .unwrap()
+                .packet_recv_rate,
+            Some(1_000)
+        );
+    }
+
+    // when the receiver wants to be woken up next, after taking what it has to do now
+    fn next_wakeup(receiver: &mut Receiver, now: Instant) -> Instant {
+        loop {
+            if let ReceiverAlgorithmAction::TimeBoundedReceive(t) =
+                receiver.next_algorithm_action(now)
+            {
+                return t;
+            }
+        }
+    }
+
+    #[test]
+    fn data_idle() {
+        let start = Instant::now();
+        let mut receiver = test_receiver(start);
+        receiver.settings.data_idle_monitor = Some(crate::DataIdleMonitor::new(
+            Duration::from_millis(500),
+            |_| {},
+        ));
+
+        let arrived = start + Duration::from_millis(100);
+        receiver.handle_packet(arrived, data(0, &receiver));
+
+        // the receiver wakes up in time to notice the stream went idle
+        let idle_at = arrived + Duration::from_millis(500);
+        let mut now = arrived;
+        while now < idle_at {
+            assert_eq!(receiver.pop_data_idle_event(), None);
+            now = next_wakeup(&mut receiver, now);
+            assert!(now <= idle_at, "{:?}", now - idle_at);
+        }
+        let _ = next_wakeup(&mut receiver, now);
+        assert_eq!(
+            receiver.pop_data_idle_event(),
+            Some(DataIdleEvent::Idle {
+                idle_for: Duration::from_millis(500)
+            })
+        );
+
+        // only once
+        let _ = next_wakeup(&mut receiver, now + Duration::from_millis(100));
+        assert_eq!(receiver.pop_data_idle_event(), None);
+
+        // and when data comes back, with how long it was gone
+        let resumed = idle_at + Duration::from_millis(200);
+        receiver.handle_packet(resumed, data(1, &receiver));
+        assert_eq!(
+            receiver.pop_data_idle_event(),
+            Some(DataIdleEvent::Resumed {
+                gap: Duration::from_millis(700)
+            })
+        );
+        assert_eq!(receiver.metrics().data_idle_periods, 1);
+    }
+
+    // when the next message is released, going from wakeup to wakeup
+    fn released_at(receiver: &mut Receiver, from: Instant) -> Instant {
+        let mut now = from;
+        loop {
+            match receiver.next_algorithm_action(now) {
+                ReceiverAlgorithmAction::OutputData(_) => return now,
+                ReceiverAlgorithmAction::TimeBoundedReceive(t) => now = t,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn delivery_delay() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut receiver = test_receiver(start);
+        assert_eq!(receiver.metrics().latency, ms(120));
+
+        // released on time, half the default RTT of 10ms on top of the latency
+        receiver.handle_packet(start + ms(5), data(0, &receiver));
+        assert_eq!(released_at(&mut receiver, start + ms(5)), start + ms(120));
+        assert_eq!(receiver.metrics().delivery_delay, ms(125));
+
+        // a message that arrives after it was due is released late
+        receiver.handle_packet(start + ms(200), data(1, &receiver));
+        assert_eq!(released_at(&mut receiver, start + ms(200)), start + ms(200));
+        let metrics = receiver.metrics();
+        assert_eq!(metrics.max_delivery_delay, ms(204));
+        assert_eq!(metrics.delivery_delay, (ms(125) * 7 + ms(204)) / 8);
+        assert_eq!(metrics.latency, ms(120));
+    }
+}
@@ -1,115 +1,7 @@
 use std::cmp::{max, min};
 use std::time::{Duration, Instant};
 
-use stats::OnlineStats;
-
-use crate::protocol::{TimeBase, TimeSpan, TimeStamp, Timer};
-
-pub(crate) struct SynchronizedRemoteClock {
-    tolerance: Duration,
-    time_base: TimeBase,
-    stats: Option<OnlineStats>,
-}
-
-impl SynchronizedRemoteClock {
-    const MAX_SAMPLES: usize = 1_000;
-    const DRIFT_TOLERANCE: Duration = Duration::from_millis(5);
-
-    pub fn new(now: Instant) -> Self {
-        Self {
-            // TODO: Drift deviation tolerance should be parameterized.
-            //       It wasn't in the reference implementation, but I added it because the reference
-            //       implementation is susceptible to invalid clock adjustments during periods of
-            //       acute network latency
-            tolerance: Self::DRIFT_TOLERANCE,
-            time_base: TimeBase::new(now),
-            stats: None,
-        }
-    }
-
-    pub fn synchronize(&mut self, now: Instant, ts: TimeStamp) {
-        let drift = self.time_base.timestamp_from(now) - ts;
-        match &mut self.stats {
-            None => {
-                self.time_base.adjust(drift);
-            }
-            Some(stats) => {
-                stats.add(drift.as_micros());
-
-                if stats.len() < Self::MAX_SAMPLES {
-                    return;
-                }
-
-                if stats.stddev() < self.tolerance.as_micros() as f64 {
-                    self.time_base
-                        .adjust(TimeSpan::from_micros(stats.mean() as i32));
-                }
-            }
-        }
-        self.stats = Some(OnlineStats::new());
-    }
-
-    pub fn instant_from(&self, now: Instant, ts: TimeStamp) -> Instant {
-        self.time_base.instant_from(now, ts)
-    }
-
-    pub fn origin_time(&self) -> Instant {
-        self.time_base.origin_time()
-    }
-}
-
-#[cfg(test)]
-mod synchronized_remote_clock {
-    use std::{cmp::Ordering, time::Duration};
-
-    use super::*;
-    use proptest::prelude::*;
-
-    proptest! {
-        #[test]
-        fn synchronize(drift_ts in 1u64..5_000_000) {
-            const MAX_SAMPLES: i32 = 1000;
-
-            let drift = Duration::from_micros(drift_ts);
-            let start = Instant::now();
-            let start_ts = TimeStamp::from_micros(100_000_000);
-
-            let mut clock = SynchronizedRemoteClock::new(start);
-
-            clock.synchronize(start, start_ts);
-            let instant = clock.instant_from(start, start_ts);
-
-            prop_assert_eq!(instant, start, "the clock should be adjusted on the first sample");
-
-            for tick_ts in 1..1002 {
-                let tick = Duration::from_micros(tick_ts as u64);
-                let now = start + tick + drift;
-                let now_ts = start_ts + TimeSpan::from_micros(tick_ts);
-
-                clock.synchronize(now, now_ts);
-                let instant = clock.instant_from(start, now_ts);
-
-                match tick_ts.cmp(&MAX_SAMPLES) {
-                    Ordering::Less => assert_eq!(instant, start + tick, "the clock should not be adjusted until {} samples: tick_ts = {}", MAX_SAMPLES, tick_ts),
-                    Ordering::Equal => assert_eq!(instant, now, "the clock should be adjusted after {} samples", MAX_SAMPLES),
-                    Ordering::Greater => assert_eq!(instant, now, "the clock should not be adjusted until the next {} samples: tick_ts = {}", MAX_SAMPLES, tick_ts),
-                }
-            }
-
-            // simulate drift variance outside tolerance (+/- 5ms)
-            for tick_ts in 1002..2002 {
-                let tick = Duration::from_micros(tick_ts as u64);
-                let now = start + tick + drift;
-                let now_ts = start_ts + TimeSpan::from_micros(tick_ts);
-
-                clock.synchronize(now, now_ts - TimeSpan::from_micros((tick_ts % 2) * 11000)); // constant 5ms drift variance
-                let instant = clock.instant_from(start, now_ts);
-
-                prop_assert_eq!(instant, now, "the clock should not be adjusted: tick_ts = {}", tick_ts);
-            }
-        }
-    }
-}
+use crate::protocol::{TimeSpan, Timer};
 
 pub(crate) struct RTT {
     mean: TimeSpan,
@@ -155,16 +47,25 @@ impl RTT {
 pub(crate) struct ReceiveTimers {
     pub(crate) ack: Timer,
     pub(crate) nak: Timer,
+    // periods set in the settings, which don't follow the RTT
+    ack_interval: Option<Duration>,
+    nak_interval: Option<Duration>,
 }
 
 impl ReceiveTimers {
     const SYN: Duration = Duration::from_millis(10);
 
-    pub fn new(now: Instant) -> ReceiveTimers {
+    pub fn new(
+        now: Instant,
+        ack_interval: Option<Duration>,
+        nak_interval: Option<Duration>,
+    ) -> ReceiveTimers {
         let (ack, nak) = Self::calculate_periods(&RTT::new());
         ReceiveTimers {
-            ack: Timer::new(ack, now),
-            nak: Timer::new(nak, now),
+            ack: Timer::new(ack_interval.unwrap_or(ack), now),
+            nak: Timer::new(nak_interval.unwrap_or(nak), now),
+            ack_interval,
+            nak_interval,
         }
     }
 
@@ -174,8 +75,12 @@ impl ReceiveTimers {
 
     pub fn update_rtt(&mut self, rtt: &RTT) {
         let (ack, nak) = Self::calculate_periods(rtt);
-        self.ack.set_period(ack);
-        self.nak.set_period(nak);
+        if self.ack_interval.is_none() {
+            self.ack.set_period(ack);
+        }
+        if self.nak_interval.is_none() {
+            self.nak.set_period(nak);
+        }
     }
 
     fn calculate_periods(rtt: &RTT) -> (Duration, Duration) {
@@ -210,7 +115,7 @@ mod receive_timers {
         let rtt_variance = ms(1);
         let syn = ms(10);
         let start = Instant::now();
-        let mut timers = ReceiveTimers::new(start);
+        let mut timers = ReceiveTimers::new(start, None, None);
 
         // next timer should be ack
         // 4 * RTT + RTTVar + SYN
@@ -243,6 +148,32 @@ mod receive_timers {
         assert!(timers.nak.check_expired(now).is_some());
     }
 
+    #[test]
+    fn fixed_periods() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut timers = ReceiveTimers::new(start, Some(ms(5)), Some(ms(300)));
+        assert_eq!(timers.next_timer(start), start + ms(5));
+        assert_eq!(timers.nak.next_instant(), start + ms(300));
+
+        // a long RTT doesn't change them
+        let mut rtt = RTT::new();
+        for _ in 0..100 {
+            rtt.update(TimeSpan::from_micros(600_000));
+        }
+        timers.update_rtt(&rtt);
+        assert!(timers.ack.check_expired(start + ms(5)).is_some());
+        assert_eq!(timers.ack.next_instant(), start + ms(10));
+        assert!(timers.nak.check_expired(start + ms(300)).is_some());
+        assert_eq!(timers.nak.next_instant(), start + ms(600));
+
+        // only the one that isn't fixed follows the RTT
+        let mut timers = ReceiveTimers::new(start, None, Some(ms(300)));
+        timers.update_rtt(&rtt);
+        assert!(timers.ack.next_instant() > start + ms(2_000));
+        assert_eq!(timers.nak.next_instant(), start + ms(300));
+    }
+
     proptest! {
         #[test]
         fn update_rtt(simulated_rtt in 45_000i32..) {
@@ -261,7 +192,7 @@ mod receive_timers {
             prop_assume!(4 * rtt_mean + rtt_variance + syn > ms(500));
 
             let start = Instant::now();
-            let mut timers = ReceiveTimers::new(start);
+            let mut timers = ReceiveTimers::new(start, None, None);
 
             timers.update_rtt(&rtt);
 
@@ -289,7 +220,7 @@ mod receive_timers {
             prop_assume!(4 * rtt_mean + rtt_variance + syn <= ms(500));
 
             let start = Instant::now();
-            let mut timers = ReceiveTimers::new(start);
+            let mut timers = ReceiveTimers::new(start, None, None);
 
             timers.update_rtt(&rtt);
 
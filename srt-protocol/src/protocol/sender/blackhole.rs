@@ -0,0 +1,119 @@
+/// Notices an MTU blackhole: a path, often through a GRE or VPN tunnel, that drops every packet over some size while
+/// smaller ones get through, see [`MtuBlackholeMonitor`](crate::MtuBlackholeMonitor)
+///
+/// Only packets that got through the first time count as delivered, as the receiver acknowledges past the ones it
+/// gave up on. Packets larger than any delivered are suspect, and once enough of them are reported lost while enough
+/// smaller ones get through, it's a blackhole. A single larger packet getting through clears the suspicion.
+#[derive(Debug, Default)]
+pub struct BlackholeDetector {
+    /// The largest payload that got through the first time
+    largest_delivered: usize,
+
+    /// Packets larger than `largest_delivered` reported lost, since it last grew
+    suspect_losses: u32,
+
+    /// Packets that got through since the first suspect loss
+    delivered_since: u32,
+
+    detected: bool,
+}
+
+impl BlackholeDetector {
+    /// How many suspect packets have to be lost, with how many smaller ones getting through meanwhile
+    const SUSPECT_LOSSES: u32 = 16;
+    const DELIVERED: u32 = 16;
+
+    /// The payload of the 576 byte datagrams every IPv4 host takes, after the IP, UDP and SRT headers. Anything
+    /// smaller getting through doesn't tell much about the MTU.
+    const MIN_PAYLOAD: usize = 576 - 44;
+
+    pub fn largest_delivered(&self) -> usize {
+        self.largest_delivered
+    }
+
+    /// Account for a packet with a payload of `size` bytes that got through the first time
+    pub fn on_delivered(&mut self, size: usize) {
+        if size > self.largest_delivered {
+            self.largest_delivered = size;
+            self.suspect_losses = 0;
+            self.delivered_since = 0;
+        } else if self.suspect_losses > 0 {
+            self.delivered_since += 1;
+        }
+    }
+
+    /// Account for a packet with a payload of `size` bytes reported lost for the first time, returning the payload
+    /// size to clamp to if that makes it a blackhole. It only ever does once.
+    pub fn on_lost(&mut self, size: usize) -> Option<usize> {
+        if self.detected || size <= self.largest_delivered {
+            return None;
+        }
+        self.suspect_losses += 1;
+        self.detected = self.suspect_losses >= Self::SUSPECT_LOSSES
+            && self.delivered_since >= Self::DELIVERED
+            && self.largest_delivered >= Self::MIN_PAYLOAD;
+        if self.detected {
+            Some(self.largest_delivered)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // large packets lost and small ones delivered in turn, `count` times, with the clamp if there was one
+    fn alternate(
+        detector: &mut BlackholeDetector,
+        large: usize,
+        small: usize,
+        count: usize,
+    ) -> Option<usize> {
+        let mut clamp = None;
+        for _ in 0..count {
+            clamp = clamp.or(detector.on_lost(large));
+            detector.on_delivered(small);
+        }
+        clamp
+    }
+
+    #[test]
+    fn blackhole() {
+        let mut detector = BlackholeDetector::default();
+        detector.on_delivered(1000);
+        assert_eq!(alternate(&mut detector, 1316, 1000, 16), None);
+        assert_eq!(alternate(&mut detector, 1316, 900, 1), Some(1000));
+
+        // only once
+        assert_eq!(alternate(&mut detector, 1316, 1000, 100), None);
+    }
+
+    #[test]
+    fn large_packet_delivered() {
+        let mut detector = BlackholeDetector::default();
+        detector.on_delivered(1000);
+        assert_eq!(alternate(&mut detector, 1316, 1000, 10), None);
+
+        // one of them got through after all, so they're lost to something else
+        detector.on_delivered(1316);
+        assert_eq!(alternate(&mut detector, 1316, 1000, 100), None);
+        assert_eq!(detector.largest_delivered(), 1316);
+    }
+
+    #[test]
+    fn nothing_smaller_delivered() {
+        let mut detector = BlackholeDetector::default();
+        detector.on_delivered(1000);
+        for _ in 0..100 {
+            assert_eq!(detector.on_lost(1316), None);
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        let mut detector = BlackholeDetector::default();
+        assert_eq!(alternate(&mut detector, 1316, 188, 100), None);
+    }
+}
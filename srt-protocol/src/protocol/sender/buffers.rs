@@ -1,21 +1,43 @@
+use std::cmp::min;
 use std::collections::VecDeque;
-use std::time::Instant;
+use std::io::IoSlice;
+use std::time::{Duration, Instant};
 
 use bytes::{Bytes, BytesMut};
 
 use crate::packet::{DataEncryption, PacketLocation};
 use crate::protocol::{TimeBase, TimeStamp};
 use crate::{
-    crypto::CryptoManager, ConnectionSettings, DataPacket, MsgNumber, SeqNumber, SocketID,
+    crypto::CryptoManager, ConnectionSettings, DataPacket, MsgNumber, Priority, SendDropPolicy,
+    SeqNumber, SocketID,
 };
 
+/// A packet waiting to be sent for the first time
+struct QueuedPacket {
+    packet: DataPacket,
+
+    /// When the message was given to be sent
+    time: Instant,
+
+    /// When the packet is dropped if it's still waiting, see [`SendDropPolicy`]
+    deadline: Option<Instant>,
+
+    /// How many times the packet can be retransmitted, `None` for as many as it takes
+    max_retransmits: Option<u32>,
+}
+
 pub struct TransmitBuffer {
     remote_socket_id: SocketID,
     max_packet_size: usize,
     time_base: TimeBase,
 
+    /// The latency messages are dropped against, `None` if they never are
+    drop_latency: Option<Duration>,
+
+    drop_policy: SendDropPolicy,
+
     /// The list of packets to transmit
-    buffer: VecDeque<DataPacket>,
+    buffer: VecDeque<QueuedPacket>,
 
     crypto: Option<CryptoManager>,
 
@@ -32,6 +54,12 @@ impl TransmitBuffer {
             remote_socket_id: settings.remote_sockid,
             max_packet_size: settings.max_packet_size as usize,
             time_base: TimeBase::new(settings.socket_start_time),
+            drop_latency: if settings.transmission_type.too_late_packet_drop() {
+                Some(settings.send_tsbpd_latency)
+            } else {
+                None
+            },
+            drop_policy: settings.send_drop_policy,
             buffer: Default::default(),
             crypto: settings.crypto_manager.clone(),
             next_sequence_number: settings.init_send_seq_num,
@@ -41,15 +69,28 @@ impl TransmitBuffer {
 
     /// In the case of a message longer than the packet size,
     /// It will be split into multiple packets
-    pub fn push_message(&mut self, data: (Instant, Bytes)) -> usize {
+    pub fn push_message(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+        max_retransmits: Option<u32>,
+    ) -> usize {
         let (time, mut payload) = data;
         let mut location = PacketLocation::FIRST;
         let mut packet_count = 0;
         let message_number = self.get_new_message_number();
+        let deadline = self.deadline(time, priority);
         loop {
             if payload.len() > self.max_packet_size as usize {
                 let this_payload = payload.slice(0..self.max_packet_size as usize);
-                self.begin_transmit(time, message_number, this_payload, location, false);
+                self.begin_transmit(
+                    time,
+                    message_number,
+                    this_payload,
+                    location,
+                    deadline,
+                    max_retransmits,
+                );
 
                 payload = payload.slice(self.max_packet_size as usize..payload.len());
                 location = PacketLocation::empty();
@@ -60,19 +101,130 @@ impl TransmitBuffer {
                     message_number,
                     payload,
                     location | PacketLocation::LAST,
-                    false,
+                    deadline,
+                    max_retransmits,
                 );
                 return packet_count + 1;
             }
         }
     }
 
-    pub fn pop_front(&mut self) -> Option<DataPacket> {
-        self.buffer.pop_front()
+    /// Like [`push_message`](TransmitBuffer::push_message), for a message in several slices, copied straight into
+    /// the packets' payloads
+    pub fn push_vectored(
+        &mut self,
+        time: Instant,
+        slices: &[IoSlice],
+        priority: Priority,
+        max_retransmits: Option<u32>,
+    ) -> usize {
+        let mut remaining: usize = slices.iter().map(|slice| slice.len()).sum();
+        let mut slices = slices.iter().map(|slice| &slice[..]);
+        let mut current: &[u8] = &[];
+        let mut location = PacketLocation::FIRST;
+        let mut packet_count = 0;
+        let message_number = self.get_new_message_number();
+        let deadline = self.deadline(time, priority);
+        loop {
+            let len = min(remaining, self.max_packet_size);
+            let mut payload = BytesMut::with_capacity(len);
+            while payload.len() < len {
+                if current.is_empty() {
+                    // there are `remaining` bytes left in the slices
+                    current = slices.next().unwrap();
+                    continue;
+                }
+                let take = min(current.len(), len - payload.len());
+                payload.extend_from_slice(&current[..take]);
+                current = &current[take..];
+            }
+            remaining -= len;
+            packet_count += 1;
+
+            if remaining == 0 {
+                location |= PacketLocation::LAST;
+            }
+            self.begin_transmit(
+                time,
+                message_number,
+                payload.freeze(),
+                location,
+                deadline,
+                max_retransmits,
+            );
+            if remaining == 0 {
+                return packet_count;
+            }
+            location = PacketLocation::empty();
+        }
+    }
+
+    /// The next packet to send, with how many times it can be retransmitted
+    pub fn pop_front(&mut self) -> Option<(DataPacket, Option<u32>)> {
+        self.buffer
+            .pop_front()
+            .map(|queued| (queued.packet, queued.max_retransmits))
+    }
+
+    /// Pop what's left of the message at the front if it has waited longer than its priority allows
+    pub fn pop_late_message(&mut self, now: Instant) -> Option<Vec<DataPacket>> {
+        let front = self.buffer.front()?;
+        if !matches!(front.deadline, Some(deadline) if now > deadline) {
+            return None;
+        }
+        let message_number = front.packet.message_number;
+        Some(self.pop_message(message_number))
+    }
+
+    /// Pop what's left of message `message_number`, if it's at the front, once some of it was sent
+    pub fn pop_message(&mut self, message_number: MsgNumber) -> Vec<DataPacket> {
+        let count = self
+            .buffer
+            .iter()
+            .take_while(|queued| queued.packet.message_number == message_number)
+            .count();
+        self.buffer
+            .drain(..count)
+            .map(|queued| queued.packet)
+            .collect()
     }
 
     pub fn front(&self) -> Option<&DataPacket> {
-        self.buffer.front()
+        self.buffer.front().map(|queued| &queued.packet)
+    }
+
+    /// Drop every packet waiting, returning how many payload bytes they held
+    pub fn clear(&mut self) -> usize {
+        self.buffer
+            .drain(..)
+            .map(|queued| queued.packet.payload.len())
+            .sum()
+    }
+
+    /// If the oldest message waiting has waited longer than the send latency
+    pub fn is_behind(&self, now: Instant) -> bool {
+        match (self.drop_latency, self.buffer.front()) {
+            (Some(latency), Some(front)) => now > front.time + latency,
+            _ => false,
+        }
+    }
+
+    pub fn drop_policy(&self) -> SendDropPolicy {
+        self.drop_policy
+    }
+
+    /// The largest payload of the packets messages are split into
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    /// Split the messages pushed from now on into packets of at most `size` bytes, those already waiting are left
+    pub fn set_max_packet_size(&mut self, size: usize) {
+        self.max_packet_size = size;
+    }
+
+    pub fn back(&self) -> Option<&DataPacket> {
+        self.buffer.back().map(|queued| &queued.packet)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -87,20 +239,31 @@ impl TransmitBuffer {
         self.time_base.timestamp_from(at)
     }
 
+    fn deadline(&self, time: Instant, priority: Priority) -> Option<Instant> {
+        let latency = self.drop_latency?;
+        let wait = match self.drop_policy {
+            SendDropPolicy::DropByPriority => priority.max_wait(latency),
+            SendDropPolicy::DropOldest => Some(latency),
+            SendDropPolicy::Block | SendDropPolicy::DropNewest => None,
+        };
+        wait.map(|wait| time + wait)
+    }
+
     fn begin_transmit(
         &mut self,
         time: Instant,
         message_num: MsgNumber,
         payload: Bytes,
         location: PacketLocation,
-        retransmitted: bool,
+        deadline: Option<Instant>,
+        max_retransmits: Option<u32>,
     ) {
         let mut packet = DataPacket {
             dest_sockid: self.remote_socket_id,
             in_order_delivery: false, // TODO: research this
             message_loc: location,
             encryption: DataEncryption::None,
-            retransmitted,
+            retransmitted: false,
             // if this marks the beginning of the next message, get a new message number, else don't
             message_number: message_num,
             seq_number: self.get_new_sequence_number(),
@@ -118,7 +281,12 @@ impl TransmitBuffer {
             packet.payload = p.freeze();
         }
 
-        self.buffer.push_back(packet)
+        self.buffer.push_back(QueuedPacket {
+            packet,
+            time,
+            deadline,
+            max_retransmits,
+        })
     }
 
     /// Gets the next available message number
@@ -135,9 +303,29 @@ impl TransmitBuffer {
     }
 }
 
+/// A packet waiting to be acknowledged
+pub struct SentPacket {
+    pub packet: DataPacket,
+
+    /// When this packet was last retransmitted, if it was
+    pub retransmitted_at: Option<Instant>,
+
+    /// How many times this packet was retransmitted
+    pub retransmits: u32,
+
+    /// How many times this packet can be retransmitted, `None` for as many as it takes
+    pub max_retransmits: Option<u32>,
+
+    /// If the packet was dropped instead of being sent, see [`TransmitBuffer::pop_late_message`]
+    pub dropped: bool,
+}
+
+/// The send buffer isn't shrunk below this, so a steady stream doesn't keep reallocating it
+const MIN_SEND_BUFFER_CAPACITY: usize = 64;
+
 pub struct SendBuffer {
     /// The buffer to store packets for retransmision, sorted chronologically
-    buffer: VecDeque<DataPacket>,
+    buffer: VecDeque<SentPacket>,
 
     /// The first sequence number in buffer, so seq number i would be found at
     /// buffer[i - first_seq]
@@ -157,12 +345,38 @@ impl SendBuffer {
             self.buffer.pop_front();
             self.first_seq += 1;
         }
+        // give back the room a burst left behind, instead of keeping it for the rest of the connection
+        if self.buffer.capacity() > MIN_SEND_BUFFER_CAPACITY
+            && self.buffer.len() < self.buffer.capacity() / 4
+        {
+            let capacity = (self.buffer.capacity() / 2).max(MIN_SEND_BUFFER_CAPACITY);
+            self.buffer.shrink_to(capacity);
+        }
+    }
+
+    /// The packets [`release_acknowledged_packets`](Self::release_acknowledged_packets) releases for `acknowledged`
+    pub fn acknowledged(&self, acknowledged: SeqNumber) -> impl Iterator<Item = &SentPacket> {
+        let count = if acknowledged > self.first_seq {
+            (acknowledged - self.first_seq) as usize
+        } else {
+            0
+        };
+        self.buffer.iter().take(count)
+    }
+
+    /// The payload bytes of the packets sent but not acknowledged yet, leaving out those dropped
+    pub fn unacknowledged_bytes(&self) -> usize {
+        self.buffer
+            .iter()
+            .filter(|sent| !sent.dropped)
+            .map(|sent| sent.packet.payload.len())
+            .sum()
     }
 
     pub fn get<'a, I: Iterator<Item = SeqNumber> + 'a>(
         &'a self,
         numbers: I,
-    ) -> impl Iterator<Item = Result<&'a DataPacket, SeqNumber>> + 'a {
+    ) -> impl Iterator<Item = Result<&'a SentPacket, SeqNumber>> + 'a {
         numbers.map(
             move |number| match self.buffer.get((number - self.first_seq) as usize) {
                 Some(p) => Ok(p),
@@ -172,11 +386,64 @@ impl SendBuffer {
     }
 
     pub fn front(&self) -> Option<&DataPacket> {
-        self.buffer.front()
+        self.buffer.front().map(|sent| &sent.packet)
+    }
+
+    pub fn back(&self) -> Option<&DataPacket> {
+        self.buffer.back().map(|sent| &sent.packet)
+    }
+
+    pub fn push_back(&mut self, data: DataPacket, max_retransmits: Option<u32>) {
+        self.buffer.push_back(SentPacket {
+            packet: data,
+            retransmitted_at: None,
+            retransmits: 0,
+            max_retransmits,
+            dropped: false,
+        });
+    }
+
+    /// Keep the place of a packet that was dropped, so it isn't retransmitted
+    pub fn push_dropped(&mut self, data: DataPacket) {
+        self.buffer.push_back(SentPacket {
+            packet: data,
+            retransmitted_at: None,
+            retransmits: 0,
+            max_retransmits: None,
+            dropped: true,
+        });
     }
 
-    pub fn push_back(&mut self, data: DataPacket) {
-        self.buffer.push_back(data);
+    /// Drop the packets of message `message_number` that weren't acknowledged yet, so they aren't retransmitted,
+    /// returning them
+    pub fn drop_message(&mut self, message_number: MsgNumber) -> Vec<DataPacket> {
+        self.buffer
+            .iter_mut()
+            .filter(|sent| !sent.dropped && sent.packet.message_number == message_number)
+            .map(|sent| {
+                sent.dropped = true;
+                sent.packet.clone()
+            })
+            .collect()
+    }
+
+    /// The packet with sequence number `number`, marked as retransmitted, unless it was acknowledged or dropped
+    pub fn retransmission(&self, number: SeqNumber) -> Option<DataPacket> {
+        let sent = self.buffer.get((number - self.first_seq) as usize)?;
+        if sent.dropped {
+            return None;
+        }
+        let mut packet = sent.packet.clone();
+        packet.retransmitted = true;
+        Some(packet)
+    }
+
+    /// Record that the packet with sequence number `number` was retransmitted at `now`
+    pub fn on_retransmit(&mut self, number: SeqNumber, now: Instant) {
+        if let Some(sent) = self.buffer.get_mut((number - self.first_seq) as usize) {
+            sent.retransmitted_at = Some(now);
+            sent.retransmits += 1;
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -184,49 +451,212 @@ impl SendBuffer {
     }
 }
 
+/// The sequence numbers of the packets to retransmit, kept as ranges so a burst loss takes a single entry
+///
+/// The ranges are sorted and neither overlap nor touch. Finding a number is a binary search, and as losses are
+/// mostly reported in order and acknowledged from the front, adding and removing them is mostly at the ends.
 pub struct LossList {
-    pub list: VecDeque<DataPacket>,
+    /// Inclusive ranges of lost sequence numbers
+    ranges: VecDeque<(SeqNumber, SeqNumber)>,
+
+    /// How many sequence numbers the ranges hold
+    len: usize,
 }
 
 impl LossList {
     pub fn new(_settings: &ConnectionSettings) -> Self {
         Self {
-            list: VecDeque::new(),
+            ranges: VecDeque::new(),
+            len: 0,
         }
     }
 
-    pub fn push_back(&mut self, mut packet: DataPacket) {
-        packet.retransmitted = true; // mark as retransmitted
-        self.list.push_back(packet);
+    /// Add `number`, merging it with the ranges it touches. Does nothing if it's already there.
+    pub fn push(&mut self, number: SeqNumber) {
+        self.push_range(number, number);
+    }
+
+    /// Add `first..=last`, merging it with the ranges it touches or overlaps
+    pub fn push_range(&mut self, first: SeqNumber, last: SeqNumber) {
+        // the ranges from the first that doesn't end before `first - 1` up to the last that starts by `last + 1`
+        let start = self.ranges.partition_point(|&(_, l)| l + 1 < first);
+        let end = self.ranges.partition_point(|&(f, _)| f <= last + 1);
+        if start == end {
+            self.ranges.insert(start, (first, last));
+            self.len += (last - first) as usize + 1;
+            return;
+        }
+
+        let merged = (
+            first.min(self.ranges[start].0),
+            last.max(self.ranges[end - 1].1),
+        );
+        let replaced: usize = self
+            .ranges
+            .range(start..end)
+            .map(|&(f, l)| (l - f) as usize + 1)
+            .sum();
+        self.ranges.drain(start + 1..end);
+        self.ranges[start] = merged;
+        self.len = self.len - replaced + (merged.1 - merged.0) as usize + 1;
     }
 
-    pub fn pop_front(&mut self) -> Option<DataPacket> {
-        self.list.pop_front()
+    pub fn pop_front(&mut self) -> Option<SeqNumber> {
+        let (first, last) = self.ranges.front_mut()?;
+        let number = *first;
+        if *first == *last {
+            self.ranges.pop_front();
+        } else {
+            *first += 1;
+        }
+        self.len -= 1;
+        Some(number)
     }
 
+    /// Remove the sequence numbers before `acknowledged`, returning how many there were
     pub fn remove_acknowledged_packets(&mut self, acknowledged: SeqNumber) -> u32 {
         let mut retransmited_packets = 0;
-        while let Some(x) = self.list.front() {
-            if acknowledged > x.seq_number {
-                let _ = self.pop_front();
+        while let Some(&(first, last)) = self.ranges.front() {
+            if last < acknowledged {
                 // this means a packet was lost then retransmitted
-                retransmited_packets += 1;
+                retransmited_packets += last - first + 1;
+                self.ranges.pop_front();
             } else {
+                if first < acknowledged {
+                    retransmited_packets += acknowledged - first;
+                    self.ranges[0].0 = acknowledged;
+                }
                 break;
             }
         }
+        self.len -= retransmited_packets as usize;
         retransmited_packets
     }
 
-    pub fn back(&self) -> Option<&DataPacket> {
-        self.list.back()
+    pub fn front(&self) -> Option<SeqNumber> {
+        self.ranges.front().map(|&(first, _)| first)
+    }
+
+    pub fn back(&self) -> Option<SeqNumber> {
+        self.ranges.back().map(|&(_, last)| last)
+    }
+
+    pub fn contains(&self, number: SeqNumber) -> bool {
+        let i = self.ranges.partition_point(|&(_, last)| last < number);
+        matches!(self.ranges.get(i), Some(&(first, _)) if first <= number)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SeqNumber> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|&(first, last)| (0..=last - first).map(move |i| first + i))
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+        self.len = 0;
     }
 
     pub fn is_empty(&self) -> bool {
-        self.list.is_empty()
+        self.ranges.is_empty()
     }
 
     pub fn len(&self) -> usize {
-        self.list.len()
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn loss_list(numbers: &[u32]) -> LossList {
+        let mut list = LossList {
+            ranges: VecDeque::new(),
+            len: 0,
+        };
+        for &n in numbers {
+            list.push(SeqNumber::new_truncate(n));
+        }
+        list
+    }
+
+    fn contents(list: &LossList) -> Vec<u32> {
+        list.iter().map(|n| n.as_raw()).collect()
+    }
+
+    #[test]
+    fn coalesces() {
+        let mut list = loss_list(&[5, 1, 3, 2, 7, 3]);
+        assert_eq!(contents(&list), [1, 2, 3, 5, 7]);
+        assert_eq!(list.ranges.len(), 3);
+        assert_eq!(list.len(), 5);
+
+        list.push(SeqNumber::new_truncate(6));
+        assert_eq!(list.ranges.len(), 2);
+        list.push(SeqNumber::new_truncate(4));
+        assert_eq!(list.ranges.len(), 1);
+        assert_eq!(contents(&list), [1, 2, 3, 4, 5, 6, 7]);
+
+        assert!(list.contains(SeqNumber::new_truncate(4)));
+        assert!(!list.contains(SeqNumber::new_truncate(8)));
+        assert!(!list.contains(SeqNumber::new_truncate(0)));
+    }
+
+    #[test]
+    fn burst() {
+        // a burst loss across the sequence numbers wrapping takes a single range
+        let start = SeqNumber::MAX - 25_000;
+        let mut list = loss_list(&[]);
+        for i in 0..50_000 {
+            list.push(SeqNumber::new_truncate(start) + i);
+        }
+        assert_eq!(list.ranges.len(), 1);
+        assert_eq!(list.len(), 50_000);
+        assert_eq!(list.front(), Some(SeqNumber::new_truncate(start)));
+        assert_eq!(list.back(), Some(SeqNumber::new_truncate(24_999)));
+        assert!(list.contains(SeqNumber::new_truncate(0)));
+
+        assert_eq!(
+            list.remove_acknowledged_packets(SeqNumber::new_truncate(20_000)),
+            45_000
+        );
+        assert_eq!(list.len(), 5_000);
+        assert_eq!(list.pop_front(), Some(SeqNumber::new_truncate(20_000)));
+        assert_eq!(list.front(), Some(SeqNumber::new_truncate(20_001)));
+        assert_eq!(list.len(), 4_999);
+
+        assert_eq!(
+            list.remove_acknowledged_packets(SeqNumber::new_truncate(30_000)),
+            4_999
+        );
+        assert!(list.is_empty());
+        assert_eq!(list.pop_front(), None);
+    }
+
+    #[test]
+    fn push_ranges() {
+        let seq = SeqNumber::new_truncate;
+        let mut list = loss_list(&[5, 20]);
+        list.push_range(seq(10), seq(12));
+        assert_eq!(contents(&list), [5, 10, 11, 12, 20]);
+        assert_eq!(list.ranges.len(), 3);
+
+        // touching the ranges either side, and overlapping one
+        list.push_range(seq(6), seq(9));
+        list.push_range(seq(11), seq(19));
+        assert_eq!(list.ranges.len(), 1);
+        assert_eq!(list.len(), 16);
+        assert_eq!(list.front(), Some(seq(5)));
+        assert_eq!(list.back(), Some(seq(20)));
+
+        // already there
+        list.push_range(seq(7), seq(8));
+        assert_eq!(list.len(), 16);
+
+        // covering them all
+        list.push_range(seq(1), seq(30));
+        assert_eq!(list.ranges.len(), 1);
+        assert_eq!(list.len(), 30);
     }
 }
@@ -1,7 +1,11 @@
+mod blackhole;
 mod buffers;
 mod congestion_control;
+mod rate_limit;
+mod retransmit_budget;
 
 use std::collections::VecDeque;
+use std::io::IoSlice;
 use std::net::SocketAddr;
 use std::time::{Duration, Instant};
 
@@ -10,14 +14,22 @@ use log::debug;
 use log::{trace, warn};
 
 use super::TimeSpan;
-use crate::loss_compression::decompress_loss_list;
+use crate::dump::SenderDump;
+use crate::loss_compression::decompress_loss_ranges;
 use crate::packet::{AckControlInfo, ControlTypes, HandshakeControlInfo, SrtControlPacket};
 use crate::protocol::handshake::Handshake;
 use crate::protocol::Timer;
-use crate::{ConnectionSettings, ControlPacket, DataPacket, Packet, SeqNumber};
+use crate::{
+    ConnectionSettings, ControlPacket, DataPacket, MsgNumber, MtuBlackholeEvent, Packet, Priority,
+    RetransmitAlgorithm, SendDropPolicy, SeqNumber,
+};
+use srt_packet::seq_num_range;
 
+use blackhole::BlackholeDetector;
 use buffers::*;
 use congestion_control::{LiveDataRate, SenderCongestionControl};
+use rate_limit::TokenBucket;
+use retransmit_budget::RetransmitLimiter;
 
 #[derive(Debug)]
 pub enum SenderError {}
@@ -25,6 +37,7 @@ pub enum SenderError {}
 pub type SenderResult = Result<(), SenderError>;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SenderMetrics {
     /// Round trip time, in microseconds
     pub rtt: TimeSpan,
@@ -35,6 +48,9 @@ pub struct SenderMetrics {
     /// packet arrival rate
     pub pkt_arr_rate: u32,
 
+    /// byte arrival rate, in bytes per second, from peers that report it
+    pub byte_arr_rate: u32,
+
     /// estimated link capacity
     pub est_link_cap: i32,
 
@@ -44,8 +60,49 @@ pub struct SenderMetrics {
     /// Total retransmitted packets
     pub retrans_packets: u32,
 
+    /// The intervals retransmissions were held back in, as they had reached their share of what's sent, see
+    /// [`RetransmitBudget`](crate::RetransmitBudget)
+    pub retrans_budget_hits: u32,
+
     /// Total received packets (packets that have been ACKed)
     pub recvd_packets: u32,
+
+    /// The interval between sending two packets, set by congestion control
+    pub snd_period: Duration,
+
+    /// The congestion window size, in packets
+    pub congestion_window: u32,
+
+    /// The flow window size, in packets
+    pub flow_window: u32,
+
+    /// Packets that have been sent, but have not been ACKed yet
+    pub packets_in_flight: u32,
+
+    /// Packets buffered, waiting to be sent or to be ACKed
+    pub buffered_packets: u32,
+
+    /// The time between the timestamps of the oldest and the newest packet buffered
+    pub buffered_timespan: Duration,
+
+    /// Packets dropped before being sent, because they waited too long, see [`SendDropPolicy`]
+    pub dropped_packets: u32,
+
+    /// Messages dropped by the sender, those that waited too long and those it had no room for
+    pub dropped_messages: u32,
+
+    /// The payload bytes of the messages dropped by the sender
+    pub dropped_bytes: u64,
+
+    /// The payload bytes still unacknowledged when the linger ran out while closing, see
+    /// [`ConnectionSettings::linger`]
+    pub abandoned_bytes: u64,
+
+    /// The largest payload new packets are sent with, lowered from the configured one after an MTU blackhole
+    pub max_payload_size: u32,
+
+    /// If an MTU blackhole was noticed, see [`MtuBlackholeMonitor`](crate::MtuBlackholeMonitor)
+    pub mtu_blackhole: bool,
 }
 
 impl SenderMetrics {
@@ -54,10 +111,24 @@ impl SenderMetrics {
             rtt: TimeSpan::from_micros(10_000),
             rtt_var: TimeSpan::from_micros(0),
             pkt_arr_rate: 0,
+            byte_arr_rate: 0,
             est_link_cap: 0,
             lost_packets: 0,
             retrans_packets: 0,
+            retrans_budget_hits: 0,
             recvd_packets: 0,
+            snd_period: Duration::from_micros(0),
+            congestion_window: 0,
+            flow_window: 0,
+            packets_in_flight: 0,
+            buffered_packets: 0,
+            buffered_timespan: Duration::from_micros(0),
+            dropped_packets: 0,
+            dropped_messages: 0,
+            dropped_bytes: 0,
+            abandoned_bytes: 0,
+            max_payload_size: 0,
+            mtu_blackhole: false,
         }
     }
 }
@@ -85,17 +156,30 @@ pub struct Sender {
     /// The congestion control
     congestion_control: SenderCongestionControl,
 
+    /// Enforces the rate limit, if any, whatever the congestion control allows
+    rate_limit: Option<TokenBucket>,
+
+    /// Keeps retransmissions to their share of what's sent, if there's a budget
+    retransmit_limiter: Option<RetransmitLimiter>,
+
     metrics: SenderMetrics,
 
     /// The buffer to store packets for retransmission, sorted chronologically
     send_buffer: SendBuffer,
 
-    /// The buffer to store the next control packet
-    output_buffer: VecDeque<Packet>,
+    /// Control packets waiting to be sent, these always go out before any data
+    control_output: VecDeque<Packet>,
+
+    /// Data packets waiting to be sent
+    data_output: VecDeque<Packet>,
 
     /// The buffer to store packets for transmission
     transmit_buffer: TransmitBuffer,
 
+    /// The newest message of each key sent in keep-latest mode, waiting for the transmit buffer to empty, see
+    /// [`handle_latest_data`](Sender::handle_latest_data)
+    latest: VecDeque<(u64, (Instant, Bytes))>,
+
     // 1) Sender's Loss List: The sender's loss list is used to store the
     //    sequence numbers of the lost packets fed back by the receiver
     //    through NAK packets or inserted in a timeout event. The numbers
@@ -114,6 +198,14 @@ pub struct Sender {
 
     close_requested: bool,
     shutdown_sent: bool,
+
+    /// When closing stops waiting for the peer to acknowledge the rest, see [`ConnectionSettings::linger`]
+    close_deadline: Option<Instant>,
+
+    blackhole: BlackholeDetector,
+
+    /// The MTU blackhole noticed, waiting to be taken by the host
+    mtu_blackhole_event: Option<MtuBlackholeEvent>,
 }
 
 impl Default for SenderMetrics {
@@ -127,18 +219,34 @@ impl Sender {
         Self {
             settings: settings.clone(),
             handshake,
-            congestion_control: SenderCongestionControl::new(LiveDataRate::Unlimited, None),
+            congestion_control: SenderCongestionControl::new(
+                LiveDataRate::Unlimited,
+                settings
+                    .transmission_type
+                    .congestion_window(settings.max_flow_size),
+            ),
+            rate_limit: settings
+                .rate_limit
+                .map(|limit| TokenBucket::new(limit, settings.socket_start_time)),
+            retransmit_limiter: settings
+                .retransmit_budget
+                .map(|budget| RetransmitLimiter::new(budget, settings.socket_start_time)),
             metrics: SenderMetrics::new(),
             send_buffer: SendBuffer::new(&settings),
             loss_list: LossList::new(&settings),
             lr_acked_packet: settings.init_send_seq_num,
             lr_acked_ack: -1, // TODO: why magic number?
-            output_buffer: VecDeque::new(),
+            control_output: VecDeque::new(),
+            data_output: VecDeque::new(),
             transmit_buffer: TransmitBuffer::new(&settings),
+            latest: VecDeque::new(),
             step: SenderAlgorithmStep::Step1,
             snd_timer: Timer::new(Duration::from_millis(1), settings.socket_start_time),
             close_requested: false,
             shutdown_sent: false,
+            close_deadline: None,
+            blackhole: BlackholeDetector::default(),
+            mtu_blackhole_event: None,
         }
     }
 
@@ -146,17 +254,191 @@ impl Sender {
         &self.settings
     }
 
+    /// Take packets from and send them to `remote` from now on, see [`SourceValidation`](crate::SourceValidation)
+    pub fn set_remote(&mut self, remote: SocketAddr) {
+        self.settings.remote = remote;
+    }
+
+    /// The sequence number the next data packet is sent with
+    pub fn next_sequence_number(&self) -> SeqNumber {
+        self.transmit_buffer.next_sequence_number
+    }
+
+    /// A snapshot of the metrics, including the current congestion control state
+    pub fn metrics(&self) -> SenderMetrics {
+        SenderMetrics {
+            snd_period: self.congestion_control.snd_period(),
+            congestion_window: self.congestion_control.window_size(),
+            flow_window: self.settings.max_flow_size,
+            packets_in_flight: self.send_buffer.len() as u32,
+            buffered_packets: (self.send_buffer.len() + self.transmit_buffer.len()) as u32,
+            buffered_timespan: self.buffered_timespan(),
+            max_payload_size: self.transmit_buffer.max_packet_size() as u32,
+            ..self.metrics
+        }
+    }
+
+    /// The MTU blackhole noticed, if there was one since this was last called, see
+    /// [`MtuBlackholeMonitor`](crate::MtuBlackholeMonitor)
+    pub fn pop_mtu_blackhole_event(&mut self) -> Option<MtuBlackholeEvent> {
+        self.mtu_blackhole_event.take()
+    }
+
+    /// A snapshot of the buffers and loss list, see [`crate::DebugDump`]
+    pub fn dump(&self) -> SenderDump {
+        SenderDump {
+            transmit_buffer: self.transmit_buffer.len(),
+            send_buffer: self.send_buffer.len(),
+            loss_list: self.loss_list.iter().collect(),
+            lr_acked_packet: self.lr_acked_packet,
+            rtt: self.metrics.rtt,
+            next_send: self.snd_timer.next_instant(),
+            close_requested: self.close_requested,
+        }
+    }
+
+    fn buffered_timespan(&self) -> Duration {
+        let oldest = self
+            .send_buffer
+            .front()
+            .or_else(|| self.transmit_buffer.front());
+        let newest = self
+            .transmit_buffer
+            .back()
+            .or_else(|| self.send_buffer.back());
+        match (oldest, newest) {
+            (Some(oldest), Some(newest)) => Duration::from_micros(
+                (newest.timestamp - oldest.timestamp).as_micros().max(0) as u64,
+            ),
+            _ => Duration::from_micros(0),
+        }
+    }
+
     pub fn handle_close(&mut self) {
         self.close_requested = true;
     }
 
     pub fn handle_data(&mut self, data: (Instant, Bytes), now: Instant) {
+        self.handle_prioritized_data(data, Priority::Normal, now)
+    }
+
+    /// Queue a message to be sent, that is dropped first if the sender falls behind, see [`Priority`]
+    pub fn handle_prioritized_data(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+        now: Instant,
+    ) {
+        self.push_message(data, priority, None, now)
+    }
+
+    /// Queue a message that is retransmitted at most `max_retransmits` times, after which it's dropped, whatever
+    /// time it has left, and the receiver is told not to wait for it
+    ///
+    /// For data that's worth less than the bandwidth it takes to deliver it late, such as the frames of a codec that
+    /// can conceal a loss. The limit is checked when the receiver reports the message lost, so with `0` it's dropped
+    /// at the first loss report.
+    pub fn handle_limited_data(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+        max_retransmits: u32,
+        now: Instant,
+    ) {
+        self.push_message(data, priority, Some(max_retransmits), now)
+    }
+
+    /// Queue a message in keep-latest mode: only the newest message of each `key` waits to be sent, an older one
+    /// still waiting is replaced, in its place
+    ///
+    /// For state that's sent whole every time, such as telemetry, where an update that was superseded isn't worth
+    /// the bandwidth. The messages wait until the transmit buffer is empty, so while the link is congested the one
+    /// that goes out is as recent as can be. Those replaced are counted as dropped.
+    pub fn handle_latest_data(&mut self, data: (Instant, Bytes), key: u64, now: Instant) {
+        match self.latest.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, queued)) => {
+                self.metrics.dropped_messages += 1;
+                self.metrics.dropped_bytes += queued.1.len() as u64;
+                *queued = data;
+            }
+            None => self.latest.push_back((key, data)),
+        }
+        self.feed_latest(now);
+    }
+
+    // move the oldest keep-latest message into the transmit buffer once it's empty
+    fn feed_latest(&mut self, now: Instant) {
+        if !self.transmit_buffer.is_empty() {
+            return;
+        }
+        if let Some((_, data)) = self.latest.pop_front() {
+            self.push_message(data, Priority::Normal, None, now);
+        }
+    }
+
+    fn push_message(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+        max_retransmits: Option<u32>,
+        now: Instant,
+    ) {
         let data_length = data.1.len();
-        let packet_count = self.transmit_buffer.push_message(data);
+        if self.sheds_new_data(data_length, now) {
+            return;
+        }
+        let packet_count = self
+            .transmit_buffer
+            .push_message(data, priority, max_retransmits);
+        self.congestion_control
+            .on_input(now, packet_count, data_length);
+    }
+
+    /// Queue a message given in several slices, such as a header and a payload in different buffers
+    ///
+    /// The slices are copied straight into the packets, without putting the message together first.
+    pub fn handle_vectored_data(
+        &mut self,
+        time: Instant,
+        slices: &[IoSlice],
+        priority: Priority,
+        now: Instant,
+    ) {
+        let data_length = slices.iter().map(|slice| slice.len()).sum();
+        if self.sheds_new_data(data_length, now) {
+            return;
+        }
+        let packet_count = self
+            .transmit_buffer
+            .push_vectored(time, slices, priority, None);
         self.congestion_control
             .on_input(now, packet_count, data_length);
     }
 
+    /// If new messages should wait, because the sender is behind with [`SendDropPolicy::Block`]
+    ///
+    /// The sender takes them regardless, it's up to the caller to stop feeding it until it catches up.
+    pub fn is_blocked(&self, now: Instant) -> bool {
+        self.transmit_buffer.drop_policy() == SendDropPolicy::Block
+            && self.transmit_buffer.is_behind(now)
+    }
+
+    // drop a new message of `length` bytes if the sender is behind with SendDropPolicy::DropNewest
+    fn sheds_new_data(&mut self, length: usize, now: Instant) -> bool {
+        if self.transmit_buffer.drop_policy() != SendDropPolicy::DropNewest
+            || !self.transmit_buffer.is_behind(now)
+        {
+            return false;
+        }
+        debug!(
+            "{:?} dropping new message of {} bytes, the sender is behind",
+            self.settings.local_sockid, length
+        );
+        self.metrics.dropped_messages += 1;
+        self.metrics.dropped_bytes += length as u64;
+        true
+    }
+
     fn handle_snd_timer(&mut self, now: Instant) {
         self.snd_timer.reset(now);
         self.step = SenderAlgorithmStep::Step1;
@@ -182,24 +464,59 @@ impl Sender {
 
     pub fn is_flushed(&self) -> bool {
         trace!("{:?} Checking is flushed: ll.len()={}, tb.len()={}, lrap={}, nsn={}, sb.len()={}, ob.len()={}", self.settings.local_sockid, self.loss_list.len(), 
-            self.transmit_buffer.len(), self.lr_acked_packet, self.transmit_buffer.next_sequence_number, self.send_buffer.len(), self.output_buffer.len());
+            self.transmit_buffer.len(), self.lr_acked_packet, self.transmit_buffer.next_sequence_number, self.send_buffer.len(), self.control_output.len() + self.data_output.len());
         self.loss_list.is_empty()
             && self.transmit_buffer.is_empty()
+            && self.latest.is_empty()
             && self.lr_acked_packet == self.transmit_buffer.next_sequence_number
-            && self.output_buffer.is_empty()
+            && self.control_output.is_empty()
+            && self.data_output.is_empty()
     }
 
+    /// The next packet to send, control packets first so ACKs, NAKs and handshakes never wait behind
+    /// a burst of data
     pub fn pop_output(&mut self) -> Option<(Packet, SocketAddr)> {
         let to = self.settings.remote;
-        self.output_buffer
+        self.control_output
             .pop_front()
+            .or_else(|| self.data_output.pop_front())
             .map(move |packet| (packet, to))
     }
 
     pub fn next_action(&mut self, now: Instant) -> SenderAlgorithmAction {
         use SenderAlgorithmAction::*;
+
+        if self.close_requested {
+            if self.close_deadline.is_none() {
+                self.close_deadline = self.settings.linger.map(|linger| now + linger);
+            }
+            if matches!(self.close_deadline, Some(deadline) if now >= deadline)
+                && !self.is_flushed()
+            {
+                self.abandon_unacknowledged();
+            }
+        }
+
+        // wake up when the linger runs out, even if nothing else happens
+        match (self.next_algorithm_action(now), self.close_deadline) {
+            (WaitForData | WaitUntilAck, Some(deadline)) => WaitUntil(deadline),
+            (WaitUntil(t), Some(deadline)) => WaitUntil(t.min(deadline)),
+            (action, _) => action,
+        }
+    }
+
+    fn next_algorithm_action(&mut self, now: Instant) -> SenderAlgorithmAction {
+        use SenderAlgorithmAction::*;
         use SenderAlgorithmStep::*;
 
+        if let Some(limit) = self.settings.rate_limit_control.take() {
+            debug!(
+                "{:?} rate limit changed to {:?}",
+                self.settings.local_sockid, limit
+            );
+            self.rate_limit = limit.map(|limit| TokenBucket::new(limit, now));
+        }
+
         // don't return close until fully flushed
         if self.close_requested && self.is_flushed() {
             if !self.shutdown_sent {
@@ -218,11 +535,43 @@ impl Sender {
             return WaitUntil(self.snd_timer.next_instant());
         }
 
+        self.drop_late_messages(now);
+        self.feed_latest(now);
+
         //   1) If the sender's loss list is not empty, retransmit the first
         //      packet in the list and remove it from the list. Go to 5).
-        if let Some(p) = self.loss_list.pop_front() {
+        let mut retransmission = None;
+        while let Some(number) = self.loss_list.front() {
+            retransmission = self.send_buffer.retransmission(number);
+            if retransmission.is_some() {
+                break;
+            }
+            // acknowledged or dropped since it was reported lost
+            self.loss_list.pop_front();
+        }
+        // over the budget, new data goes first, and the retransmission waits in the loss list
+        if let (Some(limiter), Some(p)) = (&mut self.retransmit_limiter, &retransmission) {
+            if !self.transmit_buffer.is_empty() {
+                if let Err(first) = limiter.allows(now, p.payload.len()) {
+                    if first {
+                        debug!(
+                            "{:?} holding back retransmissions, over the budget",
+                            self.settings.local_sockid
+                        );
+                        self.metrics.retrans_budget_hits += 1;
+                    }
+                    retransmission = None;
+                }
+            }
+        }
+        if let Err(until) = take_rate_limit(&mut self.rate_limit, now, retransmission.as_ref()) {
+            return WaitUntil(until);
+        }
+        if let Some(p) = retransmission {
+            self.loss_list.pop_front();
             debug!("Sending packet in loss list, seq={:?}", p.seq_number);
-            self.send_data(p);
+            self.send_buffer.on_retransmit(p.seq_number, now);
+            self.send_data(p, now);
 
             // TODO: returning here will result in sending all the packets in the loss
             //       list before progressing further through the sender algorithm. This
@@ -262,21 +611,25 @@ impl Sender {
                    self.transmit_buffer.next_sequence_number - self.congestion_control.window_size());
 
             return WaitUntilAck;
+        } else if let Err(until) =
+            take_rate_limit(&mut self.rate_limit, now, self.transmit_buffer.front())
+        {
+            return WaitUntil(until);
         } else if let Some(p) = self.pop_transmit_buffer() {
-            self.send_data(p);
+            self.send_data(p, now);
         } else if self.close_requested {
             // this covers the niche case of dropping the last packet(s)
             if let Some(dp) = self.send_buffer.front().cloned() {
-                self.send_data(dp);
+                self.send_data(dp, now);
             }
         }
 
         //   5) If the sequence number of the current packet is 16n, where n is an
         //      integer, go to 2).
-        if let Some(p) = self.pop_transmit_buffer_16n() {
+        if let Some(p) = self.pop_transmit_buffer_16n(now) {
             //      NOTE: to get the closest timing, we ignore congestion control
             //      and send the 16th packet immediately, instead of proceeding to step 2
-            self.send_data(p);
+            self.send_data(p, now);
         }
 
         //   6) Wait (SND - t) time, where SND is the inter-packet interval
@@ -311,13 +664,17 @@ impl Sender {
             // TODO: case UMSG_DROPREQ: // 111 - Msg drop request
             // TODO: case UMSG_PEERERROR: // 1000 - An error has happened to the peer side
             // TODO: case UMSG_EXT: // 0x7FFF - reserved and user defined messages
-            ControlTypes::Nak(nack) => self.handle_nack_packet(nack),
+            ControlTypes::Nak(nack) => self.handle_nack_packet(nack, now),
             ControlTypes::Shutdown => self.handle_shutdown_packet(),
             ControlTypes::Srt(srt_packet) => self.handle_srt_control_packet(srt_packet),
             // The only purpose of keep-alive packet is to tell that the peer is still alive
             // nothing needs to be done.
             // TODO: is this actually true? check reference implementation
             ControlTypes::KeepAlive => Ok(()),
+            ControlTypes::Custom { custom_type, .. } => {
+                debug!("Sender ignoring custom control packet {:#x}", custom_type);
+                Ok(())
+            }
         }
     }
 
@@ -363,12 +720,28 @@ impl Sender {
         //    value carried in the ACK.
         self.metrics.pkt_arr_rate =
             self.metrics.pkt_arr_rate / 8 * 7 + info.packet_recv_rate.unwrap_or(0) / 8;
+        if let Some(byte_recv_rate) = info.byte_recv_rate {
+            self.metrics.byte_arr_rate = self.metrics.byte_arr_rate / 8 * 7 + byte_recv_rate / 8;
+        }
 
         // 8) Update estimated link capacity: B = (B * 7 + b) / 8, where b is
         //    the value carried in the ACK.
         self.metrics.est_link_cap =
             (self.metrics.est_link_cap * 7 + info.est_link_cap.unwrap_or(0)) / 8;
 
+        // the packets that got through the first time, the others may have been given up on
+        for sent in self.send_buffer.acknowledged(info.ack_number) {
+            let size = sent.packet.payload.len();
+            if sent.dropped
+                || sent.retransmitted_at.is_some()
+                || (size > self.blackhole.largest_delivered()
+                    && self.loss_list.contains(sent.packet.seq_number))
+            {
+                continue;
+            }
+            self.blackhole.on_delivered(size);
+        }
+
         // 9) Update sender's buffer (by releasing the buffer that has been
         //    acknowledged).
         self.send_buffer
@@ -386,40 +759,117 @@ impl Sender {
         Ok(())
     }
 
-    fn handle_nack_packet(&mut self, nack: Vec<u32>) -> SenderResult {
+    fn handle_nack_packet(&mut self, nack: Vec<u32>, now: Instant) -> SenderResult {
         // 1) Add all sequence numbers carried in the NAK into the sender's loss list.
         // 2) Update the SND period by rate control (see section 3.6).
         // 3) Reset the EXP time variable.
 
-        for lost in self
-            .send_buffer
-            .get(decompress_loss_list(nack.iter().cloned()))
-        {
-            let packet = match lost {
-                Ok(p) => p,
-                Err(n) => {
-                    debug!("NAK received for packet {} that's not in the buffer, maybe it's already been ACKed", n);
-                    return Ok(());
-                }
-            };
-
-            // this has already been ack'd
-            if packet.seq_number < self.lr_acked_packet {
+        // a retransmission is only assumed to be lost once it should have been acknowledged
+        let rtt = Duration::from_micros(
+            (self.metrics.rtt.as_micros() + 4 * self.metrics.rtt_var.as_micros()).max(0) as u64,
+        );
+
+        let mut drop_requests = Vec::new();
+        let mut exhausted = Vec::new();
+        let mut clamp = None;
+        for (first, last) in decompress_loss_ranges(nack.iter().cloned()) {
+            // the ones before have already been ack'd
+            let first = first.max(self.lr_acked_packet);
+            if last < first {
                 continue;
             }
 
-            self.loss_list.push_back(packet.clone());
+            // consecutive packets to retransmit go into the loss list as one range
+            let mut lost_range: Option<(SeqNumber, SeqNumber)> = None;
+            for lost in self.send_buffer.get(seq_num_range(first, last + 1)) {
+                let sent = match lost {
+                    Ok(sent) => sent,
+                    Err(n) => {
+                        debug!("NAK received for packet {} that's not in the buffer, maybe it's already been ACKed", n);
+                        continue;
+                    }
+                };
+                let packet = &sent.packet;
+
+                // the drop request must have been lost
+                if sent.dropped {
+                    drop_requests.push(ControlTypes::DropRequest {
+                        msg_to_drop: packet.message_number,
+                        first: packet.seq_number,
+                        last: packet.seq_number,
+                    });
+                    continue;
+                }
+
+                if matches!(sent.max_retransmits, Some(max) if sent.retransmits >= max) {
+                    if !exhausted.contains(&packet.message_number) {
+                        exhausted.push(packet.message_number);
+                    }
+                    continue;
+                }
+
+                if self.settings.retransmit_algorithm == RetransmitAlgorithm::Reduced {
+                    let in_flight = matches!(sent.retransmitted_at, Some(at) if at + rtt > now);
+                    if in_flight || self.loss_list.contains(packet.seq_number) {
+                        trace!("Not retransmitting {:?} again yet", packet.seq_number);
+                        continue;
+                    }
+                }
+
+                if sent.retransmitted_at.is_none() && !self.loss_list.contains(packet.seq_number) {
+                    if let Some(size) = self.blackhole.on_lost(packet.payload.len()) {
+                        clamp = Some(size);
+                    }
+                }
+                lost_range = match lost_range {
+                    Some((first, last)) if last + 1 == packet.seq_number => {
+                        Some((first, packet.seq_number))
+                    }
+                    Some((first, last)) => {
+                        self.loss_list.push_range(first, last);
+                        Some((packet.seq_number, packet.seq_number))
+                    }
+                    None => Some((packet.seq_number, packet.seq_number)),
+                };
+            }
+            if let Some((first, last)) = lost_range {
+                self.loss_list.push_range(first, last);
+            }
+        }
+        if let Some(size) = clamp {
+            self.clamp_payload_size(size);
+        }
+        for drop_request in drop_requests {
+            self.send_control(drop_request, now);
+        }
+        for message_number in exhausted {
+            self.drop_exhausted_message(message_number, now);
         }
 
         // update CC
-        if let Some(last_packet) = self.loss_list.back() {
-            self.congestion_control.on_nak(last_packet.seq_number);
+        if let Some(last) = self.loss_list.back() {
+            self.congestion_control.on_nak(last);
         }
 
         // TODO: reset EXP
         Ok(())
     }
 
+    // packets over `size` bytes are lost on the way, while smaller ones get through
+    fn clamp_payload_size(&mut self, size: usize) {
+        let previous = self.transmit_buffer.max_packet_size();
+        warn!(
+            "{:?} packets over {} bytes are lost, while smaller ones get through, likely an MTU blackhole: clamping the payload size from {} to {}",
+            self.settings.local_sockid, size, previous, size
+        );
+        self.transmit_buffer.set_max_packet_size(size);
+        self.metrics.mtu_blackhole = true;
+        self.mtu_blackhole_event = Some(MtuBlackholeEvent {
+            payload_size: size,
+            previous_payload_size: previous,
+        });
+    }
+
     fn handle_handshake_packet(
         &mut self,
         handshake: HandshakeControlInfo,
@@ -444,29 +894,929 @@ impl Sender {
         Ok(())
     }
 
+    /// Drop the messages that waited too long to be sent, and tell the receiver not to wait for them
+    fn drop_late_messages(&mut self, now: Instant) {
+        while let Some(dropped) = self.transmit_buffer.pop_late_message(now) {
+            let (first, last) = match (dropped.first(), dropped.last()) {
+                (Some(first), Some(last)) => (first.seq_number, last.seq_number),
+                _ => continue,
+            };
+            debug!(
+                "{:?} dropping message {:?}, too late to be sent",
+                self.settings.local_sockid, dropped[0].message_number
+            );
+            self.report_dropped(&dropped, first, last, now);
+            for packet in dropped {
+                self.send_buffer.push_dropped(packet);
+            }
+        }
+    }
+
+    /// Drop a message that was retransmitted as many times as it can be, along with what's left of it to send
+    fn drop_exhausted_message(&mut self, message_number: MsgNumber, now: Instant) {
+        let mut dropped = self.send_buffer.drop_message(message_number);
+        let unsent = self.transmit_buffer.pop_message(message_number);
+        dropped.extend(unsent.iter().cloned());
+        let (first, last) = match (dropped.first(), dropped.last()) {
+            (Some(first), Some(last)) => (first.seq_number, last.seq_number),
+            _ => return,
+        };
+        debug!(
+            "{:?} dropping message {:?}, out of retransmissions",
+            self.settings.local_sockid, message_number
+        );
+        self.report_dropped(&dropped, first, last, now);
+        for packet in unsent {
+            self.send_buffer.push_dropped(packet);
+        }
+    }
+
+    // tell the receiver not to wait for the packets `first` to `last` of a message, and count them as dropped
+    fn report_dropped(
+        &mut self,
+        dropped: &[DataPacket],
+        first: SeqNumber,
+        last: SeqNumber,
+        now: Instant,
+    ) {
+        self.send_control(
+            ControlTypes::DropRequest {
+                msg_to_drop: dropped[0].message_number,
+                first,
+                last,
+            },
+            now,
+        );
+        self.metrics.dropped_packets += dropped.len() as u32;
+        self.metrics.dropped_messages += 1;
+        self.metrics.dropped_bytes += dropped
+            .iter()
+            .map(|packet| packet.payload.len() as u64)
+            .sum::<u64>();
+    }
+
+    /// Give up on everything the peer hasn't acknowledged yet, counting its bytes as abandoned
+    fn abandon_unacknowledged(&mut self) {
+        let latest: usize = self
+            .latest
+            .drain(..)
+            .map(|(_, (_, payload))| payload.len())
+            .sum();
+        let abandoned =
+            self.send_buffer.unacknowledged_bytes() + self.transmit_buffer.clear() + latest;
+        debug!(
+            "{:?} linger ran out, abandoning {} bytes",
+            self.settings.local_sockid, abandoned
+        );
+        self.metrics.abandoned_bytes += abandoned as u64;
+        self.loss_list.clear();
+        self.data_output.clear();
+        self.lr_acked_packet = self.transmit_buffer.next_sequence_number;
+        self.send_buffer
+            .release_acknowledged_packets(self.lr_acked_packet);
+    }
+
     fn pop_transmit_buffer(&mut self) -> Option<DataPacket> {
-        let packet = self.transmit_buffer.pop_front()?;
+        let (packet, max_retransmits) = self.transmit_buffer.pop_front()?;
         self.congestion_control.on_packet_sent();
-        self.send_buffer.push_back(packet.clone());
+        self.send_buffer.push_back(packet.clone(), max_retransmits);
         Some(packet)
     }
 
-    fn pop_transmit_buffer_16n(&mut self) -> Option<DataPacket> {
-        match self.transmit_buffer.front().map(|p| p.seq_number % 16) {
-            Some(0) => self.pop_transmit_buffer(),
-            _ => None,
+    fn pop_transmit_buffer_16n(&mut self, now: Instant) -> Option<DataPacket> {
+        if self.transmit_buffer.front()?.seq_number % 16 != 0 {
+            return None;
         }
+        // if the rate limit doesn't allow it, it's sent at the next period like any other
+        take_rate_limit(&mut self.rate_limit, now, self.transmit_buffer.front()).ok()?;
+        self.pop_transmit_buffer()
     }
 
     fn send_control(&mut self, control: ControlTypes, now: Instant) {
-        self.output_buffer.push_back(Packet::Control(ControlPacket {
-            timestamp: self.transmit_buffer.timestamp_from(now),
-            dest_sockid: self.settings.remote_sockid,
-            control_type: control,
-        }));
+        self.control_output
+            .push_back(Packet::Control(ControlPacket {
+                timestamp: self.transmit_buffer.timestamp_from(now),
+                dest_sockid: self.settings.remote_sockid,
+                control_type: control,
+            }));
+    }
+
+    fn send_data(&mut self, p: DataPacket, now: Instant) {
+        if let Some(limiter) = &mut self.retransmit_limiter {
+            limiter.on_sent(now, p.payload.len(), p.retransmitted);
+        }
+        self.data_output.push_back(Packet::Data(p));
+    }
+}
+
+/// Take the rate limit's tokens for sending `packet`, or return when they'll be available
+fn take_rate_limit(
+    rate_limit: &mut Option<TokenBucket>,
+    now: Instant,
+    packet: Option<&DataPacket>,
+) -> Result<(), Instant> {
+    match (rate_limit, packet) {
+        (Some(bucket), Some(packet)) => bucket.take_packet(now, packet),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::loss_compression::compress_loss_list;
+    use crate::packet::{PacketLocation, SrtShakeFlags};
+    use crate::protocol::TimeStamp;
+    use crate::{KmState, SocketID, SrtVersion, SystemClock, TransmissionType};
+    use std::sync::Arc;
+
+    fn test_sender(retransmit_algorithm: RetransmitAlgorithm, start: Instant) -> Sender {
+        Sender::new(
+            ConnectionSettings {
+                remote: ([127, 0, 0, 1], 2223).into(),
+                remote_sockid: SocketID(1),
+                local_sockid: SocketID(2),
+                socket_start_time: start,
+                clock: Arc::new(SystemClock),
+                init_send_seq_num: SeqNumber::new_truncate(0),
+                init_recv_seq_num: SeqNumber::new_truncate(0),
+                max_packet_size: 1316,
+                max_flow_size: 8192,
+                send_tsbpd_latency: Duration::from_millis(120),
+                recv_tsbpd_latency: Duration::from_millis(120),
+                crypto_manager: None,
+                send_km_state: KmState::Unsecured,
+                recv_km_state: KmState::Unsecured,
+                transmission_type: TransmissionType::Live,
+                retransmit_algorithm,
+                packet_budget: 64,
+                max_message_size: 8 * 1024 * 1024,
+                peer_version: SrtVersion::CURRENT,
+                peer_flags: SrtShakeFlags::SUPPORTED,
+                control_packet_handler: None,
+                packet_tap: None,
+                send_buffer_monitor: None,
+                data_idle_monitor: None,
+                stall_monitor: None,
+                mtu_blackhole_monitor: None,
+                break_criteria: Default::default(),
+                rate_limit: None,
+                rate_limit_control: Default::default(),
+                retransmit_budget: None,
+                ack_interval: None,
+                nak_interval: None,
+                send_drop_policy: SendDropPolicy::default(),
+                source_validation: Default::default(),
+                transfer_quota: Default::default(),
+                linger: None,
+                recv_timeout: None,
+                send_timeout: None,
+                app_protocol: None,
+                peer_extensions: vec![],
+                connect_stats: Default::default(),
+            },
+            Handshake::Connector,
+        )
+    }
+
+    fn nak(sender: &mut Sender, seq: u32, now: Instant) {
+        naks(sender, &[seq], now)
+    }
+
+    fn naks(sender: &mut Sender, seqs: &[u32], now: Instant) {
+        let loss_list =
+            compress_loss_list(seqs.iter().map(|&seq| SeqNumber::new_truncate(seq))).collect();
+        let packet = Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(2),
+            control_type: ControlTypes::Nak(loss_list),
+        });
+        sender
+            .handle_packet((packet, sender.settings().remote), now)
+            .unwrap();
+    }
+
+    // the data packets sent at `now`
+    fn sent_data(sender: &mut Sender, now: Instant) -> Vec<SeqNumber> {
+        let _ = sender.next_action(now);
+        let mut sent = vec![];
+        while let Some((packet, _)) = sender.pop_output() {
+            if let Packet::Data(data) = packet {
+                sent.push(data.seq_number);
+            }
+        }
+        sent
+    }
+
+    fn repeated_naks(retransmit_algorithm: RetransmitAlgorithm) -> Vec<Vec<SeqNumber>> {
+        let start = Instant::now();
+        let mut sender = test_sender(retransmit_algorithm, start);
+        sender.handle_data((start, Bytes::from_static(b"asdf")), start);
+        assert_eq!(sent_data(&mut sender, start), [SeqNumber::new_truncate(0)]);
+
+        // the receiver reports the loss again before the retransmission could have made it, then after
+        [1, 2, 50]
+            .iter()
+            .map(|&ms| {
+                let now = start + Duration::from_millis(ms);
+                nak(&mut sender, 0, now);
+                sent_data(&mut sender, now)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aggressive_retransmission() {
+        let zero = vec![SeqNumber::new_truncate(0)];
+        assert_eq!(
+            repeated_naks(RetransmitAlgorithm::Aggressive),
+            [zero.clone(), zero.clone(), zero]
+        );
+    }
+
+    #[test]
+    fn reduced_retransmission() {
+        let zero = vec![SeqNumber::new_truncate(0)];
+        assert_eq!(
+            repeated_naks(RetransmitAlgorithm::Reduced),
+            [zero.clone(), vec![], zero]
+        );
+    }
+
+    #[test]
+    fn buffered_timespan() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        assert_eq!(sender.metrics().buffered_timespan, Duration::from_micros(0));
+
+        sender.handle_data((start, Bytes::from_static(b"asdf")), start);
+        let later = start + Duration::from_millis(30);
+        sender.handle_data((later, Bytes::from_static(b"asdf")), later);
+        let metrics = sender.metrics();
+        assert_eq!(metrics.buffered_packets, 2);
+        assert_eq!(metrics.buffered_timespan, Duration::from_millis(30));
+
+        // sent, but not acknowledged
+        sent_data(&mut sender, later);
+        sent_data(&mut sender, later + Duration::from_millis(10));
+        let metrics = sender.metrics();
+        assert_eq!(metrics.packets_in_flight, 2);
+        assert_eq!(metrics.buffered_packets, 2);
+        assert_eq!(metrics.buffered_timespan, Duration::from_millis(30));
+    }
+
+    #[test]
+    fn control_before_data() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        for _ in 0..2 {
+            sender.handle_data((start, Bytes::from_static(b"asdf")), start);
+        }
+        let _ = sender.next_action(start);
+        let _ = sender.next_action(start + Duration::from_millis(10));
+
+        // the ACK2 is queued after the data, but goes out first
+        let ack = Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(2),
+            control_type: ControlTypes::Ack(AckControlInfo {
+                ack_seq_num: 1,
+                ack_number: SeqNumber::new_truncate(0),
+                rtt: None,
+                rtt_variance: None,
+                buffer_available: None,
+                packet_recv_rate: None,
+                est_link_cap: None,
+                byte_recv_rate: None,
+            }),
+        });
+        sender
+            .handle_packet((ack, sender.settings().remote), start)
+            .unwrap();
+
+        let mut output = vec![];
+        while let Some((packet, _)) = sender.pop_output() {
+            output.push(match packet {
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Ack2(1),
+                    ..
+                }) => None,
+                Packet::Data(data) => Some(data.seq_number.as_raw()),
+                packet => panic!("{:?}", packet),
+            });
+        }
+        assert_eq!(output, [None, Some(0), Some(1)]);
+    }
+
+    // 10 packets of 956 + 44 bytes a second, and up to 2 at once
+    fn rate_limited_sender(start: Instant) -> Sender {
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        sender.rate_limit = Some(TokenBucket::new(
+            crate::RateLimit {
+                bytes_per_second: 10_000,
+                burst: 2_000,
+            },
+            start,
+        ));
+        sender
+    }
+
+    #[test]
+    fn rate_limit() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        // never dropped for being late, however long they wait
+        for _ in 0..50 {
+            sender.handle_prioritized_data(
+                (start, Bytes::from(vec![0; 956])),
+                Priority::High,
+                start,
+            );
+        }
+
+        let mut now = start;
+        let mut sent = vec![];
+        while now <= start + Duration::from_secs(2) {
+            sent.extend(sent_data(&mut sender, now).into_iter().map(|_| now));
+            now = match sender.next_action(now) {
+                SenderAlgorithmAction::WaitUntil(t) => t,
+                action => panic!("{:?}", action),
+            };
+        }
+        // the burst, then one every 100ms, whatever the congestion control allows
+        assert_eq!(sent.len(), 2 + 20);
+        assert!(sent[1] - start < Duration::from_millis(1));
+        for at in &sent[2..] {
+            assert_eq!((*at - start).as_micros() % 100_000, 0, "{:?}", *at - start);
+        }
+    }
+
+    #[test]
+    fn rate_limit_retransmissions() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        for _ in 0..3 {
+            sender.handle_data((start, Bytes::from(vec![0; 956])), start);
+        }
+        let burst = start + Duration::from_millis(1);
+        assert_eq!(sent_data(&mut sender, start).len(), 1);
+        assert_eq!(sent_data(&mut sender, burst).len(), 1);
+
+        // retransmissions wait for the limit too, and go first
+        nak(&mut sender, 0, burst);
+        assert_eq!(sent_data(&mut sender, burst), []);
+        let next = start + Duration::from_millis(100);
+        assert_eq!(sent_data(&mut sender, next), [SeqNumber::new_truncate(0)]);
+    }
+
+    #[test]
+    fn retransmit_budget() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        sender.retransmit_limiter = Some(RetransmitLimiter::new(
+            crate::RetransmitBudget::new(0.25),
+            start,
+        ));
+        for _ in 0..4 {
+            sender.handle_data((start, Bytes::from(vec![0; 1000])), start);
+        }
+        let at = |ms| start + Duration::from_millis(ms);
+        let seq = SeqNumber::new_truncate;
+        assert_eq!(sent_data(&mut sender, at(0)), [seq(0)]);
+        assert_eq!(sent_data(&mut sender, at(1)), [seq(1)]);
+
+        // a retransmission would be a third of what's sent, new data goes first
+        nak(&mut sender, 0, at(1));
+        assert_eq!(sent_data(&mut sender, at(2)), [seq(2)]);
+        assert_eq!(sender.metrics().retrans_budget_hits, 1);
+
+        // now it's a quarter
+        assert_eq!(sent_data(&mut sender, at(3)), [seq(0)]);
+        assert_eq!(sent_data(&mut sender, at(4)), [seq(3)]);
+        assert_eq!(sender.metrics().retrans_budget_hits, 1);
+    }
+
+    #[test]
+    fn rate_limit_control() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        for _ in 0..3 {
+            sender.handle_data((start, Bytes::from(vec![0; 956])), start);
+        }
+        assert_eq!(sent_data(&mut sender, start).len(), 1);
+        assert_eq!(
+            sent_data(&mut sender, start + Duration::from_millis(1)).len(),
+            1
+        );
+        assert_eq!(sent_data(&mut sender, start + Duration::from_millis(2)), []);
+
+        // lifted while running, the last one doesn't wait for the limit anymore
+        sender.settings.rate_limit_control.set(None);
+        let now = start + Duration::from_millis(3);
+        assert_eq!(sent_data(&mut sender, now), [SeqNumber::new_truncate(2)]);
+    }
+
+    #[test]
+    fn drop_late_messages() {
+        use Priority::*;
+
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        for &priority in &[High, High, Low, Normal, Normal, High] {
+            sender.handle_prioritized_data((start, Bytes::from(vec![0; 956])), priority, start);
+        }
+
+        let mut now = start;
+        let mut sent = vec![];
+        let mut drop_requests = vec![];
+        while now <= start + Duration::from_millis(500) {
+            let _ = sender.next_action(now);
+            while let Some((packet, _)) = sender.pop_output() {
+                match packet {
+                    Packet::Data(data) => sent.push(data.seq_number.as_raw()),
+                    Packet::Control(ControlPacket {
+                        control_type: ControlTypes::DropRequest { first, last, .. },
+                        ..
+                    }) => drop_requests.push((now - start, first.as_raw(), last.as_raw())),
+                    _ => {}
+                }
+            }
+            now = match sender.next_action(now) {
+                SenderAlgorithmAction::WaitUntil(t) => t,
+                SenderAlgorithmAction::WaitForData => break,
+                action => panic!("{:?}", action),
+            };
+        }
+
+        // after the burst, low priority messages are dropped once they waited half the latency, normal ones once
+        // they waited the whole latency, and high priority ones never
+        assert_eq!(sent, [0, 1, 3, 5]);
+        assert_eq!(
+            drop_requests,
+            [
+                (Duration::from_millis(100), 2, 2),
+                (Duration::from_millis(200), 4, 4)
+            ]
+        );
+        assert_eq!(sender.metrics.dropped_packets, 2);
+        assert_eq!(sender.metrics.dropped_messages, 2);
+        assert_eq!(sender.metrics.dropped_bytes, 2 * 956);
+
+        // the drop request is sent again if the receiver still asks for the packet
+        nak(&mut sender, 2, now);
+        assert!(matches!(
+            sender.pop_output(),
+            Some((Packet::Control(ControlPacket {
+                control_type: ControlTypes::DropRequest { first, last, .. },
+                ..
+            }), _)) if first.as_raw() == 2 && last.as_raw() == 2
+        ));
+        assert_eq!(sent_data(&mut sender, now), []);
+
+        // also when the NAK names packets acknowledged since
+        ack(&mut sender, 1, 2, now);
+        naks(&mut sender, &[1, 2], now);
+        assert_eq!(sent_drops(&mut sender, now), (vec![(2, 2)], vec![]));
+    }
+
+    // the drop requests sent at `now`, as their first and last sequence numbers, and the data packets
+    fn sent_drops(sender: &mut Sender, now: Instant) -> (Vec<(u32, u32)>, Vec<u32>) {
+        let _ = sender.next_action(now);
+        let (mut drops, mut sent) = (vec![], vec![]);
+        while let Some((packet, _)) = sender.pop_output() {
+            match packet {
+                Packet::Data(data) => sent.push(data.seq_number.as_raw()),
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::DropRequest { first, last, .. },
+                    ..
+                }) => drops.push((first.as_raw(), last.as_raw())),
+                _ => {}
+            }
+        }
+        (drops, sent)
     }
 
-    fn send_data(&mut self, p: DataPacket) {
-        self.output_buffer.push_back(Packet::Data(p));
+    #[test]
+    fn max_retransmits() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        sender.handle_limited_data(
+            (start, Bytes::from(vec![0; 100])),
+            Priority::Normal,
+            1,
+            start,
+        );
+        sender.handle_data((start, Bytes::from(vec![1; 100])), start);
+        assert_eq!(sent_drops(&mut sender, start), (vec![], vec![0]));
+        let now = start + Duration::from_millis(1);
+        assert_eq!(sent_drops(&mut sender, now), (vec![], vec![1]));
+
+        let now = start + Duration::from_millis(10);
+        nak(&mut sender, 0, now);
+        assert_eq!(sent_drops(&mut sender, now), (vec![], vec![0]));
+
+        // out of retransmissions
+        let now = start + Duration::from_millis(20);
+        nak(&mut sender, 0, now);
+        assert_eq!(sent_drops(&mut sender, now), (vec![(0, 0)], vec![]));
+        assert_eq!(sender.metrics.dropped_messages, 1);
+        assert_eq!(sender.metrics.dropped_bytes, 100);
+
+        // the others are retransmitted as often as it takes
+        for ms in &[30, 40] {
+            let now = start + Duration::from_millis(*ms);
+            nak(&mut sender, 1, now);
+            assert_eq!(sent_drops(&mut sender, now), (vec![], vec![1]));
+        }
+    }
+
+    // the ranges in a NAK go into the loss list whole
+    #[test]
+    fn nak_range() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        let at = |ms| start + Duration::from_millis(ms);
+        for _ in 0..4 {
+            sender.handle_data((start, Bytes::from(vec![0; 100])), start);
+        }
+        let sent: Vec<_> = (0..10)
+            .flat_map(|ms| sent_data(&mut sender, at(ms)))
+            .map(|seq| seq.as_raw())
+            .collect();
+        assert_eq!(sent, [0, 1, 2, 3]);
+        ack(&mut sender, 1, 1, at(10));
+
+        naks(&mut sender, &[0, 1, 2, 3], at(20));
+        assert_eq!(
+            sender
+                .loss_list
+                .iter()
+                .map(|seq| seq.as_raw())
+                .collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        let sent: Vec<_> = (20..30)
+            .flat_map(|ms| sent_data(&mut sender, at(ms)))
+            .map(|seq| seq.as_raw())
+            .collect();
+        assert_eq!(sent, [1, 2, 3]);
+    }
+
+    // a NAK naming packets acknowledged since is still acted on for the others
+    #[test]
+    fn max_retransmits_acknowledged() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        sender.handle_data((start, Bytes::from(vec![0; 100])), start);
+        sender.handle_limited_data(
+            (start, Bytes::from(vec![1; 100])),
+            Priority::Normal,
+            0,
+            start,
+        );
+        assert_eq!(sent_drops(&mut sender, start), (vec![], vec![0]));
+        let now = start + Duration::from_millis(1);
+        assert_eq!(sent_drops(&mut sender, now), (vec![], vec![1]));
+        ack(&mut sender, 1, 1, now);
+
+        let now = start + Duration::from_millis(10);
+        naks(&mut sender, &[0, 1], now);
+        assert_eq!(sent_drops(&mut sender, now), (vec![(1, 1)], vec![]));
+        assert_eq!(sender.metrics.dropped_messages, 1);
+        assert_eq!(sender.metrics.dropped_bytes, 100);
+    }
+
+    #[test]
+    fn max_retransmits_unsent() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        // three packets, the first lost before the others are sent
+        sender.handle_limited_data(
+            (start, Bytes::from(vec![0; 3000])),
+            Priority::High,
+            0,
+            start,
+        );
+        assert_eq!(sent_drops(&mut sender, start), (vec![], vec![0]));
+
+        nak(&mut sender, 0, start);
+        assert_eq!(sent_drops(&mut sender, start), (vec![(0, 2)], vec![]));
+        assert_eq!(sender.metrics.dropped_packets, 3);
+        assert_eq!(sender.metrics.dropped_bytes, 3000);
+
+        // nothing's left to send
+        let now = start + Duration::from_millis(100);
+        assert_eq!(sent_drops(&mut sender, now), (vec![], vec![]));
+    }
+
+    #[test]
+    fn keep_latest() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        sender.handle_data((start, Bytes::from(vec![0; 100])), start);
+        // the first ones are still waiting when the next ones of their key come
+        for &(tag, key) in &[(11, 1), (21, 2), (12, 1), (13, 1), (22, 2)] {
+            sender.handle_latest_data((start, Bytes::from(vec![tag; 100])), key, start);
+        }
+        assert_eq!(sender.metrics.dropped_messages, 3);
+        assert_eq!(sender.metrics.dropped_bytes, 300);
+
+        let mut now = start;
+        let mut sent = vec![];
+        loop {
+            let action = sender.next_action(now);
+            while let Some((packet, _)) = sender.pop_output() {
+                if let Packet::Data(data) = packet {
+                    sent.push(data.payload[0]);
+                }
+            }
+            now = match action {
+                SenderAlgorithmAction::WaitUntil(t) => t,
+                SenderAlgorithmAction::WaitForData => break,
+                action => panic!("{:?}", action),
+            };
+        }
+        assert_eq!(sent, [0, 13, 22]);
+
+        // nothing's waiting, so it goes right away
+        sender.handle_latest_data((now, Bytes::from(vec![14; 100])), 1, now);
+        assert!(!sender.transmit_buffer.is_empty());
+        assert!(sender.latest.is_empty());
+    }
+
+    fn ack(sender: &mut Sender, ack_seq_num: i32, ack_number: u32, now: Instant) {
+        let packet = Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketID(2),
+            control_type: ControlTypes::Ack(AckControlInfo {
+                ack_seq_num,
+                ack_number: SeqNumber::new_truncate(ack_number),
+                rtt: None,
+                rtt_variance: None,
+                buffer_available: None,
+                packet_recv_rate: None,
+                est_link_cap: None,
+                byte_recv_rate: None,
+            }),
+        });
+        sender
+            .handle_packet((packet, sender.settings().remote), now)
+            .unwrap();
+    }
+
+    #[test]
+    fn mtu_blackhole() {
+        detect_mtu_blackhole(false);
+    }
+
+    // the NAKs also name the small packet before, which has been acknowledged since
+    #[test]
+    fn mtu_blackhole_acknowledged() {
+        detect_mtu_blackhole(true);
+    }
+
+    fn detect_mtu_blackhole(nak_acknowledged: bool) {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        let at = |ms| start + Duration::from_millis(ms);
+        let seq = SeqNumber::new_truncate;
+
+        sender.handle_data((at(0), Bytes::from(vec![0; 1000])), at(0));
+        assert_eq!(sent_data(&mut sender, at(0)), [seq(0)]);
+        ack(&mut sender, 1, 1, at(1));
+
+        // packets of 1000 bytes get through, full ones never do, not even retransmitted
+        for i in 0..17 {
+            let t = 10 + 10 * u64::from(i);
+            let large = 1 + 2 * i;
+            sender.handle_data((at(t), Bytes::from(vec![0; 1316])), at(t));
+            sender.handle_data((at(t), Bytes::from(vec![0; 1000])), at(t));
+            let mut sent = sent_data(&mut sender, at(t));
+            sent.extend(sent_data(&mut sender, at(t + 1)));
+            assert_eq!(sent, [seq(large), seq(large + 1)]);
+
+            assert_eq!(sender.pop_mtu_blackhole_event(), None);
+            if nak_acknowledged {
+                naks(&mut sender, &[large - 1, large], at(t + 2));
+            } else {
+                nak(&mut sender, large, at(t + 2));
+            }
+            assert_eq!(sent_data(&mut sender, at(t + 3)), [seq(large)]);
+            ack(&mut sender, 2 + i as i32, large + 2, at(t + 4));
+        }
+
+        assert_eq!(
+            sender.pop_mtu_blackhole_event(),
+            Some(MtuBlackholeEvent {
+                payload_size: 1000,
+                previous_payload_size: 1316,
+            })
+        );
+        assert_eq!(sender.pop_mtu_blackhole_event(), None);
+        let metrics = sender.metrics();
+        assert!(metrics.mtu_blackhole);
+        assert_eq!(metrics.max_payload_size, 1000);
+
+        // new messages are split to fit
+        sender.handle_data((at(500), Bytes::from(vec![0; 1316])), at(500));
+        assert_eq!(sender.transmit_buffer.len(), 2);
+    }
+
+    fn drop_policy_sender(policy: SendDropPolicy, start: Instant) -> Sender {
+        let mut sender = rate_limited_sender(start);
+        sender.settings.send_drop_policy = policy;
+        sender.transmit_buffer = TransmitBuffer::new(&sender.settings);
+        sender
+    }
+
+    // the data packets sent until `until`, and when the sender goes idle
+    fn run_until(sender: &mut Sender, from: Instant, until: Instant) -> (Vec<u32>, Instant) {
+        let mut now = from;
+        let mut sent = vec![];
+        while now <= until {
+            sent.extend(sent_data(sender, now).iter().map(|seq| seq.as_raw()));
+            now = match sender.next_action(now) {
+                SenderAlgorithmAction::WaitUntil(t) => t,
+                SenderAlgorithmAction::WaitForData => break,
+                action => panic!("{:?}", action),
+            };
+        }
+        (sent, now)
+    }
+
+    #[test]
+    fn drop_oldest() {
+        use Priority::*;
+
+        let start = Instant::now();
+        let mut sender = drop_policy_sender(SendDropPolicy::DropOldest, start);
+        for &priority in &[High, High, Low, Normal, Normal, High] {
+            sender.handle_prioritized_data((start, Bytes::from(vec![0; 956])), priority, start);
+        }
+
+        // whatever their priority, the messages still waiting after the latency are dropped
+        let (sent, _) = run_until(&mut sender, start, start + Duration::from_millis(500));
+        assert_eq!(sent, [0, 1, 2]);
+        assert_eq!(sender.metrics.dropped_messages, 3);
+        assert_eq!(sender.metrics.dropped_bytes, 3 * 956);
+    }
+
+    #[test]
+    fn drop_newest() {
+        let start = Instant::now();
+        let mut sender = drop_policy_sender(SendDropPolicy::DropNewest, start);
+        for _ in 0..4 {
+            sender.handle_data((start, Bytes::from(vec![0; 956])), start);
+        }
+
+        // once the oldest message waited longer than the latency, new ones are dropped
+        let behind = start + Duration::from_millis(150);
+        let (mut sent, now) = run_until(&mut sender, start, behind);
+        assert!(!sender.is_blocked(now));
+        sender.handle_data((now, Bytes::from(vec![0; 700])), now);
+        assert_eq!(sender.metrics.dropped_messages, 1);
+        assert_eq!(sender.metrics.dropped_bytes, 700);
+
+        // but not those already queued
+        let (rest, now) = run_until(&mut sender, now, start + Duration::from_secs(1));
+        sent.extend(rest);
+        assert_eq!(sent, [0, 1, 2, 3]);
+        assert_eq!(sender.metrics.dropped_packets, 0);
+
+        // and once caught up, new messages are taken again
+        sender.handle_data((now, Bytes::from(vec![0; 700])), now);
+        assert_eq!(sender.metrics.dropped_messages, 1);
+        let (sent, _) = run_until(&mut sender, now, start + Duration::from_secs(2));
+        assert_eq!(sent, [4]);
+    }
+
+    #[test]
+    fn block() {
+        let start = Instant::now();
+        let mut sender = drop_policy_sender(SendDropPolicy::Block, start);
+        for _ in 0..4 {
+            sender.handle_data((start, Bytes::from(vec![0; 956])), start);
+        }
+        assert!(!sender.is_blocked(start));
+
+        let (mut sent, now) = run_until(&mut sender, start, start + Duration::from_millis(150));
+        assert!(sender.is_blocked(now));
+
+        // nothing is dropped, the sender is unblocked once it caught up
+        let (rest, now) = run_until(&mut sender, now, start + Duration::from_secs(1));
+        sent.extend(rest);
+        assert_eq!(sent, [0, 1, 2, 3]);
+        assert!(!sender.is_blocked(now));
+        assert_eq!(sender.metrics.dropped_messages, 0);
+    }
+
+    #[test]
+    fn vectored_data() {
+        // the packets of each sender's message, their location and payload
+        fn sent(sender: &mut Sender, start: Instant) -> Vec<(PacketLocation, Bytes)> {
+            let mut now = start;
+            let mut sent = vec![];
+            loop {
+                let action = sender.next_action(now);
+                while let Some((packet, _)) = sender.pop_output() {
+                    if let Packet::Data(data) = packet {
+                        sent.push((data.message_loc, data.payload));
+                    }
+                }
+                match action {
+                    // until the three packets of the message are out
+                    SenderAlgorithmAction::WaitUntil(t) if sent.len() < 3 => now = t,
+                    _ => return sent,
+                }
+            }
+        }
+
+        let header = [0x47; 100];
+        let payload: Vec<u8> = (0..2600).map(|i| i as u8).collect();
+        let start = Instant::now();
+
+        let mut vectored = test_sender(RetransmitAlgorithm::Reduced, start);
+        let slices = [
+            IoSlice::new(&header),
+            IoSlice::new(&payload[..2000]),
+            IoSlice::new(&[]),
+            IoSlice::new(&payload[2000..]),
+        ];
+        vectored.handle_vectored_data(start, &slices, Priority::Normal, start);
+
+        let mut contiguous = test_sender(RetransmitAlgorithm::Reduced, start);
+        let message: Vec<u8> = header.iter().chain(&payload).copied().collect();
+        contiguous.handle_data((start, message.into()), start);
+
+        let packets = sent(&mut vectored, start);
+        let locations: Vec<_> = packets.iter().map(|(loc, _)| *loc).collect();
+        assert_eq!(
+            locations,
+            [
+                PacketLocation::FIRST,
+                PacketLocation::empty(),
+                PacketLocation::LAST
+            ]
+        );
+        assert_eq!(packets[0].1.len(), 1316);
+        assert_eq!(&packets[0].1[..100], &header[..]);
+        assert_eq!(packets, sent(&mut contiguous, start));
+    }
+
+    #[test]
+    fn ack_releases_send_buffer() {
+        let start = Instant::now();
+        let mut sender = test_sender(RetransmitAlgorithm::Aggressive, start);
+        for _ in 0..3 {
+            sender.handle_data((start, Bytes::from_static(b"asdf")), start);
+        }
+        let (sent, now) = run_until(&mut sender, start, start + Duration::from_millis(10));
+        assert_eq!(sent, [0, 1, 2]);
+        assert_eq!(sender.metrics().packets_in_flight, 3);
+
+        // each ACK releases what it acknowledges right away
+        ack(&mut sender, 1, 2, now);
+        assert_eq!(sender.metrics().packets_in_flight, 1);
+        ack(&mut sender, 2, 3, now);
+        assert_eq!(sender.metrics().packets_in_flight, 0);
+        while sender.pop_output().is_some() {}
+        assert!(sender.is_flushed());
+    }
+
+    #[test]
+    fn linger() {
+        let start = Instant::now();
+        let mut sender = rate_limited_sender(start);
+        sender.settings.linger = Some(Duration::from_millis(50));
+        for _ in 0..4 {
+            sender.handle_data((start, Bytes::from(vec![0; 956])), start);
+        }
+        // the burst goes out, the rest waits for the rate limit
+        let (sent, _) = run_until(&mut sender, start, start + Duration::from_millis(10));
+        assert_eq!(sent, [0, 1]);
+        ack(&mut sender, 1, 1, start + Duration::from_millis(10));
+
+        // closing waits for the rest until the linger runs out
+        let closed = start + Duration::from_millis(20);
+        sender.handle_close();
+        let mut now = closed;
+        loop {
+            match sender.next_action(now) {
+                SenderAlgorithmAction::WaitUntil(t) => {
+                    assert!(t <= closed + Duration::from_millis(50));
+                    now = t;
+                }
+                SenderAlgorithmAction::Close => break,
+                action => panic!("{:?}", action),
+            }
+            while sender.pop_output().is_some() {}
+        }
+        assert_eq!(now, closed + Duration::from_millis(50));
+
+        // the packet sent but not acknowledged, and the two never sent
+        assert_eq!(sender.metrics.abandoned_bytes, 3 * 956);
+        while sender.pop_output().is_some() {}
+        assert!(sender.is_flushed());
     }
 }
@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use crate::{DataPacket, RateLimit};
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+// 20 bytes for the IPv4 header, 8 for the UDP header and 16 for the SRT header
+const HEADERS_SIZE: usize = 44;
+
+/// The token bucket enforcing a [`RateLimit`], where a token is a byte
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    // in bytes * 10^9, so refilling is exact at nanosecond resolution. Negative when in debt.
+    tokens: i128,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as i128 * NANOS_PER_SEC,
+            updated: now,
+        }
+    }
+
+    /// Take the tokens to send `packet`, see [`take`](TokenBucket::take)
+    pub fn take_packet(&mut self, now: Instant, packet: &DataPacket) -> Result<(), Instant> {
+        self.take(now, packet.payload.len() + HEADERS_SIZE)
+    }
+
+    /// Take the tokens for `bytes` if there are enough, otherwise return when there will be
+    ///
+    /// Packets larger than the burst only need a full bucket, and leave it in debt.
+    pub fn take(&mut self, now: Instant, bytes: usize) -> Result<(), Instant> {
+        self.refill(now);
+        let needed = (bytes as u64).min(self.limit.burst) as i128 * NANOS_PER_SEC;
+        if self.tokens >= needed {
+            self.tokens -= bytes as i128 * NANOS_PER_SEC;
+            Ok(())
+        } else if self.limit.bytes_per_second == 0 {
+            // nothing is ever sent, check again later
+            Err(now + Duration::from_secs(1))
+        } else {
+            let rate = self.limit.bytes_per_second as i128;
+            // round up, so the tokens are there when woken up
+            let wait = (needed - self.tokens + rate - 1) / rate;
+            Err(now + Duration::from_nanos(wait as u64))
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.updated {
+            return;
+        }
+        let elapsed = (now - self.updated).as_nanos() as i128;
+        let full = self.limit.burst as i128 * NANOS_PER_SEC;
+        self.tokens = (self.tokens + elapsed * self.limit.bytes_per_second as i128).min(full);
+        self.updated = now;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limit(bytes_per_second: u64, burst: u64) -> RateLimit {
+        RateLimit {
+            bytes_per_second,
+            burst,
+        }
+    }
+
+    #[test]
+    fn burst_then_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(1_000, 300), start);
+
+        assert_eq!(bucket.take(start, 100), Ok(()));
+        assert_eq!(bucket.take(start, 200), Ok(()));
+        // empty, 100 bytes take 100ms to come back
+        let next = bucket.take(start, 100).unwrap_err();
+        assert_eq!(next - start, Duration::from_millis(100));
+        assert_eq!(bucket.take(next, 100), Ok(()));
+
+        // idle time doesn't grow the bucket past the burst
+        let later = next + Duration::from_secs(10);
+        assert_eq!(bucket.take(later, 300), Ok(()));
+        assert!(bucket.take(later, 1).is_err());
+    }
+
+    #[test]
+    fn larger_than_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit(1_000, 100), start);
+
+        assert_eq!(bucket.take(start, 400), Ok(()));
+        // the 300 bytes of debt are paid before the bucket fills again
+        let next = bucket.take(start, 100).unwrap_err();
+        assert_eq!(next - start, Duration::from_millis(400));
+    }
+}
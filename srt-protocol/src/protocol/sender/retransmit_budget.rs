@@ -0,0 +1,104 @@
+use std::time::Instant;
+
+use crate::RetransmitBudget;
+
+/// Keeps the retransmissions to their share of the bytes sent, interval by interval, see [`RetransmitBudget`]
+pub(crate) struct RetransmitLimiter {
+    budget: RetransmitBudget,
+    interval_start: Instant,
+    // the bytes sent in the interval before, new data and retransmissions
+    last_sent: u64,
+    sent: u64,
+    retransmitted: u64,
+    // if retransmissions were held back this interval
+    held_back: bool,
+}
+
+impl RetransmitLimiter {
+    pub fn new(budget: RetransmitBudget, now: Instant) -> Self {
+        RetransmitLimiter {
+            budget,
+            interval_start: now,
+            last_sent: 0,
+            sent: 0,
+            retransmitted: 0,
+            held_back: false,
+        }
+    }
+
+    /// Account for `bytes` sent, of a retransmission or of new data
+    pub fn on_sent(&mut self, now: Instant, bytes: usize, retransmission: bool) {
+        self.roll(now);
+        self.sent += bytes as u64;
+        if retransmission {
+            self.retransmitted += bytes as u64;
+        }
+    }
+
+    /// If `bytes` can be retransmitted within the budget, otherwise if it's the first time retransmissions are held
+    /// back this interval
+    pub fn allows(&mut self, now: Instant, bytes: usize) -> Result<(), bool> {
+        self.roll(now);
+        let bytes = bytes as u64;
+        let base = self.last_sent.max(self.sent + bytes);
+        if (self.retransmitted + bytes) as f64 > self.budget.max_share * base as f64 {
+            return Err(!std::mem::replace(&mut self.held_back, true));
+        }
+        Ok(())
+    }
+
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.interval_start);
+        if elapsed < self.budget.interval {
+            return;
+        }
+        // nothing was sent in the interval before if more than one went by
+        self.last_sent = if elapsed < 2 * self.budget.interval {
+            self.sent
+        } else {
+            0
+        };
+        self.interval_start = now;
+        self.sent = 0;
+        self.retransmitted = 0;
+        self.held_back = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn share_of_interval() {
+        let start = Instant::now();
+        let mut limiter = RetransmitLimiter::new(
+            RetransmitBudget {
+                max_share: 0.25,
+                interval: Duration::from_secs(1),
+            },
+            start,
+        );
+
+        // nothing sent yet, a retransmission would be all of it
+        assert_eq!(limiter.allows(start, 100), Err(true));
+        for _ in 0..3 {
+            limiter.on_sent(start, 100, false);
+        }
+        assert_eq!(limiter.allows(start, 100), Ok(()));
+        limiter.on_sent(start, 100, true);
+        assert_eq!(limiter.allows(start, 100), Err(false));
+
+        // the next interval can retransmit a quarter of the last one's bytes up front
+        let next = start + Duration::from_secs(1);
+        assert_eq!(limiter.allows(next, 100), Ok(()));
+        limiter.on_sent(next, 100, true);
+        assert_eq!(limiter.allows(next, 100), Err(true));
+
+        // after a silent interval, there's nothing to go by
+        let later = start + Duration::from_secs(5);
+        assert_eq!(limiter.allows(later, 100), Err(true));
+    }
+}
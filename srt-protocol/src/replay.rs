@@ -0,0 +1,295 @@
+//! Replay a trace of received packets into the receiver, with their original timing
+//!
+//! Time is simulated, so replaying a trace always gives the same result, and a trace captured in the field (see
+//! [`PacketTap`](crate::PacketTap)) can be turned into a regression test. Only the packets the receiver got from its peer should be
+//! replayed, the receiver's own packets are generated again.
+//!
+//! Encrypted traces can't be decrypted without the keys, so their payloads are released as they are.
+
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use log::trace;
+
+use crate::protocol::handshake::Handshake;
+use crate::protocol::receiver::{Receiver, ReceiverAlgorithmAction, ReceiverMetrics};
+use crate::{
+    packet::SrtShakeFlags, BreakCriteria, ConnectionSettings, ControlPacket, KmState, Packet,
+    RetransmitAlgorithm, SendDropPolicy, SocketID, SrtVersion, SystemClock, TransmissionType,
+};
+
+/// How long to keep going after the last packet of a trace, on top of the latency, for lost packets to be reported
+const LINGER: Duration = Duration::from_secs(1);
+
+/// A packet of a trace, received `offset` after the start of the trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracePacket {
+    pub offset: Duration,
+    pub packet: Packet,
+}
+
+/// What the receiver did during a replay, `at` is the time since the start of the trace
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum ReplayEvent {
+    /// A message was released, `delay` after it was sent
+    Released {
+        at: Duration,
+        delay: Duration,
+        payload: Bytes,
+    },
+
+    /// The receiver sent a control packet to its peer
+    Sent { at: Duration, packet: ControlPacket },
+
+    /// The receiver closed, after a shutdown from its peer
+    Closed { at: Duration },
+}
+
+/// The outcome of [`replay_receiver`]
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub events: Vec<ReplayEvent>,
+    pub metrics: ReceiverMetrics,
+}
+
+impl Replay {
+    /// The payloads of the messages released, in order
+    pub fn released(&self) -> impl Iterator<Item = &Bytes> {
+        self.events.iter().filter_map(|event| match event {
+            ReplayEvent::Released { payload, .. } => Some(payload),
+            _ => None,
+        })
+    }
+}
+
+/// Settings for replaying `trace` with a receiving latency of `latency`
+///
+/// The parts of the connection a receiver cares about that aren't in its data packets come from the defaults of a
+/// live connection. Returns `None` if the trace has no data packets.
+pub fn trace_settings(trace: &[TracePacket], latency: Duration) -> Option<ConnectionSettings> {
+    let first = trace.iter().find_map(|tp| match &tp.packet {
+        Packet::Data(data) => Some(data),
+        _ => None,
+    })?;
+
+    Some(ConnectionSettings {
+        remote: ([127, 0, 0, 1], 0).into(),
+        remote_sockid: SocketID(0),
+        local_sockid: first.dest_sockid,
+        socket_start_time: Instant::now(),
+        clock: Arc::new(SystemClock),
+        init_send_seq_num: first.seq_number,
+        init_recv_seq_num: first.seq_number,
+        max_packet_size: 1500,
+        max_flow_size: TransmissionType::Live.default_flow_size(),
+        send_tsbpd_latency: latency,
+        recv_tsbpd_latency: latency,
+        crypto_manager: None,
+        send_km_state: KmState::Unsecured,
+        recv_km_state: KmState::Unsecured,
+        transmission_type: TransmissionType::Live,
+        retransmit_algorithm: RetransmitAlgorithm::Aggressive,
+        packet_budget: 64,
+        max_message_size: 8 * 1024 * 1024,
+        peer_version: SrtVersion::CURRENT,
+        peer_flags: SrtShakeFlags::SUPPORTED,
+        control_packet_handler: None,
+        packet_tap: None,
+        send_buffer_monitor: None,
+        data_idle_monitor: None,
+        stall_monitor: None,
+        mtu_blackhole_monitor: None,
+        break_criteria: BreakCriteria::default(),
+        rate_limit: None,
+        rate_limit_control: Default::default(),
+        retransmit_budget: None,
+        ack_interval: None,
+        nak_interval: None,
+        send_drop_policy: SendDropPolicy::default(),
+        source_validation: Default::default(),
+        transfer_quota: Default::default(),
+        linger: None,
+        recv_timeout: None,
+        send_timeout: None,
+        app_protocol: None,
+        peer_extensions: vec![],
+        connect_stats: Default::default(),
+    })
+}
+
+/// Feed every packet in `trace` to a receiver with `settings`, as if they came from `settings.remote`
+///
+/// Runs until the receiver closes, or until every packet could have been released once the trace ends.
+pub fn replay_receiver(
+    settings: ConnectionSettings,
+    trace: impl IntoIterator<Item = TracePacket>,
+) -> Replay {
+    let start = settings.socket_start_time;
+    let remote: SocketAddr = settings.remote;
+    let end_after = settings.recv_tsbpd_latency + LINGER;
+    let mut receiver = Receiver::new(settings, Handshake::Connector);
+
+    let mut trace = trace.into_iter().peekable();
+    let mut events = Vec::new();
+    let mut last_offset = Duration::from_secs(0);
+    let mut now = start;
+    loop {
+        while let Some(tp) = trace.next_if(|tp| start + tp.offset <= now) {
+            trace!("Replaying {:?} at {:?}", tp.packet, tp.offset);
+            last_offset = tp.offset;
+            receiver.handle_packet(now, (tp.packet, remote));
+        }
+
+        let at = now - start;
+        let timeout = loop {
+            match receiver.next_algorithm_action(now) {
+                ReceiverAlgorithmAction::TimeBoundedReceive(t) => break t,
+                ReceiverAlgorithmAction::SendControl(packet, _) => {
+                    events.push(ReplayEvent::Sent { at, packet })
+                }
+                ReceiverAlgorithmAction::OutputData((origin, payload)) => {
+                    events.push(ReplayEvent::Released {
+                        at,
+                        delay: now.saturating_duration_since(origin),
+                        payload,
+                    })
+                }
+                ReceiverAlgorithmAction::Close => {
+                    events.push(ReplayEvent::Closed { at });
+                    return Replay {
+                        events,
+                        metrics: receiver.metrics(),
+                    };
+                }
+            }
+        };
+
+        now = match trace.peek() {
+            Some(tp) => timeout.min(start + tp.offset),
+            None if timeout <= start + last_offset + end_after => timeout,
+            None => break,
+        };
+    }
+
+    Replay {
+        events,
+        metrics: receiver.metrics(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::packet::{ControlTypes, DataEncryption, DataPacket, PacketLocation};
+    use crate::protocol::TimeStamp;
+    use crate::SeqNumber;
+
+    fn data(seq: u32, millis: u64) -> TracePacket {
+        TracePacket {
+            offset: Duration::from_millis(millis),
+            packet: Packet::Data(DataPacket {
+                seq_number: SeqNumber::new_truncate(seq),
+                message_loc: PacketLocation::ONLY,
+                in_order_delivery: false,
+                encryption: DataEncryption::None,
+                retransmitted: false,
+                message_number: crate::MsgNumber::new_truncate(seq),
+                timestamp: TimeStamp::from_micros(millis as u32 * 1_000),
+                dest_sockid: SocketID(1234),
+                payload: Bytes::from(seq.to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn in_order() {
+        let trace: Vec<_> = (0..10).map(|i| data(i, u64::from(i) * 10)).collect();
+        let settings = trace_settings(&trace, Duration::from_millis(100)).unwrap();
+
+        let replay = replay_receiver(settings, trace);
+        let released: Vec<_> = replay.released().cloned().collect();
+        assert_eq!(released, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+        for event in &replay.events {
+            if let ReplayEvent::Released { delay, .. } = event {
+                assert!(*delay >= Duration::from_millis(100), "{:?}", delay);
+                assert!(*delay < Duration::from_millis(120), "{:?}", delay);
+            }
+        }
+    }
+
+    // replaying twice gives the same result
+    #[test]
+    fn deterministic() {
+        let trace: Vec<_> = (0..10).map(|i| data(i, u64::from(i) * 10)).collect();
+        let settings = trace_settings(&trace, Duration::from_millis(100)).unwrap();
+
+        let a = replay_receiver(settings.clone(), trace.clone());
+        let b = replay_receiver(settings, trace);
+        assert_eq!(a.events, b.events);
+    }
+
+    #[test]
+    fn lost_packet() {
+        // 3 never arrives
+        let trace: Vec<_> = (0..10)
+            .filter(|&i| i != 3)
+            .map(|i| data(i, u64::from(i) * 10))
+            .collect();
+        let settings = trace_settings(&trace, Duration::from_millis(100)).unwrap();
+
+        let replay = replay_receiver(settings, trace);
+        assert!(replay.events.iter().any(|event| matches!(
+            event,
+            ReplayEvent::Sent {
+                packet: ControlPacket {
+                    control_type: ControlTypes::Nak(_),
+                    ..
+                },
+                ..
+            }
+        )));
+        let released: Vec<_> = replay.released().cloned().collect();
+        assert_eq!(
+            released,
+            (0..10)
+                .filter(|&i| i != 3)
+                .map(|i| i.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // once the lost packet is dropped, the ACKs move past it
+    #[test]
+    fn ack_past_dropped() {
+        let trace: Vec<_> = (0..10)
+            .filter(|&i| i != 3)
+            .map(|i| data(i, u64::from(i) * 10))
+            .collect();
+        let settings = trace_settings(&trace, Duration::from_millis(100)).unwrap();
+
+        let replay = replay_receiver(settings, trace);
+        let last_ack = replay
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                ReplayEvent::Sent {
+                    packet:
+                        ControlPacket {
+                            control_type: ControlTypes::Ack(ack),
+                            ..
+                        },
+                    ..
+                } => Some(ack.ack_number),
+                _ => None,
+            })
+            .last();
+        assert_eq!(last_ack, Some(SeqNumber::new_truncate(10)));
+        assert_eq!(replay.metrics.too_late_packets, 1);
+    }
+}
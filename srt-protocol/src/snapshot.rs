@@ -0,0 +1,301 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::crypto::{CryptoManager, CryptoOptions};
+use crate::packet::{Auth, CipherType, KeyFlags, PacketType, SrtKeyMessage, SrtShakeFlags};
+use crate::pending_connection::{ConnInitSettings, ConnectError};
+use crate::{ConnectStats, ConnectionSettings, KmState, SeqNumber, SocketID, SrtVersion};
+
+/// The keys of an encrypted connection, wrapped with the key derived from the passphrase, which isn't kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WrappedKeys {
+    pub even: bool,
+    pub odd: bool,
+    pub salt: Vec<u8>,
+    pub wrapped: Vec<u8>,
+}
+
+/// Experimental: the state a connection needs to carry on in another process, for upgrading a binary without
+/// breaking its connections
+///
+/// The old process takes the snapshot as it stops the connection without telling the peer, and the new one restores
+/// it over the same UDP socket, which it inherited, before the peer times out. Only what was negotiated and where the
+/// sequence numbers are is kept: data buffered in either direction is lost, which the peer sees as loss, and the
+/// keys are wrapped with the passphrase, which the new process must have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionSnapshot {
+    pub remote: SocketAddr,
+    pub remote_sockid: u32,
+    pub local_sockid: u32,
+
+    /// When the connection started, on the wall clock, so the timestamps carry on across processes
+    pub started: SystemTime,
+
+    /// The sequence number of the next data packet to send
+    pub next_send_seq_num: u32,
+
+    /// The sequence number of the next data packet expected from the peer
+    pub next_recv_seq_num: u32,
+
+    pub max_packet_size: u32,
+    pub max_flow_size: u32,
+    pub send_tsbpd_latency: Duration,
+    pub recv_tsbpd_latency: Duration,
+    pub peer_version: SrtVersion,
+    pub peer_flags: u32,
+    pub send_km_state: KmState,
+    pub recv_km_state: KmState,
+    pub keys: Option<WrappedKeys>,
+    pub app_protocol: Option<String>,
+}
+
+impl ConnectionSnapshot {
+    /// A snapshot of the connection with `settings`, at `now`, with the sender and receiver at the sequence numbers
+    /// given
+    pub fn new(
+        settings: &ConnectionSettings,
+        now: Instant,
+        next_send_seq_num: SeqNumber,
+        next_recv_seq_num: SeqNumber,
+    ) -> Self {
+        let keys = settings.crypto_manager.as_ref().map(|cm| {
+            let km = cm.generate_km();
+            WrappedKeys {
+                even: km.key_flags.contains(KeyFlags::EVEN),
+                odd: km.key_flags.contains(KeyFlags::ODD),
+                salt: km.salt,
+                wrapped: km.wrapped_keys,
+            }
+        });
+        ConnectionSnapshot {
+            remote: settings.remote,
+            remote_sockid: settings.remote_sockid.0,
+            local_sockid: settings.local_sockid.0,
+            started: SystemTime::now() - (now - settings.socket_start_time),
+            next_send_seq_num: next_send_seq_num.as_raw(),
+            next_recv_seq_num: next_recv_seq_num.as_raw(),
+            max_packet_size: settings.max_packet_size,
+            max_flow_size: settings.max_flow_size,
+            send_tsbpd_latency: settings.send_tsbpd_latency,
+            recv_tsbpd_latency: settings.recv_tsbpd_latency,
+            peer_version: settings.peer_version,
+            peer_flags: settings.peer_flags.bits(),
+            send_km_state: settings.send_km_state,
+            recv_km_state: settings.recv_km_state,
+            keys,
+            app_protocol: settings.app_protocol.clone(),
+        }
+    }
+
+    /// The settings to carry on the connection with, taking what isn't in the snapshot, such as the passphrase,
+    /// callbacks and buffer sizes, from `init`. Fails with [`ConnectError::BadSecret`] if the passphrase doesn't
+    /// unwrap the keys, and [`ConnectError::Unsecure`] if only one of the snapshot and `init` has encryption.
+    pub fn restore(self, init: &ConnInitSettings) -> Result<ConnectionSettings, ConnectError> {
+        let mut key_length_adopted = false;
+        let crypto_manager = match (self.keys, &init.crypto) {
+            (None, None) => None,
+            (Some(keys), Some(options)) => {
+                // the keys can be of the peer's length, rather than the configured one
+                let count = keys.even as usize + keys.odd as usize;
+                let size = keys.wrapped.len().saturating_sub(8) / count.max(1);
+                if count == 0
+                    || keys.wrapped.len() != size * count + 8
+                    || ![16, 24, 32].contains(&size)
+                    || keys.salt.len() != 16
+                {
+                    return Err(ConnectError::BadSecret);
+                }
+                key_length_adopted = size != usize::from(options.size);
+                let options = CryptoOptions {
+                    size: size as u8,
+                    ..options.clone()
+                };
+                let mut key_flags = KeyFlags::empty();
+                key_flags.set(KeyFlags::EVEN, keys.even);
+                key_flags.set(KeyFlags::ODD, keys.odd);
+                let km = SrtKeyMessage {
+                    pt: PacketType::KeyingMaterial,
+                    key_flags,
+                    keki: 0,
+                    cipher: CipherType::CTR,
+                    auth: Auth::None,
+                    salt: keys.salt,
+                    wrapped_keys: keys.wrapped,
+                };
+                Some(CryptoManager::new_from_kmreq(options, &km)?)
+            }
+            _ => return Err(ConnectError::Unsecure),
+        };
+
+        // as far back on this process's clock as the connection started on the wall clock
+        let now = init.clock.now();
+        let age = SystemTime::now()
+            .duration_since(self.started)
+            .unwrap_or_default();
+        Ok(ConnectionSettings {
+            remote: self.remote,
+            remote_sockid: SocketID(self.remote_sockid),
+            local_sockid: SocketID(self.local_sockid),
+            socket_start_time: now.checked_sub(age).unwrap_or(now),
+            clock: init.clock.clone(),
+            init_send_seq_num: SeqNumber::new_truncate(self.next_send_seq_num),
+            init_recv_seq_num: SeqNumber::new_truncate(self.next_recv_seq_num),
+            max_packet_size: self.max_packet_size,
+            max_flow_size: self.max_flow_size,
+            send_tsbpd_latency: self.send_tsbpd_latency,
+            recv_tsbpd_latency: self.recv_tsbpd_latency,
+            crypto_manager,
+            send_km_state: self.send_km_state,
+            recv_km_state: self.recv_km_state,
+            transmission_type: init.transmission_type,
+            retransmit_algorithm: init.retransmit_algorithm,
+            packet_budget: init.packet_budget,
+            max_message_size: init.max_message_size,
+            peer_version: self.peer_version,
+            peer_flags: SrtShakeFlags::from_bits_truncate(self.peer_flags),
+            control_packet_handler: init.control_packet_handler.clone(),
+            packet_tap: init.packet_tap.clone(),
+            send_buffer_monitor: init.send_buffer_monitor.clone(),
+            data_idle_monitor: init.data_idle_monitor.clone(),
+            stall_monitor: init.stall_monitor.clone(),
+            mtu_blackhole_monitor: init.mtu_blackhole_monitor.clone(),
+            break_criteria: init.break_criteria,
+            rate_limit: init.rate_limit,
+            rate_limit_control: Default::default(),
+            retransmit_budget: init.retransmit_budget,
+            ack_interval: init.ack_interval,
+            nak_interval: init.nak_interval,
+            send_drop_policy: init.send_drop_policy,
+            source_validation: init.source_validation,
+            transfer_quota: init.transfer_quota,
+            linger: init.linger,
+            recv_timeout: init.recv_timeout,
+            send_timeout: init.send_timeout,
+            app_protocol: self.app_protocol,
+            peer_extensions: Vec::new(),
+            connect_stats: ConnectStats {
+                key_length_adopted,
+                ..ConnectStats::default()
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> ConnectionSnapshot {
+        ConnectionSnapshot {
+            remote: ([127, 0, 0, 1], 2000).into(),
+            remote_sockid: 1,
+            local_sockid: 2,
+            started: SystemTime::now() - Duration::from_secs(10),
+            next_send_seq_num: 100,
+            next_recv_seq_num: 200,
+            max_packet_size: 1500,
+            max_flow_size: 8192,
+            send_tsbpd_latency: Duration::from_millis(120),
+            recv_tsbpd_latency: Duration::from_millis(200),
+            peer_version: SrtVersion::CURRENT,
+            peer_flags: 0,
+            send_km_state: KmState::Unsecured,
+            recv_km_state: KmState::Unsecured,
+            keys: None,
+            app_protocol: None,
+        }
+    }
+
+    fn init(passphrase: Option<&str>) -> ConnInitSettings {
+        ConnInitSettings {
+            crypto: passphrase.map(|passphrase| CryptoOptions::passphrase(16, passphrase)),
+            ..ConnInitSettings::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = snapshot().restore(&init(None)).unwrap();
+        assert_eq!(settings.local_sockid, SocketID(2));
+        assert_eq!(settings.init_send_seq_num, SeqNumber::new_truncate(100));
+        assert_eq!(settings.init_recv_seq_num, SeqNumber::new_truncate(200));
+        let age = settings.clock.now() - settings.socket_start_time;
+        assert!(age >= Duration::from_secs(10) && age < Duration::from_secs(11));
+
+        let now = settings.clock.now();
+        let again = ConnectionSnapshot::new(
+            &settings,
+            now,
+            SeqNumber::new_truncate(150),
+            SeqNumber::new_truncate(250),
+        );
+        assert_eq!(again.next_send_seq_num, 150);
+        assert_eq!(again.next_recv_seq_num, 250);
+        let drift = match again.started.duration_since(snapshot().started) {
+            Ok(drift) => drift,
+            Err(e) => e.duration(),
+        };
+        assert!(drift < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn keys() {
+        let mut settings = snapshot().restore(&init(None)).unwrap();
+        settings.crypto_manager = init(Some("password123"))
+            .crypto
+            .map(CryptoManager::new_random);
+        let now = settings.clock.now();
+        let snapshot = ConnectionSnapshot::new(
+            &settings,
+            now,
+            SeqNumber::new_truncate(0),
+            SeqNumber::new_truncate(0),
+        );
+
+        let restored = snapshot
+            .clone()
+            .restore(&init(Some("password123")))
+            .unwrap();
+        let mut data = *b"hello";
+        let seq = SeqNumber::new_truncate(5);
+        let enc = settings.crypto_manager.unwrap().encrypt(seq, &mut data);
+        assert_ne!(&data, b"hello");
+        restored
+            .crypto_manager
+            .unwrap()
+            .decrypt(seq, enc, &mut data);
+        assert_eq!(&data, b"hello");
+
+        assert!(matches!(
+            snapshot.clone().restore(&init(Some("password456"))),
+            Err(ConnectError::BadSecret)
+        ));
+        assert!(matches!(
+            snapshot.restore(&init(None)),
+            Err(ConnectError::Unsecure)
+        ));
+    }
+
+    /// Keys of the peer's length are restored whatever length is configured
+    #[test]
+    fn adopted_key_length() {
+        let mut settings = snapshot().restore(&init(None)).unwrap();
+        settings.crypto_manager = Some(CryptoManager::new_random(CryptoOptions::passphrase(
+            32,
+            "password123",
+        )));
+        let now = settings.clock.now();
+        let snapshot = ConnectionSnapshot::new(
+            &settings,
+            now,
+            SeqNumber::new_truncate(0),
+            SeqNumber::new_truncate(0),
+        );
+
+        let restored = snapshot.restore(&init(Some("password123"))).unwrap();
+        assert_eq!(restored.info().key_length, 32);
+        assert!(restored.info().key_length_adopted);
+    }
+}
@@ -0,0 +1,12 @@
+use crate::protocol::{receiver::ReceiverMetrics, sender::SenderMetrics};
+
+/// A snapshot of the statistics of a connection
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SocketStatistics {
+    /// Metrics from the sending side, including congestion control state
+    pub sender: SenderMetrics,
+
+    /// Metrics from the receiving side
+    pub receiver: ReceiverMetrics,
+}
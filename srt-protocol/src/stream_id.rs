@@ -0,0 +1,230 @@
+use std::{error::Error, fmt, str::FromStr};
+
+/// What the caller means to do with the stream, the `m` key of a [`StreamId`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamMode {
+    /// Receive the stream, the default
+    Request,
+    /// Send the stream
+    Publish,
+    /// Both send and receive
+    Bidirectional,
+}
+
+impl StreamMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            StreamMode::Request => "request",
+            StreamMode::Publish => "publish",
+            StreamMode::Bidirectional => "bidirectional",
+        }
+    }
+}
+
+/// A stream id in the standard syntax of the SRT access control guidelines, such as
+/// `#!::u=alice,r=live/cam1,m=publish`
+///
+/// The stream id is a comma separated list of `key=value` after `#!::`. The standard keys have their own field, the
+/// others are kept in [`custom`](StreamId::custom) in order. Parse one with [`str::parse`], such as the
+/// [`ConnectionInfo::stream_id`](crate::ConnectionInfo::stream_id) of a listener, and format one with `to_string`.
+/// The nested syntax, `#!:{...}`, isn't supported.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamId {
+    /// The user name, the `u` key
+    pub user: Option<String>,
+
+    /// The name of the resource, such as a stream name, the `r` key
+    pub resource: Option<String>,
+
+    /// The host name, for a listener serving several, the `h` key
+    pub host: Option<String>,
+
+    /// The session id, such as a token to check, the `s` key
+    pub session_id: Option<String>,
+
+    /// The type of the resource, `stream`, `file` or `auth` in the guidelines, the `t` key
+    pub stream_type: Option<String>,
+
+    /// What the caller means to do with the stream, the `m` key
+    pub mode: Option<StreamMode>,
+
+    /// The keys that aren't standard, with their values, in order
+    pub custom: Vec<(String, String)>,
+}
+
+/// Why a stream id couldn't be parsed as a [`StreamId`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamIdParseError {
+    /// It doesn't start with `#!::`
+    NotStandard,
+    /// An entry has no `=`
+    MissingValue(String),
+    /// A key appears twice
+    DuplicateKey(String),
+    /// The mode isn't one of `request`, `publish` or `bidirectional`
+    BadMode(String),
+}
+
+impl fmt::Display for StreamIdParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use StreamIdParseError::*;
+        match self {
+            NotStandard => write!(f, "Stream id doesn't start with #!::"),
+            MissingValue(entry) => write!(f, "Stream id entry {:?} has no value", entry),
+            DuplicateKey(key) => write!(f, "Stream id key {:?} is repeated", key),
+            BadMode(mode) => write!(f, "Unknown stream id mode {:?}", mode),
+        }
+    }
+}
+
+impl Error for StreamIdParseError {}
+
+const PREFIX: &str = "#!::";
+
+impl StreamId {
+    /// The value of the custom key `key`, if any
+    pub fn custom(&self, key: &str) -> Option<&str> {
+        self.custom
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The mode, [`StreamMode::Request`] if none is given
+    pub fn mode_or_default(&self) -> StreamMode {
+        self.mode.unwrap_or(StreamMode::Request)
+    }
+
+    fn standard_keys(&self) -> [(&'static str, Option<&str>); 6] {
+        [
+            ("u", self.user.as_deref()),
+            ("r", self.resource.as_deref()),
+            ("h", self.host.as_deref()),
+            ("s", self.session_id.as_deref()),
+            ("t", self.stream_type.as_deref()),
+            ("m", self.mode.map(StreamMode::as_str)),
+        ]
+    }
+}
+
+impl FromStr for StreamId {
+    type Err = StreamIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use StreamIdParseError::*;
+
+        let entries = match s.strip_prefix(PREFIX) {
+            Some(entries) => entries,
+            None => return Err(NotStandard),
+        };
+        let mut id = StreamId::default();
+        for entry in entries.split(',').filter(|entry| !entry.is_empty()) {
+            let (key, value) = match entry.split_once('=') {
+                Some(key_value) => key_value,
+                None => return Err(MissingValue(entry.to_string())),
+            };
+            let field = match key {
+                "u" => &mut id.user,
+                "r" => &mut id.resource,
+                "h" => &mut id.host,
+                "s" => &mut id.session_id,
+                "t" => &mut id.stream_type,
+                "m" => {
+                    if id.mode.is_some() {
+                        return Err(DuplicateKey(key.to_string()));
+                    }
+                    id.mode = Some(match value {
+                        "request" => StreamMode::Request,
+                        "publish" => StreamMode::Publish,
+                        "bidirectional" => StreamMode::Bidirectional,
+                        _ => return Err(BadMode(value.to_string())),
+                    });
+                    continue;
+                }
+                _ => {
+                    if id.custom(key).is_some() {
+                        return Err(DuplicateKey(key.to_string()));
+                    }
+                    id.custom.push((key.to_string(), value.to_string()));
+                    continue;
+                }
+            };
+            if field.is_some() {
+                return Err(DuplicateKey(key.to_string()));
+            }
+            *field = Some(value.to_string());
+        }
+        Ok(id)
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", PREFIX)?;
+        let standard = self.standard_keys();
+        let standard = standard
+            .iter()
+            .filter_map(|(key, value)| Some((*key, (*value)?)));
+        let custom = self.custom.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+        for (i, (key, value)) in standard.chain(custom).enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let id: StreamId = "#!::u=alice,r=live/cam1,m=publish,s=abc123,token=xyz"
+            .parse()
+            .unwrap();
+        assert_eq!(id.user.as_deref(), Some("alice"));
+        assert_eq!(id.resource.as_deref(), Some("live/cam1"));
+        assert_eq!(id.host, None);
+        assert_eq!(id.session_id.as_deref(), Some("abc123"));
+        assert_eq!(id.mode, Some(StreamMode::Publish));
+        assert_eq!(id.custom("token"), Some("xyz"));
+        assert_eq!(id.custom("other"), None);
+
+        let id: StreamId = "#!::r=live".parse().unwrap();
+        assert_eq!(id.mode_or_default(), StreamMode::Request);
+    }
+
+    #[test]
+    fn format() {
+        let id = StreamId {
+            user: Some("alice".into()),
+            resource: Some("live".into()),
+            mode: Some(StreamMode::Bidirectional),
+            custom: vec![("token".into(), "xyz".into())],
+            ..StreamId::default()
+        };
+        let formatted = id.to_string();
+        assert_eq!(formatted, "#!::u=alice,r=live,m=bidirectional,token=xyz");
+        assert_eq!(formatted.parse::<StreamId>().unwrap(), id);
+
+        assert_eq!(StreamId::default().to_string(), "#!::");
+    }
+
+    #[test]
+    fn parse_errors() {
+        use StreamIdParseError::*;
+        let parse = |s: &str| s.parse::<StreamId>().unwrap_err();
+
+        assert_eq!(parse("live/cam1"), NotStandard);
+        assert_eq!(parse("#!:{u=alice}"), NotStandard);
+        assert_eq!(parse("#!::u=alice,live"), MissingValue("live".into()));
+        assert_eq!(parse("#!::u=alice,u=bob"), DuplicateKey("u".into()));
+        assert_eq!(parse("#!::a=1,a=2"), DuplicateKey("a".into()));
+        assert_eq!(parse("#!::m=push"), BadMode("push".into()));
+    }
+}
@@ -0,0 +1,148 @@
+//! Sending and receiving packets over a datagram channel the host provides
+//!
+//! The state machines of this crate don't do any I/O, they take the packets that arrived and return the packets to
+//! send. A host without UDP sockets, such as a WebAssembly runtime relaying datagrams over WebTransport, implements
+//! [`DatagramTransport`] for its channel and drives them with it.
+//!
+//! The crate has no dependency on an async runtime or on sockets, so it builds for `wasm32-unknown-unknown` and
+//! `wasm32-wasip1`. In browsers, `std` has no randomness and no clock: enable the `wasm-bindgen` feature of
+//! `getrandom` 0.1 in the host crate for the random socket ids and sequence numbers, and keep in mind that the
+//! handshake cookies and `Instant::now` need a clock, which `wasm32-wasip1` has.
+
+use std::io::Cursor;
+use std::net::SocketAddr;
+
+use crate::{Packet, PacketParseError};
+
+/// The largest datagram a packet is received from, the largest UDP payload
+pub const MAX_DATAGRAM: usize = 65_536;
+
+/// A received packet and where it came from, or why it didn't parse
+pub type RecvResult = Result<(Packet, SocketAddr), PacketParseError>;
+
+/// A channel carrying datagrams between addresses, in place of a UDP socket
+///
+/// The addresses are whatever the host uses to tell peers apart, they're only compared and handed back.
+pub trait DatagramTransport {
+    type Error;
+
+    /// Send `datagram` to `to`
+    fn send_to(&mut self, datagram: &[u8], to: SocketAddr) -> Result<(), Self::Error>;
+
+    /// Receive a datagram into `buf`, with its length and where it came from, or `None` if there's none waiting
+    fn try_recv_from(&mut self, buf: &mut [u8])
+        -> Result<Option<(usize, SocketAddr)>, Self::Error>;
+
+    /// Serialize `packet` and send it to `to`
+    fn send_packet(&mut self, packet: &Packet, to: SocketAddr) -> Result<(), Self::Error> {
+        let mut datagram = Vec::new();
+        packet.serialize(&mut datagram);
+        self.send_to(&datagram, to)
+    }
+
+    /// Receive and parse a packet, if one is waiting
+    ///
+    /// Packets that fail to parse come out as the inner error, the transport can go on after them.
+    fn try_recv_packet(&mut self) -> Result<Option<RecvResult>, Self::Error> {
+        let mut buf = vec![0; MAX_DATAGRAM];
+        Ok(self.try_recv_from(&mut buf)?.map(|(len, from)| {
+            Packet::parse(&mut Cursor::new(&buf[..len])).map(|packet| (packet, from))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use crate::pending_connection::{
+        connect::{Connect, ConnectState},
+        listen::{Listen, ListenState},
+        ConnInitSettings,
+    };
+
+    type Queue = Rc<RefCell<VecDeque<(Vec<u8>, SocketAddr)>>>;
+
+    // one end of an in-memory channel
+    struct Memory {
+        local: SocketAddr,
+        incoming: Queue,
+        outgoing: Queue,
+    }
+
+    fn pair(a: SocketAddr, b: SocketAddr) -> (Memory, Memory) {
+        let (a_to_b, b_to_a) = (Queue::default(), Queue::default());
+        (
+            Memory {
+                local: a,
+                incoming: b_to_a.clone(),
+                outgoing: a_to_b.clone(),
+            },
+            Memory {
+                local: b,
+                incoming: a_to_b,
+                outgoing: b_to_a,
+            },
+        )
+    }
+
+    impl DatagramTransport for Memory {
+        type Error = Infallible;
+
+        fn send_to(&mut self, datagram: &[u8], _to: SocketAddr) -> Result<(), Infallible> {
+            self.outgoing
+                .borrow_mut()
+                .push_back((datagram.to_vec(), self.local));
+            Ok(())
+        }
+
+        fn try_recv_from(
+            &mut self,
+            buf: &mut [u8],
+        ) -> Result<Option<(usize, SocketAddr)>, Infallible> {
+            Ok(self.incoming.borrow_mut().pop_front().map(|(data, from)| {
+                buf[..data.len()].copy_from_slice(&data);
+                (data.len(), from)
+            }))
+        }
+    }
+
+    #[test]
+    fn handshake_over_memory() {
+        let caller_addr: SocketAddr = "10.0.0.1:1000".parse().unwrap();
+        let listener_addr: SocketAddr = "10.0.0.2:2000".parse().unwrap();
+        let (mut caller_chan, mut listener_chan) = pair(caller_addr, listener_addr);
+
+        let mut connect = Connect::new(
+            listener_addr,
+            caller_addr.ip(),
+            ConnInitSettings::default().copy_randomize(),
+        );
+        let mut listen = Listen::new(ConnInitSettings::default().copy_randomize());
+
+        let (packet, to) = connect.handle_tick(Instant::now()).unwrap().unwrap();
+        caller_chan.send_packet(&packet, to).unwrap();
+
+        for _ in 0..4 {
+            while let Some(packet) = listener_chan.try_recv_packet().unwrap() {
+                if let Some((reply, to)) = listen.handle_packet(packet.unwrap()).unwrap() {
+                    listener_chan.send_packet(&reply, to).unwrap();
+                }
+            }
+            while let Some(packet) = caller_chan.try_recv_packet().unwrap() {
+                if let Some((reply, to)) = connect.handle_packet(packet.unwrap()).unwrap() {
+                    caller_chan.send_packet(&reply, to).unwrap();
+                }
+            }
+        }
+
+        assert!(matches!(connect.state(), ConnectState::Connected(_)));
+        assert!(matches!(listen.state(), ListenState::Connected(..)));
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::SrtVersion;
+
+/// A behavior of the protocol that only some versions have, see [`ProtocolVersion::supports`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Handshake extensions past the SRT handshake and keys, such as the stream id, 1.3.0
+    Hsv5Extensions,
+    /// Packet filters, such as FEC, negotiated in the handshake, 1.4.0
+    PacketFilter,
+    /// AES-GCM authenticated encryption, 1.5.0
+    AesGcm,
+}
+
+impl Feature {
+    /// The first version with this feature
+    pub fn since(self) -> SrtVersion {
+        match self {
+            Feature::Hsv5Extensions => SrtVersion::new(1, 3, 0),
+            Feature::PacketFilter => SrtVersion::new(1, 4, 0),
+            Feature::AesGcm => SrtVersion::new(1, 5, 0),
+        }
+    }
+}
+
+/// The version of the protocol spoken on a connection, the older of the two sides', see
+/// [`ConnectionSettings::protocol_version`](crate::ConnectionSettings::protocol_version)
+///
+/// Behaviors that depend on the version are conditioned on [`supports`](ProtocolVersion::supports), rather than
+/// assuming both sides have the latest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ProtocolVersion(SrtVersion);
+
+impl ProtocolVersion {
+    /// The version spoken by sides with versions `local` and `peer`
+    pub fn negotiate(local: SrtVersion, peer: SrtVersion) -> Self {
+        ProtocolVersion(local.min(peer))
+    }
+
+    /// The version spoken with a peer of version `peer`, by this implementation
+    pub fn with_peer(peer: SrtVersion) -> Self {
+        Self::negotiate(SrtVersion::CURRENT, peer)
+    }
+
+    pub fn version(self) -> SrtVersion {
+        self.0
+    }
+
+    /// Whether both sides have `feature`
+    pub fn supports(self, feature: Feature) -> bool {
+        self.0 >= feature.since()
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let version =
+            ProtocolVersion::negotiate(SrtVersion::new(1, 4, 2), SrtVersion::new(1, 3, 4));
+        assert_eq!(version.version(), SrtVersion::new(1, 3, 4));
+        assert!(version.supports(Feature::Hsv5Extensions));
+        assert!(!version.supports(Feature::PacketFilter));
+
+        let version =
+            ProtocolVersion::negotiate(SrtVersion::new(1, 5, 0), SrtVersion::new(1, 5, 1));
+        assert!(version.supports(Feature::AesGcm));
+
+        let version = ProtocolVersion::with_peer(SrtVersion::new(1, 2, 0));
+        assert!(!version.supports(Feature::Hsv5Extensions));
+        // until they're implemented
+        let version = ProtocolVersion::with_peer(SrtVersion::new(9, 0, 0));
+        assert_eq!(version.version(), SrtVersion::CURRENT);
+        assert!(!version.supports(Feature::PacketFilter));
+        assert!(!version.supports(Feature::AesGcm));
+    }
+}
@@ -0,0 +1,558 @@
+// byte level vectors of the wire format of the reference implementation (libsrt)
+//
+// Vectors marked as captured were taken from wireshark on packets sent by libsrt, the rest were put together from the
+// layouts in its srtcore/packet.h, srtcore/handshake.h and haicrypt/hcrypt_msg.h. Every vector that this library can
+// send is checked both ways: decoding gives the packet, and encoding the packet gives the exact bytes back.
+
+use std::{io::Cursor, time::Duration};
+
+use bytes::Bytes;
+use srt_protocol::{
+    packet::{
+        AckControlInfo, Auth, CipherType, ControlTypes, DataEncryption, HandshakeControlInfo,
+        HandshakeVSInfo, KeyFlags, PacketLocation, PacketType, RejectReason, ShakeType, SocketType,
+        SrtControlPacket, SrtHandshake, SrtKeyMessage, SrtShakeFlags,
+    },
+    protocol::{TimeSpan, TimeStamp},
+    ControlPacket, DataPacket, MsgNumber, Packet, PacketParseError, SeqNumber, SocketID,
+    SrtVersion,
+};
+
+const CALLER_SOCKID: SocketID = SocketID(0x1A2B_3C4D);
+const LISTENER_SOCKID: SocketID = SocketID(0x5E6F_7081);
+const ISN: u32 = 0x2D5B_9F11;
+const COOKIE: i32 = 0x7A3D_2C1B;
+
+/// The key material of a captured conclusion handshake, wrapping an AES-128 even key
+const KM_SALT: &str = "9D75B0AC924C6E4C9EC40FEB4FE973DB";
+const KM_WRAP: &str = "1D215D426C18A2871EBF77E2646D9BAB15DBD7689AEF60EC";
+
+fn vector(hex: &str) -> Vec<u8> {
+    let hex: String = hex.split_whitespace().collect();
+    hex::decode(hex).unwrap()
+}
+
+fn decodes_to(hex: &str, packet: &Packet) {
+    let bytes = vector(hex);
+    let decoded = Packet::parse(&mut Cursor::new(&bytes[..])).unwrap();
+    assert_eq!(&decoded, packet);
+}
+
+fn conforms(hex: &str, packet: Packet) {
+    decodes_to(hex, &packet);
+
+    let mut encoded = vec![];
+    packet.serialize(&mut encoded);
+    assert_eq!(hex::encode_upper(encoded), hex::encode_upper(vector(hex)));
+}
+
+fn control(timestamp: u32, dest_sockid: SocketID, control_type: ControlTypes) -> Packet {
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(timestamp),
+        dest_sockid,
+        control_type,
+    })
+}
+
+fn handshake(
+    shake_type: ShakeType,
+    socket_id: SocketID,
+    syn_cookie: i32,
+    info: HandshakeVSInfo,
+) -> ControlTypes {
+    ControlTypes::Handshake(HandshakeControlInfo {
+        init_seq_num: SeqNumber::new_truncate(ISN),
+        max_packet_size: 1500,
+        max_flow_size: 8192,
+        shake_type,
+        socket_id,
+        syn_cookie,
+        peer_addr: [127, 0, 0, 1].into(),
+        info,
+    })
+}
+
+fn km(key_flags: KeyFlags) -> SrtKeyMessage {
+    SrtKeyMessage {
+        pt: PacketType::KeyingMaterial,
+        key_flags,
+        keki: 0,
+        cipher: CipherType::CTR,
+        auth: Auth::None,
+        salt: hex::decode(KM_SALT).unwrap(),
+        wrapped_keys: hex::decode(KM_WRAP).unwrap(),
+    }
+}
+
+fn data(
+    message_loc: PacketLocation,
+    encryption: DataEncryption,
+    retransmitted: bool,
+    message_number: u32,
+) -> Packet {
+    Packet::Data(DataPacket {
+        seq_number: SeqNumber::new_truncate(0x1234_5678),
+        message_loc,
+        in_order_delivery: false,
+        encryption,
+        retransmitted,
+        message_number: MsgNumber::new_truncate(message_number),
+        timestamp: TimeStamp::from_micros(123_456),
+        dest_sockid: CALLER_SOCKID,
+        payload: Bytes::from_static(b"Hello"),
+    })
+}
+
+#[test]
+fn induction_request() {
+    // an HSv5 caller starts out as HSv4, with a datagram socket type and no cookie
+    conforms(
+        "80000000 00000000 00000FA0 00000000
+         00000004 00000002 2D5B9F11 000005DC 00002000 00000001 1A2B3C4D 00000000
+         0100007F 00000000 00000000 00000000",
+        control(
+            4_000,
+            SocketID(0),
+            handshake(
+                ShakeType::Induction,
+                CALLER_SOCKID,
+                0,
+                HandshakeVSInfo::V4(SocketType::Datagram),
+            ),
+        ),
+    );
+}
+
+#[test]
+fn induction_response() {
+    // the listener answers with HSv5, the SRT magic code in place of the extension flags, and a cookie
+    conforms(
+        "80000000 00000000 00001388 1A2B3C4D
+         00000005 00004A17 2D5B9F11 000005DC 00002000 00000001 5E6F7081 7A3D2C1B
+         0100007F 00000000 00000000 00000000",
+        control(
+            5_000,
+            CALLER_SOCKID,
+            handshake(
+                ShakeType::Induction,
+                LISTENER_SOCKID,
+                COOKIE,
+                HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: None,
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            ),
+        ),
+    );
+}
+
+#[test]
+fn conclusion_request() {
+    // captured, a caller's conclusion with an HSREQ extension
+    conforms(
+        "80000000 00000000 000F9EC4 00000000
+         00000005 00000001 44BEA60D 000005DC 00002000 FFFFFFFF 3D6936B6 E3E405DD
+         0100007F 00000000 00000000 00000000
+         00010003 00010301 0000002F 00780000",
+        control(
+            1_023_684,
+            SocketID(0),
+            ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber::new_truncate(1_153_345_037),
+                max_packet_size: 1500,
+                max_flow_size: 8192,
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketID(1_030_305_462),
+                syn_cookie: -471_595_555,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
+                        version: SrtVersion::new(1, 3, 1),
+                        flags: SrtShakeFlags::TSBPDSND
+                            | SrtShakeFlags::TSBPDRCV
+                            | SrtShakeFlags::HAICRYPT
+                            | SrtShakeFlags::TLPKTDROP
+                            | SrtShakeFlags::REXMITFLG,
+                        send_latency: Duration::from_millis(120),
+                        recv_latency: Duration::from_millis(0),
+                    })),
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            }),
+        ),
+    );
+}
+
+#[test]
+fn conclusion_response() {
+    conforms(
+        "80000000 00000000 00001B58 1A2B3C4D
+         00000005 00000001 2D5B9F11 000005DC 00002000 FFFFFFFF 5E6F7081 7A3D2C1B
+         0100007F 00000000 00000000 00000000
+         00020003 00010401 0000003F 00780078",
+        control(
+            7_000,
+            CALLER_SOCKID,
+            handshake(
+                ShakeType::Conclusion,
+                LISTENER_SOCKID,
+                COOKIE,
+                HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
+                        version: SrtVersion::new(1, 4, 1),
+                        flags: SrtShakeFlags::TSBPDSND
+                            | SrtShakeFlags::TSBPDRCV
+                            | SrtShakeFlags::HAICRYPT
+                            | SrtShakeFlags::TLPKTDROP
+                            | SrtShakeFlags::NAKREPORT
+                            | SrtShakeFlags::REXMITFLG,
+                        send_latency: Duration::from_millis(120),
+                        recv_latency: Duration::from_millis(120),
+                    })),
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            ),
+        ),
+    );
+}
+
+#[test]
+fn conclusion_request_kmreq() {
+    // captured, a caller's conclusion with HSREQ and KMREQ extensions
+    conforms(
+        "80000000 00000000 00175E8A 00000000
+         00000005 00000003 6FEFB8D8 000005DC 00002000 FFFFFFFF 35E790ED 5D16CCEA
+         0100007F 00000000 00000000 00000000
+         00010003 00010301 0000002F 01F401F4
+         0003000E 12202901 00000000 02000200 00000404
+         9D75B0AC 924C6E4C 9EC40FEB 4FE973DB
+         1D215D42 6C18A287 1EBF77E2 646D9BAB 15DBD768 9AEF60EC",
+        control(
+            1_531_530,
+            SocketID(0),
+            ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber::new_truncate(1_877_981_400),
+                max_packet_size: 1500,
+                max_flow_size: 8192,
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketID(904_368_365),
+                syn_cookie: 1_561_775_338,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
+                        version: SrtVersion::new(1, 3, 1),
+                        flags: SrtShakeFlags::TSBPDSND
+                            | SrtShakeFlags::TSBPDRCV
+                            | SrtShakeFlags::HAICRYPT
+                            | SrtShakeFlags::TLPKTDROP
+                            | SrtShakeFlags::REXMITFLG,
+                        send_latency: Duration::from_millis(500),
+                        recv_latency: Duration::from_millis(500),
+                    })),
+                    ext_km: Some(SrtControlPacket::KeyManagerRequest(km(KeyFlags::EVEN))),
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            }),
+        ),
+    );
+}
+
+#[test]
+fn conclusion_response_kmrsp() {
+    // the listener echoes the key material it could unwrap, with an AES-128 key size
+    conforms(
+        "80000000 00000000 00001B58 1A2B3C4D
+         00000005 00020003 2D5B9F11 000005DC 00002000 FFFFFFFF 5E6F7081 7A3D2C1B
+         0100007F 00000000 00000000 00000000
+         00020003 00010401 0000003F 00780078
+         0004000E 12202901 00000000 02000200 00000404
+         9D75B0AC 924C6E4C 9EC40FEB 4FE973DB
+         1D215D42 6C18A287 1EBF77E2 646D9BAB 15DBD768 9AEF60EC",
+        control(
+            7_000,
+            CALLER_SOCKID,
+            handshake(
+                ShakeType::Conclusion,
+                LISTENER_SOCKID,
+                COOKIE,
+                HandshakeVSInfo::V5 {
+                    crypto_size: 16,
+                    ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
+                        version: SrtVersion::new(1, 4, 1),
+                        flags: SrtShakeFlags::TSBPDSND
+                            | SrtShakeFlags::TSBPDRCV
+                            | SrtShakeFlags::HAICRYPT
+                            | SrtShakeFlags::TLPKTDROP
+                            | SrtShakeFlags::NAKREPORT
+                            | SrtShakeFlags::REXMITFLG,
+                        send_latency: Duration::from_millis(120),
+                        recv_latency: Duration::from_millis(120),
+                    })),
+                    ext_km: Some(SrtControlPacket::KeyManagerResponse(km(KeyFlags::EVEN))),
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            ),
+        ),
+    );
+}
+
+#[test]
+fn rejection() {
+    // the handshake type is 1000 + the reason, here a wrong passphrase
+    conforms(
+        "80000000 00000000 00001770 1A2B3C4D
+         00000005 00000000 2D5B9F11 000005DC 00002000 000003F2 5E6F7081 7A3D2C1B
+         0100007F 00000000 00000000 00000000",
+        control(
+            6_000,
+            CALLER_SOCKID,
+            handshake(
+                ShakeType::Rejection(RejectReason::BadSecret),
+                LISTENER_SOCKID,
+                COOKIE,
+                HandshakeVSInfo::V5 {
+                    crypto_size: 0,
+                    ext_hs: None,
+                    ext_km: None,
+                    ext_config: None,
+                    ext_other: vec![],
+                },
+            ),
+        ),
+    );
+}
+
+#[test]
+fn km_refresh() {
+    // key material sent outside of the handshake, when the keys are refreshed, here announcing the odd key
+    conforms(
+        "FFFF0003 00000000 0001E240 5E6F7081
+         12202902 00000000 02000200 00000404
+         9D75B0AC 924C6E4C 9EC40FEB 4FE973DB
+         1D215D42 6C18A287 1EBF77E2 646D9BAB 15DBD768 9AEF60EC",
+        control(
+            123_456,
+            LISTENER_SOCKID,
+            ControlTypes::Srt(SrtControlPacket::KeyManagerRequest(km(KeyFlags::ODD))),
+        ),
+    );
+}
+
+#[test]
+fn srt_reject() {
+    // captured
+    decodes_to(
+        "FFFF0000 00000000 00018970 2BFFEFF2 00010301 0000001E 00000078",
+        &control(
+            100_720,
+            SocketID(738_193_394),
+            ControlTypes::Srt(SrtControlPacket::Reject),
+        ),
+    );
+}
+
+#[test]
+fn data_header() {
+    // the second word is PP|O|KK|R then the 26 bit message number
+    conforms(
+        "12345678 C0000001 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::ONLY, DataEncryption::None, false, 1),
+    );
+    conforms(
+        "12345678 80000002 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::FIRST, DataEncryption::None, false, 2),
+    );
+    conforms(
+        "12345678 00000002 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::MIDDLE, DataEncryption::None, false, 2),
+    );
+    conforms(
+        "12345678 44000002 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::LAST, DataEncryption::None, true, 2),
+    );
+    conforms(
+        "12345678 C8000003 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::ONLY, DataEncryption::Even, false, 3),
+    );
+    conforms(
+        "12345678 D0000003 0001E240 1A2B3C4D 48656C6C6F",
+        data(PacketLocation::ONLY, DataEncryption::Odd, false, 3),
+    );
+    conforms(
+        "12345678 C3FFFFFF 0001E240 1A2B3C4D 48656C6C6F",
+        data(
+            PacketLocation::ONLY,
+            DataEncryption::None,
+            false,
+            (1 << 26) - 1,
+        ),
+    );
+
+    let mut in_order = data(PacketLocation::ONLY, DataEncryption::None, false, 1);
+    if let Packet::Data(data) = &mut in_order {
+        data.in_order_delivery = true;
+    }
+    conforms("12345678 E0000001 0001E240 1A2B3C4D 48656C6C6F", in_order);
+}
+
+#[test]
+fn data_header_both_keys() {
+    // both key bits set isn't a valid encryption
+    let bytes = vector("12345678 D8000003 0001E240 1A2B3C4D 48656C6C6F");
+    assert!(matches!(
+        Packet::parse(&mut Cursor::new(&bytes[..])),
+        Err(PacketParseError::BadDataEncryption(0b0001_1000))
+    ));
+}
+
+#[test]
+fn ack() {
+    conforms(
+        "80020000 00000007 000186A0 1A2B3C4D
+         2D5BA011 00002710 00001388 00001FEF 000003E8 00002710 0016E360",
+        control(
+            100_000,
+            CALLER_SOCKID,
+            ControlTypes::Ack(AckControlInfo {
+                ack_seq_num: 7,
+                ack_number: SeqNumber::new_truncate(0x2D5B_A011),
+                rtt: Some(TimeSpan::from_micros(10_000)),
+                rtt_variance: Some(TimeSpan::from_micros(5_000)),
+                buffer_available: Some(8175),
+                packet_recv_rate: Some(1000),
+                est_link_cap: Some(10_000),
+                byte_recv_rate: Some(1_500_000),
+            }),
+        ),
+    );
+}
+
+#[test]
+fn ack_without_receive_rate() {
+    // older versions of the reference implementation don't send the receive rate in bytes
+    decodes_to(
+        "80020000 00000007 000186A0 1A2B3C4D
+         2D5BA011 00002710 00001388 00001FEF 000003E8 00002710",
+        &control(
+            100_000,
+            CALLER_SOCKID,
+            ControlTypes::Ack(AckControlInfo {
+                ack_seq_num: 7,
+                ack_number: SeqNumber::new_truncate(0x2D5B_A011),
+                rtt: Some(TimeSpan::from_micros(10_000)),
+                rtt_variance: Some(TimeSpan::from_micros(5_000)),
+                buffer_available: Some(8175),
+                packet_recv_rate: Some(1000),
+                est_link_cap: Some(10_000),
+                byte_recv_rate: None,
+            }),
+        ),
+    );
+}
+
+#[test]
+fn light_ack() {
+    // only the acknowledged sequence number
+    decodes_to(
+        "80020000 00000000 000186A0 1A2B3C4D 2D5BA011",
+        &control(
+            100_000,
+            CALLER_SOCKID,
+            ControlTypes::Ack(AckControlInfo {
+                ack_seq_num: 0,
+                ack_number: SeqNumber::new_truncate(0x2D5B_A011),
+                rtt: None,
+                rtt_variance: None,
+                buffer_available: None,
+                packet_recv_rate: None,
+                est_link_cap: None,
+                byte_recv_rate: None,
+            }),
+        ),
+    );
+}
+
+#[test]
+fn ack2() {
+    // the reference implementation pads ACK2, keepalive and shutdown with a zero word
+    conforms(
+        "80060000 00000007 000186A0 5E6F7081 00000000",
+        control(100_000, LISTENER_SOCKID, ControlTypes::Ack2(7)),
+    );
+}
+
+#[test]
+fn keepalive() {
+    conforms(
+        "80010000 00000000 000F4240 5E6F7081 00000000",
+        control(1_000_000, LISTENER_SOCKID, ControlTypes::KeepAlive),
+    );
+}
+
+#[test]
+fn shutdown() {
+    conforms(
+        "80050000 00000000 000F4240 5E6F7081 00000000",
+        control(1_000_000, LISTENER_SOCKID, ControlTypes::Shutdown),
+    );
+}
+
+#[test]
+fn nak() {
+    // one lost packet, then a range with the high bit set on its first number
+    conforms(
+        "80030000 00000000 000186A0 5E6F7081 2D5BA015 AD5BA018 2D5BA01C",
+        control(
+            100_000,
+            LISTENER_SOCKID,
+            ControlTypes::Nak(vec![0x2D5B_A015, 0xAD5B_A018, 0x2D5B_A01C]),
+        ),
+    );
+}
+
+#[test]
+fn drop_request() {
+    // the message number is in the additional info
+    conforms(
+        "80070000 00000005 000186A0 1A2B3C4D 2D5BA020 2D5BA022",
+        control(
+            100_000,
+            CALLER_SOCKID,
+            ControlTypes::DropRequest {
+                msg_to_drop: MsgNumber::new_truncate(5),
+                first: SeqNumber::new_truncate(0x2D5B_A020),
+                last: SeqNumber::new_truncate(0x2D5B_A022),
+            },
+        ),
+    );
+}
+
+#[test]
+fn user_defined() {
+    // a user defined type with a subtype that isn't SRT's is kept as it is
+    conforms(
+        "FFFF0100 00000009 000186A0 1A2B3C4D DEADBEEF",
+        control(
+            100_000,
+            CALLER_SOCKID,
+            ControlTypes::Custom {
+                custom_type: 0x7FFF,
+                reserved: 0x100,
+                additional_info: 9,
+                payload: Bytes::from_static(&[0xDE, 0xAD, 0xBE, 0xEF]),
+            },
+        ),
+    );
+}
@@ -5,17 +5,20 @@ use log::{info, trace};
 use rand::{prelude::StdRng, Rng, SeedableRng};
 use rand_distr::{Distribution, Normal};
 use srt_protocol::{
+    packet::SrtShakeFlags,
     protocol::{
         handshake::Handshake,
         receiver::{Receiver, ReceiverAlgorithmAction},
         sender::{Sender, SenderAlgorithmAction},
     },
-    ConnectionSettings, Packet,
+    Clock, ConnectionSettings, KmState, MockClock, Packet, RetransmitAlgorithm, SendDropPolicy,
+    SrtVersion, TransmissionType,
 };
 use std::{
     collections::BinaryHeap,
     convert::identity,
     str,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -48,19 +51,32 @@ fn lossy_deterministic() {
         (9602806002654919948, 10),
     ];
     for &(s, size) in &once_failing_seeds {
-        do_lossy_test(s, size);
+        do_lossy_test(s, size, RetransmitAlgorithm::Aggressive);
     }
 
     for _ in 0..10 {
         let seed = rand::random();
         println!("{}", seed);
-        do_lossy_test(seed, 10_000);
+        do_lossy_test(seed, 10_000, RetransmitAlgorithm::Aggressive);
     }
 }
 
-fn do_lossy_test(seed: u64, count: usize) {
+#[test]
+fn lossy_deterministic_reduced_retransmissions() {
+    let _ = env_logger::try_init();
+
+    for _ in 0..5 {
+        let seed = rand::random();
+        println!("{}", seed);
+        do_lossy_test(seed, 10_000, RetransmitAlgorithm::Reduced);
+    }
+}
+
+fn do_lossy_test(seed: u64, count: usize, retransmit_algorithm: RetransmitAlgorithm) {
     info!("Seed is: {}", seed);
-    let start = Instant::now();
+    // both sides read the simulated time
+    let clock = MockClock::new(Instant::now());
+    let start = clock.now();
     let mut rng = StdRng::seed_from_u64(seed);
 
     let s1 = ConnectionSettings {
@@ -68,6 +84,7 @@ fn do_lossy_test(seed: u64, count: usize) {
         remote_sockid: rng.gen(),
         local_sockid: rng.gen(),
         socket_start_time: start,
+        clock: Arc::new(clock.clone()),
         init_send_seq_num: rng.gen(),
         init_recv_seq_num: rng.gen(),
         max_packet_size: 1316,
@@ -75,6 +92,35 @@ fn do_lossy_test(seed: u64, count: usize) {
         send_tsbpd_latency: Duration::from_secs(8),
         recv_tsbpd_latency: Duration::from_secs(8),
         crypto_manager: None,
+        send_km_state: KmState::Unsecured,
+        recv_km_state: KmState::Unsecured,
+        transmission_type: TransmissionType::Live,
+        retransmit_algorithm,
+        packet_budget: 64,
+        max_message_size: 8 * 1024 * 1024,
+        peer_version: SrtVersion::CURRENT,
+        peer_flags: SrtShakeFlags::SUPPORTED,
+        control_packet_handler: None,
+        packet_tap: None,
+        send_buffer_monitor: None,
+        data_idle_monitor: None,
+        stall_monitor: None,
+        mtu_blackhole_monitor: None,
+        break_criteria: Default::default(),
+        rate_limit: None,
+        rate_limit_control: Default::default(),
+        retransmit_budget: None,
+        ack_interval: None,
+        nak_interval: None,
+        send_drop_policy: SendDropPolicy::default(),
+        source_validation: Default::default(),
+        transfer_quota: Default::default(),
+        linger: None,
+        recv_timeout: None,
+        send_timeout: None,
+        app_protocol: None,
+        peer_extensions: vec![],
+        connect_stats: Default::default(),
     };
 
     let s2 = ConnectionSettings {
@@ -82,6 +128,7 @@ fn do_lossy_test(seed: u64, count: usize) {
         remote_sockid: s1.local_sockid,
         local_sockid: s1.remote_sockid,
         socket_start_time: start,
+        clock: Arc::new(clock.clone()),
         init_send_seq_num: s1.init_recv_seq_num,
         init_recv_seq_num: s1.init_send_seq_num,
         max_packet_size: 1316,
@@ -89,6 +136,35 @@ fn do_lossy_test(seed: u64, count: usize) {
         send_tsbpd_latency: Duration::from_secs(8),
         recv_tsbpd_latency: Duration::from_secs(8),
         crypto_manager: None,
+        send_km_state: KmState::Unsecured,
+        recv_km_state: KmState::Unsecured,
+        transmission_type: TransmissionType::Live,
+        retransmit_algorithm,
+        packet_budget: 64,
+        max_message_size: 8 * 1024 * 1024,
+        peer_version: SrtVersion::CURRENT,
+        peer_flags: SrtShakeFlags::SUPPORTED,
+        control_packet_handler: None,
+        packet_tap: None,
+        send_buffer_monitor: None,
+        data_idle_monitor: None,
+        stall_monitor: None,
+        mtu_blackhole_monitor: None,
+        break_criteria: Default::default(),
+        rate_limit: None,
+        rate_limit_control: Default::default(),
+        retransmit_budget: None,
+        ack_interval: None,
+        nak_interval: None,
+        send_drop_policy: SendDropPolicy::default(),
+        source_validation: Default::default(),
+        transfer_quota: Default::default(),
+        linger: None,
+        recv_timeout: None,
+        send_timeout: None,
+        app_protocol: None,
+        peer_extensions: vec![],
+        connect_stats: Default::default(),
     };
 
     let mut sendr = Sender::new(s1, Handshake::Connector);
@@ -235,6 +311,7 @@ fn do_lossy_test(seed: u64, count: usize) {
 
         let delta = new_current - current_time;
         current_time = new_current;
+        clock.set(current_time);
 
         trace!("Delta = {:?}", delta);
     }
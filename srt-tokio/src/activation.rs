@@ -0,0 +1,116 @@
+//! Sockets inherited from the process that started this one, for socket activation and restarts without downtime
+//!
+//! With systemd socket activation, the service manager binds the sockets of a `.socket` unit and passes them to the
+//! service on start, as file descriptors from 3 on, telling how many in `LISTEN_FDS` and for which process in
+//! `LISTEN_PID`. The socket then outlives the service, so a restart doesn't drop the packets arriving meanwhile, and
+//! the service needs no privileges to bind it. A daemon restarting itself can do the same by passing its sockets to
+//! the new process that way.
+//!
+//! [`listen_fds`] takes the inherited sockets, to use with
+//! [`build_multiplexed_with_udp`](crate::SrtSocketBuilder::build_multiplexed_with_udp) or
+//! [`connect_with_udp`](crate::SrtSocketBuilder::connect_with_udp), or
+//! [`build_multiplexed_activated`](crate::SrtSocketBuilder::build_multiplexed_activated) listens on all of them.
+//! Sockets from another way of passing file descriptors, such as over a unix socket, are used the same way once
+//! converted with [`FromRawFd`].
+
+use std::env;
+use std::io;
+use std::mem;
+use std::net::UdpSocket;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::process;
+
+/// The first inherited file descriptor, after stdin, stdout and stderr
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Take the sockets this process inherited, as `LISTEN_FDS` and `LISTEN_PID` tell, empty if it didn't inherit any
+///
+/// The variables are removed, so child processes don't take the sockets too, and calling this again returns none.
+/// Every inherited file descriptor must be a UDP socket, as configured in the `.socket` unit with `ListenDatagram=`.
+/// They're all checked before any is taken, so on an error none is closed.
+pub fn listen_fds() -> Result<Vec<UdpSocket>, io::Error> {
+    let pid = env::var("LISTEN_PID");
+    let fds = env::var("LISTEN_FDS");
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    // meant for another process, such as the parent
+    match pid.map(|pid| pid.parse::<u32>()) {
+        Ok(Ok(pid)) if pid == process::id() => {}
+        _ => return Ok(Vec::new()),
+    }
+    let count = match fds.map(|fds| fds.parse::<RawFd>()) {
+        Ok(Ok(count)) if count >= 0 => count,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "LISTEN_FDS is not a number of file descriptors",
+            ))
+        }
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let end = LISTEN_FDS_START.checked_add(count).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "LISTEN_FDS is more file descriptors than there can be",
+        )
+    })?;
+
+    for fd in LISTEN_FDS_START..end {
+        if !is_udp_socket(fd)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Inherited file descriptor {} is not a UDP socket", fd),
+            ));
+        }
+    }
+
+    let mut sockets = Vec::new();
+    for fd in LISTEN_FDS_START..end {
+        // SAFETY: the service manager passed these to this process, and the variables are removed above, so they're
+        // only taken once
+        #[allow(unsafe_code)]
+        sockets.push(unsafe { UdpSocket::from_raw_fd(fd) });
+    }
+    Ok(sockets)
+}
+
+// a datagram socket of an internet address family, without taking ownership of it
+#[allow(unsafe_code)]
+fn is_udp_socket(fd: RawFd) -> Result<bool, io::Error> {
+    let mut socket_type: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: the option is written to `socket_type`, which is `len` bytes
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut socket_type as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: all zeros is an empty address
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    // SAFETY: the address is written to `addr`, which is `len` bytes
+    let res = unsafe {
+        libc::getsockname(
+            fd,
+            &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr,
+            &mut len,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let family = libc::c_int::from(addr.ss_family);
+    Ok(socket_type == libc::SOCK_DGRAM && (family == libc::AF_INET || family == libc::AF_INET6))
+}
@@ -0,0 +1,221 @@
+//! Push and pull adapters shaped like GStreamer's `appsrc` and `appsink`, for writing gst plugins on top of this
+//! crate or porting pipelines from `gst-srt`
+//!
+//! An [`AppSrc`] sends the buffers the application pushes into it, and calls its `need_data` callback whenever it ran
+//! dry. An [`AppSink`] hands every message received to its `on_data` callback, or to whoever pulls them. Buffers are
+//! `(Instant, Bytes)`, the origin time standing in for the PTS.
+//!
+//! ```no_run
+//! use srt_tokio::app::{AppSink, AppSrc};
+//! use srt_tokio::SrtSocketBuilder;
+//! use bytes::Bytes;
+//! use std::{io, time::Instant};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), io::Error> {
+//!     let source = SrtSocketBuilder::new_listen().local_port(3333).connect().await?;
+//!     let sink = SrtSocketBuilder::new_connect("127.0.0.1:4444").connect().await?;
+//!
+//!     let mut frames = 0;
+//!     let (appsrc, handle) = AppSrc::new(sink);
+//!     let appsrc = appsrc.need_data(move |handle| {
+//!         frames += 1;
+//!         handle.push_buffer((Instant::now(), Bytes::from(format!("frame {}", frames))));
+//!         if frames == 100 {
+//!             handle.end_of_stream();
+//!         }
+//!     });
+//!     let appsink = AppSink::new(source).on_data(|(_, data)| println!("{} bytes", data.len()));
+//!
+//!     futures::try_join!(appsrc.run(), appsink.run())?;
+//!     drop(handle);
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::{future, prelude::*};
+use log::debug;
+
+#[derive(Default)]
+struct SrcQueue {
+    buffers: VecDeque<(Instant, Bytes)>,
+    eos: bool,
+    waker: Option<Waker>,
+}
+
+/// Sends the buffers pushed through its [`AppSrcHandle`] on a connection, like GStreamer's `appsrc`
+///
+/// The buffers are sent in the order they were pushed. Once [`end_of_stream`](AppSrcHandle::end_of_stream) was
+/// called and everything pushed before was sent, the sink is closed.
+pub struct AppSrc<S> {
+    sink: S,
+    handle: AppSrcHandle,
+    need_data: Option<Box<dyn FnMut(&AppSrcHandle) + Send>>,
+}
+
+/// A cheap to clone handle to push buffers into an [`AppSrc`], from any task or thread
+#[derive(Clone)]
+pub struct AppSrcHandle {
+    queue: Arc<Mutex<SrcQueue>>,
+}
+
+impl<S> AppSrc<S>
+where
+    S: Sink<(Instant, Bytes), Error = io::Error> + Unpin,
+{
+    /// Create a source sending on `sink`, such as a [`SrtSocket`](crate::SrtSocket) or its send half
+    pub fn new(sink: S) -> (Self, AppSrcHandle) {
+        let handle = AppSrcHandle {
+            queue: Default::default(),
+        };
+        (
+            AppSrc {
+                sink,
+                handle: handle.clone(),
+                need_data: None,
+            },
+            handle,
+        )
+    }
+
+    /// Call `need_data` every time the queue runs dry, for the application to push more or end the stream
+    ///
+    /// Like the `need-data` signal, it's called once each time, and not again until a buffer was pushed.
+    pub fn need_data(mut self, need_data: impl FnMut(&AppSrcHandle) + Send + 'static) -> Self {
+        self.need_data = Some(Box::new(need_data));
+        self
+    }
+
+    /// Send the buffers pushed until the end of the stream, then close the sink. Returns how many were sent.
+    pub async fn run(mut self) -> Result<u64, io::Error> {
+        let mut sent = 0;
+        let mut asked = false;
+        loop {
+            let buffer = future::poll_fn(|cx| self.poll_buffer(cx, &mut asked)).await;
+            match buffer {
+                Some(buffer) => {
+                    // no flush, that would wait for every buffer to be acknowledged
+                    self.sink.feed(buffer).await?;
+                    sent += 1;
+                }
+                None => break,
+            }
+        }
+        debug!(
+            "appsrc reached the end of the stream after {} buffers",
+            sent
+        );
+        self.sink.close().await?;
+        Ok(sent)
+    }
+
+    fn poll_buffer(
+        &mut self,
+        cx: &mut Context,
+        asked: &mut bool,
+    ) -> Poll<Option<(Instant, Bytes)>> {
+        loop {
+            {
+                let mut queue = self.handle.queue.lock().unwrap();
+                if let Some(buffer) = queue.buffers.pop_front() {
+                    *asked = false;
+                    return Poll::Ready(Some(buffer));
+                }
+                if queue.eos {
+                    return Poll::Ready(None);
+                }
+                if *asked || self.need_data.is_none() {
+                    queue.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+            // outside the lock, the callback pushes
+            *asked = true;
+            if let Some(need_data) = &mut self.need_data {
+                need_data(&self.handle);
+            }
+        }
+    }
+}
+
+impl AppSrcHandle {
+    /// Queue a buffer to be sent. Buffers pushed after the end of the stream are dropped.
+    pub fn push_buffer(&self, buffer: (Instant, Bytes)) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.eos {
+            debug!("Dropping buffer pushed after the end of the stream");
+            return;
+        }
+        queue.buffers.push_back(buffer);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// End the stream, the sink is closed once what's queued was sent
+    pub fn end_of_stream(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.eos = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// How many buffers are waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().buffers.len()
+    }
+}
+
+/// Hands the messages received on a connection to a callback, or to whoever pulls them, like GStreamer's `appsink`
+pub struct AppSink<S> {
+    source: S,
+    on_data: Option<Box<dyn FnMut((Instant, Bytes)) + Send>>,
+}
+
+impl<S> AppSink<S>
+where
+    S: Stream<Item = Result<(Instant, Bytes), io::Error>> + Unpin,
+{
+    /// Create a sink receiving from `source`, such as a [`SrtSocket`](crate::SrtSocket) or its receive half
+    pub fn new(source: S) -> Self {
+        AppSink {
+            source,
+            on_data: None,
+        }
+    }
+
+    /// Call `on_data` with every message received, when running the sink with [`run`](AppSink::run)
+    pub fn on_data(mut self, on_data: impl FnMut((Instant, Bytes)) + Send + 'static) -> Self {
+        self.on_data = Some(Box::new(on_data));
+        self
+    }
+
+    /// Wait for the next message, `None` at the end of the stream, like `pull-sample`
+    pub async fn pull_sample(&mut self) -> Result<Option<(Instant, Bytes)>, io::Error> {
+        self.source.try_next().await
+    }
+
+    /// Give every message to the `on_data` callback until the end of the stream. Returns how many were received.
+    pub async fn run(mut self) -> Result<u64, io::Error> {
+        let mut received = 0;
+        while let Some(sample) = self.source.try_next().await? {
+            received += 1;
+            if let Some(on_data) = &mut self.on_data {
+                on_data(sample);
+            }
+        }
+        debug!(
+            "appsink reached the end of the stream after {} buffers",
+            received
+        );
+        Ok(received)
+    }
+}
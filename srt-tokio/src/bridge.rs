@@ -0,0 +1,296 @@
+//! Carry the messages of a connection over a byte stream such as TCP, and back, keeping where they start and end
+//!
+//! A byte stream doesn't keep the boundaries of what was written to it: copying messages into it and sending on
+//! whatever the reads from it return splits and merges them. Two codecs find the boundaries again:
+//! * [`LengthPrefixCodec`] sends each message after its length, for streams with a bridge at both ends
+//! * [`TsRealigner`] cuts MPEG-TS at its packets, for streams to and from anything that speaks TS over TCP
+//!
+//! [`bridge`] copies between a connection and a stream in both directions with one of them.
+//!
+//! ```no_run
+//! use srt_tokio::bridge::{bridge, TsRealigner};
+//! use srt_tokio::SrtSocketBuilder;
+//! use tokio::net::TcpStream;
+//! use std::io;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), io::Error> {
+//!     let sock = SrtSocketBuilder::new_connect("127.0.0.1:3333").connect().await?;
+//!     let tcp = TcpStream::connect("127.0.0.1:4444").await?;
+//!     bridge(sock, tcp, TsRealigner::new()).await
+//! }
+//! ```
+
+use std::io;
+use std::time::Instant;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{future, prelude::*};
+use log::{info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::codec::{Decoder, Encoder, Framed, LengthDelimitedCodec};
+
+use crate::SrtSocket;
+
+/// The size of an MPEG-TS packet
+pub const TS_PACKET: usize = 188;
+
+/// The byte every MPEG-TS packet starts with
+pub const TS_SYNC: u8 = 0x47;
+
+/// Each message after its length, as a big endian 32-bit word
+///
+/// Decoding fails with [`InvalidData`](io::ErrorKind::InvalidData) on a length over the maximum, as the stream can't
+/// be trusted to be framed after that.
+#[derive(Debug)]
+pub struct LengthPrefixCodec(LengthDelimitedCodec);
+
+impl LengthPrefixCodec {
+    /// Frame messages of at most `max_len` bytes, such as the
+    /// [`max_message_size`](crate::SrtSocketBuilder::max_message_size) of the connection
+    pub fn new(max_len: usize) -> Self {
+        LengthPrefixCodec(
+            LengthDelimitedCodec::builder()
+                .length_field_length(4)
+                .max_frame_length(max_len)
+                .new_codec(),
+        )
+    }
+}
+
+impl Decoder for LengthPrefixCodec {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, io::Error> {
+        Ok(self.0.decode(src)?.map(BytesMut::freeze))
+    }
+}
+
+impl Encoder<Bytes> for LengthPrefixCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), io::Error> {
+        self.0.encode(item, dst)
+    }
+}
+
+/// Cuts MPEG-TS into messages of whole packets, finding the packets again by their sync bytes when the stream loses
+/// them
+///
+/// Decoding locks on once three packets in a row start with the sync byte, skipping the bytes before them, and
+/// releases messages of [`packets_per_message`](Self::packets_per_message) packets, 7 by default to fill the
+/// 1316 bytes of a live payload. A packet not followed by a sync byte, as bytes were lost in it, loses the lock,
+/// releasing the packets before it.
+///
+/// Encoding passes messages through, dropping any that aren't whole packets, as writing them would misalign the
+/// packets after them for whoever reads the stream.
+#[derive(Debug)]
+pub struct TsRealigner {
+    packets_per_message: usize,
+    locked: bool,
+}
+
+impl TsRealigner {
+    pub fn new() -> Self {
+        TsRealigner {
+            packets_per_message: 7,
+            locked: false,
+        }
+    }
+
+    /// Release messages of `packets` TS packets
+    ///
+    /// # Panics:
+    /// * `packets` is zero
+    pub fn packets_per_message(mut self, packets: usize) -> Self {
+        assert!(packets > 0, "Messages must hold at least one TS packet");
+        self.packets_per_message = packets;
+
+        self
+    }
+
+    // finds three packets in a row, dropping what's before them
+    fn lock(&mut self, src: &mut BytesMut) -> bool {
+        let span = 2 * TS_PACKET + 1;
+        if src.len() < span {
+            return false;
+        }
+        let found = (0..=src.len() - span).find(|&i| {
+            src[i] == TS_SYNC && src[i + TS_PACKET] == TS_SYNC && src[i + 2 * TS_PACKET] == TS_SYNC
+        });
+        let skip = found.unwrap_or(src.len() - span + 1);
+        if skip > 0 {
+            warn!("Skipping {} bytes to align with the TS packets", skip);
+            src.advance(skip);
+        }
+        self.locked = found.is_some();
+        self.locked
+    }
+}
+
+impl Default for TsRealigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for TsRealigner {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, io::Error> {
+        if !self.locked && !self.lock(src) {
+            return Ok(None);
+        }
+        // a packet is only known to be whole once the next one is seen starting with the sync byte
+        let seen = (src.len().saturating_sub(1) / TS_PACKET).min(self.packets_per_message);
+        let whole = (0..seen)
+            .find(|i| src[(i + 1) * TS_PACKET] != TS_SYNC)
+            .unwrap_or(seen);
+        if whole < seen {
+            info!("Lost the alignment of the TS packets");
+            self.locked = false;
+            if whole == 0 {
+                return self.decode(src);
+            }
+        } else if whole < self.packets_per_message {
+            return Ok(None);
+        }
+        Ok(Some(src.split_to(whole * TS_PACKET).freeze()))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Bytes>, io::Error> {
+        if let Some(message) = self.decode(src)? {
+            return Ok(Some(message));
+        }
+        // the last packets, fewer than a message's worth, with nothing after them to tell if the last is whole
+        let whole = src.len() / TS_PACKET;
+        if self.locked && whole > 0 {
+            return Ok(Some(src.split_to(whole * TS_PACKET).freeze()));
+        }
+        src.clear();
+        Ok(None)
+    }
+}
+
+impl Encoder<Bytes> for TsRealigner {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), io::Error> {
+        if item.len() % TS_PACKET != 0 || item.iter().step_by(TS_PACKET).any(|b| *b != TS_SYNC) {
+            warn!(
+                "Dropping a {} byte message that isn't whole TS packets",
+                item.len()
+            );
+            return Ok(());
+        }
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+/// Copy the messages received on `socket` into `io` and the messages `codec` finds in `io` to `socket`, until either
+/// ends, which closes the other
+///
+/// Messages from `io` are sent as soon as they're found, so the timing of the stream is kept as well as it's read.
+pub async fn bridge<T, C>(socket: SrtSocket, io: T, codec: C) -> Result<(), io::Error>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    C: Decoder<Item = Bytes, Error = io::Error> + Encoder<Bytes, Error = io::Error> + Unpin,
+{
+    let (mut srt_sink, srt_stream) = socket.split();
+    let (mut io_sink, io_stream) = Framed::new(io, codec).split();
+
+    let to_io = async {
+        io_sink
+            .send_all(&mut srt_stream.map_ok(|(_, message)| message))
+            .await?;
+        io_sink.close().await
+    };
+    let to_srt = async {
+        srt_sink
+            .send_all(&mut io_stream.map_ok(|message| (Instant::now(), message)))
+            .await?;
+        srt_sink.close().await
+    };
+    futures::pin_mut!(to_io, to_srt);
+    match future::select(to_io, to_srt).await {
+        future::Either::Left((res, _)) | future::Either::Right((res, _)) => res,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn packets(count: usize, first: u8) -> Vec<u8> {
+        (0..count)
+            .flat_map(|i| {
+                let mut packet = vec![first.wrapping_add(i as u8); TS_PACKET];
+                packet[0] = TS_SYNC;
+                packet
+            })
+            .collect()
+    }
+
+    #[test]
+    fn length_prefix() {
+        let mut codec = LengthPrefixCodec::new(1316);
+        let mut buf = BytesMut::new();
+        codec.encode(Bytes::from("hello"), &mut buf).unwrap();
+        codec.encode(Bytes::from(""), &mut buf).unwrap();
+        assert_eq!(&buf[..4], [0, 0, 0, 5]);
+
+        // split anywhere
+        let mut src = buf.split_to(3);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.unsplit(buf);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::from("hello")));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Bytes::new()));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        let mut too_long = BytesMut::from(&[0, 0, 0x10, 0][..]);
+        assert_eq!(
+            codec.decode(&mut too_long).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn realign() {
+        let mut codec = TsRealigner::new().packets_per_message(2);
+        // joined midway through a packet, with the rest of the first one looking like a sync byte
+        let mut src = BytesMut::from(&[0x12, TS_SYNC, 0x34][..]);
+        src.extend_from_slice(&packets(5, 1));
+
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), packets(2, 1));
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), packets(2, 3));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        // some bytes lost in the middle of the next packet
+        src.truncate(100);
+        src.extend_from_slice(&packets(4, 6));
+        assert_eq!(codec.decode(&mut src).unwrap().unwrap(), packets(2, 6));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        // what's left at the end
+        src.extend_from_slice(&[0x47; 10]);
+        assert_eq!(codec.decode_eof(&mut src).unwrap().unwrap(), packets(2, 8));
+        assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+    }
+
+    #[test]
+    fn unaligned_messages_dropped() {
+        let mut codec = TsRealigner::new();
+        let mut dst = BytesMut::new();
+        codec.encode(packets(7, 0).into(), &mut dst).unwrap();
+        codec
+            .encode(Bytes::from(&packets(1, 0)[1..]), &mut dst)
+            .unwrap();
+        let mut bad_sync = packets(2, 0);
+        bad_sync[TS_PACKET] = 0;
+        codec.encode(bad_sync.into(), &mut dst).unwrap();
+        assert_eq!(dst, packets(7, 0));
+    }
+}
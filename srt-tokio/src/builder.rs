@@ -1,18 +1,40 @@
-use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
-use std::{io, time::Duration};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::{io, sync::Arc, thread, time::Duration};
 
+use bytes::Bytes;
 use tokio::net::UdpSocket;
+use tokio::time::delay_for;
 use tokio_util::udp::UdpFramed;
 
-use futures::{future::ready, Sink, Stream, StreamExt};
+use futures::{
+    select,
+    stream::{select_all, FuturesUnordered},
+    Future, FutureExt, Sink, Stream, StreamExt,
+};
 
-use crate::tokio::create_bidrectional_srt;
+#[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+use crate::timestamping::TimestampedSocket;
+use crate::tokio::create_bidrectional_srt_raw;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use crate::UringSocket;
 use crate::{
-    connection::Connection, crypto::CryptoOptions, multiplex, pending_connection, PackChan, Packet,
-    PacketCodec, PacketParseError, SrtSocket,
+    connection::Connection,
+    crypto::{CryptoOptions, Secret},
+    multiplex_with_sock, multiplex_with_stats, pending_connection, AsyncDatagramTransport,
+    ConnectionDriver, MultiplexStats, PackChan, Packet, PacketCodec, PacketParseError, Resolver,
+    ShardedMultiplexer, SpawnPolicy, SrtSocket, SystemResolver, TemplateCodec, TransportFramed,
+};
+use log::{info, warn};
+use srt_protocol::{
+    packet::{HandshakeExtension, RejectReason},
+    pending_connection::ConnInitSettings,
+    protocol::handshake::Handshake,
+    Authenticator, Clock, ConnectPhase, ConnectProgress, ConnectionSettings, ConnectionSnapshot,
+    ControlPacket, ControlPacketHandler, DataIdleMonitor, KeyLengthPolicy, LibsrtOptionError,
+    MtuBlackholeMonitor, PacketTap, RateLimit, RetransmitAlgorithm, RetransmitBudget,
+    SendBufferMonitor, SendDropPolicy, SourceValidation, SrtVersion, StallMonitor, TransferQuota,
+    TransmissionType,
 };
-use log::warn;
-use srt_protocol::pending_connection::ConnInitSettings;
 
 /// Struct to build sockets.
 ///
@@ -50,18 +72,51 @@ use srt_protocol::pending_connection::ConnInitSettings;
 /// # }
 /// ```
 ///
+/// With the `serde` feature, builders can be loaded from configuration files. Only `conn_type` is required, the
+/// resolver, the spawn policy, the callbacks and the handshake extensions aren't serialized, see [`ConnInitSettings`].
+///
 /// # Panics:
 /// * There is no tokio runtime
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[must_use]
 pub struct SrtSocketBuilder {
+    #[cfg_attr(feature = "serde", serde(default = "default_local_addr"))]
     local_addr: SocketAddr,
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra_local_addrs: Vec<SocketAddr>,
     conn_type: ConnInitMethod,
+    #[cfg_attr(feature = "serde", serde(default))]
+    extra_remote_addrs: Vec<SocketAddr>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    remote_host: Option<(String, u16)>,
+    #[cfg_attr(feature = "serde", serde(skip, default = "default_resolver"))]
+    resolver: Arc<dyn Resolver>,
+    #[cfg_attr(feature = "serde", serde(default))]
     init_settings: ConnInitSettings,
+    #[cfg_attr(feature = "serde", serde(default))]
+    kernel_timestamps: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    spawn_policy: SpawnPolicy,
+    #[cfg(feature = "io-uring")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    io_uring: bool,
+}
+
+fn default_local_addr() -> SocketAddr {
+    SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)
+}
+
+fn default_resolver() -> Arc<dyn Resolver> {
+    Arc::new(SystemResolver)
 }
 
+/// How long to wait for a connection attempt before starting the next one to another address, as RFC 8305 recommends
+const CONNECT_STAGGER: Duration = Duration::from_millis(250);
+
 /// Describes how this SRT entity will connect to the other.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConnInitMethod {
     /// Listens on the local socket, expecting there to be a [`Connect`](ConnInitMethod::Connect) instance that eventually connects to this socket.
     /// This almost certianly menas you should use it with [`SrtSocketBuilder::local_port`],
@@ -81,9 +136,17 @@ impl SrtSocketBuilder {
     /// Generally easier to use [`new_listen`](SrtSocketBuilder::new_listen), [`new_connect`](SrtSocketBuilder::new_connect) or [`new_rendezvous`](SrtSocketBuilder::new_rendezvous)
     pub fn new(conn_type: ConnInitMethod) -> Self {
         SrtSocketBuilder {
-            local_addr: "0.0.0.0:0".parse().unwrap(),
+            local_addr: default_local_addr(),
+            extra_local_addrs: Vec::new(),
             conn_type,
+            extra_remote_addrs: Vec::new(),
+            remote_host: None,
+            resolver: default_resolver(),
             init_settings: ConnInitSettings::default(),
+            kernel_timestamps: false,
+            spawn_policy: SpawnPolicy::default(),
+            #[cfg(feature = "io-uring")]
+            io_uring: false,
         }
     }
 
@@ -91,14 +154,34 @@ impl SrtSocketBuilder {
         Self::new(ConnInitMethod::Listen)
     }
 
-    /// Connects to the first address yielded by `to`
+    /// Connects to an address yielded by `to`
+    ///
+    /// If `to` resolves to several addresses, [`connect`](SrtSocketBuilder::connect) tries them all, starting a new
+    /// attempt every 250ms or as soon as one fails, alternating between IPv6 and IPv4. The first to be connected is
+    /// kept, and the others are dropped.
     ///
     /// # Panics
     /// * `to` fails to resolve to a [`SocketAddr`]
     pub fn new_connect(to: impl ToSocketAddrs) -> Self {
-        Self::new(ConnInitMethod::Connect(
-            to.to_socket_addrs().unwrap().next().unwrap(),
-        ))
+        let mut addrs = interleave_families(to.to_socket_addrs().unwrap().collect()).into_iter();
+        let mut builder = Self::new(ConnInitMethod::Connect(addrs.next().unwrap()));
+        builder.extra_remote_addrs = addrs.collect();
+        builder
+    }
+
+    /// Connects to `host`, resolving it again every time [`connect`](SrtSocketBuilder::connect) is called, so
+    /// reconnecting with a clone of the builder follows the host to new addresses. Like
+    /// [`new_connect`](SrtSocketBuilder::new_connect), every address it resolves to is tried.
+    ///
+    /// The [`conn_type`](SrtSocketBuilder::conn_type) has an unspecified address until connected. Resolves with the
+    /// system resolver, unless another is set with [`resolver`](SrtSocketBuilder::resolver).
+    pub fn new_connect_host(host: impl Into<String>, port: u16) -> Self {
+        let mut builder = Self::new(ConnInitMethod::Connect(SocketAddr::new(
+            Ipv4Addr::UNSPECIFIED.into(),
+            port,
+        )));
+        builder.remote_host = Some((host.into(), port));
+        builder
     }
 
     /// Connects to the first address yielded by `to`
@@ -137,6 +220,15 @@ impl SrtSocketBuilder {
         self
     }
 
+    /// Also listen on `addr`, for hosts with several interfaces to take connections on. Only used by
+    /// [`build_multiplexed`](SrtSocketBuilder::build_multiplexed), where every address gets its own socket, and the
+    /// connections to all of them come out of the same stream.
+    pub fn also_bind(mut self, addr: SocketAddr) -> Self {
+        self.extra_local_addrs.push(addr);
+
+        self
+    }
+
     /// Set the latency of the connection. The more latency, the more time SRT has to recover lost packets.
     /// This sets both the send and receive latency
     pub fn latency(mut self, latency: Duration) -> Self {
@@ -146,19 +238,93 @@ impl SrtSocketBuilder {
         self
     }
 
-    // the minimum latency to receive at
+    /// Select live or file mode. This sets TSBPD, too late packet drop, periodic NAK reports and the flow window all at once,
+    /// like `SRTO_TRANSTYPE` does in the reference implementation.
+    ///
+    /// This resets the latency to the default for the mode, so call [`latency`](SrtSocketBuilder::latency) after this if you need a different one.
+    ///
+    /// ```
+    /// # use srt_tokio::{SrtSocketBuilder, TransmissionType};
+    /// let builder = SrtSocketBuilder::new_listen().transmission_type(TransmissionType::File);
+    /// ```
+    pub fn transmission_type(mut self, transmission_type: TransmissionType) -> Self {
+        self.init_settings.transmission_type = transmission_type;
+        self.init_settings.send_latency = transmission_type.default_latency();
+        self.init_settings.recv_latency = transmission_type.default_latency();
+
+        self
+    }
+
+    /// Apply options named as in the reference implementation, such as those of an `srt://` URL, see
+    /// [`ConnInitSettings::apply_libsrt_options`] for the ones supported.
+    ///
+    /// ```
+    /// # use srt_tokio::SrtSocketBuilder;
+    /// let builder = SrtSocketBuilder::new_connect("127.0.0.1:3333")
+    ///     .libsrt_options(&[("latency", "200"), ("passphrase", "password123"), ("pbkeylen", "32")])
+    ///     .unwrap();
+    /// ```
+    pub fn libsrt_options(mut self, options: &[(&str, &str)]) -> Result<Self, LibsrtOptionError> {
+        self.init_settings.apply_libsrt_options(options)?;
+
+        Ok(self)
+    }
+
+    /// Apply options as FFmpeg's `srt` protocol names them, with latencies in microseconds, see
+    /// [`ConnInitSettings::apply_ffmpeg_options`] for the ones supported. A `mode` must be the one this builder
+    /// connects with, `caller` for [`new_connect`](SrtSocketBuilder::new_connect) and so on.
+    ///
+    /// ```
+    /// # use srt_tokio::SrtSocketBuilder;
+    /// // ffmpeg -i input.ts -f mpegts "srt://127.0.0.1:3333?latency=200000&pbkeylen=16&passphrase=password123"
+    /// let builder = SrtSocketBuilder::new_connect("127.0.0.1:3333")
+    ///     .ffmpeg_options(&[("latency", "200000"), ("pbkeylen", "16"), ("passphrase", "password123")])
+    ///     .unwrap();
+    /// ```
+    pub fn ffmpeg_options(mut self, options: &[(&str, &str)]) -> Result<Self, LibsrtOptionError> {
+        let mode = match self.conn_type {
+            ConnInitMethod::Listen => "listener",
+            ConnInitMethod::Connect(_) => "caller",
+            ConnInitMethod::Rendezvous(_) => "rendezvous",
+        };
+        if let Some(&(name, value)) = options
+            .iter()
+            .find(|&&(name, value)| name == "mode" && value != mode)
+        {
+            return Err(LibsrtOptionError::Invalid(name.into(), value.into()));
+        }
+        self.init_settings.apply_ffmpeg_options(options)?;
+
+        Ok(self)
+    }
+
+    /// Set how lost packets are retransmitted, see [`RetransmitAlgorithm`]. Defaults to
+    /// [`Aggressive`](RetransmitAlgorithm::Aggressive).
+    pub fn retransmit_algorithm(mut self, algorithm: RetransmitAlgorithm) -> Self {
+        self.init_settings.retransmit_algorithm = algorithm;
+
+        self
+    }
+
+    /// Set the minimum latency to receive at, the equivalent of `SRTO_RCVLATENCY`.
+    ///
+    /// The latency used for data coming from the peer is the max of this and the peer's send latency.
     pub fn receive_latency(mut self, latency: Duration) -> Self {
         self.init_settings.recv_latency = latency;
         self
     }
 
-    // the minimum latency to send at
+    /// Set the minimum latency the peer should receive at, the equivalent of `SRTO_PEERLATENCY`.
+    ///
+    /// The latency used for data going to the peer is the max of this and the peer's receive latency.
+    /// As connections are bidirectional, this can be different from the [`receive_latency`](SrtSocketBuilder::receive_latency).
     pub fn send_latency(mut self, latency: Duration) -> Self {
         self.init_settings.send_latency = latency;
         self
     }
 
-    /// Se the crypto paramters. However, this is currently unimplemented.
+    /// Encrypt with AES keys of `size` bytes, wrapped with a key derived from `passphrase`. Responding to a peer with
+    /// keys of another length takes the peer's, unless the [`key_length_policy`](Self::key_length_policy) refuses it.
     ///
     /// # Panics:
     /// * size is not 16, 24, or 32.
@@ -169,14 +335,420 @@ impl SrtSocketBuilder {
             // NOT
             size => panic!("Invaid crypto size {}", size),
         }
+        self.init_settings.crypto = Some(CryptoOptions::passphrase(size, passphrase));
+
+        self
+    }
+
+    /// Encrypt with AES keys wrapped with `kek` itself, rather than a key derived from a passphrase, for keys managed
+    /// elsewhere such as in a KMS or HSM. The keys are as long as `kek`. `sek` is the key data is encrypted with when
+    /// this side makes the keys, as the initiator, a random one if `None`. What's exchanged is the same as with a
+    /// passphrase, but the peer needs the same `kek`.
+    ///
+    /// # Panics:
+    /// * `kek` is not 16, 24, or 32 bytes, or `sek` isn't as long
+    pub fn pre_shared_key(mut self, kek: &[u8], sek: Option<&[u8]>) -> Self {
+        match kek.len() {
+            // OK
+            16 | 24 | 32 => {}
+            // NOT
+            size => panic!("Invalid key encrypting key size {}", size),
+        }
+        if let Some(sek) = sek {
+            assert_eq!(
+                sek.len(),
+                kek.len(),
+                "The stream encrypting key must be as long as the key encrypting key"
+            );
+        }
         self.init_settings.crypto = Some(CryptoOptions {
-            size,
-            passphrase: passphrase.into(),
+            size: kek.len() as u8,
+            secret: Secret::Kek(kek.to_vec()),
+            sek: sek.map(<[u8]>::to_vec),
         });
 
         self
     }
 
+    /// Set the number of packets to handle in a row before yielding to other tasks on the runtime, defaults to 64.
+    /// This keeps a connection receiving a flood of packets from starving the rest of the process.
+    ///
+    /// # Panics:
+    /// * `budget` is zero
+    pub fn packet_budget(mut self, budget: usize) -> Self {
+        assert!(budget > 0, "Packet budget must be non-zero");
+        self.init_settings.packet_budget = budget;
+
+        self
+    }
+
+    /// Set the largest message that will be reassembled when receiving, in bytes. Defaults to 8MiB.
+    /// Larger messages are dropped and counted in [`ReceiverMetrics::oversized_messages`](srt_protocol::protocol::receiver::ReceiverMetrics::oversized_messages),
+    /// but the connection is kept alive.
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.init_settings.max_message_size = size;
+
+        self
+    }
+
+    /// Refuse connections where only one side can decrypt, the equivalent of `SRTO_ENFORCEDENCRYPTION`. Defaults to `true`.
+    ///
+    /// When disabled, a connection with a wrong or missing password is made anyway, and the data that can't be
+    /// decrypted is passed on as is. The outcome is reported in [`ConnectionInfo`](crate::ConnectionInfo).
+    pub fn enforced_encryption(mut self, enforced: bool) -> Self {
+        self.init_settings.enforced_encryption = enforced;
+
+        self
+    }
+
+    /// Set what to do when the peer's keys are of another length than the one given to [`crypto`](Self::crypto),
+    /// see [`KeyLengthPolicy`]. Defaults to taking the peer's length, as the reference implementation does.
+    ///
+    /// A refused length fails the connection with [`ConnectionRefused`](io::ErrorKind::ConnectionRefused), or with
+    /// [`enforced_encryption`](Self::enforced_encryption) disabled, connects with
+    /// [`KmState::BadCryptoMode`](crate::KmState::BadCryptoMode).
+    pub fn key_length_policy(mut self, policy: KeyLengthPolicy) -> Self {
+        self.init_settings.key_length_policy = policy;
+
+        self
+    }
+
+    /// Refuse to connect to peers older than `version`, the equivalent of `SRTO_MINVERSION`. Defaults to accepting any version.
+    ///
+    /// Listeners reject older callers with [`RejectReason::Version`](srt_protocol::packet::RejectReason::Version),
+    /// which makes connecting fail with [`io::ErrorKind::ConnectionRefused`] on the caller's side.
+    pub fn min_peer_version(mut self, version: SrtVersion) -> Self {
+        self.init_settings.min_peer_version = version;
+
+        self
+    }
+
+    /// Set a callback for control packets with a reserved or user defined type that aren't handled by this library,
+    /// see [`ControlTypes::Custom`](srt_protocol::packet::ControlTypes::Custom). Without one, they are ignored.
+    ///
+    /// It is called from the socket's task, so it should not block.
+    pub fn control_packet_handler(
+        mut self,
+        handler: impl Fn(&ControlPacket) + Send + Sync + 'static,
+    ) -> Self {
+        self.init_settings.control_packet_handler = Some(ControlPacketHandler::new(handler));
+
+        self
+    }
+
+    /// Decide if a listener accepts a connection with `authenticate`, once the handshake is otherwise complete, see
+    /// [`Authenticator`]. The caller's conclusion is only answered once it has, and `timeout` bounds how long that
+    /// takes.
+    ///
+    /// Rejected callers fail to connect with [`io::ErrorKind::ConnectionRefused`]. Ignored when not listening.
+    pub fn authenticator<F>(
+        mut self,
+        timeout: Duration,
+        authenticate: impl Fn(&ConnectionSettings) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = Result<(), RejectReason>> + Send + 'static,
+    {
+        self.init_settings.authenticator = Some(Authenticator::new(authenticate));
+        self.init_settings.auth_timeout = timeout;
+
+        self
+    }
+
+    /// Send `id` as the stream id, for the listener to tell what the caller wants, see
+    /// [`ConnectionInfo::stream_id`](crate::ConnectionInfo::stream_id). Use [`StreamId`](crate::StreamId) for the
+    /// standard syntax.
+    pub fn stream_id(mut self, id: &str) -> Self {
+        self.init_settings
+            .extensions
+            .push(HandshakeExtension::stream_id(id));
+
+        self
+    }
+
+    /// Set a callback seeing every packet sent and received once connected, see [`PacketTap`].
+    ///
+    /// To capture the packets for Wireshark, use the tap of a [`PcapngWriter`](crate::pcapng::PcapngWriter).
+    pub fn packet_tap(mut self, tap: PacketTap) -> Self {
+        self.init_settings.packet_tap = Some(tap);
+
+        self
+    }
+
+    /// Set how the host of [`new_connect_host`](SrtSocketBuilder::new_connect_host) is resolved. Defaults to
+    /// [`SystemResolver`].
+    pub fn resolver(mut self, resolver: impl Resolver + 'static) -> Self {
+        self.resolver = Arc::new(resolver);
+
+        self
+    }
+
+    /// Set where the connection reads the time from, for its timestamps, timers and TSBPD. Defaults to
+    /// [`SystemClock`](crate::SystemClock), see [`CoarseClock`](crate::CoarseClock) for high packet rates.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.init_settings.clock = Arc::new(clock);
+
+        self
+    }
+
+    /// Set a callback for when the data buffered by the sender crosses one of its thresholds, see
+    /// [`SendBufferMonitor`].
+    pub fn send_buffer_monitor(mut self, monitor: SendBufferMonitor) -> Self {
+        self.init_settings.send_buffer_monitor = Some(monitor);
+
+        self
+    }
+
+    /// Set a callback for when no data arrives for a while, and for when it comes back, see [`DataIdleMonitor`]
+    pub fn data_idle_monitor(mut self, monitor: DataIdleMonitor) -> Self {
+        self.init_settings.data_idle_monitor = Some(monitor);
+
+        self
+    }
+
+    /// Set a callback for when the connection's task stops making progress, see [`StallMonitor`]
+    pub fn stall_monitor(mut self, monitor: StallMonitor) -> Self {
+        self.init_settings.stall_monitor = Some(monitor);
+
+        self
+    }
+
+    /// Set a callback for when the sender notices an MTU blackhole and clamps the payload size, see
+    /// [`MtuBlackholeMonitor`]. The payload size is clamped whether or not this is set.
+    pub fn mtu_blackhole_monitor(mut self, monitor: MtuBlackholeMonitor) -> Self {
+        self.init_settings.mtu_blackhole_monitor = Some(monitor);
+
+        self
+    }
+
+    /// Set a callback for when connecting moves on to another phase, see [`ConnectProgress`]. If the host of
+    /// [`new_connect_host`](SrtSocketBuilder::new_connect_host) resolves to several addresses, the phases of
+    /// connecting to each are reported as they're tried.
+    pub fn connect_progress(mut self, progress: ConnectProgress) -> Self {
+        self.init_settings.connect_progress = Some(progress);
+
+        self
+    }
+
+    /// Set how many times in a row the expiration timer, firing twice a second, can fire without hearing from the
+    /// peer before the connection is broken. Defaults to 16, see [`BreakCriteria`](crate::BreakCriteria).
+    pub fn max_exp_count(mut self, count: u32) -> Self {
+        self.init_settings.break_criteria.max_exp_count = count;
+
+        self
+    }
+
+    /// Set how long the peer can be silent before the connection is broken, once the expiration count is also
+    /// reached. Defaults to 5 seconds, the equivalent of `SRTO_PEERIDLETIMEO`.
+    pub fn peer_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.init_settings.break_criteria.peer_idle_timeout = timeout;
+
+        self
+    }
+
+    /// Break the connection after `count` loss reports in a row from the peer without the acknowledged data moving
+    /// forward. By default loss reports never break the connection.
+    pub fn max_nak_storm(mut self, count: u32) -> Self {
+        self.init_settings.break_criteria.max_nak_storm = Some(count);
+
+        self
+    }
+
+    /// Never send data faster than `limit`, whatever the congestion control would allow, see [`RateLimit`]. By
+    /// default the rate isn't limited.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.init_settings.rate_limit = Some(limit);
+
+        self
+    }
+
+    /// Keep retransmissions to a share of what's sent, so they don't crowd out new data on a link that can't carry the
+    /// rate asked of it, see [`RetransmitBudget`]. By default they aren't capped.
+    ///
+    /// # Panics:
+    /// * the share isn't above 0 and at most 1, or the interval is zero
+    pub fn retransmit_budget(mut self, budget: RetransmitBudget) -> Self {
+        assert!(
+            budget.max_share > 0.0 && budget.max_share <= 1.0,
+            "Invalid retransmission share {}",
+            budget.max_share
+        );
+        assert!(
+            budget.interval > Duration::from_secs(0),
+            "Retransmission budget interval must be non-zero"
+        );
+        self.init_settings.retransmit_budget = Some(budget);
+
+        self
+    }
+
+    /// Acknowledge what's received every `interval`, instead of every 4 * RTT + RTTVar + SYN (10ms), such as a short
+    /// one on a LAN with little latency to spare, or a long one over satellite where frequent ACKs only add traffic
+    ///
+    /// # Panics:
+    /// * `interval` is under 1ms or over 1s
+    pub fn ack_interval(mut self, interval: Duration) -> Self {
+        assert!(
+            interval >= Duration::from_millis(1) && interval <= Duration::from_secs(1),
+            "ACK interval {:?} not between 1ms and 1s",
+            interval
+        );
+        self.init_settings.ack_interval = Some(interval);
+
+        self
+    }
+
+    /// Report the losses still missing every `interval`, instead of twice the ACK period following the RTT, such as a
+    /// fixed one on a link whose RTT swings too much for the NAKs to follow it
+    ///
+    /// # Panics:
+    /// * `interval` is under 1ms or over 10s
+    pub fn nak_interval(mut self, interval: Duration) -> Self {
+        assert!(
+            interval >= Duration::from_millis(1) && interval <= Duration::from_secs(10),
+            "NAK interval {:?} not between 1ms and 10s",
+            interval
+        );
+        self.init_settings.nak_interval = Some(interval);
+
+        self
+    }
+
+    /// Set what is dropped when the application gives the socket messages faster than it can send them in live mode,
+    /// see [`SendDropPolicy`]. Defaults to dropping by [`Priority`](crate::Priority).
+    pub fn send_drop_policy(mut self, policy: SendDropPolicy) -> Self {
+        self.init_settings.send_drop_policy = policy;
+
+        self
+    }
+
+    /// Set what's done with packets for the connection from another address than the peer's, see
+    /// [`SourceValidation`]. Defaults to [`SourceValidation::Lenient`], listeners facing the internet should be
+    /// [`Strict`](SourceValidation::Strict).
+    pub fn source_validation(mut self, validation: SourceValidation) -> Self {
+        self.init_settings.source_validation = validation;
+
+        self
+    }
+
+    /// Close the connection, with [`CloseReason::QuotaExceeded`](crate::CloseReason::QuotaExceeded), once it has
+    /// transferred or lasted as much as `quota` allows. Defaults to no limit.
+    pub fn transfer_quota(mut self, quota: TransferQuota) -> Self {
+        self.init_settings.transfer_quota = quota;
+
+        self
+    }
+
+    /// Stop waiting for the peer to acknowledge what was sent `linger` after closing, and abandon the rest, see
+    /// [`SrtSocket::close`](crate::SrtSocket::close). By default closing waits until everything is acknowledged.
+    pub fn linger(mut self, linger: Duration) -> Self {
+        self.init_settings.linger = Some(linger);
+
+        self
+    }
+
+    /// Give up receiving after `timeout` without a message, with [`SrtSocket::recv`], failing with
+    /// [`TimedOut`](io::ErrorKind::TimedOut), the equivalent of `SRTO_RCVTIMEO`. By default it waits forever.
+    pub fn recv_timeout(mut self, timeout: Duration) -> Self {
+        self.init_settings.recv_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Give up sending after `timeout` without the message being queued and acknowledged, with
+    /// [`SrtSocket::send_with_priority`], failing with [`TimedOut`](io::ErrorKind::TimedOut), the equivalent of
+    /// `SRTO_SNDTIMEO`. By default it waits forever.
+    pub fn send_timeout(mut self, timeout: Duration) -> Self {
+        self.init_settings.send_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Send a handshake extension block of type `type_id` with the conclusion handshake, for extensions this library
+    /// doesn't know about. The peer's are in [`ConnectionSettings::peer_extensions`](crate::ConnectionSettings).
+    /// Can be called several times, the blocks are sent in order, zero padded to whole 32-bit words.
+    pub fn handshake_extension(mut self, type_id: u16, data: impl Into<Bytes>) -> Self {
+        self.init_settings.extensions.push(HandshakeExtension {
+            type_id,
+            data: data.into(),
+        });
+
+        self
+    }
+
+    /// Send `data`, opaque to this library, with the conclusion handshake, for the peer to read in
+    /// [`ConnectionInfo::peer_metadata`](crate::ConnectionInfo::peer_metadata) before any data flows, such as codec
+    /// parameters or a session token. Calling it again replaces the metadata.
+    ///
+    /// # Panics:
+    /// * `data` is longer than [`HandshakeExtension::MAX_METADATA`]
+    pub fn metadata(mut self, data: &[u8]) -> Self {
+        assert!(
+            data.len() <= HandshakeExtension::MAX_METADATA,
+            "Metadata of {} bytes is more than the {} that fit in the handshake",
+            data.len(),
+            HandshakeExtension::MAX_METADATA
+        );
+        self.init_settings
+            .extensions
+            .retain(|ext| ext.type_id != HandshakeExtension::METADATA);
+        self.init_settings
+            .extensions
+            .push(HandshakeExtension::metadata(data));
+
+        self
+    }
+
+    /// Negotiate the application protocol spoken over the connection, so one listening port can serve several. A
+    /// caller offers `protocols` in order of preference, a listener lists those it supports and picks the first of
+    /// its own the caller offered, refusing callers offering none of them. The pick is in
+    /// [`ConnectionInfo::app_protocol`](crate::ConnectionInfo::app_protocol) on both sides, and is `None` unless both
+    /// set this. Calling it again replaces the protocols.
+    ///
+    /// # Panics:
+    /// * `protocols` is empty
+    /// * a protocol is empty or longer than 255 bytes
+    pub fn app_protocols(mut self, protocols: &[&str]) -> Self {
+        assert!(!protocols.is_empty(), "No application protocols given");
+        self.init_settings
+            .extensions
+            .retain(|ext| ext.type_id != HandshakeExtension::APP_PROTOCOL);
+        self.init_settings
+            .extensions
+            .push(HandshakeExtension::app_protocols(protocols));
+
+        self
+    }
+
+    /// Read when packets arrived from the kernel's receive timestamps, rather than when the connection's task gets to
+    /// them, for more accurate arrival speed and clock drift estimates when the runtime is busy. Needs the
+    /// `kernel-timestamps` feature, on by default, and only on Linux, for connections with their own socket;
+    /// elsewhere, or if multiplexed, this is ignored. Off by default.
+    pub fn kernel_timestamps(mut self, enabled: bool) -> Self {
+        self.kernel_timestamps = enabled;
+
+        self
+    }
+
+    /// Send and receive through io_uring instead of the reactor's UDP socket, with one system call for a batch of
+    /// packets rather than one each, see [`UringSocket`](crate::UringSocket). Needs the `io-uring` feature, and only on
+    /// Linux, for connections with their own socket; elsewhere, or if multiplexed, this is ignored. Doesn't read
+    /// kernel timestamps. Off by default.
+    #[cfg(feature = "io-uring")]
+    pub fn io_uring(mut self, enabled: bool) -> Self {
+        self.io_uring = enabled;
+
+        self
+    }
+
+    /// Where the task driving the connection runs, such as on a runtime dedicated to networking, or nowhere so the
+    /// application runs it itself, see [`SpawnPolicy`]. Defaults to [`SpawnPolicy::Current`].
+    pub fn spawn_policy(mut self, policy: SpawnPolicy) -> Self {
+        self.spawn_policy = policy;
+
+        self
+    }
+
     /// Connect with a custom socket. Not typically used, see [`connect`](SrtSocketBuilder::connect) instead.
     pub async fn connect_with_sock<T>(self, mut socket: T) -> Result<SrtSocket, io::Error>
     where
@@ -186,46 +758,239 @@ impl SrtSocketBuilder {
             + Send
             + 'static,
     {
+        let spawn_policy = self.spawn_policy.clone();
+        let conn = self.handshake(&mut socket).await?;
+
+        Ok(create_bidrectional_srt_raw(
+            socket,
+            conn,
+            None,
+            &spawn_policy,
+        ))
+    }
+
+    /// Connect over a UDP socket that's already bound, such as one with socket options the builder doesn't set, instead
+    /// of binding one to the local address, which is taken from the socket. Kernel timestamps and io_uring are ignored.
+    pub async fn connect_with_udp(
+        mut self,
+        socket: std::net::UdpSocket,
+    ) -> Result<SrtSocket, io::Error> {
+        self.local_addr = socket.local_addr()?;
+        let socket = tokio_udp(socket)?;
+        self.connect_with_sock(UdpFramed::new(socket, TemplateCodec::new()))
+            .await
+    }
+
+    /// Connect over a custom transport instead of UDP, such as unix datagram sockets, see
+    /// [`AsyncDatagramTransport`]. The local address is taken from the transport. Kernel timestamps and io_uring are
+    /// ignored.
+    pub async fn connect_with_transport<T>(mut self, transport: T) -> Result<SrtSocket, io::Error>
+    where
+        T: AsyncDatagramTransport + Send + 'static,
+    {
+        self.local_addr = transport.local_addr()?;
+        self.connect_with_sock(TransportFramed::new(transport))
+            .await
+    }
+
+    /// Experimental: carry on a connection another process suspended, from its snapshot, see
+    /// [`SrtSocket::suspend`]. `socket` is the one the connection used, inherited from that process, such as with
+    /// [`activation::listen_fds`](crate::activation::listen_fds).
+    ///
+    /// The snapshot keeps what was negotiated, the rest of the settings come from this builder, which must have the
+    /// same passphrase. Fails with [`InvalidData`](io::ErrorKind::InvalidData) if the passphrase can't unwrap the
+    /// snapshot's keys, or only one of them is encrypted.
+    pub fn restore(
+        self,
+        snapshot: ConnectionSnapshot,
+        socket: std::net::UdpSocket,
+    ) -> Result<SrtSocket, io::Error> {
+        let settings = snapshot
+            .restore(&self.init_settings)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let socket = UdpFramed::new(tokio_udp(socket)?, TemplateCodec::new());
+        let conn = Connection {
+            settings,
+            handshake: Handshake::Connector,
+        };
+
+        Ok(create_bidrectional_srt_raw(
+            socket,
+            conn,
+            None,
+            &self.spawn_policy,
+        ))
+    }
+
+    async fn handshake<T>(self, socket: &mut T) -> Result<Connection, io::Error>
+    where
+        T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>>
+            + Sink<(Packet, SocketAddr), Error = io::Error>
+            + Unpin,
+    {
+        let progress = self.init_settings.connect_progress.clone();
         let conn = match self.conn_type {
-            ConnInitMethod::Listen => {
-                pending_connection::listen(&mut socket, self.init_settings).await?
-            }
+            ConnInitMethod::Listen => pending_connection::listen(socket, self.init_settings).await,
             ConnInitMethod::Connect(addr) => {
-                pending_connection::connect(
-                    &mut socket,
-                    addr,
-                    self.local_addr.ip(),
-                    self.init_settings,
-                )
-                .await?
+                pending_connection::connect(socket, addr, self.local_addr.ip(), self.init_settings)
+                    .await
             }
             ConnInitMethod::Rendezvous(remote_public) => {
                 pending_connection::rendezvous(
-                    &mut socket,
+                    socket,
                     self.local_addr,
                     remote_public,
                     self.init_settings,
                 )
-                .await?
+                .await
             }
-        };
+        }?;
+        if let Some(progress) = progress {
+            progress.call(ConnectPhase::Connected);
+        }
 
-        Ok(create_bidrectional_srt(
-            socket.filter_map(|res| {
-                ready(res.map_err(|e| warn!("Error parsing packet: {}", e)).ok())
-            }),
-            conn,
-        ))
+        Ok(conn)
     }
 
     /// Connects to the remote socket. Resolves when it has been connected successfully.
-    pub async fn connect(self) -> Result<SrtSocket, io::Error> {
+    pub async fn connect(mut self) -> Result<SrtSocket, io::Error> {
+        if let Some((host, port)) = &self.remote_host {
+            if let Some(progress) = &self.init_settings.connect_progress {
+                progress.call(ConnectPhase::Resolving);
+            }
+            let addrs = interleave_families(self.resolver.resolve(host, *port).await?);
+            let mut addrs = addrs.into_iter();
+            self.conn_type = match addrs.next() {
+                Some(addr) => ConnInitMethod::Connect(addr),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("{} did not resolve to any address", host),
+                    ))
+                }
+            };
+            self.extra_remote_addrs = addrs.collect();
+        }
+
+        match self.conn_type {
+            ConnInitMethod::Connect(remote) if !self.extra_remote_addrs.is_empty() => {
+                self.connect_any(remote).await
+            }
+            _ => self.connect_one().await,
+        }
+    }
+
+    /// Like [`connect`](SrtSocketBuilder::connect), without spawning anything: the connection runs as long as the
+    /// application runs the returned [`ConnectionDriver`], and is torn down as soon as it's dropped. The spawn policy
+    /// is ignored.
+    pub async fn connect_driven(self) -> Result<(SrtSocket, ConnectionDriver), io::Error> {
+        let mut socket = self.spawn_policy(SpawnPolicy::Manual).connect().await?;
+        let driver = socket.take_driver().expect("not spawned");
+
+        Ok((socket, driver))
+    }
+
+    async fn connect_one(self) -> Result<SrtSocket, io::Error> {
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        {
+            if self.io_uring {
+                if self.kernel_timestamps {
+                    warn!("Kernel timestamps aren't read through io_uring, ignoring");
+                }
+                let socket = UringSocket::bind(self.local_addr)?;
+                return self.connect_with_sock(socket).await;
+            }
+        }
+        #[cfg(all(feature = "io-uring", not(target_os = "linux")))]
+        {
+            if self.io_uring {
+                warn!("io_uring is only supported on Linux, ignoring");
+            }
+        }
+        #[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+        {
+            if self.kernel_timestamps {
+                return self.connect_timestamped().await;
+            }
+        }
+        #[cfg(not(all(feature = "kernel-timestamps", target_os = "linux")))]
+        {
+            if self.kernel_timestamps {
+                warn!("Kernel timestamps need the kernel-timestamps feature, on Linux, ignoring");
+            }
+        }
         let la = self.local_addr;
         Ok(self
-            .connect_with_sock(UdpFramed::new(UdpSocket::bind(&la).await?, PacketCodec {}))
+            .connect_with_sock(UdpFramed::new(
+                UdpSocket::bind(&la).await?,
+                TemplateCodec::new(),
+            ))
             .await?)
     }
 
+    #[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+    async fn connect_timestamped(self) -> Result<SrtSocket, io::Error> {
+        let mut socket = TimestampedSocket::bind(self.local_addr)?;
+        let arrival = socket.arrival_time();
+        let spawn_policy = self.spawn_policy.clone();
+        let conn = self.handshake(&mut socket).await?;
+
+        Ok(create_bidrectional_srt_raw(
+            socket,
+            conn,
+            Some(arrival),
+            &spawn_policy,
+        ))
+    }
+
+    // staggered attempts to every remote address, each from its own socket
+    async fn connect_any(self, remote: SocketAddr) -> Result<SrtSocket, io::Error> {
+        let mut candidates = Some(remote)
+            .into_iter()
+            .chain(self.extra_remote_addrs.clone())
+            .enumerate();
+        let attempt = |(i, remote): (usize, SocketAddr)| {
+            let mut builder = self.clone();
+            builder.conn_type = ConnInitMethod::Connect(remote);
+            builder.local_addr = local_addr_for(self.local_addr, remote);
+            if i > 0 {
+                builder.init_settings = builder.init_settings.copy_randomize();
+            }
+            async move {
+                info!("Connecting to {}", remote);
+                (remote, builder.connect_one().await)
+            }
+        };
+
+        let mut attempts = FuturesUnordered::new();
+        let mut last_error = None;
+        loop {
+            if attempts.is_empty() {
+                match candidates.next() {
+                    Some(candidate) => attempts.push(attempt(candidate)),
+                    None => return Err(last_error.unwrap()),
+                }
+            }
+            select! {
+                res = attempts.select_next_some() => match res {
+                    (_, Ok(socket)) => return Ok(socket),
+                    (remote, Err(e)) => {
+                        warn!("Failed to connect to {}: {}", remote, e);
+                        last_error = Some(e);
+                        if let Some(candidate) = candidates.next() {
+                            attempts.push(attempt(candidate));
+                        }
+                    }
+                },
+                _ = delay_for(CONNECT_STAGGER).fuse() => {
+                    if let Some(candidate) = candidates.next() {
+                        attempts.push(attempt(candidate));
+                    }
+                },
+            }
+        }
+    }
+
     /// Build a multiplexed connection. This acts as a sort of server, allowing many connections to this one socket.
     ///
     /// # Panics:
@@ -234,8 +999,188 @@ impl SrtSocketBuilder {
         self,
     ) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
         match self.conn_type {
-            ConnInitMethod::Listen => multiplex(self.local_addr, self.init_settings).await,
+            ConnInitMethod::Listen => self.multiplex_all(MultiplexStats::default()).await,
+            _ => panic!("Cannot bind multiplexed with any connection mode other than listen"),
+        }
+    }
+
+    /// Build a multiplexed connection, like [`build_multiplexed`](SrtSocketBuilder::build_multiplexed), along with a
+    /// handle to the egress statistics of its connections.
+    ///
+    /// Connections sharing the socket take turns sending, so one with a high bitrate can't starve the others.
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    pub async fn build_multiplexed_with_stats(
+        self,
+    ) -> Result<
+        (
+            MultiplexStats,
+            impl Stream<Item = Result<(Connection, PackChan), io::Error>>,
+        ),
+        io::Error,
+    > {
+        match self.conn_type {
+            ConnInitMethod::Listen => {
+                let stats = MultiplexStats::default();
+                let server = self.multiplex_all(stats.clone()).await?;
+                Ok((stats, server))
+            }
+            _ => panic!("Cannot bind multiplexed with any connection mode other than listen"),
+        }
+    }
+
+    /// Build a multiplexed connection over a custom socket, like an [`Aggregator`](crate::Aggregator), instead of
+    /// binding UDP sockets to the local addresses.
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    pub fn build_multiplexed_with_sock<T>(
+        self,
+        socket: T,
+    ) -> impl Stream<Item = Result<(Connection, PackChan), io::Error>>
+    where
+        T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>>
+            + Sink<(Packet, SocketAddr), Error = io::Error>
+            + Unpin,
+    {
+        match self.conn_type {
+            ConnInitMethod::Listen => {
+                multiplex_with_sock(socket, self.init_settings, MultiplexStats::default())
+            }
+            _ => panic!("Cannot bind multiplexed with any connection mode other than listen"),
+        }
+    }
+
+    /// Build a multiplexed connection over a UDP socket that's already bound, such as one with socket options the
+    /// builder doesn't set, instead of binding to the local addresses.
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    pub fn build_multiplexed_with_udp(
+        self,
+        socket: std::net::UdpSocket,
+    ) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
+        let socket = UdpFramed::new(tokio_udp(socket)?, PacketCodec {});
+        Ok(self.build_multiplexed_with_sock(socket))
+    }
+
+    /// Build a multiplexed connection over a custom transport instead of UDP, see [`AsyncDatagramTransport`].
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    pub fn build_multiplexed_with_transport<T>(
+        self,
+        transport: T,
+    ) -> impl Stream<Item = Result<(Connection, PackChan), io::Error>>
+    where
+        T: AsyncDatagramTransport,
+    {
+        self.build_multiplexed_with_sock(TransportFramed::new(transport))
+    }
+
+    /// Build a multiplexed connection over the sockets this process inherited, such as with systemd socket activation,
+    /// instead of binding to the local addresses, see the [`activation`](crate::activation) module. Fails if it didn't
+    /// inherit any.
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    #[cfg(all(feature = "activation", unix))]
+    pub fn build_multiplexed_activated(
+        self,
+    ) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
+        let sockets = crate::activation::listen_fds()?;
+        if sockets.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No sockets were inherited, LISTEN_FDS isn't set for this process",
+            ));
+        }
+        let mut servers = Vec::new();
+        for socket in sockets {
+            info!("Listening on inherited socket {}", socket.local_addr()?);
+            let server = self.clone().build_multiplexed_with_udp(socket)?;
+            servers.push(server.boxed());
+        }
+        Ok(select_all(servers))
+    }
+
+    /// Build a multiplexed connection with one multiplexer per core, all bound to the local address, for servers with
+    /// many connections, see [`ShardedMultiplexer`]. Extra local addresses aren't listened on. The shards are
+    /// spawned as the [`spawn_policy`](SrtSocketBuilder::spawn_policy) says.
+    ///
+    /// # Panics:
+    /// If this is built with a non-listen builder
+    pub async fn build_multiplexed_sharded(self) -> Result<ShardedMultiplexer, io::Error> {
+        match self.conn_type {
+            ConnInitMethod::Listen => {
+                if !self.extra_local_addrs.is_empty() {
+                    warn!("A sharded multiplexer only listens on {}", self.local_addr);
+                }
+                let shards = thread::available_parallelism().map_or(1, |n| n.get());
+                ShardedMultiplexer::bind_with_spawn_policy(
+                    self.local_addr,
+                    shards,
+                    self.init_settings,
+                    MultiplexStats::default(),
+                    &self.spawn_policy,
+                )
+                .await
+            }
             _ => panic!("Cannot bind multiplexed with any connection mode other than listen"),
         }
     }
+
+    async fn multiplex_all(
+        self,
+        stats: MultiplexStats,
+    ) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
+        let mut servers = Vec::new();
+        for addr in Some(self.local_addr)
+            .into_iter()
+            .chain(self.extra_local_addrs)
+        {
+            let server =
+                multiplex_with_stats(addr, self.init_settings.clone(), stats.clone()).await?;
+            servers.push(server.boxed());
+        }
+        Ok(select_all(servers))
+    }
+}
+
+// the reactor needs it non-blocking, which a socket from elsewhere may not be
+fn tokio_udp(socket: std::net::UdpSocket) -> Result<UdpSocket, io::Error> {
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket)
+}
+
+// alternate address families, starting with the first one's, like RFC 8305 does
+fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = match addrs.first() {
+        Some(addr) => addr.is_ipv6(),
+        None => return addrs,
+    };
+    let (first, second): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|a| a.is_ipv6() == first_v6);
+    let mut interleaved = Vec::with_capacity(first.len() + second.len());
+    let (mut first, mut second) = (first.into_iter(), second.into_iter());
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+// an unspecified local address can only reach remotes of its own family
+fn local_addr_for(local: SocketAddr, remote: SocketAddr) -> SocketAddr {
+    if !local.ip().is_unspecified() || local.is_ipv6() == remote.is_ipv6() {
+        return local;
+    }
+    let ip: IpAddr = if remote.is_ipv6() {
+        Ipv6Addr::UNSPECIFIED.into()
+    } else {
+        Ipv4Addr::UNSPECIFIED.into()
+    };
+    SocketAddr::new(ip, local.port())
 }
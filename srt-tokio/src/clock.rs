@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use tokio::time::interval;
+
+use crate::Clock;
+
+#[derive(Debug)]
+struct Ticks {
+    base: Instant,
+    elapsed_micros: AtomicU64,
+}
+
+/// A clock that's only read from the system every `resolution`, so reading it is a single atomic load
+///
+/// Meant for the per packet path of connections with high packet rates, where the time doesn't need to be more
+/// precise than the resolution. It's updated by a task spawned on the runtime, so must be created within one, and
+/// the task stops once every clone of the clock is dropped.
+#[derive(Debug, Clone)]
+pub struct CoarseClock(Arc<Ticks>);
+
+impl CoarseClock {
+    pub fn new(resolution: Duration) -> Self {
+        let ticks = Arc::new(Ticks {
+            base: Instant::now(),
+            elapsed_micros: AtomicU64::new(0),
+        });
+        tokio::spawn(tick(Arc::downgrade(&ticks), resolution));
+        CoarseClock(ticks)
+    }
+}
+
+async fn tick(ticks: Weak<Ticks>, resolution: Duration) {
+    let mut interval = interval(resolution);
+    loop {
+        interval.tick().await;
+        let ticks = match ticks.upgrade() {
+            Some(ticks) => ticks,
+            None => return,
+        };
+        let elapsed = ticks.base.elapsed().as_micros() as u64;
+        // never goes back, even if ticks race
+        ticks.elapsed_micros.fetch_max(elapsed, Ordering::Relaxed);
+    }
+}
+
+impl Clock for CoarseClock {
+    fn now(&self) -> Instant {
+        self.0.base + Duration::from_micros(self.0.elapsed_micros.load(Ordering::Relaxed))
+    }
+}
@@ -1,8 +1,19 @@
+use crate::packet::{ControlTemplate, ControlTypes};
 use crate::{Packet, PacketParseError};
-use bytes::BytesMut;
-use std::io::{self, Cursor};
+use bytes::{Bytes, BytesMut};
+use std::io;
 use tokio_util::codec::{Decoder, Encoder};
 
+/// The largest datagram read, which is what `UdpFramed` makes room for before each read
+pub(crate) const MAX_DATAGRAM: usize = 64 * 1024;
+
+/// Datagrams are read one after the other into buffers this large, which the payloads parsed from them share
+pub(crate) const SLAB_SIZE: usize = 1024 * 1024;
+
+/// Parses and serializes packets, to frame a UDP socket for [`connect_with_sock`](crate::SrtSocketBuilder::connect_with_sock)
+///
+/// The payloads of data packets are slices of the datagram they were read in, not copies. With the `strict` feature,
+/// packets that fail to parse are logged as a hexdump, for debugging interop issues.
 pub struct PacketCodec;
 
 impl Decoder for PacketCodec {
@@ -10,7 +21,13 @@ impl Decoder for PacketCodec {
     type Error = PacketParseError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Packet>, Self::Error> {
-        Packet::parse(&mut Cursor::new(buf)).map(Some)
+        let datagram = buf.split().freeze();
+        // the next datagram is read after this one while there's room for it, otherwise UdpFramed would allocate a
+        // buffer for every datagram, which the payload would keep alive in the receive buffer
+        if buf.capacity() < MAX_DATAGRAM {
+            *buf = BytesMut::with_capacity(SLAB_SIZE);
+        }
+        parse(datagram).map(Some)
     }
 }
 
@@ -23,3 +40,93 @@ impl Encoder<Packet> for PacketCodec {
         Ok(())
     }
 }
+
+/// A [`PacketCodec`] for a socket carrying a single connection, which encodes its ACKs and keepalives from a
+/// [`ControlTemplate`] instead of serializing each one
+///
+/// Sockets shared by connections, like a multiplexer's, use a plain [`PacketCodec`]; the template would be rebuilt
+/// each time the connection sending changes.
+#[derive(Debug, Default)]
+pub struct TemplateCodec {
+    template: Option<ControlTemplate>,
+}
+
+impl TemplateCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Decoder for TemplateCodec {
+    type Item = Packet;
+    type Error = PacketParseError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Packet>, Self::Error> {
+        PacketCodec.decode(buf)
+    }
+}
+
+impl Encoder<Packet> for TemplateCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, packet: Packet, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        if let Packet::Control(control) = &packet {
+            if matches!(
+                control.control_type,
+                ControlTypes::Ack(_) | ControlTypes::KeepAlive
+            ) {
+                let dest_sockid = control.dest_sockid;
+                if self.template.as_ref().map(ControlTemplate::dest_sockid) != Some(dest_sockid) {
+                    self.template = Some(ControlTemplate::new(dest_sockid));
+                }
+                if let Some(encoded) = self.template.as_mut().and_then(|t| t.encode(control)) {
+                    buf.extend_from_slice(encoded);
+                    return Ok(());
+                }
+            }
+        }
+        PacketCodec.encode(packet, buf)
+    }
+}
+
+/// A slab zeroed once, so datagrams can be read into it without a copy, then split off it
+pub(crate) fn zeroed_slab() -> BytesMut {
+    let mut slab = BytesMut::with_capacity(SLAB_SIZE);
+    slab.resize(SLAB_SIZE, 0);
+    slab
+}
+
+/// Parse the packet in the datagram `buf`, logging it if it's malformed and the `strict` feature is enabled
+pub(crate) fn parse(buf: Bytes) -> Result<Packet, PacketParseError> {
+    let res = Packet::parse(&mut buf.clone());
+    #[cfg(feature = "strict")]
+    {
+        if let Err(e) = &res {
+            log::warn!(
+                "Malformed packet ({}), {} bytes:\n{}",
+                e,
+                buf.len(),
+                hexdump(&buf)
+            );
+        }
+    }
+    res
+}
+
+// 16 bytes a line, prefixed by their offset
+#[cfg(feature = "strict")]
+fn hexdump(buf: &[u8]) -> String {
+    use std::fmt::Write;
+
+    let mut dump = String::new();
+    for (i, line) in buf.chunks(16).enumerate() {
+        if i > 0 {
+            dump.push('\n');
+        }
+        let _ = write!(dump, "{:04x}:", i * 16);
+        for byte in line {
+            let _ = write!(dump, " {:02x}", byte);
+        }
+    }
+    dump
+}
@@ -0,0 +1,164 @@
+//! Fan out one stream to many subscribers, the core of a relay server
+//!
+//! Subscribers coming from a [multiplexed listener](crate::SrtSocketBuilder::build_multiplexed) are only served
+//! while the listener is polled, so keep polling it after the distributor is done until they are closed.
+//!
+//! ```no_run
+//! use srt_tokio::{tokio::create_bidrectional_srt, SlowSubscriberPolicy, SrtSocketBuilder, StreamDistributor};
+//! use futures::prelude::*;
+//! use std::io;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), io::Error> {
+//!     let source = SrtSocketBuilder::new_listen().local_port(3333).connect().await?;
+//!     let mut subscribers = SrtSocketBuilder::new_listen()
+//!         .local_port(3334)
+//!         .build_multiplexed()
+//!         .await?
+//!         .filter_map(|res| async { res.ok() })
+//!         .map(|(conn, chan)| create_bidrectional_srt(chan, conn))
+//!         .boxed();
+//!
+//!     let mut distributor = StreamDistributor::new(1024, SlowSubscriberPolicy::DropPackets);
+//!     distributor.run(source, &mut subscribers).await
+//! }
+//! ```
+
+use std::{io, time::Instant};
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+use futures::select_biased;
+use log::{debug, info, warn};
+
+/// What to do with a subscriber whose buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowSubscriberPolicy {
+    /// Drop the packets that don't fit in the buffer, the subscriber gets the rest of the stream
+    DropPackets,
+
+    /// Disconnect the subscriber, closing its sink
+    Disconnect,
+}
+
+/// Sends every packet from one source to each subscriber, see the [module docs](self)
+///
+/// Each subscriber gets its own task and buffer, so a slow subscriber doesn't hold back the others.
+pub struct StreamDistributor {
+    subscribers: Vec<mpsc::Sender<(Instant, Bytes)>>,
+    buffer_size: usize,
+    policy: SlowSubscriberPolicy,
+    dropped_packets: u64,
+}
+
+impl StreamDistributor {
+    /// Create a distributor buffering up to `buffer_size` packets for each subscriber
+    pub fn new(buffer_size: usize, policy: SlowSubscriberPolicy) -> Self {
+        StreamDistributor {
+            subscribers: vec![],
+            buffer_size,
+            policy,
+            dropped_packets: 0,
+        }
+    }
+
+    /// Add a subscriber, which gets every packet distributed from now on.
+    ///
+    /// The sink is closed once the distributor is done with it, after sending anything still buffered.
+    pub fn add<S>(&mut self, mut sink: S)
+    where
+        S: Sink<(Instant, Bytes), Error = io::Error> + Send + Unpin + 'static,
+    {
+        let (tx, rx) = mpsc::channel(self.buffer_size);
+        self.subscribers.push(tx);
+
+        tokio::spawn(async move {
+            if let Err(e) = sink.send_all(&mut rx.map(Ok)).await {
+                warn!("Error sending to subscriber: {}", e);
+            }
+            if let Err(e) = sink.close().await {
+                warn!("Error closing subscriber: {}", e);
+            }
+        });
+    }
+
+    /// The number of connected subscribers
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// The number of packets dropped because a subscriber was too slow, with [`SlowSubscriberPolicy::DropPackets`]
+    pub fn dropped_packets(&self) -> u64 {
+        self.dropped_packets
+    }
+
+    /// Queue a packet for every subscriber, without waiting.
+    ///
+    /// Subscribers that closed are removed, and full subscribers are handled according to the policy.
+    pub fn distribute(&mut self, item: (Instant, Bytes)) {
+        let mut i = 0;
+        while i != self.subscribers.len() {
+            let keep = match self.subscribers[i].try_send(item.clone()) {
+                Ok(()) => true,
+                Err(e) if e.is_disconnected() => {
+                    info!("Subscriber disconnected");
+                    false
+                }
+                Err(_) => match self.policy {
+                    SlowSubscriberPolicy::DropPackets => {
+                        self.dropped_packets += 1;
+                        true
+                    }
+                    SlowSubscriberPolicy::Disconnect => {
+                        info!("Disconnecting slow subscriber");
+                        false
+                    }
+                },
+            };
+
+            if keep {
+                i += 1;
+            } else {
+                self.subscribers.remove(i);
+            }
+        }
+    }
+
+    /// Distribute `source` to every subscriber, adding subscribers from `subscribers` as they come.
+    ///
+    /// Resolves when `source` ends or fails, and closes every subscriber.
+    pub async fn run<S>(
+        &mut self,
+        source: impl Stream<Item = Result<(Instant, Bytes), io::Error>> + Unpin,
+        subscribers: impl Stream<Item = S> + Unpin,
+    ) -> Result<(), io::Error>
+    where
+        S: Sink<(Instant, Bytes), Error = io::Error> + Send + Unpin + 'static,
+    {
+        let mut source = source.fuse();
+        let mut subscribers = subscribers.fuse();
+        let result = loop {
+            // new subscribers first, so they don't miss packets that are already ready
+            select_biased! {
+                sub = subscribers.next() => if let Some(sub) = sub {
+                    debug!("New subscriber");
+                    self.add(sub);
+                },
+                item = source.next() => match item {
+                    Some(Ok(item)) => self.distribute(item),
+                    Some(Err(e)) => break Err(e),
+                    None => break Ok(()),
+                },
+            }
+        };
+
+        // dropping the queues closes the subscribers once they are drained
+        self.subscribers.clear();
+        result
+    }
+}
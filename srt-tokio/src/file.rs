@@ -0,0 +1,244 @@
+//! Helpers to transfer a whole file over a [`SrtSocket`]
+//!
+//! The transfer is framed with a small application level protocol on top of SRT messages,
+//! where each message starts with a one byte tag:
+//! 1. Sender -> Receiver: `HEADER` + the file size (u64 BE)
+//! 2. Receiver -> Sender: `RESUME` + the offset to start at (u64 BE), the length of the partial file it already has
+//! 3. Sender -> Receiver: `DATA` + a chunk of the file, repeated until the whole file has been sent
+//! 4. Sender -> Receiver: `END` + the SHA-1 of the whole file, checked by the receiver
+//!
+//! This is best used with [`TransmissionType::File`](crate::TransmissionType::File), as in live mode
+//! packets that are too late are dropped and the transfer would fail.
+
+use std::convert::TryInto;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::prelude::*;
+use log::info;
+use sha1::{Digest, Sha1};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::SrtSocket;
+
+const HEADER: u8 = 0;
+const RESUME: u8 = 1;
+const DATA: u8 = 2;
+const END: u8 = 3;
+
+// leave room for the tag in a single packet
+const CHUNK_SIZE: usize = 1315;
+
+/// The state of a file transfer, passed to the progress callback after every chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileProgress {
+    /// The number of bytes of the file the receiver has, including the resumed bytes
+    pub transferred: u64,
+
+    /// The size of the whole file
+    pub total: u64,
+
+    /// The offset the transfer started at, non-zero if a partial file was resumed
+    pub resumed_from: u64,
+}
+
+/// Send the file at `path` to a peer calling [`recv_file`], calling `progress` after each chunk is sent.
+///
+/// Resolves once the whole file has been sent, this does not close the socket.
+pub async fn send_file(
+    sock: &mut SrtSocket,
+    path: impl AsRef<Path>,
+    mut progress: impl FnMut(FileProgress),
+) -> Result<FileProgress, io::Error> {
+    let mut file = File::open(path.as_ref()).await?;
+    let total = file.metadata().await?.len();
+
+    let mut header = BytesMut::with_capacity(9);
+    header.put_u8(HEADER);
+    header.put_u64(total);
+    sock.send((Instant::now(), header.freeze())).await?;
+
+    let resume = expect_message(sock, RESUME).await?;
+    let resumed_from = parse_u64(&resume)?;
+    if resumed_from > total {
+        return Err(invalid_data(format!(
+            "Receiver asked to resume at {}, but the file is only {} bytes",
+            resumed_from, total
+        )));
+    }
+    info!(
+        "Sending {:?}, resuming from {}",
+        path.as_ref(),
+        resumed_from
+    );
+
+    // the bytes before the resume point aren't sent, but they still need to be in the checksum
+    let mut hasher = Sha1::new();
+    hash_prefix(&mut file, &mut hasher, resumed_from).await?;
+
+    let mut state = FileProgress {
+        transferred: resumed_from,
+        total,
+        resumed_from,
+    };
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buf[..]).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+
+        let mut chunk = BytesMut::with_capacity(read + 1);
+        chunk.put_u8(DATA);
+        chunk.extend_from_slice(&buf[..read]);
+        sock.send((Instant::now(), chunk.freeze())).await?;
+
+        state.transferred += read as u64;
+        progress(state);
+    }
+
+    let mut end = BytesMut::with_capacity(21);
+    end.put_u8(END);
+    end.extend_from_slice(&hasher.finalize()[..]);
+    sock.send((Instant::now(), end.freeze())).await?;
+
+    Ok(state)
+}
+
+/// Receive a file sent with [`send_file`] into `path`, calling `progress` after each chunk is received.
+///
+/// If `resume` is set and `path` already exists, its contents are kept and only the rest of the file is requested.
+/// Otherwise, the file is truncated. Fails with [`io::ErrorKind::InvalidData`] if the checksum doesn't match, or if
+/// the sender sends more than the size it announced.
+pub async fn recv_file(
+    sock: &mut SrtSocket,
+    path: impl AsRef<Path>,
+    resume: bool,
+    mut progress: impl FnMut(FileProgress),
+) -> Result<FileProgress, io::Error> {
+    let header = expect_message(sock, HEADER).await?;
+    let total = parse_u64(&header)?;
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        // the existing contents are needed to resume, it is truncated below if not resuming
+        .truncate(false)
+        .open(path.as_ref())
+        .await?;
+
+    let existing = file.metadata().await?.len();
+    let resumed_from = if resume && existing <= total {
+        existing
+    } else {
+        0
+    };
+    file.set_len(resumed_from).await?;
+
+    let mut hasher = Sha1::new();
+    hash_prefix(&mut file, &mut hasher, resumed_from).await?;
+
+    let mut resume_msg = BytesMut::with_capacity(9);
+    resume_msg.put_u8(RESUME);
+    resume_msg.put_u64(resumed_from);
+    sock.send((Instant::now(), resume_msg.freeze())).await?;
+    info!(
+        "Receiving {:?}, resuming from {}",
+        path.as_ref(),
+        resumed_from
+    );
+
+    let mut state = FileProgress {
+        transferred: resumed_from,
+        total,
+        resumed_from,
+    };
+    let checksum = loop {
+        let msg = next_message(sock).await?;
+        match msg[0] {
+            DATA => {
+                let chunk = &msg[1..];
+                if state.transferred + chunk.len() as u64 > total {
+                    return Err(invalid_data(format!(
+                        "File transfer sent more than the {} bytes announced",
+                        total
+                    )));
+                }
+                hasher.update(chunk);
+                file.write_all(chunk).await?;
+
+                state.transferred += chunk.len() as u64;
+                progress(state);
+            }
+            END => break msg.slice(1..),
+            tag => {
+                return Err(invalid_data(format!(
+                    "Unexpected message {} during file transfer",
+                    tag
+                )))
+            }
+        }
+    };
+    file.flush().await?;
+
+    if state.transferred != total {
+        return Err(invalid_data(format!(
+            "File transfer ended after {} bytes, expected {}",
+            state.transferred, total
+        )));
+    }
+    if hasher.finalize()[..] != checksum[..] {
+        return Err(invalid_data("File checksum mismatch".into()));
+    }
+
+    Ok(state)
+}
+
+async fn hash_prefix(file: &mut File, hasher: &mut Sha1, len: u64) -> Result<(), io::Error> {
+    let mut buf = vec![0; 64 * 1024];
+    let mut remaining = len;
+    while remaining > 0 {
+        let to_read = remaining.min(buf.len() as u64) as usize;
+        file.read_exact(&mut buf[..to_read]).await?;
+        hasher.update(&buf[..to_read]);
+        remaining -= to_read as u64;
+    }
+    Ok(())
+}
+
+async fn next_message(sock: &mut SrtSocket) -> Result<Bytes, io::Error> {
+    match sock.try_next().await? {
+        Some((_, msg)) if !msg.is_empty() => Ok(msg),
+        Some(_) => Err(invalid_data("Empty message during file transfer".into())),
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Connection closed during file transfer",
+        )),
+    }
+}
+
+async fn expect_message(sock: &mut SrtSocket, tag: u8) -> Result<Bytes, io::Error> {
+    let msg = next_message(sock).await?;
+    if msg[0] != tag {
+        return Err(invalid_data(format!(
+            "Expected message {} during file transfer, got {}",
+            tag, msg[0]
+        )));
+    }
+    Ok(msg.slice(1..))
+}
+
+fn parse_u64(msg: &[u8]) -> Result<u64, io::Error> {
+    msg.try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| invalid_data(format!("Expected 8 bytes, got {}", msg.len())))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
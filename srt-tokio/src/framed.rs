@@ -0,0 +1,161 @@
+//! Typed messages over a connection, with a codec like [`tokio_util::codec::Framed`] uses
+//!
+//! [`SrtFramed`] treats the connection as a byte stream: what is received is appended to a buffer the decoder reads
+//! frames from, whatever the messages it came in, and frames are encoded and sent in messages of at most 64KiB, well
+//! under the [`max_message_size`](crate::SrtSocketBuilder::max_message_size) of the peer. This only works if nothing is lost, so use it with
+//! [`TransmissionType::File`](crate::TransmissionType::File).
+//!
+//! ```no_run
+//! use srt_tokio::{SrtFramed, SrtSocketBuilder, TransmissionType};
+//! use tokio_util::codec::LengthDelimitedCodec;
+//! use futures::prelude::*;
+//! use bytes::Bytes;
+//! use std::io;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), io::Error> {
+//!     let sock = SrtSocketBuilder::new_connect("127.0.0.1:3333")
+//!         .transmission_type(TransmissionType::File)
+//!         .connect()
+//!         .await?;
+//!     let mut framed = SrtFramed::new(sock, LengthDelimitedCodec::new());
+//!     framed.send(Bytes::from(r#"{"hello":"world"}"#)).await?;
+//!     while let Some(frame) = framed.try_next().await? {
+//!         println!("{:?}", frame);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::{Bytes, BytesMut};
+use futures::{ready, Sink, Stream};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::SrtSocket;
+
+// larger frames are split, so they're not dropped by a peer with a small max_message_size
+const MAX_MESSAGE: usize = 64 * 1024;
+
+/// A [`SrtSocket`] sending and receiving frames with the codec `C`, see the [module docs](self)
+pub struct SrtFramed<C> {
+    socket: SrtSocket,
+    codec: C,
+    read_buf: BytesMut,
+    // set once the socket ended and the decoder was given what was left
+    eof: bool,
+    write_buf: BytesMut,
+    // encoded, but not taken by the socket yet
+    pending: VecDeque<Bytes>,
+}
+
+impl<C> SrtFramed<C> {
+    /// Frame `socket` with `codec`
+    pub fn new(socket: SrtSocket, codec: C) -> Self {
+        SrtFramed {
+            socket,
+            codec,
+            read_buf: BytesMut::new(),
+            eof: false,
+            write_buf: BytesMut::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &SrtSocket {
+        &self.socket
+    }
+
+    pub fn get_mut(&mut self) -> &mut SrtSocket {
+        &mut self.socket
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// The socket and the codec back, along with what was received but not decoded yet
+    pub fn into_parts(self) -> (SrtSocket, C, BytesMut) {
+        (self.socket, self.codec, self.read_buf)
+    }
+
+    // hand the encoded messages to the socket while it takes them
+    fn poll_pending(&mut self, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        while !self.pending.is_empty() {
+            ready!(Pin::new(&mut self.socket).poll_ready(cx))?;
+            if let Some(message) = self.pending.pop_front() {
+                Pin::new(&mut self.socket).start_send((Instant::now(), message))?;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C> Stream for SrtFramed<C>
+where
+    C: Decoder + Unpin,
+    C::Error: From<io::Error>,
+{
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.eof {
+                return Poll::Ready(None);
+            }
+            if let Some(frame) = this.codec.decode(&mut this.read_buf)? {
+                return Poll::Ready(Some(Ok(frame)));
+            }
+            match ready!(Pin::new(&mut this.socket).poll_next(cx)) {
+                Some(message) => this.read_buf.extend_from_slice(&message?.1),
+                None => {
+                    this.eof = true;
+                    return Poll::Ready(this.codec.decode_eof(&mut this.read_buf)?.map(Ok));
+                }
+            }
+        }
+    }
+}
+
+impl<C, I> Sink<I> for SrtFramed<C>
+where
+    C: Encoder<I> + Unpin,
+    C::Error: From<io::Error>,
+{
+    type Error = C::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(ready!(self.poll_pending(cx))?))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        this.codec.encode(item, &mut this.write_buf)?;
+        while !this.write_buf.is_empty() {
+            let len = this.write_buf.len().min(MAX_MESSAGE);
+            this.pending
+                .push_back(this.write_buf.split_to(len).freeze());
+        }
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_pending(cx))?;
+        Poll::Ready(Ok(ready!(Pin::new(&mut self.socket).poll_flush(cx))?))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_pending(cx))?;
+        Poll::Ready(Ok(ready!(Pin::new(&mut self.socket).poll_close(cx))?))
+    }
+}
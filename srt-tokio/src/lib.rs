@@ -1,8 +1,11 @@
 #![deny(clippy::all)]
-#![forbid(unsafe_code)]
+// the only exceptions are the system calls for kernel timestamps, in `timestamping`, and for io_uring, in `uring`, and
+// taking inherited sockets, in `activation`
+#![deny(unsafe_code)]
 #![recursion_limit = "256"]
 
-//! Implementation of [SRT](https://www.haivision.com/products/srt-secure-reliable-transport/) in pure safe rust.
+//! Implementation of [SRT](https://www.haivision.com/products/srt-secure-reliable-transport/) in safe rust, apart from the system calls
+//! behind the optional [kernel timestamps](SrtSocketBuilder::kernel_timestamps) and io_uring backend.
 //!
 //! Generally used for live video streaming across lossy but high bandwidth connections.
 //!
@@ -48,19 +51,75 @@
 //! ```
 //!
 
+#[cfg(all(feature = "activation", unix))]
+pub mod activation;
+#[cfg(feature = "app")]
+pub mod app;
+pub mod bridge;
 mod builder;
 mod channel;
+mod clock;
 mod codec;
+pub mod distributor;
+mod file;
+mod framed;
+mod loopback;
+pub mod multicast;
 mod multiplex;
+pub mod multistream;
+#[cfg(feature = "netem")]
+pub mod netem;
+pub mod pcapng;
 mod pending_connection;
+pub mod relay;
+mod resolver;
+#[cfg(feature = "socks5")]
+pub mod socks5;
+pub mod srtla;
+mod timestamping;
 pub mod tokio;
+mod transport;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+#[allow(unsafe_code)]
+mod uring;
 mod util;
-
-use codec::PacketCodec;
+mod watchdog;
 
 pub use crate::builder::{ConnInitMethod, SrtSocketBuilder};
-pub use crate::multiplex::{multiplex, PackChan, StreamerServer};
-pub use crate::tokio::SrtSocket;
+pub use crate::clock::CoarseClock;
+pub use crate::codec::{PacketCodec, TemplateCodec};
+pub use crate::distributor::{SlowSubscriberPolicy, StreamDistributor};
+pub use crate::file::{recv_file, send_file, FileProgress};
+pub use crate::framed::SrtFramed;
+pub use crate::loopback::Loopback;
+pub use crate::multicast::{MulticastReceiver, MulticastSender};
+pub use crate::multiplex::{
+    multiplex, multiplex_with_sock, multiplex_with_stats, ConnectionHandle, EgressStats,
+    MultiplexStats, PackChan, ShardedMultiplexer, StreamerServer,
+};
+pub use crate::multistream::{MultiStream, SubStream};
+pub use crate::relay::{relay, RelayTiming};
+pub use crate::resolver::{Resolver, SystemResolver};
+pub use crate::srtla::{
+    Aggregator, AggregatorStats, BondStats, BondedSocket, BondingMode, LinkQuality, LinkStats,
+};
+pub use crate::tokio::{
+    CloseReason, ConnectionDriver, SpawnPolicy, SrtRecvHalf, SrtSendHalf, SrtSender, SrtSocket,
+};
+pub use crate::transport::{AsyncDatagramTransport, TransportFramed};
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use crate::uring::UringSocket;
+pub use srt_protocol::{
+    Authenticator, BreakCriteria, Callback, Clock, ConnectPhase, ConnectProgress, ConnectStats,
+    ConnectionDump, ConnectionEvent, ConnectionInfo, ConnectionSnapshot, ControlPacketHandler,
+    ControlRecord, DataIdleEvent, DataIdleMonitor, DebugDump, EventRecord, Feature,
+    HandshakeExtension, KeyLengthPolicy, KmState, LibsrtOptionError, MockClock, MtuBlackholeEvent,
+    MtuBlackholeMonitor, PacketDirection, PacketTap, Priority, ProtocolVersion, RateLimit,
+    RateLimitControl, ReceiverDump, RetransmitAlgorithm, RetransmitBudget, SendBufferLevel,
+    SendBufferMonitor, SendDropPolicy, SenderDump, SocketStatistics, SourceValidation, SrtVersion,
+    StallEvent, StallMonitor, StreamId, StreamIdParseError, StreamMode, SystemClock, TaskStage,
+    TransferQuota, TransmissionType, WrappedKeys,
+};
 
 use srt_protocol::connection::{self, Connection, ConnectionSettings};
 use srt_protocol::crypto;
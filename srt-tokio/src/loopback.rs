@@ -0,0 +1,125 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::prelude::*;
+use log::trace;
+
+use crate::{Packet, PacketParseError};
+
+/// How many packets are in flight from one end to the other before they're dropped, as a full socket buffer would
+const CAPACITY: usize = 8192;
+
+/// One end of an in-process transport, for connecting two sockets without UDP
+///
+/// What one end sends arrives at the other, whatever address it's sent to, from the address of the sending end. Like
+/// UDP, packets are dropped rather than waited on when the other end falls behind, and nothing is delivered once it's
+/// dropped. Use each end in place of the UDP socket, with
+/// [`connect_with_sock`](crate::SrtSocketBuilder::connect_with_sock), for tests, benchmarks and examples that
+/// shouldn't depend on free ports.
+///
+/// ```
+/// use bytes::Bytes;
+/// use futures::prelude::*;
+/// use srt_tokio::{Loopback, SrtSocketBuilder};
+/// use std::time::Instant;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let (listen_end, call_end) = Loopback::pair();
+/// let listener = SrtSocketBuilder::new_listen().connect_with_sock(listen_end);
+/// let caller = SrtSocketBuilder::new_connect(call_end.peer_addr()).connect_with_sock(call_end);
+/// let (mut listener, mut caller) = futures::try_join!(listener, caller)?;
+///
+/// caller.send((Instant::now(), Bytes::from("hello"))).await?;
+/// caller.close().await?;
+/// assert_eq!(listener.try_next().await?.unwrap().1, "hello");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Loopback {
+    local: SocketAddr,
+    peer: SocketAddr,
+    sender: mpsc::Sender<(Packet, SocketAddr)>,
+    recvr: mpsc::Receiver<(Packet, SocketAddr)>,
+}
+
+impl Loopback {
+    /// Two connected ends, at `127.0.0.1:1` and `127.0.0.1:2`
+    pub fn pair() -> (Loopback, Loopback) {
+        Self::pair_with_addrs(([127, 0, 0, 1], 1).into(), ([127, 0, 0, 1], 2).into())
+    }
+
+    /// Two connected ends, at `a` and `b`
+    pub fn pair_with_addrs(a: SocketAddr, b: SocketAddr) -> (Loopback, Loopback) {
+        let (a_send, b_recv) = mpsc::channel(CAPACITY);
+        let (b_send, a_recv) = mpsc::channel(CAPACITY);
+        (
+            Loopback {
+                local: a,
+                peer: b,
+                sender: a_send,
+                recvr: a_recv,
+            },
+            Loopback {
+                local: b,
+                peer: a,
+                sender: b_send,
+                recvr: b_recv,
+            },
+        )
+    }
+
+    /// The address of this end, where the other end's packets come from
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    /// The address of the other end, to connect to
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer
+    }
+}
+
+impl Stream for Loopback {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.recvr)
+            .poll_next(cx)
+            .map(|item| item.map(Ok))
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for Loopback {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (pack, _): (Packet, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        let from = self.local;
+        match self.sender.try_send((pack, from)) {
+            Err(e) if e.is_disconnected() => {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))
+            }
+            Err(_) => {
+                trace!("Loopback to {} full, dropping packet", self.peer);
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_close(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
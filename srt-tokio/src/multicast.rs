@@ -0,0 +1,170 @@
+//! Experimental multicast-assisted distribution, for receivers on the same LAN as the sender
+//!
+//! A [`MulticastSender`] sends the data of a connection to a multicast group rather than to the peer, while
+//! handshakes, control packets and retransmissions still go to the peer's unicast address. A [`MulticastReceiver`]
+//! joins the group and receives the data from it alongside its unicast socket, so whatever the group loses is
+//! reported and retransmitted over unicast as it would be on any connection. Both are used in place of the UDP
+//! socket, with [`connect_with_sock`](crate::SrtSocketBuilder::connect_with_sock).
+//!
+//! Each packet on the group is still addressed to the socket id of one receiver, so a group carries one connection,
+//! other receivers that joined it ignore what isn't theirs. Only IPv4 groups are supported.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::prelude::*;
+use futures::ready;
+use log::info;
+use tokio::net::UdpSocket;
+use tokio_util::udp::UdpFramed;
+
+use crate::packet::{ControlTypes, DataPacket};
+use crate::{ControlPacket, Packet, PacketCodec, PacketParseError, SocketID};
+
+fn group_addr(group: SocketAddr) -> Result<Ipv4Addr, io::Error> {
+    match group {
+        SocketAddr::V4(group) if group.ip().is_multicast() => Ok(*group.ip()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not an IPv4 multicast group", group),
+        )),
+    }
+}
+
+/// A socket sending the original transmission of data to a multicast group, see the [module documentation](self)
+pub struct MulticastSender {
+    sock: UdpFramed<PacketCodec>,
+    group: SocketAddr,
+}
+
+impl MulticastSender {
+    /// Bind to `local`, to send data to `group`, reaching as far as `ttl` routers
+    pub async fn bind(local: SocketAddr, group: SocketAddr, ttl: u32) -> Result<Self, io::Error> {
+        group_addr(group)?;
+        let sock = UdpSocket::bind(local).await?;
+        sock.set_multicast_ttl_v4(ttl)?;
+        info!("Sending data from {} to {}", sock.local_addr()?, group);
+        Ok(MulticastSender {
+            sock: UdpFramed::new(sock, PacketCodec),
+            group,
+        })
+    }
+}
+
+impl Stream for MulticastSender {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.sock).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for MulticastSender {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_ready(cx)
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (pack, to): (Packet, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        // retransmissions are only for the peer that asked for them
+        let to = match &pack {
+            Packet::Data(DataPacket {
+                retransmitted: false,
+                ..
+            }) => self.group,
+            _ => to,
+        };
+        Pin::new(&mut self.sock).start_send((pack, to))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_close(cx)
+    }
+}
+
+/// A socket receiving data from a multicast group as well as from the peer, see the [module documentation](self)
+pub struct MulticastReceiver {
+    sock: UdpFramed<PacketCodec>,
+    group: UdpFramed<PacketCodec>,
+    // learned from the handshake, to pick our packets out of the group
+    local_sockid: Option<SocketID>,
+    // where the packets from the group are said to come from, wherever the sender sent them from
+    peer: Option<SocketAddr>,
+}
+
+impl MulticastReceiver {
+    /// Bind to `local`, and join `group` on the interface of `local`, or the default one if unspecified
+    pub async fn bind(local: SocketAddr, group: SocketAddr) -> Result<Self, io::Error> {
+        let group_ip = group_addr(group)?;
+        let interface = match local {
+            SocketAddr::V4(local) => *local.ip(),
+            SocketAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        };
+        let sock = UdpSocket::bind(local).await?;
+        let group_sock = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, group.port())).await?;
+        group_sock.join_multicast_v4(group_ip, interface)?;
+        info!("Joined {} on {}", group, interface);
+        Ok(MulticastReceiver {
+            sock: UdpFramed::new(sock, PacketCodec),
+            group: UdpFramed::new(group_sock, PacketCodec),
+            local_sockid: None,
+            peer: None,
+        })
+    }
+}
+
+impl Stream for MulticastReceiver {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if let Poll::Ready(item) = Pin::new(&mut self.sock).poll_next(cx) {
+            return Poll::Ready(item);
+        }
+        loop {
+            match ready!(Pin::new(&mut self.group).poll_next(cx)) {
+                Some(Ok((Packet::Data(data), _)))
+                    if Some(data.dest_sockid) == self.local_sockid =>
+                {
+                    if let Some(peer) = self.peer {
+                        return Poll::Ready(Some(Ok((Packet::Data(data), peer))));
+                    }
+                }
+                // for other receivers, or not meant for the group
+                Some(_) => {}
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for MulticastReceiver {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_ready(cx)
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: (Packet, SocketAddr)) -> Result<(), Self::Error> {
+        if let Packet::Control(ControlPacket {
+            control_type: ControlTypes::Handshake(shake),
+            ..
+        }) = &item.0
+        {
+            self.local_sockid = Some(shake.socket_id);
+        }
+        self.peer = Some(item.1);
+        Pin::new(&mut self.sock).start_send(item)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sock).poll_close(cx)
+    }
+}
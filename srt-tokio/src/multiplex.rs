@@ -1,35 +1,61 @@
+mod egress;
+mod registry;
+mod sharded;
 mod streamer_server;
 
+pub use self::egress::{EgressStats, MultiplexStats};
+pub use self::registry::ConnectionHandle;
+pub use self::sharded::ShardedMultiplexer;
 pub use self::streamer_server::StreamerServer;
 
 use std::collections::HashMap;
 use std::io;
 use std::net::SocketAddr;
 
-use futures::future::{pending, select_all};
+use futures::channel::mpsc;
+use futures::future::{poll_fn, BoxFuture};
 use futures::prelude::*;
 use futures::select;
-use futures::stream::unfold;
+use futures::stream::{unfold, FuturesUnordered};
 
-use log::warn;
+use log::{debug, info, warn};
 use tokio::net::UdpSocket;
 use tokio_util::udp::UdpFramed;
 
+use self::egress::{Egress, EgressScheduler};
+use self::registry::CloseRequest;
 use crate::channel::Channel;
-use crate::protocol::handshake::Handshake;
-use crate::{Connection, Packet, PacketCodec, SocketID};
+use crate::packet::{ControlTypes, RejectReason, ShakeType};
+use crate::pending_connection::authenticate;
+use crate::protocol::{handshake::Handshake, TimeStamp};
+use crate::util::{is_transient, PacketBudget};
+use crate::{
+    Connection, ConnectionSettings, ControlPacket, Packet, PacketCodec, PacketParseError, SocketID,
+};
 use srt_protocol::pending_connection::{
     listen::{Listen, ListenState},
     ConnInitSettings,
 };
+use srt_protocol::SeqNumber;
 
 pub type PackChan = Channel<(Packet, SocketAddr)>;
 
-struct MultiplexState {
-    sock: UdpFramed<PacketCodec>,
+// a conclusion, by the caller's address, initial sequence number and cookie
+type ConclusionKey = (SocketAddr, SeqNumber, i32);
+
+struct MultiplexState<T> {
+    sock: T,
     pending: HashMap<SocketAddr, Listen>,
+    // handshakes done, waiting for the authenticator to decide
+    authenticating: HashMap<SocketAddr, Listen>,
+    auths: FuturesUnordered<BoxFuture<'static, (SocketAddr, Result<(), RejectReason>)>>,
     conns: HashMap<SocketID, PackChan>,
+    // the connections by the conclusion they were accepted with, so one sent again is answered by its connection
+    accepted: HashMap<ConclusionKey, SocketID>,
+    egress: EgressScheduler,
     init_settings: ConnInitSettings,
+    closes: mpsc::UnboundedReceiver<CloseRequest>,
+    close_requests: mpsc::UnboundedSender<CloseRequest>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -37,73 +63,153 @@ enum Action {
     Delegate(Packet, SocketAddr),
     Remove(SocketID),
     Send((Packet, SocketAddr)),
+    Close(CloseRequest),
+    Authenticated(SocketAddr, Result<(), RejectReason>),
+    Nothing,
 }
 
-impl MultiplexState {
+impl<T> MultiplexState<T>
+where
+    T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>>
+        + Sink<(Packet, SocketAddr), Error = io::Error>
+        + Unpin,
+{
     async fn next_conn(&mut self) -> Result<Option<(Connection, PackChan)>, io::Error> {
+        let mut budget = PacketBudget::new(self.init_settings.packet_budget);
         loop {
-            // impl Future<Output = (Packet, SocketAddr)
-            let conns = &mut self.conns;
-            let joined = async {
-                // select_all panics if there are no elements, but pending is the correct behavior
-                if conns.is_empty() {
-                    pending().await
-                } else {
-                    select_all(
-                        conns
-                            .iter_mut()
-                            .map(|(sid, chan)| chan.next().map(move |p| (sid, p))),
-                    )
-                    .await
-                }
-            };
+            let (conns, egress) = (&mut self.conns, &mut self.egress);
+            let joined = poll_fn(|cx| egress.poll_next(conns, cx));
             let action = select! {
                 new_pack = self.sock.next().fuse() => {
                     match new_pack {
                         None => return Ok(None),
+                        Some(Err(e)) if is_transient(&e) => {
+                            // on Windows, an earlier send reached a closed port, the others are fine
+                            debug!("Ignoring receive error: {}", e);
+                            Action::Nothing
+                        }
                         Some(Err(e)) => return Err(io::Error::from(e)),
                         Some(Ok((pack, from))) => {
                             Action::Delegate(pack, from)
                         }
                     }
                 },
-                ((sockid, pack), _, _) = joined.fuse() => {
-                    match pack {
-                        None  => { Action::Remove(*sockid) }
-                        Some(pack) => { Action::Send(pack)  }
+                egress = joined.fuse() => {
+                    match egress {
+                        Egress::Closed(sockid) => { Action::Remove(sockid) }
+                        Egress::Send(pack) => { Action::Send(pack)  }
+                    }
+                },
+                close = self.closes.next() => {
+                    match close {
+                        Some(close) => Action::Close(close),
+                        None => Action::Nothing,
+                    }
+                },
+                auth = self.auths.next() => {
+                    match auth {
+                        Some((from, result)) => Action::Authenticated(from, result),
+                        None => Action::Nothing,
                     }
                 },
             };
 
+            // don't starve other tasks if packets keep coming in
+            budget.spend().await;
+
             match action {
                 Action::Delegate(pack, from) => {
                     if let Some(complete) = self.delegate_packet(pack, from).await? {
                         return Ok(Some(complete));
                     }
                 }
-                Action::Remove(sockid) => {
-                    self.conns.remove(&sockid);
-                }
+                Action::Remove(sockid) => self.remove(sockid),
                 Action::Send(pack) => {
-                    self.sock.send(pack).await?;
+                    // feed whatever else is ready too, so a socket that batches sends it together
+                    self.sock.feed(pack).await?;
+                    let (conns, egress) = (&mut self.conns, &mut self.egress);
+                    let accepted = &mut self.accepted;
+                    while let Some(next) = poll_fn(|cx| egress.poll_next(conns, cx)).now_or_never()
+                    {
+                        match next {
+                            Egress::Send(pack) => self.sock.feed(pack).await?,
+                            Egress::Closed(sockid) => {
+                                conns.remove(&sockid);
+                                egress.remove(sockid);
+                                accepted.retain(|_, id| *id != sockid);
+                            }
+                        }
+                    }
+                    self.sock.flush().await?;
                 }
+                Action::Close(close) => self.close(close).await?,
+                Action::Authenticated(from, result) => {
+                    if let Some(complete) = self.authenticated(from, result).await? {
+                        return Ok(Some(complete));
+                    }
+                }
+                Action::Nothing => {}
             }
         }
     }
 
+    fn remove(&mut self, sockid: SocketID) {
+        self.conns.remove(&sockid);
+        self.egress.remove(sockid);
+        self.accepted.retain(|_, id| *id != sockid);
+    }
+
+    async fn close(&mut self, close: CloseRequest) -> Result<(), io::Error> {
+        let chan = match self.conns.get_mut(&close.local_sockid) {
+            Some(chan) => chan,
+            None => return Ok(()),
+        };
+        info!(
+            "Closing {:?} from {}: {:?}",
+            close.local_sockid, close.peer, close.reason
+        );
+        let shutdown = |dest_sockid| {
+            Packet::Control(ControlPacket {
+                timestamp: TimeStamp::from_micros(0),
+                dest_sockid,
+                control_type: ControlTypes::Shutdown,
+            })
+        };
+        // the connection closes as if the peer had, and is removed once it has
+        if chan
+            .send((shutdown(close.local_sockid), close.peer))
+            .await
+            .is_err()
+        {
+            self.remove(close.local_sockid);
+        }
+        self.sock
+            .send((shutdown(close.remote_sockid), close.peer))
+            .await
+    }
+
     async fn delegate_packet(
         &mut self,
         pack: Packet,
         from: SocketAddr,
     ) -> Result<Option<(Connection, PackChan)>, io::Error> {
-        // fast path--an already established connection
-        if let Some(chan) = self.conns.get_mut(&pack.dest_sockid()) {
-            let dst_sockid = pack.dest_sockid();
+        // fast path--an already established connection, or the conclusion it was accepted with sent again, which
+        // the connection answers like the first time
+        let dst_sockid =
+            match Self::conclusion_key(&pack, from).and_then(|key| self.accepted.get(&key)) {
+                Some(sockid) => *sockid,
+                None => pack.dest_sockid(),
+            };
+        if let Some(chan) = self.conns.get_mut(&dst_sockid) {
             if let Err(_send_err) = chan.send((pack, from)).await {
-                self.conns.remove(&dst_sockid);
+                self.remove(dst_sockid);
             }
             return Ok(None);
         }
+        // the conclusion sent again, while the authenticator decides
+        if self.authenticating.contains_key(&from) {
+            return Ok(None);
+        }
 
         // new connection?
         let this_conn_settings = self.init_settings.clone();
@@ -113,24 +219,102 @@ impl MultiplexState {
             .or_insert_with(|| Listen::new(this_conn_settings.copy_randomize()));
 
         // already started connection?
-        match listen.handle_packet((pack, from)) {
-            Ok(Some(pa)) => self.sock.send(pa).await?,
-            Err(e) => warn!("{:?}", e),
-            _ => {}
-        }
+        let response = match listen.handle_packet((pack, from)) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("{:?}", e);
+                None
+            }
+        };
         if let ListenState::Connected(resp_handshake, settings) = listen.state().clone() {
-            let (s, r) = Channel::channel(100);
+            // remove from pending connections, it's been resolved
+            let listen = self.pending.remove(&from).unwrap();
+            if self.init_settings.authenticator.is_some() {
+                let init_settings = self.init_settings.clone();
+                self.auths.push(
+                    async move { (from, authenticate(&init_settings, &settings).await) }.boxed(),
+                );
+                self.authenticating.insert(from, listen);
+                return Ok(None);
+            }
+            if let Some(pa) = response {
+                self.sock.send(pa).await?;
+            }
+            return Ok(Some(self.accept(resp_handshake, settings)));
+        }
+        if let Some(pa) = response {
+            self.sock.send(pa).await?;
+        }
+        Ok(None)
+    }
 
-            self.conns.insert(settings.local_sockid, r);
+    async fn authenticated(
+        &mut self,
+        from: SocketAddr,
+        result: Result<(), RejectReason>,
+    ) -> Result<Option<(Connection, PackChan)>, io::Error> {
+        let listen = match self.authenticating.remove(&from) {
+            Some(listen) => listen,
+            None => return Ok(None),
+        };
+        if let Err(reason) = result {
+            warn!("Rejecting connection from {}: {:?}", from, reason);
+            if let Some(rejection) = listen.reject(reason) {
+                self.sock.send(rejection).await?;
+            }
+            return Ok(None);
+        }
+        match listen.state().clone() {
+            ListenState::Connected(resp_handshake, settings) => {
+                self.sock
+                    .send((Packet::Control(resp_handshake.clone()), from))
+                    .await?;
+                Ok(Some(self.accept(resp_handshake, settings)))
+            }
+            _ => Ok(None),
+        }
+    }
 
-            let conn = Connection {
-                settings,
-                handshake: Handshake::Listener(resp_handshake.control_type),
-            };
-            self.pending.remove(&from); // remove from pending connections, it's been resolved
-            return Ok(Some((conn, s)));
+    fn accept(
+        &mut self,
+        resp_handshake: ControlPacket,
+        settings: ConnectionSettings,
+    ) -> (Connection, PackChan) {
+        let (s, r) = Channel::channel(100);
+
+        if let ControlTypes::Handshake(shake) = &resp_handshake.control_type {
+            self.accepted.insert(
+                (
+                    settings.remote,
+                    settings.init_recv_seq_num,
+                    shake.syn_cookie,
+                ),
+                settings.local_sockid,
+            );
+        }
+        self.conns.insert(settings.local_sockid, r);
+        self.egress.add(ConnectionHandle::new(
+            &settings,
+            self.close_requests.clone(),
+        ));
+
+        let conn = Connection {
+            settings,
+            handshake: Handshake::Listener(resp_handshake.control_type),
+        };
+        (conn, s)
+    }
+
+    fn conclusion_key(pack: &Packet, from: SocketAddr) -> Option<ConclusionKey> {
+        match pack {
+            Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(shake),
+                ..
+            }) if shake.shake_type == ShakeType::Conclusion => {
+                Some((from, shake.init_seq_num, shake.syn_cookie))
+            }
+            _ => None,
         }
-        Ok(None)
     }
 }
 
@@ -138,12 +322,43 @@ pub async fn multiplex(
     addr: SocketAddr,
     init_settings: ConnInitSettings,
 ) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
-    Ok(unfold(
+    multiplex_with_stats(addr, init_settings, MultiplexStats::default()).await
+}
+
+/// Like [`multiplex`], keeping the egress statistics of every connection in `stats`
+pub async fn multiplex_with_stats(
+    addr: SocketAddr,
+    init_settings: ConnInitSettings,
+    stats: MultiplexStats,
+) -> Result<impl Stream<Item = Result<(Connection, PackChan), io::Error>>, io::Error> {
+    let sock = UdpFramed::new(UdpSocket::bind(addr).await?, PacketCodec);
+    Ok(multiplex_with_sock(sock, init_settings, stats))
+}
+
+/// Like [`multiplex_with_stats`], over a custom socket instead of a UDP socket, such as an [`Aggregator`](crate::Aggregator)
+pub fn multiplex_with_sock<T>(
+    sock: T,
+    init_settings: ConnInitSettings,
+    stats: MultiplexStats,
+) -> impl Stream<Item = Result<(Connection, PackChan), io::Error>>
+where
+    T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>>
+        + Sink<(Packet, SocketAddr), Error = io::Error>
+        + Unpin,
+{
+    let (close_requests, closes) = mpsc::unbounded();
+    unfold(
         MultiplexState {
-            sock: UdpFramed::new(UdpSocket::bind(addr).await?, PacketCodec),
+            sock,
             pending: HashMap::new(),
+            authenticating: HashMap::new(),
+            auths: FuturesUnordered::new(),
             conns: HashMap::new(),
+            accepted: HashMap::new(),
+            egress: EgressScheduler::new(stats),
             init_settings,
+            closes,
+            close_requests,
         },
         |mut state| async move {
             match state.next_conn().await {
@@ -152,5 +367,5 @@ pub async fn multiplex(
                 Ok(None) => None,
             }
         },
-    ))
+    )
 }
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::StreamExt;
+
+use super::ConnectionHandle;
+use crate::{PackChan, Packet, SocketID};
+
+/// How many packets are taken from a connection ahead of sending them
+const QUEUE_LEN: usize = 64;
+
+/// Egress queue statistics of one connection of a multiplexer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EgressStats {
+    /// Packets waiting for their turn to be sent
+    pub queued: usize,
+
+    /// The most packets that have been waiting at once
+    pub max_queued: usize,
+
+    /// Packets sent on the socket
+    pub sent_packets: u64,
+
+    /// Payload bytes of the data packets sent
+    pub sent_payload_bytes: u64,
+}
+
+/// A handle to the live connections of a multiplexer and their egress statistics, see
+/// [`SrtSocketBuilder::build_multiplexed_with_stats`](crate::SrtSocketBuilder::build_multiplexed_with_stats)
+///
+/// Connections are removed once they're closed.
+#[derive(Debug, Clone, Default)]
+pub struct MultiplexStats(Arc<Mutex<HashMap<SocketID, ConnectionHandle>>>);
+
+impl MultiplexStats {
+    /// The statistics of the connection with local socket id `sockid`
+    pub fn egress(&self, sockid: SocketID) -> Option<EgressStats> {
+        self.0.lock().unwrap().get(&sockid).map(|conn| conn.egress)
+    }
+
+    /// The statistics of every connection, by their local socket id
+    pub fn all_egress(&self) -> Vec<(SocketID, EgressStats)> {
+        self.connections()
+            .into_iter()
+            .map(|conn| (conn.local_sockid, conn.egress))
+            .collect()
+    }
+
+    /// The connection with local socket id `sockid`, if it's still open
+    pub fn connection(&self, sockid: SocketID) -> Option<ConnectionHandle> {
+        self.0.lock().unwrap().get(&sockid).cloned()
+    }
+
+    /// Every open connection, sorted by local socket id, for an admin endpoint listing who is connected
+    pub fn connections(&self) -> Vec<ConnectionHandle> {
+        let mut all: Vec<_> = self.0.lock().unwrap().values().cloned().collect();
+        all.sort_by_key(|conn| conn.local_sockid.0);
+        all
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum Egress {
+    Send((Packet, SocketAddr)),
+    Closed(SocketID),
+}
+
+#[derive(Default)]
+struct EgressQueue {
+    control: VecDeque<(Packet, SocketAddr)>,
+    data: VecDeque<(Packet, SocketAddr)>,
+    closed: bool,
+}
+
+impl EgressQueue {
+    fn len(&self) -> usize {
+        self.control.len() + self.data.len()
+    }
+
+    fn push(&mut self, pack: (Packet, SocketAddr)) {
+        match pack.0 {
+            Packet::Control(_) => self.control.push_back(pack),
+            Packet::Data(_) => self.data.push_back(pack),
+        }
+    }
+}
+
+/// Takes turns sending a packet from each connection with some queued, so a connection that always has packets to
+/// send can't starve the others
+///
+/// Control packets have strict priority over data: any connection's ACKs, NAKs and handshakes are sent before the
+/// next data packet, so they don't wait behind a burst and inflate the RTT the peers measure.
+#[derive(Default)]
+pub struct EgressScheduler {
+    order: Vec<SocketID>,
+    next: usize,
+    queues: HashMap<SocketID, EgressQueue>,
+    stats: MultiplexStats,
+}
+
+impl EgressScheduler {
+    pub fn new(stats: MultiplexStats) -> Self {
+        EgressScheduler {
+            stats,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, handle: ConnectionHandle) {
+        let sockid = handle.local_sockid;
+        self.order.push(sockid);
+        self.queues.insert(sockid, EgressQueue::default());
+        self.stats.0.lock().unwrap().insert(sockid, handle);
+    }
+
+    pub fn remove(&mut self, sockid: SocketID) {
+        if let Some(idx) = self.order.iter().position(|s| *s == sockid) {
+            self.order.remove(idx);
+            if idx < self.next {
+                self.next -= 1;
+            }
+        }
+        self.queues.remove(&sockid);
+        self.stats.0.lock().unwrap().remove(&sockid);
+    }
+
+    /// The next packet to send, from the connection after the last one that sent
+    pub fn poll_next(
+        &mut self,
+        conns: &mut HashMap<SocketID, PackChan>,
+        cx: &mut Context,
+    ) -> Poll<Egress> {
+        let mut stats = self.stats.0.lock().unwrap();
+
+        for (sockid, queue) in &mut self.queues {
+            let chan = match conns.get_mut(sockid) {
+                Some(chan) => chan,
+                None => {
+                    queue.closed = true;
+                    continue;
+                }
+            };
+            while !queue.closed && queue.len() < QUEUE_LEN {
+                match chan.poll_next_unpin(cx) {
+                    Poll::Ready(Some(pack)) => queue.push(pack),
+                    Poll::Ready(None) => queue.closed = true,
+                    Poll::Pending => break,
+                }
+            }
+            if let Some(stats) = stats.get_mut(sockid).map(|conn| &mut conn.egress) {
+                stats.queued = queue.len();
+                stats.max_queued = stats.max_queued.max(stats.queued);
+            }
+        }
+
+        let len = self.order.len();
+        for i in 0..len * 2 {
+            let idx = (self.next + i) % len;
+            let sockid = self.order[idx];
+            let queue = self.queues.get_mut(&sockid).unwrap();
+            // the first round only looks for control packets
+            let pack = if i < len {
+                queue.control.pop_front()
+            } else {
+                queue.data.pop_front()
+            };
+            if let Some(pack) = pack {
+                self.next = (idx + 1) % len;
+                if let Some(stats) = stats.get_mut(&sockid).map(|conn| &mut conn.egress) {
+                    stats.queued = queue.len();
+                    stats.sent_packets += 1;
+                    if let Packet::Data(data) = &pack.0 {
+                        stats.sent_payload_bytes += data.payload.len() as u64;
+                    }
+                }
+                return Poll::Ready(Egress::Send(pack));
+            }
+        }
+
+        // only once everything they queued is sent
+        match self.order.iter().find(|s| self.queues[s].closed) {
+            Some(sockid) => Poll::Ready(Egress::Closed(*sockid)),
+            None => Poll::Pending,
+        }
+    }
+}
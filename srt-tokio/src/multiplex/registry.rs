@@ -0,0 +1,79 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use futures::channel::mpsc;
+
+use super::EgressStats;
+use crate::packet::RejectReason;
+use crate::{ConnectionSettings, RateLimit, RateLimitControl, SocketID};
+
+/// Asks the multiplexer to close a connection, see [`ConnectionHandle::close`]
+#[derive(Debug)]
+pub(crate) struct CloseRequest {
+    pub local_sockid: SocketID,
+    pub remote_sockid: SocketID,
+    pub peer: SocketAddr,
+    pub reason: RejectReason,
+}
+
+/// A live connection of a multiplexer, see [`MultiplexStats::connections`](super::MultiplexStats::connections)
+#[derive(Debug, Clone)]
+pub struct ConnectionHandle {
+    /// The socket id of this side of the connection, which the multiplexer tells its connections apart by
+    pub local_sockid: SocketID,
+
+    /// The socket id of the peer
+    pub remote_sockid: SocketID,
+
+    /// Where the peer connected from
+    pub peer: SocketAddr,
+
+    /// The stream id the peer sent, if any, see [`ConnectionInfo::stream_id`](crate::ConnectionInfo::stream_id)
+    pub stream_id: Option<String>,
+
+    /// When the handshake completed
+    pub connected_at: Instant,
+
+    /// How the connection's packets are sent, see [`EgressStats`]
+    pub egress: EgressStats,
+
+    rate_limit: RateLimitControl,
+    closes: mpsc::UnboundedSender<CloseRequest>,
+}
+
+impl ConnectionHandle {
+    pub(crate) fn new(
+        settings: &ConnectionSettings,
+        closes: mpsc::UnboundedSender<CloseRequest>,
+    ) -> Self {
+        ConnectionHandle {
+            local_sockid: settings.local_sockid,
+            remote_sockid: settings.remote_sockid,
+            peer: settings.remote,
+            stream_id: settings.info().stream_id,
+            connected_at: settings.clock.now(),
+            egress: EgressStats::default(),
+            rate_limit: settings.rate_limit_control.clone(),
+            closes,
+        }
+    }
+
+    /// Close the connection for `reason`, such as a stream an ingest server won't take anymore
+    ///
+    /// The peer is sent a shutdown, and the connection's socket ends as if the peer had closed it. SRT has no way to
+    /// tell the peer why once connected, so the reason is only logged. Does nothing if the connection is already
+    /// closed.
+    pub fn close(&self, reason: RejectReason) {
+        let _ = self.closes.unbounded_send(CloseRequest {
+            local_sockid: self.local_sockid,
+            remote_sockid: self.remote_sockid,
+            peer: self.peer,
+            reason,
+        });
+    }
+
+    /// Cap the rate the connection sends data at to `limit`, or lift the cap with `None`, see [`RateLimitControl`]
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.rate_limit.set(limit);
+    }
+}
@@ -0,0 +1,176 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::future::{self, AbortHandle};
+use futures::prelude::*;
+use log::{info, warn};
+use tokio::net::UdpSocket;
+use tokio_util::udp::UdpFramed;
+
+use super::{multiplex_with_sock, MultiplexStats, PackChan};
+use crate::{Connection, ConnectionDriver, PacketCodec, SpawnPolicy};
+use srt_protocol::pending_connection::ConnInitSettings;
+
+/// Several multiplexers listening on the same address, for servers with more connections than one task can serve
+///
+/// Each shard has its own UDP socket bound with `SO_REUSEPORT`, so the kernel spreads the peers over them by hashing
+/// their addresses, and every packet from a peer, handshake included, reaches the same shard. The shards run on tasks
+/// of their own, which a multi-threaded runtime spreads over its worker threads, and serve their connections whether
+/// [`incoming`](ShardedMultiplexer::incoming) is polled or not. Dropping the incoming stream, or the multiplexer
+/// before taking it, stops them all and closes their sockets.
+///
+/// `SO_REUSEPORT` only balances like this on Linux, elsewhere, or without the `reuse-port` feature, there's a single
+/// shard.
+pub struct ShardedMultiplexer {
+    incoming: Incoming,
+    drivers: Vec<ConnectionDriver>,
+    local_addr: SocketAddr,
+    shards: usize,
+}
+
+impl ShardedMultiplexer {
+    /// Bind `shards` multiplexers to `addr`, at least one. With port 0, they all share the port the first one gets.
+    ///
+    /// The shards are spawned with `tokio::spawn`.
+    pub async fn bind(
+        addr: SocketAddr,
+        shards: usize,
+        init_settings: ConnInitSettings,
+        stats: MultiplexStats,
+    ) -> Result<Self, io::Error> {
+        Self::bind_with_spawn_policy(addr, shards, init_settings, stats, &SpawnPolicy::Current)
+            .await
+    }
+
+    /// Like [`bind`](ShardedMultiplexer::bind), with the shards spawned as `spawn_policy` says. With
+    /// [`SpawnPolicy::Manual`], they're taken with [`take_drivers`](ShardedMultiplexer::take_drivers).
+    pub async fn bind_with_spawn_policy(
+        addr: SocketAddr,
+        shards: usize,
+        init_settings: ConnInitSettings,
+        stats: MultiplexStats,
+        spawn_policy: &SpawnPolicy,
+    ) -> Result<Self, io::Error> {
+        let shards = if cfg!(all(feature = "reuse-port", target_os = "linux")) {
+            shards.max(1)
+        } else {
+            if shards > 1 {
+                warn!(
+                    "Sharding the multiplexer needs SO_REUSEPORT load balancing, using one shard"
+                );
+            }
+            1
+        };
+
+        let mut sockets = Vec::with_capacity(shards);
+        let mut local_addr = addr;
+        for _ in 0..shards {
+            let sock = bind_shard(local_addr)?;
+            local_addr = sock.local_addr()?;
+            sockets.push(sock);
+        }
+        info!("Listening on {} with {} shards", local_addr, shards);
+
+        let (tx, connections) = mpsc::unbounded();
+        let mut aborts = Vec::with_capacity(shards);
+        let mut drivers = Vec::new();
+        for sock in sockets {
+            let mut server = multiplex_with_sock(
+                UdpFramed::new(sock, PacketCodec),
+                init_settings.clone(),
+                stats.clone(),
+            )
+            .boxed();
+            let tx = tx.clone();
+            let (shard, abort) = future::abortable(async move {
+                while let Some(conn) = server.next().await {
+                    if tx.unbounded_send(conn).is_err() {
+                        // nobody is listening anymore
+                        break;
+                    }
+                }
+            });
+            aborts.push(abort);
+            drivers.extend(spawn_policy.spawn(shard.map(|_| ()).boxed()));
+        }
+
+        Ok(ShardedMultiplexer {
+            incoming: Incoming {
+                connections,
+                aborts,
+            },
+            drivers,
+            local_addr,
+            shards,
+        })
+    }
+
+    /// The address the shards are bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The number of shards
+    pub fn shards(&self) -> usize {
+        self.shards
+    }
+
+    /// The tasks running the shards, when bound with [`SpawnPolicy::Manual`]. Nothing is served until they run.
+    ///
+    /// Empty if they were spawned, or already taken.
+    pub fn take_drivers(&mut self) -> Vec<ConnectionDriver> {
+        std::mem::take(&mut self.drivers)
+    }
+
+    /// The connections of every shard, in the order they complete their handshake
+    pub fn incoming(self) -> impl Stream<Item = Result<(Connection, PackChan), io::Error>> {
+        self.incoming
+    }
+}
+
+// the connections of every shard, stopping the shards when dropped, since with the shards holding senders the
+// channel alone can't tell them nobody is listening until they have a connection to send
+struct Incoming {
+    connections: mpsc::UnboundedReceiver<Result<(Connection, PackChan), io::Error>>,
+    aborts: Vec<AbortHandle>,
+}
+
+impl Stream for Incoming {
+    type Item = Result<(Connection, PackChan), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.connections).poll_next(cx)
+    }
+}
+
+impl Drop for Incoming {
+    fn drop(&mut self) {
+        for abort in &self.aborts {
+            abort.abort();
+        }
+    }
+}
+
+#[cfg(all(feature = "reuse-port", target_os = "linux"))]
+fn bind_shard(addr: SocketAddr) -> Result<UdpSocket, io::Error> {
+    use net2::unix::UnixUdpBuilderExt;
+
+    let builder = if addr.is_ipv4() {
+        net2::UdpBuilder::new_v4()?
+    } else {
+        net2::UdpBuilder::new_v6()?
+    };
+    let sock = builder.reuse_port(true)?.bind(addr)?;
+    sock.set_nonblocking(true)?;
+    UdpSocket::from_std(sock)
+}
+
+#[cfg(not(all(feature = "reuse-port", target_os = "linux")))]
+fn bind_shard(addr: SocketAddr) -> Result<UdpSocket, io::Error> {
+    let sock = std::net::UdpSocket::bind(addr)?;
+    sock.set_nonblocking(true)?;
+    UdpSocket::from_std(sock)
+}
@@ -0,0 +1,226 @@
+//! Experimental multiplexing of several logical streams over one connection
+//!
+//! Each message sent through a [`SubStream`] starts with a small header: the id of the stream it belongs to, and its
+//! sequence number within that stream, both big endian `u32`s. The connection still delivers the messages in one
+//! order, the receiving [`MultiStream`] hands each to the stream it was sent on, so both peers need to use one.
+//!
+//! Streams are opened by id on each side, there's no negotiation. Messages for a stream that isn't open yet are kept,
+//! up to [`STREAM_BUFFER`] of them, until it is. As the connection drops messages that are too late, a stream can
+//! skip messages, which shows as a gap in its sequence numbers and is counted in
+//! [`lost_messages`](MultiStream::lost_messages).
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::channel::mpsc;
+use futures::prelude::*;
+use log::{info, warn};
+
+use crate::{ConnectionSettings, SocketStatistics, SrtSendHalf, SrtSender, SrtSocket};
+
+/// The length of the header in front of each message, the stream id and the sequence number
+pub const HEADER_LEN: usize = 8;
+
+/// How many messages are kept for each stream that isn't open, or isn't read fast enough
+pub const STREAM_BUFFER: usize = 1024;
+
+#[derive(Default)]
+struct Demux {
+    open: HashMap<u32, mpsc::Sender<(Instant, Bytes)>>,
+    pending: HashMap<u32, VecDeque<(Instant, Bytes)>>,
+    next_seq: HashMap<u32, u32>,
+    lost_messages: u64,
+    closed: bool,
+}
+
+impl Demux {
+    fn route(&mut self, time: Instant, mut data: Bytes) {
+        if data.len() < HEADER_LEN {
+            warn!(
+                "Message of {} bytes is too short for a stream header",
+                data.len()
+            );
+            self.lost_messages += 1;
+            return;
+        }
+        let id = data.get_u32();
+        let seq = data.get_u32();
+
+        let next = self.next_seq.entry(id).or_insert(seq);
+        let gap = seq.wrapping_sub(*next);
+        // a stream the peer opened again starts over, that isn't a loss
+        if gap < 1 << 31 {
+            self.lost_messages += u64::from(gap);
+        }
+        *next = seq.wrapping_add(1);
+
+        if let Some(stream) = self.open.get_mut(&id) {
+            if stream.try_send((time, data)).is_err() {
+                self.lost_messages += 1;
+            }
+        } else {
+            self.buffer(id, (time, data));
+        }
+    }
+
+    fn buffer(&mut self, id: u32, item: (Instant, Bytes)) {
+        let pending = self.pending.entry(id).or_default();
+        if pending.len() == STREAM_BUFFER {
+            pending.pop_front();
+            self.lost_messages += 1;
+        }
+        pending.push_back(item);
+    }
+}
+
+/// A connection carrying several logical streams, see the [module documentation](self)
+///
+/// Closing or dropping it closes the connection, which ends every stream.
+pub struct MultiStream {
+    send: SrtSendHalf,
+    demux: Arc<Mutex<Demux>>,
+}
+
+/// One logical stream of a [`MultiStream`], created with [`MultiStream::open_stream`]
+///
+/// Receives the messages the peer sent on the stream with the same id, and sends on it, with the timestamps of the
+/// connection's `Stream` and `Sink` implementations.
+pub struct SubStream {
+    id: u32,
+    next_seq: u32,
+    sender: SrtSender,
+    recvr: mpsc::Receiver<(Instant, Bytes)>,
+    demux: Arc<Mutex<Demux>>,
+}
+
+impl MultiStream {
+    /// Start sorting the messages received on `socket` into streams, on a task of its own
+    pub fn new(socket: SrtSocket) -> Self {
+        let (send, mut recv) = socket.split();
+        let demux = Arc::new(Mutex::new(Demux::default()));
+
+        let task_demux = demux.clone();
+        tokio::spawn(async move {
+            loop {
+                match recv.next().await {
+                    Some(Ok((time, data))) => task_demux.lock().unwrap().route(time, data),
+                    Some(Err(e)) => {
+                        warn!("Error receiving streams: {}", e);
+                        break;
+                    }
+                    None => break,
+                }
+            }
+            info!("Connection closed, ending its streams");
+            let mut demux = task_demux.lock().unwrap();
+            demux.closed = true;
+            demux.open.clear();
+        });
+
+        MultiStream { send, demux }
+    }
+
+    /// Open the stream with id `id`, getting the messages already received for it
+    ///
+    /// Fails if the stream is already open, it can be opened again once its [`SubStream`] has been dropped.
+    pub fn open_stream(&self, id: u32) -> Result<SubStream, io::Error> {
+        let mut demux = self.demux.lock().unwrap();
+        if demux.open.contains_key(&id) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("stream {} is already open", id),
+            ));
+        }
+
+        let (mut tx, recvr) = mpsc::channel(STREAM_BUFFER);
+        for item in demux.pending.remove(&id).unwrap_or_default() {
+            // fits, the channel holds as many as are kept
+            let _ = tx.try_send(item);
+        }
+        if !demux.closed {
+            demux.open.insert(id, tx);
+        }
+
+        Ok(SubStream {
+            id,
+            next_seq: 0,
+            sender: self.send.sender(),
+            recvr,
+            demux: self.demux.clone(),
+        })
+    }
+
+    /// The number of messages lost over every stream, skipped by the connection or dropped to keep up
+    pub fn lost_messages(&self) -> u64 {
+        self.demux.lock().unwrap().lost_messages
+    }
+
+    pub fn settings(&self) -> &ConnectionSettings {
+        self.send.settings()
+    }
+
+    /// A snapshot of the connection's statistics, see [`SrtSocket::stats`]
+    pub fn stats(&self) -> SocketStatistics {
+        self.send.stats()
+    }
+
+    /// Close the connection, once the peer has acknowledged what every stream sent, see [`SrtSocket::close`]
+    pub async fn close(&mut self) -> Result<u64, io::Error> {
+        self.send.close().await
+    }
+}
+
+impl SubStream {
+    /// The id of this stream
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Drop for SubStream {
+    fn drop(&mut self) {
+        // later messages are kept for when it's opened again
+        self.demux.lock().unwrap().open.remove(&self.id);
+    }
+}
+
+impl Stream for SubStream {
+    type Item = Result<(Instant, Bytes), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.recvr)
+            .poll_next(cx)
+            .map(|item| item.map(Ok))
+    }
+}
+
+impl Sink<(Instant, Bytes)> for SubStream {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender).poll_ready(cx)
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (time, payload): (Instant, Bytes),
+    ) -> Result<(), Self::Error> {
+        let mut data = BytesMut::with_capacity(HEADER_LEN + payload.len());
+        data.put_u32(self.id);
+        data.put_u32(self.next_seq);
+        data.put(payload);
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Pin::new(&mut self.sender).start_send((time, data.freeze()))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        // only this stream's handle, the connection stays open for the others
+        Pin::new(&mut self.sender).poll_flush(cx)
+    }
+}
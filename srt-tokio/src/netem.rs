@@ -0,0 +1,308 @@
+//! Loss and delay like a bad network's, to soak-test applications without setting up `netem`, see
+//! [`ImpairedTransport`]
+//!
+//! ```no_run
+//! use srt_tokio::netem::{GilbertElliott, Impairment, ImpairedTransport};
+//! use srt_tokio::SrtSocketBuilder;
+//! use std::time::Duration;
+//! use tokio::net::UdpSocket;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), std::io::Error> {
+//! let impairment = Impairment {
+//!     // 2% of the packets lost, in bursts of 5 on average
+//!     loss: GilbertElliott::bursty(0.02, 5.0),
+//!     delay: Duration::from_millis(40),
+//!     jitter: Duration::from_millis(5),
+//!     seed: None,
+//! };
+//! let socket = UdpSocket::bind("0.0.0.0:0").await?;
+//! let srt = SrtSocketBuilder::new_connect("127.0.0.1:3333")
+//!     .connect_with_transport(ImpairedTransport::new(socket, impairment))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::ready;
+use log::trace;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use tokio::time::{delay_until, Delay};
+
+use crate::codec::MAX_DATAGRAM;
+use crate::AsyncDatagramTransport;
+
+/// The Gilbert–Elliott loss model: a good state and a bad state, each losing datagrams at its own rate, switching
+/// between them at random for each datagram, so losses come in bursts like they do on real links
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GilbertElliott {
+    /// The chance of going from the good state to the bad one, for each datagram
+    pub good_to_bad: f64,
+
+    /// The chance of going from the bad state back to the good one, for each datagram
+    pub bad_to_good: f64,
+
+    /// The chance of losing a datagram in the good state
+    pub loss_good: f64,
+
+    /// The chance of losing a datagram in the bad state
+    pub loss_bad: f64,
+}
+
+impl GilbertElliott {
+    /// No loss
+    pub fn none() -> Self {
+        Self::uniform(0.0)
+    }
+
+    /// Each datagram lost with the chance `rate`, independently of the others
+    pub fn uniform(rate: f64) -> Self {
+        GilbertElliott {
+            good_to_bad: 0.0,
+            bad_to_good: 1.0,
+            loss_good: rate,
+            loss_bad: rate,
+        }
+    }
+
+    /// `rate` of the datagrams lost, in bursts of `mean_burst` in a row on average, the simple Gilbert model where
+    /// the bad state loses everything and the good state nothing
+    pub fn bursty(rate: f64, mean_burst: f64) -> Self {
+        let bad_to_good = 1.0 / mean_burst.max(1.0);
+        GilbertElliott {
+            good_to_bad: (rate * bad_to_good / (1.0 - rate)).min(1.0),
+            bad_to_good,
+            loss_good: 0.0,
+            loss_bad: 1.0,
+        }
+    }
+
+    /// The share of datagrams lost in the long run
+    pub fn loss_rate(&self) -> f64 {
+        let switches = self.good_to_bad + self.bad_to_good;
+        if switches == 0.0 {
+            return self.loss_good;
+        }
+        let bad = self.good_to_bad / switches;
+        (1.0 - bad) * self.loss_good + bad * self.loss_bad
+    }
+
+    // moves the model on a datagram from the state `bad`, returning if it's lost
+    fn lose(&self, bad: &mut bool, rng: &mut impl Rng) -> bool {
+        let switch = if *bad {
+            self.bad_to_good
+        } else {
+            self.good_to_bad
+        };
+        if rng.gen::<f64>() < switch {
+            *bad = !*bad;
+        }
+        let rate = if *bad { self.loss_bad } else { self.loss_good };
+        rng.gen::<f64>() < rate
+    }
+}
+
+impl Default for GilbertElliott {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// How an [`ImpairedTransport`] impairs the datagrams it receives
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Impairment {
+    /// Which datagrams are lost
+    pub loss: GilbertElliott,
+
+    /// How long datagrams are held back on average
+    pub delay: Duration,
+
+    /// The standard deviation of the delay, normally distributed, which reorders datagrams when it's large compared
+    /// to the time between them
+    pub jitter: Duration,
+
+    /// Seeds the randomness, so runs can be repeated, or `None` for a random seed
+    pub seed: Option<u64>,
+}
+
+// a datagram held back until it's due, in the order received when due at the same time
+struct Held {
+    due: Reverse<(Instant, u64)>,
+    datagram: Vec<u8>,
+    from: SocketAddr,
+}
+
+impl PartialEq for Held {
+    fn eq(&self, other: &Self) -> bool {
+        self.due == other.due
+    }
+}
+
+impl Eq for Held {}
+
+impl PartialOrd for Held {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Held {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.due.cmp(&other.due)
+    }
+}
+
+/// An [`AsyncDatagramTransport`] losing and delaying the datagrams it receives, see [`Impairment`]
+///
+/// Only what's received is impaired, so wrap the transports of both ends to impair both directions. What's sent goes
+/// through untouched.
+pub struct ImpairedTransport<T> {
+    inner: T,
+    impairment: Impairment,
+    rng: StdRng,
+    // in the bad state of the loss model
+    bad: bool,
+    held: BinaryHeap<Held>,
+    received: u64,
+    timer: Option<Delay>,
+    recv_buf: Vec<u8>,
+}
+
+impl<T: AsyncDatagramTransport> ImpairedTransport<T> {
+    pub fn new(inner: T, impairment: Impairment) -> Self {
+        let rng = match impairment.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        ImpairedTransport {
+            inner,
+            impairment,
+            rng,
+            bad: false,
+            held: BinaryHeap::new(),
+            received: 0,
+            timer: None,
+            recv_buf: vec![0; MAX_DATAGRAM],
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    fn delay(&mut self) -> Duration {
+        let Impairment { delay, jitter, .. } = self.impairment;
+        if jitter == Duration::from_secs(0) {
+            return delay;
+        }
+        let normal = Normal::new(delay.as_secs_f64(), jitter.as_secs_f64()).unwrap();
+        Duration::from_secs_f64(normal.sample(&mut self.rng).max(0.0))
+    }
+}
+
+impl<T: AsyncDatagramTransport> AsyncDatagramTransport for ImpairedTransport<T> {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context,
+        buf: &[u8],
+        target: &SocketAddr,
+    ) -> Poll<Result<usize, io::Error>> {
+        self.inner.poll_send_to(cx, buf, target)
+    }
+
+    fn poll_recv_from(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), io::Error>> {
+        // take in everything that arrived, so it's held back from when it did
+        while let Poll::Ready(res) = self.inner.poll_recv_from(cx, &mut self.recv_buf) {
+            let (len, from) = res?;
+            if self.impairment.loss.lose(&mut self.bad, &mut self.rng) {
+                trace!("Losing a {} byte datagram from {}", len, from);
+                continue;
+            }
+            let due = Instant::now() + self.delay();
+            self.received += 1;
+            self.held.push(Held {
+                due: Reverse((due, self.received)),
+                datagram: self.recv_buf[..len].to_vec(),
+                from,
+            });
+        }
+
+        loop {
+            let due = match self.held.peek() {
+                Some(held) => (held.due.0).0,
+                None => return Poll::Pending,
+            };
+            if due <= Instant::now() {
+                let held = self.held.pop().unwrap();
+                let len = held.datagram.len().min(buf.len());
+                buf[..len].copy_from_slice(&held.datagram[..len]);
+                return Poll::Ready(Ok((len, held.from)));
+            }
+            let timer = self.timer.get_or_insert_with(|| delay_until(due.into()));
+            if timer.deadline() != due.into() {
+                timer.reset(due.into());
+            }
+            ready!(Pin::new(timer).poll(cx));
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.inner.local_addr()
+    }
+
+    fn max_payload(&self) -> usize {
+        self.inner.max_payload()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn losses(model: GilbertElliott, count: usize) -> Vec<bool> {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let mut bad = false;
+        (0..count).map(|_| model.lose(&mut bad, &mut rng)).collect()
+    }
+
+    #[test]
+    fn bursty_loss() {
+        let model = GilbertElliott::bursty(0.1, 4.0);
+        assert!((model.loss_rate() - 0.1).abs() < 1e-9);
+
+        let lost = losses(model, 100_000);
+        let rate = lost.iter().filter(|lost| **lost).count() as f64 / lost.len() as f64;
+        assert!((rate - 0.1).abs() < 0.01, "{}", rate);
+
+        // runs of losses
+        let bursts = lost.windows(2).filter(|w| !w[0] && w[1]).count();
+        let mean_burst = lost.iter().filter(|lost| **lost).count() as f64 / bursts as f64;
+        assert!((mean_burst - 4.0).abs() < 0.3, "{}", mean_burst);
+    }
+
+    #[test]
+    fn uniform_loss() {
+        let model = GilbertElliott::uniform(0.05);
+        assert!((model.loss_rate() - 0.05).abs() < 1e-9);
+        assert!(!losses(GilbertElliott::none(), 10_000).contains(&true));
+
+        let lost = losses(model, 100_000);
+        let rate = lost.iter().filter(|lost| **lost).count() as f64 / lost.len() as f64;
+        assert!((rate - 0.05).abs() < 0.005, "{}", rate);
+    }
+}
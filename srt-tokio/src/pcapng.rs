@@ -0,0 +1,528 @@
+//! Capture the packets of a connection in the pcapng format, for offline analysis with Wireshark
+//!
+//! Packets are written as UDP datagrams with made up IP headers, so Wireshark's SRT dissector picks them up. It is a
+//! heuristic dissector, if the packets show up as plain UDP enable `srt_udp` in Analyze > Enabled Protocols.
+//!
+//! Captures can be read back with [`read_capture`], to [replay](srt_protocol::replay) them. That works with captures
+//! made with Wireshark or tcpdump in the pcapng format too.
+//!
+//! ```no_run
+//! use srt_tokio::{pcapng::PcapngWriter, SrtSocketBuilder};
+//! use std::{fs::File, io};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), io::Error> {
+//!     let capture = PcapngWriter::new(File::create("srt.pcapng")?, "127.0.0.1:3333".parse().unwrap())?;
+//!     let sock = SrtSocketBuilder::new_listen()
+//!         .local_port(3333)
+//!         .packet_tap(capture.into_tap())
+//!         .connect()
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{
+    convert::TryInto,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BufMut;
+use log::{trace, warn};
+use srt_protocol::replay::TracePacket;
+
+use crate::{Packet, PacketDirection, PacketTap};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const LINKTYPE_NULL: u16 = 0;
+const LINKTYPE_ETHERNET: u16 = 1;
+// raw IPv4 or IPv6 packets, without a link layer header
+const LINKTYPE_RAW: u16 = 101;
+const LINKTYPE_LINUX_SLL: u16 = 113;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_EPB_FLAGS: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const EPB_FLAGS_INBOUND: u32 = 0b01;
+const EPB_FLAGS_OUTBOUND: u32 = 0b10;
+
+const IPPROTO_UDP: u8 = 17;
+const TTL: u8 = 64;
+
+/// Writes packets in the pcapng format to `W`, see the [module docs](self)
+pub struct PcapngWriter<W> {
+    out: W,
+    local: SocketAddr,
+    time_base: (Instant, SystemTime),
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Start a capture written to `out`, with `local` as the address of this side of the connection.
+    ///
+    /// Writes the header of the file right away.
+    pub fn new(mut out: W, local: SocketAddr) -> Result<Self, io::Error> {
+        let mut header = Vec::new();
+
+        // section header block, of unspecified length
+        write_block(&mut header, SECTION_HEADER_BLOCK, |body| {
+            body.put_u32_le(BYTE_ORDER_MAGIC);
+            body.put_u16_le(1); // major version
+            body.put_u16_le(0); // minor version
+            body.put_i64_le(-1);
+        });
+
+        // one interface, with timestamps in the default resolution of microseconds
+        write_block(&mut header, INTERFACE_DESCRIPTION_BLOCK, |body| {
+            body.put_u16_le(LINKTYPE_RAW);
+            body.put_u16_le(0); // reserved
+            body.put_u32_le(0); // no snapshot length limit
+        });
+
+        out.write_all(&header)?;
+
+        Ok(PcapngWriter {
+            out,
+            local,
+            time_base: (Instant::now(), SystemTime::now()),
+        })
+    }
+
+    /// Write one packet, sent or received at `time` from or to `peer`
+    pub fn write_packet(
+        &mut self,
+        time: Instant,
+        direction: PacketDirection,
+        packet: &Packet,
+        peer: SocketAddr,
+    ) -> Result<(), io::Error> {
+        let (src, dst) = match direction {
+            PacketDirection::Ingress => (peer, self.local),
+            PacketDirection::Egress => (self.local, peer),
+        };
+
+        let mut payload = Vec::new();
+        packet.serialize(&mut payload);
+        let datagram = udp_datagram(src, dst, &payload);
+
+        let (base_instant, base_time) = self.time_base;
+        let time = if time >= base_instant {
+            base_time + (time - base_instant)
+        } else {
+            base_time - (base_instant - time)
+        };
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+
+        let mut block = Vec::with_capacity(datagram.len() + 48);
+        write_block(&mut block, ENHANCED_PACKET_BLOCK, |body| {
+            body.put_u32_le(0); // interface id
+            body.put_u32_le((micros >> 32) as u32);
+            body.put_u32_le(micros as u32);
+            body.put_u32_le(datagram.len() as u32); // captured length
+            body.put_u32_le(datagram.len() as u32); // original length
+            body.put_slice(&datagram);
+            pad(body);
+
+            body.put_u16_le(OPT_EPB_FLAGS);
+            body.put_u16_le(4);
+            body.put_u32_le(match direction {
+                PacketDirection::Ingress => EPB_FLAGS_INBOUND,
+                PacketDirection::Egress => EPB_FLAGS_OUTBOUND,
+            });
+            body.put_u16_le(OPT_ENDOFOPT);
+            body.put_u16_le(0);
+        });
+
+        self.out.write_all(&block)
+    }
+
+    /// The writer the capture is written to
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write + Send + 'static> PcapngWriter<W> {
+    /// A tap writing every packet of the connection it is set on, see [`SrtSocketBuilder::packet_tap`](crate::SrtSocketBuilder::packet_tap)
+    ///
+    /// Packets are written from the socket's task, so `W` should not block for long. Errors writing are logged.
+    pub fn into_tap(self) -> PacketTap {
+        let writer = Mutex::new(self);
+        PacketTap::new(move |time, direction, packet, peer| {
+            if let Err(e) = writer
+                .lock()
+                .unwrap()
+                .write_packet(time, direction, packet, peer)
+            {
+                warn!("Failed to write captured packet: {}", e);
+            }
+        })
+    }
+}
+
+// write a block with its type and length around the body written by `body`
+fn write_block(out: &mut Vec<u8>, block_type: u32, body: impl FnOnce(&mut Vec<u8>)) {
+    let mut contents = Vec::new();
+    body(&mut contents);
+    let length = contents.len() as u32 + 12;
+
+    out.put_u32_le(block_type);
+    out.put_u32_le(length);
+    out.put_slice(&contents);
+    out.put_u32_le(length);
+}
+
+// pad to 32 bits, as every field of a block is aligned to that
+fn pad(out: &mut Vec<u8>) {
+    out.resize(out.len() + padding(out.len()), 0);
+}
+
+// the padding needed after `len` bytes
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+// a UDP datagram, in an IPv4 packet if both addresses are IPv4, or an IPv6 one otherwise
+fn udp_datagram(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let udp_length = payload.len() as u16 + 8;
+    let mut packet = Vec::with_capacity(usize::from(udp_length) + 40);
+
+    let pseudo_header = match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+            let mut header = Vec::with_capacity(20);
+            header.put_u8(0x45); // version 4, 5 words of header
+            header.put_u8(0);
+            header.put_u16(udp_length + 20);
+            header.put_u16(0); // identification
+            header.put_u16(0x4000); // don't fragment
+            header.put_u8(TTL);
+            header.put_u8(IPPROTO_UDP);
+            header.put_u16(0);
+            header.put_slice(&src_ip.octets());
+            header.put_slice(&dst_ip.octets());
+            let sum = checksum(&[&header]);
+            header[10..12].copy_from_slice(&sum.to_be_bytes());
+            packet.put_slice(&header);
+
+            let mut pseudo = Vec::with_capacity(12);
+            pseudo.put_slice(&src_ip.octets());
+            pseudo.put_slice(&dst_ip.octets());
+            pseudo.put_u8(0);
+            pseudo.put_u8(IPPROTO_UDP);
+            pseudo.put_u16(udp_length);
+            pseudo
+        }
+        (src_ip, dst_ip) => {
+            let src_ip = to_ipv6(src_ip);
+            let dst_ip = to_ipv6(dst_ip);
+            packet.put_u32(0x6000_0000); // version 6, no traffic class or flow label
+            packet.put_u16(udp_length);
+            packet.put_u8(IPPROTO_UDP);
+            packet.put_u8(TTL);
+            packet.put_slice(&src_ip);
+            packet.put_slice(&dst_ip);
+
+            let mut pseudo = Vec::with_capacity(40);
+            pseudo.put_slice(&src_ip);
+            pseudo.put_slice(&dst_ip);
+            pseudo.put_u32(u32::from(udp_length));
+            pseudo.put_u32(u32::from(IPPROTO_UDP));
+            pseudo
+        }
+    };
+
+    let mut udp_header = Vec::with_capacity(8);
+    udp_header.put_u16(src.port());
+    udp_header.put_u16(dst.port());
+    udp_header.put_u16(udp_length);
+    udp_header.put_u16(0);
+    // a checksum of zero means it wasn't computed, so it is sent as all ones instead
+    let sum = match checksum(&[&pseudo_header, &udp_header, payload]) {
+        0 => 0xFFFF,
+        sum => sum,
+    };
+    udp_header[6..8].copy_from_slice(&sum.to_be_bytes());
+
+    packet.put_slice(&udp_header);
+    packet.put_slice(payload);
+    packet
+}
+
+fn to_ipv6(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+        IpAddr::V6(ip) => ip.octets(),
+    }
+}
+
+// the internet checksum, the ones' complement of the ones' complement sum of every 16 bit word
+//
+// every part but the last must have an even length
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum = 0u32;
+    for part in parts {
+        for word in part.chunks(2) {
+            sum += u32::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]));
+        }
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A packet read from a capture by [`read_capture`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedPacket {
+    pub time: SystemTime,
+    /// The direction, if the capture recorded it
+    pub direction: Option<PacketDirection>,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub packet: Packet,
+}
+
+struct Interface {
+    link_type: u16,
+    // in units per second
+    resolution: u64,
+}
+
+/// Read every SRT packet in a pcapng capture, skipping anything else
+///
+/// Only UDP over IPv4 or IPv6 is understood, on the link types Wireshark and tcpdump usually capture with.
+pub fn read_capture(mut input: impl Read) -> Result<Vec<CapturedPacket>, io::Error> {
+    let mut buf = Vec::new();
+    input.read_to_end(&mut buf)?;
+
+    if buf.len() < 12 || u32_at(&buf, 0, true) != SECTION_HEADER_BLOCK {
+        return Err(invalid("not a pcapng file"));
+    }
+
+    let mut little_endian = true;
+    let mut interfaces = Vec::new();
+    let mut packets = Vec::new();
+
+    let mut rest = &buf[..];
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(invalid("truncated block"));
+        }
+        if u32_at(rest, 0, true) == SECTION_HEADER_BLOCK {
+            little_endian = match u32_at(rest, 8, true) {
+                BYTE_ORDER_MAGIC => true,
+                magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => false,
+                _ => return Err(invalid("bad byte order magic")),
+            };
+            // interface ids are per section
+            interfaces.clear();
+        }
+
+        let block_type = u32_at(rest, 0, little_endian);
+        let length = u32_at(rest, 4, little_endian) as usize;
+        if length < 12 || padding(length) != 0 || length > rest.len() {
+            return Err(invalid("bad block length"));
+        }
+        let body = &rest[8..length - 4];
+        rest = &rest[length..];
+
+        match block_type {
+            INTERFACE_DESCRIPTION_BLOCK if body.len() >= 8 => {
+                let mut resolution = 1_000_000;
+                for (code, value) in options(&body[8..], little_endian) {
+                    if code == OPT_IF_TSRESOL && !value.is_empty() {
+                        resolution = match value[0] {
+                            exp if exp & 0x80 != 0 => 1u64.checked_shl(u32::from(exp & 0x7F)),
+                            exp => 10u64.checked_pow(u32::from(exp)),
+                        }
+                        .ok_or_else(|| invalid("bad timestamp resolution"))?;
+                    }
+                }
+                interfaces.push(Interface {
+                    link_type: u16_at(body, 0, little_endian),
+                    resolution,
+                });
+            }
+            ENHANCED_PACKET_BLOCK if body.len() >= 20 => {
+                let interface = interfaces
+                    .get(u32_at(body, 0, little_endian) as usize)
+                    .ok_or_else(|| invalid("packet from an unknown interface"))?;
+                let captured = u32_at(body, 12, little_endian) as usize;
+                if 20 + captured > body.len() {
+                    return Err(invalid("bad captured length"));
+                }
+
+                let ticks = u64::from(u32_at(body, 4, little_endian)) << 32
+                    | u64::from(u32_at(body, 8, little_endian));
+                let time = UNIX_EPOCH
+                    + Duration::from_secs(ticks / interface.resolution)
+                    + Duration::from_nanos(
+                        (ticks % interface.resolution) * 1_000_000_000 / interface.resolution,
+                    );
+
+                let options_start = 20 + captured + padding(captured);
+                let direction = options(body.get(options_start..).unwrap_or(&[]), little_endian)
+                    .find(|(code, value)| *code == OPT_EPB_FLAGS && value.len() >= 4)
+                    .and_then(|(_, value)| match u32_at(value, 0, little_endian) & 0b11 {
+                        EPB_FLAGS_INBOUND => Some(PacketDirection::Ingress),
+                        EPB_FLAGS_OUTBOUND => Some(PacketDirection::Egress),
+                        _ => None,
+                    });
+
+                let frame = &body[20..20 + captured];
+                if let Some((src, dst, mut payload)) = parse_frame(interface.link_type, frame) {
+                    match Packet::parse(&mut payload) {
+                        Ok(packet) => packets.push(CapturedPacket {
+                            time,
+                            direction,
+                            src,
+                            dst,
+                            packet,
+                        }),
+                        Err(e) => trace!("Skipping UDP packet that isn't SRT: {}", e),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(packets)
+}
+
+/// What the receiver of the first data packet in `captured` got from its peer, as a trace to
+/// [replay](srt_protocol::replay::replay_receiver)
+pub fn receiver_trace(captured: &[CapturedPacket]) -> Vec<TracePacket> {
+    let (sender, receiver) = match captured
+        .iter()
+        .find(|cp| matches!(cp.packet, Packet::Data(_)))
+    {
+        Some(cp) => (cp.src, cp.dst),
+        None => return vec![],
+    };
+
+    let mut received = captured
+        .iter()
+        .filter(|cp| cp.src == sender && cp.dst == receiver)
+        .peekable();
+    let start = match received.peek() {
+        Some(cp) => cp.time,
+        None => return vec![],
+    };
+    received
+        .map(|cp| TracePacket {
+            // captures aren't always in order
+            offset: cp.time.duration_since(start).unwrap_or_default(),
+            packet: cp.packet.clone(),
+        })
+        .collect()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn u16_at(buf: &[u8], at: usize, little_endian: bool) -> u16 {
+    let bytes = buf[at..at + 2].try_into().unwrap();
+    if little_endian {
+        u16::from_le_bytes(bytes)
+    } else {
+        u16::from_be_bytes(bytes)
+    }
+}
+
+fn u32_at(buf: &[u8], at: usize, little_endian: bool) -> u32 {
+    let bytes = buf[at..at + 4].try_into().unwrap();
+    if little_endian {
+        u32::from_le_bytes(bytes)
+    } else {
+        u32::from_be_bytes(bytes)
+    }
+}
+
+// the options at the end of a block, as (code, value)
+fn options(mut buf: &[u8], little_endian: bool) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if buf.len() < 4 {
+            return None;
+        }
+        let code = u16_at(buf, 0, little_endian);
+        let length = usize::from(u16_at(buf, 2, little_endian));
+        if code == OPT_ENDOFOPT || 4 + length > buf.len() {
+            return None;
+        }
+        let value = &buf[4..4 + length];
+        buf = &buf[(4 + length + padding(length)).min(buf.len())..];
+        Some((code, value))
+    })
+}
+
+// the addresses and payload of a UDP datagram in a captured frame
+fn parse_frame(link_type: u16, frame: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let ip = match link_type {
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => frame,
+        LINKTYPE_NULL => frame.get(4..)?,
+        LINKTYPE_LINUX_SLL => frame.get(16..)?,
+        LINKTYPE_ETHERNET => {
+            // skip any VLAN tags
+            let mut at = 12;
+            while frame.get(at..at + 2)? == [0x81, 0x00] {
+                at += 4;
+            }
+            frame.get(at + 2..)?
+        }
+        _ => return None,
+    };
+
+    let (src_ip, dst_ip, udp): (IpAddr, IpAddr, _) = match ip.first()? >> 4 {
+        4 => {
+            let header_length = usize::from(ip[0] & 0x0F) * 4;
+            // fragments can't be put back together here
+            let fragmented = u16::from_be_bytes(ip.get(6..8)?.try_into().ok()?) & 0x3FFF != 0;
+            if *ip.get(9)? != IPPROTO_UDP || fragmented {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (
+                Ipv4Addr::from(src).into(),
+                Ipv4Addr::from(dst).into(),
+                ip.get(header_length..)?,
+            )
+        }
+        6 => {
+            // extension headers aren't supported
+            if *ip.get(6)? != IPPROTO_UDP {
+                return None;
+            }
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            (
+                Ipv6Addr::from(src).into(),
+                Ipv6Addr::from(dst).into(),
+                ip.get(40..)?,
+            )
+        }
+        _ => return None,
+    };
+
+    let src_port = u16::from_be_bytes(udp.get(0..2)?.try_into().ok()?);
+    let dst_port = u16::from_be_bytes(udp.get(2..4)?.try_into().ok()?);
+    let length = usize::from(u16::from_be_bytes(udp.get(4..6)?.try_into().ok()?));
+    Some((
+        SocketAddr::new(src_ip, src_port),
+        SocketAddr::new(dst_ip, dst_port),
+        udp.get(8..length)?,
+    ))
+}
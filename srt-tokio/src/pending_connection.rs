@@ -8,20 +8,21 @@ use std::{
 };
 
 use srt_protocol::{
+    packet::RejectReason,
     pending_connection::{
         connect::{Connect, ConnectState},
         listen::{Listen, ListenState},
         rendezvous::Rendezvous,
-        ConnInitSettings,
+        ConnInitSettings, ConnectError,
     },
     protocol::handshake::Handshake,
-    Connection, Packet, PacketParseError,
+    Connection, ConnectionSettings, Packet, PacketParseError,
 };
 
 use crate::util::get_packet;
 
 use futures::prelude::*;
-use tokio::time::interval;
+use tokio::time::{interval, timeout};
 
 pub async fn connect<T>(
     sock: &mut T,
@@ -47,6 +48,12 @@ where
             Ok(Some(packet)) => {
                 sock.send(packet).await?;
             }
+            Err(e @ ConnectError::Rejected(_)) => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, e));
+            }
+            Err(e) if e.reject_reason().is_some() => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, e));
+            }
             Err(e) => {
                 warn!("{:?}", e);
             }
@@ -70,18 +77,33 @@ where
         + Sink<(Packet, SocketAddr), Error = io::Error>
         + Unpin,
 {
-    let mut listen = Listen::new(init_settings);
+    let mut listen = Listen::new(init_settings.clone());
 
     loop {
         let packet = get_packet(sock).await?;
-        match listen.handle_packet(packet) {
-            Ok(Some(packet)) => sock.send(packet).await?,
+        let response = match listen.handle_packet(packet) {
+            Ok(response) => response,
             Err(e) => {
                 warn!("{:?}", e);
+                None
             }
-            _ => {}
-        }
+        };
         if let ListenState::Connected(resp_handshake, settings) = listen.state().clone() {
+            // the response waits for the authenticator, the caller sends the conclusion again meanwhile
+            if let Err(reason) = authenticate(&init_settings, &settings).await {
+                warn!(
+                    "Rejecting connection from {}: {:?}",
+                    settings.remote, reason
+                );
+                if let Some(rejection) = listen.reject(reason) {
+                    sock.send(rejection).await?;
+                }
+                listen = Listen::new(init_settings.clone());
+                continue;
+            }
+            if let Some(packet) = response {
+                sock.send(packet).await?;
+            }
             return Ok(Connection {
                 settings,
                 handshake: Handshake::Listener(resp_handshake.control_type),
@@ -90,6 +112,19 @@ where
     }
 }
 
+/// Run the authenticator of `init_settings` on a listener's connection, if there is one
+pub async fn authenticate(
+    init_settings: &ConnInitSettings,
+    settings: &ConnectionSettings,
+) -> Result<(), RejectReason> {
+    match &init_settings.authenticator {
+        Some(authenticator) => timeout(init_settings.auth_timeout, authenticator.call(settings))
+            .await
+            .unwrap_or(Err(RejectReason::Timeout)),
+        None => Ok(()),
+    }
+}
+
 pub async fn rendezvous<T>(
     sock: &mut T,
     local_addr: SocketAddr,
@@ -117,6 +152,12 @@ where
             Ok(Some((packet, address))) => {
                 sock.send((Packet::Control(packet), address)).await?;
             }
+            Err(e @ ConnectError::Rejected(_)) => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, e));
+            }
+            Err(e) if e.reject_reason().is_some() => {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, e));
+            }
             Err(e) => {
                 warn!("rendezvous {:?} error: {}", sockid, e);
             }
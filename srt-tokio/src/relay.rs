@@ -0,0 +1,61 @@
+//! Forward messages from one connection to another, keeping their original timing
+//!
+//! Received messages come with their origin instant, and are released one latency later. Sending them on with
+//! [`Instant::now`] would add the jitter of the first hop to the second one, and lose the origin of the message.
+//! Instead, the origin instant is moved forward by the latency of the first hop, so the message is stamped with
+//! when it should have been released, and the latencies of the two hops add up.
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use log::info;
+
+use crate::ConnectionSettings;
+
+/// How to rebase the timestamps of messages relayed between two connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayTiming {
+    offset: Duration,
+    earliest: Instant,
+}
+
+impl RelayTiming {
+    /// The timing for relaying from the connection with settings `from` to the connection with settings `to`
+    pub fn new(from: &ConnectionSettings, to: &ConnectionSettings) -> Self {
+        RelayTiming {
+            offset: from.recv_tsbpd_latency,
+            earliest: to.socket_start_time,
+        }
+    }
+
+    /// The instant to send a message received with the instant `origin` with
+    ///
+    /// Nothing can be sent from before the outgoing connection was started, so earlier instants are clamped.
+    pub fn rebase(&self, origin: Instant) -> Instant {
+        (origin + self.offset).max(self.earliest)
+    }
+}
+
+/// Forward every message from `from` to `to`, rebasing the timestamps with `timing`, see the [module docs](self)
+///
+/// Resolves with the number of messages forwarded once `from` ends and `to` is closed.
+pub async fn relay<R, S>(from: &mut R, to: &mut S, timing: RelayTiming) -> Result<u64, io::Error>
+where
+    R: Stream<Item = Result<(Instant, Bytes), io::Error>> + Unpin,
+    S: Sink<(Instant, Bytes), Error = io::Error> + Unpin,
+{
+    let mut forwarded = 0;
+    while let Some((origin, payload)) = from.try_next().await? {
+        // no flush, that would wait for every message to be acknowledged
+        to.feed((timing.rebase(origin), payload)).await?;
+        forwarded += 1;
+    }
+    info!("Relay source ended after {} messages", forwarded);
+
+    to.close().await?;
+    Ok(forwarded)
+}
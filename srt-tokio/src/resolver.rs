@@ -0,0 +1,25 @@
+use std::fmt::Debug;
+use std::io;
+use std::net::SocketAddr;
+
+use futures::future::{BoxFuture, FutureExt};
+use tokio::net::lookup_host;
+
+/// Resolves the host of a caller to the addresses to connect to, see
+/// [`SrtSocketBuilder::new_connect_host`](crate::SrtSocketBuilder::new_connect_host)
+///
+/// Implement this to use a custom DNS client, or service discovery.
+pub trait Resolver: Debug + Send + Sync {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>>;
+}
+
+/// Resolves hosts with the system resolver
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        let host = host.to_string();
+        async move { Ok(lookup_host((&*host, port)).await?.collect()) }.boxed()
+    }
+}
@@ -0,0 +1,288 @@
+//! SRT through a SOCKS5 proxy, for sites that can only reach the internet through one, see [`Socks5Transport`]
+//!
+//! The proxy relays the UDP datagrams with `UDP ASSOCIATE`, from [RFC 1928](https://tools.ietf.org/html/rfc1928),
+//! authenticating with a username and password, from [RFC 1929](https://tools.ietf.org/html/rfc1929), if it asks.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::task::{Context, Poll};
+
+use futures::ready;
+use log::{debug, trace};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::codec::MAX_DATAGRAM;
+use crate::AsyncDatagramTransport;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const USER_PASS: u8 = 2;
+const NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const UDP_ASSOCIATE: u8 = 3;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// The most a relayed datagram's header takes, with an IPv6 address
+const MAX_HEADER: usize = 22;
+
+/// An [`AsyncDatagramTransport`] that relays datagrams through a SOCKS5 proxy
+///
+/// ```no_run
+/// use srt_tokio::{socks5::Socks5Transport, SrtSocketBuilder};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let proxy = Socks5Transport::connect("10.0.0.1:1080".parse().unwrap(), None).await?;
+/// let socket = SrtSocketBuilder::new_connect("203.0.113.5:4000")
+///     .connect_with_transport(proxy)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Socks5Transport {
+    udp: UdpSocket,
+    /// Where the proxy relays datagrams from
+    relay: SocketAddr,
+    /// The association lasts as long as this connection
+    _control: TcpStream,
+    send_buf: Vec<u8>,
+    recv_buf: Vec<u8>,
+}
+
+impl Socks5Transport {
+    /// Ask the proxy at `proxy` to relay datagrams, with `credentials`, a username and a password, if it needs them.
+    ///
+    /// Fails with [`PermissionDenied`](io::ErrorKind::PermissionDenied) if the proxy doesn't accept the credentials, or
+    /// needs some, and [`ConnectionRefused`](io::ErrorKind::ConnectionRefused) if it refuses to relay.
+    pub async fn connect(
+        proxy: SocketAddr,
+        credentials: Option<(&str, &str)>,
+    ) -> Result<Self, io::Error> {
+        let mut control = TcpStream::connect(proxy).await?;
+
+        let methods: &[u8] = match credentials {
+            Some(_) => &[NO_AUTH, USER_PASS],
+            None => &[NO_AUTH],
+        };
+        let mut greeting = vec![VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        control.write_all(&greeting).await?;
+
+        let mut choice = [0; 2];
+        control.read_exact(&mut choice).await?;
+        if choice[0] != VERSION {
+            return Err(invalid_data("Not a SOCKS5 proxy"));
+        }
+        match (choice[1], credentials) {
+            (NO_AUTH, _) => {}
+            (USER_PASS, Some((username, password))) => {
+                authenticate(&mut control, username, password).await?
+            }
+            (NO_ACCEPTABLE_METHODS, _) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "The proxy needs credentials",
+                ))
+            }
+            (method, _) => {
+                return Err(invalid_data(format!(
+                    "The proxy chose authentication method {} that wasn't offered",
+                    method
+                )))
+            }
+        }
+
+        // the datagrams could come from anywhere as far as the proxy can tell, behind NAT, so no address is given
+        let any = match control.local_addr()?.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let udp = UdpSocket::bind((any, 0)).await?;
+
+        let mut request = vec![VERSION, UDP_ASSOCIATE, 0];
+        put_addr(&mut request, &SocketAddr::new(any, 0));
+        control.write_all(&request).await?;
+
+        let mut reply = [0; 3];
+        control.read_exact(&mut reply).await?;
+        if reply[0] != VERSION {
+            return Err(invalid_data("Not a SOCKS5 proxy"));
+        }
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("The proxy refused to relay: {}", reply_message(reply[1])),
+            ));
+        }
+        let mut relay = read_addr(&mut control).await?;
+        // relayed through the address the proxy was reached at
+        if relay.ip().is_unspecified() {
+            relay.set_ip(proxy.ip());
+        }
+        debug!("Relaying through {}", relay);
+
+        Ok(Socks5Transport {
+            udp,
+            relay,
+            _control: control,
+            send_buf: Vec::new(),
+            recv_buf: vec![0; MAX_DATAGRAM],
+        })
+    }
+
+    /// Where the proxy relays datagrams from
+    pub fn relay_addr(&self) -> SocketAddr {
+        self.relay
+    }
+}
+
+impl AsyncDatagramTransport for Socks5Transport {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context,
+        buf: &[u8],
+        target: &SocketAddr,
+    ) -> Poll<Result<usize, io::Error>> {
+        // RSV and FRAG, which is always 0 as datagrams aren't fragmented
+        self.send_buf.clear();
+        self.send_buf.extend_from_slice(&[0, 0, 0]);
+        put_addr(&mut self.send_buf, target);
+        self.send_buf.extend_from_slice(buf);
+        ready!(self.udp.poll_send_to(cx, &self.send_buf, &self.relay))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_recv_from(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), io::Error>> {
+        loop {
+            let (len, from) = ready!(self.udp.poll_recv_from(cx, &mut self.recv_buf))?;
+            if from != self.relay {
+                trace!("Ignoring a datagram from {}, not the relay", from);
+                continue;
+            }
+            match parse_datagram(&self.recv_buf[..len]) {
+                Some((source, payload)) => {
+                    let len = payload.len().min(buf.len());
+                    buf[..len].copy_from_slice(&payload[..len]);
+                    return Poll::Ready(Ok((len, source)));
+                }
+                None => trace!("Ignoring a malformed or fragmented datagram from the relay"),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.udp.local_addr()
+    }
+
+    fn max_payload(&self) -> usize {
+        65_507 - MAX_HEADER
+    }
+}
+
+async fn authenticate(
+    control: &mut TcpStream,
+    username: &str,
+    password: &str,
+) -> Result<(), io::Error> {
+    if username.len() > 255 || password.len() > 255 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "SOCKS5 usernames and passwords are at most 255 bytes",
+        ));
+    }
+    let mut request = vec![1, username.len() as u8];
+    request.extend_from_slice(username.as_bytes());
+    request.push(password.len() as u8);
+    request.extend_from_slice(password.as_bytes());
+    control.write_all(&request).await?;
+
+    let mut status = [0; 2];
+    control.read_exact(&mut status).await?;
+    if status[1] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "The proxy didn't accept the credentials",
+        ));
+    }
+    Ok(())
+}
+
+fn put_addr(into: &mut Vec<u8>, addr: &SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            into.push(ATYP_IPV4);
+            into.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            into.push(ATYP_IPV6);
+            into.extend_from_slice(&ip.octets());
+        }
+    }
+    into.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+async fn read_addr(control: &mut TcpStream) -> Result<SocketAddr, io::Error> {
+    let ip = match control.read_u8().await? {
+        ATYP_IPV4 => {
+            let mut octets = [0; 4];
+            control.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0; 16];
+            control.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        }
+        ATYP_DOMAIN => return Err(invalid_data("The proxy gave its relay as a domain name")),
+        atyp => return Err(invalid_data(format!("Unknown address type {}", atyp))),
+    };
+    let port = control.read_u16().await?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+// the source and payload of a datagram from the relay, unless it's malformed or a fragment
+fn parse_datagram(datagram: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let (header, rest) = (datagram.get(..4)?, datagram.get(4..)?);
+    if header[2] != 0 {
+        return None;
+    }
+    let (ip, rest) = match header[3] {
+        ATYP_IPV4 => {
+            let mut octets = [0; 4];
+            octets.copy_from_slice(rest.get(..4)?);
+            (IpAddr::from(octets), &rest[4..])
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(rest.get(..16)?);
+            (IpAddr::from(octets), &rest[16..])
+        }
+        _ => return None,
+    };
+    let port = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]);
+    Some((SocketAddr::new(ip, port), &rest[2..]))
+}
+
+fn reply_message(reply: u8) -> &'static str {
+    match reply {
+        1 => "general failure",
+        2 => "not allowed by the ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,619 @@
+//! Experimental link aggregation below SRT, in the spirit of [SRTLA](https://github.com/BELABOX/srtla)
+//!
+//! A [`BondedSocket`] sends the packets of a connection over several UDP sockets, each bound to a different local
+//! address, like the cellular modems of a bonded contribution rig. An [`Aggregator`] in front of the receiving socket
+//! gathers them back, so the connection only ever sees one address for a bonded peer. Both are used in place of the
+//! UDP socket, with [`connect_with_sock`](crate::SrtSocketBuilder::connect_with_sock) or, for the aggregator,
+//! [`build_multiplexed_with_sock`](crate::SrtSocketBuilder::build_multiplexed_with_sock).
+//!
+//! Unlike SRTLA there's no registration, the uplinks of a peer are recognized by the socket id their packets are sent
+//! to. So handshakes go over the first uplink only, and packets are spread over the uplinks in turn, not by how much
+//! each can take. In [`BondingMode::Broadcast`], data is sent over every uplink instead, and the aggregator drops the
+//! copies that arrive after the first, counting them in its [`AggregatorStats`]. In [`BondingMode::Balancing`], data
+//! is spread over the uplinks by the weight given to each, such as their bandwidth, and the receive buffer of the
+//! connection puts it back in order like any reordering on the way.
+//!
+//! The aggregator also rates each uplink of a peer, see [`LinkQuality`]. ACKs go over every uplink in turn to keep
+//! timing them, the rest of what's sent back goes over the best one.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::prelude::*;
+use log::{info, warn};
+use tokio::net::UdpSocket;
+use tokio_util::udp::UdpFramed;
+
+use crate::packet::{ControlTypes, HandshakeControlInfo};
+use crate::protocol::TimeBase;
+use crate::{ControlPacket, Packet, PacketCodec, PacketParseError, SocketID};
+
+mod dedup;
+mod quality;
+mod stats;
+
+pub use self::quality::LinkQuality;
+pub use self::stats::{AggregatorStats, BondStats, LinkStats};
+
+use self::dedup::DedupWindow;
+
+/// How long an uplink the aggregator hasn't heard from is still sent to
+const LINK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long the aggregator remembers a peer it hasn't heard from at all
+const PEER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the aggregator waits for the ACK2 answering an ACK before counting it lost
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many ACKs a bonded socket remembers the uplink of, to answer them the same way
+const ACKS_REMEMBERED: usize = 16;
+
+fn handshake(pack: &Packet) -> Option<&HandshakeControlInfo> {
+    match pack {
+        Packet::Control(ControlPacket {
+            control_type: ControlTypes::Handshake(shake),
+            ..
+        }) => Some(shake),
+        _ => None,
+    }
+}
+
+fn ack_seq_num(pack: &Packet) -> Option<i32> {
+    match pack {
+        Packet::Control(ControlPacket {
+            control_type: ControlTypes::Ack(ack),
+            ..
+        }) => Some(ack.ack_seq_num),
+        _ => None,
+    }
+}
+
+fn ack2_seq_num(pack: &Packet) -> Option<i32> {
+    match pack {
+        Packet::Control(ControlPacket {
+            control_type: ControlTypes::Ack2(seq_num),
+            ..
+        }) => Some(*seq_num),
+        _ => None,
+    }
+}
+
+/// How a [`BondedSocket`] spreads packets over its uplinks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BondingMode {
+    /// Each packet goes over one uplink, in turn, the default
+    RoundRobin,
+    /// Data goes over every uplink, so it arrives as long as one of them delivers it. Control packets still go over
+    /// one uplink in turn.
+    Broadcast,
+    /// Data is spread over the uplinks in proportion to their weight, see
+    /// [`BondedSocket::set_weight`]. Control packets still go over one uplink in turn.
+    Balancing,
+}
+
+struct Uplink {
+    sock: UdpFramed<PacketCodec>,
+    local: SocketAddr,
+    failed: bool,
+    weight: u32,
+    // how far behind its share the uplink is, for balancing
+    credit: i64,
+}
+
+/// A socket sending over several uplinks, see the [module documentation](self)
+///
+/// An uplink that fails to send is given up on, the socket only fails once all of them have. ACK2s go back over the
+/// uplink the ACK they answer came from, so the aggregator can time each uplink.
+pub struct BondedSocket {
+    uplinks: Vec<Uplink>,
+    mode: BondingMode,
+    next_recv: usize,
+    next_send: usize,
+
+    /// The uplinks the latest ACKs came from, by ack sequence number
+    acks: VecDeque<(i32, usize)>,
+}
+
+impl BondedSocket {
+    /// Bind an uplink to each of `locals`, the first one carries the handshake
+    pub async fn bind(locals: &[SocketAddr]) -> Result<Self, io::Error> {
+        if locals.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a bonded socket needs at least one uplink",
+            ));
+        }
+        let mut uplinks = Vec::with_capacity(locals.len());
+        for local in locals {
+            let sock = UdpSocket::bind(local).await?;
+            uplinks.push(Uplink {
+                local: sock.local_addr()?,
+                sock: UdpFramed::new(sock, PacketCodec),
+                failed: false,
+                weight: 1,
+                credit: 0,
+            });
+        }
+        Ok(BondedSocket {
+            uplinks,
+            mode: BondingMode::RoundRobin,
+            next_recv: 0,
+            next_send: 0,
+            acks: VecDeque::new(),
+        })
+    }
+
+    /// Change how packets are spread over the uplinks, see [`BondingMode`]
+    pub fn set_mode(&mut self, mode: BondingMode) {
+        self.mode = mode;
+    }
+
+    /// Set the weight of the uplink bound to `local` in [`BondingMode::Balancing`], 1 by default
+    ///
+    /// The uplinks carry data in proportion to their weight, such as their bandwidth in kbps. An uplink weighing 0
+    /// only carries control packets.
+    pub fn set_weight(&mut self, local: SocketAddr, weight: u32) -> Result<(), io::Error> {
+        match self.uplinks.iter_mut().find(|uplink| uplink.local == local) {
+            Some(uplink) => {
+                uplink.weight = weight;
+                Ok(())
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no uplink is bound to {}", local),
+            )),
+        }
+    }
+
+    /// The local addresses of the uplinks that haven't failed
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.alive().map(|(_, uplink)| uplink.local).collect()
+    }
+
+    fn alive(&self) -> impl Iterator<Item = (usize, &Uplink)> {
+        self.uplinks.iter().enumerate().filter(|(_, u)| !u.failed)
+    }
+
+    // smooth weighted round robin: every live uplink earns its weight, and the one furthest ahead pays the total
+    fn next_weighted(&mut self) -> Option<usize> {
+        let mut total = 0;
+        let mut best: Option<(usize, i64)> = None;
+        for (idx, uplink) in self.uplinks.iter_mut().enumerate() {
+            if uplink.failed || uplink.weight == 0 {
+                continue;
+            }
+            uplink.credit += i64::from(uplink.weight);
+            total += i64::from(uplink.weight);
+            if matches!(best, Some((_, credit)) if credit >= uplink.credit) {
+                continue;
+            }
+            best = Some((idx, uplink.credit));
+        }
+        let (best, _) = best?;
+        self.uplinks[best].credit -= total;
+        Some(best)
+    }
+
+    fn fail(&mut self, idx: usize, e: io::Error) {
+        warn!(
+            "Uplink {} failed, not sending on it anymore: {}",
+            self.uplinks[idx].local, e
+        );
+        self.uplinks[idx].failed = true;
+    }
+
+    fn all_failed(&self) -> Result<(), io::Error> {
+        match self.alive().next() {
+            Some(_) => Ok(()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "every uplink failed",
+            )),
+        }
+    }
+
+    // poll every live uplink with `poll`, failing the uplinks that return an error
+    fn poll_all(
+        &mut self,
+        cx: &mut Context,
+        mut poll: impl FnMut(
+            Pin<&mut UdpFramed<PacketCodec>>,
+            &mut Context,
+        ) -> Poll<Result<(), io::Error>>,
+    ) -> Poll<Result<(), io::Error>> {
+        let mut pending = false;
+        for idx in 0..self.uplinks.len() {
+            if self.uplinks[idx].failed {
+                continue;
+            }
+            match poll(Pin::new(&mut self.uplinks[idx].sock), cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => self.fail(idx, e),
+                Poll::Pending => pending = true,
+            }
+        }
+        self.all_failed()?;
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl Stream for BondedSocket {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let count = self.uplinks.len();
+        // start after the uplink received from last, so a busy one doesn't starve the others
+        for i in 0..count {
+            let idx = (self.next_recv + i) % count;
+            if self.uplinks[idx].failed {
+                continue;
+            }
+            if let Poll::Ready(Some(item)) = Pin::new(&mut self.uplinks[idx].sock).poll_next(cx) {
+                self.next_recv = idx + 1;
+                if let Some(seq_num) = item.as_ref().ok().and_then(|(pack, _)| ack_seq_num(pack)) {
+                    if self.acks.len() == ACKS_REMEMBERED {
+                        self.acks.pop_front();
+                    }
+                    self.acks.push_back((seq_num, idx));
+                }
+                return Poll::Ready(Some(item));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for BondedSocket {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_all(cx, |sock, cx| sock.poll_ready(cx))
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: (Packet, SocketAddr)) -> Result<(), Self::Error> {
+        let alive: Vec<usize> = self.alive().map(|(idx, _)| idx).collect();
+        if self.mode == BondingMode::Broadcast && matches!(item.0, Packet::Data(_)) {
+            for idx in alive {
+                if let Err(e) = Pin::new(&mut self.uplinks[idx].sock).start_send(item.clone()) {
+                    self.fail(idx, e);
+                }
+            }
+            return self.all_failed();
+        }
+        let answered = ack2_seq_num(&item.0).and_then(|seq_num| {
+            self.acks
+                .iter()
+                .find(|(ack, idx)| *ack == seq_num && !self.uplinks[*idx].failed)
+                .map(|(_, idx)| *idx)
+        });
+        let idx = if handshake(&item.0).is_some() {
+            // the peer recognizes the other uplinks once it knows which connection they're for
+            alive[0]
+        } else if let Some(idx) = answered {
+            idx
+        } else if let (BondingMode::Balancing, Packet::Data(_), Some(idx)) =
+            (self.mode, &item.0, self.next_weighted())
+        {
+            idx
+        } else {
+            self.next_send = self.next_send.wrapping_add(1);
+            alive[self.next_send % alive.len()]
+        };
+        if let Err(e) = Pin::new(&mut self.uplinks[idx].sock).start_send(item) {
+            // the packet is lost, like it could be on the way, and retransmitted
+            self.fail(idx, e);
+            self.all_failed()?;
+        }
+        Ok(())
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_all(cx, |sock, cx| sock.poll_flush(cx))
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_all(cx, |sock, cx| sock.poll_close(cx))
+    }
+}
+
+struct Peer {
+    /// The socket id the peer's packets are sent to, once it was told in a handshake
+    sockid: Option<SocketID>,
+
+    /// The uplinks of the peer, with when they were last heard from. The first one is the address the connection
+    /// sees the peer as.
+    links: Vec<(SocketAddr, Instant)>,
+
+    next_send: usize,
+
+    /// The data received from any uplink, to drop what arrives again over another
+    received: DedupWindow,
+
+    /// The ACKs sent to the peer and not answered yet, with the uplink they went over and when
+    acks: VecDeque<(i32, SocketAddr, Instant)>,
+}
+
+impl Peer {
+    fn last_heard(&self) -> Option<Instant> {
+        self.links.iter().map(|(_, heard)| *heard).max()
+    }
+
+    fn live_links(&self, now: Instant) -> Vec<SocketAddr> {
+        self.links
+            .iter()
+            .filter(|(_, heard)| now - *heard < LINK_TIMEOUT)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    fn next_link(&mut self, now: Instant) -> SocketAddr {
+        let live = self.live_links(now);
+        if live.is_empty() {
+            // nothing heard lately, the most recent is the best guess
+            let (addr, _) = self.links.iter().max_by_key(|(_, heard)| *heard).unwrap();
+            return *addr;
+        }
+        self.next_send = self.next_send.wrapping_add(1);
+        live[self.next_send % live.len()]
+    }
+
+    // the live link with the best score, or the next one in turn until they're all rated
+    fn best_link(&mut self, stats: &AggregatorStats, now: Instant) -> SocketAddr {
+        let peer = self.links[0].0;
+        let mut best = None;
+        for link in self.live_links(now) {
+            let score = match stats.quality(peer, link) {
+                Some(quality) if quality.rtt.is_some() => quality.score(),
+                _ => return self.next_link(now),
+            };
+            if matches!(best, Some((_, best_score)) if best_score >= score) {
+                continue;
+            }
+            best = Some((link, score));
+        }
+        match best {
+            Some((link, _)) => {
+                stats.sending_over(peer, link, now);
+                link
+            }
+            None => self.next_link(now),
+        }
+    }
+
+    fn ack_sent(&mut self, seq_num: i32, link: SocketAddr, now: Instant) {
+        self.acks.push_back((seq_num, link, now));
+    }
+
+    // the ACKs sent before one that's answered won't be anymore, as only the latest is
+    fn ack_answered(
+        &mut self,
+        seq_num: i32,
+        from: SocketAddr,
+        stats: &AggregatorStats,
+        now: Instant,
+    ) {
+        let peer = self.links[0].0;
+        while let Some(&(ack, link, sent)) = self.acks.front() {
+            if ack > seq_num {
+                break;
+            }
+            self.acks.pop_front();
+            stats.update(peer, link, |quality| {
+                if ack == seq_num && link == from {
+                    quality.answered(now - sent)
+                } else {
+                    quality.unanswered()
+                }
+            });
+        }
+        self.expire_acks(stats, now);
+    }
+
+    fn expire_acks(&mut self, stats: &AggregatorStats, now: Instant) {
+        let peer = self.links[0].0;
+        while let Some(&(_, link, sent)) = self.acks.front() {
+            if now - sent < ACK_TIMEOUT {
+                break;
+            }
+            self.acks.pop_front();
+            stats.update(peer, link, LinkQuality::unanswered);
+        }
+    }
+}
+
+/// Gathers the uplinks of bonded peers in front of a socket, see the [module documentation](self)
+///
+/// Packets from peers that aren't bonded go through unchanged. What is sent to a bonded peer goes over the uplinks it
+/// was heard from in the last couple of seconds, ACKs in turn and the rest over the best rated one.
+pub struct Aggregator<T> {
+    inner: T,
+
+    /// The peers, by the address the connection sees them as
+    peers: HashMap<SocketAddr, Peer>,
+
+    /// The address the connection sees every uplink as
+    links: HashMap<SocketAddr, SocketAddr>,
+
+    /// The address the connection sees a peer as, by the socket its packets are sent to
+    sockids: HashMap<SocketID, SocketAddr>,
+
+    /// What packets' arrival is timestamped against, for the jitter of each uplink
+    time_base: TimeBase,
+
+    stats: AggregatorStats,
+}
+
+impl<T> Aggregator<T> {
+    /// Aggregate the peers of `inner`, typically a `UdpFramed<PacketCodec>`
+    pub fn new(inner: T) -> Self {
+        Aggregator {
+            inner,
+            peers: HashMap::new(),
+            links: HashMap::new(),
+            sockids: HashMap::new(),
+            time_base: TimeBase::new(Instant::now()),
+            stats: AggregatorStats::default(),
+        }
+    }
+
+    /// A handle to the statistics of the peers, which stays valid once the aggregator is handed to a connection
+    pub fn stats(&self) -> AggregatorStats {
+        self.stats.clone()
+    }
+
+    // the address the connection sees the sender of `pack` as
+    fn aggregate(&mut self, pack: &Packet, from: SocketAddr, now: Instant) -> SocketAddr {
+        let addr = match self.links.get(&from) {
+            Some(addr) => *addr,
+            None => match self.sockids.get(&pack.dest_sockid()) {
+                // another uplink of a peer already connected
+                Some(addr) => {
+                    info!("Bonding {} with {}", from, addr);
+                    self.peers.get_mut(addr).unwrap().links.push((from, now));
+                    self.links.insert(from, *addr);
+                    self.stats.update(*addr, from, |_| {});
+                    *addr
+                }
+                _ => {
+                    self.forget_idle(now);
+                    self.peers.insert(
+                        from,
+                        Peer {
+                            sockid: None,
+                            links: vec![(from, now)],
+                            next_send: 0,
+                            received: DedupWindow::new(),
+                            acks: VecDeque::new(),
+                        },
+                    );
+                    self.links.insert(from, from);
+                    self.stats.update(from, from, |_| {});
+                    from
+                }
+            },
+        };
+
+        let peer = self.peers.get_mut(&addr).unwrap();
+        if let Some(link) = peer.links.iter_mut().find(|(link, _)| *link == from) {
+            if now - link.1 >= LINK_TIMEOUT {
+                info!("Uplink {} of {} is back", from, addr);
+                self.stats.update(addr, from, |quality| quality.broke(now));
+            }
+            link.1 = now;
+        }
+        if let Some(seq_num) = ack2_seq_num(pack) {
+            peer.ack_answered(seq_num, from, &self.stats, now);
+        }
+        addr
+    }
+
+    fn forget_idle(&mut self, now: Instant) {
+        let idle: Vec<SocketAddr> = self
+            .peers
+            .iter()
+            .filter(
+                |(_, peer)| matches!(peer.last_heard(), Some(heard) if now - heard > PEER_TIMEOUT),
+            )
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in idle {
+            let peer = self.peers.remove(&addr).unwrap();
+            for (link, _) in &peer.links {
+                self.links.remove(link);
+            }
+            if let Some(sockid) = peer.sockid {
+                self.sockids.remove(&sockid);
+            }
+            self.stats.forget(addr);
+        }
+    }
+}
+
+impl<T> Stream for Aggregator<T>
+where
+    T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>> + Unpin,
+{
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match futures::ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok((pack, from))) => {
+                    let now = Instant::now();
+                    let addr = this.aggregate(&pack, from, now);
+                    if let Packet::Data(data) = &pack {
+                        let peer = this.peers.get_mut(&addr).unwrap();
+                        let duplicate = !peer.received.insert(data.seq_number);
+                        let bytes = data.payload.len();
+                        this.stats.data_packet(addr, from, bytes, duplicate, now);
+                        if !data.retransmitted {
+                            let arrived = this.time_base.timestamp_from(now);
+                            this.stats.update(addr, from, |quality| {
+                                quality.data_arrived(arrived, data.timestamp)
+                            });
+                        }
+                        if duplicate {
+                            continue;
+                        }
+                    }
+                    return Poll::Ready(Some(Ok((pack, addr))));
+                }
+                other => return Poll::Ready(other),
+            }
+        }
+    }
+}
+
+impl<T> Sink<(Packet, SocketAddr)> for Aggregator<T>
+where
+    T: Sink<(Packet, SocketAddr), Error = io::Error> + Unpin,
+{
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_ready(cx)
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (pack, to): (Packet, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        let to = match (this.peers.get_mut(&to), handshake(&pack)) {
+            // the handshake goes back the way it came, and tells which socket the other uplinks will send to
+            (Some(peer), Some(shake)) => {
+                if let Some(old) = peer.sockid.replace(shake.socket_id) {
+                    this.sockids.remove(&old);
+                }
+                this.sockids.insert(shake.socket_id, to);
+                to
+            }
+            (Some(peer), None) => {
+                let now = Instant::now();
+                match ack_seq_num(&pack) {
+                    Some(seq_num) => {
+                        peer.expire_acks(&this.stats, now);
+                        let link = peer.next_link(now);
+                        peer.ack_sent(seq_num, link, now);
+                        link
+                    }
+                    None => peer.best_link(&this.stats, now),
+                }
+            }
+            (None, _) => to,
+        };
+        Pin::new(&mut this.inner).start_send((pack, to))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
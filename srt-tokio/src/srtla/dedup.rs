@@ -0,0 +1,71 @@
+use srt_protocol::SeqNumber;
+
+/// How many sequence numbers behind the highest one a duplicate is still recognized
+const WINDOW: u32 = 8192;
+
+/// The data packets of a peer received lately, to drop the copies that arrive again over another uplink
+///
+/// A bit per sequence number in a ring, so checking and recording a packet is O(1).
+pub struct DedupWindow {
+    bits: Vec<u64>,
+    highest: Option<SeqNumber>,
+}
+
+impl DedupWindow {
+    pub fn new() -> Self {
+        DedupWindow {
+            bits: vec![0; (WINDOW / 64) as usize],
+            highest: None,
+        }
+    }
+
+    /// Record that `seq` was received, `false` if it already was
+    pub fn insert(&mut self, seq: SeqNumber) -> bool {
+        let highest = match self.highest {
+            Some(highest) => highest,
+            None => {
+                self.highest = Some(seq);
+                self.set(seq);
+                return true;
+            }
+        };
+        if seq > highest {
+            let ahead = seq - highest;
+            if ahead >= WINDOW {
+                self.bits.iter_mut().for_each(|word| *word = 0);
+            } else {
+                // forget what was received a window ago
+                for n in 1..ahead {
+                    self.clear(highest + n);
+                }
+            }
+            self.highest = Some(seq);
+            self.set(seq);
+            true
+        } else if highest - seq >= WINDOW {
+            // too old to tell, the connection drops it if it was received
+            true
+        } else {
+            !self.set(seq)
+        }
+    }
+
+    fn index(seq: SeqNumber) -> (usize, u64) {
+        // the sequence numbers wrap at a multiple of the window, so the ring does too
+        let bit = seq.as_raw() % WINDOW;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    // returns if it was set already
+    fn set(&mut self, seq: SeqNumber) -> bool {
+        let (word, mask) = Self::index(seq);
+        let was_set = self.bits[word] & mask != 0;
+        self.bits[word] |= mask;
+        was_set
+    }
+
+    fn clear(&mut self, seq: SeqNumber) {
+        let (word, mask) = Self::index(seq);
+        self.bits[word] &= !mask;
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use crate::protocol::{jitter::InterarrivalJitter, TimeStamp};
+
+/// How long a break weighs on the score of a link
+const BREAK_MEMORY: Duration = Duration::from_secs(30);
+
+/// How well an uplink of a bonded peer does lately, as the [`Aggregator`](super::Aggregator) sees it
+///
+/// The round trip is timed from each ACK the aggregator sends over the uplink to the ACK2 answering it, which a
+/// [`BondedSocket`](super::BondedSocket) sends back the same way. An ACK left unanswered counts as lost, including
+/// one overtaken by a later ACK over a faster uplink, as the peer only answers the latest. The jitter is of the data
+/// arriving over the uplink, see [`InterarrivalJitter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkQuality {
+    /// The smoothed round trip time, once one was measured
+    pub rtt: Option<Duration>,
+
+    /// How much the round trip time varies
+    pub rtt_var: Duration,
+
+    /// How much the time data takes to arrive over the uplink varies, the inter-arrival jitter of RFC 3550
+    pub jitter: Duration,
+
+    /// The smoothed share of ACKs that weren't answered, from 0 to 1
+    pub loss: f64,
+
+    /// How many times the uplink went silent for a couple of seconds and came back
+    pub breaks: u32,
+
+    /// When it last came back from a break
+    pub last_break: Option<Instant>,
+
+    arrivals: InterarrivalJitter,
+}
+
+impl Default for LinkQuality {
+    fn default() -> Self {
+        LinkQuality {
+            rtt: None,
+            rtt_var: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+            loss: 0.0,
+            breaks: 0,
+            last_break: None,
+            arrivals: InterarrivalJitter::default(),
+        }
+    }
+}
+
+impl LinkQuality {
+    /// A score from 0 to 1, higher is better, to compare uplinks
+    ///
+    /// Each 100ms of round trip, and each 50ms of jitter, weigh as much as the link itself, the share of ACKs lost is
+    /// taken off, and a link that broke in the last 30 seconds counts half.
+    pub fn score(&self) -> f64 {
+        let rtt = self.rtt.map_or(0.0, |rtt| rtt.as_secs_f64() * 10.0);
+        let jitter = self.jitter.as_secs_f64() * 20.0;
+        let broke = match self.last_break {
+            Some(at) if at.elapsed() < BREAK_MEMORY => 0.5,
+            _ => 1.0,
+        };
+        (1.0 - self.loss) / (1.0 + rtt + jitter) * broke
+    }
+
+    pub(crate) fn answered(&mut self, rtt: Duration) {
+        match self.rtt {
+            None => {
+                self.rtt = Some(rtt);
+                self.rtt_var = rtt / 2;
+            }
+            Some(srtt) => {
+                let diff = if rtt > srtt { rtt - srtt } else { srtt - rtt };
+                self.rtt_var = (self.rtt_var * 3 + diff) / 4;
+                self.rtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+        self.loss -= self.loss / 16.0;
+    }
+
+    pub(crate) fn data_arrived(&mut self, arrived: TimeStamp, timestamp: TimeStamp) {
+        self.arrivals.on_packet(arrived, timestamp);
+        self.jitter = self.arrivals.jitter();
+    }
+
+    pub(crate) fn unanswered(&mut self) {
+        self.loss += (1.0 - self.loss) / 16.0;
+    }
+
+    pub(crate) fn broke(&mut self, now: Instant) {
+        self.breaks += 1;
+        self.last_break = Some(now);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::LinkQuality;
+
+/// What an [`Aggregator`](super::Aggregator) received over one uplink of a bonded peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkStats {
+    /// The address of the uplink
+    pub addr: SocketAddr,
+
+    /// Data packets received over it, duplicates included
+    pub data_packets: u64,
+
+    /// Data packets dropped because they had already arrived over another uplink
+    pub duplicates: u64,
+
+    /// Data packets that arrived over it first, and were delivered
+    pub delivered: u64,
+
+    /// How well it does lately
+    pub quality: LinkQuality,
+}
+
+/// What an [`Aggregator`](super::Aggregator) received from a bonded peer, over all of its uplinks
+///
+/// The aggregator sends back over the best rated uplink, see [`LinkQuality`]. Its first uplink, the address the
+/// connection sees the peer as, is the main one, the others are backups.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BondStats {
+    /// Each uplink, by the address it sends from, in the order they were first heard from
+    pub links: Vec<LinkStats>,
+
+    /// Data packets delivered to the connection, without the duplicates
+    pub delivered_packets: u64,
+
+    /// The payload bytes of the data packets delivered
+    pub delivered_bytes: u64,
+
+    /// How many times the aggregator switched to another uplink to send back over
+    pub switchovers: u64,
+
+    first_data: Option<Instant>,
+    sending_over: Option<SocketAddr>,
+    on_backup_since: Option<Instant>,
+    backup_time: Duration,
+}
+
+impl BondStats {
+    /// The duplicates dropped over every uplink
+    pub fn duplicates(&self) -> u64 {
+        self.links.iter().map(|link| link.duplicates).sum()
+    }
+
+    /// The payload bytes delivered a second, on average since the first data
+    pub fn delivered_rate(&self) -> f64 {
+        match self.first_data {
+            Some(first) if first.elapsed() > Duration::from_secs(0) => {
+                self.delivered_bytes as f64 / first.elapsed().as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The share of the delivered data that arrived over `link` first, from 0 to 1
+    pub fn contribution(&self, link: SocketAddr) -> f64 {
+        match self.links.iter().find(|stats| stats.addr == link) {
+            Some(stats) if self.delivered_packets > 0 => {
+                stats.delivered as f64 / self.delivered_packets as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The uplink the aggregator sends back over, once the uplinks are rated
+    pub fn sending_over(&self) -> Option<SocketAddr> {
+        self.sending_over
+    }
+
+    /// How long the aggregator has sent back over a backup uplink rather than the main one
+    pub fn time_on_backup(&self) -> Duration {
+        match self.on_backup_since {
+            Some(since) => self.backup_time + since.elapsed(),
+            None => self.backup_time,
+        }
+    }
+
+    /// The uplink with the best [`score`](LinkQuality::score), if any
+    pub fn best_link(&self) -> Option<&LinkStats> {
+        self.links
+            .iter()
+            .max_by(|a, b| a.quality.score().partial_cmp(&b.quality.score()).unwrap())
+    }
+
+    fn link(&mut self, addr: SocketAddr) -> &mut LinkStats {
+        match self.links.iter().position(|link| link.addr == addr) {
+            Some(idx) => &mut self.links[idx],
+            None => {
+                self.links.push(LinkStats {
+                    addr,
+                    data_packets: 0,
+                    duplicates: 0,
+                    delivered: 0,
+                    quality: LinkQuality::default(),
+                });
+                self.links.last_mut().unwrap()
+            }
+        }
+    }
+}
+
+/// A handle to the statistics of the peers of an [`Aggregator`](super::Aggregator), see
+/// [`Aggregator::stats`](super::Aggregator::stats)
+///
+/// Peers are by the address the connection sees them as, the first uplink they were heard from. They're removed once
+/// the aggregator forgets them.
+#[derive(Debug, Clone, Default)]
+pub struct AggregatorStats(Arc<Mutex<HashMap<SocketAddr, BondStats>>>);
+
+impl AggregatorStats {
+    /// The statistics of `peer`, if it's known
+    pub fn peer(&self, peer: SocketAddr) -> Option<BondStats> {
+        self.0.lock().unwrap().get(&peer).cloned()
+    }
+
+    /// Every known peer, with its statistics
+    pub fn peers(&self) -> Vec<(SocketAddr, BondStats)> {
+        let mut all: Vec<_> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(addr, stats)| (*addr, stats.clone()))
+            .collect();
+        all.sort_by_key(|(addr, _)| *addr);
+        all
+    }
+
+    pub(crate) fn data_packet(
+        &self,
+        peer: SocketAddr,
+        link: SocketAddr,
+        bytes: usize,
+        duplicate: bool,
+        now: Instant,
+    ) {
+        let mut peers = self.0.lock().unwrap();
+        let bond = peers.entry(peer).or_default();
+        if !duplicate {
+            bond.first_data.get_or_insert(now);
+            bond.delivered_packets += 1;
+            bond.delivered_bytes += bytes as u64;
+        }
+        let link = bond.link(link);
+        link.data_packets += 1;
+        if duplicate {
+            link.duplicates += 1;
+        } else {
+            link.delivered += 1;
+        }
+    }
+
+    pub(crate) fn sending_over(&self, peer: SocketAddr, link: SocketAddr, now: Instant) {
+        let mut peers = self.0.lock().unwrap();
+        let bond = peers.entry(peer).or_default();
+        match bond.sending_over.replace(link) {
+            Some(previous) if previous == link => return,
+            Some(_) => bond.switchovers += 1,
+            None => {}
+        }
+        // the first uplink of a peer is the address it's known by
+        match (link == peer, bond.on_backup_since) {
+            (true, Some(since)) => {
+                bond.backup_time += now - since;
+                bond.on_backup_since = None;
+            }
+            (false, None) => bond.on_backup_since = Some(now),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn quality(&self, peer: SocketAddr, link: SocketAddr) -> Option<LinkQuality> {
+        let peers = self.0.lock().unwrap();
+        let links = &peers.get(&peer)?.links;
+        links
+            .iter()
+            .find(|stats| stats.addr == link)
+            .map(|stats| stats.quality)
+    }
+
+    pub(crate) fn update(
+        &self,
+        peer: SocketAddr,
+        link: SocketAddr,
+        f: impl FnOnce(&mut LinkQuality),
+    ) {
+        let mut peers = self.0.lock().unwrap();
+        f(&mut peers.entry(peer).or_default().link(link).quality);
+    }
+
+    pub(crate) fn forget(&self, peer: SocketAddr) {
+        self.0.lock().unwrap().remove(&peer);
+    }
+}
@@ -0,0 +1,241 @@
+//! Kernel receive timestamps, see [`SrtSocketBuilder::kernel_timestamps`](crate::SrtSocketBuilder::kernel_timestamps)
+//!
+//! On Linux the socket asks for `SO_TIMESTAMPING` software receive timestamps, and reads them from the control
+//! messages of each datagram. The connection then uses when the kernel got a packet instead of when the task
+//! dequeued it, so the arrival speed and the clock drift estimates don't pick up the scheduler's jitter.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+/// When the packet last read from a [`TimestampedSocket`] arrived, shared with the connection's task
+#[derive(Debug, Clone, Default)]
+pub struct ArrivalTime(Arc<Mutex<Option<SystemTime>>>);
+
+impl ArrivalTime {
+    /// When the last packet arrived, on the clock that reads `now`. `None` if the kernel didn't timestamp it.
+    pub fn take(&self, now: Instant) -> Option<Instant> {
+        let arrived = self.0.lock().unwrap().take()?;
+        // the kernel timestamps on the wall clock, so this only keeps how long ago it was
+        let age = SystemTime::now()
+            .duration_since(arrived)
+            .unwrap_or_default();
+        Some(now.checked_sub(age).unwrap_or(now))
+    }
+
+    #[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+    fn set(&self, arrived: Option<SystemTime>) {
+        *self.0.lock().unwrap() = arrived;
+    }
+}
+
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub(crate) use linux::to_socket_addr;
+#[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+pub use linux::TimestampedSocket;
+
+#[cfg(all(feature = "kernel-timestamps", target_os = "linux"))]
+#[allow(unsafe_code)]
+mod linux {
+    use std::io;
+    use std::mem;
+    use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+    use std::os::unix::io::AsRawFd;
+    use std::pin::Pin;
+    use std::ptr;
+    use std::task::{Context, Poll};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use bytes::BytesMut;
+    use futures::{ready, Sink, Stream};
+    use mio::Ready;
+    use tokio::io::PollEvented;
+
+    use super::ArrivalTime;
+    use crate::codec::{self, MAX_DATAGRAM};
+    use crate::{Packet, PacketParseError};
+
+    /// A UDP socket that reads the kernel receive timestamp of every packet, framed like a
+    /// `UdpFramed<PacketCodec>`
+    pub struct TimestampedSocket {
+        io: PollEvented<mio::net::UdpSocket>,
+        arrival: ArrivalTime,
+        /// Where the next datagrams are read, the packets parsed from those before keep the rest of the slab
+        recv_buf: BytesMut,
+        send_buf: Option<(BytesMut, SocketAddr)>,
+    }
+
+    impl TimestampedSocket {
+        pub fn bind(addr: SocketAddr) -> Result<Self, io::Error> {
+            let sock = net::UdpSocket::bind(addr)?;
+            sock.set_nonblocking(true)?;
+
+            let flags = libc::SOF_TIMESTAMPING_RX_SOFTWARE | libc::SOF_TIMESTAMPING_SOFTWARE;
+            // SAFETY: the option value is a c_uint that outlives the call
+            let res = unsafe {
+                libc::setsockopt(
+                    sock.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_TIMESTAMPING,
+                    &flags as *const libc::c_uint as *const libc::c_void,
+                    mem::size_of::<libc::c_uint>() as libc::socklen_t,
+                )
+            };
+            if res != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(TimestampedSocket {
+                io: PollEvented::new(mio::net::UdpSocket::from_socket(sock)?)?,
+                arrival: ArrivalTime::default(),
+                recv_buf: codec::zeroed_slab(),
+                send_buf: None,
+            })
+        }
+
+        /// The arrival time of the packets as they're read, for the connection's task
+        pub fn arrival_time(&self) -> ArrivalTime {
+            self.arrival.clone()
+        }
+    }
+
+    impl Stream for TimestampedSocket {
+        type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                ready!(this.io.poll_read_ready(cx, Ready::readable()))?;
+                if this.recv_buf.len() < MAX_DATAGRAM {
+                    this.recv_buf = codec::zeroed_slab();
+                }
+                match recv_timestamped(this.io.get_ref(), &mut this.recv_buf[..MAX_DATAGRAM]) {
+                    Ok((len, from, arrived)) => {
+                        this.arrival.set(arrived);
+                        let packet = codec::parse(this.recv_buf.split_to(len).freeze());
+                        return Poll::Ready(Some(packet.map(|packet| (packet, from))));
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        this.io.clear_read_ready(cx, Ready::readable())?;
+                    }
+                    Err(e) => return Poll::Ready(Some(Err(e.into()))),
+                }
+            }
+        }
+    }
+
+    impl Sink<(Packet, SocketAddr)> for TimestampedSocket {
+        type Error = io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+            self.poll_flush(cx)
+        }
+
+        fn start_send(
+            self: Pin<&mut Self>,
+            (packet, to): (Packet, SocketAddr),
+        ) -> Result<(), Self::Error> {
+            let mut buf = BytesMut::new();
+            packet.serialize(&mut buf);
+            self.get_mut().send_buf = Some((buf, to));
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+            let this = self.get_mut();
+            while let Some((buf, to)) = &this.send_buf {
+                ready!(this.io.poll_write_ready(cx))?;
+                match this.io.get_ref().send_to(buf, to) {
+                    Ok(_) => this.send_buf = None,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        this.io.clear_write_ready(cx)?;
+                    }
+                    Err(e) => {
+                        this.send_buf = None;
+                        return Poll::Ready(Err(e));
+                    }
+                }
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    // recvmsg, with the software receive timestamp from the control messages if there is one
+    fn recv_timestamped(
+        sock: &mio::net::UdpSocket,
+        buf: &mut [u8],
+    ) -> Result<(usize, SocketAddr, Option<SystemTime>), io::Error> {
+        // room for a scm_timestamping (three timespecs), aligned for cmsghdr
+        let mut control = [0u64; 16];
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = &mut addr as *mut libc::sockaddr_storage as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        // SAFETY: every pointer in msg points to a live buffer of the length given
+        let len = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut arrived = None;
+        // SAFETY: the kernel filled msg_control with msg_controllen bytes of control messages
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET
+                    && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPING
+                {
+                    // the software timestamp is the first of the three
+                    let ts: libc::timespec = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const _);
+                    if ts.tv_sec != 0 || ts.tv_nsec != 0 {
+                        arrived =
+                            Some(UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        Ok((len as usize, to_socket_addr(&addr)?, arrived))
+    }
+
+    pub(crate) fn to_socket_addr(addr: &libc::sockaddr_storage) -> Result<SocketAddr, io::Error> {
+        // SAFETY: the family says which sockaddr the storage holds
+        match addr.ss_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in) };
+                Ok(SocketAddrV4::new(
+                    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                    u16::from_be(addr.sin_port),
+                )
+                .into())
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(addr as *const _ as *const libc::sockaddr_in6) };
+                Ok(SocketAddrV6::new(
+                    Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                    u16::from_be(addr.sin6_port),
+                    addr.sin6_flowinfo,
+                    addr.sin6_scope_id,
+                )
+                .into())
+            }
+            family => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected address family {}", family),
+            )),
+        }
+    }
+}
@@ -1,3 +1,7 @@
 mod socket;
 
-pub use socket::{create_bidrectional_srt, SrtSocket};
+pub(crate) use socket::create_bidrectional_srt_raw;
+pub use socket::{
+    create_bidrectional_srt, create_bidrectional_srt_driven, CloseReason, ConnectionDriver,
+    SpawnPolicy, SrtRecvHalf, SrtSendHalf, SrtSender, SrtSocket,
+};
@@ -1,60 +1,257 @@
 use crate::packet::ControlTypes::*;
 
+use crate::packet::SrtControlPacket;
 use crate::protocol::connection::{Connection, ConnectionAction};
 use crate::protocol::handshake::Handshake;
 use crate::protocol::receiver::{Receiver, ReceiverAlgorithmAction};
 use crate::protocol::sender::{Sender, SenderAlgorithmAction};
 use crate::protocol::TimeBase;
+use crate::timestamping::ArrivalTime;
+use crate::util::{is_transient, PacketBudget};
+use crate::watchdog::Watchdog;
 use crate::Packet::*;
-use crate::{ConnectionSettings, ControlPacket, Packet};
+use crate::{
+    ConnectionEvent, ConnectionInfo, ConnectionSettings, ConnectionSnapshot, ControlPacket,
+    DebugDump, EventRecord, Packet, PacketDirection, PacketParseError, Priority, SendBufferLevel,
+    SocketStatistics, SourceValidation, TaskStage,
+};
+use srt_protocol::{ControlHistory, EventLog};
 
+use std::collections::VecDeque;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
 use std::{
-    io, mem,
+    error::Error,
+    fmt,
+    io::{self, IoSlice},
+    mem,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::channel::{mpsc, oneshot};
 use futures::prelude::*;
+use futures::stream::FusedStream;
 use futures::{future, ready, select};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
+use tokio::runtime::Handle;
 use tokio::time::delay_until;
 
 /// Connected SRT connection, generally created with [`SrtSocketBuilder`](crate::SrtSocketBuilder).
 ///
 /// These are bidirectional sockets, meaning data can be sent in either direction.
-/// Use the `Stream + Sink` implementatino to send or receive data.
+/// Use the `Stream + Sink` implementatino to send or receive data. Sending and receiving use independent buffers
+/// and latencies, see [`ConnectionSettings::send_tsbpd_latency`] and [`ConnectionSettings::recv_tsbpd_latency`].
 ///
 /// The sockets yield and consume `(Instant, Bytes)`, representng the data and the origin instant. This instant
 /// defines when the packet will be released on the receiving side, at more or less one latency later.
+///
+/// Use [`send_with_priority`](SrtSocket::send_with_priority) for data that can be dropped before other data when
+/// the connection can't keep up, sending through the `Sink` implementation uses [`Priority::Normal`].
+///
+/// Use [`split`](SrtSocket::split) to send and receive from different tasks.
 pub struct SrtSocket {
-    // receiver datastructures
-    recvr: mpsc::Receiver<(Instant, Bytes)>,
+    recv: SrtRecvHalf,
+    send: SrtSendHalf,
+
+    // the task driving the connection, until it's taken, with SpawnPolicy::Manual
+    driver: Option<ConnectionDriver>,
+}
+
+/// The task driving a connection, left to the application with [`SpawnPolicy::Manual`]
+///
+/// It runs until the connection closes. Dropping it tears the connection down there and then: the socket is closed,
+/// the [`SrtSocket`] stops receiving and fails to send, and its [`close_reason`](SrtSocket::close_reason) is
+/// [`CloseReason::Aborted`] by the time it sees so.
+pub type ConnectionDriver = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// records that the connection's task was dropped, before its channels are, so the socket sees why it ended as soon as
+// it sees it end
+struct Abortable<F> {
+    task: F,
+    close_reason: Arc<Mutex<Option<CloseReason>>>,
+}
 
-    // sender datastructures
-    sender: mpsc::Sender<(Instant, Bytes)>,
+impl<F: Future + Unpin> Future for Abortable<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.task).poll(cx)
+    }
+}
+
+impl<F> Drop for Abortable<F> {
+    fn drop(&mut self) {
+        self.close_reason
+            .lock()
+            .unwrap()
+            .get_or_insert(CloseReason::Aborted);
+    }
+}
+
+/// Where the task driving each connection runs, see
+/// [`SrtSocketBuilder::spawn_policy`](crate::SrtSocketBuilder::spawn_policy)
+#[derive(Debug, Clone)]
+pub enum SpawnPolicy {
+    /// Spawned with `tokio::spawn`, on the runtime the connection is made from
+    Current,
+    /// Spawned on the runtime of a handle, such as one dedicated to networking
+    Handle(Handle),
+    /// Not spawned, the application takes it with [`SrtSocket::take_driver`] and runs it however it likes
+    Manual,
+}
+
+impl Default for SpawnPolicy {
+    fn default() -> Self {
+        SpawnPolicy::Current
+    }
+}
+
+impl SpawnPolicy {
+    // the driver back if it's left to the application
+    pub(crate) fn spawn(&self, driver: ConnectionDriver) -> Option<ConnectionDriver> {
+        match self {
+            SpawnPolicy::Current => {
+                tokio::spawn(driver);
+                None
+            }
+            SpawnPolicy::Handle(handle) => {
+                handle.spawn(driver);
+                None
+            }
+            SpawnPolicy::Manual => Some(driver),
+        }
+    }
+}
+
+/// The sending half of a [`SrtSocket`], created with [`SrtSocket::split`]
+///
+/// Closing or dropping this half closes the connection once the receiving half has received everything the peer
+/// sent, as closing a [`SrtSocket`] would.
+pub struct SrtSendHalf {
+    sender: mpsc::Sender<(Instant, Bytes, Priority, SendMode)>,
 
-    // agnostic
     close: oneshot::Receiver<()>,
 
     settings: ConnectionSettings,
 
+    // updated by the task every iteration
+    statistics: Arc<Mutex<SocketStatistics>>,
+
+    dump_requests: mpsc::UnboundedSender<oneshot::Sender<DebugDump>>,
+
+    suspend_requests: mpsc::UnboundedSender<oneshot::Sender<ConnectionSnapshot>>,
+
+    // set by the task before it exits
+    close_reason: Arc<Mutex<Option<CloseReason>>>,
+
+    // kept by the task, and after it exits
+    events: Arc<Mutex<EventLog>>,
+
     // shared state to wake up the
     flush_wakeup: Arc<Mutex<(Option<Waker>, bool)>>,
 
     _drop_oneshot: oneshot::Sender<()>,
 }
 
+/// A cheap to clone handle to send data on a connection from several tasks, created with [`SrtSocket::sender`]
+///
+/// All handles share the connection's send queue, so they wait when it is full.
+/// Data sent from one handle is sent in order, data from different handles is sent in the order it was queued.
+///
+/// The connection stays open until every handle, and the socket or its [`SrtSendHalf`], is closed or dropped.
+/// Dropping the socket or its send half closes the connection regardless, after which sending on a handle fails.
+/// Flushing a handle only waits for its data to be queued, flush the socket to wait for the peer to acknowledge it.
+#[derive(Clone)]
+pub struct SrtSender {
+    sender: mpsc::Sender<(Instant, Bytes, Priority, SendMode)>,
+}
+
+/// The receiving half of a [`SrtSocket`], created with [`SrtSocket::split`]
+///
+/// Data received after this half is dropped is discarded.
+pub struct SrtRecvHalf {
+    // what's released in the same iteration of the connection's task comes in one batch
+    recvr: mpsc::Receiver<Vec<(Instant, Bytes)>>,
+
+    // the rest of the last batch
+    released: VecDeque<(Instant, Bytes)>,
+
+    settings: ConnectionSettings,
+
+    // updated by the task every iteration
+    statistics: Arc<Mutex<SocketStatistics>>,
+
+    dump_requests: mpsc::UnboundedSender<oneshot::Sender<DebugDump>>,
+
+    latency_changes: mpsc::UnboundedSender<Duration>,
+
+    // set by the task before it exits
+    close_reason: Arc<Mutex<Option<CloseReason>>>,
+
+    // kept by the task, and after it exits
+    events: Arc<Mutex<EventLog>>,
+}
+
+/// Why a connection closed, see [`SrtSocket::close_reason`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The peer closed the connection
+    PeerShutdown,
+    /// Nothing was heard from the peer for the connection timeout
+    Timeout,
+    /// The underlying socket ended, such as a multiplexer shutting down
+    SocketClosed,
+    /// It was closed locally, by closing the socket or its sending half, or dropping every sender
+    Local,
+    /// The socket or its sending half was dropped without closing it
+    Dropped,
+    /// It was suspended to carry on in another process, see [`SrtSocket::suspend`]
+    Suspended,
+    /// It used up its [`TransferQuota`](crate::TransferQuota)
+    QuotaExceeded,
+    /// The task driving it was dropped before it finished, such as a [`ConnectionDriver`], or the runtime it was
+    /// spawned on shutting down. The peer isn't told, and times out.
+    Aborted,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CloseReason::*;
+        match self {
+            PeerShutdown => write!(f, "Closed by the peer"),
+            Timeout => write!(f, "Timed out waiting for the peer"),
+            SocketClosed => write!(f, "The underlying socket closed"),
+            Local => write!(f, "Closed locally"),
+            Dropped => write!(f, "Dropped locally"),
+            Suspended => write!(f, "Suspended to carry on elsewhere"),
+            QuotaExceeded => write!(f, "Transfer quota exceeded"),
+            Aborted => write!(f, "The connection's task was dropped"),
+        }
+    }
+}
+
+impl Error for CloseReason {}
+
+// how a message is sent, besides its priority
+#[derive(Debug, Clone, Copy)]
+enum SendMode {
+    Reliable,
+    MaxRetransmits(u32),
+    KeepLatest(u64),
+}
+
 #[allow(clippy::large_enum_variant)]
 enum Action {
     Nothing,
     CloseSender,
-    Send(Option<(Instant, Bytes)>),
-    DelegatePacket(Option<(Packet, SocketAddr)>),
+    Send(Option<(Instant, Bytes, Priority, SendMode)>),
+    DelegatePacket(Option<Result<(Packet, SocketAddr), PacketParseError>>),
+    Dump(Option<oneshot::Sender<DebugDump>>),
+    SetLatency(Option<Duration>),
+    Suspend(Option<oneshot::Sender<ConnectionSnapshot>>),
 }
 
 /// This spawns two new tasks:
@@ -68,6 +265,43 @@ where
         + Send
         + Unpin
         + 'static,
+{
+    create_bidrectional_srt_raw(sock.map(Ok), conn, None, &SpawnPolicy::Current)
+}
+
+/// Like [`create_bidrectional_srt`], without spawning anything: the connection runs as long as the application runs
+/// the returned [`ConnectionDriver`], such as for connections accepted through a [`multiplex`](crate::multiplex)
+pub fn create_bidrectional_srt_driven<T>(
+    sock: T,
+    conn: crate::Connection,
+) -> (SrtSocket, ConnectionDriver)
+where
+    T: Stream<Item = (Packet, SocketAddr)>
+        + Sink<(Packet, SocketAddr), Error = io::Error>
+        + Send
+        + Unpin
+        + 'static,
+{
+    let mut socket = create_bidrectional_srt_raw(sock.map(Ok), conn, None, &SpawnPolicy::Manual);
+    let driver = socket.take_driver().unwrap();
+    (socket, driver)
+}
+
+/// Like [`create_bidrectional_srt`], from a socket that also yields the packets that failed to parse, so they're
+/// counted. When each packet arrived is read from `arrival`, if any, as it's received. The task driving the
+/// connection is spawned as `spawn_policy` says.
+pub(crate) fn create_bidrectional_srt_raw<T>(
+    sock: T,
+    conn: crate::Connection,
+    arrival: Option<ArrivalTime>,
+    spawn_policy: &SpawnPolicy,
+) -> SrtSocket
+where
+    T: Stream<Item = Result<(Packet, SocketAddr), PacketParseError>>
+        + Sink<(Packet, SocketAddr), Error = io::Error>
+        + Send
+        + Unpin
+        + 'static,
 {
     let (mut release, recvr) = mpsc::channel(128);
     let (sender, new_data) = mpsc::channel(128);
@@ -78,20 +312,106 @@ where
     let fw = Arc::new(Mutex::new((None as Option<Waker>, true)));
     let flush_wakeup = fw.clone();
 
-    tokio::spawn(async move {
+    let stats = Arc::new(Mutex::new(SocketStatistics::default()));
+    let statistics = stats.clone();
+
+    let (dump_requests, dump_recv) = mpsc::unbounded();
+    let (latency_changes, latency_recv) = mpsc::unbounded();
+    let (suspend_requests, suspend_recv) = mpsc::unbounded();
+    let close_reason = Arc::new(Mutex::new(None));
+    let task_close_reason = close_reason.clone();
+    let watchdog = Watchdog::new(conn.settings.stall_monitor.clone(), stats.clone());
+    let history = Arc::new(Mutex::new(ControlHistory::default()));
+    let ingress_history = history.clone();
+    let egress_history = history.clone();
+    let events = Arc::new(Mutex::new(EventLog::default()));
+    let task_events = events.clone();
+    let ingress_events = events.clone();
+    let egress_events = events.clone();
+
+    let ingress_tap = conn.settings.packet_tap.clone();
+    let ingress_clock = conn.settings.clock.clone();
+    let egress_tap = conn.settings.packet_tap.clone();
+    let egress_clock = conn.settings.clock.clone();
+    let sock = sock
+        .inspect(move |res| {
+            let (pack, from) = match res {
+                Ok(res) => res,
+                Err(_) => return,
+            };
+            if let Control(cp) = pack {
+                let mut history = ingress_history.lock().unwrap();
+                history.record(ingress_clock.now(), PacketDirection::Ingress, cp);
+                if is_key_material(cp) {
+                    let event = ConnectionEvent::KeyMaterial(PacketDirection::Ingress);
+                    ingress_events
+                        .lock()
+                        .unwrap()
+                        .record(ingress_clock.now(), event);
+                }
+            }
+            if let Some(tap) = &ingress_tap {
+                tap.call(ingress_clock.now(), PacketDirection::Ingress, pack, *from);
+            }
+        })
+        .with(move |(pack, to): (Packet, SocketAddr)| {
+            if let Control(cp) = &pack {
+                let mut history = egress_history.lock().unwrap();
+                history.record(egress_clock.now(), PacketDirection::Egress, cp);
+                if is_key_material(cp) {
+                    let event = ConnectionEvent::KeyMaterial(PacketDirection::Egress);
+                    egress_events
+                        .lock()
+                        .unwrap()
+                        .record(egress_clock.now(), event);
+                }
+            }
+            if let Some(tap) = &egress_tap {
+                tap.call(egress_clock.now(), PacketDirection::Egress, &pack, to);
+            }
+            future::ready(Ok::<_, io::Error>((pack, to)))
+        });
+
+    let driver_close_reason = close_reason.clone();
+    let task = Box::pin(async move {
         let mut close_receiver = close_oneshot.fuse();
         let _close_sender = close_send; // exists for drop
         let mut new_data = new_data.fuse();
+        let mut dump_recv = dump_recv.fuse();
+        let mut latency_recv = latency_recv.fuse();
+        let mut suspend_recv = suspend_recv.fuse();
         let mut sock = sock.fuse();
 
+        let clock = conn_copy.settings.clock.clone();
         let time_base = TimeBase::new(conn_copy.settings.socket_start_time);
         let mut connection = Connection::new(conn_copy.settings.clone());
         let mut sender = Sender::new(conn_copy.settings.clone(), conn_copy.handshake);
-        let mut receiver = Receiver::new(conn_copy.settings, Handshake::Connector);
+        let mut receiver = Receiver::new(conn_copy.settings.clone(), Handshake::Connector);
 
         let mut flushed = true;
+        let mut send_buffer_level = 0;
+        let mut budget = PacketBudget::new(conn_copy.settings.packet_budget);
+        // what ended the connection first, if it's closing
+        let mut reason = None;
+        let mut too_late_packets = 0;
+        let record = |event: ConnectionEvent| {
+            task_events.lock().unwrap().record(clock.now(), event);
+        };
+        let connect_stats = &conn_copy.settings.connect_stats;
+        record(ConnectionEvent::Connected {
+            connect_time: connect_stats.connect_time,
+            induction_retries: connect_stats.induction_retries,
+            conclusion_retries: connect_stats.conclusion_retries,
+        });
+        // before the halves see the connection closed, as `release` and `_close_sender` drop after
+        let finish = |reason: CloseReason| {
+            debug!("{:?} closed: {}", conn_copy.settings.local_sockid, reason);
+            record(ConnectionEvent::Closed(reason.to_string()));
+            *task_close_reason.lock().unwrap() = Some(reason);
+        };
         loop {
-            let (sender_timeout, close) = match sender.next_action(Instant::now()) {
+            watchdog.stage(TaskStage::Handling);
+            let (sender_timeout, close) = match sender.next_action(clock.now()) {
                 SenderAlgorithmAction::WaitUntilAck | SenderAlgorithmAction::WaitForData => {
                     (None, false)
                 }
@@ -101,17 +421,47 @@ where
                     (None, true)
                 }
             };
+            // flushing once lets sockets that can batch packets send them together
+            watchdog.stage(TaskStage::Sending);
             while let Some(out) = sender.pop_output() {
-                if let Err(e) = sock.send(out).await {
+                connection.on_packet_sent(&out.0);
+                if let Err(e) = sock.feed(out).await {
                     error!("Error while seding packet: {:?}", e); // TODO: real error handling
                 }
             }
+            if let Err(e) = sock.flush().await {
+                error!("Error while seding packet: {:?}", e);
+            }
+            watchdog.stage(TaskStage::Handling);
+            let metrics = sender.metrics();
+            stats.lock().unwrap().sender = metrics;
+            if let Some(monitor) = &sender.settings().send_buffer_monitor {
+                let level = monitor.level(metrics.buffered_timespan);
+                if level != send_buffer_level {
+                    send_buffer_level = level;
+                    monitor.call(SendBufferLevel {
+                        level,
+                        timespan: metrics.buffered_timespan,
+                        packets: metrics.buffered_packets,
+                    });
+                }
+            }
+            while let Some(event) = sender.pop_mtu_blackhole_event() {
+                record(ConnectionEvent::MtuClamped {
+                    payload_size: event.payload_size,
+                    previous_payload_size: event.previous_payload_size,
+                });
+                if let Some(monitor) = &sender.settings().mtu_blackhole_monitor {
+                    monitor.call(event);
+                }
+            }
 
             if close && receiver.is_flushed() {
                 trace!(
                     "{:?} Send returned close and receiver flushed",
                     sender.settings().local_sockid
                 );
+                finish(reason.unwrap_or(CloseReason::Local));
                 return;
             } else if close {
                 trace!(
@@ -120,24 +470,27 @@ where
                 );
             }
 
+            // released together, so the receiving half is woken once for them all
+            let mut batch = Vec::new();
             let recvr_timeout = loop {
-                match receiver.next_algorithm_action(Instant::now()) {
+                match receiver.next_algorithm_action(clock.now()) {
                     ReceiverAlgorithmAction::TimeBoundedReceive(t2) => {
                         break Some(t2);
                     }
                     ReceiverAlgorithmAction::SendControl(cp, addr) => {
+                        watchdog.stage(TaskStage::Sending);
                         if let Err(e) = sock.send((Packet::Control(cp), addr)).await {
                             error!("Error while sending packet {:?}", e);
                         }
+                        watchdog.stage(TaskStage::Handling);
                     }
-                    ReceiverAlgorithmAction::OutputData(ib) => {
-                        if let Err(e) = release.send(ib).await {
-                            error!("Error while releasing packet {:?}", e);
-                        }
-                    }
+                    ReceiverAlgorithmAction::OutputData(ib) => batch.push(ib),
                     ReceiverAlgorithmAction::Close => {
                         if sender.is_flushed() {
                             trace!("Recv returned close and sender flushed");
+                            watchdog.stage(TaskStage::Releasing);
+                            release_batch(&mut release, batch).await;
+                            finish(reason.unwrap_or(CloseReason::Local));
                             return;
                         } else {
                             trace!(
@@ -149,15 +502,39 @@ where
                     }
                 };
             };
+            if !batch.is_empty() {
+                watchdog.stage(TaskStage::Releasing);
+                release_batch(&mut release, batch).await;
+                watchdog.stage(TaskStage::Handling);
+            }
+            while let Some(event) = receiver.pop_data_idle_event() {
+                if let Some(monitor) = &sender.settings().data_idle_monitor {
+                    monitor.call(event);
+                }
+            }
+            let metrics = receiver.metrics();
+            if metrics.too_late_packets > too_late_packets {
+                record(ConnectionEvent::TooLateDrop {
+                    packets: metrics.too_late_packets - too_late_packets,
+                });
+                too_late_packets = metrics.too_late_packets;
+            }
+            stats.lock().unwrap().receiver = metrics;
+
             let connection_timeout = loop {
-                match connection.next_action(Instant::now()) {
+                match connection.next_action(clock.now()) {
                     ConnectionAction::ContinueUntil(timeout) => break Some(timeout),
                     ConnectionAction::Close => {
+                        if reason != Some(CloseReason::Timeout) {
+                            record(ConnectionEvent::Broken);
+                        }
+                        reason = Some(CloseReason::Timeout);
                         if receiver.is_flushed() {
                             info!(
                                 "{:?} Receiver flush and connection timeout",
                                 sender.settings().local_sockid
                             );
+                            finish(CloseReason::Timeout);
                             return;
                         }
 
@@ -168,10 +545,31 @@ where
 
                         break None;
                     } // timeout
+                    ConnectionAction::QuotaExceeded => {
+                        info!(
+                            "{:?} Transfer quota exceeded, closing",
+                            sender.settings().local_sockid
+                        );
+                        if let Err(e) = sock
+                            .send((
+                                Control(ControlPacket {
+                                    timestamp: time_base.timestamp_from(clock.now()),
+                                    dest_sockid: sender.settings().remote_sockid,
+                                    control_type: Shutdown,
+                                }),
+                                sender.settings().remote,
+                            ))
+                            .await
+                        {
+                            error!("Error while sending shutdown: {:?}", e);
+                        }
+                        finish(CloseReason::QuotaExceeded);
+                        return;
+                    }
                     ConnectionAction::SendKeepAlive => sock
                         .send((
                             Control(ControlPacket {
-                                timestamp: time_base.timestamp_from(Instant::now()),
+                                timestamp: time_base.timestamp_from(clock.now()),
                                 dest_sockid: sender.settings().remote_sockid,
                                 control_type: KeepAlive,
                             }),
@@ -200,7 +598,7 @@ where
 
             let timeout_fut = async {
                 if let Some(to) = timeout {
-                    let now = Instant::now();
+                    let now = clock.now();
                     trace!(
                         "{:?} scheduling wakeup at {}{:?} from {}{}",
                         sender.settings().local_sockid,
@@ -227,6 +625,18 @@ where
                 }
             };
 
+            // with SendDropPolicy::Block, new data waits in the channel until the sender catches up, and once the
+            // channel ended it's skipped, as select! does with terminated streams
+            let blocked = sender.is_blocked(clock.now());
+            let next_data = async {
+                if blocked || new_data.is_terminated() {
+                    future::pending().await
+                } else {
+                    new_data.next().await
+                }
+            };
+
+            watchdog.stage(TaskStage::Waiting);
             let action = select! {
                 // one of the entities requested wakeup
                 _ = timeout_fut.fuse() => Action::Nothing,
@@ -234,35 +644,84 @@ where
                 res = sock.next() =>
                     Action::DelegatePacket(res),
                 // new packet queued
-                res = new_data.next() => {
+                res = next_data.fuse() => {
                     Action::Send(res)
                 }
                 // socket closed
                 _ = close_receiver =>  {
                     Action::CloseSender
                 }
+                // debug dump requested
+                req = dump_recv.next() => Action::Dump(req),
+                // receive latency changed
+                latency = latency_recv.next() => Action::SetLatency(latency),
+                // suspend requested
+                req = suspend_recv.next() => Action::Suspend(req),
             };
             match action {
                 Action::Nothing => {}
                 Action::DelegatePacket(res) => {
+                    // don't starve other tasks if packets keep coming in
+                    budget.spend().await;
                     match res {
-                        Some((pack, from)) => {
-                            connection.on_packet(Instant::now());
+                        Some(Err(e)) if is_transient(&e) => {
+                            debug!("Ignoring receive error: {}", e);
+                        }
+                        Some(Err(e)) => {
+                            warn!("Error parsing packet: {}", e);
+                            receiver.handle_malformed_packet();
+                        }
+                        Some(Ok((_, from)))
+                            if from != sender.settings().remote
+                                && sender.settings().source_validation
+                                    == SourceValidation::Strict =>
+                        {
+                            receiver.handle_spoofed_packet(from);
+                        }
+                        Some(Ok((pack, from))) => {
+                            if from != sender.settings().remote
+                                && sender.settings().source_validation == SourceValidation::Migrate
+                            {
+                                info!(
+                                    "{:?} peer moved from {} to {}",
+                                    sender.settings().local_sockid,
+                                    sender.settings().remote,
+                                    from
+                                );
+                                record(ConnectionEvent::Migrated {
+                                    from: sender.settings().remote,
+                                    to: from,
+                                });
+                                sender.set_remote(from);
+                                receiver.set_remote(from);
+                            }
+                            let now = clock.now();
+                            let arrived = arrival
+                                .as_ref()
+                                .and_then(|arrival| arrival.take(now))
+                                .unwrap_or(now);
+                            connection.on_packet(now, &pack);
                             match &pack {
-                                Data(_) => receiver.handle_packet(Instant::now(), (pack, from)),
+                                Data(_) => {
+                                    receiver.handle_timestamped_packet(now, arrived, (pack, from))
+                                }
                                 Control(cp) => match &cp.control_type {
                                     // sender-responsble packets
-                                    Handshake(_) | Ack { .. } | Nak(_) | DropRequest { .. } => {
-                                        sender.handle_packet((pack, from), Instant::now()).unwrap();
+                                    Handshake(_) | Ack { .. } | Nak(_) => {
+                                        sender.handle_packet((pack, from), now).unwrap();
                                     }
                                     // receiver-respnsible
-                                    Ack2(_) => receiver.handle_packet(Instant::now(), (pack, from)),
+                                    Ack2(_) | DropRequest { .. } => receiver
+                                        .handle_timestamped_packet(now, arrived, (pack, from)),
                                     // both
                                     Shutdown => {
-                                        sender
-                                            .handle_packet((pack.clone(), from), Instant::now())
-                                            .unwrap();
-                                        receiver.handle_packet(Instant::now(), (pack, from));
+                                        reason.get_or_insert(CloseReason::PeerShutdown);
+                                        sender.handle_packet((pack.clone(), from), now).unwrap();
+                                        receiver.handle_timestamped_packet(
+                                            now,
+                                            arrived,
+                                            (pack, from),
+                                        );
                                     }
                                     // neither--this exists just to keep the connection alive
                                     KeepAlive => {}
@@ -270,6 +729,15 @@ where
                                         dbg!(s);
                                         // unimplemented!("{:?}", s);
                                     }
+                                    Custom { custom_type, .. } => {
+                                        match &sender.settings().control_packet_handler {
+                                            Some(handler) => handler.call(cp),
+                                            None => debug!(
+                                                "Ignoring custom control packet {:#x}",
+                                                custom_type
+                                            ),
+                                        }
+                                    }
                                 },
                             }
                         }
@@ -278,60 +746,673 @@ where
                                 "{:?} Exiting because underlying stream ended",
                                 sender.settings().local_sockid
                             );
+                            finish(CloseReason::SocketClosed);
                             break;
                         }
                     }
                 }
                 Action::Send(res) => match res {
-                    Some(item) => {
+                    Some((time, payload, priority, mode)) => {
                         trace!("{:?} queued packet to send", sender.settings().local_sockid);
-                        sender.handle_data(item, Instant::now());
+                        let data = (time, payload);
+                        match mode {
+                            SendMode::Reliable => {
+                                sender.handle_prioritized_data(data, priority, clock.now())
+                            }
+                            SendMode::MaxRetransmits(max) => {
+                                sender.handle_limited_data(data, priority, max, clock.now())
+                            }
+                            SendMode::KeepLatest(key) => {
+                                sender.handle_latest_data(data, key, clock.now())
+                            }
+                        }
                     }
                     None => {
                         debug!("Incoming data stream closed");
+                        reason.get_or_insert(CloseReason::Local);
                         sender.handle_close();
                     }
                 },
-                Action::CloseSender => sender.handle_close(),
+                Action::CloseSender => {
+                    reason.get_or_insert(CloseReason::Dropped);
+                    sender.handle_close()
+                }
+                Action::Dump(req) => {
+                    if let Some(req) = req {
+                        let settings = sender.settings();
+                        let _ = req.send(DebugDump {
+                            at: clock.now(),
+                            local_sockid: settings.local_sockid,
+                            remote_sockid: settings.remote_sockid,
+                            remote: settings.remote,
+                            info: settings.info(),
+                            sender: sender.dump(),
+                            receiver: receiver.dump(),
+                            connection: connection.dump(),
+                            recent_control: history.lock().unwrap().records(),
+                            events: task_events.lock().unwrap().records(),
+                        });
+                    }
+                }
+                Action::SetLatency(latency) => {
+                    if let Some(latency) = latency {
+                        record(ConnectionEvent::LatencyRaised(latency));
+                        receiver.set_latency(latency, clock.now());
+                    }
+                }
+                Action::Suspend(req) => {
+                    if let Some(req) = req {
+                        // without a shutdown, so the peer waits for the process restoring it
+                        let _ = req.send(ConnectionSnapshot::new(
+                            sender.settings(),
+                            clock.now(),
+                            sender.next_sequence_number(),
+                            receiver.lrsn(),
+                        ));
+                        finish(CloseReason::Suspended);
+                        return;
+                    }
+                }
             }
         }
     });
+    let driver = spawn_policy.spawn(Box::pin(Abortable {
+        task,
+        close_reason: driver_close_reason,
+    }));
 
     SrtSocket {
-        recvr,
-        sender,
-        close: close_recv,
-        settings: conn.settings,
-        flush_wakeup,
-        _drop_oneshot,
+        recv: SrtRecvHalf {
+            recvr,
+            released: VecDeque::new(),
+            settings: conn.settings.clone(),
+            statistics: statistics.clone(),
+            dump_requests: dump_requests.clone(),
+            latency_changes,
+            close_reason: close_reason.clone(),
+            events: events.clone(),
+        },
+        send: SrtSendHalf {
+            sender,
+            close: close_recv,
+            settings: conn.settings,
+            statistics,
+            dump_requests,
+            suspend_requests,
+            close_reason,
+            events,
+            flush_wakeup,
+            _drop_oneshot,
+        },
+        driver,
+    }
+}
+
+fn is_key_material(packet: &ControlPacket) -> bool {
+    matches!(
+        packet.control_type,
+        Srt(SrtControlPacket::KeyManagerRequest(_)) | Srt(SrtControlPacket::KeyManagerResponse(_))
+    )
+}
+
+async fn release_batch(
+    release: &mut mpsc::Sender<Vec<(Instant, Bytes)>>,
+    batch: Vec<(Instant, Bytes)>,
+) {
+    match release.send(batch).await {
+        Err(e) if e.is_disconnected() => trace!("Receiving half dropped, discarding packets"),
+        Err(e) => error!("Error while releasing packets {:?}", e),
+        Ok(()) => {}
     }
 }
 
 impl SrtSocket {
+    pub fn settings(&self) -> &ConnectionSettings {
+        &self.send.settings
+    }
+
+    /// What was negotiated with the peer during the handshake
+    pub fn info(&self) -> ConnectionInfo {
+        self.settings().info()
+    }
+
+    /// A snapshot of the connection's statistics, as of the last iteration of the connection's task
+    pub fn stats(&self) -> SocketStatistics {
+        self.send.stats()
+    }
+
+    /// A snapshot of the connection's internals, for debugging, see [`DebugDump`]
+    ///
+    /// Returns `None` once the connection's task has exited.
+    pub async fn debug_dump(&self) -> Option<DebugDump> {
+        self.send.debug_dump().await
+    }
+
+    /// Why the connection closed, once it did
+    ///
+    /// Receiving ends with `None` however the connection closed, this tells whether the peer closed it or it was cut
+    /// off.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.send.close_reason()
+    }
+
+    /// The last significant events of the connection, oldest first, such as it breaking, the peer moving, or packets
+    /// dropped for arriving too late. Kept after it closes, for post-mortems; [`debug_dump`](SrtSocket::debug_dump)
+    /// has them too while it's open.
+    pub fn events(&self) -> Vec<EventRecord> {
+        self.send.events()
+    }
+
+    /// Split the socket into a sending and a receiving half, so each can be moved to a different task
+    ///
+    /// With [`SpawnPolicy::Manual`], take the driver first, as it's dropped otherwise.
+    pub fn split(self) -> (SrtSendHalf, SrtRecvHalf) {
+        (self.send, self.recv)
+    }
+
+    /// Take the task driving the connection, with [`SpawnPolicy::Manual`], to run it however the application likes.
+    /// Nothing is sent or received until it runs.
+    ///
+    /// Returns `None` if the driver was spawned, or already taken.
+    pub fn take_driver(&mut self) -> Option<ConnectionDriver> {
+        self.driver.take()
+    }
+
+    /// Create a handle to send data on this connection, see [`SrtSender`]
+    pub fn sender(&self) -> SrtSender {
+        self.send.sender()
+    }
+
+    /// Grow the latency data is received with, for when the network turns out to need more margin
+    ///
+    /// Data is released a tenth slower than it arrives until the new latency is reached, so playback doesn't pause.
+    /// The latency can't be lowered, and [`settings`](SrtSocket::settings) keeps the one negotiated in the handshake.
+    pub fn set_latency(&self, latency: Duration) {
+        self.recv.set_latency(latency)
+    }
+
+    /// Send data like the `Sink` implementation does, with a priority other than [`Priority::Normal`]
+    ///
+    /// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if the data isn't queued and acknowledged within
+    /// [`ConnectionSettings::send_timeout`], if any. It may still be sent then.
+    pub async fn send_with_priority(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+    ) -> Result<(), io::Error> {
+        self.send.send_with_priority(data, priority).await
+    }
+
+    /// Send data like the `Sink` implementation does, retransmitting it at most `max_retransmits` times, after which
+    /// it's dropped and the receiver doesn't wait for it anymore, however much latency is left
+    ///
+    /// For data that's worth less than the bandwidth it takes to deliver it late, see [`Sender::handle_limited_data`].
+    pub async fn send_with_max_retransmits(
+        &mut self,
+        data: (Instant, Bytes),
+        max_retransmits: u32,
+    ) -> Result<(), io::Error> {
+        self.send
+            .send_with_max_retransmits(data, max_retransmits)
+            .await
+    }
+
+    /// Send data like the `Sink` implementation does, keeping only the newest message of each `key` waiting while
+    /// the link is congested, for state that's sent whole every time, such as telemetry
+    ///
+    /// The messages wait for the other data queued to be sent, see [`Sender::handle_latest_data`].
+    pub async fn send_keep_latest(
+        &mut self,
+        data: (Instant, Bytes),
+        key: u64,
+    ) -> Result<(), io::Error> {
+        self.send.send_keep_latest(data, key).await
+    }
+
+    /// Send data like the `Sink` implementation does, failing with [`TimedOut`](io::ErrorKind::TimedOut) if it isn't
+    /// queued and acknowledged within `timeout`, whatever [`ConnectionSettings::send_timeout`] is
+    pub async fn send_timeout(
+        &mut self,
+        data: (Instant, Bytes),
+        timeout: Duration,
+    ) -> Result<(), io::Error> {
+        self.send.send_timeout(data, timeout).await
+    }
+
+    /// Receive the next message, or `None` once the connection is closed, see [`close_reason`](SrtSocket::close_reason)
+    ///
+    /// Fails with [`TimedOut`](io::ErrorKind::TimedOut) if nothing arrives within
+    /// [`ConnectionSettings::recv_timeout`], if any, after which receiving can be tried again.
+    pub async fn recv(&mut self) -> Result<Option<(Instant, Bytes)>, io::Error> {
+        self.recv.recv().await
+    }
+
+    /// Like [`recv`](SrtSocket::recv), waiting at most `timeout` whatever [`ConnectionSettings::recv_timeout`] is
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<(Instant, Bytes)>, io::Error> {
+        self.recv.recv_timeout(timeout).await
+    }
+
+    /// Receive every message that's been released, at least one, or `None` once the connection is closed
+    ///
+    /// Messages released at the same time, such as a burst whose latency ran out in the same timer tick, are woken for
+    /// once and handed over together, rather than one wakeup each. Fails like [`recv`](SrtSocket::recv).
+    pub async fn recv_many(&mut self) -> Result<Option<Vec<(Instant, Bytes)>>, io::Error> {
+        self.recv.recv_many().await
+    }
+
+    /// Poll for every message that's been released, see [`recv_many`](SrtSocket::recv_many)
+    pub fn poll_next_many(&mut self, cx: &mut Context) -> Poll<Option<Vec<(Instant, Bytes)>>> {
+        self.recv.poll_next_many(cx)
+    }
+
+    /// The next message, if one's been released, without receiving it, so the next receive still returns it
+    ///
+    /// Doesn't wait, so it's `None` until the first message's latency has passed, such as for sniffing whether a
+    /// stream is MPEG-TS or fragmented MP4 before handing the socket to the matching pipeline.
+    pub fn try_peek_msg(&mut self) -> Option<(Instant, Bytes)> {
+        self.recv.try_peek_msg()
+    }
+
+    /// Send a message given in several slices, such as a header and a payload in different buffers
+    ///
+    /// The slices are copied once, into the buffer the packets' payloads are sliced from, so they don't need to be put
+    /// together first. See [`Sender::handle_vectored_data`] for packing them into packets without the connection's
+    /// task.
+    pub async fn send_vectored(
+        &mut self,
+        time: Instant,
+        slices: &[IoSlice<'_>],
+    ) -> Result<(), io::Error> {
+        self.send.send_vectored(time, slices).await
+    }
+
+    /// Close the connection, once the peer acknowledged everything sent or the linger ran out
+    ///
+    /// Resolves once the connection's task exited, with how many payload bytes were abandoned unacknowledged, see
+    /// [`ConnectionSettings::linger`]. Closing through the `Sink` implementation does the same, without the count.
+    pub async fn close(&mut self) -> Result<u64, io::Error> {
+        self.send.close().await
+    }
+
+    /// Experimental: stop the connection without telling the peer, for another process to carry it on from the
+    /// snapshot with [`SrtSocketBuilder::restore`](crate::SrtSocketBuilder::restore), see [`ConnectionSnapshot`]
+    ///
+    /// Data not yet sent or received is dropped. Fails with [`NotConnected`](io::ErrorKind::NotConnected) if the
+    /// connection already closed.
+    pub async fn suspend(self) -> Result<ConnectionSnapshot, io::Error> {
+        let (send, recv) = oneshot::channel();
+        let closed = || io::Error::new(io::ErrorKind::NotConnected, "Connection already closed");
+        self.send
+            .suspend_requests
+            .unbounded_send(send)
+            .map_err(|_| closed())?;
+        recv.await.map_err(|_| closed())
+    }
+}
+
+impl SrtSendHalf {
     pub fn settings(&self) -> &ConnectionSettings {
         &self.settings
     }
+
+    /// Create a handle to send data on this connection, see [`SrtSender`]
+    pub fn sender(&self) -> SrtSender {
+        SrtSender {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// A snapshot of the connection's statistics, see [`SrtSocket::stats`]
+    pub fn stats(&self) -> SocketStatistics {
+        *self.statistics.lock().unwrap()
+    }
+
+    /// A snapshot of the connection's internals, see [`SrtSocket::debug_dump`]
+    pub async fn debug_dump(&self) -> Option<DebugDump> {
+        request_dump(&self.dump_requests).await
+    }
+
+    /// See [`SrtSocket::close_reason`]
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        *self.close_reason.lock().unwrap()
+    }
+
+    /// See [`SrtSocket::events`]
+    pub fn events(&self) -> Vec<EventRecord> {
+        self.events.lock().unwrap().records()
+    }
+
+    /// See [`SrtSocket::send_with_priority`]
+    pub async fn send_with_priority(
+        &mut self,
+        data: (Instant, Bytes),
+        priority: Priority,
+    ) -> Result<(), io::Error> {
+        let timeout = self.settings.send_timeout;
+        with_timeout(
+            timeout,
+            self.send_and_flush(data, priority, SendMode::Reliable),
+        )
+        .await
+    }
+
+    /// See [`SrtSocket::send_with_max_retransmits`]
+    pub async fn send_with_max_retransmits(
+        &mut self,
+        data: (Instant, Bytes),
+        max_retransmits: u32,
+    ) -> Result<(), io::Error> {
+        let timeout = self.settings.send_timeout;
+        with_timeout(
+            timeout,
+            self.send_and_flush(
+                data,
+                Priority::Normal,
+                SendMode::MaxRetransmits(max_retransmits),
+            ),
+        )
+        .await
+    }
+
+    /// See [`SrtSocket::send_keep_latest`]
+    pub async fn send_keep_latest(
+        &mut self,
+        data: (Instant, Bytes),
+        key: u64,
+    ) -> Result<(), io::Error> {
+        let timeout = self.settings.send_timeout;
+        with_timeout(
+            timeout,
+            self.send_and_flush(data, Priority::Normal, SendMode::KeepLatest(key)),
+        )
+        .await
+    }
+
+    /// See [`SrtSocket::send_timeout`]
+    pub async fn send_timeout(
+        &mut self,
+        data: (Instant, Bytes),
+        timeout: Duration,
+    ) -> Result<(), io::Error> {
+        with_timeout(
+            Some(timeout),
+            self.send_and_flush(data, Priority::Normal, SendMode::Reliable),
+        )
+        .await
+    }
+
+    async fn send_and_flush(
+        &mut self,
+        (time, payload): (Instant, Bytes),
+        priority: Priority,
+        mode: SendMode,
+    ) -> Result<(), io::Error> {
+        self.sender
+            .send((time, payload, priority, mode))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+        self.flush().await
+    }
+
+    /// See [`SrtSocket::send_vectored`]
+    pub async fn send_vectored(
+        &mut self,
+        time: Instant,
+        slices: &[IoSlice<'_>],
+    ) -> Result<(), io::Error> {
+        let timeout = self.settings.send_timeout;
+        with_timeout(
+            timeout,
+            self.send_and_flush((time, gather(slices)), Priority::Normal, SendMode::Reliable),
+        )
+        .await
+    }
+
+    /// See [`SrtSocket::close`]
+    pub async fn close(&mut self) -> Result<u64, io::Error> {
+        futures::SinkExt::close(self).await?;
+        Ok(self.stats().sender.abandoned_bytes)
+    }
+}
+
+impl SrtSender {
+    /// Queue data like the `Sink` implementation does, with a priority other than [`Priority::Normal`]
+    pub async fn send_with_priority(
+        &mut self,
+        (time, payload): (Instant, Bytes),
+        priority: Priority,
+    ) -> Result<(), io::Error> {
+        self.sender
+            .send((time, payload, priority, SendMode::Reliable))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+
+    /// Queue data like the `Sink` implementation does, see [`SrtSocket::send_with_max_retransmits`]
+    pub async fn send_with_max_retransmits(
+        &mut self,
+        (time, payload): (Instant, Bytes),
+        max_retransmits: u32,
+    ) -> Result<(), io::Error> {
+        self.sender
+            .send((
+                time,
+                payload,
+                Priority::Normal,
+                SendMode::MaxRetransmits(max_retransmits),
+            ))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+
+    /// Queue a message given in several slices, see [`SrtSocket::send_vectored`]
+    pub async fn send_vectored(
+        &mut self,
+        time: Instant,
+        slices: &[IoSlice<'_>],
+    ) -> Result<(), io::Error> {
+        self.send((time, gather(slices))).await
+    }
+
+    /// Queue data like the `Sink` implementation does, see [`SrtSocket::send_keep_latest`]
+    pub async fn send_keep_latest(
+        &mut self,
+        (time, payload): (Instant, Bytes),
+        key: u64,
+    ) -> Result<(), io::Error> {
+        self.sender
+            .send((time, payload, Priority::Normal, SendMode::KeepLatest(key)))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+}
+
+// fails with TimedOut if `fut` doesn't resolve within `timeout`, if any
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output = Result<T, io::Error>>,
+) -> Result<T, io::Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fut)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))?,
+        None => fut.await,
+    }
+}
+
+fn gather(slices: &[IoSlice]) -> Bytes {
+    let mut message = BytesMut::with_capacity(slices.iter().map(|slice| slice.len()).sum());
+    for slice in slices {
+        message.extend_from_slice(slice);
+    }
+    message.freeze()
+}
+
+impl SrtRecvHalf {
+    pub fn settings(&self) -> &ConnectionSettings {
+        &self.settings
+    }
+
+    /// A snapshot of the connection's statistics, see [`SrtSocket::stats`]
+    pub fn stats(&self) -> SocketStatistics {
+        *self.statistics.lock().unwrap()
+    }
+
+    /// A snapshot of the connection's internals, see [`SrtSocket::debug_dump`]
+    pub async fn debug_dump(&self) -> Option<DebugDump> {
+        request_dump(&self.dump_requests).await
+    }
+
+    /// See [`SrtSocket::close_reason`]
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        *self.close_reason.lock().unwrap()
+    }
+
+    /// See [`SrtSocket::events`]
+    pub fn events(&self) -> Vec<EventRecord> {
+        self.events.lock().unwrap().records()
+    }
+
+    /// See [`SrtSocket::set_latency`]
+    pub fn set_latency(&self, latency: Duration) {
+        // the connection is gone if this fails, so there's nothing left to change
+        let _ = self.latency_changes.unbounded_send(latency);
+    }
+
+    /// See [`SrtSocket::recv`]
+    pub async fn recv(&mut self) -> Result<Option<(Instant, Bytes)>, io::Error> {
+        let timeout = self.settings.recv_timeout;
+        with_timeout(timeout, async { self.next().await.transpose() }).await
+    }
+
+    /// See [`SrtSocket::recv_timeout`]
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<(Instant, Bytes)>, io::Error> {
+        with_timeout(Some(timeout), async { self.next().await.transpose() }).await
+    }
+
+    /// See [`SrtSocket::recv_many`]
+    pub async fn recv_many(&mut self) -> Result<Option<Vec<(Instant, Bytes)>>, io::Error> {
+        let timeout = self.settings.recv_timeout;
+        with_timeout(timeout, async {
+            Ok(future::poll_fn(|cx| self.poll_next_many(cx)).await)
+        })
+        .await
+    }
+
+    /// See [`SrtSocket::try_peek_msg`]
+    pub fn try_peek_msg(&mut self) -> Option<(Instant, Bytes)> {
+        if self.released.is_empty() {
+            if let Ok(Some(batch)) = self.recvr.try_next() {
+                self.released.extend(batch);
+            }
+        }
+        self.released.front().cloned()
+    }
+
+    /// See [`SrtSocket::poll_next_many`]
+    pub fn poll_next_many(&mut self, cx: &mut Context) -> Poll<Option<Vec<(Instant, Bytes)>>> {
+        let mut released: Vec<_> = self.released.drain(..).collect();
+        loop {
+            match Pin::new(&mut self.recvr).poll_next(cx) {
+                Poll::Ready(Some(batch)) => released.extend(batch),
+                Poll::Ready(None) if released.is_empty() => return Poll::Ready(None),
+                Poll::Pending if released.is_empty() => return Poll::Pending,
+                _ => return Poll::Ready(Some(released)),
+            }
+        }
+    }
 }
 
 impl Stream for SrtSocket {
     type Item = Result<(Instant, Bytes), io::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        Poll::Ready(ready!(Pin::new(&mut self.recvr).poll_next(cx)).map(Ok))
+        Pin::new(&mut self.recv).poll_next(cx)
     }
 }
 
 impl Sink<(Instant, Bytes)> for SrtSocket {
     type Error = io::Error;
 
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.send).poll_ready(cx)
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
+        Pin::new(&mut self.send).start_send(item)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.send).poll_close(cx)
+    }
+}
+
+impl Stream for SrtRecvHalf {
+    type Item = Result<(Instant, Bytes), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        if let Some(released) = self.released.pop_front() {
+            return Poll::Ready(Some(Ok(released)));
+        }
+        match ready!(Pin::new(&mut self.recvr).poll_next(cx)) {
+            Some(batch) => {
+                self.released.extend(batch);
+                Poll::Ready(self.released.pop_front().map(Ok))
+            }
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Sink<(Instant, Bytes)> for SrtSender {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender)
+            .poll_ready(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (time, payload): (Instant, Bytes),
+    ) -> Result<(), Self::Error> {
+        self.sender
+            .start_send((time, payload, Priority::Normal, SendMode::Reliable))
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender)
+            .poll_flush(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender)
+            .poll_close(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+}
+
+impl Sink<(Instant, Bytes)> for SrtSendHalf {
+    type Error = io::Error;
+
     fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(ready!(Pin::new(&mut self.sender).poll_ready(cx))
             .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?))
     }
-    fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
+    fn start_send(
+        mut self: Pin<&mut Self>,
+        (time, payload): (Instant, Bytes),
+    ) -> Result<(), Self::Error> {
         Ok(self
             .sender
-            .start_send(item)
+            .start_send((time, payload, Priority::Normal, SendMode::Reliable))
             .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?)
     }
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
@@ -359,3 +1440,11 @@ impl Sink<(Instant, Bytes)> for SrtSocket {
         }
     }
 }
+
+async fn request_dump(
+    requests: &mpsc::UnboundedSender<oneshot::Sender<DebugDump>>,
+) -> Option<DebugDump> {
+    let (send, recv) = oneshot::channel();
+    requests.unbounded_send(send).ok()?;
+    recv.await.ok()
+}
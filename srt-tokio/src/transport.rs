@@ -0,0 +1,161 @@
+//! Carrying SRT over something other than a UDP socket, see [`AsyncDatagramTransport`]
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::BytesMut;
+use futures::{ready, Sink, Stream};
+use log::warn;
+use tokio::net::UdpSocket;
+
+use crate::codec::{self, MAX_DATAGRAM};
+use crate::{Packet, PacketParseError};
+
+/// Something that carries datagrams between addresses, like a UDP socket, for SRT over other substrates such as unix
+/// datagram sockets, QUIC datagrams or a test harness
+///
+/// Wrapped in a [`TransportFramed`], it can be connected over with
+/// [`connect_with_transport`](crate::SrtSocketBuilder::connect_with_transport) and multiplexed with
+/// [`build_multiplexed_with_transport`](crate::SrtSocketBuilder::build_multiplexed_with_transport). Peers are
+/// addressed with a `SocketAddr` whatever the substrate, so one that addresses them otherwise maps its addresses
+/// onto socket addresses.
+///
+/// This is the trait the tokio driver runs on. Hosts driving the state machines themselves without a runtime use its
+/// synchronous counterpart, [`srt_protocol::DatagramTransport`], instead.
+pub trait AsyncDatagramTransport: Unpin {
+    /// Send `buf` as one datagram to `target`, returning how many bytes were sent
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context,
+        buf: &[u8],
+        target: &SocketAddr,
+    ) -> Poll<Result<usize, io::Error>>;
+
+    /// Receive one datagram into `buf`, returning its length and where it came from
+    fn poll_recv_from(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), io::Error>>;
+
+    fn local_addr(&self) -> Result<SocketAddr, io::Error>;
+
+    /// The largest datagram that can be sent, packets larger than this are dropped
+    fn max_payload(&self) -> usize;
+}
+
+impl AsyncDatagramTransport for UdpSocket {
+    fn poll_send_to(
+        &mut self,
+        cx: &mut Context,
+        buf: &[u8],
+        target: &SocketAddr,
+    ) -> Poll<Result<usize, io::Error>> {
+        UdpSocket::poll_send_to(self, cx, buf, target)
+    }
+
+    fn poll_recv_from(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), io::Error>> {
+        UdpSocket::poll_recv_from(self, cx, buf)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        UdpSocket::local_addr(self)
+    }
+
+    fn max_payload(&self) -> usize {
+        // over IPv4, the smaller of the two
+        65_507
+    }
+}
+
+/// Frames a [`AsyncDatagramTransport`] with the packets it carries, like a `UdpFramed<PacketCodec>` does a UDP socket
+pub struct TransportFramed<T> {
+    transport: T,
+    /// Where the next datagrams are read, the packets parsed from those before keep the rest of the slab
+    recv_buf: BytesMut,
+    send_buf: Option<(BytesMut, SocketAddr)>,
+}
+
+impl<T: AsyncDatagramTransport> TransportFramed<T> {
+    pub fn new(transport: T) -> Self {
+        TransportFramed {
+            transport,
+            recv_buf: codec::zeroed_slab(),
+            send_buf: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: AsyncDatagramTransport> Stream for TransportFramed<T> {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.recv_buf.len() < MAX_DATAGRAM {
+            this.recv_buf = codec::zeroed_slab();
+        }
+        let (len, from) = ready!(this
+            .transport
+            .poll_recv_from(cx, &mut this.recv_buf[..MAX_DATAGRAM]))?;
+        let packet = codec::parse(this.recv_buf.split_to(len).freeze());
+        Poll::Ready(Some(packet.map(|packet| (packet, from))))
+    }
+}
+
+impl<T: AsyncDatagramTransport> Sink<(Packet, SocketAddr)> for TransportFramed<T> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        (packet, to): (Packet, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let mut buf = BytesMut::new();
+        packet.serialize(&mut buf);
+        let max_payload = this.transport.max_payload();
+        if buf.len() > max_payload {
+            // as the network would
+            warn!(
+                "Dropping a {} byte datagram to {}, the transport carries at most {}",
+                buf.len(),
+                to,
+                max_payload
+            );
+            return Ok(());
+        }
+        this.send_buf = Some((buf, to));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some((buf, to)) = &this.send_buf {
+            let res = ready!(this.transport.poll_send_to(cx, buf, to));
+            this.send_buf = None;
+            res?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
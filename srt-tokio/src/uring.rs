@@ -0,0 +1,644 @@
+//! An io_uring backend for the UDP socket, see [`SrtSocketBuilder::io_uring`](crate::SrtSocketBuilder::io_uring)
+//!
+//! The socket keeps a batch of receives queued in the ring, so packets are read without a system call each, and the
+//! packets fed between two flushes are submitted with a single one. The ring signals completions on an eventfd,
+//! which is registered with tokio's reactor like any other file descriptor.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem;
+use std::net::{self, SocketAddr};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::task::{Context, Poll, Waker};
+
+use bytes::Bytes;
+use futures::{ready, Sink, Stream};
+use log::warn;
+use mio::unix::EventedFd;
+use mio::{Evented, PollOpt, Ready, Token};
+use tokio::io::PollEvented;
+
+use crate::timestamping::to_socket_addr;
+use crate::{codec, Packet, PacketParseError};
+
+/// How many receives are kept queued in the ring
+const RECV_SLOTS: usize = 32;
+
+/// How many packets can be sent before the kernel is done with the first one
+const SEND_SLOTS: usize = 32;
+
+/// Enough for any packet this library sends, larger datagrams are dropped
+const BUFFER_LEN: usize = 2048;
+
+// from linux/io_uring.h
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_OP_SENDMSG: u8 = 9;
+const IORING_OP_RECVMSG: u8 = 10;
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+const IORING_REGISTER_EVENTFD: libc::c_uint = 4;
+
+// the completions of cancellations, which only matter for the operations they cancel
+const CANCEL: u64 = u64::MAX;
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    pad: [u64; 2],
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+struct Mmap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: &File, len: usize, offset: libc::off_t) -> Result<Self, io::Error> {
+        // SAFETY: a fresh shared mapping of the ring, the kernel checks the length and offset
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    // SAFETY: `offset` has to be one the kernel gave for a u32 in this mapping
+    unsafe fn atomic(&self, offset: u32) -> &AtomicU32 {
+        &*(self.ptr.add(offset as usize) as *const AtomicU32)
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: mapped in `new`, and nothing points into it anymore
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+    }
+}
+
+struct Ring {
+    // the mappings go before the ring they map
+    sq: Mmap,
+    cq: Mmap,
+    sqes: Mmap,
+    fd: File,
+    params: Params,
+    sq_tail: u32,
+    to_submit: u32,
+}
+
+impl Ring {
+    fn new(entries: u32) -> Result<Self, io::Error> {
+        let mut params = Params::default();
+        // SAFETY: params is a live io_uring_params
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries,
+                &mut params as *mut Params,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: a new file descriptor that nothing else owns
+        let fd = unsafe { File::from_raw_fd(fd as RawFd) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+        Ok(Ring {
+            sq: Mmap::new(&fd, sq_len, IORING_OFF_SQ_RING)?,
+            cq: Mmap::new(&fd, cq_len, IORING_OFF_CQ_RING)?,
+            sqes: Mmap::new(&fd, sqes_len, IORING_OFF_SQES)?,
+            fd,
+            params,
+            sq_tail: 0,
+            to_submit: 0,
+        })
+    }
+
+    fn register_eventfd(&self, eventfd: RawFd) -> Result<(), io::Error> {
+        // SAFETY: the argument is one live file descriptor
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_register,
+                self.fd.as_raw_fd(),
+                IORING_REGISTER_EVENTFD,
+                &eventfd as *const RawFd,
+                1,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    // queue an operation, it's only started on the next submit
+    fn push(&mut self, sqe: Sqe) -> Result<(), io::Error> {
+        let off = self.params.sq_off;
+        // SAFETY: the offsets are the kernel's, and the index is masked to the ring's size
+        unsafe {
+            let head = self.sq.atomic(off.head).load(Ordering::Acquire);
+            if self.sq_tail.wrapping_sub(head) == self.params.sq_entries {
+                // without a polling thread the kernel takes everything submitted
+                self.submit(0)?;
+            }
+            let mask = *(self.sq.ptr.add(off.ring_mask as usize) as *const u32);
+            let index = self.sq_tail & mask;
+            ptr::write((self.sqes.ptr as *mut Sqe).add(index as usize), sqe);
+            ptr::write(
+                (self.sq.ptr.add(off.array as usize) as *mut u32).add(index as usize),
+                index,
+            );
+            self.sq_tail = self.sq_tail.wrapping_add(1);
+            self.sq
+                .atomic(off.tail)
+                .store(self.sq_tail, Ordering::Release);
+        }
+        self.to_submit += 1;
+        Ok(())
+    }
+
+    // start the queued operations, and wait for `wait` of them to complete
+    fn submit(&mut self, wait: u32) -> Result<(), io::Error> {
+        if self.to_submit == 0 && wait == 0 {
+            return Ok(());
+        }
+        let flags = if wait > 0 { IORING_ENTER_GETEVENTS } else { 0 };
+        // SAFETY: no signal mask is passed
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                self.to_submit,
+                wait,
+                flags,
+                ptr::null::<libc::sigset_t>(),
+                0,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.to_submit -= res as u32;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Cqe> {
+        let off = self.params.cq_off;
+        // SAFETY: the offsets are the kernel's, and the index is masked to the ring's size
+        unsafe {
+            let head = self.cq.atomic(off.head).load(Ordering::Relaxed);
+            if head == self.cq.atomic(off.tail).load(Ordering::Acquire) {
+                return None;
+            }
+            let mask = *(self.cq.ptr.add(off.ring_mask as usize) as *const u32);
+            let cqe = ptr::read(
+                (self.cq.ptr.add(off.cqes as usize) as *const Cqe).add((head & mask) as usize),
+            );
+            self.cq
+                .atomic(off.head)
+                .store(head.wrapping_add(1), Ordering::Release);
+            Some(cqe)
+        }
+    }
+}
+
+struct EventFd(File);
+
+impl Evented for EventFd {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).register(poll, token, interest, opts)
+    }
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.0.as_raw_fd()).deregister(poll)
+    }
+}
+
+// everything a recvmsg or sendmsg points to, boxed so it doesn't move while the kernel uses it
+struct Slot {
+    buf: Vec<u8>,
+    addr: libc::sockaddr_storage,
+    iov: libc::iovec,
+    msg: libc::msghdr,
+}
+
+impl Slot {
+    fn new(len: usize) -> Box<Slot> {
+        // SAFETY: all zeros is valid for the C structs
+        Box::new(Slot {
+            buf: vec![0; len],
+            addr: unsafe { mem::zeroed() },
+            iov: unsafe { mem::zeroed() },
+            msg: unsafe { mem::zeroed() },
+        })
+    }
+
+    fn msghdr(&mut self, opcode: u8, fd: RawFd, namelen: usize, user_data: u64) -> Sqe {
+        self.iov.iov_base = self.buf.as_mut_ptr() as *mut libc::c_void;
+        self.iov.iov_len = self.buf.len();
+        self.msg.msg_name = &mut self.addr as *mut libc::sockaddr_storage as *mut libc::c_void;
+        self.msg.msg_namelen = namelen as libc::socklen_t;
+        self.msg.msg_iov = &mut self.iov;
+        self.msg.msg_iovlen = 1;
+        self.msg.msg_flags = 0;
+        Sqe {
+            opcode,
+            fd,
+            addr: &mut self.msg as *mut libc::msghdr as u64,
+            len: 1,
+            user_data,
+            ..Sqe::default()
+        }
+    }
+
+    fn set_addr(&mut self, addr: SocketAddr) -> usize {
+        // SAFETY: all zeros is a valid sockaddr_storage
+        self.addr = unsafe { mem::zeroed() };
+        // SAFETY: sockaddr_storage is large and aligned enough for either
+        match addr {
+            SocketAddr::V4(addr) => {
+                let sin = unsafe { &mut *(&mut self.addr as *mut _ as *mut libc::sockaddr_in) };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(addr) => {
+                let sin6 = unsafe { &mut *(&mut self.addr as *mut _ as *mut libc::sockaddr_in6) };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        }
+    }
+}
+
+/// A UDP socket sending and receiving through io_uring, framed like a `UdpFramed<PacketCodec>`
+///
+/// Flushing submits the packets fed since the last flush, errors sending them are returned by a later call.
+pub struct UringSocket {
+    recv_slots: Vec<Box<Slot>>,
+    send_slots: Vec<Box<Slot>>,
+    // by user data, receives first
+    in_flight: Vec<bool>,
+    received: VecDeque<(usize, i32)>,
+    free_sends: Vec<usize>,
+    send_error: Option<io::Error>,
+    recv_waker: Option<Waker>,
+    send_waker: Option<Waker>,
+    event: PollEvented<EventFd>,
+    ring: Ring,
+    sock: net::UdpSocket,
+}
+
+// SAFETY: the raw pointers all point into the mappings and slots this socket owns
+unsafe impl Send for UringSocket {}
+
+impl UringSocket {
+    pub fn bind(addr: SocketAddr) -> Result<Self, io::Error> {
+        let sock = net::UdpSocket::bind(addr)?;
+        let ring = Ring::new((RECV_SLOTS + SEND_SLOTS) as u32)?;
+
+        // SAFETY: no pointers involved
+        let eventfd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if eventfd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: a new file descriptor that nothing else owns
+        let eventfd = EventFd(unsafe { File::from_raw_fd(eventfd) });
+        ring.register_eventfd(eventfd.0.as_raw_fd())?;
+
+        let mut socket = UringSocket {
+            recv_slots: (0..RECV_SLOTS).map(|_| Slot::new(BUFFER_LEN)).collect(),
+            send_slots: (0..SEND_SLOTS).map(|_| Slot::new(0)).collect(),
+            in_flight: vec![false; RECV_SLOTS + SEND_SLOTS],
+            received: VecDeque::with_capacity(RECV_SLOTS),
+            free_sends: (0..SEND_SLOTS).collect(),
+            send_error: None,
+            recv_waker: None,
+            send_waker: None,
+            event: PollEvented::new(eventfd)?,
+            ring,
+            sock,
+        };
+        for slot in 0..RECV_SLOTS {
+            socket.recv(slot)?;
+        }
+        socket.ring.submit(0)?;
+        Ok(socket)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.sock.local_addr()
+    }
+
+    fn recv(&mut self, slot: usize) -> Result<(), io::Error> {
+        let fd = self.sock.as_raw_fd();
+        let sqe = self.recv_slots[slot].msghdr(
+            IORING_OP_RECVMSG,
+            fd,
+            mem::size_of::<libc::sockaddr_storage>(),
+            slot as u64,
+        );
+        self.ring.push(sqe)?;
+        self.in_flight[slot] = true;
+        Ok(())
+    }
+
+    // read a completed receive, and queue the slot again
+    fn take_received(
+        &mut self,
+        slot: usize,
+        res: i32,
+    ) -> Option<Result<(Packet, SocketAddr), PacketParseError>> {
+        let recvd = &self.recv_slots[slot];
+        let item = if res < 0 {
+            Some(Err(io::Error::from_raw_os_error(-res).into()))
+        } else if recvd.msg.msg_flags & libc::MSG_TRUNC != 0 {
+            warn!("Dropping a datagram larger than {} bytes", BUFFER_LEN);
+            None
+        } else {
+            // the slot's buffer is handed back to the ring for the next receive below, so the packet needs a copy
+            let packet = codec::parse(Bytes::copy_from_slice(&recvd.buf[..res as usize]));
+            Some(
+                to_socket_addr(&recvd.addr)
+                    .map_err(PacketParseError::from)
+                    .and_then(|from| packet.map(|packet| (packet, from))),
+            )
+        };
+        if let Err(e) = self.recv(slot) {
+            return Some(Err(e.into()));
+        }
+        item
+    }
+
+    fn drain(&mut self) {
+        let (mut received, mut sent) = (false, false);
+        while let Some(cqe) = self.ring.pop() {
+            if cqe.user_data == CANCEL {
+                continue;
+            }
+            let id = cqe.user_data as usize;
+            self.in_flight[id] = false;
+            if id < RECV_SLOTS {
+                self.received.push_back((id, cqe.res));
+                received = true;
+            } else {
+                self.free_sends.push(id - RECV_SLOTS);
+                if cqe.res < 0 && self.send_error.is_none() {
+                    self.send_error = Some(io::Error::from_raw_os_error(-cqe.res));
+                }
+                sent = true;
+            }
+        }
+        // whichever task polled the eventfd, the other direction may be waiting on these
+        if received {
+            if let Some(waker) = self.recv_waker.take() {
+                waker.wake();
+            }
+        }
+        if sent {
+            if let Some(waker) = self.send_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    // wait for the ring to signal completions, and read them
+    fn poll_completions(&mut self, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+        loop {
+            ready!(self.event.poll_read_ready(cx, Ready::readable()))?;
+            let mut count = [0; 8];
+            match (&self.event.get_ref().0).read(&mut count) {
+                Ok(_) => {
+                    self.drain();
+                    return Poll::Ready(Ok(()));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.event.clear_read_ready(cx, Ready::readable())?;
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+}
+
+impl Drop for UringSocket {
+    fn drop(&mut self) {
+        // the kernel uses the slots until their operations complete, so cancel them before they're freed
+        let in_flight: Vec<_> = (0..self.in_flight.len())
+            .filter(|&id| self.in_flight[id])
+            .collect();
+        let mut cancelled = || -> Result<(), io::Error> {
+            for &id in &in_flight {
+                self.ring.push(Sqe {
+                    opcode: IORING_OP_ASYNC_CANCEL,
+                    fd: -1,
+                    addr: id as u64,
+                    user_data: CANCEL,
+                    ..Sqe::default()
+                })?;
+            }
+            while self.in_flight.iter().any(|&f| f) {
+                match self.ring.submit(1) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                    Ok(()) => {}
+                }
+                while let Some(cqe) = self.ring.pop() {
+                    if cqe.user_data != CANCEL {
+                        self.in_flight[cqe.user_data as usize] = false;
+                    }
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = cancelled() {
+            warn!(
+                "Failed to cancel io_uring operations, leaking their buffers: {}",
+                e
+            );
+            mem::forget(mem::take(&mut self.recv_slots));
+            mem::forget(mem::take(&mut self.send_slots));
+        }
+    }
+}
+
+impl Stream for UringSocket {
+    type Item = Result<(Packet, SocketAddr), PacketParseError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((slot, res)) = this.received.pop_front() {
+                match this.take_received(slot, res) {
+                    Some(item) => return Poll::Ready(Some(item)),
+                    None => continue,
+                }
+            }
+            // queue the slots again, all at once
+            this.ring.submit(0)?;
+            this.drain();
+            if this.received.is_empty() {
+                this.recv_waker = Some(cx.waker().clone());
+                ready!(this.poll_completions(cx))?;
+            }
+        }
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for UringSocket {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(e) = this.send_error.take() {
+                return Poll::Ready(Err(e));
+            }
+            if !this.free_sends.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.ring.submit(0)?;
+            this.drain();
+            if this.free_sends.is_empty() {
+                this.send_waker = Some(cx.waker().clone());
+                ready!(this.poll_completions(cx))?;
+            }
+        }
+    }
+
+    fn start_send(
+        self: Pin<&mut Self>,
+        (packet, to): (Packet, SocketAddr),
+    ) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let slot = this
+            .free_sends
+            .pop()
+            .expect("start_send called without poll_ready");
+        let fd = this.sock.as_raw_fd();
+        let send = &mut this.send_slots[slot];
+        send.buf.clear();
+        packet.serialize(&mut send.buf);
+        let namelen = send.set_addr(to);
+        let id = RECV_SLOTS + slot;
+        let sqe = send.msghdr(IORING_OP_SENDMSG, fd, namelen, id as u64);
+        this.ring.push(sqe)?;
+        this.in_flight[id] = true;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some(e) = this.send_error.take() {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(this.ring.submit(0))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
@@ -1,6 +1,7 @@
 use futures::prelude::*;
-use log::warn;
+use log::{debug, warn};
 use std::{io, net::SocketAddr};
+use tokio::task::yield_now;
 
 use crate::{Packet, PacketParseError};
 
@@ -13,7 +14,39 @@ pub async fn get_packet<
         match sock.next().await {
             None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "")),
             Some(Ok(t)) => break Ok(t),
+            Some(Err(e)) if is_transient(&e) => debug!("Ignoring receive error: {}", e),
             Some(Err(e)) => warn!("Failed to parse packet: {}", e),
         }
     }
 }
+
+/// Whether a receive error is left over from an earlier send, and the socket can go on receiving
+///
+/// Windows reports the ICMP port unreachable that answers a datagram as `WSAECONNRESET` on a later receive, even on an
+/// unconnected UDP socket, where it says nothing about the other peers.
+pub fn is_transient(e: &PacketParseError) -> bool {
+    matches!(e, PacketParseError::Io(e) if e.kind() == io::ErrorKind::ConnectionReset)
+}
+
+/// Counts packets handled in a row, yielding to other tasks on the runtime once `budget` is used up
+pub struct PacketBudget {
+    budget: usize,
+    remaining: usize,
+}
+
+impl PacketBudget {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            remaining: budget,
+        }
+    }
+
+    pub async fn spend(&mut self) {
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.remaining = self.budget;
+            let () = yield_now().await;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::{SocketStatistics, StallEvent, StallMonitor, TaskStage};
+
+struct Progress {
+    at: Instant,
+    stage: TaskStage,
+}
+
+/// Watches a connection's task for a [`StallMonitor`], does nothing without one
+///
+/// The task reports its stage as it goes, and a thread checks it made progress lately. The thread exits once the
+/// watchdog is dropped, with the task.
+pub struct Watchdog {
+    progress: Option<Arc<Mutex<Progress>>>,
+}
+
+impl Watchdog {
+    pub fn new(monitor: Option<StallMonitor>, statistics: Arc<Mutex<SocketStatistics>>) -> Self {
+        let monitor = match monitor {
+            Some(monitor) => monitor,
+            None => return Watchdog { progress: None },
+        };
+        let progress = Arc::new(Mutex::new(Progress {
+            at: Instant::now(),
+            stage: TaskStage::Handling,
+        }));
+        let watched = Arc::downgrade(&progress);
+        thread::spawn(move || watch(monitor, watched, statistics));
+        Watchdog {
+            progress: Some(progress),
+        }
+    }
+
+    /// The task is now at `stage`, which is progress
+    pub fn stage(&self, stage: TaskStage) {
+        if let Some(progress) = &self.progress {
+            *progress.lock().unwrap() = Progress {
+                at: Instant::now(),
+                stage,
+            };
+        }
+    }
+}
+
+fn watch(
+    monitor: StallMonitor,
+    progress: Weak<Mutex<Progress>>,
+    statistics: Arc<Mutex<SocketStatistics>>,
+) {
+    let timeout = monitor.timeout();
+    let mut reported = None;
+    loop {
+        thread::sleep(timeout / 4);
+        let (at, stage) = match progress.upgrade() {
+            Some(progress) => {
+                let progress = progress.lock().unwrap();
+                (progress.at, progress.stage)
+            }
+            None => return,
+        };
+        // once per stall
+        if at.elapsed() < timeout || reported == Some(at) {
+            continue;
+        }
+        reported = Some(at);
+        warn!(
+            "Connection task stalled for {:?}, {:?}",
+            at.elapsed(),
+            stage
+        );
+        monitor.call(StallEvent {
+            stalled_for: at.elapsed(),
+            stage,
+            statistics: *statistics.lock().unwrap(),
+        });
+    }
+}
@@ -0,0 +1,39 @@
+#![cfg(all(feature = "activation", unix))]
+
+use std::env;
+use std::io;
+
+use srt_tokio::{activation, SrtSocketBuilder};
+
+/// Sockets meant for another process aren't taken, nor are the variables left for children
+#[tokio::test]
+async fn not_activated() {
+    let _ = env_logger::try_init();
+
+    env::set_var("LISTEN_PID", "1");
+    env::set_var("LISTEN_FDS", "1");
+    assert!(activation::listen_fds().unwrap().is_empty());
+    assert!(env::var("LISTEN_PID").is_err());
+    assert!(env::var("LISTEN_FDS").is_err());
+
+    let err = SrtSocketBuilder::new_listen()
+        .build_multiplexed_activated()
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+    env::set_var("LISTEN_PID", std::process::id().to_string());
+    env::set_var("LISTEN_FDS", "many");
+    assert_eq!(
+        activation::listen_fds().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+
+    // past the largest file descriptor
+    env::set_var("LISTEN_PID", std::process::id().to_string());
+    env::set_var("LISTEN_FDS", i32::MAX.to_string());
+    assert_eq!(
+        activation::listen_fds().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+}
@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::prelude::*;
+use tokio::time::{delay_for, timeout};
+
+use srt_protocol::packet::RejectReason;
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{Priority, RateLimit, SrtSocketBuilder};
+
+/// A connection closed through its handle ends on both sides, and leaves the registry
+#[tokio::test]
+async fn close() {
+    let _ = env_logger::try_init();
+
+    let (stats, server) = SrtSocketBuilder::new_listen()
+        .local_port(6070)
+        .build_multiplexed_with_stats()
+        .await
+        .unwrap();
+
+    let (closed_send, closed) = oneshot::channel();
+    tokio::spawn(async move {
+        let mut server = server.boxed();
+        let (conn, chan) = server.try_next().await.unwrap().unwrap();
+        tokio::spawn(async move {
+            let mut sock = create_bidrectional_srt(chan, conn);
+            while sock.try_next().await.unwrap().is_some() {}
+            closed_send.send(()).unwrap();
+        });
+        // keep the multiplexer going
+        while server.try_next().await.unwrap().is_some() {}
+    });
+
+    let mut client = SrtSocketBuilder::new_connect("127.0.0.1:6070")
+        .local_port(6071)
+        .connect()
+        .await
+        .unwrap();
+    delay_for(Duration::from_millis(100)).await;
+
+    let conns = stats.connections();
+    assert_eq!(conns.len(), 1);
+    conns[0].close(RejectReason::Peer);
+
+    let end = timeout(Duration::from_secs(1), client.try_next()).await;
+    assert_eq!(end.unwrap().unwrap(), None);
+    timeout(Duration::from_secs(1), closed)
+        .await
+        .unwrap()
+        .unwrap();
+    delay_for(Duration::from_millis(100)).await;
+    assert!(stats.connections().is_empty());
+}
+
+/// The rate limit of a connection can be set and lifted through its handle
+#[tokio::test]
+async fn set_rate_limit() {
+    let _ = env_logger::try_init();
+
+    let (stats, server) = SrtSocketBuilder::new_listen()
+        .local_port(6072)
+        .build_multiplexed_with_stats()
+        .await
+        .unwrap();
+
+    let (go_send, go) = oneshot::channel();
+    tokio::spawn(async move {
+        let mut server = server.boxed();
+        let (conn, chan) = server.try_next().await.unwrap().unwrap();
+        tokio::spawn(async move {
+            let mut sock = create_bidrectional_srt(chan, conn);
+            go.await.unwrap();
+            let mut sender = sock.sender();
+            for _ in 0..100 {
+                sender
+                    .send_with_priority(
+                        (Instant::now(), Bytes::from(vec![0; 1000])),
+                        Priority::High,
+                    )
+                    .await
+                    .unwrap();
+            }
+            while sock.try_next().await.unwrap().is_some() {}
+        });
+        while server.try_next().await.unwrap().is_some() {}
+    });
+
+    let mut client = SrtSocketBuilder::new_connect("127.0.0.1:6072")
+        .local_port(6073)
+        .connect()
+        .await
+        .unwrap();
+    tokio::spawn(async move { while client.try_next().await.unwrap().is_some() {} });
+    delay_for(Duration::from_millis(100)).await;
+
+    let conn = stats.connections().remove(0);
+    conn.set_rate_limit(Some(RateLimit::new(10_000)));
+    go_send.send(()).unwrap();
+
+    // the burst, then 10kB a second
+    delay_for(Duration::from_millis(500)).await;
+    let egress = stats.egress(conn.local_sockid).unwrap();
+    assert!(egress.sent_payload_bytes < 10_000, "{:?}", egress);
+
+    conn.set_rate_limit(None);
+    delay_for(Duration::from_millis(500)).await;
+    let egress = stats.egress(conn.local_sockid).unwrap();
+    assert!(egress.sent_payload_bytes >= 100_000, "{:?}", egress);
+}
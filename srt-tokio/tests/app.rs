@@ -0,0 +1,69 @@
+#![cfg(feature = "app")]
+
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+
+use srt_tokio::app::{AppSink, AppSrc};
+use srt_tokio::SrtSocketBuilder;
+
+/// Buffers produced on demand by `need_data` come out of `on_data` in order, then both ends see the end of the stream
+#[tokio::test]
+async fn need_data_to_on_data() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6057).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6057").connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let mut next = 0;
+    let (appsrc, _handle) = AppSrc::new(sender);
+    let appsrc = appsrc.need_data(move |handle| {
+        if next == 10 {
+            handle.end_of_stream();
+            return;
+        }
+        handle.push_buffer((Instant::now(), Bytes::from(vec![next; 100])));
+        next += 1;
+    });
+
+    let (data_send, data) = mpsc::unbounded();
+    let appsink = AppSink::new(recvr).on_data(move |(_, payload)| {
+        let _ = data_send.unbounded_send(payload[0]);
+    });
+
+    let (sent, received) = futures::try_join!(appsrc.run(), appsink.run()).unwrap();
+    assert_eq!(sent, 10);
+    assert_eq!(received, 10);
+    assert_eq!(data.collect::<Vec<_>>().await, (0..10).collect::<Vec<_>>());
+}
+
+/// Buffers pushed from the outside are sent as they come, and can be pulled one by one
+#[tokio::test]
+async fn push_and_pull() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6058).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6058").connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let (appsrc, handle) = AppSrc::new(sender);
+    let sending = tokio::spawn(appsrc.run());
+    let mut appsink = AppSink::new(recvr);
+
+    for i in 0..3 {
+        handle.push_buffer((Instant::now(), Bytes::from(format!("buffer {}", i))));
+        let (_, payload) = appsink.pull_sample().await.unwrap().unwrap();
+        assert_eq!(payload, format!("buffer {}", i));
+    }
+
+    handle.end_of_stream();
+    // pushed too late
+    handle.push_buffer((Instant::now(), Bytes::from_static(b"dropped")));
+    assert_eq!(handle.queued(), 0);
+
+    assert_eq!(sending.await.unwrap().unwrap(), 3);
+    assert_eq!(appsink.pull_sample().await.unwrap(), None);
+}
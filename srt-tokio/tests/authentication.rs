@@ -0,0 +1,106 @@
+use std::io;
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_protocol::{packet::RejectReason, ConnectionSettings};
+use srt_tokio::SrtSocketBuilder;
+
+// stands for a call to a service validating the token in the stream id
+fn check_token(settings: &ConnectionSettings) -> impl Future<Output = Result<(), RejectReason>> {
+    let stream_id = settings.info().stream_id;
+    async move {
+        delay_for(Duration::from_millis(50)).await;
+        match stream_id.as_deref() {
+            Some("#!::u=admin,t=good") => Ok(()),
+            _ => Err(RejectReason::Peer),
+        }
+    }
+}
+
+/// Callers the authenticator turns down are rejected, and the listener waits for the next one
+#[tokio::test]
+async fn authenticate() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6074)
+        .authenticator(Duration::from_secs(1), check_token)
+        .connect();
+    let callers = async {
+        let err = SrtSocketBuilder::new_connect("127.0.0.1:6074")
+            .stream_id("#!::u=admin,t=bad")
+            .connect()
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+        SrtSocketBuilder::new_connect("127.0.0.1:6074")
+            .stream_id("#!::u=admin,t=good")
+            .connect()
+            .await
+    };
+    let (listener, caller) = futures::try_join!(listener, callers).unwrap();
+    assert_eq!(
+        listener.info().stream_id.as_deref(),
+        Some("#!::u=admin,t=good")
+    );
+    assert_eq!(caller.info().stream_id, None);
+}
+
+/// An authenticator that doesn't decide in time rejects the caller
+#[tokio::test]
+async fn authenticate_timeout() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6076)
+        .authenticator(Duration::from_millis(100), |_| future::pending())
+        .connect()
+        .boxed_local();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6076")
+        .connect()
+        .boxed_local();
+
+    match future::select(listener, caller).await {
+        future::Either::Right((caller, _)) => {
+            assert_eq!(caller.unwrap_err().kind(), io::ErrorKind::ConnectionRefused)
+        }
+        future::Either::Left(_) => panic!("The listener shouldn't connect"),
+    }
+}
+
+/// A multiplexer keeps serving others while it authenticates
+#[tokio::test]
+async fn authenticate_multiplexed() {
+    let _ = env_logger::try_init();
+
+    let server = SrtSocketBuilder::new_listen()
+        .local_port(6077)
+        .authenticator(Duration::from_secs(1), check_token)
+        .build_multiplexed()
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        let mut server = server.boxed();
+        while let Some((conn, _)) = server.try_next().await.unwrap() {
+            assert_eq!(
+                conn.settings.info().stream_id.as_deref(),
+                Some("#!::u=admin,t=good")
+            );
+        }
+    });
+
+    let good = SrtSocketBuilder::new_connect("127.0.0.1:6077")
+        .local_port(6078)
+        .stream_id("#!::u=admin,t=good")
+        .connect();
+    let bad = SrtSocketBuilder::new_connect("127.0.0.1:6077")
+        .local_port(6079)
+        .stream_id("#!::u=admin,t=bad")
+        .connect();
+    let (good, bad) = futures::join!(good, bad);
+    good.unwrap();
+    assert_eq!(bad.unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+}
@@ -1,11 +1,15 @@
-use srt_tokio::SrtSocketBuilder;
+use srt_tokio::{ConnInitMethod, SrtSocket, SrtSocketBuilder};
 
 use bytes::Bytes;
 use futures::{stream, SinkExt, StreamExt, TryStreamExt};
+use std::str;
 use std::time::{Duration, Instant};
 use tokio::spawn;
 use tokio::time::interval;
 
+mod lossy_conn;
+use crate::lossy_conn::LossyConn;
+
 #[tokio::test]
 async fn bidirectional() {
     let _ = env_logger::try_init();
@@ -15,12 +19,13 @@ async fn bidirectional() {
     let a = SrtSocketBuilder::new_connect("127.0.0.1:5000").connect();
     let b = SrtSocketBuilder::new_listen().local_port(5000).connect();
 
+    let mut handles = vec![];
     for fut in vec![a, b] {
-        spawn(async move {
+        handles.push(spawn(async move {
             let side = fut.await.unwrap();
             let (mut s, mut r) = side.split();
 
-            spawn(async move {
+            let recv = spawn(async move {
                 for i in 0..ITERS {
                     let (_, payload) = r.try_next().await.unwrap().unwrap();
 
@@ -34,6 +39,138 @@ async fn bidirectional() {
 
             s.send_all(&mut counting_stream).await.unwrap();
             s.close().await.unwrap();
-        });
+            recv.await.unwrap();
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
     }
 }
+
+/// Send in both directions at once over a lossy link, with a different latency in each direction
+#[tokio::test]
+async fn lossy_bidirectional() {
+    let _ = env_logger::try_init();
+
+    const ITERS: u32 = 500;
+
+    let (a, b) = LossyConn::channel(
+        0.05,
+        Duration::from_millis(20),
+        Duration::from_millis(4),
+        "127.0.0.1:6005",
+        "127.0.0.1:6006",
+    );
+
+    // a -> b is at 2s, b -> a is at 1s
+    let a = SrtSocketBuilder::new(ConnInitMethod::Listen)
+        .local_port(6005)
+        .send_latency(Duration::from_secs(2))
+        .receive_latency(Duration::from_secs(1))
+        .connect_with_sock(a);
+    let b = SrtSocketBuilder::new(ConnInitMethod::Connect("127.0.0.1:6005".parse().unwrap()))
+        .local_port(6006)
+        .send_latency(Duration::from_millis(500))
+        .receive_latency(Duration::from_millis(500))
+        .connect_with_sock(b);
+
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    assert_eq!(a.settings().send_tsbpd_latency, Duration::from_secs(2));
+    assert_eq!(a.settings().recv_tsbpd_latency, Duration::from_secs(1));
+    assert_eq!(b.settings().send_tsbpd_latency, Duration::from_secs(1));
+    assert_eq!(b.settings().recv_tsbpd_latency, Duration::from_secs(2));
+
+    async fn run(side: SrtSocket, latency: Duration) -> u32 {
+        let (mut s, mut r) = side.split();
+
+        let send = async move {
+            let mut counting_stream = stream::iter(0..ITERS)
+                .zip(interval(Duration::from_millis(5)))
+                .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))));
+
+            s.send_all(&mut counting_stream).await.unwrap();
+            s.close().await.unwrap();
+        };
+
+        let recv = async move {
+            let mut next_data = 0;
+            let mut dropped = 0;
+            while let Some((ts, payload)) = r.try_next().await.unwrap() {
+                let diff = ts.elapsed();
+                assert!(
+                    diff > latency - Duration::from_millis(100)
+                        && diff < latency + Duration::from_millis(700),
+                    "Latency not in tolerance zone: {:?}, expected {:?}",
+                    diff,
+                    latency
+                );
+
+                let actual: u32 = str::from_utf8(&payload[..]).unwrap().parse().unwrap();
+                dropped += actual - next_data;
+                next_data = actual + 1;
+            }
+            dropped + ITERS - next_data
+        };
+
+        futures::join!(send, recv).1
+    }
+
+    let (a_dropped, b_dropped) = futures::join!(
+        run(a, Duration::from_secs(1)),
+        run(b, Duration::from_secs(2))
+    );
+    assert!(
+        a_dropped < 10,
+        "Expected less than 10 drops, got {}",
+        a_dropped
+    );
+    assert!(
+        b_dropped < 10,
+        "Expected less than 10 drops, got {}",
+        b_dropped
+    );
+}
+
+/// Dropping the receiving half shouldn't stop the sending half, or the peer from closing
+#[tokio::test]
+async fn dropped_recv_half() {
+    let _ = env_logger::try_init();
+
+    const ITERS: u32 = 100;
+
+    let a = SrtSocketBuilder::new_connect("127.0.0.1:6010").connect();
+    let b = SrtSocketBuilder::new_listen().local_port(6010).connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    let a = spawn(async move {
+        let (mut s, mut r) = a.split();
+
+        let mut counting_stream = stream::iter(0..ITERS)
+            .zip(interval(Duration::from_millis(1)))
+            .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))));
+        s.send_all(&mut counting_stream).await.unwrap();
+
+        for i in 0..ITERS {
+            let (_, payload) = r.try_next().await.unwrap().unwrap();
+            assert_eq!(payload, Bytes::from(i.to_string()));
+        }
+        s.close().await.unwrap();
+        assert_eq!(r.try_next().await.unwrap(), None);
+    });
+
+    let b = spawn(async move {
+        let (mut s, r) = b.split();
+        drop(r);
+
+        let mut counting_stream = stream::iter(0..ITERS)
+            .zip(interval(Duration::from_millis(1)))
+            .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))));
+        s.send_all(&mut counting_stream).await.unwrap();
+        s.close().await.unwrap();
+    });
+
+    let (a, b) = futures::join!(a, b);
+    a.unwrap();
+    b.unwrap();
+}
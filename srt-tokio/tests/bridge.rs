@@ -0,0 +1,112 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use srt_tokio::bridge::{bridge, LengthPrefixCodec, TsRealigner, TS_PACKET, TS_SYNC};
+use srt_tokio::SrtSocketBuilder;
+
+fn ts_packets(count: usize) -> Vec<u8> {
+    (0..count)
+        .flat_map(|i| {
+            let mut packet = vec![i as u8; TS_PACKET];
+            packet[0] = TS_SYNC;
+            packet
+        })
+        .collect()
+}
+
+/// TS written to the stream in chunks that don't line up with the packets comes out of the connection in whole
+/// packets, and goes back into the stream as it was sent
+#[tokio::test]
+async fn ts_over_tcp() {
+    let _ = env_logger::try_init();
+
+    let mut tcp_listener = TcpListener::bind("127.0.0.1:6100").await.unwrap();
+    let srt_listener = SrtSocketBuilder::new_listen().local_port(6099).connect();
+    let srt_caller = SrtSocketBuilder::new_connect("127.0.0.1:6099").connect();
+    let (gateway, mut remote) = futures::try_join!(srt_listener, srt_caller).unwrap();
+
+    let (tcp, mut far_end) = futures::try_join!(
+        async { tcp_listener.accept().await.map(|(stream, _)| stream) },
+        TcpStream::connect("127.0.0.1:6100")
+    )
+    .unwrap();
+    tokio::spawn(bridge(gateway, tcp, TsRealigner::new()));
+
+    // a partial packet from before the stream was joined, then 14 packets in odd chunks
+    let ts = ts_packets(14);
+    far_end.write_all(&ts_packets(1)[100..]).await.unwrap();
+    for chunk in ts.chunks(500) {
+        far_end.write_all(chunk).await.unwrap();
+    }
+    // the last packet isn't known to be whole until something follows it
+    far_end.write_all(&[TS_SYNC]).await.unwrap();
+
+    let first = remote.try_next().await.unwrap().unwrap().1;
+    let second = remote.try_next().await.unwrap().unwrap().1;
+    assert_eq!(first, ts[..7 * TS_PACKET]);
+    assert_eq!(second, ts[7 * TS_PACKET..]);
+
+    remote
+        .send((Instant::now(), Bytes::from(ts_packets(7))))
+        .await
+        .unwrap();
+    // not whole packets, dropped rather than misaligning the stream
+    remote
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    remote
+        .send((Instant::now(), Bytes::from(ts_packets(2))))
+        .await
+        .unwrap();
+    remote.close().await.unwrap();
+
+    let mut received = Vec::new();
+    far_end.read_to_end(&mut received).await.unwrap();
+    let mut expected = ts_packets(7);
+    expected.extend_from_slice(&ts_packets(2));
+    assert_eq!(received, expected);
+}
+
+/// Messages keep their boundaries through a stream with a bridge at each end
+#[tokio::test]
+async fn length_prefixed_over_tcp() {
+    let _ = env_logger::try_init();
+
+    let mut tcp_listener = TcpListener::bind("127.0.0.1:6103").await.unwrap();
+    let a = SrtSocketBuilder::new_listen().local_port(6101).connect();
+    let a_gateway = SrtSocketBuilder::new_connect("127.0.0.1:6101").connect();
+    let b = SrtSocketBuilder::new_listen().local_port(6102).connect();
+    let b_gateway = SrtSocketBuilder::new_connect("127.0.0.1:6102").connect();
+    let (mut a, a_gateway, mut b, b_gateway) =
+        futures::try_join!(a, a_gateway, b, b_gateway).unwrap();
+
+    let (a_tcp, b_tcp) = futures::try_join!(
+        async { tcp_listener.accept().await.map(|(stream, _)| stream) },
+        TcpStream::connect("127.0.0.1:6103")
+    )
+    .unwrap();
+    tokio::spawn(bridge(a_gateway, a_tcp, LengthPrefixCodec::new(1316)));
+    tokio::spawn(bridge(b_gateway, b_tcp, LengthPrefixCodec::new(1316)));
+
+    let messages = ["one", "two", "three", "four"];
+    for message in &messages {
+        a.send((Instant::now(), Bytes::from(*message)))
+            .await
+            .unwrap();
+    }
+    for message in &messages {
+        assert_eq!(b.try_next().await.unwrap().unwrap().1, *message);
+    }
+
+    b.send((Instant::now(), Bytes::from("back"))).await.unwrap();
+    assert_eq!(a.try_next().await.unwrap().unwrap().1, "back");
+
+    // closing one end closes the other, through both bridges
+    a.close().await.unwrap();
+    assert_eq!(b.try_next().await.unwrap(), None);
+}
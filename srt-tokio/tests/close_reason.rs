@@ -0,0 +1,47 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{CloseReason, SrtSocketBuilder};
+
+/// Each side of a connection tells why it closed
+#[tokio::test]
+async fn close_reason() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6083).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6083").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+    assert_eq!(sender.close_reason(), None);
+
+    sender
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    sender.close().await.unwrap();
+    assert_eq!(sender.close_reason(), Some(CloseReason::Local));
+
+    let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "hello");
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+    assert_eq!(recvr.close_reason(), Some(CloseReason::PeerShutdown));
+}
+
+/// Dropping the sending half closes the connection, which the receiving half can tell
+#[tokio::test]
+async fn dropped() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen().local_port(6084).connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6084").connect();
+    let (a, mut b) = futures::try_join!(a, b).unwrap();
+
+    let (send, mut recv) = a.split();
+    drop(send);
+    assert_eq!(recv.try_next().await.unwrap(), None);
+    assert_eq!(recv.close_reason(), Some(CloseReason::Dropped));
+
+    assert_eq!(b.try_next().await.unwrap(), None);
+    assert_eq!(b.close_reason(), Some(CloseReason::PeerShutdown));
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{Clock, CoarseClock, SrtSocketBuilder};
+
+const RESOLUTION: Duration = Duration::from_millis(1);
+
+#[tokio::test]
+async fn coarse_clock_ticks() {
+    let _ = env_logger::try_init();
+
+    let clock = CoarseClock::new(RESOLUTION);
+    let start = clock.now();
+    delay_for(Duration::from_millis(50)).await;
+
+    let now = clock.now();
+    assert!(
+        now - start >= Duration::from_millis(40),
+        "{:?}",
+        now - start
+    );
+    assert!(now <= Instant::now());
+}
+
+/// A connection can read the time from a coarse clock on both ends
+#[tokio::test]
+async fn coarse_clock_connection() {
+    let _ = env_logger::try_init();
+
+    let clock = CoarseClock::new(RESOLUTION);
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6037")
+        .clock(clock.clone())
+        .latency(Duration::from_millis(100))
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6037)
+        .clock(clock.clone())
+        .latency(Duration::from_millis(100))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        for i in 0..100 {
+            sender
+                .send((clock.now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(2)).await;
+        }
+        sender.close().await.unwrap();
+    });
+
+    for i in 0..100 {
+        let (origin, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+        // released after the latency, give or take the resolution
+        let delay = Instant::now() - origin;
+        assert!(delay >= Duration::from_millis(95), "{:?}", delay);
+    }
+    assert!(recvr.try_next().await.unwrap().is_none());
+}
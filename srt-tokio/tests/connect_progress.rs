@@ -0,0 +1,60 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use srt_tokio::{ConnectPhase, ConnectProgress, SrtSocketBuilder};
+
+fn recorder() -> (ConnectProgress, Arc<Mutex<Vec<ConnectPhase>>>) {
+    let phases = Arc::new(Mutex::new(Vec::new()));
+    let progress = {
+        let phases = phases.clone();
+        ConnectProgress::new(move |phase| phases.lock().unwrap().push(phase))
+    };
+    (progress, phases)
+}
+
+/// Every phase of an encrypted caller is reported in order, once
+#[tokio::test]
+async fn caller_phases() {
+    let _ = env_logger::try_init();
+
+    let (caller_progress, caller_phases) = recorder();
+    let (listener_progress, listener_phases) = recorder();
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6104)
+        .crypto(16, "password123")
+        .connect_progress(listener_progress)
+        .connect();
+    let caller = SrtSocketBuilder::new_connect_host("127.0.0.1", 6104)
+        .crypto(16, "password123")
+        .connect_progress(caller_progress)
+        .connect();
+    let (_listener, _caller) = futures::try_join!(listener, caller).unwrap();
+
+    assert_eq!(
+        *caller_phases.lock().unwrap(),
+        [
+            ConnectPhase::Resolving,
+            ConnectPhase::InductionSent,
+            ConnectPhase::ConclusionSent,
+            ConnectPhase::KeyExchange,
+            ConnectPhase::Connected
+        ]
+    );
+    assert_eq!(*listener_phases.lock().unwrap(), [ConnectPhase::Connected]);
+}
+
+/// A caller with no one to answer it is stuck after sending the induction, however many times it sends it
+#[tokio::test]
+async fn stuck_in_induction() {
+    let _ = env_logger::try_init();
+
+    let (progress, phases) = recorder();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6105")
+        .connect_progress(progress)
+        .connect();
+    assert!(timeout(Duration::from_millis(500), caller).await.is_err());
+
+    assert_eq!(*phases.lock().unwrap(), [ConnectPhase::InductionSent]);
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use srt_protocol::packet::CipherType;
+use srt_tokio::{SrtSocketBuilder, SrtVersion, TransmissionType};
+
+#[tokio::test]
+async fn live_info() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6016)
+        .send_latency(Duration::from_millis(120))
+        .receive_latency(Duration::from_millis(80))
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6016")
+        .latency(Duration::from_millis(100))
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    for sock in &[&a, &b] {
+        let info = sock.info();
+        assert_eq!(info.peer_version, SrtVersion::CURRENT);
+        assert!(info.send_tsbpd);
+        assert!(info.recv_tsbpd);
+        assert!(info.too_late_packet_drop);
+        assert!(info.periodic_nak);
+        assert_eq!(info.cipher, CipherType::None);
+        assert_eq!(info.key_length, 0);
+        assert_eq!(info.stream_id, None);
+        assert_eq!(info.peer_metadata, None);
+        assert_eq!(info.app_protocol, None);
+    }
+
+    assert_eq!(a.info().send_latency, Duration::from_millis(120));
+    assert_eq!(a.info().recv_latency, Duration::from_millis(100));
+    assert_eq!(b.info().send_latency, Duration::from_millis(100));
+    assert_eq!(b.info().recv_latency, Duration::from_millis(120));
+}
+
+#[tokio::test]
+async fn encrypted_info() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6017)
+        .crypto(24, "password123")
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6017")
+        .crypto(24, "password123")
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    for sock in &[&a, &b] {
+        assert_eq!(sock.info().cipher, CipherType::CTR);
+        assert_eq!(sock.info().key_length, 24);
+    }
+}
+
+#[tokio::test]
+async fn file_info() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6018)
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6018")
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    for sock in &[&a, &b] {
+        let info = sock.info();
+        assert!(!info.send_tsbpd);
+        assert!(!info.recv_tsbpd);
+        assert!(!info.too_late_packet_drop);
+        assert!(!info.periodic_nak);
+    }
+}
+
+/// One side of a rendezvous wins the cookie contest, and initiates with a handshake request the other answers
+#[tokio::test]
+async fn rendezvous_connect_stats() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_rendezvous("127.0.0.1:6066")
+        .local_port(6065)
+        .connect();
+    let b = SrtSocketBuilder::new_rendezvous("127.0.0.1:6065")
+        .local_port(6066)
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    let (initiator, responder) = match a.settings().connect_stats.won_cookie_contest {
+        Some(true) => (&a, &b),
+        Some(false) => (&b, &a),
+        None => panic!("No cookie contest in rendezvous"),
+    };
+    let initiator = &initiator.settings().connect_stats;
+    let responder = &responder.settings().connect_stats;
+    assert_eq!(responder.won_cookie_contest, Some(false));
+    assert_eq!(initiator.peer_extension_types, vec![2]);
+    assert_eq!(responder.peer_extension_types, vec![1]);
+    assert!(initiator.connect_time > Duration::from_secs(0));
+    assert!(responder.connect_time > Duration::from_secs(0));
+}
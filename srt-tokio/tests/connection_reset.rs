@@ -0,0 +1,73 @@
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_protocol::{
+    packet::{ControlTypes, HandshakeControlInfo, HandshakeVSInfo, ShakeType, SocketType},
+    protocol::TimeStamp,
+    ControlPacket, Packet, SeqNumber, SocketID,
+};
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::SrtSocketBuilder;
+
+/// A caller that goes away in the middle of its handshake doesn't take the listener down with it
+///
+/// The listener's answer reaches a closed port, which Windows reports as a `WSAECONNRESET` on the listener's next
+/// receive.
+#[tokio::test]
+async fn connection_reset() {
+    let _ = env_logger::try_init();
+
+    let mut listener = SrtSocketBuilder::new_listen()
+        .local_port(6050)
+        .build_multiplexed()
+        .await
+        .unwrap()
+        .boxed();
+
+    let induction = Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: SocketID(0),
+        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+            init_seq_num: SeqNumber::new_truncate(0),
+            max_packet_size: 1500,
+            max_flow_size: 8192,
+            shake_type: ShakeType::Induction,
+            socket_id: SocketID(1234),
+            syn_cookie: 0,
+            peer_addr: [127, 0, 0, 1].into(),
+            info: HandshakeVSInfo::V4(SocketType::Datagram),
+        }),
+    });
+    let mut buf = vec![];
+    induction.serialize(&mut buf);
+    let stray = UdpSocket::bind("127.0.0.1:0").unwrap();
+    stray.send_to(&buf, "127.0.0.1:6050").unwrap();
+    drop(stray);
+
+    let caller = async {
+        // after the listener answered the stray caller
+        delay_for(Duration::from_millis(100)).await;
+        let mut caller = SrtSocketBuilder::new_connect("127.0.0.1:6050")
+            .connect()
+            .await
+            .unwrap();
+        caller
+            .send((Instant::now(), Bytes::from("still listening")))
+            .await
+            .unwrap();
+        caller.close().await.unwrap();
+    };
+    let listen = async move {
+        let (conn, chan) = listener.try_next().await.unwrap().unwrap();
+        let mut conn = create_bidrectional_srt(chan, conn);
+        // the multiplexer carries the connection's packets while it's polled
+        tokio::spawn(listener.for_each(|_| async {}));
+        let (_, data) = conn.try_next().await.unwrap().unwrap();
+        assert_eq!(data, "still listening");
+    };
+    futures::join!(caller, listen);
+}
@@ -1,6 +1,7 @@
+use std::io;
 use std::time::{Duration, Instant};
 
-use srt_tokio::SrtSocketBuilder;
+use srt_tokio::{KeyLengthPolicy, KmState, SrtSocketBuilder};
 
 use bytes::Bytes;
 use futures::{SinkExt, TryStreamExt};
@@ -50,4 +51,128 @@ async fn crypto_exchange() {
 }
 
 // TODO: bad password
-// TODO: mismatch
+
+/// Keys wrapped with a pre-shared key encrypting key, and the caller's stream encrypting key
+#[tokio::test]
+async fn pre_shared_key() {
+    let _ = env_logger::try_init();
+
+    let kek = [0x5a; 32];
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6113)
+        .pre_shared_key(&kek, None)
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6113")
+        .pre_shared_key(&kek, Some(&[0xa5; 32]))
+        .connect();
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+
+    assert_eq!(listener.info().key_length, 32);
+    assert_eq!(listener.info().recv_km_state, KmState::Secured);
+    caller
+        .send((Instant::now(), Bytes::from("Hello")))
+        .await
+        .unwrap();
+    let (_, by) = listener.try_next().await.unwrap().unwrap();
+    assert_eq!(&by[..], b"Hello");
+
+    // a passphrase can't stand in for the key
+    let listener = spawn(
+        SrtSocketBuilder::new_listen()
+            .local_port(6114)
+            .pre_shared_key(&kek, None)
+            .connect(),
+    );
+    let res = SrtSocketBuilder::new_connect("127.0.0.1:6114")
+        .crypto(32, "password123")
+        .connect()
+        .await;
+    assert_eq!(
+        res.map(|_| ()).unwrap_err().kind(),
+        io::ErrorKind::ConnectionRefused
+    );
+    drop(listener);
+}
+
+#[test]
+#[should_panic]
+fn pre_shared_key_length() {
+    SrtSocketBuilder::new_listen().pre_shared_key(&[0; 16], Some(&[0; 32]));
+}
+
+/// By default, the listener takes the caller's key length
+#[tokio::test]
+async fn key_length_adopted() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6110)
+        .crypto(16, "password123")
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6110")
+        .crypto(32, "password123")
+        .connect();
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+
+    assert_eq!(listener.info().key_length, 32);
+    assert!(listener.info().key_length_adopted);
+    assert_eq!(caller.info().key_length, 32);
+    assert!(!caller.info().key_length_adopted);
+    assert_eq!(listener.info().recv_km_state, KmState::Secured);
+
+    caller
+        .send((Instant::now(), Bytes::from("Hello")))
+        .await
+        .unwrap();
+    let (_, by) = listener.try_next().await.unwrap().unwrap();
+    assert_eq!(&by[..], b"Hello");
+}
+
+/// A listener that won't downgrade refuses shorter keys
+#[tokio::test]
+async fn key_length_downgrade_refused() {
+    let _ = env_logger::try_init();
+
+    let listener = spawn(
+        SrtSocketBuilder::new_listen()
+            .local_port(6111)
+            .crypto(32, "password123")
+            .key_length_policy(KeyLengthPolicy::NoDowngrade)
+            .connect(),
+    );
+    let res = SrtSocketBuilder::new_connect("127.0.0.1:6111")
+        .crypto(16, "password123")
+        .connect()
+        .await;
+
+    assert_eq!(
+        res.map(|_| ()).unwrap_err().kind(),
+        io::ErrorKind::ConnectionRefused
+    );
+    drop(listener);
+}
+
+/// Without enforced encryption, a refused key length connects, with keys neither side can use
+#[tokio::test]
+async fn key_length_mismatch_unenforced() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6112)
+        .crypto(16, "password123")
+        .key_length_policy(KeyLengthPolicy::Exact)
+        .enforced_encryption(false)
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6112")
+        .crypto(24, "password123")
+        .enforced_encryption(false)
+        .connect();
+    let (listener, caller) = futures::try_join!(listener, caller).unwrap();
+
+    for sock in &[&listener, &caller] {
+        assert_eq!(sock.info().send_km_state, KmState::BadCryptoMode);
+        assert_eq!(sock.info().recv_km_state, KmState::BadCryptoMode);
+    }
+    assert_eq!(listener.info().key_length, 16);
+    assert_eq!(caller.info().key_length, 24);
+}
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use futures::channel::mpsc;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use srt_protocol::packet::ControlTypes;
+use srt_protocol::protocol::TimeStamp;
+use srt_protocol::{ControlPacket, Packet};
+use srt_tokio::SrtSocketBuilder;
+
+/// Control packets of a type SRT doesn't know about should be passed to the handler, and not break the connection
+#[tokio::test]
+async fn custom_control_packet() {
+    let _ = env_logger::try_init();
+
+    let (handled_send, mut handled) = mpsc::unbounded();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6009").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6009)
+        .control_packet_handler(move |packet| {
+            let _ = handled_send.unbounded_send(packet.control_type.clone());
+        })
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let custom = ControlTypes::Custom {
+        custom_type: 0x7FFF,
+        reserved: 0x1234,
+        additional_info: 7,
+        payload: Bytes::from_static(b"hello"),
+    };
+    let mut buf = BytesMut::new();
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: recvr.settings().local_sockid,
+        control_type: custom.clone(),
+    })
+    .serialize(&mut buf);
+
+    let mut raw = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    raw.send_to(&buf[..], "127.0.0.1:6009").await.unwrap();
+
+    let received = timeout(Duration::from_secs(1), handled.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(received, custom);
+
+    // the connection still works after the custom packet
+    sender
+        .send((Instant::now(), Bytes::from_static(b"after")))
+        .await
+        .unwrap();
+    sender.close().await.unwrap();
+
+    let (_, msg) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(msg, "after");
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
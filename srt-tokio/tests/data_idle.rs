@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+use tokio::time::{delay_for, timeout};
+
+use srt_tokio::{DataIdleEvent, DataIdleMonitor, SrtSocketBuilder};
+
+/// The monitor should be called once the sender pauses for longer than the timeout, then again when it goes on
+#[tokio::test]
+async fn data_idle_monitor() {
+    let _ = env_logger::try_init();
+
+    let (events_send, mut events) = mpsc::unbounded();
+    let monitor = DataIdleMonitor::new(Duration::from_millis(200), move |event| {
+        let _ = events_send.unbounded_send((Instant::now(), event));
+    });
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6053").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6053)
+        .data_idle_monitor(monitor)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        sender
+            .send((Instant::now(), Bytes::from("before")))
+            .await
+            .unwrap();
+        // the connection stays up, only the data stops
+        delay_for(Duration::from_millis(600)).await;
+        sender
+            .send((Instant::now(), Bytes::from("after")))
+            .await
+            .unwrap();
+        sender.close().await.unwrap();
+    });
+
+    assert_eq!(recvr.try_next().await.unwrap().unwrap().1, "before");
+    let stopped = Instant::now();
+
+    let (idle_at, idle) = timeout(Duration::from_secs(1), events.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(idle, DataIdleEvent::Idle { idle_for } if idle_for >= Duration::from_millis(200)),
+        "{:?}",
+        idle
+    );
+    assert!(idle_at - stopped < Duration::from_millis(300));
+
+    let (_, resumed) = timeout(Duration::from_secs(1), events.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        matches!(resumed, DataIdleEvent::Resumed { gap } if gap >= Duration::from_millis(500)),
+        "{:?}",
+        resumed
+    );
+
+    assert_eq!(recvr.try_next().await.unwrap().unwrap().1, "after");
+    assert_eq!(recvr.stats().receiver.data_idle_periods, 1);
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{PacketDirection, SrtSocketBuilder};
+
+#[tokio::test]
+async fn debug_dump() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6038)
+        .latency(Duration::from_millis(50))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6038")
+        .latency(Duration::from_millis(50))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    for i in 0..50 {
+        sender
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+    }
+    sender.flush().await.unwrap();
+    for _ in 0..50 {
+        recvr.try_next().await.unwrap().unwrap();
+    }
+    // let the last ACK2 through
+    delay_for(Duration::from_millis(50)).await;
+
+    let sent = sender.debug_dump().await.unwrap();
+    assert_eq!(sent.local_sockid, sender.settings().local_sockid);
+    assert_eq!(sent.info, sender.info());
+    assert_eq!(sent.sender.transmit_buffer, 0);
+    assert_eq!(sent.sender.send_buffer, 0);
+    assert!(sent.sender.loss_list.is_empty());
+    assert_eq!(
+        sent.sender.lr_acked_packet,
+        sender.settings().init_send_seq_num + 50
+    );
+    assert!(sent
+        .recent_control
+        .iter()
+        .any(|r| r.direction == PacketDirection::Ingress));
+    assert!(sent.recent_control.windows(2).all(|w| w[0].at <= w[1].at));
+
+    let received = recvr.debug_dump().await.unwrap();
+    assert_eq!(received.receiver.next_release, sent.sender.lr_acked_packet);
+    assert_eq!(received.receiver.buffered_packets, 0);
+    assert!(received.receiver.loss_list.is_empty());
+    assert_eq!(received.receiver.lr_ack_acked, sent.sender.lr_acked_packet);
+
+    let report = received.to_string();
+    assert!(report.contains("receiver: next_release="), "{}", report);
+    assert!(report.contains("recent control packets"), "{}", report);
+
+    sender.close().await.unwrap();
+    assert!(recvr.try_next().await.unwrap().is_none());
+    // the connection is gone
+    assert!(sender.debug_dump().await.is_none());
+}
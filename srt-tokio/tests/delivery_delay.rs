@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// On a link without loss, messages are delivered with the configured latency, give or take the round trip time
+#[tokio::test]
+async fn delivery_delay() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6054")
+        .latency(Duration::from_millis(200))
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6054)
+        .latency(Duration::from_millis(200))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        for i in 0..20 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(10)).await;
+        }
+        sender.close().await.unwrap();
+    });
+
+    let mut received = 0;
+    while recvr.try_next().await.unwrap().is_some() {
+        received += 1;
+    }
+    assert_eq!(received, 20);
+
+    let stats = recvr.stats().receiver;
+    assert_eq!(stats.latency, Duration::from_millis(200));
+    assert!(
+        stats.delivery_delay >= Duration::from_millis(200)
+            && stats.delivery_delay < Duration::from_millis(250),
+        "{:?}",
+        stats.delivery_delay
+    );
+    assert!(stats.max_delivery_delay >= stats.delivery_delay);
+}
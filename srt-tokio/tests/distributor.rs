@@ -0,0 +1,129 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+use futures::stream;
+use tokio::spawn;
+use tokio::time::interval;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{SlowSubscriberPolicy, SrtSocket, SrtSocketBuilder, StreamDistributor};
+
+const ITERS: u32 = 100;
+
+fn counting_stream() -> impl Stream<Item = Result<(Instant, Bytes), io::Error>> + Unpin {
+    stream::iter(0..ITERS)
+        .zip(interval(Duration::from_millis(1)))
+        .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))))
+}
+
+async fn expect_all(mut sock: SrtSocket) {
+    for i in 0..ITERS {
+        let (_, payload) = sock.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+    assert_eq!(sock.try_next().await.unwrap(), None);
+}
+
+// a subscriber that never accepts anything, and never closes
+fn stuck_subscriber() -> (
+    impl Sink<(Instant, Bytes), Error = io::Error>,
+    mpsc::Receiver<(Instant, Bytes)>,
+) {
+    let (tx, rx) = mpsc::channel(0);
+    let tx = tx.sink_map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e));
+    (tx, rx)
+}
+
+/// Every subscriber should get the whole stream, and be closed when it ends
+#[tokio::test]
+async fn distributor() {
+    let _ = env_logger::try_init();
+
+    let mut listener = SrtSocketBuilder::new_listen()
+        .local_port(6012)
+        .build_multiplexed()
+        .await
+        .unwrap()
+        .map(|res| res.unwrap())
+        .map(|(conn, chan)| create_bidrectional_srt(chan, conn))
+        .boxed();
+
+    let (connected, connected_recv) = mpsc::unbounded();
+    let clients: Vec<_> = (0..3)
+        .map(|_| {
+            let connected = connected.clone();
+            spawn(async move {
+                let sock = SrtSocketBuilder::new_connect("127.0.0.1:6012")
+                    .connect()
+                    .await
+                    .unwrap();
+                connected.unbounded_send(()).unwrap();
+                expect_all(sock).await;
+            })
+        })
+        .collect();
+
+    // start once everyone is connected
+    let source = connected_recv
+        .take(3)
+        .collect::<Vec<_>>()
+        .into_stream()
+        .flat_map(|_| counting_stream());
+
+    let mut distributor = StreamDistributor::new(1024, SlowSubscriberPolicy::DropPackets);
+    distributor.run(source, &mut listener).await.unwrap();
+    assert_eq!(distributor.dropped_packets(), 0);
+
+    // the multiplexer needs to keep running for the subscribers to finish
+    futures::select! {
+        res = future::try_join_all(clients).fuse() => {
+            res.unwrap();
+        },
+        _ = listener.next().fuse() => panic!("Unexpected connection"),
+    }
+}
+
+/// A stuck subscriber should be disconnected, without holding back the others
+#[tokio::test]
+async fn disconnect_slow_subscriber() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6013").connect();
+    let recvr = SrtSocketBuilder::new_listen().local_port(6013).connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let (stuck, _stuck_recv) = stuck_subscriber();
+    let mut distributor = StreamDistributor::new(16, SlowSubscriberPolicy::Disconnect);
+    distributor.add(Box::pin(stuck));
+    distributor.add(sender);
+    assert_eq!(distributor.len(), 2);
+
+    let recvr = spawn(expect_all(recvr));
+    distributor
+        .run(counting_stream(), stream::empty::<SrtSocket>())
+        .await
+        .unwrap();
+
+    recvr.await.unwrap();
+    assert_eq!(distributor.dropped_packets(), 0);
+}
+
+/// With the drop policy, a stuck subscriber loses packets but stays connected
+#[tokio::test]
+async fn drop_slow_subscriber_packets() {
+    let _ = env_logger::try_init();
+
+    let (stuck, _stuck_recv) = stuck_subscriber();
+    let mut distributor = StreamDistributor::new(16, SlowSubscriberPolicy::DropPackets);
+    distributor.add(Box::pin(stuck));
+
+    for i in 0..ITERS {
+        distributor.distribute((Instant::now(), Bytes::from(i.to_string())));
+    }
+    assert_eq!(distributor.len(), 1);
+    assert!(distributor.dropped_packets() > 0);
+    assert!(distributor.dropped_packets() < u64::from(ITERS));
+}
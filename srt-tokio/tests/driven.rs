@@ -0,0 +1,40 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::future;
+use futures::prelude::*;
+
+use srt_tokio::{CloseReason, SrtSocketBuilder};
+
+/// A connection runs as long as its driver does, and ends as soon as the driver is dropped
+#[tokio::test]
+async fn driven() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6125)
+        .connect_driven();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6125").connect_driven();
+    let ((mut a, a_driver), (mut b, b_driver)) = futures::try_join!(a, b).unwrap();
+
+    let (a_driver, abort) = future::abortable(a_driver);
+    let a_driver = tokio::spawn(a_driver);
+    tokio::spawn(b_driver);
+
+    a.send((Instant::now(), Bytes::from_static(b"hello")))
+        .await
+        .unwrap();
+    let (_, payload) = b.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "hello");
+    assert_eq!(a.close_reason(), None);
+
+    abort.abort();
+    assert!(a_driver.await.unwrap().is_err());
+
+    assert_eq!(a.try_next().await.unwrap(), None);
+    assert_eq!(a.close_reason(), Some(CloseReason::Aborted));
+    assert!(a
+        .send((Instant::now(), Bytes::from_static(b"goodbye")))
+        .await
+        .is_err());
+}
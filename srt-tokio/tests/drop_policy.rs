@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{RateLimit, SendDropPolicy, SrtSocketBuilder};
+
+/// A live sender dropping the newest messages sends everything it took, and counts what it didn't
+#[tokio::test]
+async fn drop_newest() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6052)
+        .latency(Duration::from_millis(200))
+        .rate_limit(RateLimit::new(20_000))
+        .send_drop_policy(SendDropPolicy::DropNewest)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6052")
+        .latency(Duration::from_millis(200))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // 50kB/s, more than twice the rate limit
+    let sending = tokio::spawn(async move {
+        let mut handle = sender.sender();
+        for i in 0..60u8 {
+            handle
+                .send((Instant::now(), Bytes::from(vec![i; 956])))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(20)).await;
+        }
+        drop(handle);
+        delay_for(Duration::from_secs(2)).await;
+        let stats = sender.stats().sender;
+        sender.close().await.unwrap();
+        stats
+    });
+
+    let mut received = vec![];
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        received.push(payload[0]);
+    }
+    let stats = sending.await.unwrap();
+
+    // nothing queued was dropped, every message taken arrives, in order
+    assert_eq!(stats.dropped_packets, 0);
+    assert!(stats.dropped_messages > 0);
+    assert_eq!(stats.dropped_bytes, u64::from(stats.dropped_messages) * 956);
+    assert_eq!(received.len() + stats.dropped_messages as usize, 60);
+    assert!(received.windows(2).all(|w| w[0] < w[1]), "{:?}", received);
+}
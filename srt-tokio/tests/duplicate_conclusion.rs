@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use srt_protocol::{
+    packet::{
+        ControlTypes, HandshakeControlInfo, HandshakeVSInfo, ShakeType, SocketType,
+        SrtControlPacket, SrtHandshake, SrtShakeFlags,
+    },
+    protocol::TimeStamp,
+    ControlPacket, Packet, SeqNumber, SocketID,
+};
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{SrtSocketBuilder, SrtVersion};
+
+async fn send(sock: &mut UdpSocket, shake: HandshakeControlInfo) {
+    let mut buf = vec![];
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: SocketID(0),
+        control_type: ControlTypes::Handshake(shake),
+    })
+    .serialize(&mut buf);
+    sock.send_to(&buf, "127.0.0.1:6132").await.unwrap();
+}
+
+// the next handshake, once connected there are other packets too
+async fn recv(sock: &mut UdpSocket) -> HandshakeControlInfo {
+    let mut buf = [0; 1500];
+    loop {
+        let (len, _) = timeout(Duration::from_secs(2), sock.recv_from(&mut buf))
+            .await
+            .expect("no handshake in time")
+            .unwrap();
+        if let Packet::Control(ControlPacket {
+            control_type: ControlTypes::Handshake(shake),
+            ..
+        }) = Packet::parse(&mut &buf[..len]).unwrap()
+        {
+            return shake;
+        }
+    }
+}
+
+/// A caller that didn't get the answer to its conclusion sends it again, the multiplexer answers the same without
+/// accepting another connection
+#[tokio::test]
+async fn duplicate_conclusion() {
+    let _ = env_logger::try_init();
+
+    let mut listener = SrtSocketBuilder::new_listen()
+        .local_port(6132)
+        .build_multiplexed()
+        .await
+        .unwrap()
+        .boxed();
+
+    let caller = async {
+        let mut sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let induction = HandshakeControlInfo {
+            init_seq_num: SeqNumber::new_truncate(1234),
+            max_packet_size: 1500,
+            max_flow_size: 8192,
+            shake_type: ShakeType::Induction,
+            socket_id: SocketID(5678),
+            syn_cookie: 0,
+            peer_addr: [127, 0, 0, 1].into(),
+            info: HandshakeVSInfo::V4(SocketType::Datagram),
+        };
+        send(&mut sock, induction.clone()).await;
+        let cookie = recv(&mut sock).await.syn_cookie;
+
+        let conclusion = HandshakeControlInfo {
+            shake_type: ShakeType::Conclusion,
+            syn_cookie: cookie,
+            info: HandshakeVSInfo::V5 {
+                crypto_size: 0,
+                ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
+                    version: SrtVersion::CURRENT,
+                    flags: SrtShakeFlags::SUPPORTED,
+                    send_latency: Duration::from_millis(120),
+                    recv_latency: Duration::from_millis(120),
+                })),
+                ext_km: None,
+                ext_config: None,
+                ext_other: vec![],
+            },
+            ..induction
+        };
+        send(&mut sock, conclusion.clone()).await;
+        let response = recv(&mut sock).await;
+        (sock, conclusion, response)
+    };
+    let ((mut sock, conclusion, response), accepted) = futures::join!(caller, listener.try_next());
+    let (conn, chan) = accepted.unwrap().unwrap();
+    assert_eq!(response.shake_type, ShakeType::Conclusion);
+    assert_eq!(response.socket_id, conn.settings.local_sockid);
+    let _conn = create_bidrectional_srt(chan, conn);
+
+    // the multiplexer carries the connection's packets while it's polled
+    let again = async {
+        send(&mut sock, conclusion).await;
+        recv(&mut sock).await
+    };
+    let (again, next) = futures::join!(
+        again,
+        timeout(Duration::from_millis(500), listener.try_next())
+    );
+    assert_eq!(again, response);
+    assert!(next.is_err(), "accepted another connection");
+}
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{CloseReason, ConnectionEvent, SrtSocketBuilder};
+
+/// The events of a connection are kept after it closes
+#[tokio::test]
+async fn event_log() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen().local_port(6129).connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6129").connect();
+    let (mut a, mut b) = futures::try_join!(a, b).unwrap();
+
+    b.set_latency(Duration::from_millis(500));
+    // handled by the connection's task in its own time
+    delay_for(Duration::from_millis(100)).await;
+    let dump = b.debug_dump().await.unwrap();
+    let events: Vec<_> = dump.events.into_iter().map(|r| r.event).collect();
+    assert!(matches!(events[0], ConnectionEvent::Connected { .. }));
+    assert_eq!(
+        events[1],
+        ConnectionEvent::LatencyRaised(Duration::from_millis(500))
+    );
+
+    a.close().await.unwrap();
+    assert_eq!(b.try_next().await.unwrap(), None);
+    assert_eq!(b.close_reason(), Some(CloseReason::PeerShutdown));
+
+    let events: Vec<_> = b.events().into_iter().map(|r| r.event).collect();
+    assert_eq!(
+        events.last(),
+        Some(&ConnectionEvent::Closed(
+            CloseReason::PeerShutdown.to_string()
+        ))
+    );
+}
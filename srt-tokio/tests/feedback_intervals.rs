@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::interval;
+
+use srt_protocol::packet::ControlTypes;
+use srt_protocol::{ControlPacket, Packet};
+use srt_tokio::{PacketDirection, PacketTap, SrtSocketBuilder};
+
+/// A long ACK interval acknowledges a steady stream far less often than the RTT would
+#[tokio::test]
+async fn ack_interval() {
+    let _ = env_logger::try_init();
+
+    let acks = Arc::new(AtomicUsize::new(0));
+    let tap = {
+        let acks = acks.clone();
+        PacketTap::new(move |_, direction, packet, _| {
+            if direction == PacketDirection::Egress
+                && matches!(
+                    packet,
+                    Packet::Control(ControlPacket {
+                        control_type: ControlTypes::Ack { .. },
+                        ..
+                    })
+                )
+            {
+                acks.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    };
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6109").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6109)
+        .ack_interval(Duration::from_millis(100))
+        .nak_interval(Duration::from_millis(200))
+        .packet_tap(tap)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // 500ms of data
+    let mut messages = stream::iter(0..250)
+        .zip(interval(Duration::from_millis(2)))
+        .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))));
+    let send = async {
+        sender.send_all(&mut messages).await.unwrap();
+        sender.close().await.unwrap();
+    };
+    let receive = async {
+        let mut count = 0;
+        while recvr.try_next().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 250);
+    };
+    futures::join!(send, receive);
+
+    let acks = acks.load(Ordering::SeqCst);
+    assert!((3..=8).contains(&acks), "{} ACKs sent", acks);
+}
+
+#[test]
+#[should_panic]
+fn ack_interval_too_short() {
+    SrtSocketBuilder::new_listen().ack_interval(Duration::from_micros(500));
+}
+
+#[test]
+#[should_panic]
+fn nak_interval_too_long() {
+    SrtSocketBuilder::new_listen().nak_interval(Duration::from_secs(11));
+}
@@ -0,0 +1,56 @@
+use srt_tokio::{SrtSocketBuilder, TransmissionType};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use std::time::{Duration, Instant};
+
+/// In file mode there is no TSBPD, so even with a large latency configured messages should arrive right away, and all of them should arrive
+#[tokio::test]
+async fn file_transmission() {
+    let _ = env_logger::try_init();
+
+    const PACKETS: u32 = 1_000;
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6000")
+        .transmission_type(TransmissionType::File)
+        .latency(Duration::from_secs(5))
+        .connect();
+
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6000)
+        .transmission_type(TransmissionType::File)
+        .latency(Duration::from_secs(5))
+        .connect();
+
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    assert_eq!(sender.settings().transmission_type, TransmissionType::File);
+
+    let sendr_fut = async move {
+        for i in 0..PACKETS {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+        }
+        sender.close().await.unwrap();
+    };
+
+    let recvr_fut = async move {
+        let start = Instant::now();
+        let mut next = 0;
+        while let Some((_, packet)) = recvr.try_next().await.unwrap() {
+            assert_eq!(packet, next.to_string());
+            next += 1;
+        }
+        assert_eq!(next, PACKETS);
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "File mode should not wait for the latency, took {:?}",
+            start.elapsed()
+        );
+    };
+
+    futures::join!(sendr_fut, recvr_fut);
+}
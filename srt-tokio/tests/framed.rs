@@ -0,0 +1,69 @@
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio_util::codec::{LengthDelimitedCodec, LinesCodec};
+
+use srt_tokio::{SrtFramed, SrtSocketBuilder, TransmissionType};
+
+/// Frames come out as they went in, whatever the messages they were split or packed into
+#[tokio::test]
+async fn length_delimited() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6059)
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6059")
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let frames: Vec<Bytes> = (0..20usize)
+        .map(|i| Bytes::from(vec![i as u8; i * 7919]))
+        .collect();
+
+    let mut sender = SrtFramed::new(sender, LengthDelimitedCodec::new());
+    let to_send = frames.clone();
+    tokio::spawn(async move {
+        for frame in to_send {
+            sender.feed(frame).await.unwrap();
+        }
+        sender.close().await.unwrap();
+    });
+
+    let received: Vec<_> = SrtFramed::new(recvr, LengthDelimitedCodec::new())
+        .map_ok(|frame| frame.freeze())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(received, frames);
+}
+
+/// Typed messages, with a codec of the lines of text
+#[tokio::test]
+async fn lines() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6060)
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6060")
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let mut sender = SrtFramed::new(sender, LinesCodec::new());
+    tokio::spawn(async move {
+        for line in &["{\"id\":1}", "{\"id\":2}", "{\"id\":3}"] {
+            sender.send(*line).await.unwrap();
+        }
+        futures::SinkExt::<&str>::close(&mut sender).await.unwrap();
+    });
+
+    let mut recvr = SrtFramed::new(recvr, LinesCodec::new());
+    assert_eq!(recvr.try_next().await.unwrap().unwrap(), "{\"id\":1}");
+    assert_eq!(recvr.try_next().await.unwrap().unwrap(), "{\"id\":2}");
+    assert_eq!(recvr.try_next().await.unwrap().unwrap(), "{\"id\":3}");
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
@@ -0,0 +1,128 @@
+use std::io;
+
+use srt_tokio::{HandshakeExtension, SrtSocketBuilder};
+use tokio::spawn;
+
+/// Extension blocks from the application reach the peer's settings, in both directions
+#[tokio::test]
+async fn handshake_extensions() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6047)
+        .handshake_extension(0x7f00, &b"from listener"[..])
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6047")
+        .handshake_extension(0x7f01, &b"one"[..])
+        .handshake_extension(0x7f02, &b"two!"[..])
+        .connect();
+    let (caller, listener) = futures::try_join!(caller, listener).unwrap();
+
+    let ext = |type_id, data: &'static [u8]| HandshakeExtension {
+        type_id,
+        data: data.into(),
+    };
+    assert_eq!(
+        listener.settings().peer_extensions,
+        [ext(0x7f01, b"one\0"), ext(0x7f02, b"two!")]
+    );
+    assert_eq!(
+        caller.settings().peer_extensions,
+        [ext(0x7f00, b"from listener\0\0\0")]
+    );
+}
+
+/// Application metadata reaches the peer's info, with the last set replacing the ones before
+#[tokio::test]
+async fn metadata() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6098)
+        .metadata(b"session=1234\0")
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6098")
+        .metadata(b"codec=h264")
+        .metadata(b"codec=hevc")
+        .connect();
+    let (caller, listener) = futures::try_join!(caller, listener).unwrap();
+
+    assert_eq!(
+        listener.info().peer_metadata.as_deref(),
+        Some(&b"codec=hevc"[..])
+    );
+    assert_eq!(
+        caller.info().peer_metadata.as_deref(),
+        Some(&b"session=1234\0"[..])
+    );
+}
+
+/// Metadata that wouldn't fit in the handshake is turned down up front
+#[test]
+#[should_panic]
+fn metadata_too_long() {
+    let _ = SrtSocketBuilder::new_listen().metadata(&[0; HandshakeExtension::MAX_METADATA + 1]);
+}
+
+/// The listener picks the first of its application protocols the caller offered
+#[tokio::test]
+async fn app_protocol() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6115)
+        .app_protocols(&["live/2", "live/1", "file"])
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6115")
+        .app_protocols(&["file", "live/1", "live/2"])
+        .connect();
+    let (caller, listener) = futures::try_join!(caller, listener).unwrap();
+
+    assert_eq!(listener.info().app_protocol.as_deref(), Some("live/2"));
+    assert_eq!(caller.info().app_protocol.as_deref(), Some("live/2"));
+}
+
+/// Only one side setting application protocols negotiates none
+#[tokio::test]
+async fn app_protocol_one_sided() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen().local_port(6116).connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6116")
+        .app_protocols(&["live/1"])
+        .connect();
+    let (caller, listener) = futures::try_join!(caller, listener).unwrap();
+
+    assert_eq!(listener.info().app_protocol, None);
+    assert_eq!(caller.info().app_protocol, None);
+}
+
+/// A caller offering none of the listener's application protocols is refused
+#[tokio::test]
+async fn app_protocol_refused() {
+    let _ = env_logger::try_init();
+
+    let listener = spawn(
+        SrtSocketBuilder::new_listen()
+            .local_port(6117)
+            .app_protocols(&["live/2"])
+            .connect(),
+    );
+    let res = SrtSocketBuilder::new_connect("127.0.0.1:6117")
+        .app_protocols(&["live/1", "file"])
+        .connect()
+        .await;
+
+    assert_eq!(
+        res.map(|_| ()).unwrap_err().kind(),
+        io::ErrorKind::ConnectionRefused
+    );
+    drop(listener);
+}
+
+/// There's no negotiating over no application protocols
+#[test]
+#[should_panic]
+fn app_protocols_empty() {
+    let _ = SrtSocketBuilder::new_listen().app_protocols(&[]);
+}
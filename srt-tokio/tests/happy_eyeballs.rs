@@ -0,0 +1,36 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio::time::timeout;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// An unreachable first address shouldn't hold up connecting to the next one
+#[tokio::test]
+async fn happy_eyeballs() {
+    let _ = env_logger::try_init();
+
+    // nothing listens on 6033
+    let addrs: Vec<SocketAddr> = vec![
+        "127.0.0.1:6033".parse().unwrap(),
+        "127.0.0.1:6034".parse().unwrap(),
+    ];
+
+    let start = Instant::now();
+    let sender = SrtSocketBuilder::new_connect(&addrs[..]).connect();
+    let recvr = SrtSocketBuilder::new_listen().local_port(6034).connect();
+    let (sender, _recvr) = timeout(
+        Duration::from_secs(2),
+        futures::future::try_join(sender, recvr),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(sender.settings().remote, addrs[1]);
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "{:?}",
+        start.elapsed()
+    );
+}
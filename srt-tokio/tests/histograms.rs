@@ -0,0 +1,51 @@
+#![cfg(feature = "histograms")]
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// On a link without loss, the delivery delay percentiles are all about the latency, and there are round trip times
+#[tokio::test]
+async fn percentiles() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6118")
+        .latency(Duration::from_millis(200))
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6118)
+        .latency(Duration::from_millis(200))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        for i in 0..50 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(10)).await;
+        }
+        sender.close().await.unwrap();
+    });
+
+    while recvr.try_next().await.unwrap().is_some() {}
+
+    let stats = recvr.stats().receiver;
+    let delay = stats.delivery_delay_percentiles;
+    assert!(delay.samples > 0 && delay.samples <= 50, "{:?}", delay);
+    assert!(
+        delay.p50 >= Duration::from_millis(198) && delay.max < Duration::from_millis(260),
+        "{:?}",
+        delay
+    );
+    assert!(delay.p50 <= delay.p90 && delay.p90 <= delay.p99 && delay.p99 <= delay.max);
+
+    let rtt = stats.rtt_percentiles;
+    assert!(rtt.samples > 0, "{:?}", rtt);
+    assert!(rtt.max < Duration::from_millis(100), "{:?}", rtt);
+}
@@ -0,0 +1,58 @@
+use std::net::UdpSocket;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{CloseReason, SrtSocketBuilder};
+
+/// A suspended connection carries on from its snapshot over a copy of its socket, like a new process inheriting it
+#[tokio::test]
+async fn hot_restart() {
+    let _ = env_logger::try_init();
+
+    let sock = UdpSocket::bind("127.0.0.1:6089").unwrap();
+    let inherited = sock.try_clone().unwrap();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .crypto(16, "password123")
+        .connect_with_udp(sock);
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6089")
+        .local_port(6090)
+        .crypto(16, "password123")
+        .connect();
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+
+    caller
+        .send((Instant::now(), Bytes::from("before")))
+        .await
+        .unwrap();
+    assert_eq!(listener.try_next().await.unwrap().unwrap().1, "before");
+
+    let snapshot = listener.suspend().await.unwrap();
+
+    assert!(SrtSocketBuilder::new_listen()
+        .crypto(16, "password456")
+        .restore(snapshot.clone(), inherited.try_clone().unwrap())
+        .is_err());
+    let mut restored = SrtSocketBuilder::new_listen()
+        .crypto(16, "password123")
+        .restore(snapshot, inherited)
+        .unwrap();
+
+    caller
+        .send((Instant::now(), Bytes::from("after")))
+        .await
+        .unwrap();
+    assert_eq!(restored.try_next().await.unwrap().unwrap().1, "after");
+
+    restored
+        .send((Instant::now(), Bytes::from("back")))
+        .await
+        .unwrap();
+    assert_eq!(caller.try_next().await.unwrap().unwrap().1, "back");
+
+    restored.close().await.unwrap();
+    assert_eq!(caller.try_next().await.unwrap(), None);
+    assert_eq!(caller.close_reason(), Some(CloseReason::PeerShutdown));
+}
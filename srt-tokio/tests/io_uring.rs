@@ -0,0 +1,47 @@
+#![cfg(all(feature = "io-uring", target_os = "linux"))]
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{SrtSocket, SrtSocketBuilder};
+
+async fn exchange(sock: &mut SrtSocket) {
+    let mut tx = sock.sender();
+    let send = async {
+        for i in 0..1000 {
+            tx.send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            if i % 100 == 0 {
+                delay_for(Duration::from_millis(1)).await;
+            }
+        }
+    };
+    let recv = async {
+        for i in 0..1000 {
+            let (_, data) = sock.try_next().await.unwrap().unwrap();
+            assert_eq!(data, i.to_string());
+        }
+    };
+    futures::join!(send, recv);
+}
+
+/// A connection over io_uring talks to one over the reactor's socket, in both directions
+#[tokio::test]
+async fn io_uring() {
+    let _ = env_logger::try_init();
+
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6049)
+        .io_uring(true)
+        .connect();
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6049").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    futures::join!(exchange(&mut sender), exchange(&mut recvr));
+
+    futures::try_join!(sender.close(), recvr.close()).unwrap();
+}
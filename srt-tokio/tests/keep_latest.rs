@@ -0,0 +1,58 @@
+use std::str;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{RateLimit, SrtSocketBuilder};
+
+/// While the link can't keep up, updates replace the ones of their key still waiting, and the newest gets through
+#[tokio::test]
+async fn keep_latest() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6131)
+        .latency(Duration::from_secs(1))
+        .rate_limit(RateLimit::new(10_000))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6131")
+        .latency(Duration::from_secs(1))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let sending = tokio::spawn(async move {
+        let mut handle = sender.sender();
+        // far more than the rate limit allows, two sources of 500 byte updates
+        for i in 0..100_u32 {
+            for key in 0..2 {
+                let update = format!("{} {:<500}", key, i);
+                handle
+                    .send_keep_latest((Instant::now(), Bytes::from(update)), key)
+                    .await
+                    .unwrap();
+            }
+        }
+        drop(handle);
+        sender.close().await.unwrap();
+        sender.stats().sender
+    });
+
+    let mut received = vec![vec![], vec![]];
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        let mut update = str::from_utf8(&payload).unwrap().split_whitespace();
+        let key: usize = update.next().unwrap().parse().unwrap();
+        received[key].push(update.next().unwrap().parse::<u32>().unwrap());
+    }
+    let stats = sending.await.unwrap();
+
+    for updates in &received {
+        assert_eq!(updates.last(), Some(&99));
+        assert!(updates.len() < 20, "{:?}", updates);
+        assert!(updates.windows(2).all(|w| w[0] < w[1]), "{:?}", updates);
+    }
+    assert_eq!(
+        received[0].len() + received[1].len() + stats.dropped_messages as usize,
+        200
+    );
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Connections reading kernel receive timestamps still transfer everything, and measure the arrival speed
+#[tokio::test]
+async fn kernel_timestamps() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6044)
+        .kernel_timestamps(true)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6044")
+        .kernel_timestamps(true)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let recvr_fut = async move {
+        let mut count = 0;
+        while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+            assert_eq!(payload, count.to_string());
+            count += 1;
+        }
+        assert_eq!(count, 200);
+    };
+
+    let sendr_fut = async move {
+        for i in 0..200 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(5)).await;
+        }
+        sender.flush().await.unwrap();
+
+        // at most a packet every 5ms, as reported back in the ACKs
+        let rate = sender.stats().sender.pkt_arr_rate;
+        assert!(rate > 0 && rate < 250, "{}", rate);
+
+        sender.close().await.unwrap();
+    };
+
+    futures::join!(recvr_fut, sendr_fut);
+}
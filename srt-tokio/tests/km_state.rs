@@ -0,0 +1,86 @@
+use std::io;
+
+use srt_tokio::{KmState, SrtSocketBuilder};
+
+#[tokio::test]
+async fn secured() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6022)
+        .crypto(16, "password123")
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6022")
+        .crypto(16, "password123")
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    for sock in &[&a, &b] {
+        assert_eq!(sock.info().send_km_state, KmState::Secured);
+        assert_eq!(sock.info().recv_km_state, KmState::Secured);
+    }
+}
+
+#[tokio::test]
+async fn bad_secret() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6023)
+        .crypto(16, "password123")
+        .enforced_encryption(false)
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6023")
+        .crypto(16, "password456")
+        .enforced_encryption(false)
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    for sock in &[&a, &b] {
+        assert_eq!(sock.info().send_km_state, KmState::BadSecret);
+        assert_eq!(sock.info().recv_km_state, KmState::BadSecret);
+    }
+}
+
+#[tokio::test]
+async fn no_secret() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6024)
+        .enforced_encryption(false)
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6024")
+        .crypto(16, "password123")
+        .enforced_encryption(false)
+        .connect();
+    let (a, b) = futures::try_join!(a, b).unwrap();
+
+    assert_eq!(a.info().send_km_state, KmState::Unsecured);
+    assert_eq!(a.info().recv_km_state, KmState::NoSecret);
+    assert_eq!(b.info().send_km_state, KmState::NoSecret);
+    assert_eq!(b.info().recv_km_state, KmState::Unsecured);
+}
+
+/// With the default enforced encryption, a wrong password is rejected
+#[tokio::test]
+async fn enforced_bad_secret() {
+    let _ = env_logger::try_init();
+
+    let listener = tokio::spawn(
+        SrtSocketBuilder::new_listen()
+            .local_port(6025)
+            .crypto(16, "password123")
+            .connect(),
+    );
+    let res = SrtSocketBuilder::new_connect("127.0.0.1:6025")
+        .crypto(16, "password456")
+        .connect()
+        .await;
+
+    assert_eq!(
+        res.map(|_| ()).unwrap_err().kind(),
+        io::ErrorKind::ConnectionRefused
+    );
+    drop(listener);
+}
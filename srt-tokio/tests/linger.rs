@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{Priority, RateLimit, SrtSocketBuilder};
+
+/// Closing gives up on what the peer hasn't acknowledged once the linger runs out, and says how much that was
+#[tokio::test]
+async fn linger() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6055)
+        .rate_limit(RateLimit::new(10_000))
+        .linger(Duration::from_millis(300))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6055").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let receiving = tokio::spawn(async move {
+        let mut received = 0;
+        while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+            assert_eq!(payload.len(), 956);
+            received += 1;
+        }
+        received
+    });
+
+    // about 5 seconds worth at the rate limit, never dropped for being late
+    let mut handle = sender.sender();
+    for _ in 0..50 {
+        handle
+            .send_with_priority((Instant::now(), Bytes::from(vec![0; 956])), Priority::High)
+            .await
+            .unwrap();
+    }
+    drop(handle);
+
+    let closing = Instant::now();
+    let abandoned = sender.close().await.unwrap();
+    assert!(closing.elapsed() < Duration::from_secs(1));
+    assert!(abandoned > 0);
+    assert_eq!(abandoned % 956, 0);
+    assert_eq!(sender.stats().sender.abandoned_bytes, abandoned);
+
+    // the packets still in flight may make it, but none of the rest
+    let received = receiving.await.unwrap();
+    assert!(received < 50);
+    assert!(
+        received + abandoned / 956 >= 50,
+        "{} {}",
+        received,
+        abandoned
+    );
+}
+
+/// Without a linger, closing waits for everything to be acknowledged
+#[tokio::test]
+async fn no_linger() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6056).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6056").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let receiving = tokio::spawn(async move {
+        let mut received = 0;
+        while recvr.try_next().await.unwrap().is_some() {
+            received += 1;
+        }
+        received
+    });
+
+    let mut handle = sender.sender();
+    for _ in 0..20 {
+        handle
+            .send((Instant::now(), Bytes::from(vec![0; 956])))
+            .await
+            .unwrap();
+    }
+    drop(handle);
+
+    assert_eq!(sender.close().await.unwrap(), 0);
+    assert_eq!(receiving.await.unwrap(), 20);
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{CloseReason, Loopback, SrtSocketBuilder};
+
+/// Two sockets connected in process carry data both ways, and close like over UDP
+#[tokio::test]
+async fn loopback() {
+    let _ = env_logger::try_init();
+
+    let (a, b) = Loopback::pair();
+    assert_eq!(a.peer_addr(), b.local_addr());
+    let listener = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(20))
+        .connect_with_sock(a);
+    let caller = SrtSocketBuilder::new_connect(b.peer_addr())
+        .latency(Duration::from_millis(20))
+        .connect_with_sock(b);
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+    assert_eq!(listener.settings().remote, "127.0.0.1:2".parse().unwrap());
+
+    let send = async {
+        for i in 0..1000 {
+            caller
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+        }
+        let (_, reply) = caller.try_next().await.unwrap().unwrap();
+        assert_eq!(reply, "done");
+        caller.close().await.unwrap();
+    };
+    let recv = async {
+        for i in 0..1000 {
+            let (_, payload) = listener.try_next().await.unwrap().unwrap();
+            assert_eq!(payload, i.to_string());
+        }
+        listener
+            .send((Instant::now(), Bytes::from("done")))
+            .await
+            .unwrap();
+        assert_eq!(listener.try_next().await.unwrap(), None);
+        assert_eq!(listener.close_reason(), Some(CloseReason::PeerShutdown));
+    };
+    futures::join!(send, recv);
+}
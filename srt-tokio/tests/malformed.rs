@@ -0,0 +1,55 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Garbage sent to a connected socket is counted and dropped, without disturbing the connection
+#[tokio::test]
+async fn malformed_packets() {
+    let _ = env_logger::try_init();
+
+    let recvr = SrtSocketBuilder::new_listen().local_port(6045).connect();
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6045").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let mut garbage = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let packets: &[&[u8]] = &[
+        // shorter than a header
+        &[0x80, 0x00],
+        // a NAK with half a loss list entry
+        &[0x80, 0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        // a handshake for UDT version 3
+        &[
+            0x80, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ],
+    ];
+    for packet in packets {
+        garbage.send_to(packet, "127.0.0.1:6045").await.unwrap();
+    }
+
+    let sendr_fut = async move {
+        delay_for(Duration::from_millis(100)).await;
+        for i in 0..10 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+        }
+        sender.close().await.unwrap();
+    };
+    let recvr_fut = async move {
+        let mut received = 0;
+        while recvr.try_next().await.unwrap().is_some() {
+            received += 1;
+        }
+        assert_eq!(received, 10);
+        assert_eq!(recvr.stats().receiver.malformed_packets, 3);
+    };
+    futures::join!(sendr_fut, recvr_fut);
+}
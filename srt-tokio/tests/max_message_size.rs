@@ -0,0 +1,41 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// A message larger than the receiver's max message size should be dropped, but the connection should keep working
+#[tokio::test]
+async fn max_message_size() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6008").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6008)
+        .max_message_size(10_000)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let sendr_fut = async move {
+        for msg in &[&[b'a'; 5_000][..], &[b'b'; 20_000][..], &[b'c'; 10_000][..]] {
+            sender
+                .send((Instant::now(), Bytes::copy_from_slice(msg)))
+                .await
+                .unwrap();
+        }
+        sender.close().await.unwrap();
+    };
+
+    let recvr_fut = async move {
+        let (_, a) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(a, &[b'a'; 5_000][..]);
+        let (_, c) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(c, &[b'c'; 10_000][..]);
+        assert_eq!(recvr.stats().receiver.oversized_messages, 1);
+
+        assert_eq!(recvr.try_next().await.unwrap(), None);
+    };
+
+    futures::join!(sendr_fut, recvr_fut);
+}
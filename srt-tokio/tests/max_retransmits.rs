@@ -0,0 +1,65 @@
+use std::str;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{ConnInitMethod, SrtSocketBuilder};
+
+mod lossy_conn;
+use crate::lossy_conn::LossyConn;
+
+/// Messages that can't be retransmitted are dropped at the first loss, however much latency is left
+#[tokio::test]
+async fn max_retransmits() {
+    let _ = env_logger::try_init();
+
+    const MESSAGES: u32 = 200;
+
+    // 10% packet loss, 10ms delay
+    let (send, recv) = LossyConn::channel(
+        0.1,
+        Duration::from_millis(10),
+        Duration::from_millis(0),
+        "127.0.0.1:6130",
+        "127.0.0.1:0",
+    );
+
+    let sender = SrtSocketBuilder::new(ConnInitMethod::Listen)
+        .local_port(6130)
+        .latency(Duration::from_secs(1))
+        .connect_with_sock(send);
+    let recvr = SrtSocketBuilder::new(ConnInitMethod::Connect("127.0.0.1:6130".parse().unwrap()))
+        .latency(Duration::from_secs(1))
+        .connect_with_sock(recv);
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let sending = tokio::spawn(async move {
+        let mut handle = sender.sender();
+        for i in 0..MESSAGES {
+            handle
+                .send_with_max_retransmits((Instant::now(), Bytes::from(i.to_string())), 0)
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(5)).await;
+        }
+        drop(handle);
+        delay_for(Duration::from_secs(2)).await;
+        let stats = sender.stats().sender;
+        sender.close().await.unwrap();
+        stats
+    });
+
+    let mut received = vec![];
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        received.push(str::from_utf8(&payload).unwrap().parse::<u32>().unwrap());
+    }
+    let stats = sending.await.unwrap();
+
+    // nothing was recovered, the lost messages were given up on instead
+    assert!(stats.dropped_messages > 0);
+    assert!(received.len() < MESSAGES as usize);
+    assert!(received.len() + stats.dropped_messages as usize >= MESSAGES as usize);
+    assert!(received.windows(2).all(|w| w[0] < w[1]), "{:?}", received);
+}
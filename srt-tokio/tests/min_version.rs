@@ -0,0 +1,62 @@
+use std::io;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use srt_tokio::{SrtSocketBuilder, SrtVersion};
+
+/// A listener should reject callers older than its minimum version, failing the connection on the caller's side
+#[tokio::test]
+async fn listener_rejects_old_caller() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6019)
+        .min_peer_version(SrtVersion::new(99, 0, 0))
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6019").connect();
+
+    let listener = tokio::spawn(listener);
+    let err = match timeout(Duration::from_secs(2), caller).await.unwrap() {
+        Ok(_) => panic!("Connection should have been rejected"),
+        Err(e) => e,
+    };
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+    // the listener is still waiting for an acceptable caller
+    let listener = timeout(Duration::from_millis(200), listener).await;
+    assert!(listener.is_err());
+}
+
+/// A caller should refuse a listener older than its minimum version
+#[tokio::test]
+async fn caller_refuses_old_listener() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen().local_port(6020).connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6020")
+        .min_peer_version(SrtVersion::new(99, 0, 0))
+        .connect();
+
+    let _listener = tokio::spawn(listener);
+    let err = match timeout(Duration::from_secs(2), caller).await.unwrap() {
+        Ok(_) => panic!("Connection should have been refused"),
+        Err(e) => e,
+    };
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+}
+
+#[tokio::test]
+async fn current_version_accepted() {
+    let _ = env_logger::try_init();
+
+    let listener = SrtSocketBuilder::new_listen()
+        .local_port(6021)
+        .min_peer_version(SrtVersion::CURRENT)
+        .connect();
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6021")
+        .min_peer_version(SrtVersion::CURRENT)
+        .connect();
+
+    futures::try_join!(listener, caller).unwrap();
+}
@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{MulticastReceiver, MulticastSender, SrtSocketBuilder};
+
+/// Data sent through the group arrives in order, with the handshake and the rest over unicast
+// needs a route for multicast, which containers don't always have
+#[tokio::test]
+#[ignore]
+async fn multicast() {
+    let _ = env_logger::try_init();
+
+    let group = "239.255.60.63:6064".parse().unwrap();
+    let sock = MulticastSender::bind("0.0.0.0:6063".parse().unwrap(), group, 1)
+        .await
+        .unwrap();
+    let sender = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(sock);
+    let sock = MulticastReceiver::bind("0.0.0.0:0".parse().unwrap(), group)
+        .await
+        .unwrap();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6063")
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(sock);
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        for i in 0..100 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(2)).await;
+        }
+        sender.close().await.unwrap();
+    });
+
+    for i in 0..100 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
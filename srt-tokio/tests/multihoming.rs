@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::SrtSocketBuilder;
+
+/// Connections to every address a multiplexed listener is bound to should come out of one stream
+#[tokio::test]
+async fn multihoming() {
+    let _ = env_logger::try_init();
+
+    let mut server = SrtSocketBuilder::new_listen()
+        .local_addr([127, 0, 0, 1].into())
+        .local_port(6031)
+        .also_bind("127.0.0.1:6032".parse().unwrap())
+        .build_multiplexed()
+        .await
+        .unwrap()
+        .boxed();
+
+    let accept = async move {
+        let mut accepted = HashSet::new();
+        while accepted.len() < 2 {
+            let (conn, chan) = server.try_next().await.unwrap().unwrap();
+            accepted.insert(conn.settings.remote.port());
+            let mut sender = create_bidrectional_srt(chan, conn);
+            tokio::spawn(async move {
+                sender
+                    .send((Instant::now(), Bytes::from("hello")))
+                    .await
+                    .unwrap();
+                sender.close().await.unwrap();
+            });
+        }
+        // the connections only make progress while the server is polled
+        tokio::spawn(async move { while server.next().await.is_some() {} });
+        accepted
+    };
+
+    let connect = future::try_join_all((6031..=6032).map(|port| async move {
+        let mut recvr = SrtSocketBuilder::new_connect(("127.0.0.1", port))
+            .connect()
+            .await?;
+        assert_eq!(recvr.settings().remote.port(), port);
+        let (_, payload) = recvr.try_next().await?.unwrap();
+        assert_eq!(payload, "hello");
+        assert!(recvr.try_next().await?.is_none());
+        Ok::<_, std::io::Error>(recvr.settings().local_sockid)
+    }));
+
+    let (accepted, connected) = future::join(accept, connect).await;
+    assert_eq!(accepted.len(), 2);
+    assert_eq!(connected.unwrap().len(), 2);
+}
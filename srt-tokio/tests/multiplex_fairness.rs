@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::prelude::*;
+use futures::stream;
+use log::info;
+use tokio::time::delay_for;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::SrtSocketBuilder;
+
+const BULK: usize = 1_000;
+const SMALL: usize = 20;
+
+/// How many messages are sent at once, a burst the size of the whole bulk would overflow the receiving socket's buffer
+const BULK_BURST: usize = 50;
+
+/// A connection sending as fast as it can shouldn't keep one sharing its socket from sending
+#[tokio::test]
+async fn multiplex_fairness() {
+    let _ = env_logger::try_init();
+
+    let (stats, server) = SrtSocketBuilder::new_listen()
+        .local_port(6029)
+        .build_multiplexed_with_stats()
+        .await
+        .unwrap();
+
+    let (close_send, close_recv) = oneshot::channel::<()>();
+    let close_recv = close_recv.shared();
+    let mut sockids = vec![];
+    tokio::spawn({
+        let mut server = server.boxed();
+        async move {
+            for count in &[BULK, SMALL] {
+                let (conn, chan) = server.try_next().await.unwrap().unwrap();
+                let count = *count;
+                let close_recv = close_recv.clone();
+                tokio::spawn(async move {
+                    let mut sender = create_bidrectional_srt(chan, conn);
+                    let burst = BULK_BURST.min(count);
+                    for _ in 0..count / burst {
+                        let mut messages = stream::iter(
+                            (0..burst)
+                                .map(|_| Ok((Instant::now(), Bytes::from(count.to_string())))),
+                        );
+                        sender.send_all(&mut messages).await.unwrap();
+                        delay_for(Duration::from_millis(5)).await;
+                    }
+                    let _ = close_recv.await;
+                    sender.close().await.unwrap();
+                    info!("Sender of {} finished", count);
+                });
+            }
+            // the connections only make progress while the server is polled
+            while server.next().await.is_some() {}
+        }
+    });
+
+    let mut recvrs = vec![];
+    for _ in 0..2 {
+        let mut recvr = SrtSocketBuilder::new_connect("127.0.0.1:6029")
+            .connect()
+            .await
+            .unwrap();
+        sockids.push(recvr.settings().remote_sockid);
+        recvrs.push(tokio::spawn(async move {
+            let (_, first) = recvr.try_next().await.unwrap().unwrap();
+            let count: usize = std::str::from_utf8(&first).unwrap().parse().unwrap();
+            for _ in 1..count {
+                recvr.try_next().await.unwrap().unwrap();
+            }
+            (count, recvr)
+        }));
+    }
+    let mut received = vec![];
+    for recvr in future::join_all(recvrs).await {
+        received.push(recvr.unwrap());
+    }
+    let mut counts: Vec<_> = received.iter().map(|(count, _)| *count).collect();
+    counts.sort_unstable();
+    assert_eq!(counts, [SMALL, BULK]);
+
+    let all = stats.all_egress();
+    assert_eq!(all.len(), 2);
+    for sockid in &sockids {
+        let egress = stats.egress(*sockid).unwrap();
+        info!("Egress of {:?}: {:?}", sockid, egress);
+        assert!(egress.sent_packets >= SMALL as u64, "{:?}", egress);
+        assert!(egress.max_queued <= 64, "{:?}", egress);
+    }
+
+    close_send.send(()).unwrap();
+    for (_, mut recvr) in received {
+        assert!(recvr.try_next().await.unwrap().is_none());
+    }
+
+    // closed connections are dropped from the stats
+    for _ in 0..100 {
+        if stats.all_egress().is_empty() {
+            return;
+        }
+        delay_for(Duration::from_millis(10)).await;
+    }
+    panic!("Closed connections still in {:?}", stats.all_egress());
+}
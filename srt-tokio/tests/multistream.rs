@@ -0,0 +1,61 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{MultiStream, SrtSocketBuilder};
+
+/// Streams sent over one connection each arrive in their own order, including one opened after its messages came in
+#[tokio::test]
+async fn multistream() {
+    let _ = env_logger::try_init();
+
+    let recvr = SrtSocketBuilder::new_listen().local_port(6046).connect();
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6046").connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+    let mut sender = MultiStream::new(sender);
+    let recvr = MultiStream::new(recvr);
+
+    let sendr_fut = async move {
+        let mut streams = vec![];
+        for id in 1..=3 {
+            streams.push(sender.open_stream(id).unwrap());
+        }
+        assert!(sender.open_stream(1).is_err());
+
+        for i in 0..100 {
+            for stream in &mut streams {
+                let data = format!("{}:{}", stream.id(), i);
+                stream
+                    .send((Instant::now(), Bytes::from(data)))
+                    .await
+                    .unwrap();
+            }
+        }
+        drop(streams);
+        sender.close().await.unwrap();
+    };
+    let recvr_fut = async move {
+        let read = |mut stream: srt_tokio::SubStream| async move {
+            let mut expected = 0;
+            while let Some((_, data)) = stream.try_next().await.unwrap() {
+                assert_eq!(
+                    &data[..],
+                    format!("{}:{}", stream.id(), expected).as_bytes()
+                );
+                expected += 1;
+            }
+            assert_eq!(expected, 100);
+        };
+        let first = read(recvr.open_stream(1).unwrap());
+        let second = read(recvr.open_stream(2).unwrap());
+        let late = async {
+            delay_for(Duration::from_millis(500)).await;
+            read(recvr.open_stream(3).unwrap()).await
+        };
+        futures::join!(first, second, late);
+        assert_eq!(recvr.lost_messages(), 0);
+    };
+    futures::join!(sendr_fut, recvr_fut);
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "netem")]
+
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::interval;
+
+use srt_tokio::netem::{GilbertElliott, ImpairedTransport, Impairment};
+use srt_tokio::SrtSocketBuilder;
+
+/// Everything gets through bursty loss in both directions, in order, and no earlier than the delay
+#[tokio::test]
+async fn bursty_loss() {
+    let _ = env_logger::try_init();
+
+    const COUNT: usize = 300;
+
+    let impaired = |port, seed| async move {
+        let socket = UdpSocket::bind(("127.0.0.1", port)).await?;
+        Ok::<_, std::io::Error>(ImpairedTransport::new(
+            socket,
+            Impairment {
+                loss: GilbertElliott::bursty(0.05, 3.0),
+                delay: Duration::from_millis(20),
+                jitter: Duration::from_millis(2),
+                seed: Some(seed),
+            },
+        ))
+    };
+
+    let sender = async {
+        SrtSocketBuilder::new_listen()
+            .latency(Duration::from_secs(1))
+            .connect_with_transport(impaired(6107, 1).await?)
+            .await
+    };
+    let receiver = async {
+        SrtSocketBuilder::new_connect("127.0.0.1:6107")
+            .latency(Duration::from_secs(1))
+            .connect_with_transport(impaired(6108, 2).await?)
+            .await
+    };
+    let (mut sender, mut receiver) = futures::try_join!(sender, receiver).unwrap();
+
+    let send = async move {
+        let mut messages = stream::iter(0..COUNT)
+            .zip(interval(Duration::from_millis(5)))
+            .map(|(i, _)| Ok((Instant::now(), Bytes::from(i.to_string()))));
+        sender.send_all(&mut messages).await.unwrap();
+        sender.close().await.unwrap();
+    };
+    let receive = async move {
+        let mut next = 0;
+        while let Some((sent, message)) = receiver.try_next().await.unwrap() {
+            assert_eq!(message, next.to_string());
+            assert!(sent.elapsed() >= Duration::from_millis(20));
+            next += 1;
+        }
+        assert_eq!(next, COUNT);
+    };
+    futures::join!(send, receive);
+}
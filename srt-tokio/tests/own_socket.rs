@@ -0,0 +1,60 @@
+use std::net::UdpSocket;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::SrtSocketBuilder;
+
+/// Both sides connect over UDP sockets they bound themselves
+#[tokio::test]
+async fn own_socket() {
+    let _ = env_logger::try_init();
+
+    let listen_sock = UdpSocket::bind("127.0.0.1:6086").unwrap();
+    let call_sock = UdpSocket::bind("127.0.0.1:6087").unwrap();
+
+    let listener = SrtSocketBuilder::new_listen().connect_with_udp(listen_sock);
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6086").connect_with_udp(call_sock);
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+    assert_eq!(
+        listener.settings().remote,
+        "127.0.0.1:6087".parse().unwrap()
+    );
+
+    caller
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    caller.close().await.unwrap();
+    assert_eq!(listener.try_next().await.unwrap().unwrap().1, "hello");
+}
+
+/// A multiplexer serves connections over a UDP socket it's given
+#[tokio::test]
+async fn own_socket_multiplexed() {
+    let _ = env_logger::try_init();
+
+    let sock = UdpSocket::bind("127.0.0.1:6088").unwrap();
+    let mut server = SrtSocketBuilder::new_listen()
+        .build_multiplexed_with_udp(sock)
+        .unwrap()
+        .boxed();
+
+    tokio::spawn(async move {
+        let (conn, chan) = server.try_next().await.unwrap().unwrap();
+        let mut sender = create_bidrectional_srt(chan, conn);
+        sender
+            .send((Instant::now(), Bytes::from("hello")))
+            .await
+            .unwrap();
+        sender.close().await.unwrap();
+    });
+
+    let mut recvr = SrtSocketBuilder::new_connect("127.0.0.1:6088")
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(recvr.try_next().await.unwrap().unwrap().1, "hello");
+}
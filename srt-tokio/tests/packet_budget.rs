@@ -0,0 +1,50 @@
+use srt_tokio::SrtSocketBuilder;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use std::time::Instant;
+
+/// Even with a budget of a single packet, where the connection yields after every packet, all data should get through
+#[tokio::test]
+async fn packet_budget() {
+    let _ = env_logger::try_init();
+
+    const PACKETS: u32 = 100;
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6007)
+        .packet_budget(1)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6007")
+        .packet_budget(1)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let sendr_fut = async move {
+        for i in 0..PACKETS {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+        }
+        sender.close().await.unwrap();
+    };
+
+    let recvr_fut = async move {
+        let mut next = 0;
+        while let Some((_, packet)) = recvr.try_next().await.unwrap() {
+            assert_eq!(packet, next.to_string());
+            next += 1;
+        }
+        assert_eq!(next, PACKETS);
+    };
+
+    futures::join!(sendr_fut, recvr_fut);
+}
+
+#[test]
+#[should_panic]
+fn zero_packet_budget() {
+    let _ = SrtSocketBuilder::new_listen().packet_budget(0);
+}
@@ -0,0 +1,146 @@
+use std::convert::TryInto;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_protocol::packet::ControlTypes;
+use srt_protocol::{ControlPacket, Packet};
+use srt_tokio::{pcapng::PcapngWriter, PacketDirection, PacketTap, SrtSocketBuilder};
+
+const ITERS: usize = 10;
+
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn u32_le(buf: &[u8]) -> u32 {
+    u32::from_le_bytes(buf[..4].try_into().unwrap())
+}
+
+fn u16_be(buf: &[u8]) -> u16 {
+    u16::from_be_bytes(buf[..2].try_into().unwrap())
+}
+
+/// Every packet sent and received after connecting should go through the tap
+#[tokio::test]
+async fn packet_tap() {
+    let _ = env_logger::try_init();
+
+    let tapped = Arc::new(Mutex::new(Vec::new()));
+    let tap = {
+        let tapped = tapped.clone();
+        PacketTap::new(move |_, direction, packet, _| {
+            tapped.lock().unwrap().push((direction, packet.clone()));
+        })
+    };
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6026")
+        .packet_tap(tap)
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen().local_port(6026).connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    for i in 0..ITERS {
+        sender
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+    }
+    sender.close().await.unwrap();
+    for i in 0..ITERS {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+
+    let tapped = tapped.lock().unwrap();
+    let sent_data = tapped
+        .iter()
+        .filter(|(dir, pack)| *dir == PacketDirection::Egress && matches!(pack, Packet::Data(_)))
+        .count();
+    assert!(sent_data >= ITERS, "Only {} data packets tapped", sent_data);
+    assert!(tapped
+        .iter()
+        .any(|(dir, pack)| *dir == PacketDirection::Ingress
+            && matches!(
+                pack,
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Ack { .. },
+                    ..
+                })
+            )));
+}
+
+/// The capture should be valid pcapng, with the packets in UDP datagrams
+#[tokio::test]
+async fn pcapng_capture() {
+    let _ = env_logger::try_init();
+
+    let buf = SharedBuf::default();
+    let local = "127.0.0.1:6027".parse().unwrap();
+    let capture = PcapngWriter::new(buf.clone(), local).unwrap();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6027").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6027)
+        .packet_tap(capture.into_tap())
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    for i in 0..ITERS {
+        sender
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+    }
+    sender.close().await.unwrap();
+    for _ in 0..ITERS {
+        recvr.try_next().await.unwrap().unwrap();
+    }
+
+    let buf = buf.0.lock().unwrap();
+    let mut blocks = Vec::new();
+    let mut rest = &buf[..];
+    while !rest.is_empty() {
+        let length = u32_le(&rest[4..]) as usize;
+        assert_eq!(length % 4, 0);
+        assert_eq!(u32_le(&rest[length - 4..]) as usize, length);
+        blocks.push((u32_le(rest), &rest[8..length - 4]));
+        rest = &rest[length..];
+    }
+
+    assert_eq!(blocks[0].0, 0x0A0D_0D0A);
+    assert_eq!(u32_le(blocks[0].1), 0x1A2B_3C4D);
+    assert_eq!(blocks[1].0, 1);
+
+    let mut data_packets = 0;
+    for (block_type, body) in &blocks[2..] {
+        assert_eq!(*block_type, 6);
+        let captured = u32_le(&body[12..]) as usize;
+        let ip = &body[20..20 + captured];
+        assert_eq!(ip[0], 0x45);
+        assert_eq!(ip[9], 17);
+        let udp = &ip[20..];
+        assert_eq!(u16_be(&udp[4..]) as usize, udp.len());
+
+        // data only flows to the listener
+        let packet = Packet::parse(&mut &udp[8..]).unwrap();
+        if let Packet::Data(_) = packet {
+            assert_eq!(u16_be(&udp[2..]), 6027);
+            data_packets += 1;
+        }
+    }
+    assert!(data_packets >= ITERS);
+}
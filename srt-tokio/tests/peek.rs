@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Peeking leaves the message to be received
+#[tokio::test]
+async fn try_peek_msg() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6128).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6128").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+    assert_eq!(recvr.try_peek_msg(), None);
+
+    // an MPEG-TS sync byte
+    sender
+        .send((Instant::now(), Bytes::from_static(b"\x47first")))
+        .await
+        .unwrap();
+    sender
+        .send((Instant::now(), Bytes::from_static(b"second")))
+        .await
+        .unwrap();
+
+    let peeked = loop {
+        match recvr.try_peek_msg() {
+            Some((_, payload)) => break payload,
+            None => delay_for(Duration::from_millis(10)).await,
+        }
+    };
+    assert_eq!(peeked[0], 0x47);
+    assert_eq!(recvr.try_peek_msg().unwrap().1, peeked);
+
+    let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "\x47first");
+    let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "second");
+}
@@ -0,0 +1,54 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{Priority, RateLimit, SrtSocketBuilder};
+
+/// When a live connection can't send everything, low priority messages are dropped and high priority ones make it
+#[tokio::test]
+async fn low_priority_dropped_first() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6040)
+        .latency(Duration::from_millis(300))
+        .rate_limit(RateLimit::new(20_000))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6040")
+        .latency(Duration::from_millis(300))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // 10kB/s of high priority messages, 30kB/s of low priority ones
+    tokio::spawn(async move {
+        let mut handle = sender.sender();
+        for i in 0..100u8 {
+            let (priority, tag) = if i % 4 == 0 {
+                (Priority::High, b'H')
+            } else {
+                (Priority::Low, b'L')
+            };
+            handle
+                .send_with_priority((Instant::now(), Bytes::from(vec![tag; 956])), priority)
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(25)).await;
+        }
+        drop(handle);
+        delay_for(Duration::from_secs(1)).await;
+        assert!(sender.stats().sender.dropped_packets > 0);
+        sender.close().await.unwrap();
+    });
+
+    let (mut high, mut low) = (0, 0);
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        match payload[0] {
+            b'H' => high += 1,
+            _ => low += 1,
+        }
+    }
+    assert_eq!(high, 25);
+    assert!(low < 75, "{}", low);
+}
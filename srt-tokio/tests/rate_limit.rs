@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{RateLimit, SrtSocketBuilder, TransmissionType};
+
+/// 100 packets of 956 + 44 bytes take half a second at 200kB/s, even on loopback
+#[tokio::test]
+async fn rate_limit() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6039)
+        .transmission_type(TransmissionType::File)
+        .rate_limit(RateLimit::new(200_000))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6039")
+        .transmission_type(TransmissionType::File)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let start = Instant::now();
+    tokio::spawn(async move {
+        for _ in 0..100 {
+            sender
+                .send((Instant::now(), Bytes::from(vec![0; 956])))
+                .await
+                .unwrap();
+        }
+        sender.close().await.unwrap();
+    });
+
+    let mut received = 0;
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        assert_eq!(payload.len(), 956);
+        received += 1;
+    }
+    let elapsed = start.elapsed();
+    assert_eq!(received, 100);
+    // less the 2 packet burst
+    assert!(elapsed >= Duration::from_millis(480), "{:?}", elapsed);
+}
@@ -0,0 +1,67 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Messages released at the same time are received together
+#[tokio::test]
+async fn recv_many() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6126).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6126").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // all sent at once, so they're released at once, one latency later
+    let now = Instant::now();
+    for i in 0..50_u32 {
+        sender
+            .feed((now, Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+    }
+    sender.close().await.unwrap();
+
+    let mut received = Vec::new();
+    let mut batches = 0;
+    while let Some(batch) = recvr.recv_many().await.unwrap() {
+        assert!(!batch.is_empty());
+        batches += 1;
+        received.extend(batch.into_iter().map(|(_, payload)| payload));
+    }
+    let expected: Vec<_> = (0..50_u32).map(|i| Bytes::from(i.to_string())).collect();
+    assert_eq!(received, expected);
+    assert!(batches < 10, "{} batches", batches);
+}
+
+/// Receiving one by one still gets every message of a batch, in order, after some were taken together
+#[tokio::test]
+async fn mixed() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6127).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6127").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let now = Instant::now();
+    for i in 0..10_u32 {
+        sender
+            .feed((now, Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+    }
+    sender.close().await.unwrap();
+
+    let mut received = Vec::new();
+    while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+        received.push(payload);
+        if received.len() == 3 {
+            let batch = recvr.recv_many().await.unwrap().unwrap();
+            received.extend(batch.into_iter().map(|(_, payload)| payload));
+        }
+    }
+    let expected: Vec<_> = (0..10_u32).map(|i| Bytes::from(i.to_string())).collect();
+    assert_eq!(received, expected);
+}
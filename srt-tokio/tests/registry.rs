@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::SrtSocketBuilder;
+
+/// The multiplexer lists the connections that are open, with who they're with
+#[tokio::test]
+async fn registry() {
+    let _ = env_logger::try_init();
+
+    let (stats, server) = SrtSocketBuilder::new_listen()
+        .local_port(6067)
+        .build_multiplexed_with_stats()
+        .await
+        .unwrap();
+
+    tokio::spawn(async move {
+        let mut server = server.boxed();
+        while let Some((conn, chan)) = server.try_next().await.unwrap() {
+            tokio::spawn(async move {
+                let mut sock = create_bidrectional_srt(chan, conn);
+                // until the peer closes
+                while sock.try_next().await.unwrap().is_some() {}
+                sock.close().await.unwrap();
+            });
+        }
+    });
+
+    assert!(stats.connections().is_empty());
+
+    let mut a = SrtSocketBuilder::new_connect("127.0.0.1:6067")
+        .local_port(6068)
+        .connect()
+        .await
+        .unwrap();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6067")
+        .local_port(6069)
+        .connect()
+        .await
+        .unwrap();
+    // the listener connects once the callers hear back
+    delay_for(Duration::from_millis(100)).await;
+
+    let conns = stats.connections();
+    assert_eq!(conns.len(), 2);
+    for sock in &[&a, &b] {
+        let conn = conns
+            .iter()
+            .find(|conn| conn.local_sockid == sock.settings().remote_sockid)
+            .unwrap();
+        assert_eq!(conn.remote_sockid, sock.settings().local_sockid);
+        assert_eq!(conn.stream_id, None);
+        assert_eq!(stats.connection(conn.local_sockid).unwrap().peer, conn.peer);
+    }
+    let mut ports: Vec<_> = conns.iter().map(|conn| conn.peer.port()).collect();
+    ports.sort_unstable();
+    assert_eq!(ports, vec![6068, 6069]);
+
+    a.close().await.unwrap();
+    delay_for(Duration::from_millis(500)).await;
+    let conns = stats.connections();
+    assert_eq!(conns.len(), 1);
+    assert_eq!(conns[0].local_sockid, b.settings().remote_sockid);
+}
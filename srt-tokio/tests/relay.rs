@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use futures::stream;
+use tokio::time::interval;
+
+use srt_tokio::{relay, RelayTiming, SrtSocketBuilder};
+
+/// Messages going through a relay should keep their origin, offset by the first hop's latency
+#[tokio::test]
+async fn relay_timing() {
+    let _ = env_logger::try_init();
+
+    const ITERS: usize = 100;
+    let first_hop = Duration::from_millis(500);
+    let second_hop = Duration::from_millis(300);
+
+    let source = SrtSocketBuilder::new_connect("127.0.0.1:6014")
+        .latency(first_hop)
+        .connect();
+    let relay_in = SrtSocketBuilder::new_listen()
+        .local_port(6014)
+        .latency(first_hop)
+        .connect();
+    let (mut source, mut relay_in) = futures::try_join!(source, relay_in).unwrap();
+
+    let relay_out = SrtSocketBuilder::new_connect("127.0.0.1:6015")
+        .latency(second_hop)
+        .connect();
+    let sink = SrtSocketBuilder::new_listen()
+        .local_port(6015)
+        .latency(second_hop)
+        .connect();
+    let (mut relay_out, mut sink) = futures::try_join!(relay_out, sink).unwrap();
+
+    let sent = Arc::new(Mutex::new(Vec::new()));
+
+    let source_fut = {
+        let sent = sent.clone();
+        async move {
+            let mut counting_stream = stream::iter(0..ITERS)
+                .zip(interval(Duration::from_millis(5)))
+                .map(move |(i, _)| {
+                    let now = Instant::now();
+                    sent.lock().unwrap().push(now);
+                    Ok((now, Bytes::from(i.to_string())))
+                });
+            source.send_all(&mut counting_stream).await.unwrap();
+            source.close().await.unwrap();
+        }
+    };
+
+    let timing = RelayTiming::new(relay_in.settings(), relay_out.settings());
+    let relay_fut = async move {
+        assert_eq!(
+            relay(&mut relay_in, &mut relay_out, timing).await.unwrap(),
+            ITERS as u64
+        );
+    };
+
+    let sink_fut = async move {
+        for i in 0..ITERS {
+            let (ts, payload) = sink.try_next().await.unwrap().unwrap();
+            let released = Instant::now();
+            assert_eq!(payload, i.to_string());
+
+            let origin = sent.lock().unwrap()[i];
+            let rebased = ts - origin;
+            assert!(
+                rebased > first_hop - Duration::from_millis(20)
+                    && rebased < first_hop + Duration::from_millis(20),
+                "Relayed timestamp off by {:?}, expected {:?}",
+                rebased,
+                first_hop
+            );
+
+            let total = released - origin;
+            assert!(
+                total > first_hop + second_hop - Duration::from_millis(50)
+                    && total < first_hop + second_hop + Duration::from_millis(200),
+                "End to end latency was {:?}, expected {:?}",
+                total,
+                first_hop + second_hop
+            );
+        }
+        assert_eq!(sink.try_next().await.unwrap(), None);
+    };
+
+    futures::join!(source_fut, relay_fut, sink_fut);
+}
@@ -0,0 +1,66 @@
+use std::fs::{self, File};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_protocol::replay::{replay_receiver, trace_settings};
+use srt_tokio::pcapng::{read_capture, receiver_trace, PcapngWriter};
+use srt_tokio::{PacketDirection, SrtSocketBuilder};
+
+const ITERS: usize = 100;
+
+/// A captured connection should be replayed into a receiver that releases the same messages
+#[tokio::test]
+async fn replay_capture() {
+    let _ = env_logger::try_init();
+
+    let path = std::env::temp_dir().join("srt-rs-replay-capture.pcapng");
+    let capture = PcapngWriter::new(
+        File::create(&path).unwrap(),
+        "127.0.0.1:6028".parse().unwrap(),
+    )
+    .unwrap();
+
+    let latency = Duration::from_millis(50);
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6028")
+        .latency(latency)
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6028)
+        .latency(latency)
+        .packet_tap(capture.into_tap())
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let send = async move {
+        for i in 0..ITERS {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(1)).await;
+        }
+        sender.close().await.unwrap();
+    };
+    let recv = async move { while recvr.try_next().await.unwrap().is_some() {} };
+    futures::join!(send, recv);
+
+    let captured = read_capture(File::open(&path).unwrap()).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(captured
+        .iter()
+        .any(|cp| cp.direction == Some(PacketDirection::Egress)));
+
+    let trace = receiver_trace(&captured);
+    assert!(trace.len() > ITERS);
+    let settings = trace_settings(&trace, latency).unwrap();
+
+    let replay = replay_receiver(settings, trace);
+    let released: Vec<_> = replay.released().cloned().collect();
+    assert_eq!(
+        released,
+        (0..ITERS).map(|i| i.to_string()).collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{self, BoxFuture, FutureExt};
+
+use srt_tokio::{Resolver, SrtSocketBuilder};
+
+/// Resolves every host to whatever address is set, counting the lookups
+#[derive(Debug, Clone, Default)]
+struct FakeResolver(Arc<Mutex<(Vec<SocketAddr>, usize)>>);
+
+impl Resolver for FakeResolver {
+    fn resolve(&self, host: &str, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        assert_eq!(host, "ingest.example");
+        assert_eq!(port, 9000);
+        let mut state = self.0.lock().unwrap();
+        state.1 += 1;
+        future::ready(Ok(state.0.clone())).boxed()
+    }
+}
+
+/// Reconnecting should resolve the host again, and follow it to its new address
+#[tokio::test]
+async fn reresolve_on_reconnect() {
+    let _ = env_logger::try_init();
+
+    let resolver = FakeResolver::default();
+    let builder =
+        SrtSocketBuilder::new_connect_host("ingest.example", 9000).resolver(resolver.clone());
+
+    let first = "127.0.0.1:6035".parse().unwrap();
+    resolver.0.lock().unwrap().0 = vec![first];
+    let (sender, _recvr) = futures::try_join!(
+        builder.clone().connect(),
+        SrtSocketBuilder::new_listen().local_port(6035).connect()
+    )
+    .unwrap();
+    assert_eq!(sender.settings().remote, first);
+    drop(sender);
+
+    let second = "127.0.0.1:6036".parse().unwrap();
+    resolver.0.lock().unwrap().0 = vec![second];
+    let (sender, _recvr) = futures::try_join!(
+        builder.clone().connect(),
+        SrtSocketBuilder::new_listen().local_port(6036).connect()
+    )
+    .unwrap();
+    assert_eq!(sender.settings().remote, second);
+    assert_eq!(resolver.0.lock().unwrap().1, 2);
+
+    // nothing to connect to
+    resolver.0.lock().unwrap().0 = vec![];
+    match builder.connect().await {
+        Err(e) => assert_eq!(e.kind(), io::ErrorKind::NotFound),
+        Ok(_) => panic!("Connected without an address"),
+    }
+}
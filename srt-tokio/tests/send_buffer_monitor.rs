@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+use tokio::time::timeout;
+
+use srt_tokio::{SendBufferMonitor, SrtSocketBuilder};
+
+/// The monitor should be called when the buffered time span reaches the threshold, then again once it's acknowledged
+#[tokio::test]
+async fn send_buffer_monitor() {
+    let _ = env_logger::try_init();
+
+    let (levels_send, mut levels) = mpsc::unbounded();
+    let monitor = SendBufferMonitor::new(vec![Duration::from_millis(100)], move |level| {
+        let _ = levels_send.unbounded_send(level);
+    });
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6030")
+        .send_buffer_monitor(monitor)
+        .connect();
+    let recvr = SrtSocketBuilder::new_listen().local_port(6030).connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // two messages half a second apart in the source, buffered at once
+    let now = Instant::now();
+    let mut messages = stream::iter(vec![
+        Ok((now, Bytes::from("1"))),
+        Ok((now + Duration::from_millis(500), Bytes::from("2"))),
+    ]);
+    sender.send_all(&mut messages).await.unwrap();
+
+    let high = timeout(Duration::from_secs(1), levels.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(high.level, 1);
+    assert_eq!(high.timespan, Duration::from_millis(500));
+    assert_eq!(high.packets, 2);
+
+    let low = timeout(Duration::from_secs(1), levels.next())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(low.level, 0);
+
+    sender.close().await.unwrap();
+    assert_eq!(recvr.try_next().await.unwrap().unwrap().1, "1");
+    assert_eq!(recvr.try_next().await.unwrap().unwrap().1, "2");
+}
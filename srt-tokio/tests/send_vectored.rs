@@ -0,0 +1,42 @@
+use std::io::IoSlice;
+use std::time::Instant;
+
+use futures::prelude::*;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// A message sent in slices arrives as one, across several packets
+#[tokio::test]
+async fn send_vectored() {
+    let _ = env_logger::try_init();
+
+    let header = [0x47; 4];
+    let payload: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+
+    let sender = async {
+        let mut sender = SrtSocketBuilder::new_connect("127.0.0.1:6051")
+            .connect()
+            .await
+            .unwrap();
+        sender
+            .send_vectored(
+                Instant::now(),
+                &[IoSlice::new(&header), IoSlice::new(&payload)],
+            )
+            .await
+            .unwrap();
+        sender.close().await.unwrap();
+    };
+    let recvr = async {
+        let mut recvr = SrtSocketBuilder::new_listen()
+            .local_port(6051)
+            .connect()
+            .await
+            .unwrap();
+        let (_, message) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(&message[..4], &header[..]);
+        assert_eq!(&message[4..], &payload[..]);
+        assert!(recvr.try_next().await.unwrap().is_none());
+    };
+    futures::join!(sender, recvr);
+}
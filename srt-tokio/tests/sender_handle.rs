@@ -0,0 +1,59 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::spawn;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Several tasks sending on clones of one handle should all get their data through, in order per task
+#[tokio::test]
+async fn sender_handle() {
+    let _ = env_logger::try_init();
+
+    const PRODUCERS: usize = 4;
+    const ITERS: usize = 50;
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6011").connect();
+    let recvr = SrtSocketBuilder::new_listen().local_port(6011).connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let handle = sender.sender();
+    let producers: Vec<_> = (0..PRODUCERS)
+        .map(|p| {
+            let mut handle = handle.clone();
+            spawn(async move {
+                for i in 0..ITERS {
+                    handle
+                        .send((Instant::now(), Bytes::from(format!("{} {}", p, i))))
+                        .await
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(handle);
+
+    let sendr_fut = async move {
+        for producer in producers {
+            producer.await.unwrap();
+        }
+        // every handle is gone, so this closes the connection
+        sender.close().await.unwrap();
+    };
+
+    let recvr_fut = async move {
+        let mut next = [0; PRODUCERS];
+        while let Some((_, packet)) = recvr.try_next().await.unwrap() {
+            let packet = std::str::from_utf8(&packet[..]).unwrap();
+            let mut parts = packet.split(' ').map(|p| p.parse::<usize>().unwrap());
+            let (p, i) = (parts.next().unwrap(), parts.next().unwrap());
+
+            assert_eq!(i, next[p], "Producer {} out of order", p);
+            next[p] += 1;
+        }
+        assert_eq!(next, [ITERS; PRODUCERS]);
+    };
+
+    futures::join!(sendr_fut, recvr_fut);
+}
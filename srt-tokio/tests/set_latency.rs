@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::SrtSocketBuilder;
+
+/// Growing the latency mid stream delays the data more and more, without a pause in what's received
+#[tokio::test]
+async fn set_latency() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6041)
+        .latency(Duration::from_millis(100))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6041")
+        .latency(Duration::from_millis(100))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    tokio::spawn(async move {
+        let mut handle = sender.sender();
+        for i in 0..300 {
+            handle
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(10)).await;
+        }
+        drop(handle);
+        sender.close().await.unwrap();
+    });
+
+    let mut delays = vec![];
+    let mut max_gap = Duration::from_millis(0);
+    let mut last = None;
+    while let Some((origin, payload)) = recvr.try_next().await.unwrap() {
+        let now = Instant::now();
+        if payload == "20" {
+            recvr.set_latency(Duration::from_millis(300));
+        }
+        if let Some(last) = last {
+            max_gap = max_gap.max(now - last);
+        }
+        last = Some(now);
+        delays.push(now - origin);
+    }
+
+    assert_eq!(delays.len(), 300);
+    assert!(delays[10] < Duration::from_millis(150), "{:?}", delays[10]);
+    // 200ms more takes 2s to grow into, at a tenth slower than real time
+    assert!(
+        delays[299] > Duration::from_millis(280),
+        "{:?}",
+        delays[299]
+    );
+    assert!(max_gap < Duration::from_millis(50), "{:?}", max_gap);
+}
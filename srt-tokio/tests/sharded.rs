@@ -0,0 +1,83 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::future::join_all;
+use futures::prelude::*;
+
+use srt_protocol::pending_connection::ConnInitSettings;
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{MultiplexStats, ShardedMultiplexer, SpawnPolicy, SrtSocketBuilder};
+
+/// Callers spread over the shards all connect, and each is served by the shard that took its handshake
+#[tokio::test]
+async fn sharded() {
+    let _ = env_logger::try_init();
+
+    let server = ShardedMultiplexer::bind(
+        "127.0.0.1:6048".parse().unwrap(),
+        4,
+        ConnInitSettings::default(),
+        MultiplexStats::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(server.local_addr().port(), 6048);
+    let mut incoming = server.incoming().boxed();
+
+    let callers = join_all((0..8).map(|i| async move {
+        let mut caller = SrtSocketBuilder::new_connect("127.0.0.1:6048")
+            .connect()
+            .await
+            .unwrap();
+        caller
+            .send((Instant::now(), Bytes::from(format!("caller {}", i))))
+            .await
+            .unwrap();
+        caller.close().await.unwrap();
+    }));
+
+    let listener = async {
+        let mut conns = vec![];
+        for _ in 0..8 {
+            let (conn, chan) = incoming.try_next().await.unwrap().unwrap();
+            let socket = create_bidrectional_srt(chan, conn);
+            conns.push(tokio::spawn(socket.try_collect::<Vec<_>>()));
+        }
+        let mut received = vec![];
+        for conn in join_all(conns).await {
+            for (_, data) in conn.unwrap().unwrap() {
+                received.push(String::from_utf8(data.to_vec()).unwrap());
+            }
+        }
+        received.sort();
+        let expected: Vec<_> = (0..8).map(|i| format!("caller {}", i)).collect();
+        assert_eq!(received, expected);
+    };
+    futures::join!(callers, listener);
+}
+
+/// The shards stop once nobody takes their connections
+#[tokio::test]
+async fn sharded_drop() {
+    let _ = env_logger::try_init();
+
+    let mut server = ShardedMultiplexer::bind_with_spawn_policy(
+        "127.0.0.1:6134".parse().unwrap(),
+        2,
+        ConnInitSettings::default(),
+        MultiplexStats::default(),
+        &SpawnPolicy::Manual,
+    )
+    .await
+    .unwrap();
+    let drivers = server.take_drivers();
+    assert_eq!(drivers.len(), server.shards());
+    assert!(server.take_drivers().is_empty());
+
+    let shards = tokio::spawn(join_all(drivers));
+    drop(server.incoming());
+    tokio::time::timeout(std::time::Duration::from_secs(1), shards)
+        .await
+        .unwrap()
+        .unwrap();
+}
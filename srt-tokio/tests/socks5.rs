@@ -0,0 +1,123 @@
+#![cfg(feature = "socks5")]
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+
+use srt_tokio::socks5::Socks5Transport;
+use srt_tokio::SrtSocketBuilder;
+
+/// Just enough of a SOCKS5 proxy to relay the datagrams of one client over IPv4, needing `user` and `pass`
+async fn proxy(mut listener: TcpListener, relay_port: u16) {
+    let (mut control, _) = listener.accept().await.unwrap();
+
+    let mut greeting = [0; 2];
+    control.read_exact(&mut greeting).await.unwrap();
+    let mut methods = vec![0; usize::from(greeting[1])];
+    control.read_exact(&mut methods).await.unwrap();
+    assert!(methods.contains(&2));
+    control.write_all(&[5, 2]).await.unwrap();
+
+    let mut version_len = [0; 2];
+    control.read_exact(&mut version_len).await.unwrap();
+    let mut username = vec![0; usize::from(version_len[1])];
+    control.read_exact(&mut username).await.unwrap();
+    let mut password = vec![0; usize::from(control.read_u8().await.unwrap())];
+    control.read_exact(&mut password).await.unwrap();
+    let accepted = username == b"user" && password == b"pass";
+    control
+        .write_all(&[1, if accepted { 0 } else { 1 }])
+        .await
+        .unwrap();
+    if !accepted {
+        return;
+    }
+
+    let mut request = [0; 10];
+    control.read_exact(&mut request).await.unwrap();
+    assert_eq!(request[..4], [5, 3, 0, 1]);
+    let mut relay = UdpSocket::bind(("127.0.0.1", relay_port)).await.unwrap();
+    let mut reply = vec![5, 0, 0, 1, 127, 0, 0, 1];
+    reply.extend_from_slice(&relay_port.to_be_bytes());
+    control.write_all(&reply).await.unwrap();
+
+    let mut client = None;
+    let mut buf = vec![0; 65_536];
+    loop {
+        let (len, from) = relay.recv_from(&mut buf).await.unwrap();
+        // the client sends first, with the header the proxy strips
+        if client.map_or(true, |client| client == from) {
+            client = Some(from);
+            let to = SocketAddr::from((
+                [buf[4], buf[5], buf[6], buf[7]],
+                u16::from_be_bytes([buf[8], buf[9]]),
+            ));
+            relay.send_to(&buf[10..len], &to).await.unwrap();
+        } else if let Some(client) = client {
+            let mut datagram = vec![0, 0, 0, 1, 127, 0, 0, 1];
+            datagram.extend_from_slice(&from.port().to_be_bytes());
+            datagram.extend_from_slice(&buf[..len]);
+            relay.send_to(&datagram, &client).await.unwrap();
+        }
+    }
+}
+
+/// A caller that can only reach the listener through a proxy connects and exchanges data with it
+#[tokio::test]
+async fn through_proxy() {
+    let _ = env_logger::try_init();
+
+    let control = TcpListener::bind("127.0.0.1:6093").await.unwrap();
+    tokio::spawn(proxy(control, 6094));
+    let listener = SrtSocketBuilder::new_listen().local_port(6095).connect();
+    let caller = async {
+        let transport =
+            Socks5Transport::connect("127.0.0.1:6093".parse().unwrap(), Some(("user", "pass")))
+                .await?;
+        assert_eq!(transport.relay_addr(), "127.0.0.1:6094".parse().unwrap());
+        SrtSocketBuilder::new_connect("127.0.0.1:6095")
+            .connect_with_transport(transport)
+            .await
+    };
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+    // the listener only sees the relay
+    assert_eq!(
+        listener.settings().remote,
+        "127.0.0.1:6094".parse().unwrap()
+    );
+    assert_eq!(caller.settings().remote, "127.0.0.1:6095".parse().unwrap());
+
+    caller
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    assert_eq!(listener.try_next().await.unwrap().unwrap().1, "hello");
+    listener
+        .send((Instant::now(), Bytes::from("back")))
+        .await
+        .unwrap();
+    assert_eq!(caller.try_next().await.unwrap().unwrap().1, "back");
+
+    caller.close().await.unwrap();
+    assert_eq!(listener.try_next().await.unwrap(), None);
+}
+
+/// The proxy turning down the credentials fails the transport
+#[tokio::test]
+async fn bad_credentials() {
+    let _ = env_logger::try_init();
+
+    let control = TcpListener::bind("127.0.0.1:6096").await.unwrap();
+    tokio::spawn(proxy(control, 6097));
+    let res =
+        Socks5Transport::connect("127.0.0.1:6096".parse().unwrap(), Some(("user", "wrong"))).await;
+    assert_eq!(
+        res.err().map(|e| e.kind()),
+        Some(io::ErrorKind::PermissionDenied)
+    );
+}
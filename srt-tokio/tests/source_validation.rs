@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::{delay_for, timeout};
+
+use srt_protocol::packet::ControlTypes;
+use srt_protocol::protocol::TimeStamp;
+use srt_protocol::{ControlPacket, Packet, SocketID};
+use srt_tokio::{SourceValidation, SrtSocketBuilder};
+
+async fn send_control(
+    from: &mut UdpSocket,
+    to: &str,
+    dest_sockid: SocketID,
+    control_type: ControlTypes,
+) {
+    let mut buf = BytesMut::new();
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid,
+        control_type,
+    })
+    .serialize(&mut buf);
+    from.send_to(&buf[..], to).await.unwrap();
+}
+
+/// A shutdown from another address than the peer's doesn't close a strict connection, and is counted
+#[tokio::test]
+async fn strict() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6119").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6119)
+        .source_validation(SourceValidation::Strict)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let mut spoofer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let sockid = recvr.settings().local_sockid;
+    send_control(
+        &mut spoofer,
+        "127.0.0.1:6119",
+        sockid,
+        ControlTypes::Shutdown,
+    )
+    .await;
+    delay_for(Duration::from_millis(100)).await;
+
+    sender
+        .send((Instant::now(), Bytes::from_static(b"after")))
+        .await
+        .unwrap();
+    sender.close().await.unwrap();
+
+    let (_, msg) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(msg, "after");
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+    assert_eq!(recvr.stats().receiver.spoofed_packets, 1);
+}
+
+/// A migrating connection follows the peer to its new address
+#[tokio::test]
+async fn migrate() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_connect("127.0.0.1:6120").connect();
+    let recvr = SrtSocketBuilder::new_listen()
+        .local_port(6120)
+        .source_validation(SourceValidation::Migrate)
+        .connect();
+    let (sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let mut moved = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let sockid = recvr.settings().local_sockid;
+    send_control(
+        &mut moved,
+        "127.0.0.1:6120",
+        sockid,
+        ControlTypes::KeepAlive,
+    )
+    .await;
+
+    // the keepalives go to the new address from then on
+    let mut buf = [0; 1500];
+    let (len, _) = timeout(Duration::from_secs(2), moved.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    let packet = Packet::parse(&mut &buf[..len]).unwrap();
+    assert_eq!(packet.dest_sockid(), sender.settings().local_sockid);
+}
@@ -0,0 +1,84 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::prelude::*;
+use tokio::runtime;
+use tokio::time::timeout;
+
+use srt_tokio::{SpawnPolicy, SrtSocketBuilder};
+
+/// Nothing gets through until the application runs the drivers
+#[tokio::test]
+async fn manual() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6123)
+        .spawn_policy(SpawnPolicy::Manual)
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6123")
+        .spawn_policy(SpawnPolicy::Manual)
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // flushing would wait for the peer to acknowledge it
+    sender
+        .feed((Instant::now(), Bytes::from_static(b"hello")))
+        .await
+        .unwrap();
+    assert!(timeout(Duration::from_millis(200), recvr.try_next())
+        .await
+        .is_err());
+
+    let sender_driver = tokio::spawn(sender.take_driver().unwrap());
+    let recvr_driver = tokio::spawn(recvr.take_driver().unwrap());
+    assert!(sender.take_driver().is_none());
+
+    let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "hello");
+
+    sender.close().await.unwrap();
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+    sender_driver.await.unwrap();
+    recvr_driver.await.unwrap();
+}
+
+/// The drivers can run on another runtime
+#[tokio::test]
+async fn handle() {
+    let _ = env_logger::try_init();
+
+    let mut rt = runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()
+        .unwrap();
+    let handle = rt.handle().clone();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let network = thread::spawn(move || rt.block_on(stopped));
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6124)
+        .spawn_policy(SpawnPolicy::Handle(handle.clone()))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6124")
+        .spawn_policy(SpawnPolicy::Handle(handle))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+    assert!(sender.take_driver().is_none());
+
+    sender
+        .send((Instant::now(), Bytes::from_static(b"hello")))
+        .await
+        .unwrap();
+    let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+    assert_eq!(payload, "hello");
+
+    sender.close().await.unwrap();
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+
+    stop.send(()).unwrap();
+    network.join().unwrap().unwrap();
+}
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::time::delay_for;
+use tokio_util::udp::UdpFramed;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{Aggregator, BondedSocket, BondingMode, SrtSocketBuilder};
+
+async fn bonded_sender(port: u16, count: usize, mode: BondingMode) {
+    let uplinks: Vec<SocketAddr> = vec![
+        "127.0.0.1:0".parse().unwrap(),
+        "127.0.0.1:0".parse().unwrap(),
+    ];
+    let mut sock = BondedSocket::bind(&uplinks).await.unwrap();
+    sock.set_mode(mode);
+    let mut sender = SrtSocketBuilder::new_connect(format!("127.0.0.1:{}", port))
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(sock)
+        .await
+        .unwrap();
+    for i in 0..count {
+        sender
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await
+            .unwrap();
+        delay_for(Duration::from_millis(2)).await;
+    }
+    sender.close().await.unwrap();
+}
+
+/// The two uplinks of a bonded sender are both used, and arrive as one connection
+#[tokio::test]
+async fn bonded_connection() {
+    let _ = env_logger::try_init();
+
+    let sources = Arc::new(Mutex::new(HashSet::new()));
+    let seen = sources.clone();
+    let udp = UdpFramed::new(
+        UdpSocket::bind("127.0.0.1:6042").await.unwrap(),
+        srt_tokio::PacketCodec,
+    )
+    .inspect(move |res| {
+        if let Ok((_, from)) = res {
+            seen.lock().unwrap().insert(*from);
+        }
+    });
+    let recvr = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(Aggregator::new(udp));
+
+    tokio::spawn(bonded_sender(6042, 100, BondingMode::RoundRobin));
+
+    let mut recvr = recvr.await.unwrap();
+    for i in 0..100 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+    assert_eq!(sources.lock().unwrap().len(), 2);
+}
+
+/// Bonded peers sharing a multiplexed aggregator are kept apart
+#[tokio::test]
+async fn bonded_multiplexed() {
+    let _ = env_logger::try_init();
+
+    let udp = UdpFramed::new(
+        UdpSocket::bind("127.0.0.1:6043").await.unwrap(),
+        srt_tokio::PacketCodec,
+    );
+    let mut server = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .build_multiplexed_with_sock(Aggregator::new(udp))
+        .boxed();
+
+    tokio::spawn(bonded_sender(6043, 50, BondingMode::RoundRobin));
+    tokio::spawn(bonded_sender(6043, 50, BondingMode::RoundRobin));
+
+    let mut receivers = vec![];
+    for _ in 0..2 {
+        let (conn, chan) = server.next().await.unwrap().unwrap();
+        let mut recvr = create_bidrectional_srt(chan, conn);
+        receivers.push(tokio::spawn(async move {
+            let mut received = vec![];
+            while let Some((_, payload)) = recvr.try_next().await.unwrap() {
+                received.push(payload);
+            }
+            received
+        }));
+    }
+    tokio::spawn(async move { while server.next().await.is_some() {} });
+
+    for recvr in receivers {
+        let received = recvr.await.unwrap();
+        let expected: Vec<_> = (0..50).map(|i| Bytes::from(i.to_string())).collect();
+        assert_eq!(received, expected);
+    }
+}
+
+/// In broadcast mode every packet arrives over both uplinks, and is delivered once
+#[tokio::test]
+async fn broadcast() {
+    let _ = env_logger::try_init();
+
+    let udp = UdpFramed::new(
+        UdpSocket::bind("127.0.0.1:6080").await.unwrap(),
+        srt_tokio::PacketCodec,
+    );
+    let aggregator = Aggregator::new(udp);
+    let stats = aggregator.stats();
+    let recvr = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(aggregator);
+
+    tokio::spawn(bonded_sender(6080, 100, BondingMode::Broadcast));
+
+    let mut recvr = recvr.await.unwrap();
+    for i in 0..100 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+
+    let peers = stats.peers();
+    assert_eq!(peers.len(), 1);
+    let (_, bond) = &peers[0];
+    assert_eq!(bond.links.len(), 2);
+    assert!(bond.duplicates() >= 90, "{:?}", bond);
+    assert!(bond.delivered_packets >= 100, "{:?}", bond);
+    assert!(bond.delivered_bytes >= 190, "{:?}", bond);
+    assert!(bond.delivered_rate() > 0.0);
+    let contributions: f64 = bond
+        .links
+        .iter()
+        .map(|link| bond.contribution(link.addr))
+        .sum();
+    assert!((contributions - 1.0).abs() < 1e-9);
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
+
+/// Every uplink of a bonded peer is timed and rated
+#[tokio::test]
+async fn link_quality() {
+    let _ = env_logger::try_init();
+
+    let udp = UdpFramed::new(
+        UdpSocket::bind("127.0.0.1:6081").await.unwrap(),
+        srt_tokio::PacketCodec,
+    );
+    let aggregator = Aggregator::new(udp);
+    let stats = aggregator.stats();
+    let recvr = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(aggregator);
+
+    tokio::spawn(bonded_sender(6081, 200, BondingMode::RoundRobin));
+
+    let mut recvr = recvr.await.unwrap();
+    for i in 0..200 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+
+    let peers = stats.peers();
+    let (_, bond) = &peers[0];
+    assert_eq!(bond.links.len(), 2);
+    for link in &bond.links {
+        let quality = link.quality;
+        assert!(
+            quality.rtt.unwrap() < Duration::from_millis(100),
+            "{:?}",
+            quality
+        );
+        assert!(quality.loss < 0.5, "{:?}", quality);
+        assert_eq!(quality.breaks, 0);
+        assert!(quality.score() > 0.0);
+    }
+    assert!(bond.best_link().is_some());
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
+
+/// In balancing mode the uplinks carry data in proportion to their weight
+#[tokio::test]
+async fn balancing() {
+    let _ = env_logger::try_init();
+
+    let udp = UdpFramed::new(
+        UdpSocket::bind("127.0.0.1:6082").await.unwrap(),
+        srt_tokio::PacketCodec,
+    );
+    let aggregator = Aggregator::new(udp);
+    let stats = aggregator.stats();
+    let recvr = SrtSocketBuilder::new_listen()
+        .latency(Duration::from_millis(100))
+        .connect_with_sock(aggregator);
+
+    let uplinks: Vec<SocketAddr> = vec![
+        "127.0.0.1:0".parse().unwrap(),
+        "127.0.0.1:0".parse().unwrap(),
+    ];
+    let mut sock = BondedSocket::bind(&uplinks).await.unwrap();
+    let locals = sock.local_addrs();
+    sock.set_mode(BondingMode::Balancing);
+    sock.set_weight(locals[0], 3).unwrap();
+    assert!(sock.set_weight("127.0.0.1:1".parse().unwrap(), 1).is_err());
+    tokio::spawn(async move {
+        let mut sender = SrtSocketBuilder::new_connect("127.0.0.1:6082")
+            .latency(Duration::from_millis(100))
+            .connect_with_sock(sock)
+            .await
+            .unwrap();
+        for i in 0..200 {
+            sender
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await
+                .unwrap();
+            delay_for(Duration::from_millis(2)).await;
+        }
+        sender.close().await.unwrap();
+    });
+
+    let mut recvr = recvr.await.unwrap();
+    for i in 0..200 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, i.to_string());
+    }
+
+    let (_, bond) = stats.peers().remove(0);
+    let data = |local| {
+        bond.links
+            .iter()
+            .find(|link| link.addr == local)
+            .unwrap()
+            .data_packets
+    };
+    let (heavy, light) = (data(locals[0]), data(locals[1]));
+    assert!(light > 0 && heavy >= light * 2, "{} {}", heavy, light);
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+}
@@ -0,0 +1,41 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+use tokio::time::delay_for;
+
+use srt_tokio::{SrtSocketBuilder, StallMonitor, TaskStage};
+
+/// A receiver that isn't read from stalls its connection's task, which the monitor reports once
+#[tokio::test]
+async fn stall_monitor() {
+    let _ = env_logger::try_init();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let seen = events.clone();
+    let sender = SrtSocketBuilder::new_listen().local_port(6085).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6085")
+        .stall_monitor(StallMonitor::new(
+            Duration::from_millis(200),
+            move |event| seen.lock().unwrap().push(event),
+        ))
+        .connect();
+    let (mut sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    for _ in 0..500 {
+        sender
+            .send((Instant::now(), Bytes::from("frozen")))
+            .await
+            .unwrap();
+    }
+    delay_for(Duration::from_secs(1)).await;
+
+    {
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1, "{:?}", *events);
+        assert_eq!(events[0].stage, TaskStage::Releasing);
+        assert!(events[0].stalled_for >= Duration::from_millis(200));
+    }
+    drop(recvr);
+}
@@ -0,0 +1,40 @@
+use srt_tokio::SrtSocketBuilder;
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use std::time::{Duration, Instant};
+
+/// Make sure the congestion control state shows up in the socket's statistics
+#[tokio::test]
+async fn congestion_stats() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6004).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6004").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let recvr_fut = async move { while recvr.try_next().await.unwrap().is_some() {} };
+
+    let sendr_fut = async move {
+        for _ in 0..100 {
+            sender
+                .send((Instant::now(), Bytes::from(vec![0; 1000])))
+                .await
+                .unwrap();
+            tokio::time::delay_for(Duration::from_millis(1)).await;
+        }
+        sender.flush().await.unwrap();
+
+        let stats = sender.stats().sender;
+        assert_eq!(stats.congestion_window, 1000);
+        assert_eq!(stats.flow_window, 8192);
+        assert_eq!(stats.packets_in_flight, 0);
+        assert_eq!(stats.recvd_packets, 100);
+        assert!(stats.snd_period > Duration::from_micros(0));
+
+        sender.close().await.unwrap();
+    };
+
+    futures::join!(recvr_fut, sendr_fut);
+}
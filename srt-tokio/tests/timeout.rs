@@ -1,11 +1,13 @@
-use srt_tokio::SrtSocketBuilder;
+use srt_tokio::{Priority, RateLimit, SrtSocketBuilder};
 use std::{
     env,
-    io::Write,
+    io::{self, Write},
     path::PathBuf,
     process::{Command, Stdio},
+    time::Instant,
 };
 
+use bytes::Bytes;
 use futures::prelude::*;
 use tokio::time::{delay_for, Duration};
 
@@ -65,3 +67,73 @@ async fn receiver_timeout() {
     };
     futures::join!(sender, recvr);
 }
+
+/// Receiving gives up after the timeout set on the builder, or the one given, and can be tried again after
+#[tokio::test]
+async fn recv_timeout() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen().local_port(6061).connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6061")
+        .recv_timeout(Duration::from_millis(200))
+        .connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    let start = Instant::now();
+    let err = recvr.recv().await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let start = Instant::now();
+    let err = recvr
+        .recv_timeout(Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_millis(200));
+
+    sender
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    let (_, payload) = recvr.recv().await.unwrap().unwrap();
+    assert_eq!(payload, "hello");
+
+    sender.close().await.unwrap();
+    assert_eq!(recvr.recv().await.unwrap(), None);
+}
+
+/// Sending gives up when what was sent isn't acknowledged in time
+#[tokio::test]
+async fn send_timeout() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6062)
+        .rate_limit(RateLimit::new(10_000))
+        .send_timeout(Duration::from_millis(100))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6062").connect();
+    let (mut sender, recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    // a second's worth at the rate limit
+    let err = sender
+        .send_with_priority(
+            (Instant::now(), Bytes::from(vec![0; 10_000])),
+            Priority::High,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    let err = sender
+        .send_timeout(
+            (Instant::now(), Bytes::from(vec![0; 10_000])),
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    drop(recvr);
+}
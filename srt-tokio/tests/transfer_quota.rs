@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::prelude::*;
+
+use srt_tokio::{CloseReason, SrtSocketBuilder, TransferQuota};
+
+/// The sender closes once it sent as many packets as it's allowed, after the receiver got them
+#[tokio::test]
+async fn send_packets() {
+    let _ = env_logger::try_init();
+
+    let sender = SrtSocketBuilder::new_listen()
+        .local_port(6121)
+        .transfer_quota(TransferQuota::default().with_send_packets(3))
+        .connect();
+    let recvr = SrtSocketBuilder::new_connect("127.0.0.1:6121").connect();
+    let (mut sender, mut recvr) = futures::try_join!(sender, recvr).unwrap();
+
+    for _ in 0..3 {
+        sender
+            .send((Instant::now(), Bytes::from_static(b"hello")))
+            .await
+            .unwrap();
+    }
+    assert_eq!(sender.try_next().await.unwrap(), None);
+    assert_eq!(sender.close_reason(), Some(CloseReason::QuotaExceeded));
+
+    for _ in 0..3 {
+        let (_, payload) = recvr.try_next().await.unwrap().unwrap();
+        assert_eq!(payload, "hello");
+    }
+    assert_eq!(recvr.try_next().await.unwrap(), None);
+    assert_eq!(recvr.close_reason(), Some(CloseReason::PeerShutdown));
+}
+
+/// A connection closes once it lasted as long as it's allowed, even when nothing's sent
+#[tokio::test]
+async fn duration() {
+    let _ = env_logger::try_init();
+
+    let a = SrtSocketBuilder::new_listen()
+        .local_port(6122)
+        .transfer_quota(TransferQuota::default().with_duration(Duration::from_millis(500)))
+        .connect();
+    let b = SrtSocketBuilder::new_connect("127.0.0.1:6122").connect();
+    let (mut a, mut b) = futures::try_join!(a, b).unwrap();
+
+    let started = Instant::now();
+    assert_eq!(a.try_next().await.unwrap(), None);
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(a.close_reason(), Some(CloseReason::QuotaExceeded));
+
+    assert_eq!(b.try_next().await.unwrap(), None);
+    assert_eq!(b.close_reason(), Some(CloseReason::PeerShutdown));
+}
@@ -0,0 +1,127 @@
+use std::io;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+
+use srt_tokio::tokio::create_bidrectional_srt;
+use srt_tokio::{AsyncDatagramTransport, SrtSocketBuilder};
+
+/// Datagrams over channels, standing in for a substrate that isn't UDP
+struct ChannelTransport {
+    addr: SocketAddr,
+    to_peer: mpsc::UnboundedSender<(Vec<u8>, SocketAddr)>,
+    from_peer: mpsc::UnboundedReceiver<(Vec<u8>, SocketAddr)>,
+}
+
+impl ChannelTransport {
+    fn pair() -> (ChannelTransport, ChannelTransport) {
+        let (a_tx, a_rx) = mpsc::unbounded();
+        let (b_tx, b_rx) = mpsc::unbounded();
+        (
+            ChannelTransport {
+                addr: "10.0.0.1:1".parse().unwrap(),
+                to_peer: b_tx,
+                from_peer: a_rx,
+            },
+            ChannelTransport {
+                addr: "10.0.0.2:2".parse().unwrap(),
+                to_peer: a_tx,
+                from_peer: b_rx,
+            },
+        )
+    }
+}
+
+impl AsyncDatagramTransport for ChannelTransport {
+    fn poll_send_to(
+        &mut self,
+        _cx: &mut Context,
+        buf: &[u8],
+        _target: &SocketAddr,
+    ) -> Poll<Result<usize, io::Error>> {
+        // a closed peer is like an unreachable one
+        let _ = self.to_peer.unbounded_send((buf.to_vec(), self.addr));
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_recv_from(
+        &mut self,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<(usize, SocketAddr), io::Error>> {
+        match futures::ready!(self.from_peer.poll_next_unpin(cx)) {
+            Some((datagram, from)) => {
+                buf[..datagram.len()].copy_from_slice(&datagram);
+                Poll::Ready(Ok((datagram.len(), from)))
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        Ok(self.addr)
+    }
+
+    fn max_payload(&self) -> usize {
+        1500
+    }
+}
+
+/// A connection carries data over a transport that isn't UDP, from a multiplexer to a caller
+#[tokio::test]
+async fn custom_transport() {
+    let _ = env_logger::try_init();
+
+    let (server_end, client_end) = ChannelTransport::pair();
+    let mut server = SrtSocketBuilder::new_listen().build_multiplexed_with_transport(server_end);
+    tokio::spawn(async move {
+        while let Some(Ok((conn, chan))) = server.next().await {
+            assert_eq!(conn.settings.remote, "10.0.0.2:2".parse().unwrap());
+            let mut sender = create_bidrectional_srt(chan, conn);
+            tokio::spawn(async move {
+                sender
+                    .send((Instant::now(), Bytes::from("hello")))
+                    .await
+                    .unwrap();
+                sender.close().await.unwrap();
+            });
+        }
+    });
+
+    let mut caller = SrtSocketBuilder::new_connect("10.0.0.1:1")
+        .connect_with_transport(client_end)
+        .await
+        .unwrap();
+    assert_eq!(caller.settings().remote, "10.0.0.1:1".parse().unwrap());
+    assert_eq!(caller.try_next().await.unwrap().unwrap().1, "hello");
+    assert_eq!(caller.try_next().await.unwrap(), None);
+}
+
+/// A tokio UDP socket is a transport too
+#[tokio::test]
+async fn udp_transport() {
+    let _ = env_logger::try_init();
+
+    let listen_sock = UdpSocket::bind("127.0.0.1:6091").await.unwrap();
+    let call_sock = UdpSocket::bind("127.0.0.1:6092").await.unwrap();
+    let listener = SrtSocketBuilder::new_listen().connect_with_transport(listen_sock);
+    let caller = SrtSocketBuilder::new_connect("127.0.0.1:6091").connect_with_transport(call_sock);
+    let (mut listener, mut caller) = futures::try_join!(listener, caller).unwrap();
+    assert_eq!(
+        listener.settings().remote,
+        "127.0.0.1:6092".parse().unwrap()
+    );
+
+    caller
+        .send((Instant::now(), Bytes::from("hello")))
+        .await
+        .unwrap();
+    caller.close().await.unwrap();
+    assert_eq!(listener.try_next().await.unwrap().unwrap().1, "hello");
+    assert_eq!(listener.try_next().await.unwrap(), None);
+}
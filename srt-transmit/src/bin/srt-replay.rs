@@ -0,0 +1,103 @@
+//! Replay the data received in a pcapng capture into the receiver, to reproduce issues seen in the field
+
+use std::{fs::File, process::exit, time::Duration};
+
+use anyhow::{bail, format_err, Error};
+use clap::{App, Arg};
+
+use srt_protocol::{
+    packet::ControlTypes,
+    replay::{replay_receiver, trace_settings, ReplayEvent},
+};
+use srt_tokio::pcapng::{read_capture, receiver_trace};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    env_logger::Builder::from_default_env()
+        .format_timestamp_micros()
+        .init();
+
+    let matches = App::new("srt-replay")
+        .version("1.0")
+        .about("Replays the packets received in a capture into the SRT receiver, with their original timing")
+        .arg(
+            Arg::with_name("CAPTURE")
+                .help("The pcapng capture to replay")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("latency")
+                .long("latency")
+                .takes_value(true)
+                .default_value("120")
+                .help("The receiving latency of the connection, in milliseconds"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .help("Print every packet the receiver sends"),
+        )
+        .get_matches();
+
+    let path = matches.value_of("CAPTURE").unwrap();
+    let latency = matches.value_of("latency").unwrap();
+    let latency = Duration::from_millis(
+        latency
+            .parse()
+            .map_err(|e| format_err!("Invalid latency {}: {}", latency, e))?,
+    );
+    let verbose = matches.is_present("verbose");
+
+    let captured = read_capture(File::open(path)?)?;
+    let trace = receiver_trace(&captured);
+    let settings = match trace_settings(&trace, latency) {
+        Some(settings) => settings,
+        None => bail!("No SRT data packets in {}", path),
+    };
+    println!(
+        "Replaying {} of {} packets, with a latency of {:?}",
+        trace.len(),
+        captured.len(),
+        latency
+    );
+
+    let replay = replay_receiver(settings, trace);
+    let (mut released, mut naks) = (0, 0);
+    for event in &replay.events {
+        match event {
+            ReplayEvent::Released { at, delay, payload } => {
+                released += 1;
+                if verbose {
+                    println!(
+                        "{:?}: released {} bytes, {:?} after sent",
+                        at,
+                        payload.len(),
+                        delay
+                    );
+                }
+            }
+            ReplayEvent::Sent { at, packet } => {
+                if let ControlTypes::Nak(_) = packet.control_type {
+                    naks += 1;
+                }
+                if verbose {
+                    println!("{:?}: sent {:?}", at, packet.control_type);
+                }
+            }
+            ReplayEvent::Closed { at } => println!("{:?}: closed", at),
+        }
+    }
+
+    println!(
+        "Released {} messages, sent {} NAKs, {:?}",
+        released, naks, replay.metrics
+    );
+    Ok(())
+}
@@ -25,7 +25,9 @@ use tokio::{io::AsyncReadExt, net::UdpSocket, prelude::AsyncRead};
 use tokio_util::{codec::BytesCodec, udp::UdpFramed};
 
 use log::info;
-use srt_tokio::{ConnInitMethod, SrtSocketBuilder, StreamerServer};
+use srt_tokio::{
+    ConnInitMethod, RetransmitAlgorithm, SrtSocketBuilder, StreamerServer, TransmissionType,
+};
 
 const AFTER_HELPTEXT: &str = include_str!("helptext.txt");
 
@@ -85,6 +87,20 @@ fn add_srt_args<C>(
 where
     C: Deref<Target = str>,
 {
+    let args: Vec<_> = args.collect();
+
+    // transtype resets other options to its defaults, so it needs to be applied first
+    if let Some((_, v)) = args.iter().find(|(k, _)| &**k == "transtype") {
+        builder = builder.transmission_type(match &**v {
+            "live" => TransmissionType::Live,
+            "file" => TransmissionType::File,
+            unrecog => bail!(
+                "Unrecognized transtype '{}', expected live or file",
+                unrecog
+            ),
+        });
+    }
+
     let mut crypto: Option<(u8, String)> = None;
     for (k, v) in args {
         match &*k {
@@ -129,8 +145,18 @@ where
                     None => crypto = Some((kl, "".into())),
                 }
             }
+            "retransmitalgo" => {
+                builder = builder.retransmit_algorithm(match &*v {
+                    "aggressive" => RetransmitAlgorithm::Aggressive,
+                    "reduced" => RetransmitAlgorithm::Reduced,
+                    unrecog => bail!(
+                        "Unrecognized retransmitalgo '{}', expected aggressive or reduced",
+                        unrecog
+                    ),
+                })
+            }
             // this has already been handled, ignore
-            "rendezvous" | "multiplex" | "autoreconnect" => (),
+            "rendezvous" | "multiplex" | "autoreconnect" | "transtype" => (),
             unrecog => bail!("Unrecgonized parameter '{}' for srt", unrecog),
         };
     }
@@ -240,19 +266,33 @@ where
     Ok(addr)
 }
 
+// a caller to a host name resolves it again on every connect, so reconnecting follows the host to new addresses
+fn make_srt_builder(
+    addr: Option<SocketAddr>,
+    url: &Url,
+    local_port: u16,
+) -> Result<SrtSocketBuilder, Error> {
+    let conn_init_method = get_conn_init_method(
+        addr,
+        url.query_pairs()
+            .find_map(|(a, b)| if a == "rendezvous" { Some(b) } else { None })
+            .as_deref(),
+    )?;
+    let builder = match (conn_init_method, url.host()) {
+        (ConnInitMethod::Connect(remote), Some(Host::Domain(host))) => {
+            SrtSocketBuilder::new_connect_host(host, remote.port())
+        }
+        (conn_init_method, _) => SrtSocketBuilder::new(conn_init_method),
+    };
+    Ok(builder.local_port(local_port))
+}
+
 async fn make_srt_input(
     input_addr: Option<SocketAddr>,
     input_url: Url,
     input_local_port: u16,
 ) -> Result<BoxStream<'static, Bytes>, Error> {
-    let mut builder = SrtSocketBuilder::new(get_conn_init_method(
-        input_addr,
-        input_url
-            .query_pairs()
-            .find_map(|(a, b)| if a == "rendezvous" { Some(b) } else { None })
-            .as_deref(),
-    )?)
-    .local_port(input_local_port);
+    let mut builder = make_srt_builder(input_addr, &input_url, input_local_port)?;
 
     builder = add_srt_args(input_url.query_pairs(), builder)?;
 
@@ -324,14 +364,7 @@ async fn make_srt_ouput(
     output_url: Url,
     output_local_port: u16,
 ) -> Result<BoxSink, Error> {
-    let builder = SrtSocketBuilder::new(get_conn_init_method(
-        output_addr,
-        output_url
-            .query_pairs()
-            .find_map(|(a, b)| if a == "rendezvous" { Some(b) } else { None })
-            .as_deref(),
-    )?)
-    .local_port(output_local_port);
+    let builder = make_srt_builder(output_addr, &output_url, output_local_port)?;
 
     let is_multiplex = match (
         output_url
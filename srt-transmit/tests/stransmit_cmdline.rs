@@ -381,6 +381,7 @@ mod stransmit_rs_ui {
         multiplex_parameter,
         bad_pbkeylen,
         bad_pbkeylen_str,
-        pbkeylen_no_pw
+        pbkeylen_no_pw,
+        bad_transtype
     );
 }